@@ -0,0 +1,410 @@
+//! Moving a deployed agent from one server to another.
+//!
+//! A migration runs snapshot → deploy → restore → verify → stop source →
+//! clean up. The source keeps running until the destination has come up with
+//! the source's state and reports ready, so a migration that fails before that
+//! point removes the destination again and leaves the agent where it was.
+//! Once the source is stopped it is snapshotted again and the destination
+//! restored and verified with that final state, so nothing the source wrote
+//! during the migration is lost; if that fails the source is started again.
+//! Every stage is published as `AppEvent::MigrationProgress`.
+
+use crate::decision_journal::DECISION_JOURNAL_PATH;
+use crate::deployment_commander::{DeploymentCommander, DeploymentState};
+use crate::self_updater::SELF_UPDATE_STATE_PATH;
+use crate::server_config::TargetServer;
+use crate::ssh_deployer::SshDeployer;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use common::ssh::shell_quote_path;
+use common::{host_port, AppEvent, EventBus, MigrationStage, MigrationStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Most recent journal entries carried over to the destination
+pub const JOURNAL_TAIL_LINES: usize = 1000;
+
+/// State that belongs to the source host rather than to the agent
+const HOST_SPECIFIC_FILES: [&str; 1] = [SELF_UPDATE_STATE_PATH];
+
+/// Monitoring API port polled for `/ready` on the destination
+const DEFAULT_API_PORT: u16 = 8080;
+
+/// How long the destination gets to report ready after the restore
+const DEFAULT_VERIFY_TIMEOUT: Duration = Duration::from_secs(120);
+
+const VERIFY_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// An agent's state as read from its deployment directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentSnapshot {
+    pub server_id: String,
+    pub taken_at: DateTime<Utc>,
+    /// Path relative to the deployment directory → contents: the portfolio in
+    /// `config/state.json`, the other `config/*.json` and `data/*.json` files
+    /// and the tail of the decision journal. Secrets are never included.
+    pub files: BTreeMap<String, String>,
+}
+
+impl AgentSnapshot {
+    /// Read the snapshot of the agent deployed to `remote_path`
+    pub fn read(deployer: &mut SshDeployer, server_id: &str, remote_path: &str) -> Result<Self> {
+        let listing = deployer.execute_checked(&format!(
+            "cd {} && find config data -maxdepth 1 -type f -name '*.json' 2>/dev/null || true",
+            shell_quote_path(remote_path)
+        ))?;
+
+        let mut files = BTreeMap::new();
+        for path in listing
+            .lines()
+            .map(str::trim)
+            .filter(|p| is_snapshot_path(p))
+        {
+            let contents = deployer.download_file(&format!("{}/{}", remote_path, path))?;
+            let contents = String::from_utf8(contents)
+                .with_context(|| format!("{} is not valid UTF-8", path))?;
+            files.insert(path.to_string(), contents);
+        }
+
+        let journal = deployer.execute_checked(&format!(
+            "tail -n {} {} 2>/dev/null || true",
+            JOURNAL_TAIL_LINES,
+            shell_quote_path(&format!("{}/{}", remote_path, DECISION_JOURNAL_PATH))
+        ))?;
+        if !journal.trim().is_empty() {
+            files.insert(DECISION_JOURNAL_PATH.to_string(), journal);
+        }
+
+        Ok(Self {
+            server_id: server_id.to_string(),
+            taken_at: Utc::now(),
+            files,
+        })
+    }
+
+    /// Write the snapshot into `remote_path` while the kernel there is stopped,
+    /// so it cannot overwrite the restored state on shutdown
+    pub fn restore(
+        &self,
+        deployer: &mut SshDeployer,
+        server: &TargetServer,
+        remote_path: &str,
+    ) -> Result<()> {
+        let docker = server.docker_config();
+        deployer.stop_service(docker.as_ref())?;
+        for dir in ["config", "data"] {
+            deployer.create_remote_directory(&format!("{}/{}", remote_path, dir))?;
+        }
+        for (path, contents) in &self.files {
+            deployer.upload_bytes(contents.as_bytes(), &format!("{}/{}", remote_path, path))?;
+        }
+        deployer.start_service(docker.as_ref())
+    }
+}
+
+/// Whether a file listed on the source belongs in a snapshot
+fn is_snapshot_path(path: &str) -> bool {
+    let in_state_dir = path
+        .split_once('/')
+        .is_some_and(|(dir, name)| ["config", "data"].contains(&dir) && !name.contains('/'));
+    in_state_dir
+        && path.ends_with(".json")
+        && !path.contains("..")
+        && !HOST_SPECIFIC_FILES.contains(&path)
+}
+
+/// Carries out `Decision::Migrate` between servers of the deployment config
+pub struct AgentMigrator {
+    commander: Arc<DeploymentCommander>,
+    events: Option<EventBus>,
+    http: reqwest::Client,
+    api_port: u16,
+    verify_timeout: Duration,
+}
+
+impl AgentMigrator {
+    pub fn new(commander: Arc<DeploymentCommander>) -> Self {
+        Self {
+            commander,
+            events: None,
+            http: reqwest::Client::new(),
+            api_port: DEFAULT_API_PORT,
+            verify_timeout: DEFAULT_VERIFY_TIMEOUT,
+        }
+    }
+
+    /// Publish `AppEvent::MigrationProgress` on every stage
+    pub fn with_event_bus(mut self, bus: EventBus) -> Self {
+        self.events = Some(bus);
+        self
+    }
+
+    /// Monitoring API port of the deployed agents
+    pub fn with_api_port(mut self, port: u16) -> Self {
+        self.api_port = port;
+        self
+    }
+
+    pub fn with_verify_timeout(mut self, timeout: Duration) -> Self {
+        self.verify_timeout = timeout;
+        self
+    }
+
+    /// Move the agent on server `from` to server `to`, returning how far it got
+    pub async fn migrate(&self, from: &str, to: &str, reason: &str) -> MigrationStatus {
+        let now = Utc::now();
+        let mut status = MigrationStatus {
+            id: uuid::Uuid::new_v4().to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            reason: reason.to_string(),
+            stage: MigrationStage::Snapshotting,
+            snapshot_files: 0,
+            error: None,
+            started_at: now,
+            updated_at: now,
+        };
+        info!("Migrating agent from {} to {}: {}", from, to, reason);
+        self.publish(&status);
+
+        match self.run(&mut status).await {
+            Ok(()) => {
+                info!("Migrated agent from {} to {}", from, to);
+                self.advance(&mut status, MigrationStage::Completed);
+            }
+            Err(e) => {
+                error!("Migration from {} to {} failed: {:#}", from, to, e);
+                status.error = Some(format!("{:?} failed: {:#}", status.stage, e));
+                self.advance(&mut status, MigrationStage::Failed);
+            }
+        }
+        status
+    }
+
+    async fn run(&self, status: &mut MigrationStatus) -> Result<()> {
+        if status.from == status.to {
+            anyhow::bail!("Source and destination are both {}", status.from);
+        }
+        let source = self.commander.find_server(&status.from).await?;
+        let destination = self.commander.find_server(&status.to).await?;
+
+        let snapshot = self.snapshot(&source).await?;
+        status.snapshot_files = snapshot.files.len();
+
+        // Until the destination is verified, a failure takes it down again
+        let brought_up = self
+            .bring_up(status, &destination, snapshot)
+            .await
+            .context("the source was left running");
+        if brought_up.is_err() {
+            self.remove_after_failure(&destination).await;
+            return brought_up;
+        }
+
+        self.advance(status, MigrationStage::StoppingSource);
+        let stopped = self
+            .stop(&source)
+            .await
+            .context("the source was left running");
+        if stopped.is_err() {
+            self.remove_after_failure(&destination).await;
+            return stopped;
+        }
+
+        // What the source wrote since the first snapshot goes over as well
+        if let Err(e) = self.catch_up(status, &source, &destination).await {
+            let restarted = self.start(&source).await;
+            self.remove_after_failure(&destination).await;
+            return Err(match restarted {
+                Ok(()) => e.context("the source was started again"),
+                Err(restart) => e.context(format!(
+                    "the source could not be started again: {:#}",
+                    restart
+                )),
+            });
+        }
+        self.commander
+            .set_state(&source.id, DeploymentState::Stopped)
+            .await;
+
+        self.advance(status, MigrationStage::CleaningUp);
+        self.remove(&source).await
+    }
+
+    /// Read the state of the agent deployed to `server`
+    async fn snapshot(&self, server: &TargetServer) -> Result<AgentSnapshot> {
+        let mut deployer = self.commander.open_session(server).await?;
+        let server_id = server.id.clone();
+        let remote_path = server.remote_path.clone();
+        tokio::task::spawn_blocking(move || {
+            AgentSnapshot::read(&mut deployer, &server_id, &remote_path)
+        })
+        .await?
+    }
+
+    /// Deploy to the destination, restore the snapshot there and wait for it to
+    /// report ready
+    async fn bring_up(
+        &self,
+        status: &mut MigrationStatus,
+        destination: &TargetServer,
+        snapshot: AgentSnapshot,
+    ) -> Result<()> {
+        self.advance(status, MigrationStage::Deploying);
+        self.commander.deploy_to_server(&destination.id).await?;
+        self.restore(status, destination, snapshot).await
+    }
+
+    /// Snapshot the stopped source again and bring the destination up with it
+    async fn catch_up(
+        &self,
+        status: &mut MigrationStatus,
+        source: &TargetServer,
+        destination: &TargetServer,
+    ) -> Result<()> {
+        let snapshot = self.snapshot(source).await?;
+        status.snapshot_files = snapshot.files.len();
+        self.restore(status, destination, snapshot).await
+    }
+
+    async fn restore(
+        &self,
+        status: &mut MigrationStatus,
+        destination: &TargetServer,
+        snapshot: AgentSnapshot,
+    ) -> Result<()> {
+        self.advance(status, MigrationStage::Restoring);
+        let mut deployer = self.commander.open_session(destination).await?;
+        let server = destination.clone();
+        tokio::task::spawn_blocking(move || {
+            snapshot.restore(&mut deployer, &server, &server.remote_path)
+        })
+        .await??;
+
+        self.advance(status, MigrationStage::Verifying);
+        self.verify(destination).await
+    }
+
+    async fn stop(&self, server: &TargetServer) -> Result<()> {
+        let deployer = self.commander.open_session(server).await?;
+        let docker = server.docker_config();
+        tokio::task::spawn_blocking(move || deployer.stop_service(docker.as_ref())).await?
+    }
+
+    async fn start(&self, server: &TargetServer) -> Result<()> {
+        let deployer = self.commander.open_session(server).await?;
+        let docker = server.docker_config();
+        tokio::task::spawn_blocking(move || deployer.start_service(docker.as_ref())).await?
+    }
+
+    /// Poll the destination's `/ready` until it succeeds or the timeout passes;
+    /// without a reachable API, a running kernel process has to do
+    async fn verify(&self, destination: &TargetServer) -> Result<()> {
+        let url = format!("http://{}/ready", host_port(&destination.ip, self.api_port));
+        let deadline = tokio::time::Instant::now() + self.verify_timeout;
+        loop {
+            let ready = match self
+                .http
+                .get(&url)
+                .timeout(VERIFY_POLL_INTERVAL)
+                .send()
+                .await
+            {
+                Ok(response) => response.status().is_success(),
+                Err(_) => self
+                    .commander
+                    .check_server_status(&destination.id)
+                    .await
+                    .unwrap_or(false),
+            };
+            if ready {
+                return Ok(());
+            }
+            if tokio::time::Instant::now() >= deadline {
+                anyhow::bail!(
+                    "{} did not become ready within {:?}",
+                    destination.id,
+                    self.verify_timeout
+                );
+            }
+            tokio::time::sleep(VERIFY_POLL_INTERVAL).await;
+        }
+    }
+
+    async fn remove_after_failure(&self, server: &TargetServer) {
+        if let Err(e) = self.remove(server).await {
+            warn!("Failed to remove the deployment on {}: {:#}", server.id, e);
+        }
+    }
+
+    async fn remove(&self, server: &TargetServer) -> Result<()> {
+        let deployer = self.commander.open_session(server).await?;
+        let docker = server.docker_config();
+        let remote_path = server.remote_path.clone();
+        tokio::task::spawn_blocking(move || {
+            deployer.remove_deployment(&remote_path, docker.as_ref())
+        })
+        .await??;
+        self.commander
+            .set_state(&server.id, DeploymentState::NotDeployed)
+            .await;
+        Ok(())
+    }
+
+    fn advance(&self, status: &mut MigrationStatus, stage: MigrationStage) {
+        status.stage = stage;
+        status.updated_at = Utc::now();
+        self.publish(status);
+    }
+
+    fn publish(&self, status: &MigrationStatus) {
+        if let Some(bus) = &self.events {
+            let _ = bus.publish(AppEvent::MigrationProgress(Box::new(status.clone())));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_carries_agent_state_but_not_secrets_or_host_state() {
+        assert!(is_snapshot_path("config/state.json"));
+        assert!(is_snapshot_path("config/strategy.json"));
+        assert!(is_snapshot_path("data/identity.json"));
+
+        assert!(!is_snapshot_path("config/secrets/approval_token"));
+        assert!(!is_snapshot_path("config/secrets/signing.json"));
+        assert!(!is_snapshot_path(SELF_UPDATE_STATE_PATH));
+        assert!(!is_snapshot_path("logs/aurelia.json"));
+        assert!(!is_snapshot_path("config/../../etc/passwd.json"));
+        assert!(!is_snapshot_path("kernel"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_servers_fail_without_touching_anything() {
+        let bus = EventBus::new(8);
+        let mut rx = bus.subscribe_as("test", &[common::Topic::Deployment]);
+        let commander = Arc::new(DeploymentCommander::new("kernel".into()));
+        let migrator = AgentMigrator::new(commander).with_event_bus(bus);
+
+        let status = migrator.migrate("old", "new", "cheaper host").await;
+        assert_eq!(status.stage, MigrationStage::Failed);
+        assert!(status.error.unwrap().contains("Server old not found"));
+
+        let stages: Vec<_> = (0..2)
+            .map(|_| match rx.try_recv().unwrap() {
+                AppEvent::MigrationProgress(status) => status.stage,
+                other => panic!("unexpected event {:?}", other.kind()),
+            })
+            .collect();
+        assert_eq!(
+            stages,
+            [MigrationStage::Snapshotting, MigrationStage::Failed]
+        );
+    }
+}
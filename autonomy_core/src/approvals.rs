@@ -0,0 +1,437 @@
+//! Human approval for the agent's most consequential actions.
+//!
+//! With approvals enabled, the gated kinds of action (deployments, scaling and
+//! emergency shutdowns by default) wait as pending approvals instead of running
+//! straight away. Each request is posted to `notify_url` and listed on
+//! `/api/approvals`, where an operator holding the approval token approves or
+//! rejects it. A request nobody answers is approved after `auto_approve_seconds`
+//! if that is set, and otherwise rejected after `expire_seconds`.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use common::secrets;
+use common::SecretStore;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+
+pub const APPROVALS_CONFIG_PATH: &str = "config/approvals.json";
+
+/// Secret holding the bearer token for approving and rejecting requests
+pub const APPROVAL_TOKEN_SECRET: &str = "approval_token";
+
+/// Decided requests kept for `/api/approvals`
+const HISTORY_LIMIT: usize = 100;
+const NOTIFY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalKind {
+    Deploy,
+    Scale,
+    EmergencyShutdown,
+    /// Moving an agent to another server, which removes it from the source
+    Migrate,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApprovalConfig {
+    /// Off means full autonomy: nothing waits for an operator
+    pub enabled: bool,
+    /// Kinds of action that need approval
+    pub actions: Vec<ApprovalKind>,
+    /// Approve a request nobody answered after this long; `None`, or a value not
+    /// below `expire_seconds`, waits for an operator
+    pub auto_approve_seconds: Option<u64>,
+    /// Reject a request nobody answered after this long
+    pub expire_seconds: u64,
+    /// Webhook that receives every new [`ApprovalRequest`] as JSON
+    pub notify_url: Option<String>,
+}
+
+impl Default for ApprovalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            actions: vec![
+                ApprovalKind::Deploy,
+                ApprovalKind::Scale,
+                ApprovalKind::EmergencyShutdown,
+                ApprovalKind::Migrate,
+            ],
+            auto_approve_seconds: None,
+            expire_seconds: 86400,
+            notify_url: None,
+        }
+    }
+}
+
+impl ApprovalConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalStatus {
+    Pending,
+    Approved,
+    Rejected,
+    /// Nobody answered within `auto_approve_seconds`
+    AutoApproved,
+    /// Nobody answered within `expire_seconds`
+    Expired,
+}
+
+impl ApprovalStatus {
+    pub fn allows(self) -> bool {
+        matches!(self, Self::Approved | Self::AutoApproved)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalRequest {
+    pub id: String,
+    pub kind: ApprovalKind,
+    pub summary: String,
+    /// The action as the agent would execute it
+    pub detail: serde_json::Value,
+    pub status: ApprovalStatus,
+    pub requested_at: DateTime<Utc>,
+    /// When the request decides itself if nobody answers
+    pub deadline: DateTime<Utc>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub decided_by: Option<String>,
+}
+
+/// Why an operator's decision was not applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApprovalError {
+    NotFound,
+    AlreadyDecided(ApprovalStatus),
+}
+
+impl std::fmt::Display for ApprovalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "no such approval request"),
+            Self::AlreadyDecided(status) => write!(f, "request was already decided: {:?}", status),
+        }
+    }
+}
+
+impl std::error::Error for ApprovalError {}
+
+#[derive(Default)]
+struct GateState {
+    requests: Vec<ApprovalRequest>,
+    waiters: HashMap<String, oneshot::Sender<bool>>,
+}
+
+#[derive(Clone)]
+pub struct ApprovalGate {
+    config: ApprovalConfig,
+    token: Option<String>,
+    state: Arc<Mutex<GateState>>,
+    client: reqwest::Client,
+}
+
+impl Default for ApprovalGate {
+    fn default() -> Self {
+        Self::new(ApprovalConfig::default())
+    }
+}
+
+impl ApprovalGate {
+    pub fn new(config: ApprovalConfig) -> Self {
+        Self {
+            config,
+            token: None,
+            state: Arc::new(Mutex::new(GateState::default())),
+            client: reqwest::Client::builder()
+                .timeout(NOTIFY_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Accept operator decisions that present `token`; without one, only the
+    /// timeouts decide
+    pub fn with_token(mut self, token: String) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    /// The approval token, which the primary also presents to replicas when it
+    /// pushes config to them
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    pub fn config(&self) -> &ApprovalConfig {
+        &self.config
+    }
+
+    pub fn requires_approval(&self, kind: ApprovalKind) -> bool {
+        self.config.enabled && self.config.actions.contains(&kind)
+    }
+
+    /// Whether `presented` is the approval token, compared in constant time
+    pub fn is_authorized(&self, presented: Option<&str>) -> bool {
+        self.token
+            .as_deref()
+            .is_some_and(|token| secrets::token_matches(token, presented))
+    }
+
+    /// Wait until the action may run. Returns at once with `true` for kinds that
+    /// need no approval, otherwise once the request is decided.
+    pub async fn authorize(
+        &self,
+        kind: ApprovalKind,
+        summary: impl Into<String>,
+        detail: &impl Serialize,
+    ) -> bool {
+        if !self.requires_approval(kind) {
+            return true;
+        }
+
+        let (timeout_status, wait_seconds) = match self.config.auto_approve_seconds {
+            Some(seconds) if seconds < self.config.expire_seconds => {
+                (ApprovalStatus::AutoApproved, seconds)
+            }
+            _ => (ApprovalStatus::Expired, self.config.expire_seconds),
+        };
+        let now = Utc::now();
+        let request = ApprovalRequest {
+            id: uuid::Uuid::new_v4().to_string(),
+            kind,
+            summary: summary.into(),
+            detail: serde_json::to_value(detail).unwrap_or_default(),
+            status: ApprovalStatus::Pending,
+            requested_at: now,
+            deadline: now + chrono::Duration::seconds(wait_seconds as i64),
+            decided_at: None,
+            decided_by: None,
+        };
+        let (tx, mut rx) = oneshot::channel();
+        {
+            let mut state = self.state.lock().expect("approval lock poisoned");
+            state.requests.push(request.clone());
+            state.waiters.insert(request.id.clone(), tx);
+        }
+        info!(
+            id = %request.id,
+            kind = ?kind,
+            "[Approvals] Waiting for approval: {}",
+            request.summary
+        );
+        self.notify(&request).await;
+
+        tokio::select! {
+            allowed = &mut rx => return allowed.unwrap_or(false),
+            _ = tokio::time::sleep(std::time::Duration::from_secs(wait_seconds)) => {}
+        }
+        // An operator may have decided just as the deadline passed; then this is a no-op
+        let _ = self.decide(&request.id, timeout_status, None);
+        rx.await.unwrap_or(false)
+    }
+
+    /// Pending requests first, then recently decided ones, newest first
+    pub fn requests(&self) -> Vec<ApprovalRequest> {
+        let state = self.state.lock().expect("approval lock poisoned");
+        let mut requests = state.requests.clone();
+        requests.sort_by_key(|r| {
+            (
+                r.status != ApprovalStatus::Pending,
+                std::cmp::Reverse(r.requested_at),
+            )
+        });
+        requests
+    }
+
+    pub fn pending(&self) -> Vec<ApprovalRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|r| r.status == ApprovalStatus::Pending)
+            .collect()
+    }
+
+    pub fn approve(&self, id: &str, operator: &str) -> Result<ApprovalRequest, ApprovalError> {
+        self.decide(id, ApprovalStatus::Approved, Some(operator))
+    }
+
+    pub fn reject(&self, id: &str, operator: &str) -> Result<ApprovalRequest, ApprovalError> {
+        self.decide(id, ApprovalStatus::Rejected, Some(operator))
+    }
+
+    fn decide(
+        &self,
+        id: &str,
+        status: ApprovalStatus,
+        operator: Option<&str>,
+    ) -> Result<ApprovalRequest, ApprovalError> {
+        let mut state = self.state.lock().expect("approval lock poisoned");
+        let request = state
+            .requests
+            .iter_mut()
+            .find(|r| r.id == id)
+            .ok_or(ApprovalError::NotFound)?;
+        if request.status != ApprovalStatus::Pending {
+            return Err(ApprovalError::AlreadyDecided(request.status));
+        }
+        request.status = status;
+        request.decided_at = Some(Utc::now());
+        request.decided_by = operator.map(str::to_string);
+        let request = request.clone();
+        info!(
+            id = %request.id,
+            by = operator.unwrap_or("timeout"),
+            "[Approvals] {:?}: {}",
+            status,
+            request.summary
+        );
+
+        if let Some(waiter) = state.waiters.remove(id) {
+            let _ = waiter.send(status.allows());
+        }
+        let decided = state
+            .requests
+            .iter()
+            .filter(|r| r.status != ApprovalStatus::Pending)
+            .count();
+        if decided > HISTORY_LIMIT {
+            let mut excess = decided - HISTORY_LIMIT;
+            state.requests.retain(|r| {
+                if excess > 0 && r.status != ApprovalStatus::Pending {
+                    excess -= 1;
+                    return false;
+                }
+                true
+            });
+        }
+        Ok(request)
+    }
+
+    async fn notify(&self, request: &ApprovalRequest) {
+        let Some(url) = &self.config.notify_url else {
+            return;
+        };
+        match self.client.post(url).json(request).send().await {
+            Ok(response) if response.status().is_success() => {}
+            Ok(response) => warn!(
+                "[Approvals] Notification to {} returned {}",
+                url,
+                response.status()
+            ),
+            Err(e) => warn!("[Approvals] Notification to {} failed: {}", url, e),
+        }
+    }
+}
+
+/// The approval token from `store`, generating and storing one if there is none
+pub fn load_or_create_token(store: &SecretStore) -> Result<String> {
+    if let Some(token) = store.get(APPROVAL_TOKEN_SECRET)? {
+        return Ok(token);
+    }
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    store.set(APPROVAL_TOKEN_SECRET, &token)?;
+    info!(
+        "[Approvals] Generated an approval token in {}",
+        store.dir().join(APPROVAL_TOKEN_SECRET).display()
+    );
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_actions_wait_for_an_operator_or_the_timeout() {
+        let gate = ApprovalGate::new(ApprovalConfig {
+            enabled: true,
+            actions: vec![ApprovalKind::Deploy, ApprovalKind::EmergencyShutdown],
+            ..Default::default()
+        })
+        .with_token("secret".to_string());
+        assert!(gate.authorize(ApprovalKind::Scale, "scale", &()).await);
+        assert!(gate.is_authorized(Some("secret")));
+        assert!(!gate.is_authorized(Some("secreT")));
+        assert!(!gate.is_authorized(None));
+
+        let waiting = tokio::spawn({
+            let gate = gate.clone();
+            async move {
+                gate.authorize(ApprovalKind::Deploy, "deploy", &["10.0.0.1"])
+                    .await
+            }
+        });
+        let pending = loop {
+            if let Some(request) = gate.pending().pop() {
+                break request;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        assert_eq!(pending.detail, serde_json::json!(["10.0.0.1"]));
+        assert_eq!(
+            gate.approve(&pending.id, "ops").unwrap().status,
+            ApprovalStatus::Approved
+        );
+        assert!(waiting.await.unwrap());
+        assert_eq!(
+            gate.reject(&pending.id, "ops").unwrap_err(),
+            ApprovalError::AlreadyDecided(ApprovalStatus::Approved)
+        );
+        assert_eq!(
+            gate.approve("missing", "ops").unwrap_err(),
+            ApprovalError::NotFound
+        );
+
+        let rejected = tokio::spawn({
+            let gate = gate.clone();
+            async move {
+                gate.authorize(ApprovalKind::EmergencyShutdown, "shutdown", &())
+                    .await
+            }
+        });
+        let pending = loop {
+            if let Some(request) = gate.pending().pop() {
+                break request;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        gate.reject(&pending.id, "ops").unwrap();
+        assert!(!rejected.await.unwrap());
+
+        let auto = ApprovalGate::new(ApprovalConfig {
+            enabled: true,
+            auto_approve_seconds: Some(0),
+            ..Default::default()
+        });
+        assert!(auto.authorize(ApprovalKind::Scale, "scale", &()).await);
+        assert_eq!(auto.requests()[0].status, ApprovalStatus::AutoApproved);
+
+        let expiring = ApprovalGate::new(ApprovalConfig {
+            enabled: true,
+            expire_seconds: 0,
+            ..Default::default()
+        });
+        assert!(
+            !expiring
+                .authorize(ApprovalKind::Deploy, "deploy", &())
+                .await
+        );
+        assert_eq!(expiring.requests()[0].status, ApprovalStatus::Expired);
+    }
+}
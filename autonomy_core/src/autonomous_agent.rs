@@ -1,16 +1,25 @@
 use crate::{
+    agent_migration::AgentMigrator,
+    approvals::{ApprovalGate, ApprovalKind},
+    decision_journal::DecisionJournal,
     decision_maker::{
-        AutonomousDecisionMaker, Decision, DecisionContext, NodeInfo, NodeStatus, ResourceMetrics,
+        AutonomousDecisionMaker, Decision, DecisionContext, DecisionFeedback, NodeInfo, NodeStatus,
+        Outcome, ResourceMetrics,
     },
-    health_monitor::{HealthMonitor, HealthStatus},
+    decision_policy::DecisionPolicy,
+    health_monitor::{HealthMonitor, HealthStatus, HealthThresholds},
+    market_sentiment::MarketSentiment,
     recovery_manager::{FailureEvent, FailureType, RecoveryManager},
-    self_replicator::{ReplicationTarget, SelfReplicator},
+    self_replicator::{ReplicationResult, ReplicationStrategy, ReplicationTarget, SelfReplicator},
     task_scheduler::{
-        HealthCheckExecutor, ReplicationExecutor, Task, TaskScheduler, TaskStatus, TaskType,
+        DependencyMode, HealthCheckExecutor, ReplicationExecutor, Task, TaskExecutor,
+        TaskScheduler, TaskStatus, TaskType,
     },
 };
 use anyhow::Result;
 use chrono::Utc;
+use common::{AgentIdentity, AppEvent, EventBus, EventReceiver, MigrationStage};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -23,15 +32,32 @@ pub struct AutonomousAgent {
     recovery_manager: Arc<RecoveryManager>,
     self_replicator: Arc<SelfReplicator>,
     task_scheduler: Arc<TaskScheduler>,
+    identity: AgentIdentity,
+    sentiment: MarketSentiment,
+    sentiment_feed: std::sync::Mutex<Option<EventReceiver>>,
+    approvals: ApprovalGate,
+    migrator: Option<Arc<AgentMigrator>>,
+    events: Option<EventBus>,
     is_running: Arc<RwLock<bool>>,
 }
 
 impl AutonomousAgent {
     pub fn new(binary_path: PathBuf) -> Self {
+        Self::with_identity(binary_path, AgentIdentity::new_root())
+    }
+
+    /// Create an agent that replicates as `identity`
+    pub fn with_identity(binary_path: PathBuf, identity: AgentIdentity) -> Self {
+        Self::with_replicator(SelfReplicator::new(binary_path).with_identity(identity))
+    }
+
+    /// Create an agent around a fully configured replicator
+    pub fn with_replicator(self_replicator: SelfReplicator) -> Self {
         let decision_maker = Arc::new(RwLock::new(AutonomousDecisionMaker::new()));
         let health_monitor = Arc::new(HealthMonitor::new());
         let recovery_manager = Arc::new(RecoveryManager::new());
-        let self_replicator = Arc::new(SelfReplicator::new(binary_path));
+        let identity = self_replicator.identity().clone();
+        let self_replicator = Arc::new(self_replicator);
         let task_scheduler = Arc::new(TaskScheduler::new());
 
         Self {
@@ -40,6 +66,12 @@ impl AutonomousAgent {
             recovery_manager,
             self_replicator,
             task_scheduler,
+            identity,
+            sentiment: MarketSentiment::new(),
+            sentiment_feed: std::sync::Mutex::new(None),
+            approvals: ApprovalGate::default(),
+            migrator: None,
+            events: None,
             is_running: Arc::new(RwLock::new(false)),
         }
     }
@@ -75,20 +107,25 @@ impl AutonomousAgent {
             priority: 8,
             scheduled_time: Utc::now(),
             dependencies: vec![],
+            dependency_mode: DependencyMode::All,
             max_retries: 3,
             retry_count: 0,
             timeout_seconds: 30,
             status: TaskStatus::Pending,
             result: None,
+            schedule: None,
         };
 
-        self.task_scheduler
-            .schedule_recurring_task(
-                health_check_task,
-                chrono::Duration::minutes(5),
-                288, // 24 hours worth of 5-minute intervals
-            )
-            .await?;
+        // A restored queue already holds the next run
+        if !self
+            .task_scheduler
+            .is_scheduled(&health_check_task.id)
+            .await
+        {
+            self.task_scheduler
+                .schedule_cron_task(health_check_task, "*/5 * * * *")
+                .await?;
+        }
 
         // Schedule periodic replication checks
         let replication_task = Task {
@@ -96,18 +133,22 @@ impl AutonomousAgent {
             name: "Replication Status Check".to_string(),
             task_type: TaskType::Replication,
             priority: 6,
-            scheduled_time: Utc::now() + chrono::Duration::minutes(10),
+            scheduled_time: Utc::now(),
             dependencies: vec![],
+            dependency_mode: DependencyMode::All,
             max_retries: 2,
             retry_count: 0,
             timeout_seconds: 60,
             status: TaskStatus::Pending,
             result: None,
+            schedule: None,
         };
 
-        self.task_scheduler
-            .schedule_recurring_task(replication_task, chrono::Duration::hours(1), 24)
-            .await?;
+        if !self.task_scheduler.is_scheduled(&replication_task.id).await {
+            self.task_scheduler
+                .schedule_cron_task(replication_task, "10 * * * *")
+                .await?;
+        }
 
         Ok(())
     }
@@ -170,6 +211,32 @@ impl AutonomousAgent {
             task_scheduler.run().await;
         });
 
+        let feed = self
+            .sentiment_feed
+            .lock()
+            .expect("sentiment feed lock poisoned")
+            .take();
+        if let Some(feed) = feed {
+            let sentiment = self.sentiment.clone();
+            tokio::spawn(async move { sentiment.follow(feed).await });
+        }
+
+        if let Some(bus) = self.events.clone() {
+            let health_monitor = self.health_monitor.clone();
+            let recovery_manager = self.recovery_manager.clone();
+            let task_scheduler = self.task_scheduler.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(STATUS_INTERVAL);
+                let mut flattened = false;
+                loop {
+                    interval.tick().await;
+                    Self::publish_status(&bus, &health_monitor, &recovery_manager, &task_scheduler)
+                        .await;
+                    Self::flatten_on_critical_health(&bus, &health_monitor, &mut flattened).await;
+                }
+            });
+        }
+
         // Main decision loop
         let decision_loop_handle = tokio::spawn({
             let is_running = self.is_running.clone();
@@ -177,28 +244,80 @@ impl AutonomousAgent {
             let health_monitor = self.health_monitor.clone();
             let self_replicator = self.self_replicator.clone();
             let recovery_manager = self.recovery_manager.clone();
+            let sentiment = self.sentiment.clone();
+            let approvals = self.approvals.clone();
+            let migrator = self.migrator.clone();
 
             async move {
+                let mut pending_feedback: Vec<PendingFeedback> = Vec::new();
+
                 while *is_running.read().await {
                     // Gather context
-                    let context = Self::gather_context(&health_monitor).await;
-
-                    // Make decision
-                    let decision = {
+                    let context = Self::gather_context(&health_monitor, &sentiment).await;
+
+                    // Judge earlier scaling decisions whose capacity has had time to settle
+                    let (due, waiting): (Vec<_>, Vec<_>) = pending_feedback
+                        .into_iter()
+                        .partition(|p| p.due <= context.timestamp);
+                    pending_feedback = waiting;
+                    if !due.is_empty() {
                         let mut dm = decision_maker.write().await;
-                        match dm.make_decision(&context).await {
-                            Ok(d) => d,
-                            Err(e) => {
-                                error!("Decision making failed: {}", e);
-                                Decision::Wait {
-                                    duration_seconds: 30,
-                                }
+                        for pending in due {
+                            dm.record_feedback(
+                                &pending.resolve(context.resource_usage.cpu_percent),
+                            )
+                            .await;
+                        }
+                    }
+
+                    // Make decision. A policy may wait on the reasoning engine, so
+                    // the decision maker stays unlocked until there is one to record.
+                    let policy = decision_maker.read().await.policy();
+                    let decision = match AutonomousDecisionMaker::decide(&policy, &context).await {
+                        Ok(d) => {
+                            decision_maker.write().await.record_decision(&d, &context);
+                            d
+                        }
+                        Err(e) => {
+                            error!("Decision making failed: {}", e);
+                            Decision::Wait {
+                                duration_seconds: 30,
                             }
                         }
                     };
 
-                    // Execute decision
-                    Self::execute_decision(decision, &self_replicator, &recovery_manager).await;
+                    // Execute decision and feed its outcome back
+                    let measurement = Self::execute_decision(
+                        decision,
+                        &self_replicator,
+                        &recovery_manager,
+                        &approvals,
+                        migrator.as_deref(),
+                    )
+                    .await;
+                    let mut dm = decision_maker.write().await;
+                    if let Some(decision_id) = dm.last_decision_id().map(str::to_string) {
+                        match measurement {
+                            Measurement::Immediate(outcome, metrics) => {
+                                dm.record_feedback(&DecisionFeedback {
+                                    decision_id,
+                                    outcome,
+                                    metrics,
+                                })
+                                .await;
+                            }
+                            Measurement::AfterScaling { metrics } => {
+                                pending_feedback.push(PendingFeedback {
+                                    decision_id,
+                                    due: Utc::now()
+                                        + chrono::Duration::seconds(SCALE_SETTLE_SECONDS),
+                                    cpu_before: context.resource_usage.cpu_percent,
+                                    metrics,
+                                });
+                            }
+                        }
+                    }
+                    drop(dm);
 
                     // Wait before next decision cycle
                     tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
@@ -219,7 +338,49 @@ impl AutonomousAgent {
         Ok(())
     }
 
-    async fn gather_context(health_monitor: &Arc<HealthMonitor>) -> DecisionContext {
+    async fn publish_status(
+        bus: &EventBus,
+        health_monitor: &HealthMonitor,
+        recovery_manager: &RecoveryManager,
+        task_scheduler: &TaskScheduler,
+    ) {
+        let _ = bus.publish(AppEvent::SchedulerStatus(task_scheduler.get_status().await));
+        let _ = bus.publish(AppEvent::RecoveryStats(
+            recovery_manager.get_recovery_stats().await,
+        ));
+        let _ = bus.publish(AppEvent::HealthSummary(Box::new(
+            health_monitor.get_current_health().await,
+        )));
+    }
+
+    /// Pull the kill switch once when health turns critical or failed; it may be
+    /// pulled again after health recovered in between.
+    async fn flatten_on_critical_health(
+        bus: &EventBus,
+        health_monitor: &HealthMonitor,
+        flattened: &mut bool,
+    ) {
+        let reason = match health_monitor.get_current_health().await.status {
+            HealthStatus::Critical(reason) => format!("health critical: {}", reason),
+            HealthStatus::Failed(reason) => format!("health failed: {}", reason),
+            HealthStatus::Healthy | HealthStatus::Degraded(_) => {
+                *flattened = false;
+                return;
+            }
+        };
+        if std::mem::replace(flattened, true) {
+            return;
+        }
+        error!("{}, flattening all positions", reason);
+        if let Err(e) = bus.send_control(AppEvent::EmergencyFlatten(reason)).await {
+            error!("Failed to send EmergencyFlatten event: {}", e);
+        }
+    }
+
+    async fn gather_context(
+        health_monitor: &Arc<HealthMonitor>,
+        sentiment: &MarketSentiment,
+    ) -> DecisionContext {
         let health_summary = health_monitor.get_current_health().await;
 
         let system_health = match health_summary.status {
@@ -249,7 +410,7 @@ impl AutonomousAgent {
             }],
             failed_nodes: vec![],
             pending_tasks: 0,
-            market_conditions: None,
+            market_conditions: sentiment.conditions().await,
         }
     }
 
@@ -257,10 +418,39 @@ impl AutonomousAgent {
         decision: Decision,
         self_replicator: &Arc<SelfReplicator>,
         recovery_manager: &Arc<RecoveryManager>,
-    ) {
+        approvals: &ApprovalGate,
+        migrator: Option<&AgentMigrator>,
+    ) -> Measurement {
         info!("Executing decision: {:?}", decision);
+        let mut metrics = HashMap::new();
 
-        match decision {
+        let gated = match &decision {
+            Decision::Deploy {
+                target_servers,
+                reason,
+                ..
+            } => Some((
+                ApprovalKind::Deploy,
+                format!("Deploy to {}: {}", target_servers.join(", "), reason),
+            )),
+            Decision::Scale { factor, reason } => Some((
+                ApprovalKind::Scale,
+                format!("Scale by factor {}: {}", factor, reason),
+            )),
+            Decision::Migrate { from, to, reason } => Some((
+                ApprovalKind::Migrate,
+                format!("Migrate from {} to {}: {}", from, to, reason),
+            )),
+            _ => None,
+        };
+        if let Some((kind, summary)) = gated {
+            if !approvals.authorize(kind, summary, &decision).await {
+                warn!("Decision was not approved: {:?}", decision);
+                return Measurement::Immediate(Outcome::Neutral, metrics);
+            }
+        }
+
+        let outcome = match decision {
             Decision::Deploy {
                 target_servers,
                 priority,
@@ -291,9 +481,11 @@ impl AutonomousAgent {
                     self_replicator.add_target(target).await;
                 }
 
-                if let Err(e) = self_replicator.replicate().await {
+                let result = self_replicator.replicate().await;
+                if let Err(e) = &result {
                     error!("Replication failed: {}", e);
                 }
+                replication_outcome(vec![result], &mut metrics)
             }
 
             Decision::Scale { factor, reason } => {
@@ -301,10 +493,18 @@ impl AutonomousAgent {
 
                 // Trigger scaling operations
                 let replicas_needed = (factor * 2.0) as usize;
+                let mut results = Vec::new();
                 for _ in 0..replicas_needed {
-                    if let Err(e) = self_replicator.replicate().await {
+                    let result = self_replicator.replicate().await;
+                    if let Err(e) = &result {
                         error!("Scaling replication failed: {}", e);
                     }
+                    results.push(result);
+                }
+                // Whether new capacity helped only shows once it carries load
+                match replication_outcome(results, &mut metrics) {
+                    Outcome::Success => return Measurement::AfterScaling { metrics },
+                    outcome => outcome,
                 }
             }
 
@@ -327,23 +527,60 @@ impl AutonomousAgent {
                     auto_recoverable: true,
                 };
 
-                if let Err(e) = recovery_manager.handle_failure(failure).await {
-                    error!("Recovery failed: {}", e);
+                match recovery_manager.handle_failure(failure).await {
+                    Ok(result) => {
+                        metrics.insert(
+                            "recovery_time_seconds".to_string(),
+                            result.recovery_time_seconds as f64,
+                        );
+                        if result.success {
+                            Outcome::Success
+                        } else {
+                            warn!("Recovery did not succeed: {:?}", result.error);
+                            Outcome::Failure
+                        }
+                    }
+                    Err(e) => {
+                        error!("Recovery failed: {}", e);
+                        Outcome::Failure
+                    }
                 }
             }
 
+            Decision::Migrate { from, to, reason } => match migrator {
+                Some(migrator) => {
+                    let status = migrator.migrate(&from, &to, &reason).await;
+                    metrics.insert("snapshot_files".to_string(), status.snapshot_files as f64);
+                    metrics.insert(
+                        "migration_seconds".to_string(),
+                        (status.updated_at - status.started_at).num_seconds() as f64,
+                    );
+                    match status.stage {
+                        MigrationStage::Completed => Outcome::Success,
+                        _ => Outcome::Failure,
+                    }
+                }
+                None => {
+                    warn!(
+                        "No migrator configured, cannot migrate from {} to {}",
+                        from, to
+                    );
+                    Outcome::Neutral
+                }
+            },
+
             Decision::Monitor { interval_seconds } => {
                 debug!("Monitoring with interval {} seconds", interval_seconds);
+                Outcome::Neutral
             }
 
             Decision::Wait { duration_seconds } => {
                 debug!("Waiting for {} seconds", duration_seconds);
+                Outcome::Neutral
             }
+        };
 
-            _ => {
-                warn!("Unhandled decision type: {:?}", decision);
-            }
-        }
+        Measurement::Immediate(outcome, metrics)
     }
 
     pub async fn stop(&self) {
@@ -351,6 +588,108 @@ impl AutonomousAgent {
         *self.is_running.write().await = false;
     }
 
+    pub fn identity(&self) -> &AgentIdentity {
+        &self.identity
+    }
+
+    /// Run tasks of type `TaskType::Custom(name)` with `executor`
+    pub async fn register_custom_executor(&self, name: &str, executor: Box<dyn TaskExecutor>) {
+        self.task_scheduler
+            .register_custom_executor(name, executor)
+            .await;
+    }
+
+    /// Run tasks on `task_scheduler`, e.g. one that persists its queue
+    pub fn with_task_scheduler(mut self, task_scheduler: TaskScheduler) -> Self {
+        self.task_scheduler = Arc::new(task_scheduler);
+        self
+    }
+
+    /// Persist every decision and its outcome to `journal`
+    pub fn with_decision_journal(mut self, journal: DecisionJournal) -> Self {
+        let decision_maker = std::mem::take(self.decision_maker_mut());
+        *self.decision_maker_mut() = decision_maker.with_journal(journal);
+        self
+    }
+
+    /// Factor market sentiment and regimes from `AppEvent::SentimentUpdate`s and
+    /// `AppEvent::MarketConditions` on `feed` into decisions
+    pub fn with_sentiment_feed(mut self, feed: EventReceiver) -> Self {
+        *self
+            .sentiment_feed
+            .get_mut()
+            .expect("sentiment feed lock poisoned") = Some(feed);
+        self
+    }
+
+    /// Hold deployments, scaling and emergency shutdowns until `approvals` lets them through
+    pub fn with_approval_gate(mut self, approvals: ApprovalGate) -> Self {
+        let recovery_manager = Arc::get_mut(&mut self.recovery_manager)
+            .expect("recovery manager is only configured before the agent runs");
+        *recovery_manager = std::mem::take(recovery_manager).with_approval_gate(approvals.clone());
+        self.approvals = approvals;
+        self
+    }
+
+    /// Carry out `Decision::Migrate` with `migrator`; without one it is ignored
+    pub fn with_migrator(mut self, migrator: AgentMigrator) -> Self {
+        self.migrator = Some(Arc::new(migrator));
+        self
+    }
+
+    /// Publish the scheduler, recovery and health status every `STATUS_INTERVAL`
+    pub fn with_event_bus(mut self, bus: EventBus) -> Self {
+        self.events = Some(bus);
+        self
+    }
+
+    /// Start out deciding with `policy` instead of the rule-based default
+    pub fn with_decision_policy(mut self, policy: Box<dyn DecisionPolicy>) -> Self {
+        self.decision_maker_mut().set_policy(policy);
+        self
+    }
+
+    /// Switch the decision policy of a running agent; takes effect on the next decision
+    pub async fn set_decision_policy(&self, policy: Box<dyn DecisionPolicy>) {
+        self.decision_maker.write().await.set_policy(policy);
+    }
+
+    /// Start out checking health against `thresholds` instead of the defaults
+    pub fn with_health_thresholds(mut self, thresholds: HealthThresholds) -> Self {
+        self.health_monitor = Arc::new(HealthMonitor::new().with_thresholds(thresholds));
+        self
+    }
+
+    /// Retune the health checks of a running agent; takes effect on the next check
+    pub async fn set_health_thresholds(&self, thresholds: HealthThresholds) {
+        self.health_monitor.set_thresholds(thresholds).await;
+    }
+
+    /// Replace the replication strategy of a running agent
+    pub fn set_replication_strategy(&self, strategy: ReplicationStrategy) {
+        self.self_replicator.set_strategy(strategy);
+    }
+
+    pub async fn decision_policy_name(&self) -> &'static str {
+        self.decision_maker.read().await.policy_name()
+    }
+
+    fn decision_maker_mut(&mut self) -> &mut AutonomousDecisionMaker {
+        Arc::get_mut(&mut self.decision_maker)
+            .expect("decision maker is only configured before the agent runs")
+            .get_mut()
+    }
+
+    /// The replicator, e.g. for reporting replication status
+    pub fn self_replicator(&self) -> Arc<SelfReplicator> {
+        self.self_replicator.clone()
+    }
+
+    /// Replicas deployed by this agent
+    pub async fn get_lineage(&self) -> Vec<crate::self_replicator::LineageRecord> {
+        self.self_replicator.get_lineage().await
+    }
+
     pub async fn get_status(&self) -> AgentStatus {
         let health_summary = self.health_monitor.get_current_health().await;
         let replication_status = self.self_replicator.get_status().await;
@@ -358,6 +697,7 @@ impl AutonomousAgent {
         let scheduler_status = self.task_scheduler.get_status().await;
 
         AgentStatus {
+            identity: self.identity.clone(),
             is_running: *self.is_running.read().await,
             health_status: format!("{:?}", health_summary.status),
             active_replicas: replication_status.active_replicas,
@@ -368,8 +708,84 @@ impl AutonomousAgent {
     }
 }
 
+/// How often subsystem status is published on the bus
+const STATUS_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_secs(30);
+
+/// How long new replicas get to take over load before a scaling decision is judged
+const SCALE_SETTLE_SECONDS: i64 = 120;
+
+/// What executing a decision revealed about its outcome
+enum Measurement {
+    Immediate(Outcome, HashMap<String, f64>),
+    /// Replicas were added; the outcome depends on the load once they settle
+    AfterScaling {
+        metrics: HashMap<String, f64>,
+    },
+}
+
+/// A scaling decision waiting for its post-scale load to be measured
+struct PendingFeedback {
+    decision_id: String,
+    due: chrono::DateTime<Utc>,
+    cpu_before: f64,
+    metrics: HashMap<String, f64>,
+}
+
+impl PendingFeedback {
+    fn resolve(mut self, cpu_after: f64) -> DecisionFeedback {
+        self.metrics
+            .insert("cpu_before".to_string(), self.cpu_before);
+        self.metrics.insert("cpu_after".to_string(), cpu_after);
+        DecisionFeedback {
+            decision_id: self.decision_id,
+            outcome: scaling_outcome(self.cpu_before, cpu_after),
+            metrics: self.metrics,
+        }
+    }
+}
+
+/// Scaling succeeded if it took a noticeable share of the load off this agent and
+/// failed if the load kept rising.
+fn scaling_outcome(cpu_before: f64, cpu_after: f64) -> Outcome {
+    if cpu_after <= cpu_before * 0.9 {
+        Outcome::Success
+    } else if cpu_after > cpu_before {
+        Outcome::Failure
+    } else {
+        Outcome::Neutral
+    }
+}
+
+/// Judge replication attempts: any deployed replica is a success, only failed
+/// attempts a failure, and nothing attempted is neutral.
+fn replication_outcome(
+    results: Vec<Result<Vec<ReplicationResult>>>,
+    metrics: &mut HashMap<String, f64>,
+) -> Outcome {
+    let mut succeeded = 0;
+    let mut failed = 0;
+    for result in results {
+        match result {
+            Ok(results) => {
+                succeeded += results.iter().filter(|r| r.success).count();
+                failed += results.iter().filter(|r| !r.success).count();
+            }
+            Err(_) => failed += 1,
+        }
+    }
+    metrics.insert("replicas_deployed".to_string(), succeeded as f64);
+    metrics.insert("replication_failures".to_string(), failed as f64);
+
+    match (succeeded, failed) {
+        (0, 0) => Outcome::Neutral,
+        (0, _) => Outcome::Failure,
+        _ => Outcome::Success,
+    }
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct AgentStatus {
+    pub identity: AgentIdentity,
     pub is_running: bool,
     pub health_status: String,
     pub active_replicas: usize,
@@ -377,3 +793,46 @@ pub struct AgentStatus {
     pub running_tasks: usize,
     pub recovery_success_rate: f64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scaling_is_judged_by_post_scale_load() {
+        let pending = PendingFeedback {
+            decision_id: "d1".to_string(),
+            due: Utc::now(),
+            cpu_before: 80.0,
+            metrics: HashMap::from([("replicas_deployed".to_string(), 2.0)]),
+        };
+        let feedback = pending.resolve(50.0);
+        assert!(matches!(feedback.outcome, Outcome::Success));
+        assert_eq!(feedback.metrics["cpu_after"], 50.0);
+        assert_eq!(feedback.metrics["replicas_deployed"], 2.0);
+
+        assert!(matches!(scaling_outcome(80.0, 78.0), Outcome::Neutral));
+        assert!(matches!(scaling_outcome(80.0, 95.0), Outcome::Failure));
+    }
+
+    #[tokio::test]
+    async fn test_subsystem_status_is_published_on_the_system_topic() {
+        let bus = EventBus::new(8);
+        let mut rx = bus.subscribe_as("test", &[common::Topic::System]);
+        let agent = AutonomousAgent::new(PathBuf::from("kernel")).with_event_bus(bus.clone());
+        AutonomousAgent::publish_status(
+            &bus,
+            &agent.health_monitor,
+            &agent.recovery_manager,
+            &agent.task_scheduler,
+        )
+        .await;
+
+        let kinds: Vec<_> = (0..3).map(|_| rx.try_recv().unwrap().kind()).collect();
+        assert_eq!(
+            kinds,
+            ["scheduler_status", "recovery_stats", "health_summary"]
+        );
+        assert!(rx.try_recv().is_err());
+    }
+}
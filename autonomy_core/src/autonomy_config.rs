@@ -0,0 +1,29 @@
+use crate::task_executors::ExecutorConfig;
+use anyhow::Result;
+use common::DecisionPolicyKind;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Where the autonomy settings live, relative to the deployment directory
+pub const AUTONOMY_CONFIG_PATH: &str = "config/autonomy.json";
+
+/// Settings of the autonomous agent
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutonomyConfig {
+    pub decision_policy: DecisionPolicyKind,
+    /// Executors for custom task types
+    pub task_executors: Vec<ExecutorConfig>,
+}
+
+impl AutonomyConfig {
+    /// Load the settings from `path`, using the defaults when the file does not exist
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
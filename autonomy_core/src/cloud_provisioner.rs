@@ -0,0 +1,462 @@
+use crate::server_config::{ServerConfig, TargetServer};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use survival_protocol::{Budget, MINIMUM_RUNWAY_HOURS};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// 云服务器配置，相对于部署目录；缺少该文件时不自动购买服务器
+pub const CLOUD_CONFIG_PATH: &str = "config/cloud.json";
+
+/// 已购买的服务器，重启后仍需要能释放
+pub const CLOUD_SERVERS_PATH: &str = "data/cloud_servers.json";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CloudProviderKind {
+    Hetzner,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CloudConfig {
+    pub provider: CloudProviderKind,
+    /// 保存 API 令牌的环境变量
+    pub api_token_env: String,
+    pub server_type: String,
+    pub image: String,
+    pub location: String,
+    /// 注入新服务器的公钥，部署时使用对应的私钥登录
+    pub ssh_public_key_path: String,
+    pub ssh_private_key_path: String,
+    pub username: String,
+    pub remote_path: String,
+    /// 所选机型的每小时成本，用于预算检查
+    pub hourly_cost: f64,
+    /// 本代理最多同时持有的云服务器数量
+    pub max_servers: usize,
+}
+
+impl Default for CloudConfig {
+    fn default() -> Self {
+        Self {
+            provider: CloudProviderKind::Hetzner,
+            api_token_env: "HCLOUD_TOKEN".to_string(),
+            server_type: "cx22".to_string(),
+            image: "ubuntu-24.04".to_string(),
+            location: "fsn1".to_string(),
+            ssh_public_key_path: "~/.ssh/id_ed25519.pub".to_string(),
+            ssh_private_key_path: "~/.ssh/id_ed25519".to_string(),
+            username: "root".to_string(),
+            remote_path: "/opt/aurelia".to_string(),
+            hourly_cost: 0.01,
+            max_servers: 3,
+        }
+    }
+}
+
+impl CloudConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Option<Self>> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+
+    /// 按配置的提供商创建客户端，API 令牌从环境变量读取
+    pub fn build_provisioner(&self) -> Result<Arc<dyn CloudProvisioner>> {
+        let token = std::env::var(&self.api_token_env)
+            .with_context(|| format!("{} is not set", self.api_token_env))?;
+        Ok(match self.provider {
+            CloudProviderKind::Hetzner => Arc::new(HetznerProvisioner::new(token)),
+        })
+    }
+}
+
+/// A VM to create
+#[derive(Debug, Clone)]
+pub struct VmSpec {
+    pub name: String,
+    pub server_type: String,
+    pub image: String,
+    pub location: String,
+    pub ssh_public_key: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProvisionedVm {
+    pub provider: CloudProviderKind,
+    pub id: String,
+    pub name: String,
+    pub ip: String,
+    pub hourly_cost: f64,
+}
+
+/// A cloud API the agent can buy replication targets from
+#[async_trait]
+pub trait CloudProvisioner: Send + Sync {
+    fn kind(&self) -> CloudProviderKind;
+
+    /// Create a VM reachable over SSH with `spec.ssh_public_key`
+    async fn create_vm(&self, spec: &VmSpec) -> Result<ProvisionedVm>;
+
+    async fn destroy_vm(&self, id: &str) -> Result<()>;
+}
+
+/// Hetzner Cloud, https://docs.hetzner.cloud
+pub struct HetznerProvisioner {
+    client: reqwest::Client,
+    token: String,
+    base_url: String,
+}
+
+impl HetznerProvisioner {
+    pub fn new(token: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            token,
+            base_url: "https://api.hetzner.cloud/v1".to_string(),
+        }
+    }
+
+    /// Talk to another endpoint, e.g. a mock API in tests
+    pub fn with_base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = url.into();
+        self
+    }
+
+    async fn post(&self, path: &str, body: serde_json::Value) -> Result<serde_json::Value> {
+        let response = self
+            .client
+            .post(format!("{}{}", self.base_url, path))
+            .bearer_auth(&self.token)
+            .json(&body)
+            .send()
+            .await?;
+        let status = response.status();
+        let body: serde_json::Value = response.json().await?;
+        if !status.is_success() {
+            return Err(anyhow::anyhow!(
+                "Hetzner API {} returned {}: {}",
+                path,
+                status,
+                body["error"]["message"].as_str().unwrap_or_default()
+            ));
+        }
+        Ok(body)
+    }
+
+    /// ID of the SSH key with this public key, uploading it if needed
+    async fn ssh_key_id(&self, name: &str, public_key: &str) -> Result<u64> {
+        let existing: serde_json::Value = self
+            .client
+            .get(format!("{}/ssh_keys", self.base_url))
+            .bearer_auth(&self.token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let found = existing["ssh_keys"].as_array().and_then(|keys| {
+            keys.iter()
+                .find(|key| key["public_key"].as_str().map(str::trim) == Some(public_key.trim()))
+                .and_then(|key| key["id"].as_u64())
+        });
+        if let Some(id) = found {
+            return Ok(id);
+        }
+
+        let created = self
+            .post(
+                "/ssh_keys",
+                serde_json::json!({ "name": name, "public_key": public_key.trim() }),
+            )
+            .await?;
+        created["ssh_key"]["id"]
+            .as_u64()
+            .ok_or_else(|| anyhow::anyhow!("Hetzner API returned no SSH key ID"))
+    }
+}
+
+#[async_trait]
+impl CloudProvisioner for HetznerProvisioner {
+    fn kind(&self) -> CloudProviderKind {
+        CloudProviderKind::Hetzner
+    }
+
+    async fn create_vm(&self, spec: &VmSpec) -> Result<ProvisionedVm> {
+        let key_id = self
+            .ssh_key_id(&format!("{}-key", spec.name), &spec.ssh_public_key)
+            .await?;
+        let created = self
+            .post(
+                "/servers",
+                serde_json::json!({
+                    "name": spec.name,
+                    "server_type": spec.server_type,
+                    "image": spec.image,
+                    "location": spec.location,
+                    "ssh_keys": [key_id],
+                    "labels": { "managed-by": "aurelia" },
+                }),
+            )
+            .await?;
+
+        let server = &created["server"];
+        let id = server["id"]
+            .as_u64()
+            .ok_or_else(|| anyhow::anyhow!("Hetzner API returned no server ID"))?;
+        let ip = server["public_net"]["ipv4"]["ip"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Server {} has no public IPv4 address", id))?;
+
+        Ok(ProvisionedVm {
+            provider: CloudProviderKind::Hetzner,
+            id: id.to_string(),
+            name: spec.name.clone(),
+            ip: ip.to_string(),
+            hourly_cost: 0.0,
+        })
+    }
+
+    async fn destroy_vm(&self, id: &str) -> Result<()> {
+        self.client
+            .delete(format!("{}/servers/{}", self.base_url, id))
+            .bearer_auth(&self.token)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// 购买前的预算检查：加上新服务器的成本后资金跑道不得低于 `MINIMUM_RUNWAY_HOURS`
+pub fn check_budget(budget: Option<&Budget>, hourly_cost: f64) -> Result<()> {
+    let budget = budget.ok_or_else(|| anyhow::anyhow!("No budget information available"))?;
+    let runway = budget.projected_runway_hours(hourly_cost);
+    if runway < MINIMUM_RUNWAY_HOURS {
+        return Err(anyhow::anyhow!(
+            "Projected runway {:.1}h with another server is below {:.1}h",
+            runway,
+            MINIMUM_RUNWAY_HOURS
+        ));
+    }
+    Ok(())
+}
+
+/// 购买和释放云服务器，并记录本代理持有的服务器
+pub struct CloudFleet {
+    provisioner: Arc<dyn CloudProvisioner>,
+    config: CloudConfig,
+    servers: RwLock<BTreeMap<String, ProvisionedVm>>,
+    store: Option<PathBuf>,
+}
+
+impl CloudFleet {
+    pub fn new(provisioner: Arc<dyn CloudProvisioner>, config: CloudConfig) -> Self {
+        Self {
+            provisioner,
+            config,
+            servers: RwLock::new(BTreeMap::new()),
+            store: None,
+        }
+    }
+
+    /// 将持有的服务器保存到 `path`，并恢复之前保存的记录
+    pub fn with_persistence(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if path.exists() {
+            let servers = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+            self.servers = RwLock::new(servers);
+        }
+        self.store = Some(path);
+        Ok(self)
+    }
+
+    pub async fn servers(&self) -> Vec<ProvisionedVm> {
+        self.servers.read().await.values().cloned().collect()
+    }
+
+    pub async fn owns(&self, ip: &str) -> bool {
+        self.servers.read().await.contains_key(ip)
+    }
+
+    /// 持有的服务器作为部署目标
+    pub async fn target_servers(&self) -> Vec<TargetServer> {
+        self.servers
+            .read()
+            .await
+            .values()
+            .map(|vm| self.target_server(vm))
+            .collect()
+    }
+
+    fn target_server(&self, vm: &ProvisionedVm) -> TargetServer {
+        let mut server = TargetServer::new(
+            format!("cloud-{}", vm.id),
+            vm.name.clone(),
+            vm.ip.clone(),
+            self.config.username.clone(),
+        );
+        server.ssh_key_path = Some(self.config.ssh_private_key_path.clone());
+        server.remote_path = self.config.remote_path.clone();
+        server.hourly_cost = Some(vm.hourly_cost);
+        server.tags = vec!["cloud".to_string()];
+        server
+    }
+
+    /// 预算允许时购买一台服务器，返回可直接部署的目标服务器
+    pub async fn acquire(&self, budget: Option<&Budget>, name: &str) -> Result<TargetServer> {
+        let owned = self.servers.read().await.len();
+        if owned >= self.config.max_servers {
+            return Err(anyhow::anyhow!(
+                "Already holding {} cloud servers (max {})",
+                owned,
+                self.config.max_servers
+            ));
+        }
+        check_budget(budget, self.config.hourly_cost)?;
+
+        let public_key_path = ServerConfig::expand_ssh_key_path(&self.config.ssh_public_key_path);
+        let ssh_public_key = std::fs::read_to_string(&public_key_path)
+            .with_context(|| format!("Failed to read SSH public key {:?}", public_key_path))?;
+        let spec = VmSpec {
+            name: name.to_string(),
+            server_type: self.config.server_type.clone(),
+            image: self.config.image.clone(),
+            location: self.config.location.clone(),
+            ssh_public_key,
+        };
+
+        let mut vm = self.provisioner.create_vm(&spec).await?;
+        vm.hourly_cost = self.config.hourly_cost;
+        info!(
+            "Acquired {:?} server {} ({}) at {:.3}/h",
+            vm.provider, vm.name, vm.ip, vm.hourly_cost
+        );
+
+        let server = self.target_server(&vm);
+        let mut servers = self.servers.write().await;
+        servers.insert(vm.ip.clone(), vm);
+        self.persist(&servers);
+        Ok(server)
+    }
+
+    /// 销毁通过本代理购买的服务器
+    pub async fn release(&self, ip: &str) -> Result<()> {
+        let Some(vm) = self.servers.read().await.get(ip).cloned() else {
+            return Err(anyhow::anyhow!(
+                "{} is not a cloud server of this agent",
+                ip
+            ));
+        };
+        self.provisioner.destroy_vm(&vm.id).await?;
+        info!("Released cloud server {} ({})", vm.name, vm.ip);
+
+        let mut servers = self.servers.write().await;
+        servers.remove(ip);
+        self.persist(&servers);
+        Ok(())
+    }
+
+    fn persist(&self, servers: &BTreeMap<String, ProvisionedVm>) {
+        let Some(path) = &self.store else {
+            return;
+        };
+        let result = (|| -> Result<()> {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, serde_json::to_vec_pretty(servers)?)?;
+            Ok(())
+        })();
+        if let Err(e) = result {
+            warn!("Failed to persist cloud servers: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct FakeCloud {
+        created: AtomicUsize,
+        destroyed: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl CloudProvisioner for FakeCloud {
+        fn kind(&self) -> CloudProviderKind {
+            CloudProviderKind::Hetzner
+        }
+
+        async fn create_vm(&self, spec: &VmSpec) -> Result<ProvisionedVm> {
+            let n = self.created.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(ProvisionedVm {
+                provider: CloudProviderKind::Hetzner,
+                id: n.to_string(),
+                name: spec.name.clone(),
+                ip: format!("10.0.0.{}", n),
+                hourly_cost: 0.0,
+            })
+        }
+
+        async fn destroy_vm(&self, _id: &str) -> Result<()> {
+            self.destroyed.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_acquire_is_gated_by_budget_and_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = dir.path().join("id.pub");
+        std::fs::write(&key, "ssh-ed25519 AAAA test").unwrap();
+        let config = CloudConfig {
+            ssh_public_key_path: key.to_string_lossy().into_owned(),
+            hourly_cost: 0.5,
+            ..CloudConfig::default()
+        };
+        let cloud = Arc::new(FakeCloud::default());
+        let store = dir.path().join("servers.json");
+        let fleet = CloudFleet::new(cloud.clone(), config.clone())
+            .with_persistence(&store)
+            .unwrap();
+
+        let poor = Budget {
+            funds: 10.0,
+            hourly_cost: 0.5,
+            recent_pnl: 0.0,
+            ..Budget::default()
+        };
+        assert!(fleet.acquire(Some(&poor), "replica-1").await.is_err());
+        assert!(fleet.acquire(None, "replica-1").await.is_err());
+
+        let funded = Budget {
+            funds: 100.0,
+            ..poor
+        };
+        let server = fleet.acquire(Some(&funded), "replica-1").await.unwrap();
+        assert_eq!(server.ip, "10.0.0.1");
+        assert_eq!(server.hourly_cost, Some(0.5));
+        assert_eq!(cloud.created.load(Ordering::SeqCst), 1);
+
+        // A restarted agent can still release what it bought
+        let restored = CloudFleet::new(cloud.clone(), config)
+            .with_persistence(&store)
+            .unwrap();
+        assert!(restored.owns("10.0.0.1").await);
+        restored.release("10.0.0.1").await.unwrap();
+        assert!(restored.servers().await.is_empty());
+        assert_eq!(cloud.destroyed.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,11 @@
+use anyhow::Result;
+
+/// Source of truth for how many agents make up the fleet.
+///
+/// The primary answers from the agents reporting to its monitoring service;
+/// replicas ask the primary.
+#[async_trait::async_trait]
+pub trait ClusterRegistry: Send + Sync {
+    /// Number of live agents in the fleet, including the caller.
+    async fn fleet_size(&self) -> Result<usize>;
+}
@@ -0,0 +1,215 @@
+use crate::decision_maker::{Decision, DecisionContext, DecisionFeedback};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Default location of the journal, relative to the deployment directory
+pub const DECISION_JOURNAL_PATH: &str = "data/decisions.jsonl";
+
+/// Number of decisions kept in memory for queries; the file keeps everything
+const MAX_RECORDS_IN_MEMORY: usize = 10_000;
+
+/// A decision together with the context it was made in and, once known, its outcome
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecisionRecord {
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    pub decision: Decision,
+    pub context: DecisionContext,
+    pub outcome: Option<DecisionFeedback>,
+}
+
+/// One line of the journal file. Outcomes are appended when they become known and
+/// linked to their decision through `DecisionFeedback::decision_id` on load.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum JournalEntry {
+    Decision(Box<DecisionRecord>),
+    Outcome(DecisionFeedback),
+}
+
+#[derive(Debug)]
+struct Inner {
+    file: Option<File>,
+    records: VecDeque<DecisionRecord>,
+}
+
+/// Append-only JSON Lines journal of the decision maker's decisions.
+#[derive(Debug, Clone)]
+pub struct DecisionJournal {
+    path: Option<PathBuf>,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl DecisionJournal {
+    /// Open the journal at `path`, replaying existing entries.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut records = VecDeque::new();
+        if path.exists() {
+            let reader = BufReader::new(File::open(&path)?);
+            for line in reader.lines() {
+                let line = line?;
+                match serde_json::from_str::<JournalEntry>(&line) {
+                    Ok(entry) => apply(&mut records, entry),
+                    Err(e) => warn!("Skipping unreadable decision journal entry: {}", e),
+                }
+            }
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path: Some(path),
+            inner: Arc::new(Mutex::new(Inner {
+                file: Some(file),
+                records,
+            })),
+        })
+    }
+
+    /// A journal that only keeps decisions in memory.
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            inner: Arc::new(Mutex::new(Inner {
+                file: None,
+                records: VecDeque::new(),
+            })),
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Record a decision and return its ID.
+    pub fn record_decision(&self, decision: &Decision, context: &DecisionContext) -> String {
+        let record = DecisionRecord {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            decision: decision.clone(),
+            context: context.clone(),
+            outcome: None,
+        };
+        let id = record.id.clone();
+        self.append(JournalEntry::Decision(Box::new(record)));
+        id
+    }
+
+    /// Attach the outcome of an earlier decision.
+    pub fn record_outcome(&self, feedback: &DecisionFeedback) {
+        self.append(JournalEntry::Outcome(feedback.clone()));
+    }
+
+    /// Decisions made after `since`, oldest first.
+    pub fn since(&self, since: Option<DateTime<Utc>>) -> Vec<DecisionRecord> {
+        let inner = self.inner.lock().expect("decision journal lock poisoned");
+        inner
+            .records
+            .iter()
+            .filter(|r| match since {
+                Some(since) => r.timestamp > since,
+                None => true,
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn append(&self, entry: JournalEntry) {
+        let mut inner = self.inner.lock().expect("decision journal lock poisoned");
+
+        // A failing disk must not stop the agent from deciding
+        if let Some(file) = inner.file.as_mut() {
+            let written = serde_json::to_string(&entry)
+                .map_err(std::io::Error::from)
+                .and_then(|line| writeln!(file, "{}", line));
+            if let Err(e) = written {
+                warn!("Failed to write decision journal: {}", e);
+            }
+        }
+
+        apply(&mut inner.records, entry);
+    }
+}
+
+fn apply(records: &mut VecDeque<DecisionRecord>, entry: JournalEntry) {
+    match entry {
+        JournalEntry::Decision(record) => {
+            records.push_back(*record);
+            if records.len() > MAX_RECORDS_IN_MEMORY {
+                records.pop_front();
+            }
+        }
+        JournalEntry::Outcome(feedback) => {
+            if let Some(record) = records
+                .iter_mut()
+                .rev()
+                .find(|r| r.id == feedback.decision_id)
+            {
+                record.outcome = Some(feedback);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decision_maker::{Outcome, ResourceMetrics};
+    use std::collections::HashMap;
+
+    fn context() -> DecisionContext {
+        DecisionContext {
+            timestamp: Utc::now(),
+            system_health: 1.0,
+            resource_usage: ResourceMetrics {
+                cpu_percent: 10.0,
+                memory_mb: 100.0,
+                disk_gb: 1.0,
+                network_mbps: 0.0,
+            },
+            active_nodes: vec![],
+            failed_nodes: vec![],
+            pending_tasks: 0,
+            market_conditions: None,
+        }
+    }
+
+    #[test]
+    fn test_journal_links_outcomes_and_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("decisions.jsonl");
+
+        let journal = DecisionJournal::open(&path).unwrap();
+        let decision = Decision::Monitor {
+            interval_seconds: 30,
+        };
+        let id = journal.record_decision(&decision, &context());
+        journal.record_outcome(&DecisionFeedback {
+            decision_id: id.clone(),
+            outcome: Outcome::Success,
+            metrics: HashMap::new(),
+        });
+        drop(journal);
+
+        let reopened = DecisionJournal::open(&path).unwrap();
+        let records = reopened.since(None);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].id, id);
+        assert!(matches!(
+            records[0].outcome.as_ref().map(|f| &f.outcome),
+            Some(Outcome::Success)
+        ));
+        assert!(reopened.since(Some(records[0].timestamp)).is_empty());
+    }
+}
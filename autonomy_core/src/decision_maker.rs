@@ -1,8 +1,14 @@
+use crate::decision_journal::DecisionJournal;
+use crate::decision_policy::{DecisionPolicy, RuleBasedPolicy};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
+use common::BoundedHistory;
+pub use common::MarketConditions;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use tracing::{debug, info, warn};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::info;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Decision {
@@ -84,41 +90,19 @@ pub enum NodeStatus {
     Unknown,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MarketConditions {
-    pub volatility: f64,
-    pub opportunity_score: f64,
-    pub risk_level: f64,
-}
-
-pub struct AutonomousDecisionMaker {
-    thresholds: DecisionThresholds,
-    decision_history: Vec<(DateTime<Utc>, Decision)>,
-    learning_rate: f64,
-}
+/// Decisions kept in memory; the journal holds the full record
+const DECISION_HISTORY_LEN: usize = 1000;
 
-#[derive(Debug, Clone)]
-struct DecisionThresholds {
-    min_health_for_expansion: f64,
-    max_cpu_before_scaling: f64,
-    max_memory_before_scaling: f64,
-    #[allow(dead_code)]
-    min_nodes_required: usize,
-    max_nodes_allowed: usize,
-    failure_tolerance: f64,
-}
+/// The decision policy, locked on its own so that a slow decision does not hold
+/// up everything else that needs the decision maker
+pub type SharedPolicy = Arc<Mutex<Box<dyn DecisionPolicy>>>;
 
-impl Default for DecisionThresholds {
-    fn default() -> Self {
-        Self {
-            min_health_for_expansion: 0.8,
-            max_cpu_before_scaling: 75.0,
-            max_memory_before_scaling: 80.0,
-            min_nodes_required: 1,
-            max_nodes_allowed: 10,
-            failure_tolerance: 0.3,
-        }
-    }
+pub struct AutonomousDecisionMaker {
+    policy: SharedPolicy,
+    policy_name: &'static str,
+    decision_history: BoundedHistory<Decision>,
+    journal: DecisionJournal,
+    last_decision_id: Option<String>,
 }
 
 impl Default for AutonomousDecisionMaker {
@@ -129,172 +113,88 @@ impl Default for AutonomousDecisionMaker {
 
 impl AutonomousDecisionMaker {
     pub fn new() -> Self {
-        Self {
-            thresholds: DecisionThresholds::default(),
-            decision_history: Vec::new(),
-            learning_rate: 0.1,
-        }
+        Self::with_policy(Box::new(RuleBasedPolicy::new()))
     }
 
-    pub async fn make_decision(&mut self, context: &DecisionContext) -> Result<Decision> {
-        info!("Making autonomous decision based on current context");
-
-        // 1. Check for critical failures first
-        if let Some(decision) = self.check_failures(context) {
-            self.record_decision(decision.clone());
-            return Ok(decision);
-        }
-
-        // 2. Check resource pressure
-        if let Some(decision) = self.check_resource_pressure(context) {
-            self.record_decision(decision.clone());
-            return Ok(decision);
-        }
-
-        // 3. Check for expansion opportunities
-        if let Some(decision) = self.check_expansion_opportunity(context) {
-            self.record_decision(decision.clone());
-            return Ok(decision);
-        }
-
-        // 4. Check market conditions for trading decisions
-        if let Some(decision) = self.check_market_conditions(context) {
-            self.record_decision(decision.clone());
-            return Ok(decision);
+    pub fn with_policy(policy: Box<dyn DecisionPolicy>) -> Self {
+        Self {
+            policy_name: policy.name(),
+            policy: Arc::new(Mutex::new(policy)),
+            decision_history: BoundedHistory::new(DECISION_HISTORY_LEN),
+            journal: DecisionJournal::in_memory(),
+            last_decision_id: None,
         }
-
-        // 5. Default to monitoring
-        let decision = Decision::Monitor {
-            interval_seconds: 30,
-        };
-        self.record_decision(decision.clone());
-        Ok(decision)
     }
 
-    fn check_failures(&self, context: &DecisionContext) -> Option<Decision> {
-        if !context.failed_nodes.is_empty() {
-            let failed_node = &context.failed_nodes[0];
-            warn!("Detected failed node: {}", failed_node.id);
-
-            // Decide recovery action based on failure rate
-            let failure_rate = context.failed_nodes.len() as f64
-                / (context.active_nodes.len() + context.failed_nodes.len()) as f64;
-
-            let recovery_action = if failure_rate > self.thresholds.failure_tolerance {
-                RecoveryAction::Failover
-            } else {
-                RecoveryAction::Redeploy
-            };
-
-            return Some(Decision::Recover {
-                failed_node: failed_node.id.clone(),
-                recovery_action,
-            });
-        }
-        None
+    /// Persist decisions and their outcomes to `journal`
+    pub fn with_journal(mut self, journal: DecisionJournal) -> Self {
+        self.journal = journal;
+        self
     }
 
-    fn check_resource_pressure(&self, context: &DecisionContext) -> Option<Decision> {
-        let metrics = &context.resource_usage;
-
-        if metrics.cpu_percent > self.thresholds.max_cpu_before_scaling
-            || metrics.memory_mb > self.thresholds.max_memory_before_scaling
-        {
-            info!(
-                "Resource pressure detected: CPU {}%, Memory {}MB",
-                metrics.cpu_percent, metrics.memory_mb
-            );
-
-            // Calculate scaling factor
-            let cpu_pressure = metrics.cpu_percent / self.thresholds.max_cpu_before_scaling;
-            let mem_pressure = metrics.memory_mb / self.thresholds.max_memory_before_scaling;
-            let scale_factor = f64::max(cpu_pressure, mem_pressure).min(2.0);
+    pub fn journal(&self) -> &DecisionJournal {
+        &self.journal
+    }
 
-            return Some(Decision::Scale {
-                factor: scale_factor,
-                reason: format!(
-                    "High resource usage: CPU {:.1}%, Memory {:.0}MB",
-                    metrics.cpu_percent, metrics.memory_mb
-                ),
-            });
-        }
-        None
+    /// Replace the policy used for future decisions; a decision in progress
+    /// finishes with the old one
+    pub fn set_policy(&mut self, policy: Box<dyn DecisionPolicy>) {
+        info!(
+            "Switching decision policy from {} to {}",
+            self.policy_name,
+            policy.name()
+        );
+        self.policy_name = policy.name();
+        self.policy = Arc::new(Mutex::new(policy));
     }
 
-    fn check_expansion_opportunity(&self, context: &DecisionContext) -> Option<Decision> {
-        if context.system_health >= self.thresholds.min_health_for_expansion
-            && context.active_nodes.len() < self.thresholds.max_nodes_allowed
-        {
-            // Identify potential target servers for expansion
-            let target_servers = self.identify_expansion_targets(context);
+    pub fn policy_name(&self) -> &'static str {
+        self.policy_name
+    }
 
-            if !target_servers.is_empty() {
-                info!("Expansion opportunity identified");
-                return Some(Decision::Deploy {
-                    target_servers,
-                    priority: Priority::Normal,
-                    reason: format!(
-                        "System health good ({:.1}%), expanding network",
-                        context.system_health * 100.0
-                    ),
-                });
-            }
-        }
-        None
+    /// The current policy, for deciding without holding the decision maker.
+    /// Record what it decides with [`Self::record_decision`].
+    pub fn policy(&self) -> SharedPolicy {
+        self.policy.clone()
     }
 
-    fn check_market_conditions(&self, context: &DecisionContext) -> Option<Decision> {
-        if let Some(market) = &context.market_conditions {
-            if market.opportunity_score > 0.7 && market.risk_level < 0.3 {
-                debug!("Favorable market conditions detected");
-                // Market conditions are good but we return None
-                // as trading decisions are handled by strategy engine
-            }
-        }
-        None
+    /// Journal ID of the most recent decision, used to report its outcome
+    pub fn last_decision_id(&self) -> Option<&str> {
+        self.last_decision_id.as_deref()
     }
 
-    fn identify_expansion_targets(&self, _context: &DecisionContext) -> Vec<String> {
-        // In a real implementation, this would analyze network topology,
-        // geographic distribution, and available resources
-        vec!["192.168.1.102".to_string(), "192.168.1.103".to_string()]
+    pub async fn make_decision(&mut self, context: &DecisionContext) -> Result<Decision> {
+        let decision = Self::decide(&self.policy, context).await?;
+        self.record_decision(&decision, context);
+        Ok(decision)
     }
 
-    fn record_decision(&mut self, decision: Decision) {
-        self.decision_history.push((Utc::now(), decision));
+    /// Ask `policy`, as returned by [`Self::policy`], for a decision
+    pub async fn decide(policy: &SharedPolicy, context: &DecisionContext) -> Result<Decision> {
+        let mut policy = policy.lock().await;
+        info!(
+            "Making autonomous decision based on current context using {} policy",
+            policy.name()
+        );
+        policy.decide(context).await
+    }
 
-        // Keep only recent history
-        if self.decision_history.len() > 1000 {
-            self.decision_history.drain(0..100);
-        }
+    pub fn record_decision(&mut self, decision: &Decision, context: &DecisionContext) {
+        self.last_decision_id = Some(self.journal.record_decision(decision, context));
+        self.decision_history.push(decision.clone());
     }
 
-    pub fn adjust_thresholds(&mut self, feedback: &DecisionFeedback) {
-        // Simple learning: adjust thresholds based on outcome
-        match feedback.outcome {
-            Outcome::Success => {
-                // Slightly relax thresholds on success
-                self.thresholds.min_health_for_expansion *= 1.0 - self.learning_rate * 0.1;
-                self.thresholds.max_cpu_before_scaling *= 1.0 + self.learning_rate * 0.05;
-            }
-            Outcome::Failure => {
-                // Tighten thresholds on failure
-                self.thresholds.min_health_for_expansion *= 1.0 + self.learning_rate * 0.1;
-                self.thresholds.max_cpu_before_scaling *= 1.0 - self.learning_rate * 0.05;
-            }
-            Outcome::Neutral => {
-                // No adjustment
-            }
-        }
+    pub async fn adjust_thresholds(&mut self, feedback: &DecisionFeedback) {
+        self.policy.lock().await.adjust(feedback);
+    }
 
-        // Ensure thresholds stay within reasonable bounds
-        self.thresholds.min_health_for_expansion =
-            self.thresholds.min_health_for_expansion.clamp(0.5, 0.95);
-        self.thresholds.max_cpu_before_scaling =
-            self.thresholds.max_cpu_before_scaling.clamp(50.0, 90.0);
+    /// Learn from the outcome of a decision and record it in the journal
+    pub async fn record_feedback(&mut self, feedback: &DecisionFeedback) {
+        self.adjust_thresholds(feedback).await;
+        self.journal.record_outcome(feedback);
     }
 
-    pub fn get_decision_history(&self) -> &[(DateTime<Utc>, Decision)] {
+    pub fn get_decision_history(&self) -> &BoundedHistory<Decision> {
         &self.decision_history
     }
 }
@@ -0,0 +1,539 @@
+use crate::decision_maker::{
+    Decision, DecisionContext, DecisionFeedback, Outcome, Priority, RecoveryAction,
+};
+use anyhow::Result;
+use common::{AppEvent, CorrelationId, DecisionPolicyKind, EventBus, MarketRegime, Topic};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
+
+/// How long the LLM-advised policy waits for the reasoning engine
+const LLM_ADVICE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Market risk above which the rule-based policy does not expand; a risk of 0.85
+/// corresponds to a sentiment of -0.7.
+const BEARISH_RISK_LEVEL: f64 = 0.85;
+
+/// Strategy the decision maker uses to turn a context into a decision
+#[async_trait::async_trait]
+pub trait DecisionPolicy: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn decide(&mut self, context: &DecisionContext) -> Result<Decision>;
+
+    /// Learn from the outcome of an earlier decision
+    fn adjust(&mut self, _feedback: &DecisionFeedback) {}
+}
+
+/// Build the policy for `kind`; the LLM-advised policy talks to the reasoning engine over `bus`
+pub fn build_policy(kind: DecisionPolicyKind, bus: &EventBus) -> Box<dyn DecisionPolicy> {
+    match kind {
+        DecisionPolicyKind::RuleBased => Box::new(RuleBasedPolicy::new()),
+        DecisionPolicyKind::CostMinimizing => Box::new(CostMinimizingPolicy),
+        DecisionPolicyKind::AggressiveExpansion => Box::new(AggressiveExpansionPolicy::new()),
+        DecisionPolicyKind::LlmAdvised => Box::new(LlmAdvisedPolicy::new(bus.clone())),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DecisionThresholds {
+    min_health_for_expansion: f64,
+    max_cpu_before_scaling: f64,
+    max_memory_before_scaling: f64,
+    #[allow(dead_code)]
+    min_nodes_required: usize,
+    max_nodes_allowed: usize,
+    failure_tolerance: f64,
+}
+
+impl Default for DecisionThresholds {
+    fn default() -> Self {
+        Self {
+            min_health_for_expansion: 0.8,
+            max_cpu_before_scaling: 75.0,
+            max_memory_before_scaling: 80.0,
+            min_nodes_required: 1,
+            max_nodes_allowed: 10,
+            failure_tolerance: 0.3,
+        }
+    }
+}
+
+/// Threshold rules that relax after successes and tighten after failures
+#[derive(Debug, Clone)]
+pub struct RuleBasedPolicy {
+    thresholds: DecisionThresholds,
+    learning_rate: f64,
+    expansion_priority: Priority,
+}
+
+impl Default for RuleBasedPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RuleBasedPolicy {
+    pub fn new() -> Self {
+        Self {
+            thresholds: DecisionThresholds::default(),
+            learning_rate: 0.1,
+            expansion_priority: Priority::Normal,
+        }
+    }
+
+    fn evaluate(&self, context: &DecisionContext) -> Decision {
+        // 1. Check for critical failures first
+        if let Some(decision) = self.check_failures(context) {
+            return decision;
+        }
+
+        // 2. Check resource pressure
+        if let Some(decision) = self.check_resource_pressure(context) {
+            return decision;
+        }
+
+        // 3. Check for expansion opportunities
+        if let Some(decision) = self.check_expansion_opportunity(context) {
+            return decision;
+        }
+
+        // 4. Check market conditions for trading decisions
+        if let Some(decision) = self.check_market_conditions(context) {
+            return decision;
+        }
+
+        // 5. Default to monitoring
+        Decision::Monitor {
+            interval_seconds: 30,
+        }
+    }
+
+    fn check_failures(&self, context: &DecisionContext) -> Option<Decision> {
+        if !context.failed_nodes.is_empty() {
+            let failed_node = &context.failed_nodes[0];
+            warn!("Detected failed node: {}", failed_node.id);
+
+            // Decide recovery action based on failure rate
+            let failure_rate = context.failed_nodes.len() as f64
+                / (context.active_nodes.len() + context.failed_nodes.len()) as f64;
+
+            let recovery_action = if failure_rate > self.thresholds.failure_tolerance {
+                RecoveryAction::Failover
+            } else {
+                RecoveryAction::Redeploy
+            };
+
+            return Some(Decision::Recover {
+                failed_node: failed_node.id.clone(),
+                recovery_action,
+            });
+        }
+        None
+    }
+
+    fn check_resource_pressure(&self, context: &DecisionContext) -> Option<Decision> {
+        let metrics = &context.resource_usage;
+
+        if metrics.cpu_percent > self.thresholds.max_cpu_before_scaling
+            || metrics.memory_mb > self.thresholds.max_memory_before_scaling
+        {
+            info!(
+                "Resource pressure detected: CPU {}%, Memory {}MB",
+                metrics.cpu_percent, metrics.memory_mb
+            );
+
+            // Calculate scaling factor
+            let cpu_pressure = metrics.cpu_percent / self.thresholds.max_cpu_before_scaling;
+            let mem_pressure = metrics.memory_mb / self.thresholds.max_memory_before_scaling;
+            let scale_factor = f64::max(cpu_pressure, mem_pressure).min(2.0);
+
+            return Some(Decision::Scale {
+                factor: scale_factor,
+                reason: format!(
+                    "High resource usage: CPU {:.1}%, Memory {:.0}MB",
+                    metrics.cpu_percent, metrics.memory_mb
+                ),
+            });
+        }
+        None
+    }
+
+    fn check_expansion_opportunity(&self, context: &DecisionContext) -> Option<Decision> {
+        // New nodes cost money; do not add them into a strongly bearish or volatile market
+        if let Some(market) = &context.market_conditions {
+            if market.risk_level > BEARISH_RISK_LEVEL || market.regime == MarketRegime::Volatile {
+                debug!(
+                    "Holding off expansion, market sentiment is {:.2} in a {:?} regime",
+                    market.sentiment, market.regime
+                );
+                return None;
+            }
+        }
+        if context.system_health >= self.thresholds.min_health_for_expansion
+            && context.active_nodes.len() < self.thresholds.max_nodes_allowed
+        {
+            // Identify potential target servers for expansion
+            let target_servers = identify_expansion_targets(context);
+
+            if !target_servers.is_empty() {
+                info!("Expansion opportunity identified");
+                return Some(Decision::Deploy {
+                    target_servers,
+                    priority: self.expansion_priority.clone(),
+                    reason: format!(
+                        "System health good ({:.1}%), expanding network",
+                        context.system_health * 100.0
+                    ),
+                });
+            }
+        }
+        None
+    }
+
+    fn check_market_conditions(&self, context: &DecisionContext) -> Option<Decision> {
+        if let Some(market) = &context.market_conditions {
+            if market.opportunity_score > 0.7 && market.risk_level < 0.3 {
+                debug!("Favorable market conditions detected");
+                // Market conditions are good but we return None
+                // as trading decisions are handled by strategy engine
+            }
+        }
+        None
+    }
+}
+
+#[async_trait::async_trait]
+impl DecisionPolicy for RuleBasedPolicy {
+    fn name(&self) -> &'static str {
+        "rule_based"
+    }
+
+    async fn decide(&mut self, context: &DecisionContext) -> Result<Decision> {
+        Ok(self.evaluate(context))
+    }
+
+    fn adjust(&mut self, feedback: &DecisionFeedback) {
+        // Simple learning: adjust thresholds based on outcome
+        match feedback.outcome {
+            Outcome::Success => {
+                // Slightly relax thresholds on success
+                self.thresholds.min_health_for_expansion *= 1.0 - self.learning_rate * 0.1;
+                self.thresholds.max_cpu_before_scaling *= 1.0 + self.learning_rate * 0.05;
+            }
+            Outcome::Failure => {
+                // Tighten thresholds on failure
+                self.thresholds.min_health_for_expansion *= 1.0 + self.learning_rate * 0.1;
+                self.thresholds.max_cpu_before_scaling *= 1.0 - self.learning_rate * 0.05;
+            }
+            Outcome::Neutral => {
+                // No adjustment
+            }
+        }
+
+        // Ensure thresholds stay within reasonable bounds
+        self.thresholds.min_health_for_expansion =
+            self.thresholds.min_health_for_expansion.clamp(0.5, 0.95);
+        self.thresholds.max_cpu_before_scaling =
+            self.thresholds.max_cpu_before_scaling.clamp(50.0, 90.0);
+    }
+}
+
+/// Never pays for a new server unless the fleet is saturated; failed nodes are
+/// restarted in place instead of redeployed elsewhere
+#[derive(Debug, Clone, Default)]
+pub struct CostMinimizingPolicy;
+
+impl CostMinimizingPolicy {
+    const SCALE_CPU_PERCENT: f64 = 90.0;
+}
+
+#[async_trait::async_trait]
+impl DecisionPolicy for CostMinimizingPolicy {
+    fn name(&self) -> &'static str {
+        "cost_minimizing"
+    }
+
+    async fn decide(&mut self, context: &DecisionContext) -> Result<Decision> {
+        if let Some(failed_node) = context.failed_nodes.first() {
+            return Ok(Decision::Recover {
+                failed_node: failed_node.id.clone(),
+                recovery_action: RecoveryAction::Restart,
+            });
+        }
+
+        let cpu = context.resource_usage.cpu_percent;
+        if cpu > Self::SCALE_CPU_PERCENT {
+            return Ok(Decision::Scale {
+                factor: 1.0 + (cpu - Self::SCALE_CPU_PERCENT) / 100.0,
+                reason: format!("CPU saturated at {:.1}%", cpu),
+            });
+        }
+
+        Ok(Decision::Monitor {
+            interval_seconds: 60,
+        })
+    }
+}
+
+/// The rule-based policy with looser thresholds, deploying at high priority
+#[derive(Debug, Clone)]
+pub struct AggressiveExpansionPolicy {
+    rules: RuleBasedPolicy,
+}
+
+impl Default for AggressiveExpansionPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AggressiveExpansionPolicy {
+    pub fn new() -> Self {
+        let mut rules = RuleBasedPolicy::new();
+        rules.thresholds.min_health_for_expansion = 0.5;
+        rules.thresholds.max_cpu_before_scaling = 60.0;
+        rules.thresholds.max_nodes_allowed = 50;
+        rules.expansion_priority = Priority::High;
+        Self { rules }
+    }
+}
+
+#[async_trait::async_trait]
+impl DecisionPolicy for AggressiveExpansionPolicy {
+    fn name(&self) -> &'static str {
+        "aggressive_expansion"
+    }
+
+    async fn decide(&mut self, context: &DecisionContext) -> Result<Decision> {
+        Ok(self.rules.evaluate(context))
+    }
+
+    fn adjust(&mut self, feedback: &DecisionFeedback) {
+        self.rules.adjust(feedback);
+    }
+}
+
+/// Asks the reasoning engine for advice and maps its answer onto a decision.
+/// Failures are still handled by the rules, and so is any answer that is late or
+/// cannot be understood.
+pub struct LlmAdvisedPolicy {
+    bus: EventBus,
+    rules: RuleBasedPolicy,
+    timeout: Duration,
+}
+
+impl LlmAdvisedPolicy {
+    pub fn new(bus: EventBus) -> Self {
+        Self {
+            bus,
+            rules: RuleBasedPolicy::new(),
+            timeout: LLM_ADVICE_TIMEOUT,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn ask(&self, context: &DecisionContext) -> Option<String> {
+        // Subscribe before asking so the answer cannot slip past us
+        let mut rx = self
+            .bus
+            .subscribe_as("decision_policy", &[Topic::Reasoning]);
+        let id = CorrelationId::new();
+        if self
+            .bus
+            .send(AppEvent::LlmQuery(prompt(context), id.clone()))
+            .is_err()
+        {
+            warn!("Nobody is listening for LLM queries");
+            return None;
+        }
+
+        let answer = tokio::time::timeout(self.timeout, async {
+            loop {
+                match rx.recv().await {
+                    // Other components ask the reasoning engine too
+                    Ok(AppEvent::LlmResponse(response, answered)) if answered == id => {
+                        return Some(response)
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+        .await;
+
+        answer.unwrap_or_else(|_| {
+            warn!("Reasoning engine did not answer within {:?}", self.timeout);
+            None
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl DecisionPolicy for LlmAdvisedPolicy {
+    fn name(&self) -> &'static str {
+        "llm_advised"
+    }
+
+    async fn decide(&mut self, context: &DecisionContext) -> Result<Decision> {
+        let fallback = self.rules.evaluate(context);
+        if matches!(fallback, Decision::Recover { .. }) {
+            return Ok(fallback);
+        }
+
+        let advice = match self.ask(context).await {
+            Some(advice) => advice,
+            None => return Ok(fallback),
+        };
+        Ok(interpret_advice(&advice, context).unwrap_or_else(|| {
+            debug!("Could not act on LLM advice: {}", advice);
+            fallback
+        }))
+    }
+
+    fn adjust(&mut self, feedback: &DecisionFeedback) {
+        self.rules.adjust(feedback);
+    }
+}
+
+fn prompt(context: &DecisionContext) -> String {
+    let market = context
+        .market_conditions
+        .as_ref()
+        .map(|m| {
+            format!(
+                "Market sentiment is {:+.2} on a -1 to 1 scale and the market regime is {:?}. ",
+                m.sentiment, m.regime
+            )
+        })
+        .unwrap_or_default();
+    format!(
+        "You manage a fleet of {} healthy and {} failed nodes. System health is {:.0}%, \
+         CPU {:.1}%, memory {:.0}MB, {} pending tasks. \
+         {}Answer with one of DEPLOY, SCALE or WAIT and a short reason.",
+        context.active_nodes.len(),
+        context.failed_nodes.len(),
+        context.system_health * 100.0,
+        context.resource_usage.cpu_percent,
+        context.resource_usage.memory_mb,
+        context.pending_tasks,
+        market,
+    )
+}
+
+fn interpret_advice(advice: &str, context: &DecisionContext) -> Option<Decision> {
+    let upper = advice.to_uppercase();
+    let reason = format!("LLM advice: {}", advice.trim());
+
+    if upper.contains("DEPLOY") {
+        let target_servers = identify_expansion_targets(context);
+        (!target_servers.is_empty()).then_some(Decision::Deploy {
+            target_servers,
+            priority: Priority::Normal,
+            reason,
+        })
+    } else if upper.contains("SCALE") {
+        Some(Decision::Scale {
+            factor: 1.5,
+            reason,
+        })
+    } else if upper.contains("WAIT") {
+        Some(Decision::Wait {
+            duration_seconds: 60,
+        })
+    } else {
+        None
+    }
+}
+
+fn identify_expansion_targets(_context: &DecisionContext) -> Vec<String> {
+    // In a real implementation, this would analyze network topology,
+    // geographic distribution, and available resources
+    vec!["192.168.1.102".to_string(), "192.168.1.103".to_string()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decision_maker::ResourceMetrics;
+    use chrono::Utc;
+
+    fn context(cpu_percent: f64) -> DecisionContext {
+        DecisionContext {
+            timestamp: Utc::now(),
+            system_health: 0.6,
+            resource_usage: ResourceMetrics {
+                cpu_percent,
+                memory_mb: 10.0,
+                disk_gb: 1.0,
+                network_mbps: 0.0,
+            },
+            active_nodes: vec![],
+            failed_nodes: vec![],
+            pending_tasks: 0,
+            market_conditions: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_policies_differ_on_the_same_context() {
+        let bus = EventBus::new(16);
+        let ctx = context(65.0);
+
+        let mut rules = build_policy(DecisionPolicyKind::RuleBased, &bus);
+        assert!(matches!(
+            rules.decide(&ctx).await.unwrap(),
+            Decision::Monitor { .. }
+        ));
+
+        let mut aggressive = build_policy(DecisionPolicyKind::AggressiveExpansion, &bus);
+        assert!(matches!(
+            aggressive.decide(&ctx).await.unwrap(),
+            Decision::Scale { .. }
+        ));
+
+        let mut cheap = build_policy(DecisionPolicyKind::CostMinimizing, &bus);
+        assert!(matches!(
+            cheap.decide(&context(95.0)).await.unwrap(),
+            Decision::Scale { .. }
+        ));
+
+        // Without a reasoning engine the LLM policy falls back to the rules
+        let mut llm = LlmAdvisedPolicy::new(bus).with_timeout(Duration::from_millis(10));
+        assert!(matches!(
+            llm.decide(&ctx).await.unwrap(),
+            Decision::Monitor { .. }
+        ));
+        assert_eq!(
+            interpret_advice("WAIT: markets are quiet", &ctx)
+                .map(|d| matches!(d, Decision::Wait { .. })),
+            Some(true)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_llm_policy_only_takes_the_answer_to_its_query() {
+        let bus = EventBus::new(16);
+        let mut engine = bus.subscribe_as("reasoning_engine", &[Topic::Reasoning]);
+        let responder = bus.clone();
+        tokio::spawn(async move {
+            while let Ok(event) = engine.recv().await {
+                if let AppEvent::LlmQuery(_, id) = event {
+                    let other = CorrelationId::new();
+                    let _ = responder.send(AppEvent::LlmResponse("WAIT: not yours".into(), other));
+                    let _ = responder.send(AppEvent::LlmResponse("SCALE: busy".into(), id));
+                }
+            }
+        });
+
+        let mut llm = LlmAdvisedPolicy::new(bus).with_timeout(Duration::from_secs(5));
+        assert!(matches!(
+            llm.decide(&context(65.0)).await.unwrap(),
+            Decision::Scale { .. }
+        ));
+    }
+}
@@ -1,32 +1,138 @@
 use crate::server_config::{ServerConfig, TargetServer};
 use crate::ssh_deployer::{AuthMethod, SshDeployer};
+use crate::ssh_tunnel::LocalForward;
 use anyhow::Result;
-use chrono::{DateTime, Utc};
+use chrono::Utc;
+use common::{
+    host_port, AppEvent, CancellationToken, EventBus, ReleaseSigner, SshConnectionManager,
+};
+pub use common::{DeploymentState, DeploymentStatus};
+use futures_util::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, RwLock, Semaphore};
 use tracing::{error, info};
 
-/// Deployment status for tracking
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct DeploymentStatus {
+/// Previously deployed binaries, used as bases for delta uploads
+const ARTIFACT_CACHE_DIR: &str = "data/artifacts";
+
+/// Servers a fleet command runs on at the same time
+const MAX_PARALLEL_COMMANDS: usize = 16;
+
+/// Log streams open at the same time, each holding an SSH session and a thread
+pub const MAX_LOG_STREAMS: usize = 8;
+
+/// A command to run across the fleet, see [`DeploymentCommander::execute_on_all`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FleetCommand {
+    /// Shell command; `{{id}}`, `{{name}}`, `{{ip}}`, `{{port}}`, `{{username}}`
+    /// and `{{remote_path}}` are replaced with each server's values. Other
+    /// `{{...}}`, such as Docker's `{{.Names}}`, are left as written
+    pub command: String,
+    /// Only run on enabled servers carrying this tag
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// Give up on a server after this long, defaults to the configured command timeout
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
+}
+
+impl FleetCommand {
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout_seconds = Some(timeout.as_secs());
+        self
+    }
+}
+
+/// Outcome of a fleet command on one server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandResult {
     pub server_id: String,
-    pub ip: String,
-    pub status: DeploymentState,
-    pub last_attempt: Option<DateTime<Utc>>,
-    pub last_success: Option<DateTime<Utc>>,
-    pub error_message: Option<String>,
+    /// The command as run, with the server's values filled in
+    pub command: String,
+    /// `None` when the command could not be run, see `error`
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl CommandResult {
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == Some(0)
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum DeploymentState {
-    NotDeployed,
-    Deploying,
-    Running,
-    Failed,
-    Stopped,
+/// Fill a fleet command template with a server's values
+fn render_command(template: &str, server: &TargetServer) -> String {
+    let vars = BTreeMap::from([
+        ("id", server.id.clone()),
+        ("name", server.name.clone()),
+        ("ip", server.ip.clone()),
+        ("port", server.port.to_string()),
+        ("username", server.username.clone()),
+        ("remote_path", server.remote_path.clone()),
+    ]);
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let value = after
+            .find("}}")
+            .and_then(|end| Some((vars.get(after[..end].trim())?, end)));
+        match value {
+            Some((value, end)) => {
+                output.push_str(&rest[..start]);
+                output.push_str(value);
+                rest = &after[end + 2..];
+            }
+            None => {
+                output.push_str(&rest[..start + 2]);
+                rest = after;
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Live log lines from a remote server, see [`DeploymentCommander::stream_logs`]
+pub struct LogStream {
+    receiver: mpsc::Receiver<Result<String>>,
+}
+
+impl LogStream {
+    /// Wait for the next log line; `None` once the remote stream has ended
+    pub async fn next_line(&mut self) -> Option<Result<String>> {
+        self.receiver.recv().await
+    }
+}
+
+impl Stream for LogStream {
+    type Item = Result<String>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
 }
 
 /// High-level deployment commander that orchestrates deployments
@@ -35,6 +141,17 @@ pub struct DeploymentCommander {
     deployment_status: Arc<RwLock<HashMap<String, DeploymentStatus>>>,
     binary_path: PathBuf,
     config_files: Vec<PathBuf>,
+    in_flight: Arc<RwLock<HashMap<String, CancellationToken>>>,
+    events: Option<EventBus>,
+    signer: Option<ReleaseSigner>,
+    pool: SshConnectionManager,
+    log_streams: Arc<Semaphore>,
+    /// Where deployed agents find the primary, see [`SshDeployer::with_primary_address`]
+    primary_address: Option<String>,
+    /// Monitoring API port of deployed agents, see [`SshDeployer::with_api_port`]
+    api_port: Option<u16>,
+    /// Loopback forwards to servers behind a bastion, by server id and port
+    forwards: Arc<RwLock<HashMap<(String, u16), LocalForward>>>,
 }
 
 impl DeploymentCommander {
@@ -52,6 +169,8 @@ impl DeploymentCommander {
                     retry_delay_seconds: 5,
                     connection_timeout_seconds: 30,
                     deployment_timeout_seconds: 300,
+                    read_timeout_seconds: 60,
+                    command_timeout_seconds: 300,
                 },
                 deployment_strategy: crate::server_config::DeploymentStrategy {
                     strategy_type: "sequential".to_string(),
@@ -67,6 +186,11 @@ impl DeploymentCommander {
                 },
             });
 
+        Self::with_config(binary_path, config)
+    }
+
+    /// Create a deployment commander from an already loaded configuration
+    pub fn with_config(binary_path: PathBuf, config: ServerConfig) -> Self {
         let mut deployment_status = HashMap::new();
         for server in &config.target_servers {
             deployment_status.insert(
@@ -82,14 +206,64 @@ impl DeploymentCommander {
             );
         }
 
+        let pool = SshConnectionManager::new(Duration::from_secs(
+            config.ssh_config.keepalive_interval_seconds,
+        ));
         Self {
             config: Arc::new(RwLock::new(config)),
             deployment_status: Arc::new(RwLock::new(deployment_status)),
             binary_path,
             config_files: vec![PathBuf::from("config/target_servers.json")],
+            in_flight: Arc::new(RwLock::new(HashMap::new())),
+            events: None,
+            signer: None,
+            pool,
+            log_streams: Arc::new(Semaphore::new(MAX_LOG_STREAMS)),
+            primary_address: None,
+            api_port: None,
+            forwards: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Publish `AppEvent::DeploymentStatusChanged` whenever a server changes state
+    /// and `AppEvent::CircuitOpen` when a server's SSH connections keep failing
+    pub fn with_event_bus(mut self, bus: EventBus) -> Self {
+        self.pool = self.pool.with_event_bus(bus.clone());
+        self.events = Some(bus);
+        self
+    }
+
+    /// Sign every deployment and verify it on the server before it starts
+    pub fn with_signer(mut self, signer: ReleaseSigner) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    async fn update_status(&self, server_id: &str, update: impl FnOnce(&mut DeploymentStatus)) {
+        let changed = {
+            let mut status = self.deployment_status.write().await;
+            status.get_mut(server_id).map(|s| {
+                update(s);
+                s.clone()
+            })
+        };
+        if let (Some(changed), Some(bus)) = (changed, &self.events) {
+            let _ = bus.publish(AppEvent::DeploymentStatusChanged(changed));
         }
     }
 
+    /// Hand deployed agents the address of the primary they report to
+    pub fn with_primary_address(mut self, address: impl Into<String>) -> Self {
+        self.primary_address = Some(address.into());
+        self
+    }
+
+    /// Monitoring API port the deployed agents serve on
+    pub fn with_api_port(mut self, port: u16) -> Self {
+        self.api_port = Some(port);
+        self
+    }
+
     /// Deploy to a specific server by ID
     pub async fn deploy_to_server(&self, server_id: &str) -> Result<()> {
         let config = self.config.read().await;
@@ -151,20 +325,47 @@ impl DeploymentCommander {
     }
 
     /// Internal deployment logic
+    #[tracing::instrument(
+        name = "deployment",
+        skip_all,
+        fields(server_id = %server.id, host = %server.ip)
+    )]
     async fn deploy_to_target(&self, server: TargetServer) -> Result<()> {
         info!("Starting deployment to {} ({})", server.name, server.ip);
 
         // Update status to deploying
-        {
-            let mut status = self.deployment_status.write().await;
-            if let Some(s) = status.get_mut(&server.id) {
-                s.status = DeploymentState::Deploying;
-                s.last_attempt = Some(Utc::now());
-            }
-        }
+        self.update_status(&server.id, |s| {
+            s.status = DeploymentState::Deploying;
+            s.last_attempt = Some(Utc::now());
+        })
+        .await;
+
+        // Register a cancellation token, fired by cancel_deployment or the deployment timeout
+        let cancel = CancellationToken::new();
+        self.in_flight
+            .write()
+            .await
+            .insert(server.id.clone(), cancel.clone());
+        let deployment_timeout = Duration::from_secs(
+            self.config
+                .read()
+                .await
+                .default_settings
+                .deployment_timeout_seconds,
+        );
+        let timer = {
+            let cancel = cancel.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(deployment_timeout).await;
+                cancel.cancel();
+            })
+        };
 
         // Create SSH deployer
-        let mut deployer = SshDeployer::new();
+        let mut deployer = self
+            .new_deployer(&server)
+            .await?
+            .with_cancellation(cancel.clone());
 
         // Determine authentication method
         let auth = match server.auth_method {
@@ -184,41 +385,66 @@ impl DeploymentCommander {
             },
         };
 
-        // Perform deployment
-        let result = deployer.full_deploy(
-            &server.ip,
-            server.port,
-            &server.username,
-            auth,
-            &self.binary_path,
-            &server.remote_path,
-            Some(self.config_files.clone()),
-            true, // Setup systemd service
-        );
+        // Perform deployment on a blocking thread so the timer and cancellation keep running
+        let target = server.clone();
+        let binary_path = self.binary_path.clone();
+        let config_files = self.config_files.clone();
+        let span = tracing::Span::current();
+        let result = tokio::task::spawn_blocking(move || {
+            let _span = span.enter();
+            deployer.full_deploy(
+                &target.ip,
+                target.port,
+                &target.username,
+                auth,
+                &binary_path,
+                &target.remote_path,
+                Some(config_files),
+                true, // Setup systemd service
+                target.provision.as_ref(),
+                target.docker_config().as_ref(),
+            )
+        })
+        .await
+        .unwrap_or_else(|e| Err(anyhow::anyhow!("Deployment task failed: {}", e)));
+
+        timer.abort();
+        self.in_flight.write().await.remove(&server.id);
+        let result = match result {
+            Err(e) if cancel.is_cancelled() => Err(e.context(format!(
+                "Deployment to {} was cancelled or exceeded {:?}",
+                server.name, deployment_timeout
+            ))),
+            other => other,
+        };
 
         // Update status based on result
-        {
-            let mut status = self.deployment_status.write().await;
-            if let Some(s) = status.get_mut(&server.id) {
-                match &result {
-                    Ok(_) => {
-                        s.status = DeploymentState::Running;
-                        s.last_success = Some(Utc::now());
-                        s.error_message = None;
-                        info!("Successfully deployed to {} ({})", server.name, server.ip);
-                    }
-                    Err(e) => {
-                        s.status = DeploymentState::Failed;
-                        s.error_message = Some(e.to_string());
-                        error!("Failed to deploy to {} ({}): {}", server.name, server.ip, e);
-                    }
-                }
-            }
+        match &result {
+            Ok(_) => info!("Successfully deployed to {} ({})", server.name, server.ip),
+            Err(e) => error!("Failed to deploy to {} ({}): {}", server.name, server.ip, e),
         }
+        self.update_status(&server.id, |s| match &result {
+            Ok(_) => {
+                s.status = DeploymentState::Running;
+                s.last_success = Some(Utc::now());
+                s.error_message = None;
+            }
+            Err(e) => {
+                s.status = DeploymentState::Failed;
+                s.error_message = Some(e.to_string());
+            }
+        })
+        .await;
 
         result
     }
 
+    /// Authenticated SSH sessions reused across deployments, status checks and
+    /// commands; share it with monitors to reuse their sessions too
+    pub fn connection_pool(&self) -> SshConnectionManager {
+        self.pool.clone()
+    }
+
     /// Check status of a deployed server
     pub async fn check_server_status(&self, server_id: &str) -> Result<bool> {
         let config = self.config.read().await;
@@ -230,7 +456,7 @@ impl DeploymentCommander {
             .clone();
         drop(config);
 
-        let mut deployer = SshDeployer::new();
+        let mut deployer = self.new_deployer(&server).await?;
 
         // Connect
         match server.auth_method {
@@ -280,7 +506,7 @@ impl DeploymentCommander {
             .clone();
         drop(config);
 
-        let mut deployer = SshDeployer::new();
+        let mut deployer = self.new_deployer(&server).await?;
 
         // Connect
         match server.auth_method {
@@ -319,8 +545,17 @@ impl DeploymentCommander {
         deployer.get_logs(&server.remote_path, lines)
     }
 
-    /// Stop kernel on a server
-    pub async fn stop_server(&self, server_id: &str) -> Result<()> {
+    /// Follow the kernel log of a server live
+    ///
+    /// The SSH session runs on a blocking thread; it is torn down within a poll
+    /// interval of the returned stream being dropped. At most [`MAX_LOG_STREAMS`]
+    /// streams are open at a time.
+    pub async fn stream_logs(&self, server_id: &str) -> Result<LogStream> {
+        let permit = self
+            .log_streams
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| anyhow::anyhow!("{} log streams are already open", MAX_LOG_STREAMS))?;
         let config = self.config.read().await;
         let server = config
             .target_servers
@@ -330,9 +565,98 @@ impl DeploymentCommander {
             .clone();
         drop(config);
 
-        let mut deployer = SshDeployer::new();
+        let deployer = self.new_deployer(&server).await?;
+        let (tx, rx) = mpsc::channel(256);
+        let (ready_tx, ready_rx) = oneshot::channel();
 
-        // Connect
+        tokio::task::spawn_blocking(move || {
+            let deployer = match Self::connect(deployer, &server) {
+                Ok(deployer) => {
+                    let _ = ready_tx.send(Ok(()));
+                    deployer
+                }
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                }
+            };
+
+            let _permit = permit;
+            let result = deployer.stream_logs(&server.remote_path, |line| match line {
+                Some(line) => tx.blocking_send(Ok(line)).is_ok(),
+                None => !tx.is_closed(),
+            });
+            if let Err(e) = result {
+                let _ = tx.blocking_send(Err(e));
+            }
+        });
+
+        // Surface connection errors to the caller instead of through the stream
+        ready_rx
+            .await
+            .map_err(|_| anyhow::anyhow!("Log streaming task for {} ended", server_id))??;
+
+        Ok(LogStream { receiver: rx })
+    }
+
+    /// Explicitly trust the current host key of a server
+    ///
+    /// Needed before deploying to a new server when strict host key checking is
+    /// enabled, or after a server's key has legitimately changed.
+    pub async fn trust_host(&self, server_id: &str) -> Result<String> {
+        let config = self.config.read().await;
+        let server = config
+            .target_servers
+            .iter()
+            .find(|s| s.id == server_id)
+            .ok_or_else(|| anyhow::anyhow!("Server {} not found", server_id))?
+            .clone();
+        drop(config);
+
+        let mut deployer = self.new_deployer(&server).await?;
+        deployer.trust_host_key(&server.ip, server.port)
+    }
+
+    /// Abort an in-flight deployment; returns false if none is running for the server
+    pub async fn cancel_deployment(&self, server_id: &str) -> bool {
+        match self.in_flight.read().await.get(server_id) {
+            Some(cancel) => {
+                info!("Cancelling deployment to {}", server_id);
+                cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// SSH deployer for `server` honouring the configured host key policy and
+    /// timeouts, going through the server's bastion if it has one
+    async fn new_deployer(&self, server: &TargetServer) -> Result<SshDeployer> {
+        let config = self.config.read().await;
+        let mut deployer = SshDeployer::new()
+            .with_strict_host_key_checking(config.ssh_config.strict_host_key_checking)
+            .with_timeouts(config.default_settings.ssh_timeouts())
+            .with_artifact_cache(PathBuf::from(ARTIFACT_CACHE_DIR))
+            .with_connection_pool(self.pool.clone())
+            .with_resource_profile(server.resource_profile)
+            .with_agent_id(&server.id);
+        if let Some(signer) = &self.signer {
+            deployer = deployer.with_signer(signer.clone());
+        }
+        if let Some(address) = &self.primary_address {
+            deployer = deployer.with_primary_address(address);
+        }
+        if let Some(port) = self.api_port {
+            deployer = deployer.with_api_port(port);
+        }
+        if let Some(jump) = &server.proxy_jump {
+            deployer = deployer.with_proxy_jump(jump.jump_host()?);
+        }
+        Ok(deployer)
+    }
+
+    /// Open an authenticated SSH session to a server
+    pub fn connect(mut deployer: SshDeployer, server: &TargetServer) -> Result<SshDeployer> {
         match server.auth_method {
             crate::server_config::AuthMethod::Password => {
                 let password = server
@@ -365,75 +689,69 @@ impl DeploymentCommander {
             }
         }
 
-        // Stop kernel
-        deployer.stop_kernel()?;
-
-        // Update status
-        {
-            let mut status = self.deployment_status.write().await;
-            if let Some(s) = status.get_mut(&server.id) {
-                s.status = DeploymentState::Stopped;
-            }
-        }
-
-        Ok(())
+        Ok(deployer)
     }
 
-    /// Get deployment status for all servers
-    pub async fn get_deployment_status(&self) -> HashMap<String, DeploymentStatus> {
-        self.deployment_status.read().await.clone()
+    /// The configured server with ID `server_id`
+    pub(crate) async fn find_server(&self, server_id: &str) -> Result<TargetServer> {
+        self.config
+            .read()
+            .await
+            .target_servers
+            .iter()
+            .find(|s| s.id == server_id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Server {} not found", server_id))
     }
 
-    /// Reload configuration from file
-    pub async fn reload_config(&mut self) -> Result<()> {
-        let new_config = ServerConfig::from_file(Path::new("config/target_servers.json"))?;
-
-        // Update deployment status for new servers
-        let mut status = self.deployment_status.write().await;
-        for server in &new_config.target_servers {
-            if !status.contains_key(&server.id) {
-                status.insert(
-                    server.id.clone(),
-                    DeploymentStatus {
-                        server_id: server.id.clone(),
-                        ip: server.ip.clone(),
-                        status: DeploymentState::NotDeployed,
-                        last_attempt: None,
-                        last_success: None,
-                        error_message: None,
-                    },
-                );
-            }
+    /// `host:port` to reach `port` on the agent at `ip` over TCP, such as its HTTP
+    /// API. Servers behind a bastion are reached through a loopback forward that
+    /// tunnels every connection; anything else, including agents that are not
+    /// configured target servers, is dialled directly.
+    pub async fn tcp_address(&self, agent_id: &str, ip: &str, port: u16) -> Result<String> {
+        let key = (agent_id.to_string(), port);
+        if let Some(forward) = self.forwards.read().await.get(&key) {
+            return Ok(forward.local_addr().to_string());
         }
-        drop(status);
+        let server = match self.find_server(agent_id).await {
+            Ok(server) if server.proxy_jump.is_some() => server,
+            _ => return Ok(host_port(ip, port)),
+        };
 
-        *self.config.write().await = new_config;
-        info!("Configuration reloaded");
-        Ok(())
+        let deployer = std::sync::Mutex::new(self.new_deployer(&server).await?);
+        let host = server.ip.clone();
+        let forward = LocalForward::bind(move || {
+            deployer
+                .lock()
+                .map_err(|_| anyhow::anyhow!("SSH deployer lock poisoned"))?
+                .open_stream(&host, port)
+        })?;
+        info!(
+            "Forwarding {} to {} through its bastion",
+            forward.local_addr(),
+            host_port(&server.ip, port)
+        );
+        let address = forward.local_addr().to_string();
+        self.forwards.write().await.entry(key).or_insert(forward);
+        Ok(address)
     }
 
-    /// Execute a command on all deployed servers
-    pub async fn execute_on_all(&self, command: &str) -> Result<Vec<(String, Result<String>)>> {
-        let config = self.config.read().await;
-        let servers: Vec<_> = config
-            .target_servers
-            .iter()
-            .filter(|s| s.enabled)
-            .cloned()
-            .collect();
-        drop(config);
-
-        let mut results = Vec::new();
-        for server in servers {
-            let result = self.execute_on_server(&server.id, command).await;
-            results.push((server.id.clone(), result));
-        }
+    /// An SSH session to `server`, opened on a blocking thread
+    pub(crate) async fn open_session(&self, server: &TargetServer) -> Result<SshDeployer> {
+        let deployer = self.new_deployer(server).await?;
+        let target = server.clone();
+        tokio::task::spawn_blocking(move || Self::connect(deployer, &target))
+            .await
+            .unwrap_or_else(|e| Err(anyhow::anyhow!("Connect task failed: {}", e)))
+    }
 
-        Ok(results)
+    /// Record a change made to a server outside of a deployment
+    pub(crate) async fn set_state(&self, server_id: &str, state: DeploymentState) {
+        self.update_status(server_id, |s| s.status = state).await;
     }
 
-    /// Execute a command on a specific server
-    async fn execute_on_server(&self, server_id: &str, command: &str) -> Result<String> {
+    /// Stop kernel on a server
+    pub async fn stop_server(&self, server_id: &str) -> Result<()> {
         let config = self.config.read().await;
         let server = config
             .target_servers
@@ -443,7 +761,7 @@ impl DeploymentCommander {
             .clone();
         drop(config);
 
-        let mut deployer = SshDeployer::new();
+        let mut deployer = self.new_deployer(&server).await?;
 
         // Connect
         match server.auth_method {
@@ -478,7 +796,184 @@ impl DeploymentCommander {
             }
         }
 
-        // Execute command
-        deployer.execute_command(command)
+        // Stop kernel
+        deployer.stop_kernel()?;
+
+        // Update status
+        self.update_status(&server.id, |s| s.status = DeploymentState::Stopped)
+            .await;
+
+        Ok(())
+    }
+
+    /// Get deployment status for all servers
+    pub async fn get_deployment_status(&self) -> HashMap<String, DeploymentStatus> {
+        self.deployment_status.read().await.clone()
+    }
+
+    /// Reload configuration from file
+    pub async fn reload_config(&mut self) -> Result<()> {
+        let new_config = ServerConfig::from_file(Path::new("config/target_servers.json"))?;
+
+        // Update deployment status for new servers
+        let mut status = self.deployment_status.write().await;
+        for server in &new_config.target_servers {
+            if !status.contains_key(&server.id) {
+                status.insert(
+                    server.id.clone(),
+                    DeploymentStatus {
+                        server_id: server.id.clone(),
+                        ip: server.ip.clone(),
+                        status: DeploymentState::NotDeployed,
+                        last_attempt: None,
+                        last_success: None,
+                        error_message: None,
+                    },
+                );
+            }
+        }
+        drop(status);
+
+        *self.config.write().await = new_config;
+        info!("Configuration reloaded");
+        Ok(())
+    }
+
+    /// Run a command on every enabled server, or those carrying `request.tag`,
+    /// concurrently over pooled sessions
+    ///
+    /// A server that cannot be reached or times out is reported in its result
+    /// rather than failing the whole run.
+    pub async fn execute_on_all(&self, request: &FleetCommand) -> Result<Vec<CommandResult>> {
+        let config = self.config.read().await;
+        let servers: Vec<TargetServer> = match &request.tag {
+            Some(tag) => config.get_servers_by_tag(tag),
+            None => config.get_enabled_servers(),
+        }
+        .into_iter()
+        .cloned()
+        .collect();
+        let timeout = request
+            .timeout_seconds
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| config.default_settings.ssh_timeouts().command);
+        drop(config);
+
+        let mut results: Vec<CommandResult> = futures_util::stream::iter(servers)
+            .map(|server| self.execute_on_server(server, &request.command, timeout))
+            .buffer_unordered(MAX_PARALLEL_COMMANDS)
+            .collect()
+            .await;
+        results.sort_by(|a, b| a.server_id.cmp(&b.server_id));
+        Ok(results)
+    }
+
+    /// Run a command on one server; failures end up in the result
+    async fn execute_on_server(
+        &self,
+        server: TargetServer,
+        template: &str,
+        timeout: Duration,
+    ) -> CommandResult {
+        let started = std::time::Instant::now();
+        let command = render_command(template, &server);
+        let output = match self.new_deployer(&server).await {
+            Ok(deployer) => {
+                let command = command.clone();
+                let target = server.clone();
+                tokio::task::spawn_blocking(move || {
+                    Self::connect(deployer, &target)?.execute_captured(&command, timeout)
+                })
+                .await
+                .unwrap_or_else(|e| Err(anyhow::anyhow!("Command task failed: {}", e)))
+            }
+            Err(e) => Err(e),
+        };
+
+        let mut result = CommandResult {
+            server_id: server.id.clone(),
+            command,
+            exit_code: None,
+            stdout: String::new(),
+            stderr: String::new(),
+            duration_ms: 0,
+            error: None,
+        };
+        match output {
+            Ok(output) => {
+                result.exit_code = Some(output.exit_status);
+                result.stdout = output.stdout;
+                result.stderr = output.stderr;
+            }
+            Err(e) => result.error = Some(format!("{:#}", e)),
+        }
+        result.duration_ms = started.elapsed().as_millis() as u64;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fleet_commands_only_fill_in_server_values() {
+        let server = TargetServer::new(
+            "replica-1".to_string(),
+            "Replica".to_string(),
+            "10.0.0.2".to_string(),
+            "ubuntu".to_string(),
+        );
+        assert_eq!(
+            render_command("tail {{ remote_path }}/logs/aurelia.log # {{id}}", &server),
+            "tail /home/ubuntu/aurelia/logs/aurelia.log # replica-1"
+        );
+        // Docker's own templates and stray braces reach the shell untouched
+        assert_eq!(
+            render_command("docker ps --format '{{.Names}}' # {{ip}} {{", &server),
+            "docker ps --format '{{.Names}}' # 10.0.0.2 {{"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_servers_behind_a_bastion_are_reached_through_a_forward() {
+        use crate::server_config::{AuthMethod as ServerAuth, ProxyJump};
+
+        let commander = DeploymentCommander::new(PathBuf::from("aurelia"));
+        let direct = TargetServer::new(
+            "replica-1".to_string(),
+            "Replica".to_string(),
+            "2001:db8::2".to_string(),
+            "ubuntu".to_string(),
+        );
+        let mut tunnelled = TargetServer::new(
+            "replica-2".to_string(),
+            "Replica".to_string(),
+            "10.0.0.3".to_string(),
+            "ubuntu".to_string(),
+        );
+        tunnelled.proxy_jump = Some(ProxyJump {
+            host: "bastion.example.com".to_string(),
+            port: 22,
+            username: "jump".to_string(),
+            ssh_key_path: None,
+            password_base64: None,
+            auth_method: ServerAuth::Key,
+        });
+        commander.config.write().await.target_servers = vec![direct, tunnelled];
+
+        let address = |id: &'static str, ip: &'static str| commander.tcp_address(id, ip, 8080);
+        assert_eq!(
+            address("replica-1", "2001:db8::2").await.unwrap(),
+            "[2001:db8::2]:8080"
+        );
+        assert_eq!(
+            address("unknown", "10.0.0.9").await.unwrap(),
+            "10.0.0.9:8080"
+        );
+        let forwarded = address("replica-2", "10.0.0.3").await.unwrap();
+        assert!(forwarded.starts_with("127.0.0.1:"));
+        // One forward per server and port
+        assert_eq!(address("replica-2", "10.0.0.3").await.unwrap(), forwarded);
     }
 }
@@ -0,0 +1,334 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Where the replicator keeps its deployment jobs, relative to the deployment directory
+pub const DEPLOYMENT_QUEUE_PATH: &str = "data/deployment_queue.json";
+
+/// 部署重试与熔断策略，写在复制策略的 `retry` 字段中
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// 第一次重试前的等待时间，之后每次翻倍
+    pub base_delay_seconds: u64,
+    pub max_delay_seconds: u64,
+    /// 等待时间随机浮动的比例，0.2 表示 ±20%
+    pub jitter: f64,
+    /// 同一目标连续失败达到该次数后隔离
+    pub quarantine_after_failures: u32,
+    pub quarantine_seconds: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            base_delay_seconds: 5,
+            max_delay_seconds: 600,
+            jitter: 0.2,
+            quarantine_after_failures: 5,
+            quarantine_seconds: 3600,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 第 `attempts` 次失败后到下一次尝试的等待时间
+    pub fn backoff(&self, attempts: u32, rng: &mut impl Rng) -> Duration {
+        let exponent = attempts.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay_seconds
+            .saturating_mul(1u64 << exponent)
+            .min(self.max_delay_seconds) as f64;
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = if jitter > 0.0 {
+            rng.gen_range(1.0 - jitter..=1.0 + jitter)
+        } else {
+            1.0
+        };
+        Duration::milliseconds((delay * factor * 1000.0) as i64)
+    }
+}
+
+/// A pending deployment to one target
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentJob {
+    pub target: String,
+    pub attempts: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    /// Queued by an emergency replication, attempted before other due jobs
+    #[serde(default)]
+    pub urgent: bool,
+}
+
+/// Failure history of one target, kept across jobs for the circuit breaker
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TargetHealth {
+    pub consecutive_failures: u32,
+    pub quarantined_until: Option<DateTime<Utc>>,
+}
+
+/// What happened to a job after a failed attempt
+#[derive(Debug, Clone, PartialEq)]
+pub enum FailureOutcome {
+    /// Retried no earlier than the given time
+    Retry(DateTime<Utc>),
+    /// Attempts exhausted, the job was dropped
+    GaveUp,
+    /// The target failed too often and is skipped until the given time
+    Quarantined(DateTime<Utc>),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PersistedQueue {
+    jobs: Vec<DeploymentJob>,
+    targets: BTreeMap<String, TargetHealth>,
+}
+
+/// Deployment jobs with exponential backoff and a per-target circuit breaker
+///
+/// Each replication cycle attempts the jobs returned by [`DeploymentQueue::due`]
+/// once; failures are rescheduled instead of sleeping inline, so one unreachable
+/// server no longer stalls the cycle.
+#[derive(Debug, Default)]
+pub struct DeploymentQueue {
+    jobs: Vec<DeploymentJob>,
+    targets: BTreeMap<String, TargetHealth>,
+    store: Option<PathBuf>,
+}
+
+impl DeploymentQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A queue that saves its state to `path` and restores whatever was saved there
+    pub fn with_persistence(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let persisted: PersistedQueue = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?
+        } else {
+            PersistedQueue::default()
+        };
+        info!(
+            "Restored {} deployment jobs from {:?}",
+            persisted.jobs.len(),
+            path
+        );
+
+        Ok(Self {
+            jobs: persisted.jobs,
+            targets: persisted.targets,
+            store: Some(path),
+        })
+    }
+
+    pub fn jobs(&self) -> &[DeploymentJob] {
+        &self.jobs
+    }
+
+    pub fn contains(&self, target: &str) -> bool {
+        self.jobs.iter().any(|job| job.target == target)
+    }
+
+    pub fn is_quarantined(&self, target: &str, now: DateTime<Utc>) -> bool {
+        self.targets
+            .get(target)
+            .and_then(|health| health.quarantined_until)
+            .is_some_and(|until| until > now)
+    }
+
+    /// Queue a deployment to `target` unless one is queued or the target is quarantined
+    pub fn enqueue(&mut self, target: &str, now: DateTime<Utc>) -> bool {
+        if self.contains(target) || self.is_quarantined(target, now) {
+            return false;
+        }
+        self.jobs.push(DeploymentJob {
+            target: target.to_string(),
+            attempts: 0,
+            next_attempt_at: now,
+            last_error: None,
+            urgent: false,
+        });
+        self.persist();
+        true
+    }
+
+    /// Queue `target` ahead of other jobs, or make its queued job due now and urgent
+    ///
+    /// Quarantined targets are still refused.
+    pub fn enqueue_urgent(&mut self, target: &str, now: DateTime<Utc>) -> bool {
+        if self.is_quarantined(target, now) {
+            return false;
+        }
+        match self.jobs.iter_mut().find(|job| job.target == target) {
+            Some(job) => {
+                job.next_attempt_at = job.next_attempt_at.min(now);
+                job.urgent = true;
+            }
+            None => self.jobs.push(DeploymentJob {
+                target: target.to_string(),
+                attempts: 0,
+                next_attempt_at: now,
+                last_error: None,
+                urgent: true,
+            }),
+        }
+        self.persist();
+        true
+    }
+
+    /// Targets whose next attempt is due, urgent jobs first, then oldest schedule first
+    pub fn due(&self, now: DateTime<Utc>) -> Vec<String> {
+        let mut due: Vec<_> = self
+            .jobs
+            .iter()
+            .filter(|job| job.next_attempt_at <= now && !self.is_quarantined(&job.target, now))
+            .collect();
+        due.sort_by_key(|job| (!job.urgent, job.next_attempt_at));
+        due.into_iter().map(|job| job.target.clone()).collect()
+    }
+
+    pub fn remove(&mut self, target: &str) {
+        self.jobs.retain(|job| job.target != target);
+        self.persist();
+    }
+
+    pub fn record_success(&mut self, target: &str) {
+        self.jobs.retain(|job| job.target != target);
+        self.targets.remove(target);
+        self.persist();
+    }
+
+    pub fn record_failure(
+        &mut self,
+        target: &str,
+        error: &str,
+        max_attempts: u32,
+        policy: &RetryPolicy,
+        now: DateTime<Utc>,
+    ) -> FailureOutcome {
+        let health = self.targets.entry(target.to_string()).or_default();
+        health.consecutive_failures += 1;
+
+        let outcome = if health.consecutive_failures >= policy.quarantine_after_failures {
+            let until = now + Duration::seconds(policy.quarantine_seconds as i64);
+            // Half-open after the cooldown: the next failure counts from zero again
+            health.consecutive_failures = 0;
+            health.quarantined_until = Some(until);
+            self.jobs.retain(|job| job.target != target);
+            warn!(
+                "Quarantining {} until {} after repeated failures",
+                target, until
+            );
+            FailureOutcome::Quarantined(until)
+        } else if let Some(job) = self.jobs.iter_mut().find(|job| job.target == target) {
+            job.attempts += 1;
+            job.last_error = Some(error.to_string());
+            if job.attempts >= max_attempts {
+                self.jobs.retain(|job| job.target != target);
+                FailureOutcome::GaveUp
+            } else {
+                job.next_attempt_at = now + policy.backoff(job.attempts, &mut rand::thread_rng());
+                FailureOutcome::Retry(job.next_attempt_at)
+            }
+        } else {
+            FailureOutcome::GaveUp
+        };
+
+        self.persist();
+        outcome
+    }
+
+    fn persist(&self) {
+        if let Err(e) = self.save() {
+            warn!("Failed to persist deployment queue: {}", e);
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.store else {
+            return Ok(());
+        };
+
+        let snapshot = PersistedQueue {
+            jobs: self.jobs.clone(),
+            targets: self.targets.clone(),
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Write then rename so a crash never leaves a truncated queue behind
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&snapshot)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_retries_then_quarantines() {
+        let policy = RetryPolicy {
+            jitter: 0.0,
+            quarantine_after_failures: 3,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(
+            policy.backoff(3, &mut rand::thread_rng()),
+            Duration::seconds(20)
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.json");
+        let mut queue = DeploymentQueue::with_persistence(&path).unwrap();
+        let now = Utc::now();
+
+        assert!(queue.enqueue("10.0.0.1", now));
+        assert!(!queue.enqueue("10.0.0.1", now));
+        assert_eq!(queue.due(now), vec!["10.0.0.1"]);
+
+        let outcome = queue.record_failure("10.0.0.1", "timeout", 5, &policy, now);
+        assert_eq!(outcome, FailureOutcome::Retry(now + Duration::seconds(5)));
+        assert!(queue.due(now).is_empty());
+
+        // State survives a restart
+        let mut queue = DeploymentQueue::with_persistence(&path).unwrap();
+        assert_eq!(queue.jobs()[0].attempts, 1);
+
+        queue.record_failure("10.0.0.1", "timeout", 5, &policy, now);
+        let outcome = queue.record_failure("10.0.0.1", "timeout", 5, &policy, now);
+        assert!(matches!(outcome, FailureOutcome::Quarantined(_)));
+        assert!(queue.jobs().is_empty());
+        assert!(!queue.enqueue("10.0.0.1", now));
+        assert!(queue.enqueue("10.0.0.1", now + Duration::hours(2)));
+    }
+
+    #[test]
+    fn test_urgent_jobs_jump_the_queue_but_not_quarantine() {
+        let policy = RetryPolicy {
+            jitter: 0.0,
+            quarantine_after_failures: 1,
+            ..RetryPolicy::default()
+        };
+        let mut queue = DeploymentQueue::new();
+        let now = Utc::now();
+
+        queue.enqueue("10.0.0.1", now - Duration::seconds(10));
+        queue.enqueue("10.0.0.2", now);
+        assert!(queue.enqueue_urgent("10.0.0.2", now));
+        assert!(queue.enqueue_urgent("10.0.0.3", now));
+        assert_eq!(queue.due(now), vec!["10.0.0.2", "10.0.0.3", "10.0.0.1"]);
+
+        queue.record_failure("10.0.0.3", "timeout", 5, &policy, now);
+        assert!(!queue.enqueue_urgent("10.0.0.3", now));
+        assert_eq!(queue.due(now), vec!["10.0.0.2", "10.0.0.1"]);
+    }
+}
@@ -1,42 +1,24 @@
 use anyhow::Result;
 use chrono::{DateTime, Duration, Utc};
+use common::bounded_history::{BoundedHistory, HISTORY_SPILL_DIR};
+pub use common::{HealthCheck, HealthMetrics, HealthStatus, HealthSummary};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct HealthMetrics {
-    pub timestamp: DateTime<Utc>,
-    pub cpu_usage: f64,
-    pub memory_usage: f64,
-    pub disk_usage: f64,
-    pub network_latency_ms: f64,
-    pub process_count: usize,
-    pub error_rate: f64,
-    pub success_rate: f64,
-    pub uptime_seconds: u64,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum HealthStatus {
-    Healthy,
-    Degraded(String),
-    Critical(String),
-    Failed(String),
-}
+/// A day of samples at the default 30 second interval
+const METRICS_HISTORY_LEN: usize = 2880;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct HealthCheck {
-    pub name: String,
-    pub status: HealthStatus,
-    pub last_check: DateTime<Utc>,
-    pub consecutive_failures: u32,
-    pub details: HashMap<String, String>,
-}
+/// Alert thresholds and check interval, reloaded on `AppEvent::ReloadConfig`
+pub const HEALTH_CONFIG_PATH: &str = "config/health.json";
 
-#[derive(Debug, Clone)]
+/// Usage percentages are compared against the warning and critical levels,
+/// error rates are fractions between 0 and 1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct HealthThresholds {
     pub cpu_warning: f64,
     pub cpu_critical: f64,
@@ -47,6 +29,8 @@ pub struct HealthThresholds {
     pub error_rate_warning: f64,
     pub error_rate_critical: f64,
     pub max_consecutive_failures: u32,
+    /// Seconds between health checks
+    pub check_interval_seconds: u64,
 }
 
 impl Default for HealthThresholds {
@@ -61,18 +45,59 @@ impl Default for HealthThresholds {
             error_rate_warning: 0.05,
             error_rate_critical: 0.1,
             max_consecutive_failures: 3,
+            check_interval_seconds: 30,
+        }
+    }
+}
+
+impl HealthThresholds {
+    /// Load thresholds from `path`, the defaults when it does not exist
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let thresholds: Self = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        thresholds.validate()?;
+        Ok(thresholds)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let levels = [
+            ("cpu", self.cpu_warning, self.cpu_critical, 100.0),
+            ("memory", self.memory_warning, self.memory_critical, 100.0),
+            ("disk", self.disk_warning, self.disk_critical, 100.0),
+            (
+                "error_rate",
+                self.error_rate_warning,
+                self.error_rate_critical,
+                1.0,
+            ),
+        ];
+        for (name, warning, critical, max) in levels {
+            if !(warning > 0.0 && warning < critical && critical <= max) {
+                anyhow::bail!(
+                    "{name}_warning {warning} and {name}_critical {critical} must satisfy 0 < warning < critical <= {max}"
+                );
+            }
+        }
+        if self.max_consecutive_failures == 0 {
+            anyhow::bail!("max_consecutive_failures must be at least 1");
         }
+        if self.check_interval_seconds == 0 {
+            anyhow::bail!("check_interval_seconds must be at least 1");
+        }
+        Ok(())
     }
 }
 
 pub struct HealthMonitor {
-    thresholds: HealthThresholds,
+    thresholds: Arc<RwLock<HealthThresholds>>,
     current_metrics: Arc<RwLock<HealthMetrics>>,
     health_checks: Arc<RwLock<HashMap<String, HealthCheck>>>,
-    metrics_history: Arc<RwLock<Vec<HealthMetrics>>>,
+    metrics_history: Arc<RwLock<BoundedHistory<HealthMetrics>>>,
     #[allow(clippy::type_complexity)]
     alert_callbacks: Arc<RwLock<Vec<Box<dyn Fn(HealthAlert) + Send + Sync>>>>,
-    monitoring_interval: Duration,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -101,15 +126,33 @@ impl Default for HealthMonitor {
 impl HealthMonitor {
     pub fn new() -> Self {
         Self {
-            thresholds: HealthThresholds::default(),
+            thresholds: Arc::new(RwLock::new(HealthThresholds::default())),
             current_metrics: Arc::new(RwLock::new(Self::default_metrics())),
             health_checks: Arc::new(RwLock::new(HashMap::new())),
-            metrics_history: Arc::new(RwLock::new(Vec::new())),
+            metrics_history: Arc::new(RwLock::new(
+                BoundedHistory::new(METRICS_HISTORY_LEN)
+                    .with_max_age(Duration::hours(24))
+                    .with_spill_file(format!("{}/health_metrics.jsonl", HISTORY_SPILL_DIR)),
+            )),
             alert_callbacks: Arc::new(RwLock::new(Vec::new())),
-            monitoring_interval: Duration::seconds(30),
         }
     }
 
+    pub fn with_thresholds(mut self, thresholds: HealthThresholds) -> Self {
+        self.thresholds = Arc::new(RwLock::new(thresholds));
+        self
+    }
+
+    pub async fn thresholds(&self) -> HealthThresholds {
+        self.thresholds.read().await.clone()
+    }
+
+    /// Apply new thresholds from the next check on
+    pub async fn set_thresholds(&self, thresholds: HealthThresholds) {
+        info!(?thresholds, "Health thresholds updated");
+        *self.thresholds.write().await = thresholds;
+    }
+
     fn default_metrics() -> HealthMetrics {
         HealthMetrics {
             timestamp: Utc::now(),
@@ -143,7 +186,8 @@ impl HealthMonitor {
             self.cleanup_history().await;
 
             // Wait for next cycle
-            tokio::time::sleep(self.monitoring_interval.to_std().unwrap()).await;
+            let interval = self.thresholds.read().await.check_interval_seconds;
+            tokio::time::sleep(std::time::Duration::from_secs(interval)).await;
         }
     }
 
@@ -154,7 +198,11 @@ impl HealthMonitor {
         *self.current_metrics.write().await = metrics.clone();
 
         // Add to history
-        self.metrics_history.write().await.push(metrics);
+        let recorded_at = metrics.timestamp;
+        self.metrics_history
+            .write()
+            .await
+            .push_at(recorded_at, metrics);
 
         Ok(())
     }
@@ -228,11 +276,12 @@ impl HealthMonitor {
 
     async fn check_cpu_health(&self, checks: &mut HashMap<String, HealthCheck>) {
         let metrics = self.current_metrics.read().await;
+        let thresholds = self.thresholds.read().await;
         let cpu_usage = metrics.cpu_usage;
 
-        let status = if cpu_usage > self.thresholds.cpu_critical {
+        let status = if cpu_usage > thresholds.cpu_critical {
             HealthStatus::Critical(format!("CPU usage: {:.1}%", cpu_usage))
-        } else if cpu_usage > self.thresholds.cpu_warning {
+        } else if cpu_usage > thresholds.cpu_warning {
             HealthStatus::Degraded(format!("CPU usage: {:.1}%", cpu_usage))
         } else {
             HealthStatus::Healthy
@@ -255,11 +304,12 @@ impl HealthMonitor {
 
     async fn check_memory_health(&self, checks: &mut HashMap<String, HealthCheck>) {
         let metrics = self.current_metrics.read().await;
+        let thresholds = self.thresholds.read().await;
         let memory_usage = metrics.memory_usage;
 
-        let status = if memory_usage > self.thresholds.memory_critical {
+        let status = if memory_usage > thresholds.memory_critical {
             HealthStatus::Critical(format!("Memory usage: {:.1}%", memory_usage))
-        } else if memory_usage > self.thresholds.memory_warning {
+        } else if memory_usage > thresholds.memory_warning {
             HealthStatus::Degraded(format!("Memory usage: {:.1}%", memory_usage))
         } else {
             HealthStatus::Healthy
@@ -282,11 +332,12 @@ impl HealthMonitor {
 
     async fn check_disk_health(&self, checks: &mut HashMap<String, HealthCheck>) {
         let metrics = self.current_metrics.read().await;
+        let thresholds = self.thresholds.read().await;
         let disk_usage = metrics.disk_usage;
 
-        let status = if disk_usage > self.thresholds.disk_critical {
+        let status = if disk_usage > thresholds.disk_critical {
             HealthStatus::Critical(format!("Disk usage: {:.1}%", disk_usage))
-        } else if disk_usage > self.thresholds.disk_warning {
+        } else if disk_usage > thresholds.disk_warning {
             HealthStatus::Degraded(format!("Disk usage: {:.1}%", disk_usage))
         } else {
             HealthStatus::Healthy
@@ -407,11 +458,7 @@ impl HealthMonitor {
     }
 
     async fn cleanup_history(&self) {
-        let mut history = self.metrics_history.write().await;
-
-        // Keep only last 24 hours of metrics
-        let cutoff = Utc::now() - Duration::hours(24);
-        history.retain(|m| m.timestamp > cutoff);
+        self.metrics_history.write().await.prune();
     }
 
     pub async fn get_current_health(&self) -> HealthSummary {
@@ -453,11 +500,3 @@ impl HealthMonitor {
         }
     }
 }
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct HealthSummary {
-    pub status: HealthStatus,
-    pub metrics: HealthMetrics,
-    pub checks: Vec<HealthCheck>,
-    pub timestamp: DateTime<Utc>,
-}
@@ -1,19 +1,49 @@
+pub mod agent_migration;
+pub mod approvals;
 pub mod autonomous_agent;
+pub mod autonomy_config;
+pub mod cloud_provisioner;
+pub mod cluster_registry;
+pub mod cron;
+pub mod decision_journal;
 pub mod decision_maker;
+pub mod decision_policy;
 pub mod deployment_commander;
+pub mod deployment_queue;
 pub mod health_monitor;
+pub mod market_sentiment;
 pub mod recovery_manager;
 pub mod self_replicator;
+pub mod self_updater;
 pub mod server_config;
 pub mod ssh_deployer;
+mod ssh_tunnel;
+pub mod target_probe;
+pub mod task_executors;
 pub mod task_scheduler;
 
+pub use agent_migration::{AgentMigrator, AgentSnapshot};
+pub use approvals::{ApprovalConfig, ApprovalGate, ApprovalKind};
 pub use autonomous_agent::AutonomousAgent;
+pub use autonomy_config::AutonomyConfig;
+pub use cloud_provisioner::{CloudConfig, CloudFleet, CloudProvisioner, HetznerProvisioner};
+pub use cluster_registry::ClusterRegistry;
+pub use decision_journal::{DecisionJournal, DecisionRecord};
 pub use decision_maker::AutonomousDecisionMaker;
-pub use deployment_commander::DeploymentCommander;
-pub use health_monitor::HealthMonitor;
+pub use decision_policy::{build_policy, DecisionPolicy};
+pub use deployment_commander::{CommandResult, DeploymentCommander, FleetCommand, LogStream};
+pub use deployment_queue::{DeploymentQueue, RetryPolicy};
+pub use health_monitor::{HealthMonitor, HealthThresholds};
+pub use market_sentiment::MarketSentiment;
 pub use recovery_manager::RecoveryManager;
-pub use self_replicator::SelfReplicator;
-pub use server_config::{ServerConfig, TargetServer};
-pub use ssh_deployer::{AuthMethod, SshDeployer};
-pub use task_scheduler::TaskScheduler;
+pub use self_replicator::{LineageRecord, ReplicationStrategy, SelfReplicator};
+pub use self_updater::{SelfUpdateConfig, SelfUpdater, StagedUpdate};
+pub use server_config::{
+    DeployMethod, DockerDeployConfig, ProxyJump, ResourceProfile, ServerConfig, TargetServer,
+};
+pub use ssh_deployer::{
+    AuthMethod, CommandOutput, JumpHost, PreflightCheck, PreflightReport, SshDeployer,
+};
+pub use target_probe::{ProbeConfig, TargetProbe, TargetProber};
+pub use task_executors::{ExecutorConfig, HttpCallbackExecutor, ShellCommandExecutor};
+pub use task_scheduler::{DependencyMode, TaskSchedule, TaskScheduler};
@@ -0,0 +1,206 @@
+use crate::decision_maker::MarketConditions;
+use common::{AppEvent, EventReceiver, LagHandler, MarketRegime, SentimentUpdate};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::RwLock;
+
+/// The latest sentiment and regime per symbol, kept up to date from
+/// `AppEvent::SentimentUpdate` and `AppEvent::MarketConditions`.
+#[derive(Clone, Default)]
+pub struct MarketSentiment {
+    latest: Arc<RwLock<HashMap<String, SentimentUpdate>>>,
+    regimes: Arc<RwLock<HashMap<String, MarketConditions>>>,
+}
+
+impl MarketSentiment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record every sentiment update and regime from `rx` until the bus closes.
+    pub async fn follow(&self, mut rx: EventReceiver) {
+        let lag = LagHandler::new("autonomous_agent");
+        loop {
+            match rx.recv().await {
+                Ok(AppEvent::SentimentUpdate(update)) => self.record(update).await,
+                Ok(AppEvent::MarketConditions(conditions)) => self.record_regime(conditions).await,
+                Ok(_) => {}
+                // Each symbol's next update replaces what was lost
+                Err(RecvError::Lagged(n)) => {
+                    lag.lagged(n);
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+
+    pub async fn record(&self, update: SentimentUpdate) {
+        self.latest
+            .write()
+            .await
+            .insert(update.symbol.clone(), update);
+    }
+
+    /// Record the conditions of one symbol as published by the regime detector.
+    pub async fn record_regime(&self, conditions: MarketConditions) {
+        let Some(symbol) = conditions.symbol.clone() else {
+            return;
+        };
+        self.regimes.write().await.insert(symbol, conditions);
+    }
+
+    /// Market conditions implied by current sentiment and regimes, or `None`
+    /// before either arrived.
+    ///
+    /// The overall sentiment is the sample-weighted mean across symbols; opportunity
+    /// rises and risk falls with it. Regime scores are averaged across symbols and
+    /// the most common regime is reported. With both, opportunity and risk are the
+    /// mean of the two estimates and volatility comes from the regimes; with
+    /// sentiment alone, disagreement between symbols is reported as volatility.
+    pub async fn conditions(&self) -> Option<MarketConditions> {
+        let from_sentiment = self.sentiment_conditions().await;
+        let from_regimes = self.regime_conditions().await;
+        match (from_sentiment, from_regimes) {
+            (Some(sentiment), Some(regimes)) => Some(MarketConditions {
+                opportunity_score: (sentiment.opportunity_score + regimes.opportunity_score) / 2.0,
+                risk_level: (sentiment.risk_level + regimes.risk_level) / 2.0,
+                sentiment: sentiment.sentiment,
+                ..regimes
+            }),
+            (sentiment, regimes) => sentiment.or(regimes),
+        }
+    }
+
+    async fn sentiment_conditions(&self) -> Option<MarketConditions> {
+        let latest = self.latest.read().await;
+        let samples: f64 = latest.values().map(|u| f64::from(u.samples.max(1))).sum();
+        if latest.is_empty() {
+            return None;
+        }
+
+        let sentiment = latest
+            .values()
+            .map(|u| u.score * f64::from(u.samples.max(1)))
+            .sum::<f64>()
+            / samples;
+        let (low, high) = latest
+            .values()
+            .fold((f64::MAX, f64::MIN), |(low, high), u| {
+                (low.min(u.score), high.max(u.score))
+            });
+        let opportunity_score = (sentiment + 1.0) / 2.0;
+
+        Some(MarketConditions {
+            volatility: (high - low) / 2.0,
+            opportunity_score,
+            risk_level: 1.0 - opportunity_score,
+            sentiment,
+            ..MarketConditions::default()
+        })
+    }
+
+    async fn regime_conditions(&self) -> Option<MarketConditions> {
+        let regimes = self.regimes.read().await;
+        if regimes.is_empty() {
+            return None;
+        }
+        let count = regimes.len() as f64;
+        let mean =
+            |score: fn(&MarketConditions) -> f64| regimes.values().map(score).sum::<f64>() / count;
+
+        let mut counts: HashMap<MarketRegime, usize> = HashMap::new();
+        for conditions in regimes.values() {
+            *counts.entry(conditions.regime).or_default() += 1;
+        }
+        // Ties go to the riskier regime so the estimate errs on the side of caution
+        let regime = counts
+            .into_iter()
+            .max_by_key(|(regime, count)| (*count, risk_rank(*regime)))
+            .map(|(regime, _)| regime)
+            .unwrap_or_default();
+
+        Some(MarketConditions {
+            symbol: None,
+            regime,
+            volatility: mean(|c| c.volatility),
+            trend_strength: mean(|c| c.trend_strength),
+            liquidity: mean(|c| c.liquidity),
+            opportunity_score: mean(|c| c.opportunity_score),
+            risk_level: mean(|c| c.risk_level),
+            sentiment: 0.0,
+            timestamp: regimes
+                .values()
+                .map(|c| c.timestamp)
+                .max()
+                .unwrap_or_default(),
+        })
+    }
+}
+
+fn risk_rank(regime: MarketRegime) -> u8 {
+    match regime {
+        MarketRegime::Unknown => 0,
+        MarketRegime::TrendingUp => 1,
+        MarketRegime::Ranging => 2,
+        MarketRegime::TrendingDown => 3,
+        MarketRegime::Illiquid => 4,
+        MarketRegime::Volatile => 5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(symbol: &str, score: f64, samples: u32) -> SentimentUpdate {
+        SentimentUpdate {
+            symbol: symbol.to_string(),
+            score,
+            samples,
+            timestamp: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_conditions_follow_sentiment() {
+        let sentiment = MarketSentiment::new();
+        assert!(sentiment.conditions().await.is_none());
+
+        sentiment.record(update("BTCUSDT", 0.8, 3)).await;
+        sentiment.record(update("ETHUSDT", -0.4, 1)).await;
+        let conditions = sentiment.conditions().await.unwrap();
+        assert!((conditions.sentiment - 0.5).abs() < 1e-9);
+        assert!((conditions.opportunity_score - 0.75).abs() < 1e-9);
+        assert!((conditions.risk_level - 0.25).abs() < 1e-9);
+        assert!((conditions.volatility - 0.6).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_conditions_combine_sentiment_and_regimes() {
+        let sentiment = MarketSentiment::new();
+        let regime = |symbol: &str, regime, risk_level| MarketConditions {
+            symbol: Some(symbol.to_string()),
+            regime,
+            volatility: 0.5,
+            opportunity_score: 1.0 - risk_level,
+            risk_level,
+            ..MarketConditions::default()
+        };
+        sentiment
+            .record_regime(regime("BTCUSDT", MarketRegime::Volatile, 0.8))
+            .await;
+        sentiment
+            .record_regime(regime("ETHUSDT", MarketRegime::Ranging, 0.4))
+            .await;
+        let conditions = sentiment.conditions().await.unwrap();
+        assert_eq!(conditions.regime, MarketRegime::Volatile);
+        assert!((conditions.risk_level - 0.6).abs() < 1e-9);
+
+        sentiment.record(update("BTCUSDT", 1.0, 1)).await;
+        let conditions = sentiment.conditions().await.unwrap();
+        assert!((conditions.sentiment - 1.0).abs() < 1e-9);
+        assert!((conditions.risk_level - 0.3).abs() < 1e-9);
+        assert!((conditions.volatility - 0.5).abs() < 1e-9);
+    }
+}
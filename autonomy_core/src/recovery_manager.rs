@@ -1,5 +1,10 @@
+use crate::approvals::{ApprovalGate, ApprovalKind};
 use anyhow::Result;
 use chrono::{DateTime, Duration, Utc};
+use common::audit::{self, AuditCategory};
+use common::bounded_history::{BoundedHistory, HISTORY_SPILL_DIR};
+use common::AureliaError;
+pub use common::RecoveryStats;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::future::Future;
@@ -18,6 +23,21 @@ pub enum FailureType {
     Unknown(String),
 }
 
+impl From<&AureliaError> for FailureType {
+    fn from(error: &AureliaError) -> Self {
+        match error {
+            AureliaError::Ssh(_) | AureliaError::Exchange(_) => FailureType::NetworkFailure,
+            AureliaError::Config(_) | AureliaError::Serialization(_) => {
+                FailureType::ConfigurationError
+            }
+            AureliaError::Deployment(_) | AureliaError::Ipc(_) | AureliaError::Storage(_) => {
+                FailureType::DependencyFailure
+            }
+            AureliaError::Io(e) => FailureType::Unknown(e.kind().to_string()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FailureEvent {
     pub id: String,
@@ -29,6 +49,21 @@ pub struct FailureEvent {
     pub auto_recoverable: bool,
 }
 
+impl FailureEvent {
+    /// Build a failure event for `component` from a typed error
+    pub fn from_error(component: &str, error: &AureliaError) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            failure_type: FailureType::from(error),
+            component: component.to_string(),
+            description: error.to_string(),
+            severity: if error.is_transient() { 5 } else { 7 },
+            auto_recoverable: true,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum RecoveryAction {
     RestartProcess,
@@ -59,14 +94,19 @@ pub struct RecoveryResult {
     pub error: Option<String>,
 }
 
+/// Failures and recoveries kept in memory, each for at most `HISTORY_DAYS`
+const HISTORY_LEN: usize = 1000;
+const HISTORY_DAYS: i64 = 7;
+
 pub struct RecoveryManager {
-    failure_history: Arc<RwLock<Vec<FailureEvent>>>,
-    recovery_history: Arc<RwLock<Vec<RecoveryResult>>>,
+    failure_history: Arc<RwLock<BoundedHistory<FailureEvent>>>,
+    recovery_history: Arc<RwLock<BoundedHistory<RecoveryResult>>>,
     recovery_strategies: Arc<RwLock<HashMap<FailureType, Vec<RecoveryAction>>>>,
     #[allow(dead_code)]
     max_recovery_attempts: u32,
     #[allow(dead_code)]
     recovery_timeout_seconds: u64,
+    approvals: ApprovalGate,
 }
 
 impl Default for RecoveryManager {
@@ -115,19 +155,29 @@ impl RecoveryManager {
         );
 
         Self {
-            failure_history: Arc::new(RwLock::new(Vec::new())),
-            recovery_history: Arc::new(RwLock::new(Vec::new())),
+            failure_history: Arc::new(RwLock::new(history("failures"))),
+            recovery_history: Arc::new(RwLock::new(history("recoveries"))),
             recovery_strategies: Arc::new(RwLock::new(strategies)),
             max_recovery_attempts: 3,
             recovery_timeout_seconds: 300,
+            approvals: ApprovalGate::default(),
         }
     }
 
+    /// Hold emergency shutdowns until `approvals` lets them through
+    pub fn with_approval_gate(mut self, approvals: ApprovalGate) -> Self {
+        self.approvals = approvals;
+        self
+    }
+
     pub async fn handle_failure(&self, failure: FailureEvent) -> Result<RecoveryResult> {
         info!("Handling failure: {:?}", failure);
 
         // Record the failure
-        self.failure_history.write().await.push(failure.clone());
+        self.failure_history
+            .write()
+            .await
+            .push_at(failure.timestamp, failure.clone());
 
         // Check if auto-recovery is possible
         if !failure.auto_recoverable {
@@ -199,7 +249,16 @@ impl RecoveryManager {
             info!("Executing recovery plan for failure {}", plan.failure_id);
 
             for action in &plan.actions {
-                match self.execute_recovery_action(action).await {
+                let result = self.execute_recovery_action(action).await;
+                audit::record(
+                    AuditCategory::RecoveryAction,
+                    format!("{:?}", action),
+                    serde_json::json!({
+                        "failure_id": plan.failure_id,
+                        "error": result.as_ref().err().map(|e| e.to_string()),
+                    }),
+                );
+                match result {
                     Ok(_) => {
                         actions_taken.push(action.clone());
                         info!("Successfully executed {:?}", action);
@@ -268,6 +327,17 @@ impl RecoveryManager {
                 self.reset_connections().await?;
             }
             RecoveryAction::EmergencyShutdown => {
+                if !self
+                    .approvals
+                    .authorize(
+                        ApprovalKind::EmergencyShutdown,
+                        "Emergency shutdown",
+                        action,
+                    )
+                    .await
+                {
+                    anyhow::bail!("Emergency shutdown was not approved");
+                }
                 self.emergency_shutdown().await?;
             }
         }
@@ -395,17 +465,8 @@ impl RecoveryManager {
     }
 
     async fn cleanup_history(&self) {
-        let cutoff = Utc::now() - Duration::days(7);
-
-        self.failure_history
-            .write()
-            .await
-            .retain(|f| f.timestamp > cutoff);
-
-        self.recovery_history.write().await.retain(|_r| {
-            // Keep recovery history based on failure timestamps
-            true
-        });
+        self.failure_history.write().await.prune();
+        self.recovery_history.write().await.prune();
     }
 
     pub async fn get_recovery_stats(&self) -> RecoveryStats {
@@ -433,11 +494,8 @@ impl RecoveryManager {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RecoveryStats {
-    pub total_recoveries: usize,
-    pub successful_recoveries: usize,
-    pub failed_recoveries: usize,
-    pub success_rate: f64,
-    pub average_recovery_time_seconds: u64,
+fn history<T>(name: &str) -> BoundedHistory<T> {
+    BoundedHistory::new(HISTORY_LEN)
+        .with_max_age(Duration::days(HISTORY_DAYS))
+        .with_spill_file(format!("{}/{}.jsonl", HISTORY_SPILL_DIR, name))
 }
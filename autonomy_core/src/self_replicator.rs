@@ -1,14 +1,34 @@
+use crate::cloud_provisioner::CloudFleet;
+use crate::cluster_registry::ClusterRegistry;
+use crate::deployment_queue::{DeploymentQueue, FailureOutcome, RetryPolicy};
 use crate::server_config::{ServerConfig, TargetServer};
+use crate::target_probe::{self, ProbeConfig, TargetProbe, TargetProber};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
+use common::bounded_history::{BoundedHistory, HISTORY_SPILL_DIR};
+use common::identity::{AgentIdentity, IDENTITY_PATH};
+pub use common::ReplicationResult;
+use common::{
+    host_port, AppEvent, DeploymentBundle, EventBus, ReleaseSigner, SshConnectionManager,
+};
 use deployment_tester::{DeploymentClient, ServerConfig as TestServerConfig};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use survival_protocol::{Budget, MINIMUM_RUNWAY_HOURS};
+use sysinfo::System;
+use tokio::sync::{watch, RwLock};
 use tracing::{error, info, warn};
 
+/// CPU usage above which additional replicas are considered useful
+const SCALE_UP_CPU_PERCENT: f64 = 70.0;
+
+/// Replication results kept in memory, older ones spill to disk
+const REPLICATION_HISTORY_LEN: usize = 1000;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReplicationTarget {
     pub ip: String,
@@ -21,16 +41,36 @@ pub struct ReplicationTarget {
     pub failure_count: u32,
 }
 
+impl ReplicationTarget {
+    pub fn from_server(server: &TargetServer) -> Self {
+        Self {
+            ip: server.ip.clone(),
+            user: server.username.clone(),
+            ssh_key_path: server.get_expanded_ssh_key_path(),
+            remote_path: PathBuf::from(&server.remote_path),
+            priority: server.priority as u8,
+            last_attempt: None,
+            success_count: 0,
+            failure_count: 0,
+        }
+    }
+}
+
+/// 副本树中的一条记录：由本代理部署的副本
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ReplicationResult {
+pub struct LineageRecord {
     pub target: String,
-    pub success: bool,
-    pub timestamp: DateTime<Utc>,
-    pub duration_seconds: u64,
-    pub error: Option<String>,
+    pub identity: AgentIdentity,
 }
 
+/// 随部署下发的复制配置，相对于部署目录
+pub const REPLICATION_CONFIG_PATH: &str = "config/replication.json";
+
+/// 副本树的追加式记录文件（JSON Lines），相对于部署目录
+pub const LINEAGE_PATH: &str = "data/lineage.jsonl";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ReplicationStrategy {
     pub max_replicas: usize,
     pub min_replicas: usize,
@@ -38,6 +78,21 @@ pub struct ReplicationStrategy {
     pub retry_attempts: u32,
     pub health_check_interval: u64,
     pub auto_scale: bool,
+    /// 是否允许本代理复制；副本默认关闭
+    pub enabled: bool,
+    /// 副本树的最大深度，主节点为第 0 代
+    pub max_generation: u32,
+    /// 整个集群（含主节点）的代理数量上限
+    pub max_fleet_size: usize,
+    /// 未配置 `hourly_cost` 的服务器的每小时成本
+    pub default_server_hourly_cost: f64,
+    /// 副本监控 API 的端口，健康检查优先通过 HTTP 进行
+    pub health_check_port: u16,
+    pub health_check_timeout_seconds: u64,
+    /// 部署失败后的退避与熔断，`retry_attempts` 为每个部署任务的最大尝试次数
+    pub retry: RetryPolicy,
+    /// 选择目标前探测其可达性和延迟
+    pub probe: ProbeConfig,
 }
 
 impl Default for ReplicationStrategy {
@@ -49,51 +104,172 @@ impl Default for ReplicationStrategy {
             retry_attempts: 3,
             health_check_interval: 60,
             auto_scale: true,
+            enabled: true,
+            max_generation: 2,
+            max_fleet_size: 10,
+            default_server_hourly_cost: 0.5,
+            health_check_port: 8080,
+            health_check_timeout_seconds: 3,
+            retry: RetryPolicy::default(),
+            probe: ProbeConfig::default(),
+        }
+    }
+}
+
+impl ReplicationStrategy {
+    /// 从部署目录中的配置加载策略
+    ///
+    /// 没有配置文件或文件中没有 `enabled` 时只有主节点允许复制；配置了主节点地址
+    /// （`AURELIA_PRIMARY_URL`）的代理是被部署的副本，即使身份是根身份也不允许。
+    pub fn load(path: &Path, identity: &AgentIdentity) -> Result<Self> {
+        Self::load_for(path, identity, common::identity::primary_url().as_deref())
+    }
+
+    fn load_for(path: &Path, identity: &AgentIdentity, primary_url: Option<&str>) -> Result<Self> {
+        let enabled = identity.is_primary() && primary_url.is_none();
+        if path.exists() {
+            let content = std::fs::read_to_string(path)?;
+            let mut value: serde_json::Value = serde_json::from_str(&content)?;
+            // 其余字段缺省时取默认值，`enabled` 则取决于本代理是否为主节点
+            if let Some(fields) = value.as_object_mut() {
+                fields.entry("enabled").or_insert(enabled.into());
+            }
+            let strategy: Self = serde_json::from_value(value)?;
+            strategy.validate()?;
+            return Ok(strategy);
+        }
+
+        Ok(Self {
+            enabled,
+            ..Self::default()
+        })
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.min_replicas > self.max_replicas {
+            anyhow::bail!(
+                "min_replicas {} exceeds max_replicas {}",
+                self.min_replicas,
+                self.max_replicas
+            );
+        }
+        if self.replication_interval_seconds == 0
+            || self.health_check_interval == 0
+            || self.health_check_timeout_seconds == 0
+        {
+            anyhow::bail!(
+                "replication_interval_seconds, health_check_interval and health_check_timeout_seconds must be at least 1"
+            );
+        }
+        if self.max_fleet_size == 0 {
+            anyhow::bail!("max_fleet_size must be at least 1");
+        }
+        if self.probe.timeout_ms == 0 || self.probe.latency_ms_per_priority <= 0.0 {
+            anyhow::bail!("probe.timeout_ms and probe.latency_ms_per_priority must be positive");
+        }
+        if self.probe.failure_rate_weight < 0.0 {
+            anyhow::bail!("probe.failure_rate_weight must not be negative");
+        }
+        Ok(())
+    }
+
+    /// 按代数和集群规模限制，返回 `identity` 本次最多还能部署的副本数
+    pub fn allowance(&self, identity: &AgentIdentity, fleet_size: usize) -> Result<usize> {
+        if !self.enabled {
+            return Err(anyhow::anyhow!("Replication is disabled on this agent"));
+        }
+
+        if identity.generation >= self.max_generation {
+            return Err(anyhow::anyhow!(
+                "Generation {} agents may not replicate (max generation {})",
+                identity.generation,
+                self.max_generation
+            ));
+        }
+
+        if fleet_size >= self.max_fleet_size {
+            return Err(anyhow::anyhow!(
+                "Fleet size {} has reached the cap of {}",
+                fleet_size,
+                self.max_fleet_size
+            ));
+        }
+
+        Ok(self.max_fleet_size - fleet_size)
+    }
+
+    /// 下发给副本的策略：沿用本节点的限制，但关闭复制
+    pub fn for_replica(&self) -> Self {
+        Self {
+            enabled: false,
+            ..self.clone()
         }
     }
 }
 
 pub struct SelfReplicator {
-    strategy: ReplicationStrategy,
+    strategy: std::sync::RwLock<ReplicationStrategy>,
     targets: Arc<RwLock<Vec<ReplicationTarget>>>,
     active_replicas: Arc<RwLock<HashMap<String, DateTime<Utc>>>>,
-    replication_history: Arc<RwLock<Vec<ReplicationResult>>>,
+    replication_history: Arc<RwLock<BoundedHistory<ReplicationResult>>>,
     binary_path: PathBuf,
     server_config: Option<ServerConfig>,
+    identity: AgentIdentity,
+    lineage: Arc<RwLock<Vec<LineageRecord>>>,
+    lineage_file: Option<PathBuf>,
+    registry: Option<Arc<dyn ClusterRegistry>>,
+    budget: Option<watch::Receiver<Budget>>,
+    http: reqwest::Client,
+    queue: Arc<RwLock<DeploymentQueue>>,
+    cloud: Option<Arc<CloudFleet>>,
+    events: Option<EventBus>,
+    signer: Option<ReleaseSigner>,
+    ssh_pool: SshConnectionManager,
+    prober: TargetProber,
 }
 
 impl SelfReplicator {
     pub fn new(binary_path: PathBuf) -> Self {
         // 尝试加载配置文件
         let server_config = Self::load_server_config();
+        Self::with_server_config(binary_path, server_config)
+    }
 
+    /// 使用已加载的服务器配置创建复制器
+    pub fn with_server_config(binary_path: PathBuf, server_config: Option<ServerConfig>) -> Self {
         // 如果有配置文件，从中初始化目标服务器
         let targets = if let Some(ref config) = server_config {
             config
                 .get_servers_by_priority()
                 .into_iter()
-                .map(|server| ReplicationTarget {
-                    ip: server.ip.clone(),
-                    user: server.username.clone(),
-                    ssh_key_path: server.get_expanded_ssh_key_path(),
-                    remote_path: PathBuf::from(&server.remote_path),
-                    priority: server.priority as u8,
-                    last_attempt: None,
-                    success_count: 0,
-                    failure_count: 0,
-                })
+                .map(ReplicationTarget::from_server)
                 .collect()
         } else {
             Vec::new()
         };
 
         Self {
-            strategy: ReplicationStrategy::default(),
+            strategy: std::sync::RwLock::new(ReplicationStrategy::default()),
             targets: Arc::new(RwLock::new(targets)),
             active_replicas: Arc::new(RwLock::new(HashMap::new())),
-            replication_history: Arc::new(RwLock::new(Vec::new())),
+            replication_history: Arc::new(RwLock::new(
+                BoundedHistory::new(REPLICATION_HISTORY_LEN)
+                    .with_spill_file(format!("{}/replications.jsonl", HISTORY_SPILL_DIR)),
+            )),
             binary_path,
             server_config,
+            identity: AgentIdentity::new_root(),
+            lineage: Arc::new(RwLock::new(Vec::new())),
+            lineage_file: None,
+            registry: None,
+            budget: None,
+            http: reqwest::Client::new(),
+            queue: Arc::new(RwLock::new(DeploymentQueue::new())),
+            cloud: None,
+            events: None,
+            signer: None,
+            ssh_pool: SshConnectionManager::default(),
+            prober: TargetProber::new(),
         }
     }
 
@@ -116,10 +292,209 @@ impl SelfReplicator {
     }
 
     pub fn with_strategy(mut self, strategy: ReplicationStrategy) -> Self {
-        self.strategy = strategy;
+        self.strategy = std::sync::RwLock::new(strategy);
+        self
+    }
+
+    /// 当前生效的复制策略
+    pub fn strategy(&self) -> ReplicationStrategy {
+        self.strategy
+            .read()
+            .expect("replication strategy lock poisoned")
+            .clone()
+    }
+
+    /// 运行中替换复制策略，下一轮复制和健康检查起生效
+    pub fn set_strategy(&self, strategy: ReplicationStrategy) {
+        info!(
+            min_replicas = strategy.min_replicas,
+            max_replicas = strategy.max_replicas,
+            "Replication strategy updated"
+        );
+        *self
+            .strategy
+            .write()
+            .expect("replication strategy lock poisoned") = strategy;
+    }
+
+    /// 使用持久化的部署队列，重启后保留重试和隔离状态
+    pub fn with_deployment_queue(mut self, queue: DeploymentQueue) -> Self {
+        self.queue = Arc::new(RwLock::new(queue));
+        self
+    }
+
+    /// 副本树追加写入 `path`，并从中恢复重启前部署的副本
+    pub fn with_lineage_file(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        match load_lineage(&path) {
+            Ok(records) => self.lineage = Arc::new(RwLock::new(records)),
+            Err(e) => error!("Failed to load lineage from {:?}: {}", path, e),
+        }
+        self.lineage_file = Some(path);
+        self
+    }
+
+    /// 设置本代理的身份，副本将以它为父节点
+    pub fn with_identity(mut self, identity: AgentIdentity) -> Self {
+        self.identity = identity;
+        self
+    }
+
+    pub fn identity(&self) -> &AgentIdentity {
+        &self.identity
+    }
+
+    /// 使用集群注册表统计集群规模；未设置时只统计本节点和本地已知的副本
+    pub fn with_cluster_registry(mut self, registry: Arc<dyn ClusterRegistry>) -> Self {
+        self.registry = Some(registry);
         self
     }
 
+    /// 根据生存协议的预算决定是否扩容；未设置时不会自动扩容
+    pub fn with_budget(mut self, budget: watch::Receiver<Budget>) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// 没有可用的目标服务器时，在预算允许的情况下从云服务商购买
+    pub fn with_cloud_fleet(mut self, cloud: Arc<CloudFleet>) -> Self {
+        self.cloud = Some(cloud);
+        self
+    }
+
+    /// 每次复制尝试结束后发布 `AppEvent::ReplicationCompleted`
+    pub fn with_event_bus(mut self, bus: EventBus) -> Self {
+        self.events = Some(bus);
+        self
+    }
+
+    /// 签名部署包，副本在启动前校验
+    pub fn with_signer(mut self, signer: ReleaseSigner) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// 部署和副本健康检查复用连接池中的 SSH 会话
+    pub fn with_connection_pool(mut self, pool: SshConnectionManager) -> Self {
+        self.ssh_pool = pool;
+        self
+    }
+
+    fn current_budget(&self) -> Option<Budget> {
+        self.budget.as_ref().map(|budget| *budget.borrow())
+    }
+
+    /// 下一个待部署服务器的每小时成本
+    async fn next_server_cost(&self) -> f64 {
+        let active = self.active_replicas.read().await;
+        let targets = self.targets.read().await;
+        targets
+            .iter()
+            .find(|t| !active.contains_key(&t.ip))
+            .and_then(|t| {
+                self.server_config
+                    .as_ref()?
+                    .target_servers
+                    .iter()
+                    .find(|s| s.ip == t.ip)?
+                    .hourly_cost
+            })
+            .unwrap_or(self.strategy().default_server_hourly_cost)
+    }
+
+    async fn fleet_size(&self) -> Result<usize> {
+        match &self.registry {
+            Some(registry) => registry.fleet_size().await,
+            None => Ok(1 + self.active_replicas.read().await.len()),
+        }
+    }
+
+    /// 检查全局防护限制，返回本次最多还能部署的副本数
+    async fn replication_allowance(&self) -> Result<usize> {
+        if !self.strategy().enabled {
+            return Err(anyhow::anyhow!("Replication is disabled on this agent"));
+        }
+
+        // 无法确认集群规模时不复制
+        let fleet_size = self
+            .fleet_size()
+            .await
+            .map_err(|e| anyhow::anyhow!("Unable to determine fleet size: {}", e))?;
+        self.strategy().allowance(&self.identity, fleet_size)
+    }
+
+    /// 本代理部署过的副本
+    pub async fn get_lineage(&self) -> Vec<LineageRecord> {
+        self.lineage.read().await.clone()
+    }
+
+    async fn record_lineage(&self, record: LineageRecord) {
+        let mut lineage = self.lineage.write().await;
+        if let Some(path) = &self.lineage_file {
+            if let Err(e) = append_lineage(path, &record) {
+                error!("Failed to persist lineage record to {:?}: {}", path, e);
+            }
+        }
+        lineage.push(record);
+    }
+
+    /// 探测目标时连接的地址：经跳板机访问的服务器探测跳板机
+    fn probe_address(&self, ip: &str) -> (String, u16) {
+        let server = self
+            .server_config
+            .as_ref()
+            .and_then(|config| config.target_servers.iter().find(|s| s.ip == ip));
+        match server {
+            Some(server) => match &server.proxy_jump {
+                Some(jump) => (jump.host.clone(), jump.port),
+                None => (server.ip.clone(), server.port),
+            },
+            None => (ip.to_string(), 22),
+        }
+    }
+
+    /// 尚未部署副本的目标，按优先级、延迟和失败率从好到差排列；探测不到的目标不参与本轮
+    async fn rank_candidates(&self, targets: &[ReplicationTarget]) -> Vec<ReplicationTarget> {
+        let config = self.strategy().probe;
+        let pending: Vec<&ReplicationTarget> = {
+            let active = self.active_replicas.read().await;
+            targets
+                .iter()
+                .filter(|t| !active.contains_key(&t.ip))
+                .collect()
+        };
+        if !config.enabled {
+            return pending.into_iter().cloned().collect();
+        }
+
+        let probes: Vec<Option<TargetProbe>> =
+            futures_util::future::join_all(pending.iter().map(|target| {
+                let (host, port) = self.probe_address(&target.ip);
+                let prober = self.prober.clone();
+                let config = config.clone();
+                async move { Some(prober.probe(&host, port, &config).await) }
+            }))
+            .await;
+        for (target, probe) in pending.iter().zip(&probes) {
+            if let Some(probe) = probe.as_ref().filter(|p| !p.reachable) {
+                warn!(
+                    "Skipping unreachable target {}: {}",
+                    target.ip,
+                    probe.error.as_deref().unwrap_or("no answer")
+                );
+            }
+        }
+        target_probe::rank(&pending, &probes, &config)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    /// 最近一次探测各目标的结果，按 `host:port` 索引
+    pub async fn target_probes(&self) -> HashMap<String, TargetProbe> {
+        self.prober.probes().await
+    }
+
     pub async fn add_target(&self, target: ReplicationTarget) {
         let mut targets = self.targets.write().await;
         targets.push(target);
@@ -134,16 +509,7 @@ impl SelfReplicator {
         let new_targets: Vec<ReplicationTarget> = config
             .get_servers_by_priority()
             .into_iter()
-            .map(|server| ReplicationTarget {
-                ip: server.ip.clone(),
-                user: server.username.clone(),
-                ssh_key_path: server.get_expanded_ssh_key_path(),
-                remote_path: PathBuf::from(&server.remote_path),
-                priority: server.priority as u8,
-                last_attempt: None,
-                success_count: 0,
-                failure_count: 0,
-            })
+            .map(ReplicationTarget::from_server)
             .collect();
 
         let count = new_targets.len();
@@ -161,16 +527,7 @@ impl SelfReplicator {
             config.save_to_file("config/target_servers.json")?;
 
             // 添加到运行时目标列表
-            let target = ReplicationTarget {
-                ip: server.ip.clone(),
-                user: server.username.clone(),
-                ssh_key_path: server.get_expanded_ssh_key_path(),
-                remote_path: PathBuf::from(&server.remote_path),
-                priority: server.priority as u8,
-                last_attempt: None,
-                success_count: 0,
-                failure_count: 0,
-            };
+            let target = ReplicationTarget::from_server(&server);
             self.targets.write().await.push(target);
 
             info!("Added server {} to configuration", server.id);
@@ -190,17 +547,35 @@ impl SelfReplicator {
     }
 
     pub async fn should_replicate(&self) -> bool {
+        if let Err(e) = self.replication_allowance().await {
+            info!("Replication not allowed: {}", e);
+            return false;
+        }
+
         let active_count = self.active_replicas.read().await.len();
 
-        if active_count < self.strategy.min_replicas {
+        if active_count < self.strategy().min_replicas {
+            // Even the minimum replica count must not eat into the runway
+            if let Some(budget) = self.current_budget() {
+                let runway = budget.projected_runway_hours(self.next_server_cost().await);
+                if runway < MINIMUM_RUNWAY_HOURS {
+                    warn!(
+                        "Active replicas ({}) below minimum, but projected runway {:.1}h is below {:.1}h",
+                        active_count, runway, MINIMUM_RUNWAY_HOURS
+                    );
+                    return false;
+                }
+            }
+
             info!(
                 "Active replicas ({}) below minimum ({}), replication needed",
-                active_count, self.strategy.min_replicas
+                active_count,
+                self.strategy().min_replicas
             );
             return true;
         }
 
-        if self.strategy.auto_scale && active_count < self.strategy.max_replicas {
+        if self.strategy().auto_scale && active_count < self.strategy().max_replicas {
             // Check system load and decide if scaling is needed
             if self.check_scaling_conditions().await {
                 info!("Scaling conditions met, initiating replication");
@@ -212,28 +587,114 @@ impl SelfReplicator {
     }
 
     async fn check_scaling_conditions(&self) -> bool {
-        // In a real implementation, this would check:
-        // - Current system load
-        // - Market conditions
-        // - Resource availability
-        // - Cost considerations
+        let Some(budget) = self.current_budget() else {
+            info!("No budget information available, not scaling");
+            return false;
+        };
+
+        let server_cost = self.next_server_cost().await;
+        let cpu_usage = current_cpu_usage().await;
 
-        // For now, we'll use a simple random decision with 20% probability
-        rand::random::<f64>() < 0.2
+        match evaluate_scaling(&budget, server_cost, cpu_usage) {
+            Ok(reason) => {
+                info!("Scaling approved: {}", reason);
+                true
+            }
+            Err(reason) => {
+                info!("Scaling rejected: {}", reason);
+                false
+            }
+        }
     }
 
     pub async fn replicate(&self) -> Result<Vec<ReplicationResult>> {
         info!("Starting autonomous self-replication process");
 
-        let targets = self.targets.read().await.clone();
+        let allowance = self.replication_allowance().await?;
         let active_replicas = self.active_replicas.read().await.len();
-        let mut results = Vec::new();
 
-        let replicas_needed = self.strategy.min_replicas.saturating_sub(active_replicas);
+        let replicas_needed = self
+            .strategy()
+            .min_replicas
+            .saturating_sub(active_replicas)
+            .min(allowance);
+
+        if let Some(cloud) = &self.cloud {
+            // Servers bought in an earlier run are targets too
+            let mut known = self.targets.write().await;
+            for server in cloud.target_servers().await {
+                if !known.iter().any(|t| t.ip == server.ip) {
+                    known.push(ReplicationTarget::from_server(&server));
+                }
+            }
+        }
+        let mut targets = self.targets.read().await.clone();
+
+        let now = Utc::now();
+        if let Some(cloud) = &self.cloud {
+            let shortfall = {
+                let active = self.active_replicas.read().await;
+                let queue = self.queue.read().await;
+                let available = targets
+                    .iter()
+                    .filter(|t| !active.contains_key(&t.ip) && !queue.contains(&t.ip))
+                    .filter(|t| !queue.is_quarantined(&t.ip, now))
+                    .count();
+                replicas_needed.saturating_sub(queue.jobs().len() + available)
+            };
+            // One purchase per cycle keeps a misbehaving API from running up costs
+            if shortfall > 0 {
+                let name = format!("aurelia-{}", &uuid::Uuid::new_v4().to_string()[..8]);
+                match cloud.acquire(self.current_budget().as_ref(), &name).await {
+                    Ok(server) => {
+                        let target = ReplicationTarget::from_server(&server);
+                        self.add_target(target.clone()).await;
+                        targets.push(target);
+                    }
+                    Err(e) => warn!("Not acquiring a cloud server: {}", e),
+                }
+            }
+        }
+
+        let ranked = self.rank_candidates(&targets).await;
+        let due = {
+            let active = self.active_replicas.read().await;
+            let mut queue = self.queue.write().await;
+            let queued = queue.jobs().len();
+            let candidates: Vec<_> = ranked
+                .iter()
+                .filter(|t| !active.contains_key(&t.ip) && !queue.contains(&t.ip))
+                .filter(|t| !queue.is_quarantined(&t.ip, now))
+                .take(replicas_needed.saturating_sub(queued))
+                .map(|t| t.ip.clone())
+                .collect();
+            for ip in candidates {
+                queue.enqueue(&ip, now);
+            }
+            queue.due(now)
+        };
 
-        for target in targets.iter().take(replicas_needed) {
+        Ok(self
+            .attempt_due(&targets, due.into_iter().take(replicas_needed))
+            .await)
+    }
+
+    /// 依次部署队列中到期的目标，并按结果更新队列、统计和历史
+    async fn attempt_due(
+        &self,
+        targets: &[ReplicationTarget],
+        due: impl Iterator<Item = String>,
+    ) -> Vec<ReplicationResult> {
+        let mut results = Vec::new();
+        for ip in due {
+            let Some(target) = targets.iter().find(|t| t.ip == ip) else {
+                // The server was removed from the configuration
+                self.queue.write().await.remove(&ip);
+                continue;
+            };
             if self.active_replicas.read().await.contains_key(&target.ip) {
-                continue; // Skip already active replicas
+                self.queue.write().await.remove(&target.ip);
+                continue;
             }
 
             let result = self.replicate_to_target(target).await;
@@ -244,6 +705,7 @@ impl SelfReplicator {
                     .write()
                     .await
                     .insert(target.ip.clone(), Utc::now());
+                self.queue.write().await.record_success(&target.ip);
 
                 // Update target statistics
                 let mut targets = self.targets.write().await;
@@ -252,6 +714,23 @@ impl SelfReplicator {
                     t.last_attempt = Some(Utc::now());
                 }
             } else {
+                let outcome = self.queue.write().await.record_failure(
+                    &target.ip,
+                    result.error.as_deref().unwrap_or("unknown error"),
+                    self.strategy().retry_attempts,
+                    &self.strategy().retry,
+                    Utc::now(),
+                );
+                match outcome {
+                    FailureOutcome::Retry(at) => info!("Retrying {} after {}", target.ip, at),
+                    FailureOutcome::GaveUp => error!(
+                        "Failed to replicate to {} after {} attempts",
+                        target.ip,
+                        self.strategy().retry_attempts
+                    ),
+                    FailureOutcome::Quarantined(_) => self.release_cloud_server(&target.ip).await,
+                }
+
                 // Update failure statistics
                 let mut targets = self.targets.write().await;
                 if let Some(t) = targets.iter_mut().find(|t| t.ip == target.ip) {
@@ -261,10 +740,31 @@ impl SelfReplicator {
             }
 
             // Record in history
-            self.replication_history.write().await.push(result);
+            if let Some(bus) = &self.events {
+                let _ = bus.publish(AppEvent::ReplicationCompleted(result.clone()));
+            }
+            let recorded_at = result.timestamp;
+            self.replication_history
+                .write()
+                .await
+                .push_at(recorded_at, result);
         }
 
-        Ok(results)
+        results
+    }
+
+    /// 释放无法部署的云服务器，避免为其继续付费
+    async fn release_cloud_server(&self, ip: &str) {
+        let Some(cloud) = &self.cloud else {
+            return;
+        };
+        if !cloud.owns(ip).await {
+            return;
+        }
+        match cloud.release(ip).await {
+            Ok(()) => self.targets.write().await.retain(|t| t.ip != ip),
+            Err(e) => error!("Failed to release cloud server {}: {}", ip, e),
+        }
     }
 
     async fn replicate_to_target(&self, target: &ReplicationTarget) -> ReplicationResult {
@@ -345,57 +845,84 @@ impl SelfReplicator {
             }
         };
 
-        let client = DeploymentClient::new(server_config);
+        let client =
+            DeploymentClient::new(server_config).with_connection_pool(self.ssh_pool.clone());
 
-        let mut attempts = 0;
-        let mut last_error = None;
-
-        while attempts < self.strategy.retry_attempts {
-            attempts += 1;
+        // 副本的身份由父节点生成并随部署包下发
+        let child = self.identity.spawn_child();
+        let bundle = match self.replica_bundle(&client, &child) {
+            Ok(bundle) => bundle,
+            Err(e) => {
+                return ReplicationResult {
+                    target: target.ip.clone(),
+                    success: false,
+                    timestamp: Utc::now(),
+                    duration_seconds: 0,
+                    error: Some(e.to_string()),
+                    agent_id: None,
+                }
+            }
+        };
 
-            match client.deploy_agent(&self.binary_path) {
-                Ok(_) => {
-                    let duration = (Utc::now() - start_time).num_seconds() as u64;
-                    info!(
-                        "Successfully replicated to {} in {} seconds",
-                        target.ip, duration
-                    );
+        // 失败后由部署队列按退避策略安排重试
+        match client.deploy_bundle(&bundle) {
+            Ok(_) => {
+                let duration = (Utc::now() - start_time).num_seconds() as u64;
+                info!(
+                    agent_id = %child.agent_id,
+                    parent_id = %self.identity.agent_id,
+                    generation = child.generation,
+                    "Successfully replicated to {} in {} seconds",
+                    target.ip,
+                    duration
+                );
 
-                    return ReplicationResult {
-                        target: target.ip.clone(),
-                        success: true,
-                        timestamp: Utc::now(),
-                        duration_seconds: duration,
-                        error: None,
-                    };
+                self.record_lineage(LineageRecord {
+                    target: target.ip.clone(),
+                    identity: child.clone(),
+                })
+                .await;
+
+                ReplicationResult {
+                    target: target.ip.clone(),
+                    success: true,
+                    timestamp: Utc::now(),
+                    duration_seconds: duration,
+                    error: None,
+                    agent_id: Some(child.agent_id.clone()),
                 }
-                Err(e) => {
-                    warn!(
-                        "Replication attempt {} to {} failed: {}",
-                        attempts, target.ip, e
-                    );
-                    last_error = Some(e.to_string());
-
-                    if attempts < self.strategy.retry_attempts {
-                        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-                    }
+            }
+            Err(e) => {
+                warn!("Replication to {} failed: {}", target.ip, e);
+                ReplicationResult {
+                    target: target.ip.clone(),
+                    success: false,
+                    timestamp: Utc::now(),
+                    duration_seconds: (Utc::now() - start_time).num_seconds() as u64,
+                    error: Some(e.to_string()),
+                    agent_id: None,
                 }
             }
         }
+    }
 
-        let duration = (Utc::now() - start_time).num_seconds() as u64;
-        error!(
-            "Failed to replicate to {} after {} attempts",
-            target.ip, attempts
-        );
-
-        ReplicationResult {
-            target: target.ip.clone(),
-            success: false,
-            timestamp: Utc::now(),
-            duration_seconds: duration,
-            error: last_error,
-        }
+    /// 部署包：默认文件加上副本的身份和关闭复制的策略
+    fn replica_bundle(
+        &self,
+        client: &DeploymentClient,
+        child: &AgentIdentity,
+    ) -> Result<DeploymentBundle> {
+        let bundle = client
+            .default_bundle(&self.binary_path)
+            .template(child.to_json()?, IDENTITY_PATH)
+            .template(
+                serde_json::to_string_pretty(&self.strategy().for_replica())?,
+                REPLICATION_CONFIG_PATH,
+            );
+        Ok(match &self.signer {
+            Some(signer) => bundle.signed_by(signer.clone()),
+            None => bundle,
+        })
     }
 
     pub async fn verify_replicas(&self) -> Result<HashMap<String, bool>> {
@@ -403,85 +930,128 @@ impl SelfReplicator {
         let active_replicas = self.active_replicas.read().await.clone();
 
         for (ip, _) in active_replicas.iter() {
-            // 从配置中获取服务器信息
-            let server_info = if let Some(ref config) = self.server_config {
-                config.target_servers.iter().find(|s| &s.ip == ip).cloned()
-            } else {
-                None
+            let healthy = match self.check_replica_http(ip).await {
+                Ok(healthy) => healthy,
+                Err(e) => {
+                    // 监控 API 不可达时才退回 SSH
+                    warn!(
+                        "Replica {} HTTP health check unreachable ({}), using SSH",
+                        ip, e
+                    );
+                    self.check_replica_ssh(ip)
+                }
             };
 
-            let server_config = if let Some(info) = server_info {
-                // 使用实际的服务器配置
-                match info.auth_method {
-                    crate::server_config::AuthMethod::Password => TestServerConfig {
-                        name: format!("replica-{}", ip),
-                        ip: ip.clone(),
-                        port: info.port,
-                        user: info.username.clone(),
-                        ssh_key_path: None,
-                        password: info.get_password(),
-                        auth_method: deployment_tester::config::AuthMethod::Password,
-                        remote_deploy_path: PathBuf::from(&info.remote_path),
-                        role: deployment_tester::config::ServerRole::Replica,
-                    },
-                    crate::server_config::AuthMethod::KeyWithPassphrase => TestServerConfig {
-                        name: format!("replica-{}", ip),
-                        ip: ip.clone(),
-                        port: info.port,
-                        user: info.username.clone(),
-                        ssh_key_path: Some(info.get_expanded_ssh_key_path()),
-                        password: info.get_password(),
-                        auth_method: deployment_tester::config::AuthMethod::KeyWithPassphrase,
-                        remote_deploy_path: PathBuf::from(&info.remote_path),
-                        role: deployment_tester::config::ServerRole::Replica,
-                    },
-                    _ => TestServerConfig {
-                        name: format!("replica-{}", ip),
-                        ip: ip.clone(),
-                        port: info.port,
-                        user: info.username.clone(),
-                        ssh_key_path: Some(info.get_expanded_ssh_key_path()),
-                        password: None,
-                        auth_method: deployment_tester::config::AuthMethod::Key,
-                        remote_deploy_path: PathBuf::from(&info.remote_path),
-                        role: deployment_tester::config::ServerRole::Replica,
-                    },
-                }
-            } else {
-                // 使用默认配置
-                TestServerConfig {
+            health_status.insert(ip.clone(), healthy);
+            if !healthy {
+                warn!("Replica {} is not running", ip);
+                // Remove from active replicas
+                self.active_replicas.write().await.remove(ip);
+            }
+        }
+
+        Ok(health_status)
+    }
+
+    /// 通过副本的 `/health` 和 `/api/status` 检查健康状态
+    ///
+    /// 副本有响应时返回是否健康；连接失败或超时返回错误。
+    async fn check_replica_http(&self, ip: &str) -> Result<bool> {
+        let timeout = std::time::Duration::from_secs(self.strategy().health_check_timeout_seconds);
+        let base_url = format!(
+            "http://{}",
+            host_port(ip, self.strategy().health_check_port)
+        );
+
+        for path in ["/health", "/api/status"] {
+            let response = self
+                .http
+                .get(format!("{}{}", base_url, path))
+                .timeout(timeout)
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                warn!("Replica {} returned {} on {}", ip, response.status(), path);
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn check_replica_ssh(&self, ip: &str) -> bool {
+        let ip = ip.to_string();
+        // 从配置中获取服务器信息
+        let server_info = if let Some(ref config) = self.server_config {
+            config.target_servers.iter().find(|s| s.ip == ip).cloned()
+        } else {
+            None
+        };
+
+        let server_config = if let Some(info) = server_info {
+            // 使用实际的服务器配置
+            match info.auth_method {
+                crate::server_config::AuthMethod::Password => TestServerConfig {
+                    name: format!("replica-{}", ip),
+                    ip: ip.clone(),
+                    port: info.port,
+                    user: info.username.clone(),
+                    ssh_key_path: None,
+                    password: info.get_password(),
+                    auth_method: deployment_tester::config::AuthMethod::Password,
+                    remote_deploy_path: PathBuf::from(&info.remote_path),
+                    role: deployment_tester::config::ServerRole::Replica,
+                },
+                crate::server_config::AuthMethod::KeyWithPassphrase => TestServerConfig {
+                    name: format!("replica-{}", ip),
+                    ip: ip.clone(),
+                    port: info.port,
+                    user: info.username.clone(),
+                    ssh_key_path: Some(info.get_expanded_ssh_key_path()),
+                    password: info.get_password(),
+                    auth_method: deployment_tester::config::AuthMethod::KeyWithPassphrase,
+                    remote_deploy_path: PathBuf::from(&info.remote_path),
+                    role: deployment_tester::config::ServerRole::Replica,
+                },
+                _ => TestServerConfig {
                     name: format!("replica-{}", ip),
                     ip: ip.clone(),
-                    port: 22,
-                    user: "ubuntu".to_string(),
-                    ssh_key_path: Some(PathBuf::from("~/.ssh/id_rsa")),
+                    port: info.port,
+                    user: info.username.clone(),
+                    ssh_key_path: Some(info.get_expanded_ssh_key_path()),
                     password: None,
                     auth_method: deployment_tester::config::AuthMethod::Key,
-                    remote_deploy_path: PathBuf::from("/home/ubuntu/aurelia_agent"),
+                    remote_deploy_path: PathBuf::from(&info.remote_path),
                     role: deployment_tester::config::ServerRole::Replica,
-                }
-            };
+                },
+            }
+        } else {
+            // 使用默认配置
+            TestServerConfig {
+                name: format!("replica-{}", ip),
+                ip: ip.clone(),
+                port: 22,
+                user: "ubuntu".to_string(),
+                ssh_key_path: Some(PathBuf::from("~/.ssh/id_rsa")),
+                password: None,
+                auth_method: deployment_tester::config::AuthMethod::Key,
+                remote_deploy_path: PathBuf::from("/home/ubuntu/aurelia_agent"),
+                role: deployment_tester::config::ServerRole::Replica,
+            }
+        };
 
-            let monitor = deployment_tester::AgentMonitor::new(server_config);
+        let monitor = deployment_tester::AgentMonitor::with_connection_pool(
+            server_config,
+            self.ssh_pool.clone(),
+        );
 
-            match monitor.check_process_status() {
-                Ok(is_running) => {
-                    health_status.insert(ip.clone(), is_running);
-                    if !is_running {
-                        warn!("Replica {} is not running", ip);
-                        // Remove from active replicas
-                        self.active_replicas.write().await.remove(ip);
-                    }
-                }
-                Err(e) => {
-                    error!("Failed to check replica {} health: {}", ip, e);
-                    health_status.insert(ip.clone(), false);
-                    self.active_replicas.write().await.remove(ip);
-                }
+        match monitor.check_process_status() {
+            Ok(is_running) => is_running,
+            Err(e) => {
+                error!("Failed to check replica {} health: {}", ip, e);
+                false
             }
         }
-
-        Ok(health_status)
     }
 
     pub async fn auto_manage(&self) {
@@ -505,20 +1075,14 @@ impl SelfReplicator {
 
             // 4. Wait for next cycle
             tokio::time::sleep(tokio::time::Duration::from_secs(
-                self.strategy.replication_interval_seconds,
+                self.strategy().replication_interval_seconds,
             ))
             .await;
         }
     }
 
     async fn cleanup_history(&self) {
-        let mut history = self.replication_history.write().await;
-
-        // Keep only last 1000 entries
-        if history.len() > 1000 {
-            let drain_count = history.len() - 1000;
-            history.drain(0..drain_count);
-        }
+        self.replication_history.write().await.prune();
     }
 
     pub async fn get_status(&self) -> ReplicationStatus {
@@ -526,32 +1090,61 @@ impl SelfReplicator {
             active_replicas: self.active_replicas.read().await.len(),
             total_targets: self.targets.read().await.len(),
             recent_failures: self.count_recent_failures().await,
-            strategy: self.strategy.clone(),
+            strategy: self.strategy(),
+            replicas_deployed: self.lineage.read().await.len(),
+            target_probes: self.target_probes().await,
         }
     }
 
+    /// 最近的复制结果，最新的在前
+    pub async fn recent_results(&self, limit: usize) -> Vec<ReplicationResult> {
+        self.replication_history
+            .read()
+            .await
+            .iter()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
     async fn count_recent_failures(&self) -> usize {
-        let history = self.replication_history.read().await;
         let one_hour_ago = Utc::now() - chrono::Duration::hours(1);
-
-        history
-            .iter()
-            .filter(|r| !r.success && r.timestamp > one_hour_ago)
+        self.replication_history
+            .read()
+            .await
+            .since(one_hour_ago)
+            .filter(|r| !r.success)
             .count()
     }
 
     pub async fn trigger_emergency_replication(&self) -> Result<()> {
         warn!("Emergency replication triggered!");
 
-        // Override normal limits for emergency
+        // Jump the queue for up to 3 targets, but never the fleet guardrails or quarantine
+        let limit = 3.min(self.replication_allowance().await?);
         let targets = self.targets.read().await.clone();
-        let mut results = Vec::new();
+        let ranked = self.rank_candidates(&targets).await;
+        let now = Utc::now();
+        let due = {
+            let active = self.active_replicas.read().await;
+            let mut queue = self.queue.write().await;
+            let candidates: Vec<_> = ranked
+                .iter()
+                .filter(|t| !active.contains_key(&t.ip))
+                .filter(|t| !queue.is_quarantined(&t.ip, now))
+                .take(limit)
+                .map(|t| t.ip.clone())
+                .collect();
+            for ip in candidates {
+                queue.enqueue_urgent(&ip, now);
+            }
+            queue.due(now)
+        };
 
-        for target in targets.iter().take(3) {
-            // Replicate to up to 3 targets immediately
-            let result = self.replicate_to_target(target).await;
-            results.push(result);
-        }
+        let results = self
+            .attempt_due(&targets, due.into_iter().take(limit))
+            .await;
 
         let successful = results.iter().filter(|r| r.success).count();
         if successful == 0 {
@@ -569,10 +1162,262 @@ impl SelfReplicator {
     }
 }
 
+async fn current_cpu_usage() -> f64 {
+    let mut sys = System::new();
+    sys.refresh_cpu();
+    tokio::time::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL).await;
+    sys.refresh_cpu();
+    sys.global_cpu_info().cpu_usage() as f64
+}
+
+/// 扩容策略：新增服务器后的预计资金跑道必须不低于 `MINIMUM_RUNWAY_HOURS`，
+/// 近期策略不能亏损，并且需要 CPU 压力或近期盈利作为扩容理由。
+/// 返回批准或拒绝的原因。
+fn evaluate_scaling(budget: &Budget, server_cost: f64, cpu_usage: f64) -> Result<String, String> {
+    let runway = budget.projected_runway_hours(server_cost);
+    if runway < MINIMUM_RUNWAY_HOURS {
+        return Err(format!(
+            "projected runway {:.1}h is below the minimum of {:.1}h",
+            runway, MINIMUM_RUNWAY_HOURS
+        ));
+    }
+
+    if budget.recent_pnl < 0.0 {
+        return Err(format!(
+            "recent strategy performance is negative ({:.2})",
+            budget.recent_pnl
+        ));
+    }
+
+    if cpu_usage >= SCALE_UP_CPU_PERCENT {
+        return Ok(format!(
+            "CPU at {:.1}%, projected runway {:.1}h",
+            cpu_usage, runway
+        ));
+    }
+
+    if budget.recent_pnl > 0.0 {
+        return Ok(format!(
+            "recent strategy profit {:.2}, projected runway {:.1}h",
+            budget.recent_pnl, runway
+        ));
+    }
+
+    Err(format!(
+        "no CPU pressure ({:.1}%) and no recent profit",
+        cpu_usage
+    ))
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReplicationStatus {
     pub active_replicas: usize,
     pub total_targets: usize,
     pub recent_failures: usize,
     pub strategy: ReplicationStrategy,
+    pub replicas_deployed: usize,
+    /// 最近一次探测各目标的结果，按 `host:port` 索引
+    #[serde(default)]
+    pub target_probes: HashMap<String, TargetProbe>,
+}
+
+/// 读取副本树记录文件，无法解析的行被跳过
+fn load_lineage(path: &Path) -> Result<Vec<LineageRecord>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(path)?;
+    Ok(content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                warn!("Skipping unreadable lineage record: {}", e);
+                None
+            }
+        })
+        .collect())
+}
+
+fn append_lineage(path: &Path, record: &LineageRecord) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedFleet(usize);
+
+    #[async_trait::async_trait]
+    impl ClusterRegistry for FixedFleet {
+        async fn fleet_size(&self) -> Result<usize> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn test_scaling_requires_runway_and_a_reason() {
+        let budget = Budget {
+            funds: 100.0,
+            hourly_cost: 0.5,
+            recent_pnl: 0.0,
+            ..Budget::default()
+        };
+        // 100 / (0.5 + 0.5) = 100h of runway
+        assert!(evaluate_scaling(&budget, 0.5, 90.0).is_ok());
+        assert!(evaluate_scaling(&budget, 0.5, 10.0).is_err());
+        // 100 / (0.5 + 4.5) = 20h of runway
+        assert!(evaluate_scaling(&budget, 4.5, 90.0).is_err());
+
+        let profitable = Budget {
+            recent_pnl: 5.0,
+            ..budget
+        };
+        assert!(evaluate_scaling(&profitable, 0.5, 10.0).is_ok());
+
+        let losing = Budget {
+            recent_pnl: -5.0,
+            ..budget
+        };
+        assert!(evaluate_scaling(&losing, 0.5, 90.0).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_guardrails_block_runaway_replication() {
+        let root = AgentIdentity::new_root();
+        let replicator = SelfReplicator::with_server_config(PathBuf::from("kernel"), None)
+            .with_identity(root.clone())
+            .with_cluster_registry(Arc::new(FixedFleet(3)));
+        assert_eq!(replicator.replication_allowance().await.unwrap(), 7);
+
+        let full = SelfReplicator::with_server_config(PathBuf::from("kernel"), None)
+            .with_identity(root.clone())
+            .with_cluster_registry(Arc::new(FixedFleet(10)));
+        assert!(full.replicate().await.is_err());
+        assert!(full.trigger_emergency_replication().await.is_err());
+
+        // Replicas are disabled unless their deployed config says otherwise
+        let child = root.spawn_child();
+        let strategy = ReplicationStrategy::load(Path::new("/nonexistent"), &child).unwrap();
+        assert!(!strategy.enabled);
+        // So is any agent that was deployed with the address of its primary
+        let primary_url = Some("http://primary:8080");
+        let strategy =
+            ReplicationStrategy::load_for(Path::new("/nonexistent"), &root, primary_url).unwrap();
+        assert!(!strategy.enabled);
+        let strategy =
+            ReplicationStrategy::load_for(Path::new("/nonexistent"), &root, None).unwrap();
+        assert!(strategy.enabled);
+        // A config file that leaves `enabled` out gets the same default
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replication.json");
+        std::fs::write(&path, r#"{"min_replicas": 1}"#).unwrap();
+        assert!(!ReplicationStrategy::load(&path, &child).unwrap().enabled);
+        assert!(
+            !ReplicationStrategy::load_for(&path, &root, primary_url)
+                .unwrap()
+                .enabled
+        );
+        let strategy = ReplicationStrategy::load_for(&path, &root, None).unwrap();
+        assert!(strategy.enabled);
+        assert_eq!(strategy.min_replicas, 1);
+
+        let grandchild = child.spawn_child();
+        let too_deep = SelfReplicator::with_server_config(PathBuf::from("kernel"), None)
+            .with_identity(grandchild)
+            .with_strategy(ReplicationStrategy::default());
+        assert!(too_deep.replication_allowance().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_http_health_check_distinguishes_unhealthy_from_unreachable() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // A replica whose monitoring API answers every request with 503
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                let _ = stream
+                    .write_all(b"HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n")
+                    .await;
+            }
+        });
+
+        let replicator = |port| {
+            SelfReplicator::with_server_config(PathBuf::from("kernel"), None).with_strategy(
+                ReplicationStrategy {
+                    health_check_port: port,
+                    ..ReplicationStrategy::default()
+                },
+            )
+        };
+        assert!(!replicator(port)
+            .check_replica_http("127.0.0.1")
+            .await
+            .unwrap());
+        // Nothing listens on port 1: the caller falls back to SSH
+        assert!(replicator(1).check_replica_http("127.0.0.1").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_recent_results_are_newest_first() {
+        let replicator = SelfReplicator::with_server_config(PathBuf::from("kernel"), None);
+        for target in ["10.0.0.1", "10.0.0.2", "10.0.0.3"] {
+            replicator
+                .replication_history
+                .write()
+                .await
+                .push(ReplicationResult {
+                    target: target.to_string(),
+                    success: true,
+                    timestamp: Utc::now(),
+                    duration_seconds: 1,
+                    error: None,
+                    agent_id: None,
+                });
+        }
+
+        let recent = replicator.recent_results(2).await;
+        let targets: Vec<_> = recent.iter().map(|r| r.target.as_str()).collect();
+        assert_eq!(targets, vec!["10.0.0.3", "10.0.0.2"]);
+    }
+
+    #[tokio::test]
+    async fn test_lineage_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lineage.jsonl");
+        let root = AgentIdentity::new_root();
+        let replicator = SelfReplicator::with_server_config(PathBuf::from("kernel"), None)
+            .with_identity(root.clone())
+            .with_lineage_file(&path);
+        for target in ["10.0.0.1", "10.0.0.2"] {
+            replicator
+                .record_lineage(LineageRecord {
+                    target: target.to_string(),
+                    identity: root.spawn_child(),
+                })
+                .await;
+        }
+
+        let restarted = SelfReplicator::with_server_config(PathBuf::from("kernel"), None)
+            .with_identity(root)
+            .with_lineage_file(&path);
+        let lineage = restarted.get_lineage().await;
+        let targets: Vec<_> = lineage.iter().map(|r| r.target.as_str()).collect();
+        assert_eq!(targets, vec!["10.0.0.1", "10.0.0.2"]);
+        assert_eq!(
+            lineage[0].identity.agent_id,
+            replicator.get_lineage().await[0].identity.agent_id
+        );
+    }
 }
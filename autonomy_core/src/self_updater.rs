@@ -0,0 +1,466 @@
+//! Self-update of the kernel binary from a release manifest.
+//!
+//! The updater polls `manifest_url` for a [`ReleaseManifest`]. When it names a
+//! binary other than the running one, the binary is downloaded next to the
+//! current one and checked against the manifest's SHA-256 and Ed25519 signature
+//! (see [`common::signing`]) before it is staged.
+//! Activating a staged update saves the agent state, moves the running binary
+//! aside and execs the new one with the same arguments, so the new kernel
+//! resumes from `config/state.json` under the same PID.
+//!
+//! The new kernel must become ready within `health_check_timeout_seconds` of
+//! starting. If it does not, or dies before that, the previous binary is put
+//! back and exec'd, and the release is never installed again.
+
+use crate::ssh_deployer::sha256_hex;
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use common::signing::{self, RELEASE_BINARY_NAME};
+use common::{HealthState, StateStore};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+pub const SELF_UPDATE_CONFIG_PATH: &str = "config/self_update.json";
+/// Where an update in progress and rejected releases are recorded
+pub const SELF_UPDATE_STATE_PATH: &str = "config/self_update_state.json";
+
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SelfUpdateConfig {
+    pub enabled: bool,
+    /// Release manifest to poll; without it the kernel only confirms or rolls
+    /// back an update that is already in progress
+    pub manifest_url: Option<String>,
+    pub check_interval_seconds: u64,
+    /// How long a freshly updated kernel has to become ready
+    pub health_check_timeout_seconds: u64,
+    /// Hex Ed25519 key releases must be signed with; defaults to the key in
+    /// `config/release_key.pub`
+    pub public_key: Option<String>,
+    /// Refuse releases without a valid signature
+    pub require_signature: bool,
+}
+
+impl Default for SelfUpdateConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            manifest_url: None,
+            check_interval_seconds: 3600,
+            health_check_timeout_seconds: 120,
+            public_key: None,
+            require_signature: true,
+        }
+    }
+}
+
+impl SelfUpdateConfig {
+    /// Load the settings from `path`, using the defaults when the file does not exist
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+/// Published next to every kernel release.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseManifest {
+    pub version: String,
+    pub binary_url: String,
+    /// Hex SHA-256 of the binary
+    pub sha256: String,
+    /// Hex Ed25519 signature of the binary, as made by `kernel sign-release`
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+/// An update that was installed but not yet confirmed by a healthy start.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingUpdate {
+    pub version: String,
+    pub sha256: String,
+    /// The binary that was running before, restored on rollback
+    pub previous_binary: PathBuf,
+    pub installed_at: DateTime<Utc>,
+    /// Starts of the new binary so far; a second start means the first one died
+    pub boots: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SelfUpdateState {
+    pub pending: Option<PendingUpdate>,
+    /// SHA-256 of releases that were rolled back
+    pub rejected: Vec<String>,
+}
+
+impl SelfUpdateState {
+    fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+/// A downloaded and verified binary, waiting to be activated.
+#[derive(Debug, Clone)]
+pub struct StagedUpdate {
+    pub manifest: ReleaseManifest,
+    pub path: PathBuf,
+}
+
+/// `kernel` -> `kernel.<suffix>`, in the same directory so renames are atomic
+fn sibling(binary: &Path, suffix: &str) -> PathBuf {
+    let mut name = binary.file_name().map(OsString::from).unwrap_or_default();
+    name.push(".");
+    name.push(suffix);
+    binary.with_file_name(name)
+}
+
+#[derive(Clone)]
+pub struct SelfUpdater {
+    config: SelfUpdateConfig,
+    binary_path: PathBuf,
+    state_path: PathBuf,
+    client: reqwest::Client,
+    state: Option<StateStore>,
+}
+
+impl SelfUpdater {
+    pub fn new(config: SelfUpdateConfig, binary_path: PathBuf) -> Self {
+        Self {
+            config,
+            binary_path,
+            state_path: PathBuf::from(SELF_UPDATE_STATE_PATH),
+            client: reqwest::Client::builder()
+                .timeout(DOWNLOAD_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            state: None,
+        }
+    }
+
+    pub fn with_state_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.state_path = path.into();
+        self
+    }
+
+    /// Save the agent state before exec'ing another binary
+    pub fn with_state_store(mut self, state: StateStore) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Poll the manifest and send the first verified update to `staged`.
+    pub async fn run(self, staged: mpsc::Sender<StagedUpdate>) {
+        let Some(url) = self.config.manifest_url.clone() else {
+            return;
+        };
+        info!(
+            "[Self Update] Checking {} every {}s",
+            url, self.config.check_interval_seconds
+        );
+        let mut interval = tokio::time::interval(Duration::from_secs(
+            self.config.check_interval_seconds.max(1),
+        ));
+        loop {
+            interval.tick().await;
+            match self.check(&url).await {
+                Ok(Some(update)) => {
+                    let _ = staged.send(update).await;
+                    return;
+                }
+                Ok(None) => {}
+                Err(e) => warn!("[Self Update] Update check failed: {:#}", e),
+            }
+        }
+    }
+
+    /// Fetch the manifest and stage its binary if it is new and not rejected.
+    pub async fn check(&self, manifest_url: &str) -> Result<Option<StagedUpdate>> {
+        let manifest: ReleaseManifest = self
+            .client
+            .get(manifest_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .context("Invalid release manifest")?;
+        let wanted = manifest.sha256.to_lowercase();
+        let current = std::fs::read(&self.binary_path)
+            .with_context(|| format!("Failed to read {:?}", self.binary_path))?;
+        if sha256_hex(&current) == wanted {
+            return Ok(None);
+        }
+        if SelfUpdateState::load(&self.state_path)?
+            .rejected
+            .contains(&wanted)
+        {
+            return Ok(None);
+        }
+
+        info!(
+            "[Self Update] Downloading release {} from {}",
+            manifest.version, manifest.binary_url
+        );
+        let binary = self
+            .client
+            .get(&manifest.binary_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        self.stage(manifest, &binary).map(Some)
+    }
+
+    /// Verify `binary` against the manifest and write it next to the running one.
+    pub fn stage(&self, manifest: ReleaseManifest, binary: &[u8]) -> Result<StagedUpdate> {
+        let actual = sha256_hex(binary);
+        if actual != manifest.sha256.to_lowercase() {
+            bail!(
+                "Release {} has SHA-256 {}, the manifest says {}",
+                manifest.version,
+                actual,
+                manifest.sha256
+            );
+        }
+        self.verify_signature(&manifest, binary)?;
+        let path = sibling(&self.binary_path, "staged");
+        std::fs::write(&path, binary)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+        }
+        info!(
+            "[Self Update] Staged release {} at {:?}",
+            manifest.version, path
+        );
+        Ok(StagedUpdate { manifest, path })
+    }
+
+    fn verify_signature(&self, manifest: &ReleaseManifest, binary: &[u8]) -> Result<()> {
+        let trusted = match &self.config.public_key {
+            Some(key) => Some(key.clone()),
+            None => signing::trusted_key(".")?,
+        };
+        match (&manifest.signature, trusted) {
+            (Some(signature), Some(key)) => {
+                signing::verify(&key, RELEASE_BINARY_NAME, binary, signature)
+                    .with_context(|| format!("Release {} is not trusted", manifest.version))?;
+            }
+            _ if self.config.require_signature => bail!(
+                "Release {} cannot be verified: it needs a signature and a trusted release key",
+                manifest.version
+            ),
+            _ => warn!(
+                "[Self Update] Installing release {} without a signature check",
+                manifest.version
+            ),
+        }
+        Ok(())
+    }
+
+    /// Swap the staged binary in, keeping the running one for rollback.
+    pub fn install(&self, update: &StagedUpdate) -> Result<()> {
+        let previous = sibling(&self.binary_path, "previous");
+        let mut record = SelfUpdateState::load(&self.state_path)?;
+        record.pending = Some(PendingUpdate {
+            version: update.manifest.version.clone(),
+            sha256: update.manifest.sha256.to_lowercase(),
+            previous_binary: previous.clone(),
+            installed_at: Utc::now(),
+            boots: 0,
+        });
+        record.save(&self.state_path)?;
+        std::fs::rename(&self.binary_path, &previous)
+            .with_context(|| format!("Failed to move {:?} aside", self.binary_path))?;
+        if let Err(e) = std::fs::rename(&update.path, &self.binary_path) {
+            std::fs::rename(&previous, &self.binary_path)?;
+            record.pending = None;
+            record.save(&self.state_path)?;
+            return Err(e).context("Failed to install the staged binary");
+        }
+        Ok(())
+    }
+
+    /// Install the update and exec it. Only returns if that failed.
+    pub fn activate(&self, update: StagedUpdate) -> Result<Infallible> {
+        info!(
+            "[Self Update] Restarting into release {}",
+            update.manifest.version
+        );
+        self.install(&update)?;
+        let error = match self.exec() {
+            Ok(never) => match never {},
+            Err(e) => e,
+        };
+        // The old binary keeps running, so it must also be the one found on restart
+        self.restore_previous()?;
+        Err(error)
+    }
+
+    /// Put the previous binary back after a failed update and record the release
+    /// as rejected. Returns whether there was an update to undo.
+    pub fn restore_previous(&self) -> Result<bool> {
+        let mut record = SelfUpdateState::load(&self.state_path)?;
+        let Some(pending) = record.pending.take() else {
+            return Ok(false);
+        };
+        if pending.previous_binary.exists() {
+            std::fs::rename(&pending.previous_binary, &self.binary_path)
+                .with_context(|| format!("Failed to restore {:?}", pending.previous_binary))?;
+        } else {
+            error!(
+                "[Self Update] Previous binary {:?} is gone, keeping release {}",
+                pending.previous_binary, pending.version
+            );
+        }
+        if !record.rejected.contains(&pending.sha256) {
+            record.rejected.push(pending.sha256);
+        }
+        record.save(&self.state_path)?;
+        Ok(true)
+    }
+
+    /// On start, confirm an update in progress once `components` are ready, or
+    /// roll back to the previous binary if they are not ready in time.
+    pub async fn confirm(&self, health: &HealthState, components: &[&str]) -> Result<()> {
+        let mut record = SelfUpdateState::load(&self.state_path)?;
+        let Some(pending) = record.pending.as_mut() else {
+            return Ok(());
+        };
+        pending.boots += 1;
+        let version = pending.version.clone();
+        let crashed = pending.boots > 1;
+        record.save(&self.state_path)?;
+
+        if !crashed {
+            let timeout = Duration::from_secs(self.config.health_check_timeout_seconds.max(1));
+            let deadline = tokio::time::Instant::now() + timeout;
+            while tokio::time::Instant::now() < deadline {
+                if health.is_live(timeout) && health.components_ready(components) {
+                    record.pending = None;
+                    record.save(&self.state_path)?;
+                    info!("[Self Update] Release {} is healthy", version);
+                    return Ok(());
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+
+        error!(
+            "[Self Update] Release {} {}, rolling back",
+            version,
+            if crashed {
+                "stopped before becoming healthy"
+            } else {
+                "failed its first health check"
+            }
+        );
+        if self.restore_previous()? {
+            self.exec()?;
+        }
+        Ok(())
+    }
+
+    fn exec(&self) -> Result<Infallible> {
+        if let Some(state) = &self.state {
+            if let Err(e) = state.save() {
+                error!("[Self Update] Failed to save agent state: {}", e);
+            }
+        }
+        exec_binary(&self.binary_path)
+    }
+}
+
+/// Replace the current process with `binary`, passing on the arguments.
+#[cfg(unix)]
+fn exec_binary(binary: &Path) -> Result<Infallible> {
+    use std::os::unix::process::CommandExt;
+    let error = std::process::Command::new(binary)
+        .args(std::env::args_os().skip(1))
+        .exec();
+    Err(anyhow!(error).context(format!("Failed to exec {:?}", binary)))
+}
+
+#[cfg(not(unix))]
+fn exec_binary(_binary: &Path) -> Result<Infallible> {
+    Err(anyhow!(
+        "Restarting into a new binary is only supported on Unix"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{ReleaseSigner, SecretStore};
+
+    #[test]
+    fn test_update_is_verified_installed_and_rolled_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("kernel");
+        std::fs::write(&binary, b"old kernel").unwrap();
+        let signer =
+            ReleaseSigner::load_or_create(&SecretStore::new(dir.path().join("secrets"))).unwrap();
+        let config = SelfUpdateConfig {
+            public_key: Some(signer.public_key()),
+            ..Default::default()
+        };
+        let updater = SelfUpdater::new(config, binary.clone())
+            .with_state_path(dir.path().join("self_update_state.json"));
+
+        let manifest = ReleaseManifest {
+            version: "1.1.0".to_string(),
+            binary_url: "https://releases.example.com/kernel".to_string(),
+            sha256: sha256_hex(b"new kernel").to_uppercase(),
+            signature: None,
+        };
+        assert!(
+            updater.stage(manifest.clone(), b"new kernel").is_err(),
+            "unsigned"
+        );
+        let manifest = ReleaseManifest {
+            signature: Some(signer.sign(RELEASE_BINARY_NAME, b"new kernel")),
+            ..manifest
+        };
+        assert!(updater.stage(manifest.clone(), b"tampered").is_err());
+        let staged = updater.stage(manifest, b"new kernel").unwrap();
+
+        updater.install(&staged).unwrap();
+        assert_eq!(std::fs::read(&binary).unwrap(), b"new kernel");
+        assert!(!staged.path.exists());
+
+        assert!(updater.restore_previous().unwrap());
+        assert_eq!(std::fs::read(&binary).unwrap(), b"old kernel");
+        let record = SelfUpdateState::load(&updater.state_path).unwrap();
+        assert!(record.pending.is_none());
+        assert_eq!(record.rejected, [sha256_hex(b"new kernel")]);
+        assert!(!updater.restore_previous().unwrap(), "nothing left to undo");
+    }
+}
@@ -1,8 +1,11 @@
+use crate::ssh_deployer::{AuthMethod as SshAuth, JumpHost};
 use anyhow::{Context, Result};
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use common::SshTimeouts;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TargetServer {
@@ -23,6 +26,256 @@ pub struct TargetServer {
     pub tags: Vec<String>,
     pub max_retries: u32,
     pub retry_delay_seconds: u64,
+    /// 服务器每小时成本，未设置时使用复制策略中的默认值
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hourly_cost: Option<f64>,
+    /// 首次部署前在服务器上执行的初始化步骤
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provision: Option<ProvisionConfig>,
+    /// 部署方式：直接运行二进制或作为 Docker 容器运行
+    #[serde(default)]
+    pub deploy_method: DeployMethod,
+    /// `deploy_method` 为 `docker` 时的容器设置，未设置时使用默认值
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docker: Option<DockerDeployConfig>,
+    /// 只能经跳板机访问时的跳板机设置
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_jump: Option<ProxyJump>,
+    /// 资源档位，决定部署到该服务器的副本使用的配置
+    #[serde(default)]
+    pub resource_profile: ResourceProfile,
+}
+
+/// 跳板机（bastion），相当于 OpenSSH 的 `ProxyJump`
+///
+/// 先登录跳板机，再经其 direct-tcpip 通道连接目标服务器。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProxyJump {
+    pub host: String,
+    #[serde(default = "default_ssh_port")]
+    pub port: u16,
+    pub username: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_key_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_base64: Option<String>,
+    #[serde(default = "default_auth_method")]
+    pub auth_method: AuthMethod,
+}
+
+fn default_ssh_port() -> u16 {
+    22
+}
+
+impl ProxyJump {
+    /// 获取解码后的跳板机密码
+    pub fn get_password(&self) -> Option<String> {
+        decode_password(self.password_base64.as_deref())
+    }
+
+    /// 连接跳板机所需的地址与认证信息
+    pub fn jump_host(&self) -> Result<JumpHost> {
+        let path = ServerConfig::expand_ssh_key_path(
+            self.ssh_key_path.as_deref().unwrap_or("~/.ssh/id_rsa"),
+        );
+        let auth = match self.auth_method {
+            AuthMethod::Password => SshAuth::Password(self.get_password().ok_or_else(|| {
+                anyhow::anyhow!("Password not available for bastion {}", self.host)
+            })?),
+            AuthMethod::Key => SshAuth::Key {
+                path,
+                passphrase: None,
+            },
+            AuthMethod::KeyWithPassphrase => SshAuth::Key {
+                path,
+                passphrase: self.get_password(),
+            },
+        };
+        Ok(JumpHost {
+            host: self.host.clone(),
+            port: self.port,
+            username: self.username.clone(),
+            auth,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeployMethod {
+    /// 上传二进制，由 systemd 或 nohup 启动
+    #[default]
+    Native,
+    Docker,
+}
+
+/// 在远程主机上以容器方式运行内核
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct DockerDeployConfig {
+    /// 拉取的镜像；未设置时用上传的二进制在远程主机上构建镜像
+    pub image: Option<String>,
+    /// 构建镜像时使用的基础镜像
+    pub base_image: String,
+    pub container_name: String,
+    pub restart_policy: String,
+    /// 额外的 `docker run` 参数，如 `--memory 1g`
+    pub extra_args: Vec<String>,
+}
+
+impl Default for DockerDeployConfig {
+    fn default() -> Self {
+        Self {
+            image: None,
+            base_image: "debian:bookworm-slim".to_string(),
+            container_name: "aurelia".to_string(),
+            restart_policy: "unless-stopped".to_string(),
+            extra_args: Vec::new(),
+        }
+    }
+}
+
+/// 各组件配置文件的路径，与各 crate 中的 `*_CONFIG_PATH` 一致
+pub(crate) const SYMBOL_UNIVERSE_PATH: &str = "config/symbols.json";
+const SAMPLING_CONFIG_PATH: &str = "config/market_sampling.json";
+const MARKET_STORE_CONFIG_PATH: &str = "config/market_store.json";
+const DERIVATIVES_CONFIG_PATH: &str = "config/derivatives.json";
+const MONITORING_CONFIG_PATH: &str = "config/monitoring.json";
+const METAMORPHOSIS_BUILD_CONFIG_PATH: &str = "config/metamorphosis_build.json";
+
+/// 服务器的资源档位，部署时据此为副本生成行情、监控和自我修改的配置
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResourceProfile {
+    /// 1GB 内存左右的小型 VPS：只订阅一个交易对，降低采样频率，不做自我修改
+    Tiny,
+    /// 与各组件的默认配置相同
+    #[default]
+    Standard,
+    /// 资源充足的服务器：更高的采样频率和更长的数据保留时间
+    Performance,
+}
+
+/// 一个资源档位对应的配置取值
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileSettings {
+    /// 最多订阅的交易对数量，`None` 表示与主节点相同
+    pub max_symbols: Option<usize>,
+    /// 每个交易对每秒最多处理的行情事件
+    pub max_events_per_second: u32,
+    pub candle_interval_seconds: u64,
+    pub flush_interval_seconds: u64,
+    pub trade_retention_hours: u64,
+    pub candle_retention_hours: u64,
+    /// 是否订阅衍生品数据流
+    pub derivatives: bool,
+    /// 监控指标历史的保留天数
+    pub metrics_retention_days: u32,
+    /// 是否运行自我修改（需要在本机编译）
+    pub metamorphosis: bool,
+}
+
+impl ResourceProfile {
+    pub fn settings(self) -> ProfileSettings {
+        match self {
+            Self::Tiny => ProfileSettings {
+                max_symbols: Some(1),
+                max_events_per_second: 2,
+                candle_interval_seconds: 300,
+                flush_interval_seconds: 5,
+                trade_retention_hours: 2,
+                candle_retention_hours: 24 * 7,
+                derivatives: false,
+                metrics_retention_days: 1,
+                metamorphosis: false,
+            },
+            Self::Standard => ProfileSettings {
+                max_symbols: None,
+                max_events_per_second: 10,
+                candle_interval_seconds: 60,
+                flush_interval_seconds: 1,
+                trade_retention_hours: 24,
+                candle_retention_hours: 24 * 30,
+                derivatives: true,
+                metrics_retention_days: 1,
+                metamorphosis: true,
+            },
+            Self::Performance => ProfileSettings {
+                max_symbols: None,
+                max_events_per_second: 50,
+                candle_interval_seconds: 60,
+                flush_interval_seconds: 1,
+                trade_retention_hours: 72,
+                candle_retention_hours: 24 * 90,
+                derivatives: true,
+                metrics_retention_days: 7,
+                metamorphosis: true,
+            },
+        }
+    }
+
+    /// 随部署包上传的配置文件，返回 `(内容, 远程路径)`
+    ///
+    /// `symbols` 为主节点订阅的交易对，按档位截取；为空时副本使用默认交易对。
+    /// 只写入档位相关的字段，其余字段在副本上取默认值。
+    pub fn render_configs(self, symbols: &[String]) -> Vec<(String, &'static str)> {
+        let settings = self.settings();
+        let mut configs = vec![
+            (
+                serde_json::json!({
+                    "enabled": true,
+                    "max_events_per_second": settings.max_events_per_second,
+                }),
+                SAMPLING_CONFIG_PATH,
+            ),
+            (
+                serde_json::json!({
+                    "candle_interval_seconds": settings.candle_interval_seconds,
+                    "flush_interval_seconds": settings.flush_interval_seconds,
+                    "trade_retention_hours": settings.trade_retention_hours,
+                    "candle_retention_hours": settings.candle_retention_hours,
+                }),
+                MARKET_STORE_CONFIG_PATH,
+            ),
+            (
+                serde_json::json!({ "enabled": settings.derivatives }),
+                DERIVATIVES_CONFIG_PATH,
+            ),
+            (
+                serde_json::json!({ "metrics_retention_days": settings.metrics_retention_days }),
+                MONITORING_CONFIG_PATH,
+            ),
+            (
+                serde_json::json!({ "enabled": settings.metamorphosis }),
+                METAMORPHOSIS_BUILD_CONFIG_PATH,
+            ),
+        ];
+        if !symbols.is_empty() {
+            let count = settings.max_symbols.unwrap_or(symbols.len());
+            let symbols: Vec<&String> = symbols.iter().take(count).collect();
+            configs.push((
+                serde_json::json!({ "symbols": symbols }),
+                SYMBOL_UNIVERSE_PATH,
+            ));
+        }
+        configs
+            .into_iter()
+            .map(|(config, path)| (serde_json::to_string_pretty(&config).unwrap(), path))
+            .collect()
+    }
+}
+
+/// 服务器初始化：安装依赖、创建用户、开放端口、调整 ulimit 等
+///
+/// 执行成功后在远程部署目录写入标记，内容不变时不再重复执行。
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ProvisionConfig {
+    /// 本地脚本路径，上传后用 `sh` 执行
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<PathBuf>,
+    /// 依次执行的命令，任一命令失败即中止部署
+    #[serde(default)]
+    pub commands: Vec<String>,
 }
 
 fn default_auth_method() -> AuthMethod {
@@ -47,6 +300,29 @@ pub struct DefaultSettings {
     pub retry_delay_seconds: u64,
     pub connection_timeout_seconds: u64,
     pub deployment_timeout_seconds: u64,
+    #[serde(default = "default_read_timeout_seconds")]
+    pub read_timeout_seconds: u64,
+    #[serde(default = "default_command_timeout_seconds")]
+    pub command_timeout_seconds: u64,
+}
+
+fn default_read_timeout_seconds() -> u64 {
+    60
+}
+
+fn default_command_timeout_seconds() -> u64 {
+    300
+}
+
+impl DefaultSettings {
+    /// SSH 连接、读取和命令超时
+    pub fn ssh_timeouts(&self) -> SshTimeouts {
+        SshTimeouts {
+            connect: Duration::from_secs(self.connection_timeout_seconds),
+            read: Duration::from_secs(self.read_timeout_seconds),
+            command: Duration::from_secs(self.command_timeout_seconds),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -169,6 +445,67 @@ impl ServerConfig {
         self.update_server(id, |s| s.enabled = enabled)
     }
 
+    /// 校验配置，返回发现的问题列表（为空表示配置有效）
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut seen_ids = std::collections::HashSet::new();
+
+        for server in &self.target_servers {
+            if !seen_ids.insert(server.id.as_str()) {
+                problems.push(format!("Duplicate server ID '{}'", server.id));
+            }
+            if server.ip.trim().is_empty() {
+                problems.push(format!("Server '{}' has an empty IP address", server.id));
+            }
+            if server.port == 0 {
+                problems.push(format!("Server '{}' has an invalid port 0", server.id));
+            }
+            if server.username.trim().is_empty() {
+                problems.push(format!("Server '{}' has an empty username", server.id));
+            }
+            if server.remote_path.trim().is_empty() {
+                problems.push(format!("Server '{}' has an empty remote_path", server.id));
+            }
+
+            match server.auth_method {
+                AuthMethod::Password => {
+                    if server.get_password().is_none() {
+                        problems.push(format!(
+                            "Server '{}' uses password auth but password_base64 is missing or invalid",
+                            server.id
+                        ));
+                    }
+                }
+                AuthMethod::Key | AuthMethod::KeyWithPassphrase => {
+                    if server.enabled && !server.get_expanded_ssh_key_path().exists() {
+                        problems.push(format!(
+                            "Server '{}' SSH key not found: {:?}",
+                            server.id,
+                            server.get_expanded_ssh_key_path()
+                        ));
+                    }
+                }
+            }
+
+            if let Some(jump) = &server.proxy_jump {
+                if jump.host.trim().is_empty() || jump.username.trim().is_empty() {
+                    problems.push(format!(
+                        "Server '{}' has a proxy_jump without host or username",
+                        server.id
+                    ));
+                }
+                if jump.auth_method == AuthMethod::Password && jump.get_password().is_none() {
+                    problems.push(format!(
+                        "Server '{}' bastion uses password auth but password_base64 is missing or invalid",
+                        server.id
+                    ));
+                }
+            }
+        }
+
+        problems
+    }
+
     /// 展开SSH密钥路径（处理~符号）
     pub fn expand_ssh_key_path(path: &str) -> PathBuf {
         if path.starts_with("~") {
@@ -198,6 +535,20 @@ impl TargetServer {
             tags: Vec::new(),
             max_retries: 3,
             retry_delay_seconds: 60,
+            hourly_cost: None,
+            provision: None,
+            deploy_method: DeployMethod::Native,
+            docker: None,
+            proxy_jump: None,
+            resource_profile: ResourceProfile::Standard,
+        }
+    }
+
+    /// 容器部署的设置；以原生方式部署时返回 `None`
+    pub fn docker_config(&self) -> Option<DockerDeployConfig> {
+        match self.deploy_method {
+            DeployMethod::Native => None,
+            DeployMethod::Docker => Some(self.docker.clone().unwrap_or_default()),
         }
     }
 
@@ -230,12 +581,7 @@ impl TargetServer {
 
     /// 获取解码后的密码
     pub fn get_password(&self) -> Option<String> {
-        self.password_base64.as_ref().and_then(|encoded| {
-            BASE64
-                .decode(encoded)
-                .ok()
-                .and_then(|bytes| String::from_utf8(bytes).ok())
-        })
+        decode_password(self.password_base64.as_deref())
     }
 
     /// 生成部署信息
@@ -252,6 +598,15 @@ impl TargetServer {
     }
 }
 
+fn decode_password(encoded: Option<&str>) -> Option<String> {
+    encoded.and_then(|encoded| {
+        BASE64
+            .decode(encoded)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -274,7 +629,11 @@ mod tests {
                     "priority": 1,
                     "tags": ["test"],
                     "max_retries": 3,
-                    "retry_delay_seconds": 60
+                    "retry_delay_seconds": 60,
+                    "provision": {"commands": ["sudo ufw allow 8080/tcp"]},
+                    "deploy_method": "docker",
+                    "docker": {"image": "ghcr.io/example/aurelia:latest"},
+                    "proxy_jump": {"host": "2001:db8::10", "username": "jump"}
                 }
             ],
             "default_settings": {
@@ -308,6 +667,27 @@ mod tests {
         let config = ServerConfig::from_file(&file_path).unwrap();
         assert_eq!(config.target_servers.len(), 1);
         assert_eq!(config.target_servers[0].id, "test-1");
+        let provision = config.target_servers[0].provision.as_ref().unwrap();
+        assert_eq!(provision.commands, vec!["sudo ufw allow 8080/tcp"]);
+        assert!(provision.script.is_none());
+        let docker = config.target_servers[0].docker_config().unwrap();
+        assert_eq!(
+            docker.image.as_deref(),
+            Some("ghcr.io/example/aurelia:latest")
+        );
+        assert_eq!(docker.restart_policy, "unless-stopped");
+        let jump = config.target_servers[0].proxy_jump.as_ref().unwrap();
+        assert_eq!(jump.port, 22);
+        assert_eq!(jump.auth_method, AuthMethod::Key);
+        let jump_host = jump.jump_host().unwrap();
+        assert_eq!(jump_host.host, "2001:db8::10");
+        assert!(matches!(
+            jump_host.auth,
+            SshAuth::Key {
+                passphrase: None,
+                ..
+            }
+        ));
     }
 
     #[test]
@@ -323,6 +703,8 @@ mod tests {
                 retry_delay_seconds: 60,
                 connection_timeout_seconds: 30,
                 deployment_timeout_seconds: 300,
+                read_timeout_seconds: 60,
+                command_timeout_seconds: 300,
             },
             deployment_strategy: DeploymentStrategy {
                 strategy_type: "progressive".to_string(),
@@ -361,4 +743,87 @@ mod tests {
         config.remove_server("test-1").unwrap();
         assert_eq!(config.target_servers.len(), 0);
     }
+
+    #[test]
+    fn test_validate_config() {
+        let mut config = ServerConfig {
+            target_servers: vec![],
+            default_settings: DefaultSettings {
+                port: 22,
+                username: "ubuntu".to_string(),
+                ssh_key_path: "~/.ssh/id_rsa".to_string(),
+                remote_path: "/home/ubuntu".to_string(),
+                max_retries: 3,
+                retry_delay_seconds: 60,
+                connection_timeout_seconds: 30,
+                deployment_timeout_seconds: 300,
+                read_timeout_seconds: 60,
+                command_timeout_seconds: 300,
+            },
+            deployment_strategy: DeploymentStrategy {
+                strategy_type: "progressive".to_string(),
+                parallel_deployments: 2,
+                delay_between_deployments_seconds: 30,
+                health_check_after_deployment: true,
+                rollback_on_failure: true,
+            },
+            ssh_config: SshConfig {
+                strict_host_key_checking: false,
+                compression: true,
+                keepalive_interval_seconds: 60,
+            },
+        };
+
+        let server = TargetServer::new_with_password(
+            "test-1".to_string(),
+            "Test Server".to_string(),
+            "192.168.1.100".to_string(),
+            "test".to_string(),
+            "secret".to_string(),
+        );
+        config.target_servers.push(server.clone());
+        assert!(config.validate().is_empty());
+
+        // 重复ID与缺失密码都应被报告
+        let mut duplicate = server;
+        duplicate.password_base64 = None;
+        config.target_servers.push(duplicate);
+        assert_eq!(config.validate().len(), 2);
+    }
+
+    #[test]
+    fn test_resource_profile_configs() {
+        let server: TargetServer = serde_json::from_value(serde_json::json!({
+            "id": "vps-1", "name": "VPS", "ip": "10.0.0.2", "port": 22,
+            "username": "ubuntu", "remote_path": "/opt/aurelia", "enabled": true,
+            "priority": 1, "tags": [], "max_retries": 3, "retry_delay_seconds": 60,
+            "resource_profile": "tiny"
+        }))
+        .unwrap();
+        assert_eq!(server.resource_profile, ResourceProfile::Tiny);
+
+        let symbols = vec!["BTCUSDT".to_string(), "ETHUSDT".to_string()];
+        let configs: std::collections::HashMap<&str, serde_json::Value> = server
+            .resource_profile
+            .render_configs(&symbols)
+            .into_iter()
+            .map(|(contents, path)| (path, serde_json::from_str(&contents).unwrap()))
+            .collect();
+        assert_eq!(
+            configs[SYMBOL_UNIVERSE_PATH]["symbols"],
+            serde_json::json!(["BTCUSDT"])
+        );
+        assert_eq!(configs[METAMORPHOSIS_BUILD_CONFIG_PATH]["enabled"], false);
+        assert_eq!(configs[DERIVATIVES_CONFIG_PATH]["enabled"], false);
+
+        // 默认档位保留主节点的全部交易对，未配置交易对时不覆盖副本的默认值
+        let standard = ResourceProfile::default().render_configs(&symbols);
+        assert!(standard.iter().any(|(contents, path)| {
+            *path == SYMBOL_UNIVERSE_PATH && contents.contains("ETHUSDT")
+        }));
+        assert!(ResourceProfile::Performance
+            .render_configs(&[])
+            .iter()
+            .all(|(_, path)| *path != SYMBOL_UNIVERSE_PATH));
+    }
 }
@@ -1,10 +1,71 @@
+use crate::self_replicator::{ReplicationStrategy, REPLICATION_CONFIG_PATH};
+use crate::server_config::{
+    DockerDeployConfig, ProvisionConfig, ResourceProfile, SYMBOL_UNIVERSE_PATH,
+};
+use crate::ssh_tunnel;
 use anyhow::{Context, Result};
-use ssh2::{Session, Sftp};
+use base64::{engine::general_purpose::STANDARD_NO_PAD, Engine as _};
+use common::audit::{self, AuditCategory};
+use common::bundle::RenderedFile;
+use common::identity::PRIMARY_URL_ENV;
+use common::sealed_config::{FLEET_KEY_SECRET, FLEET_KEY_URL_ENV, SEALED_CONFIG_PATH};
+use common::secrets::SECRET_ENV_PREFIX;
+use common::signing::{
+    self, BundleSignatures, PINNED_KEY_PATH, RELEASE_BINARY_NAME, SIGNATURES_PATH,
+};
+use common::ssh::{
+    connect_tcp, polling, read_outputs, shell_quote, shell_quote_path, write_all_cancellable,
+};
+use common::state_store::STATE_PATH;
+use common::{
+    host_port, CancellationToken, DeploymentBundle, ReleaseSigner, SessionLease,
+    SshConnectionManager, SshTimeouts,
+};
+use qbsdiff::Bsdiff;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use ssh2::{CheckResult, HashType, KnownHostFileKind, KnownHosts, Session, Sftp};
+use std::fmt;
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{BufRead, BufReader, Cursor, ErrorKind, Read};
 use std::net::TcpStream;
 use std::path::{Path, PathBuf};
-use tracing::{info, warn};
+use std::time::Duration;
+use tracing::{info, info_span, warn};
+
+/// systemd restarts the kernel if it misses watchdog keepalives for this long
+const SYSTEMD_WATCHDOG_SECS: u64 = 30;
+
+/// Environment file every way of starting the kernel reads, where an operator
+/// puts `AURELIA_SECRET_FLEET_CONFIG_KEY` or, on servers with instance
+/// metadata, `AURELIA_FLEET_KEY_URL`
+pub const FLEET_KEY_ENV_FILE: &str = "/etc/aurelia/fleet.env";
+
+/// Number of previously deployed binaries kept as delta upload bases
+const MAX_CACHED_ARTIFACTS: usize = 5;
+
+/// Lines of history sent before following a remote log
+const LOG_STREAM_BACKLOG_LINES: usize = 20;
+
+/// Directory under the deployment path holding provisioning markers
+const PROVISION_MARKER_DIR: &str = ".provisioned";
+
+/// Tag of images built on the remote host from the uploaded kernel
+const LOCAL_IMAGE_TAG: &str = "aurelia-kernel:latest";
+
+/// File at the top of every deployment directory, checked before one is removed
+const DEPLOYMENT_MARKER: &str = "kernel";
+
+/// Where the deployment directory is mounted inside the container
+const CONTAINER_DEPLOY_PATH: &str = "/opt/aurelia";
+
+/// Free space kept on top of twice the binary size, which a delta upload
+/// needs for the patched copy next to the old one
+const PREFLIGHT_DISK_HEADROOM: u64 = 64 * 1024 * 1024;
+
+/// Host keys the agent trusts. Kept apart from `~/.ssh/known_hosts`, which
+/// rewriting through libssh2 could reformat or strip of hashed and marker entries.
+pub const KNOWN_HOSTS_PATH: &str = "data/known_hosts";
 
 /// Pure Rust SSH deployment capability
 /// Allows the kernel to deploy itself to remote servers without external scripts
@@ -12,6 +73,28 @@ pub struct SshDeployer {
     session: Session,
     sftp: Option<Sftp>,
     connected: bool,
+    known_hosts_path: PathBuf,
+    strict_host_key_checking: bool,
+    timeouts: SshTimeouts,
+    cancel: CancellationToken,
+    artifact_cache: Option<PathBuf>,
+    signer: Option<ReleaseSigner>,
+    /// Sizes the configuration rendered for the deployed agent
+    resource_profile: Option<ResourceProfile>,
+    /// Bastion the target server is reached through
+    proxy_jump: Option<JumpHost>,
+    /// Authenticated sessions shared with other deployers and monitors
+    pool: Option<SshConnectionManager>,
+    /// Keeps `session` to this deployer while it is pooled
+    lease: Option<SessionLease>,
+    /// `user@host:port` of the connected server, for the audit log
+    remote: String,
+    /// `{{agent_id}}` in the templates of deployed bundles
+    agent_id: Option<String>,
+    /// `{{primary_address}}` in the templates of deployed bundles
+    primary_address: Option<String>,
+    /// Monitoring API port of the deployed agent, `{{port}}` in templates
+    api_port: u16,
 }
 
 impl Default for SshDeployer {
@@ -27,9 +110,98 @@ impl SshDeployer {
             session: Session::new().unwrap(),
             sftp: None,
             connected: false,
+            known_hosts_path: PathBuf::from(KNOWN_HOSTS_PATH),
+            strict_host_key_checking: false,
+            timeouts: SshTimeouts::default(),
+            cancel: CancellationToken::new(),
+            artifact_cache: None,
+            signer: None,
+            resource_profile: None,
+            proxy_jump: None,
+            pool: None,
+            lease: None,
+            remote: String::new(),
+            agent_id: None,
+            primary_address: None,
+            api_port: ReplicationStrategy::default().health_check_port,
         }
     }
 
+    /// Sign deployed kernels and verify them on the server before they start
+    pub fn with_signer(mut self, signer: ReleaseSigner) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Ship perception, monitoring and metamorphosis settings sized for the
+    /// server with every deployed kernel
+    pub fn with_resource_profile(mut self, profile: ResourceProfile) -> Self {
+        self.resource_profile = Some(profile);
+        self
+    }
+
+    /// Keep deployed binaries in `dir` and ship later versions as bsdiff patches
+    /// against them when the remote host has `bspatch`
+    pub fn with_artifact_cache(mut self, dir: PathBuf) -> Self {
+        self.artifact_cache = Some(dir);
+        self
+    }
+
+    /// Use the given connect, read and command timeouts
+    pub fn with_timeouts(mut self, timeouts: SshTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Abort remote commands and uploads once `cancel` fires
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Refuse unknown host keys instead of trusting them on first use. Changed
+    /// keys are refused either way.
+    pub fn with_strict_host_key_checking(mut self, strict: bool) -> Self {
+        self.strict_host_key_checking = strict;
+        self
+    }
+
+    /// Reach the server through a bastion, like OpenSSH's `ProxyJump`
+    pub fn with_proxy_jump(mut self, jump: JumpHost) -> Self {
+        self.proxy_jump = Some(jump);
+        self
+    }
+
+    /// Reuse authenticated sessions from `pool` and add new ones to it
+    pub fn with_connection_pool(mut self, pool: SshConnectionManager) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Identify the deployed agent to templates as `{{agent_id}}`
+    pub fn with_agent_id(mut self, agent_id: impl Into<String>) -> Self {
+        self.agent_id = Some(agent_id.into());
+        self
+    }
+
+    /// Tell deployed agents where the primary is, as `{{primary_address}}` in templates
+    pub fn with_primary_address(mut self, address: impl Into<String>) -> Self {
+        self.primary_address = Some(address.into());
+        self
+    }
+
+    /// Monitoring API port of the deployed agent, as `{{port}}` in templates
+    pub fn with_api_port(mut self, port: u16) -> Self {
+        self.api_port = port;
+        self
+    }
+
+    /// Use a known_hosts file other than [`KNOWN_HOSTS_PATH`]
+    pub fn with_known_hosts_file(mut self, path: PathBuf) -> Self {
+        self.known_hosts_path = path;
+        self
+    }
+
     /// Connect to a remote server using SSH key authentication
     pub fn connect_with_key(
         &mut self,
@@ -39,14 +211,30 @@ impl SshDeployer {
         private_key_path: &Path,
         passphrase: Option<&str>,
     ) -> Result<()> {
-        info!("Connecting to {}:{} as user {}", host, port, username);
+        self.connect_guarded(host, port, username, |this| {
+            this.open_with_key(host, port, username, private_key_path, passphrase)
+        })
+    }
 
-        // Establish TCP connection
-        let tcp = TcpStream::connect(format!("{}:{}", host, port))
-            .context("Failed to establish TCP connection")?;
+    fn open_with_key(
+        &mut self,
+        host: &str,
+        port: u16,
+        username: &str,
+        private_key_path: &Path,
+        passphrase: Option<&str>,
+    ) -> Result<()> {
+        info!(
+            "Connecting to {} as user {}",
+            host_port(host, port),
+            username
+        );
 
+        let tcp = self.open_stream(host, port)?;
         self.session.set_tcp_stream(tcp);
+        self.session.set_timeout(self.timeouts.session_timeout_ms());
         self.session.handshake().context("SSH handshake failed")?;
+        self.verify_host_key(&self.session, host, port)?;
 
         // Try public key authentication
         if private_key_path.exists() {
@@ -73,6 +261,10 @@ impl SshDeployer {
         }
 
         self.connected = true;
+        self.remote = SshConnectionManager::key(username, host, port);
+        if let Some(pool) = &self.pool {
+            self.lease = Some(pool.insert(&self.remote, self.session.clone()));
+        }
         info!("Successfully connected and authenticated to {}", host);
         Ok(())
     }
@@ -84,18 +276,30 @@ impl SshDeployer {
         port: u16,
         username: &str,
         password: &str,
+    ) -> Result<()> {
+        self.connect_guarded(host, port, username, |this| {
+            this.open_with_password(host, port, username, password)
+        })
+    }
+
+    fn open_with_password(
+        &mut self,
+        host: &str,
+        port: u16,
+        username: &str,
+        password: &str,
     ) -> Result<()> {
         info!(
-            "Connecting to {}:{} as user {} with password",
-            host, port, username
+            "Connecting to {} as user {} with password",
+            host_port(host, port),
+            username
         );
 
-        // Establish TCP connection
-        let tcp = TcpStream::connect(format!("{}:{}", host, port))
-            .context("Failed to establish TCP connection")?;
-
+        let tcp = self.open_stream(host, port)?;
         self.session.set_tcp_stream(tcp);
+        self.session.set_timeout(self.timeouts.session_timeout_ms());
         self.session.handshake().context("SSH handshake failed")?;
+        self.verify_host_key(&self.session, host, port)?;
 
         // Password authentication
         self.session
@@ -107,12 +311,265 @@ impl SshDeployer {
         }
 
         self.connected = true;
+        self.remote = SshConnectionManager::key(username, host, port);
+        if let Some(pool) = &self.pool {
+            self.lease = Some(pool.insert(&self.remote, self.session.clone()));
+        }
         info!("Successfully connected and authenticated to {}", host);
         Ok(())
     }
 
+    /// Reuse a pooled session or connect with `connect`. With a pool, connecting
+    /// goes through the server's circuit breaker and is refused while it is open.
+    fn connect_guarded(
+        &mut self,
+        host: &str,
+        port: u16,
+        username: &str,
+        connect: impl FnOnce(&mut Self) -> Result<()>,
+    ) -> Result<()> {
+        if self.reuse_pooled_session(host, port, username) {
+            return Ok(());
+        }
+        let key = SshConnectionManager::key(username, host, port);
+        let Some(breaker) = self.pool.as_ref().map(|pool| pool.breaker(&key)) else {
+            return connect(self);
+        };
+        if !breaker.try_acquire() {
+            return Err(anyhow::anyhow!(
+                "Circuit open for {}, not connecting until it recovers",
+                key
+            ));
+        }
+        let result = connect(self);
+        match &result {
+            Ok(()) => breaker.record_success(),
+            Err(_) => breaker.record_failure(),
+        }
+        result
+    }
+
+    /// Take over a healthy pooled session to the server instead of connecting again
+    fn reuse_pooled_session(&mut self, host: &str, port: u16, username: &str) -> bool {
+        let key = SshConnectionManager::key(username, host, port);
+        let timeout_ms = self.timeouts.session_timeout_ms();
+        let Some(lease) = self
+            .pool
+            .as_ref()
+            .and_then(|pool| pool.checkout(&key, timeout_ms))
+        else {
+            return false;
+        };
+        self.session = (*lease).clone();
+        self.lease = Some(lease);
+        self.sftp = None;
+        self.connected = true;
+        info!("Reusing authenticated SSH session to {}", key);
+        self.remote = key;
+        true
+    }
+
+    /// Record the host key of a server as trusted, replacing any previous entry
+    ///
+    /// This is the explicit acceptance step for servers whose key is unknown or has
+    /// legitimately changed. Returns the SHA256 fingerprint of the accepted key.
+    pub fn trust_host_key(&mut self, host: &str, port: u16) -> Result<String> {
+        let tcp = self.open_stream(host, port)?;
+        self.session.set_tcp_stream(tcp);
+        self.session.set_timeout(self.timeouts.session_timeout_ms());
+        self.session.handshake().context("SSH handshake failed")?;
+
+        let mut known_hosts = self.load_known_hosts(&self.session)?;
+        self.record_host_key(&self.session, &mut known_hosts, host, port)?;
+
+        let fingerprint = host_key_fingerprint(&self.session);
+        info!(
+            "Trusted host key {} for {}",
+            fingerprint,
+            host_port(host, port)
+        );
+        Ok(fingerprint)
+    }
+
+    /// TCP stream to the server, tunnelled through the bastion if there is one
+    ///
+    /// The bastion's host key is checked against the same known_hosts file and
+    /// policy as the target's. Other ports on the server, such as its HTTP API,
+    /// can be reached the same way.
+    pub fn open_stream(&self, host: &str, port: u16) -> Result<TcpStream> {
+        let Some(jump) = &self.proxy_jump else {
+            return connect_tcp(host, port, self.timeouts.connect)
+                .context("Failed to establish TCP connection");
+        };
+        info!(
+            "Connecting to {} through bastion {}",
+            host_port(host, port),
+            host_port(&jump.host, jump.port)
+        );
+
+        let tcp = connect_tcp(&jump.host, jump.port, self.timeouts.connect)
+            .context("Failed to establish TCP connection to the bastion")?;
+        let mut bastion = Session::new().context("Failed to create SSH session")?;
+        bastion.set_tcp_stream(tcp);
+        bastion.set_timeout(self.timeouts.session_timeout_ms());
+        bastion
+            .handshake()
+            .context("SSH handshake with the bastion failed")?;
+        self.verify_host_key(&bastion, &jump.host, jump.port)?;
+
+        match &jump.auth {
+            AuthMethod::Password(password) => bastion
+                .userauth_password(&jump.username, password)
+                .context("Bastion password authentication failed")?,
+            AuthMethod::Key { path, passphrase } => bastion
+                .userauth_pubkey_file(&jump.username, None, path, passphrase.as_deref())
+                .context("Bastion SSH key authentication failed")?,
+        }
+        if !bastion.authenticated() {
+            return Err(anyhow::anyhow!("Bastion authentication failed"));
+        }
+
+        ssh_tunnel::open(bastion, host, port).with_context(|| {
+            format!(
+                "Bastion {} could not reach {}",
+                host_port(&jump.host, jump.port),
+                host_port(host, port)
+            )
+        })
+    }
+
+    /// Check a server's host key against the known_hosts file
+    fn verify_host_key(&self, session: &Session, host: &str, port: u16) -> Result<()> {
+        let (key, _) = session
+            .host_key()
+            .ok_or_else(|| anyhow::anyhow!("Server did not present a host key"))?;
+        let mut known_hosts = self.load_known_hosts(session)?;
+        let fingerprint = host_key_fingerprint(session);
+
+        match known_hosts.check_port(host, port, key) {
+            CheckResult::Match => Ok(()),
+            // Refused even without strict checking: trusting a changed key on use
+            // would leave nothing for the check to protect against
+            CheckResult::Mismatch => Err(anyhow::anyhow!(
+                "Host key for {}:{} ({}) does not match {:?}, possible man-in-the-middle attack; \
+                 if the key legitimately changed, accept it with `kernel trust-host <server-id>`",
+                host,
+                port,
+                fingerprint,
+                self.known_hosts_path
+            )),
+            CheckResult::NotFound if self.strict_host_key_checking => Err(anyhow::anyhow!(
+                "Host key for {}:{} ({}) is unknown; accept it with `kernel trust-host <server-id>`",
+                host,
+                port,
+                fingerprint
+            )),
+            CheckResult::NotFound => {
+                info!(
+                    "Trusting new host key {} for {}:{} on first use",
+                    fingerprint, host, port
+                );
+                self.record_host_key(session, &mut known_hosts, host, port)
+            }
+            CheckResult::Failure => Err(anyhow::anyhow!(
+                "Failed to check host key for {}:{}",
+                host,
+                port
+            )),
+        }
+    }
+
+    fn load_known_hosts(&self, session: &Session) -> Result<KnownHosts> {
+        let mut known_hosts = session
+            .known_hosts()
+            .context("Failed to initialise known hosts")?;
+        if self.known_hosts_path.exists() {
+            known_hosts
+                .read_file(&self.known_hosts_path, KnownHostFileKind::OpenSSH)
+                .with_context(|| format!("Failed to read {:?}", self.known_hosts_path))?;
+        }
+        Ok(known_hosts)
+    }
+
+    fn record_host_key(
+        &self,
+        session: &Session,
+        known_hosts: &mut KnownHosts,
+        host: &str,
+        port: u16,
+    ) -> Result<()> {
+        let (key, key_type) = session
+            .host_key()
+            .ok_or_else(|| anyhow::anyhow!("Server did not present a host key"))?;
+        let name = known_host_name(host, port);
+
+        // Drop stale entries for this host before adding the new key
+        for entry in known_hosts.hosts()? {
+            if entry.name() == Some(name.as_str()) {
+                known_hosts.remove(&entry)?;
+            }
+        }
+        known_hosts.add(&name, key, "added by aurelia", key_type.into())?;
+
+        if let Some(parent) = self.known_hosts_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        known_hosts
+            .write_file(&self.known_hosts_path, KnownHostFileKind::OpenSSH)
+            .with_context(|| format!("Failed to write {:?}", self.known_hosts_path))?;
+        Ok(())
+    }
+
     /// Execute a command on the remote server
     pub fn execute_command(&self, command: &str) -> Result<String> {
+        let (output, exit_status) = self.run_command(command)?;
+        if exit_status != 0 {
+            warn!("Command '{}' exited with status {}", command, exit_status);
+        }
+        Ok(output)
+    }
+
+    /// Execute a command and fail if it exits with a non-zero status
+    pub fn execute_checked(&self, command: &str) -> Result<String> {
+        let (output, exit_status) = self.run_command(command)?;
+        if exit_status != 0 {
+            return Err(anyhow::anyhow!(
+                "Command '{}' exited with status {}: {}",
+                command,
+                exit_status,
+                output.trim()
+            ));
+        }
+        Ok(output)
+    }
+
+    fn run_command(&self, command: &str) -> Result<(String, i32)> {
+        self.execute_captured(command, self.timeouts.command)
+            .map(|output| (output.stdout, output.exit_status))
+    }
+
+    /// Execute a command, keeping its stdout and stderr apart, and give up once
+    /// `timeout` has elapsed
+    pub fn execute_captured(&self, command: &str, timeout: Duration) -> Result<CommandOutput> {
+        let result = self.exec_remote(command, timeout);
+        audit::record(
+            AuditCategory::SshCommand,
+            command,
+            serde_json::json!({
+                "remote": self.remote,
+                "exit_status": result.as_ref().ok().map(|output| output.exit_status),
+                "error": result.as_ref().err().map(|e| e.to_string()),
+            }),
+        );
+        // The next connect replaces a session that failed mid-command
+        if let (Err(_), Some(pool)) = (&result, &self.pool) {
+            pool.invalidate(&self.remote);
+            pool.breaker(&self.remote).record_failure();
+        }
+        result
+    }
+
+    fn exec_remote(&self, command: &str, timeout: Duration) -> Result<CommandOutput> {
         if !self.connected {
             return Err(anyhow::anyhow!("Not connected to remote server"));
         }
@@ -124,19 +581,30 @@ impl SshDeployer {
 
         channel.exec(command).context("Failed to execute command")?;
 
-        let mut output = String::new();
-        channel
-            .read_to_string(&mut output)
-            .context("Failed to read command output")?;
+        let output = polling(&self.session, || {
+            read_outputs(
+                &mut channel.stream(0),
+                &mut channel.stderr(),
+                timeout,
+                &self.cancel,
+            )
+        });
+        let (stdout, stderr) = match output {
+            Ok(output) => output,
+            Err(e) => {
+                let _ = channel.close();
+                return Err(e).with_context(|| format!("Command '{}' aborted", command));
+            }
+        };
 
         channel.wait_close()?;
         let exit_status = channel.exit_status()?;
 
-        if exit_status != 0 {
-            warn!("Command '{}' exited with status {}", command, exit_status);
-        }
-
-        Ok(output)
+        Ok(CommandOutput {
+            stdout,
+            stderr,
+            exit_status,
+        })
     }
 
     /// Create a directory on the remote server
@@ -155,7 +623,23 @@ impl SshDeployer {
 
         info!("Uploading {:?} to {}", local_path, remote_path);
 
-        // Initialize SFTP if not already done
+        // Read local file
+        let mut local_file = File::open(local_path).context("Failed to open local file")?;
+        let mut contents = Vec::new();
+        local_file
+            .read_to_end(&mut contents)
+            .context("Failed to read local file")?;
+
+        self.upload_bytes(&contents, remote_path)
+    }
+
+    /// Read a file from the remote server
+    pub fn download_file(&mut self, remote_path: &str) -> Result<Vec<u8>> {
+        if !self.connected {
+            return Err(anyhow::anyhow!("Not connected to remote server"));
+        }
+
+        info!("Downloading {}", remote_path);
         if self.sftp.is_none() {
             self.sftp = Some(
                 self.session
@@ -163,15 +647,36 @@ impl SshDeployer {
                     .context("Failed to create SFTP session")?,
             );
         }
-
         let sftp = self.sftp.as_ref().unwrap();
 
-        // Read local file
-        let mut local_file = File::open(local_path).context("Failed to open local file")?;
+        let mut remote_file = sftp
+            .open(Path::new(remote_path))
+            .context("Failed to open remote file")?;
         let mut contents = Vec::new();
-        local_file
+        remote_file
             .read_to_end(&mut contents)
-            .context("Failed to read local file")?;
+            .context("Failed to read remote file")?;
+
+        info!("Successfully downloaded {} bytes", contents.len());
+        Ok(contents)
+    }
+
+    /// Write `contents` to a file on the remote server
+    pub fn upload_bytes(&mut self, contents: &[u8], remote_path: &str) -> Result<()> {
+        if !self.connected {
+            return Err(anyhow::anyhow!("Not connected to remote server"));
+        }
+
+        // Initialize SFTP if not already done
+        if self.sftp.is_none() {
+            self.sftp = Some(
+                self.session
+                    .sftp()
+                    .context("Failed to create SFTP session")?,
+            );
+        }
+
+        let sftp = self.sftp.as_ref().unwrap();
 
         // Create remote file
         let mut remote_file = sftp
@@ -179,8 +684,7 @@ impl SshDeployer {
             .context("Failed to create remote file")?;
 
         // Write contents
-        remote_file
-            .write_all(&contents)
+        write_all_cancellable(&mut remote_file, contents, &self.cancel)
             .context("Failed to write to remote file")?;
 
         info!("Successfully uploaded {} bytes", contents.len());
@@ -196,34 +700,304 @@ impl SshDeployer {
     ) -> Result<()> {
         info!("Starting kernel deployment to {}", remote_path);
 
+        // Nothing is uploaded to a server the kernel cannot run on
+        let report = self.preflight(local_binary, remote_path)?;
+        if !report.passed() {
+            return Err(report.into());
+        }
+
+        // Deployed kernels are replicas, which must not replicate unless enabled explicitly
+        let replication =
+            serde_json::to_string_pretty(&ReplicationStrategy::default().for_replica())?;
+        let mut bundle = DeploymentBundle::new()
+            .executable(local_binary, "kernel")
+            .template(replication, REPLICATION_CONFIG_PATH);
+        // Config files passed explicitly take precedence over the profile's
+        if let Some(profile) = self.resource_profile {
+            info!("Rendering config for the {:?} resource profile", profile);
+            for (contents, destination) in profile.render_configs(&primary_symbols()) {
+                bundle = bundle.template(contents, destination);
+            }
+        }
+        for config in config_files.unwrap_or_default() {
+            if config.exists() {
+                let filename = config.file_name().unwrap().to_str().unwrap();
+                let destination = format!("config/{}", filename);
+                // Funds and allocations belong to the agent that earned them
+                if destination == STATE_PATH {
+                    warn!(
+                        "Not deploying {:?}, the replica starts its own state",
+                        config
+                    );
+                    continue;
+                }
+                bundle = bundle.file(&config, destination);
+            }
+        }
+        // Exchange keys only travel sealed with the fleet key
+        if Path::new(SEALED_CONFIG_PATH).exists() {
+            if !self.has_fleet_key_source()? {
+                return Err(anyhow::anyhow!(
+                    "{} is deployed but {} on the server sets neither {}{} nor {}; the kernel could not unseal it",
+                    SEALED_CONFIG_PATH,
+                    FLEET_KEY_ENV_FILE,
+                    SECRET_ENV_PREFIX,
+                    FLEET_KEY_SECRET.to_uppercase(),
+                    FLEET_KEY_URL_ENV
+                ));
+            }
+            bundle = bundle.file(SEALED_CONFIG_PATH, SEALED_CONFIG_PATH);
+        }
+        if let Some(signer) = &self.signer {
+            bundle = bundle.signed_by(signer.clone());
+        }
+
+        self.deploy_bundle(&bundle, remote_path)?;
+
+        info!("Kernel deployment completed successfully");
+        Ok(())
+    }
+
+    /// Check that the server has room for `local_binary`, a new enough glibc to
+    /// run it, a writable `remote_path` and a free monitoring port
+    ///
+    /// Runs read-only commands only; a server failing any check gets a report
+    /// whose [`PreflightReport::passed`] is false rather than an error.
+    pub fn preflight(&self, local_binary: &Path, remote_path: &str) -> Result<PreflightReport> {
+        let binary = std::fs::read(local_binary).context("Failed to read local binary")?;
+        let output = self.execute_command(&preflight_script(remote_path))?;
+        let report = evaluate_preflight(
+            &output,
+            binary.len() as u64,
+            required_glibc(&binary),
+            &[self.api_port],
+        );
+        for check in &report.checks {
+            if check.passed {
+                info!("Preflight {}: {}", check.name, check.detail);
+            } else {
+                warn!("Preflight {} failed: {}", check.name, check.detail);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Upload a rendered bundle into `remote_path`
+    ///
+    /// Templates can use the server's `{{agent_id}}`, `{{primary_address}}`,
+    /// `{{port}}` and `{{remote_path}}` unless the bundle sets them itself.
+    /// Executables go through the checksum/delta path of [`SshDeployer::upload_binary`].
+    /// Once a signed bundle is uploaded, the digest of every file on the server is
+    /// checked against the signed contents; nothing uploaded is run to do so.
+    pub fn deploy_bundle(&mut self, bundle: &DeploymentBundle, remote_path: &str) -> Result<()> {
+        let files = self.server_vars(bundle.clone(), remote_path).render()?;
+        if files.iter().any(|file| file.destination == PINNED_KEY_PATH) {
+            return Err(anyhow::anyhow!(
+                "Bundles cannot replace the pinned release key {}",
+                PINNED_KEY_PATH
+            ));
+        }
+        let public_key = bundle.public_key();
+        let signed = match &public_key {
+            Some(public_key) => Some(signed_digests(&files, public_key)?),
+            None => None,
+        };
+
+        // A server only ever takes bundles signed with the key it was first deployed with
+        let pinned_path = format!("{}/{}", remote_path, PINNED_KEY_PATH);
+        let pinned = self.execute_command(&format!(
+            "cat {} 2>/dev/null || true",
+            shell_quote_path(&pinned_path)
+        ))?;
+        let pinned = pinned.trim();
+        if !pinned.is_empty() && public_key.as_deref() != Some(pinned) {
+            return Err(anyhow::anyhow!(
+                "{} pins release key {}, refusing a bundle signed with {}",
+                remote_path,
+                pinned,
+                public_key.as_deref().unwrap_or("no key")
+            ));
+        }
+
         // Create remote directories
         self.create_remote_directory(remote_path)?;
         self.create_remote_directory(&format!("{}/config", remote_path))?;
         self.create_remote_directory(&format!("{}/logs", remote_path))?;
         self.create_remote_directory(&format!("{}/data", remote_path))?;
 
-        // Upload binary
-        let remote_binary = format!("{}/kernel", remote_path);
-        self.upload_file(local_binary, &remote_binary)?;
+        for file in &files {
+            let remote_file = format!("{}/{}", remote_path, file.destination);
+            if let Some((parent, _)) = remote_file.rsplit_once('/') {
+                self.create_remote_directory(parent)?;
+            }
 
-        // Make binary executable
-        self.execute_command(&format!("chmod +x {}", remote_binary))?;
+            if file.is_executable() {
+                self.upload_checked(&file.contents, &remote_file)?;
+            } else {
+                info!("Uploading {}", remote_file);
+                self.upload_bytes(&file.contents, &remote_file)?;
+            }
+            self.execute_command(&format!("chmod {:o} {}", file.mode, remote_file))?;
+        }
 
-        // Upload config files if provided
-        if let Some(configs) = config_files {
-            for config in configs {
-                if config.exists() {
-                    let filename = config.file_name().unwrap().to_str().unwrap();
-                    let remote_config = format!("{}/config/{}", remote_path, filename);
-                    self.upload_file(&config, &remote_config)?;
+        if let Some(signed) = signed {
+            info!("Verifying bundle signatures in {}", remote_path);
+            for (destination, expected) in signed {
+                let remote_file = format!("{}/{}", remote_path, destination);
+                let actual = self.remote_sha256(&remote_file)?;
+                if actual.as_deref() != Some(expected.as_str()) {
+                    return Err(anyhow::anyhow!(
+                        "Deployed bundle failed signature verification: {} has SHA-256 {}, the signed file {}",
+                        remote_file,
+                        actual.as_deref().unwrap_or("(missing)"),
+                        expected
+                    ));
                 }
             }
         }
 
-        info!("Kernel deployment completed successfully");
+        if let (Some(public_key), true) = (&public_key, pinned.is_empty()) {
+            info!("Pinning release key {} on the server", public_key);
+            self.upload_bytes(public_key.as_bytes(), &pinned_path)?;
+            self.execute_checked(&format!("chmod 444 {}", shell_quote_path(&pinned_path)))?;
+        }
+
         Ok(())
     }
 
+    fn server_vars(&self, bundle: DeploymentBundle, remote_path: &str) -> DeploymentBundle {
+        let mut bundle = bundle
+            .default_var("remote_path", remote_path)
+            .default_var("port", self.api_port);
+        if let Some(agent_id) = &self.agent_id {
+            bundle = bundle.default_var("agent_id", agent_id);
+        }
+        if let Some(address) = &self.primary_address {
+            bundle = bundle.default_var("primary_address", address);
+        }
+        bundle
+    }
+
+    /// Upload a binary, skipping it when unchanged and sending a patch when possible
+    pub fn upload_binary(&mut self, local_path: &Path, remote_path: &str) -> Result<()> {
+        if !self.connected {
+            return Err(anyhow::anyhow!("Not connected to remote server"));
+        }
+
+        let contents = std::fs::read(local_path).context("Failed to read local binary")?;
+        info!("Uploading {:?} to {}", local_path, remote_path);
+        self.upload_checked(&contents, remote_path)
+    }
+
+    fn upload_checked(&mut self, contents: &[u8], remote_path: &str) -> Result<()> {
+        let local_sha = sha256_hex(contents);
+        let remote_sha = self.remote_sha256(remote_path)?;
+
+        if remote_sha.as_deref() == Some(local_sha.as_str()) {
+            info!("{} is already up to date, skipping upload", remote_path);
+            return Ok(());
+        }
+
+        let mut patched = false;
+        if let (Some(cache), Some(remote_sha)) = (self.artifact_cache.clone(), remote_sha) {
+            match self.upload_patch(&cache, &remote_sha, contents, &local_sha, remote_path) {
+                Ok(()) => patched = true,
+                Err(e) => info!("Delta upload not possible ({}), sending full binary", e),
+            }
+        }
+
+        if !patched {
+            self.upload_bytes(contents, remote_path)?;
+            info!("Successfully uploaded {} bytes", contents.len());
+        }
+
+        self.cache_artifact(&local_sha, contents);
+        Ok(())
+    }
+
+    /// SHA-256 of a remote file, or `None` if it does not exist
+    fn remote_sha256(&self, remote_path: &str) -> Result<Option<String>> {
+        let output = self.execute_command(&format!(
+            "sha256sum {} 2>/dev/null | cut -d' ' -f1",
+            remote_path
+        ))?;
+        let sha = output.trim();
+        Ok((sha.len() == 64).then(|| sha.to_string()))
+    }
+
+    /// Ship `contents` as a bsdiff patch against the cached version with `base_sha`
+    fn upload_patch(
+        &mut self,
+        cache: &Path,
+        base_sha: &str,
+        contents: &[u8],
+        expected_sha: &str,
+        remote_path: &str,
+    ) -> Result<()> {
+        let base = std::fs::read(cache.join(base_sha))
+            .context("deployed version is not in the artifact cache")?;
+        if self
+            .execute_command("command -v bspatch")?
+            .trim()
+            .is_empty()
+        {
+            return Err(anyhow::anyhow!(
+                "bspatch is not installed on the remote host"
+            ));
+        }
+
+        let mut patch = Vec::new();
+        Bsdiff::new(&base, contents)
+            .compare(Cursor::new(&mut patch))
+            .context("Failed to compute binary diff")?;
+        if patch.len() >= contents.len() {
+            return Err(anyhow::anyhow!("patch is not smaller than the binary"));
+        }
+
+        let remote_patch = format!("{}.patch", remote_path);
+        let remote_new = format!("{}.new", remote_path);
+        self.upload_bytes(&patch, &remote_patch)?;
+
+        let output = self.execute_command(&format!(
+            "bspatch {old} {new} {patch}; rm -f {patch}; sha256sum {new} 2>/dev/null | cut -d' ' -f1",
+            old = remote_path,
+            new = remote_new,
+            patch = remote_patch
+        ))?;
+        if output.trim() != expected_sha {
+            let _ = self.execute_command(&format!("rm -f {}", remote_new));
+            return Err(anyhow::anyhow!(
+                "patched binary failed checksum verification"
+            ));
+        }
+
+        self.execute_command(&format!("mv {} {}", remote_new, remote_path))?;
+        info!(
+            "Applied {} byte patch instead of uploading {} bytes",
+            patch.len(),
+            contents.len()
+        );
+        Ok(())
+    }
+
+    /// Remember a deployed binary as the base for future patches
+    fn cache_artifact(&self, sha: &str, contents: &[u8]) {
+        let Some(cache) = &self.artifact_cache else {
+            return;
+        };
+
+        let result = std::fs::create_dir_all(cache).and_then(|_| {
+            let path = cache.join(sha);
+            if !path.exists() {
+                std::fs::write(&path, contents)?;
+            }
+            prune_artifacts(cache)
+        });
+        if let Err(e) = result {
+            warn!("Failed to cache deployed binary: {}", e);
+        }
+    }
+
     /// Start the kernel on the remote server
     pub fn start_kernel(&self, remote_path: &str) -> Result<()> {
         info!("Starting kernel at {}", remote_path);
@@ -232,14 +1006,10 @@ impl SshDeployer {
         let _ = self.execute_command("pkill -f kernel");
 
         // Start new instance in background
-        let start_command = format!(
-            "cd {} && nohup ./kernel > logs/aurelia.log 2>&1 &",
-            remote_path
-        );
-        self.execute_command(&start_command)?;
+        self.execute_command(&self.nohup_command(remote_path))?;
 
         // Verify it started
-        std::thread::sleep(std::time::Duration::from_secs(2));
+        std::thread::sleep(Duration::from_secs(2));
         let check_output = self.execute_command("ps aux | grep kernel | grep -v grep")?;
 
         if check_output.trim().is_empty() {
@@ -257,6 +1027,80 @@ impl SshDeployer {
         Ok(())
     }
 
+    /// Stop the kernel deployed by [`SshDeployer::full_deploy`] without
+    /// disabling it, so it can be started again with [`SshDeployer::start_service`]
+    pub fn stop_service(&self, docker: Option<&DockerDeployConfig>) -> Result<()> {
+        let command = match docker {
+            Some(docker) => format!("docker stop {}", docker.container_name),
+            None => "sudo systemctl stop aurelia".to_string(),
+        };
+        self.execute_checked(&command)?;
+        Ok(())
+    }
+
+    pub fn start_service(&self, docker: Option<&DockerDeployConfig>) -> Result<()> {
+        let command = match docker {
+            Some(docker) => format!("docker start {}", docker.container_name),
+            None => "sudo systemctl start aurelia".to_string(),
+        };
+        self.execute_checked(&command)?;
+        Ok(())
+    }
+
+    /// Stop the kernel for good and delete everything deployed to `remote_path`
+    pub fn remove_deployment(
+        &self,
+        remote_path: &str,
+        docker: Option<&DockerDeployConfig>,
+    ) -> Result<()> {
+        let remote_path = remote_path.trim_end_matches('/');
+        // A deployment directory below a top-level directory or home, never one of those
+        let min_depth = match remote_path {
+            p if p.starts_with('/') => 2,
+            p if p.starts_with("~/") => 1,
+            _ => usize::MAX,
+        };
+        let depth = remote_path
+            .split('/')
+            .filter(|c| !c.is_empty() && *c != "~")
+            .count();
+        if depth < min_depth || remote_path.contains("..") {
+            return Err(anyhow::anyhow!(
+                "Refusing to remove deployment directory {:?}",
+                remote_path
+            ));
+        }
+        // Every deployment has the kernel at its top; a directory without it
+        // was never deployed to, whatever its depth
+        let quoted = shell_quote_path(remote_path);
+        let marked = self.execute_command(&format!(
+            "test -f {}/{} && echo deployed || true",
+            quoted, DEPLOYMENT_MARKER
+        ))?;
+        if marked.trim() != "deployed" {
+            return Err(anyhow::anyhow!(
+                "Refusing to remove {:?}: it has no deployed {}",
+                remote_path,
+                DEPLOYMENT_MARKER
+            ));
+        }
+
+        info!("Removing deployment at {}", remote_path);
+        match docker {
+            Some(docker) => {
+                self.execute_checked(&format!("docker rm -f {}", docker.container_name))?;
+            }
+            None => {
+                self.execute_checked("sudo systemctl disable --now aurelia")?;
+                self.execute_checked(
+                    "sudo rm -f /etc/systemd/system/aurelia.service && sudo systemctl daemon-reload",
+                )?;
+            }
+        }
+        self.execute_checked(&format!("rm -rf {}", quoted))?;
+        Ok(())
+    }
+
     /// Check if kernel is running on remote server
     pub fn check_kernel_status(&self) -> Result<bool> {
         let output = self.execute_command("ps aux | grep kernel | grep -v grep")?;
@@ -264,19 +1108,83 @@ impl SshDeployer {
     }
 
     /// Create systemd service for automatic startup
+    ///
+    /// The unit is `Type=notify` with a watchdog, so a kernel that stops sending
+    /// keepalives is restarted by systemd.
     pub fn setup_systemd_service(&self, remote_path: &str, username: &str) -> Result<()> {
         info!("Setting up systemd service");
 
-        let service_content = format!(
+        // Write service file
+        let temp_service = "/tmp/aurelia.service";
+        self.execute_command(&format!(
+            "printf '%s' {} > {}",
+            shell_quote(&self.systemd_unit(remote_path, username)),
+            temp_service
+        ))?;
+
+        // Move to systemd directory and reload
+        self.execute_command(&format!("sudo mv {} /etc/systemd/system/", temp_service))?;
+        self.execute_command("sudo systemctl daemon-reload")?;
+        self.execute_command("sudo systemctl enable aurelia")?;
+
+        info!("Systemd service setup completed");
+        Ok(())
+    }
+
+    /// Environment the deployed kernel is started with, however it is started
+    fn kernel_environment(&self) -> Vec<(&'static str, String)> {
+        let mut environment = Vec::new();
+        if let Some(address) = &self.primary_address {
+            environment.push((PRIMARY_URL_ENV, address.clone()));
+        }
+        environment
+    }
+
+    /// Whether [`FLEET_KEY_ENV_FILE`] on the server gives the kernel the fleet key
+    /// or a URL to fetch it from
+    ///
+    /// The file is meant to be root-only, so it is read through `sudo -n` when
+    /// the login user cannot read it.
+    fn has_fleet_key_source(&self) -> Result<bool> {
+        let pattern = shell_quote(&format!(
+            "^({}{}|{})=.",
+            SECRET_ENV_PREFIX,
+            FLEET_KEY_SECRET.to_uppercase(),
+            FLEET_KEY_URL_ENV
+        ));
+        let output = self.execute_command(&format!(
+            "grep -qE {pattern} {file} 2>/dev/null || sudo -n grep -qE {pattern} {file} 2>/dev/null && echo found",
+            pattern = pattern,
+            file = FLEET_KEY_ENV_FILE
+        ))?;
+        Ok(output.trim() == "found")
+    }
+
+    fn systemd_unit(&self, remote_path: &str, username: &str) -> String {
+        let environment: String = self
+            .kernel_environment()
+            .iter()
+            .map(|(name, value)| {
+                let assignment = format!("{}={}", name, value);
+                format!(
+                    "Environment=\"{}\"\n",
+                    assignment.replace('\\', "\\\\").replace('"', "\\\"")
+                )
+            })
+            .collect();
+        format!(
             r#"[Unit]
 Description=Aurelia Autonomous Trading System
 After=network.target
 
 [Service]
-Type=simple
+Type=notify
+NotifyAccess=main
+WatchdogSec={}
 User={}
 WorkingDirectory={}
-ExecStart={}/kernel
+EnvironmentFile=-{}
+{}ExecStart={}/kernel
 Restart=always
 RestartSec=10
 StandardOutput=append:{}/logs/aurelia.log
@@ -285,20 +1193,36 @@ StandardError=append:{}/logs/aurelia.error.log
 [Install]
 WantedBy=multi-user.target
 "#,
-            username, remote_path, remote_path, remote_path, remote_path
-        );
-
-        // Write service file
-        let temp_service = "/tmp/aurelia.service";
-        self.execute_command(&format!("echo '{}' > {}", service_content, temp_service))?;
-
-        // Move to systemd directory and reload
-        self.execute_command(&format!("sudo mv {} /etc/systemd/system/", temp_service))?;
-        self.execute_command("sudo systemctl daemon-reload")?;
-        self.execute_command("sudo systemctl enable aurelia")?;
+            SYSTEMD_WATCHDOG_SECS,
+            username,
+            remote_path,
+            FLEET_KEY_ENV_FILE,
+            environment,
+            remote_path,
+            remote_path,
+            remote_path
+        )
+    }
 
-        info!("Systemd service setup completed");
-        Ok(())
+    /// Start the kernel in the background without a service manager, with the
+    /// variables of [`FLEET_KEY_ENV_FILE`] when the login user can read it
+    fn nohup_command(&self, remote_path: &str) -> String {
+        let environment: Vec<String> = self
+            .kernel_environment()
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, shell_quote(value)))
+            .collect();
+        let env = if environment.is_empty() {
+            String::new()
+        } else {
+            format!("env {} ", environment.join(" "))
+        };
+        format!(
+            "cd {} && if [ -r {file} ]; then set -a; . {file}; set +a; fi; {}nohup ./kernel >> logs/aurelia.log 2>&1 &",
+            shell_quote_path(remote_path),
+            env,
+            file = FLEET_KEY_ENV_FILE
+        )
     }
 
     /// Get logs from remote server
@@ -310,6 +1234,239 @@ WantedBy=multi-user.target
         self.execute_command(&command)
     }
 
+    /// Follow the remote log with `tail -F`, handing each line to `on_line`
+    ///
+    /// While the log is quiet `on_line` is called with `None` about every
+    /// [`common::ssh::READ_POLL_INTERVAL`], so the caller can stop a stream nobody reads.
+    /// Blocks until `on_line` returns `false`, the deployer is cancelled or the
+    /// remote command exits.
+    pub fn stream_logs<F>(&self, remote_path: &str, mut on_line: F) -> Result<()>
+    where
+        F: FnMut(Option<String>) -> bool,
+    {
+        if !self.connected {
+            return Err(anyhow::anyhow!("Not connected to remote server"));
+        }
+
+        let mut channel = self
+            .session
+            .channel_session()
+            .context("Failed to create SSH channel")?;
+
+        let command = format!(
+            "tail -n {} -F {}/logs/aurelia.log",
+            LOG_STREAM_BACKLOG_LINES, remote_path
+        );
+        audit::record(
+            AuditCategory::SshCommand,
+            &command,
+            serde_json::json!({ "remote": self.remote }),
+        );
+        channel.exec(&command).context("Failed to start log tail")?;
+
+        let result = polling(&self.session, || {
+            let mut reader = BufReader::new(&mut channel);
+            // Keeps a partial line across reads that time out
+            let mut line = Vec::new();
+            while !self.cancel.is_cancelled() {
+                match reader.read_until(b'\n', &mut line) {
+                    Ok(0) => break,
+                    Ok(_) => {
+                        let text = String::from_utf8_lossy(&line)
+                            .trim_end_matches(['\r', '\n'])
+                            .to_string();
+                        line.clear();
+                        if !on_line(Some(text)) {
+                            break;
+                        }
+                    }
+                    Err(e)
+                        if matches!(
+                            e.kind(),
+                            ErrorKind::TimedOut | ErrorKind::WouldBlock | ErrorKind::Interrupted
+                        ) =>
+                    {
+                        if !on_line(None) {
+                            break;
+                        }
+                    }
+                    Err(e) => return Err(e).context("Failed to read log stream"),
+                }
+            }
+            Ok(())
+        });
+
+        // Closing the channel terminates the remote tail
+        let _ = channel.close();
+        result
+    }
+
+    /// Run the server's provisioning steps unless this exact provisioning already
+    /// succeeded there; returns whether anything ran
+    pub fn provision(&mut self, remote_path: &str, provision: &ProvisionConfig) -> Result<bool> {
+        let script = provision
+            .script
+            .as_ref()
+            .map(|path| {
+                std::fs::read(path)
+                    .with_context(|| format!("Failed to read provisioning script {:?}", path))
+            })
+            .transpose()?;
+
+        // The marker is keyed on the content, so edited steps run again
+        let mut hasher = Sha256::new();
+        if let Some(script) = &script {
+            hasher.update(script);
+        }
+        for command in &provision.commands {
+            hasher.update(command.as_bytes());
+            hasher.update([0]);
+        }
+        let digest = format!("{:x}", hasher.finalize());
+        let marker_dir = format!("{}/{}", remote_path, PROVISION_MARKER_DIR);
+        let marker = format!("{}/{}", marker_dir, &digest[..16]);
+
+        if self
+            .execute_command(&format!("test -f {} && echo done", marker))?
+            .trim()
+            == "done"
+        {
+            info!("Server already provisioned ({})", &digest[..16]);
+            return Ok(false);
+        }
+
+        info!("Provisioning server");
+        self.create_remote_directory(&marker_dir)?;
+        if let Some(script) = script {
+            let remote_script = format!("{}/provision.sh", marker_dir);
+            self.upload_bytes(&script, &remote_script)?;
+            self.execute_checked(&format!("sh {}", remote_script))?;
+        }
+        for command in &provision.commands {
+            info!("Provisioning step: {}", command);
+            self.execute_checked(command)?;
+        }
+        self.execute_checked(&format!("touch {}", marker))?;
+
+        Ok(true)
+    }
+
+    /// Run the kernel as a Docker container instead of a host process
+    ///
+    /// The binary and config are uploaded as for a native deployment. Without a
+    /// configured image, one is built on the remote host around the uploaded
+    /// binary; `config`, `data` and `logs` are mounted so state survives
+    /// container replacement.
+    pub fn deploy_container(
+        &mut self,
+        local_binary: &Path,
+        remote_path: &str,
+        config_files: Option<Vec<PathBuf>>,
+        docker: &DockerDeployConfig,
+    ) -> Result<()> {
+        self.execute_checked("docker version --format '{{.Server.Version}}'")
+            .context("Docker is not available on the remote host")?;
+        self.deploy_kernel(local_binary, remote_path, config_files)?;
+
+        let image = match &docker.image {
+            Some(image) => {
+                info!("Pulling {}", image);
+                self.execute_checked(&format!("docker pull {}", image))?;
+                image.clone()
+            }
+            None => {
+                let dockerfile = format!(
+                    "FROM {base}\n\
+                     RUN apt-get update && apt-get install -y --no-install-recommends ca-certificates \
+                     && rm -rf /var/lib/apt/lists/*\n\
+                     WORKDIR {dir}\n\
+                     COPY kernel {dir}/kernel\n\
+                     ENTRYPOINT [\"sh\", \"-c\", \"exec ./kernel >> logs/aurelia.log 2>&1\"]\n",
+                    base = docker.base_image,
+                    dir = CONTAINER_DEPLOY_PATH,
+                );
+                self.upload_bytes(
+                    dockerfile.as_bytes(),
+                    &format!("{}/Dockerfile", remote_path),
+                )?;
+                info!("Building {} on the remote host", LOCAL_IMAGE_TAG);
+                self.execute_checked(&format!(
+                    "docker build -t {} {}",
+                    LOCAL_IMAGE_TAG, remote_path
+                ))?;
+                LOCAL_IMAGE_TAG.to_string()
+            }
+        };
+
+        // systemd reads the file as root, the Docker client as the login user
+        let unreadable = self.execute_command(&format!(
+            "[ -e {file} ] && [ ! -r {file} ] && echo unreadable || true",
+            file = FLEET_KEY_ENV_FILE
+        ))?;
+        if unreadable.trim() == "unreadable" {
+            return Err(anyhow::anyhow!(
+                "{} is not readable by the login user, the container would start without it",
+                FLEET_KEY_ENV_FILE
+            ));
+        }
+
+        let _ = self.execute_command(&format!("docker rm -f {}", docker.container_name));
+        self.execute_checked(&self.docker_run_command(docker, remote_path, &image))?;
+
+        info!("Container {} started from {}", docker.container_name, image);
+        Ok(())
+    }
+
+    /// `docker run` for the kernel container, with the environment the systemd
+    /// unit gives a native kernel
+    fn docker_run_command(
+        &self,
+        docker: &DockerDeployConfig,
+        remote_path: &str,
+        image: &str,
+    ) -> String {
+        let mounts: Vec<String> = ["config", "data", "logs"]
+            .iter()
+            .map(|dir| {
+                format!(
+                    "-v {}:{}/{}",
+                    shell_quote_path(&format!("{}/{}", remote_path, dir)),
+                    CONTAINER_DEPLOY_PATH,
+                    dir
+                )
+            })
+            .collect();
+        let environment: Vec<String> = self
+            .kernel_environment()
+            .iter()
+            .map(|(name, value)| format!("-e {}", shell_quote(&format!("{}={}", name, value))))
+            .collect();
+        // Optional, as `EnvironmentFile=-` is for the unit
+        let env_file = format!(
+            "$([ -r {file} ] && echo --env-file {file})",
+            file = FLEET_KEY_ENV_FILE
+        );
+        format!(
+            "docker run -d --name {} --restart {} --network host {} {} {} {} {}",
+            docker.container_name,
+            docker.restart_policy,
+            mounts.join(" "),
+            env_file,
+            environment.join(" "),
+            docker.extra_args.join(" "),
+            image
+        )
+    }
+
+    /// Check if the kernel container is running on the remote server
+    pub fn check_container_status(&self, container_name: &str) -> Result<bool> {
+        let output = self.execute_command(&format!(
+            "docker inspect -f '{{{{.State.Running}}}}' {} 2>/dev/null",
+            container_name
+        ))?;
+        Ok(output.trim() == "true")
+    }
+
     /// Perform a complete deployment with all steps
     #[allow(clippy::too_many_arguments)]
     pub fn full_deploy(
@@ -322,30 +1479,58 @@ WantedBy=multi-user.target
         remote_path: &str,
         config_files: Option<Vec<PathBuf>>,
         setup_service: bool,
+        provision: Option<&ProvisionConfig>,
+        docker: Option<&DockerDeployConfig>,
     ) -> Result<()> {
-        // Connect
-        match auth {
+        // Each phase is a span of its own, so traces show where a deployment spent its time
+        info_span!("connect").in_scope(|| match auth {
             AuthMethod::Password(password) => {
-                self.connect_with_password(host, port, username, &password)?;
+                self.connect_with_password(host, port, username, &password)
             }
             AuthMethod::Key { path, passphrase } => {
-                self.connect_with_key(host, port, username, &path, passphrase.as_deref())?;
+                self.connect_with_key(host, port, username, &path, passphrase.as_deref())
             }
+        })?;
+
+        // Prepare a fresh server before anything is copied to it
+        if let Some(provision) = provision {
+            info_span!("provision").in_scope(|| self.provision(remote_path, provision))?;
+        }
+
+        if let Some(docker) = docker {
+            // The container's restart policy takes the place of systemd
+            info_span!("upload").in_scope(|| {
+                self.deploy_container(local_binary, remote_path, config_files, docker)
+            })?;
+            let running = info_span!("verify")
+                .in_scope(|| self.check_container_status(&docker.container_name))?;
+            if running {
+                info!("Deployment successful - kernel container is running");
+                return Ok(());
+            }
+            return Err(anyhow::anyhow!(
+                "Deployment failed - container {} is not running",
+                docker.container_name
+            ));
         }
 
         // Deploy
-        self.deploy_kernel(local_binary, remote_path, config_files)?;
+        info_span!("upload")
+            .in_scope(|| self.deploy_kernel(local_binary, remote_path, config_files))?;
 
         // Setup systemd service if requested
-        if setup_service {
-            self.setup_systemd_service(remote_path, username)?;
-            self.execute_command("sudo systemctl start aurelia")?;
-        } else {
-            self.start_kernel(remote_path)?;
-        }
+        info_span!("start").in_scope(|| {
+            if setup_service {
+                self.setup_systemd_service(remote_path, username)?;
+                self.execute_command("sudo systemctl start aurelia")?;
+            } else {
+                self.start_kernel(remote_path)?;
+            }
+            anyhow::Ok(())
+        })?;
 
         // Verify deployment
-        if self.check_kernel_status()? {
+        if info_span!("verify").in_scope(|| self.check_kernel_status())? {
             info!("Deployment successful - kernel is running");
         } else {
             return Err(anyhow::anyhow!("Deployment failed - kernel is not running"));
@@ -358,12 +1543,243 @@ WantedBy=multi-user.target
     pub fn disconnect(&mut self) {
         if self.connected {
             self.sftp = None;
+            self.lease = None;
             self.connected = false;
             info!("Disconnected from remote server");
         }
     }
 }
 
+/// Symbols this agent trades, which replicas trade a profile-sized subset of
+fn primary_symbols() -> Vec<String> {
+    #[derive(Deserialize)]
+    struct Universe {
+        symbols: Vec<String>,
+    }
+    std::fs::read_to_string(SYMBOL_UNIVERSE_PATH)
+        .ok()
+        .and_then(|content| serde_json::from_str::<Universe>(&content).ok())
+        .map(|universe| universe.symbols)
+        .unwrap_or_default()
+}
+
+/// SHA-256 of each file of a rendered bundle whose signature checks out against
+/// `public_key`, as the files must read on the server
+fn signed_digests(files: &[RenderedFile], public_key: &str) -> Result<Vec<(String, String)>> {
+    let manifest = files
+        .iter()
+        .find(|file| file.destination == SIGNATURES_PATH)
+        .context("Signed bundle has no signatures")?;
+    let signatures: BundleSignatures = serde_json::from_slice(&manifest.contents)?;
+    if signatures.public_key != public_key {
+        return Err(anyhow::anyhow!("Bundle was signed with another key"));
+    }
+    if !signatures.files.contains_key(RELEASE_BINARY_NAME) {
+        return Err(anyhow::anyhow!("{} is not signed", RELEASE_BINARY_NAME));
+    }
+    let mut digests = Vec::with_capacity(signatures.files.len());
+    for (name, signature) in &signatures.files {
+        let file = files
+            .iter()
+            .find(|file| &file.destination == name)
+            .with_context(|| format!("Signed file {} is not in the bundle", name))?;
+        signing::verify(public_key, name, &file.contents, signature)?;
+        digests.push((name.clone(), sha256_hex(&file.contents)));
+    }
+    Ok(digests)
+}
+
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Judge the output of the preflight script against what the binary needs
+fn evaluate_preflight(
+    output: &str,
+    binary_len: u64,
+    glibc_needed: Option<(u32, u32)>,
+    ports: &[u16],
+) -> PreflightReport {
+    let value = |key: &str| {
+        output
+            .lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+            .map(str::trim)
+    };
+    let dir = value("dir").unwrap_or("the deployment path");
+    let mut checks = Vec::new();
+
+    let required = binary_len * 2 + PREFLIGHT_DISK_HEADROOM;
+    let free = value("free_kb")
+        .and_then(|kb| kb.parse::<u64>().ok())
+        .map(|kb| kb * 1024);
+    checks.push(match free {
+        Some(free) => PreflightCheck::new(
+            "disk_space",
+            free >= required,
+            format!(
+                "{} MiB free on {}, {} MiB needed",
+                free / (1024 * 1024),
+                dir,
+                required / (1024 * 1024)
+            ),
+        ),
+        None => PreflightCheck::new(
+            "disk_space",
+            false,
+            format!("could not read the free space of {}", dir),
+        ),
+    });
+
+    // A statically linked binary references no glibc symbol versions
+    if let Some((major, minor)) = glibc_needed {
+        let available = value("glibc")
+            .and_then(|line| line.split_whitespace().last())
+            .and_then(parse_version);
+        checks.push(match available {
+            Some(version) => PreflightCheck::new(
+                "glibc",
+                version >= (major, minor),
+                format!(
+                    "glibc {}.{} installed, {}.{} needed",
+                    version.0, version.1, major, minor
+                ),
+            ),
+            None => PreflightCheck::new(
+                "glibc",
+                false,
+                format!("no glibc found, {}.{} needed", major, minor),
+            ),
+        });
+    }
+
+    let writable = value("writable") == Some("yes");
+    checks.push(PreflightCheck::new(
+        "writable_path",
+        writable,
+        if writable {
+            format!("{} is writable", dir)
+        } else {
+            format!("{} is not writable by the deploying user", dir)
+        },
+    ));
+
+    let listening: Vec<&str> = output
+        .lines()
+        .filter_map(|line| line.strip_prefix("listen="))
+        .collect();
+    let kernel_running = value("kernel_running") == Some("yes");
+    for port in ports {
+        let suffix = format!(":{}", port);
+        let check = if listening.contains(&"unknown") {
+            PreflightCheck::new(
+                "port",
+                true,
+                format!("cannot list listening ports, {} not checked", port),
+            )
+        } else if !listening.iter().any(|addr| addr.ends_with(&suffix)) {
+            PreflightCheck::new("port", true, format!("port {} is free", port))
+        } else if kernel_running {
+            // The kernel being replaced frees it when it is restarted
+            PreflightCheck::new(
+                "port",
+                true,
+                format!("port {} is held by the running kernel", port),
+            )
+        } else {
+            PreflightCheck::new(
+                "port",
+                false,
+                format!("port {} is in use by another process", port),
+            )
+        };
+        checks.push(check);
+    }
+
+    PreflightReport { checks }
+}
+
+/// Read-only shell script reporting what [`evaluate_preflight`] checks. Only a
+/// `kernel` process running the binary in `remote_path` counts as the running
+/// kernel; a replaced binary shows up as `(deleted)`.
+fn preflight_script(remote_path: &str) -> String {
+    format!(
+        r#"d={path}; while [ ! -d "$d" ]; do d=$(dirname "$d"); done
+echo "dir=$d"
+echo "free_kb=$(df -Pk "$d" | awk 'NR==2 {{print $4}}')"
+[ -w "$d" ] && echo writable=yes || echo writable=no
+echo "glibc=$(getconf GNU_LIBC_VERSION 2>/dev/null || ldd --version 2>&1 | head -n1)"
+if [ -d {path} ]; then k="$(cd {path} && pwd -P)/kernel"
+for p in $(pgrep -x kernel 2>/dev/null); do
+case "$(readlink /proc/$p/exe 2>/dev/null)" in "$k"|"$k (deleted)") echo kernel_running=yes;; esac
+done; fi
+if command -v ss >/dev/null 2>&1; then ss -ltnH | awk '{{print "listen=" $4}}'
+elif command -v netstat >/dev/null 2>&1; then netstat -ltn | awk 'NR>2 {{print "listen=" $4}}'
+else echo listen=unknown; fi"#,
+        path = shell_quote_path(remote_path)
+    )
+}
+
+/// Newest `GLIBC_x.y` symbol version the binary references
+fn required_glibc(binary: &[u8]) -> Option<(u32, u32)> {
+    const TAG: &[u8] = b"GLIBC_";
+    let mut required = None;
+    let mut rest = binary;
+    while let Some(at) = rest.windows(TAG.len()).position(|window| window == TAG) {
+        rest = &rest[at + TAG.len()..];
+        let end = rest
+            .iter()
+            .position(|b| !(b.is_ascii_digit() || *b == b'.'))
+            .unwrap_or(rest.len());
+        let version = std::str::from_utf8(&rest[..end])
+            .ok()
+            .and_then(parse_version);
+        required = required.max(version);
+    }
+    required
+}
+
+/// `major.minor` of a version such as `2.35` or `2.35-0ubuntu3`
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.split(|c: char| !c.is_ascii_digit());
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().and_then(|m| m.parse().ok()).unwrap_or(0);
+    Some((major, minor))
+}
+
+/// Drop all but the most recently written cached artifacts
+fn prune_artifacts(cache: &Path) -> std::io::Result<()> {
+    let mut entries: Vec<_> = std::fs::read_dir(cache)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            Some((modified, entry.path()))
+        })
+        .collect();
+
+    entries.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    for (_, path) in entries.into_iter().skip(MAX_CACHED_ARTIFACTS) {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+fn host_key_fingerprint(session: &Session) -> String {
+    session
+        .host_key_hash(HashType::Sha256)
+        .map(|hash| format!("SHA256:{}", STANDARD_NO_PAD.encode(hash)))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Host name as written to known_hosts
+fn known_host_name(host: &str, port: u16) -> String {
+    if port == 22 {
+        host.to_string()
+    } else {
+        format!("[{}]:{}", host, port)
+    }
+}
+
 /// Authentication method for SSH connection
 pub enum AuthMethod {
     Password(String),
@@ -373,6 +1789,73 @@ pub enum AuthMethod {
     },
 }
 
+/// Output of a remote command, see [`SshDeployer::execute_captured`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_status: i32,
+}
+
+/// One check of [`SshDeployer::preflight`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PreflightCheck {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl PreflightCheck {
+    fn new(name: &'static str, passed: bool, detail: String) -> Self {
+        Self {
+            name,
+            passed,
+            detail,
+        }
+    }
+}
+
+/// Whether a server can take a deployment, and why not; the error a
+/// deployment aborts with when a check fails
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PreflightReport {
+    pub checks: Vec<PreflightCheck>,
+}
+
+impl PreflightReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &PreflightCheck> {
+        self.checks.iter().filter(|check| !check.passed)
+    }
+}
+
+impl fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failures: Vec<String> = self
+            .failures()
+            .map(|check| format!("{}: {}", check.name, check.detail))
+            .collect();
+        if failures.is_empty() {
+            write!(f, "preflight passed")
+        } else {
+            write!(f, "preflight failed ({})", failures.join("; "))
+        }
+    }
+}
+
+impl std::error::Error for PreflightReport {}
+
+/// Bastion host a target server is reached through
+pub struct JumpHost {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub auth: AuthMethod,
+}
+
 impl Drop for SshDeployer {
     fn drop(&mut self) {
         self.disconnect();
@@ -388,4 +1871,197 @@ mod tests {
         let deployer = SshDeployer::new();
         assert!(!deployer.connected);
     }
+
+    #[test]
+    fn test_known_host_name() {
+        assert_eq!(known_host_name("10.0.0.1", 22), "10.0.0.1");
+        assert_eq!(known_host_name("10.0.0.1", 2222), "[10.0.0.1]:2222");
+        assert_eq!(known_host_name("2001:db8::1", 2222), "[2001:db8::1]:2222");
+
+        let deployer = SshDeployer::new().with_strict_host_key_checking(true);
+        assert!(deployer.strict_host_key_checking);
+        // Never the user's own known_hosts file
+        assert_eq!(deployer.known_hosts_path, PathBuf::from(KNOWN_HOSTS_PATH));
+    }
+
+    #[test]
+    fn test_containers_get_the_environment_of_the_systemd_unit() {
+        let deployer = SshDeployer::new().with_primary_address("http://10.0.0.1:8080");
+        let unit = deployer.systemd_unit("/opt/aurelia", "ubuntu");
+        let run =
+            deployer.docker_run_command(&DockerDeployConfig::default(), "/opt/aurelia", "aurelia");
+        assert!(unit.contains(&format!("EnvironmentFile=-{}", FLEET_KEY_ENV_FILE)));
+        assert!(run.contains(&format!("--env-file {}", FLEET_KEY_ENV_FILE)));
+        for (name, value) in deployer.kernel_environment() {
+            assert!(unit.contains(&format!("Environment=\"{}={}\"", name, value)));
+            assert!(run.contains(&format!("-e {}={}", name, value)));
+        }
+        assert!(run.contains("-v /opt/aurelia/data:/opt/aurelia/data"));
+    }
+
+    #[test]
+    fn test_deployed_kernels_are_told_where_the_primary_is() {
+        let deployer = SshDeployer::new().with_primary_address("http://10.0.0.1:8080");
+        let unit = deployer.systemd_unit("/opt/aurelia", "ubuntu");
+        assert!(unit.contains("Environment=\"AURELIA_PRIMARY_URL=http://10.0.0.1:8080\"\n"));
+        assert!(unit.contains("ExecStart=/opt/aurelia/kernel"));
+        assert_eq!(
+            deployer.nohup_command("~/aurelia"),
+            "cd ~/aurelia && if [ -r /etc/aurelia/fleet.env ]; then set -a; . /etc/aurelia/fleet.env; set +a; fi; env AURELIA_PRIMARY_URL=http://10.0.0.1:8080 nohup ./kernel >> logs/aurelia.log 2>&1 &"
+        );
+
+        // The primary itself is deployed without one
+        let unit = SshDeployer::new().systemd_unit("/opt/aurelia", "ubuntu");
+        assert!(!unit.contains("AURELIA_PRIMARY_URL"));
+    }
+
+    #[test]
+    fn test_bundles_get_the_configured_api_port() {
+        let bundle = DeploymentBundle::new().template("{{agent_id}}:{{port}}", "x");
+        let deployer = SshDeployer::new()
+            .with_agent_id("replica-1")
+            .with_api_port(9090);
+        let files = deployer
+            .server_vars(bundle.clone(), "/opt/aurelia")
+            .render()
+            .unwrap();
+        assert_eq!(files[0].contents, b"replica-1:9090");
+
+        // A port set by the bundle wins
+        let files = deployer
+            .server_vars(bundle.var("port", 7070), "/opt/aurelia")
+            .render()
+            .unwrap();
+        assert_eq!(files[0].contents, b"replica-1:7070");
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_signed_digests() {
+        let dir = tempfile::tempdir().unwrap();
+        let signer = ReleaseSigner::load_or_create(&common::SecretStore::new(dir.path())).unwrap();
+        let bundle = DeploymentBundle::new()
+            .template("binary", RELEASE_BINARY_NAME)
+            .template("{}", "config/strategy.json")
+            .signed_by(signer.clone());
+        let mut files = bundle.render().unwrap();
+        let digests = signed_digests(&files, &signer.public_key()).unwrap();
+        assert!(digests.contains(&(RELEASE_BINARY_NAME.to_string(), sha256_hex(b"binary"))));
+        assert!(digests
+            .iter()
+            .any(|(name, _)| name == "config/strategy.json"));
+
+        // The deployer only vouches for what it signed itself
+        let other = tempfile::tempdir().unwrap();
+        let other = ReleaseSigner::load_or_create(&common::SecretStore::new(other.path())).unwrap();
+        assert!(signed_digests(&files, &other.public_key()).is_err());
+
+        let kernel = files
+            .iter_mut()
+            .find(|file| file.destination == RELEASE_BINARY_NAME)
+            .unwrap();
+        kernel.contents = b"tampered".to_vec();
+        assert!(signed_digests(&files, &signer.public_key()).is_err());
+    }
+
+    #[test]
+    fn test_stream_logs_requires_connection() {
+        let deployer = SshDeployer::new();
+        let result = deployer.stream_logs("/opt/aurelia", |_| true);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_preflight_report() {
+        const BINARY_LEN: u64 = 100 * 1024 * 1024;
+        let needed = required_glibc(b"\x7fELF...GLIBC_2.17\0GLIBC_2.34\0GLIBC_PRIVATE\0");
+        assert_eq!(needed, Some((2, 34)));
+        assert_eq!(required_glibc(b"statically linked"), None);
+
+        let healthy = "dir=/opt\nfree_kb=10485760\nwritable=yes\nglibc=glibc 2.35\n\
+                       listen=0.0.0.0:22\nlisten=[::]:22\n";
+        let report = evaluate_preflight(healthy, BINARY_LEN, needed, &[8080]);
+        assert!(report.passed(), "{}", report);
+
+        // Small disk, old glibc, a root-owned path and a foreign listener
+        let broken = "dir=/opt\nfree_kb=102400\nwritable=no\n\
+                      glibc=ldd (GNU libc) 2.31\nlisten=0.0.0.0:8080\n";
+        let report = evaluate_preflight(broken, BINARY_LEN, needed, &[8080]);
+        let failed: Vec<_> = report.failures().map(|check| check.name).collect();
+        assert_eq!(failed, ["disk_space", "glibc", "writable_path", "port"]);
+        assert!(report
+            .to_string()
+            .contains("glibc 2.31 installed, 2.34 needed"));
+
+        // The port of the kernel being replaced is not a conflict, musl is
+        let redeploy = "dir=/opt/aurelia\nfree_kb=10485760\nwritable=yes\n\
+                        glibc=musl libc (x86_64)\nkernel_running=yes\nlisten=*:8080\n";
+        let report = evaluate_preflight(redeploy, BINARY_LEN, needed, &[8080]);
+        let failed: Vec<_> = report.failures().map(|check| check.name).collect();
+        assert_eq!(failed, ["glibc"]);
+    }
+
+    #[test]
+    fn test_preflight_script_quotes_the_path_and_finds_only_its_own_kernel() {
+        let root = tempfile::tempdir().unwrap();
+        let deploy = root.path().join("it's an agent");
+        let other = root.path().join("other");
+        for dir in [&deploy, &other] {
+            std::fs::create_dir(dir).unwrap();
+            std::fs::copy("/bin/sleep", dir.join("kernel")).unwrap();
+        }
+        let run = |path: &Path| {
+            let script = preflight_script(path.to_str().unwrap());
+            let output = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(script)
+                .output()
+                .unwrap();
+            String::from_utf8(output.stdout).unwrap()
+        };
+
+        // Another deployment's kernel is not the one being replaced
+        let mut foreign = std::process::Command::new(other.join("kernel"))
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let output = run(&deploy);
+        assert!(output.contains(&format!("dir={}\n", deploy.display())));
+        assert!(output.contains("writable=yes\n"));
+        assert!(!output.contains("kernel_running"));
+
+        let mut own = std::process::Command::new(deploy.join("kernel"))
+            .arg("30")
+            .spawn()
+            .unwrap();
+        assert!(run(&deploy).contains("kernel_running=yes\n"));
+        // A path that does not exist yet is checked at its closest parent
+        assert!(run(&deploy.join("new")).contains(&format!("dir={}\n", deploy.display())));
+
+        for child in [&mut foreign, &mut own] {
+            child.kill().unwrap();
+            child.wait().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_remove_deployment_refuses_top_level_directories() {
+        let deployer = SshDeployer::new();
+        for path in ["/", "/opt/", "~/", "relative/dir", "/opt/../etc"] {
+            let error = deployer.remove_deployment(path, None).unwrap_err();
+            assert!(error.to_string().starts_with("Refusing"), "{}", path);
+        }
+        // Allowed, but fails later without a connection
+        let error = deployer
+            .remove_deployment("/opt/aurelia", None)
+            .unwrap_err();
+        assert!(!error.to_string().starts_with("Refusing"));
+    }
 }
@@ -0,0 +1,192 @@
+//! TCP tunnels through an SSH bastion.
+//!
+//! libssh2 sessions need a real socket, so a direct-tcpip channel opened on the
+//! bastion is bridged to a loopback connection by a forwarding thread. The
+//! thread owns the bastion session and ends when either side closes.
+//!
+//! Clients that dial an address themselves, such as HTTP clients, go through a
+//! [`LocalForward`] instead, which opens a tunnel for every connection it accepts.
+
+use anyhow::{Context, Result};
+use ssh2::{Channel, Session};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Size of the buffer used when copying between the channel and the socket
+const CHUNK_SIZE: usize = 32 * 1024;
+
+/// Pause between polls when neither side has data
+const IDLE_POLL: Duration = Duration::from_millis(5);
+
+/// Open a channel from the authenticated `bastion` to `host:port` and return a
+/// local stream connected to it.
+pub fn open(bastion: Session, host: &str, port: u16) -> Result<TcpStream> {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let channel = bastion
+        .channel_direct_tcpip(host, port, None)
+        .context("Failed to open a direct-tcpip channel")?;
+
+    let listener = TcpListener::bind(("127.0.0.1", 0))?;
+    let local = TcpStream::connect(listener.local_addr()?)?;
+    let (remote_end, _) = listener.accept()?;
+
+    std::thread::Builder::new()
+        .name("ssh-tunnel".to_string())
+        .spawn(move || {
+            if let Err(e) = forward(&bastion, channel, remote_end) {
+                warn!("SSH tunnel closed: {}", e);
+            }
+            debug!("SSH tunnel finished");
+        })?;
+    Ok(local)
+}
+
+/// A loopback port whose connections are each carried to the target over a
+/// fresh stream from `connect`.
+///
+/// The listener stays open for the life of the process, so callers keep one
+/// forward per target rather than binding one per request.
+pub struct LocalForward {
+    addr: SocketAddr,
+}
+
+impl LocalForward {
+    pub fn bind<F>(connect: F) -> Result<Self>
+    where
+        F: Fn() -> Result<TcpStream> + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind(("127.0.0.1", 0))?;
+        let addr = listener.local_addr()?;
+        let connect = Arc::new(connect);
+
+        std::thread::Builder::new()
+            .name("ssh-forward".to_string())
+            .spawn(move || {
+                for client in listener.incoming() {
+                    let client = match client {
+                        Ok(client) => client,
+                        Err(e) => {
+                            warn!("SSH forward failed to accept a connection: {}", e);
+                            continue;
+                        }
+                    };
+                    let connect = connect.clone();
+                    std::thread::spawn(move || match connect() {
+                        Ok(remote) => bridge(client, remote),
+                        Err(e) => warn!("SSH forward could not reach its target: {:#}", e),
+                    });
+                }
+            })?;
+        Ok(Self { addr })
+    }
+
+    /// Address to connect to instead of the target
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+/// Copy bytes both ways between two blocking sockets until both directions close.
+fn bridge(client: TcpStream, remote: TcpStream) {
+    let (Ok(mut client_reader), Ok(mut remote_writer)) = (client.try_clone(), remote.try_clone())
+    else {
+        warn!("SSH forward failed to clone its sockets");
+        return;
+    };
+    let upstream = std::thread::spawn(move || {
+        let _ = io::copy(&mut client_reader, &mut remote_writer);
+        let _ = remote_writer.shutdown(Shutdown::Write);
+    });
+    let (mut remote_reader, mut client_writer) = (remote, client);
+    let _ = io::copy(&mut remote_reader, &mut client_writer);
+    let _ = client_writer.shutdown(Shutdown::Write);
+    let _ = upstream.join();
+}
+
+/// Copy bytes both ways until either side reaches EOF.
+fn forward(bastion: &Session, mut channel: Channel, mut socket: TcpStream) -> io::Result<()> {
+    bastion.set_blocking(false);
+    socket.set_nonblocking(true)?;
+    let mut buf = [0u8; CHUNK_SIZE];
+
+    loop {
+        let mut idle = true;
+
+        match socket.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                write_fully(&mut channel, &buf[..n])?;
+                idle = false;
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+
+        match channel.read(&mut buf) {
+            Ok(0) if channel.eof() => break,
+            Ok(0) => {}
+            Ok(n) => {
+                write_fully(&mut socket, &buf[..n])?;
+                idle = false;
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e),
+        }
+
+        if idle {
+            std::thread::sleep(IDLE_POLL);
+        }
+    }
+
+    let _ = channel.send_eof();
+    let _ = channel.close();
+    Ok(())
+}
+
+/// `write_all` for non-blocking writers, retrying while the writer is full.
+fn write_fully<W: Write>(writer: &mut W, mut data: &[u8]) -> io::Result<()> {
+    while !data.is_empty() {
+        match writer.write(data) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => data = &data[n..],
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => std::thread::sleep(IDLE_POLL),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_forward_carries_each_connection_to_the_target() {
+        // Stands in for the server behind the bastion: answers every request
+        let target = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let target_addr = target.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in target.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = [0u8; 4];
+                stream.read_exact(&mut request).unwrap();
+                stream.write_all(b"pong").unwrap();
+            }
+        });
+
+        let forward = LocalForward::bind(move || Ok(TcpStream::connect(target_addr)?)).unwrap();
+        assert!(forward.local_addr().ip().is_loopback());
+        for _ in 0..2 {
+            let mut client = TcpStream::connect(forward.local_addr()).unwrap();
+            client.write_all(b"ping").unwrap();
+            let mut reply = String::new();
+            client.read_to_string(&mut reply).unwrap();
+            assert_eq!(reply, "pong");
+        }
+    }
+}
@@ -0,0 +1,334 @@
+//! Reachability and latency of replication targets.
+//!
+//! Before targets are queued for deployment each candidate is probed: its name
+//! is resolved, a TCP connection is opened to its SSH port and the server's
+//! `SSH-` banner awaited, and one ping is sent. Ping is informational only,
+//! since many hosts filter ICMP. Probes are cached for `cache_seconds`, and
+//! candidates are ordered by a score that combines their configured priority,
+//! measured latency and past failure rate. Targets that cannot be reached are
+//! left out until a later probe reaches them.
+
+use crate::self_replicator::ReplicationTarget;
+use chrono::{DateTime, Utc};
+use common::host_port;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+use tokio::process::Command;
+use tokio::sync::RwLock;
+use tokio::time::timeout;
+
+/// 复制目标的探测与排序，写在复制策略的 `probe` 字段中
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProbeConfig {
+    /// 关闭后按 `priority` 顺序选择目标，不做探测
+    pub enabled: bool,
+    /// DNS 解析、TCP 连接、SSH 握手信息和 ping 各自的超时
+    pub timeout_ms: u64,
+    /// 探测结果的缓存时间
+    pub cache_seconds: u64,
+    /// 延迟每增加这么多毫秒，相当于优先级降低一级
+    pub latency_ms_per_priority: f64,
+    /// 失败率为 100% 时相当于降低的优先级级数
+    pub failure_rate_weight: f64,
+}
+
+impl Default for ProbeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout_ms: 3000,
+            cache_seconds: 300,
+            latency_ms_per_priority: 100.0,
+            failure_rate_weight: 5.0,
+        }
+    }
+}
+
+impl ProbeConfig {
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+/// One probe of a target's SSH endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TargetProbe {
+    /// Whether the SSH endpoint answered with a banner
+    pub reachable: bool,
+    pub dns_ms: Option<f64>,
+    pub connect_ms: Option<f64>,
+    /// From opening the connection to receiving the SSH banner
+    pub banner_ms: Option<f64>,
+    pub ping_ms: Option<f64>,
+    pub error: Option<String>,
+    pub probed_at: DateTime<Utc>,
+}
+
+impl TargetProbe {
+    fn unreachable(error: impl Into<String>) -> Self {
+        Self {
+            reachable: false,
+            dns_ms: None,
+            connect_ms: None,
+            banner_ms: None,
+            ping_ms: None,
+            error: Some(error.into()),
+            probed_at: Utc::now(),
+        }
+    }
+
+    /// The latency targets are ordered by: connecting plus the SSH banner, which
+    /// is what a deployment waits for, else the ping round trip.
+    pub fn latency_ms(&self) -> Option<f64> {
+        match (self.connect_ms, self.banner_ms) {
+            (Some(connect), Some(banner)) => Some(connect + banner),
+            _ => self.ping_ms,
+        }
+    }
+}
+
+/// Probes targets and caches the results, shared by clones.
+#[derive(Debug, Clone, Default)]
+pub struct TargetProber {
+    cache: Arc<RwLock<HashMap<String, TargetProbe>>>,
+}
+
+impl TargetProber {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cached probe of `host:port` if it is younger than `cache_seconds`,
+    /// otherwise a fresh one.
+    pub async fn probe(&self, host: &str, port: u16, config: &ProbeConfig) -> TargetProbe {
+        let key = host_port(host, port);
+        let max_age = chrono::Duration::seconds(config.cache_seconds as i64);
+        if let Some(probe) = self.cache.read().await.get(&key) {
+            if Utc::now() - probe.probed_at < max_age {
+                return probe.clone();
+            }
+        }
+        let probe = measure(host, port, config.timeout()).await;
+        self.cache.write().await.insert(key, probe.clone());
+        probe
+    }
+
+    /// Every cached probe by `host:port`
+    pub async fn probes(&self) -> HashMap<String, TargetProbe> {
+        self.cache.read().await.clone()
+    }
+}
+
+async fn measure(host: &str, port: u16, limit: Duration) -> TargetProbe {
+    let elapsed_ms = |since: Instant| since.elapsed().as_secs_f64() * 1000.0;
+
+    let started = Instant::now();
+    let addr = match timeout(limit, tokio::net::lookup_host(host_port(host, port))).await {
+        Ok(Ok(mut addrs)) => match addrs.next() {
+            Some(addr) => addr,
+            None => return TargetProbe::unreachable("DNS lookup returned no addresses"),
+        },
+        Ok(Err(e)) => return TargetProbe::unreachable(format!("DNS lookup failed: {}", e)),
+        Err(_) => return TargetProbe::unreachable("DNS lookup timed out"),
+    };
+    let dns_ms = Some(elapsed_ms(started));
+
+    let started = Instant::now();
+    let connected = timeout(limit, TcpStream::connect(addr)).await;
+    let connect_ms = elapsed_ms(started);
+    let ping_ms = ping(host, limit).await;
+    let mut stream = match connected {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => {
+            return TargetProbe {
+                dns_ms,
+                ping_ms,
+                ..TargetProbe::unreachable(format!("connecting to {} failed: {}", addr, e))
+            }
+        }
+        Err(_) => {
+            return TargetProbe {
+                dns_ms,
+                ping_ms,
+                ..TargetProbe::unreachable(format!("connecting to {} timed out", addr))
+            }
+        }
+    };
+
+    let started = Instant::now();
+    let mut banner = [0u8; 64];
+    let (banner_ms, error) = match timeout(limit, stream.read(&mut banner)).await {
+        Ok(Ok(n)) if banner[..n].starts_with(b"SSH-") => (Some(elapsed_ms(started)), None),
+        Ok(Ok(_)) => (None, Some("port does not speak SSH".to_string())),
+        Ok(Err(e)) => (None, Some(format!("reading the SSH banner failed: {}", e))),
+        Err(_) => (None, Some("no SSH banner before the timeout".to_string())),
+    };
+    TargetProbe {
+        reachable: banner_ms.is_some(),
+        dns_ms,
+        connect_ms: Some(connect_ms),
+        banner_ms,
+        ping_ms,
+        error,
+        probed_at: Utc::now(),
+    }
+}
+
+/// Round trip of one ping, `None` when ping is unavailable or gets no answer.
+async fn ping(host: &str, limit: Duration) -> Option<f64> {
+    let wait = limit.as_secs().max(1).to_string();
+    let output = timeout(
+        limit + Duration::from_secs(1),
+        Command::new("ping")
+            .args(["-c", "1", "-W", &wait, host])
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .ok()?
+    .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_ping_time(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_ping_time(output: &str) -> Option<f64> {
+    let rest = &output[output.find("time=")? + "time=".len()..];
+    let end = rest
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(rest.len());
+    rest[..end].parse().ok()
+}
+
+/// How good a target is to deploy to next, lower is better: its priority, one
+/// step per `latency_ms_per_priority` of latency, and `failure_rate_weight`
+/// steps at a 100% failure rate. `None` for targets the probe did not reach.
+pub fn score(
+    target: &ReplicationTarget,
+    probe: Option<&TargetProbe>,
+    config: &ProbeConfig,
+) -> Option<f64> {
+    if probe.is_some_and(|p| !p.reachable) {
+        return None;
+    }
+    let latency = probe.and_then(TargetProbe::latency_ms).unwrap_or(0.0);
+    let attempts = target.success_count + target.failure_count;
+    let failure_rate = if attempts == 0 {
+        0.0
+    } else {
+        target.failure_count as f64 / attempts as f64
+    };
+    Some(
+        target.priority as f64
+            + latency / config.latency_ms_per_priority.max(f64::EPSILON)
+            + failure_rate * config.failure_rate_weight,
+    )
+}
+
+/// Reachable targets, best first. `probes` pairs with `targets`; a target
+/// without a probe is ranked on priority and failure rate alone.
+pub fn rank<'a>(
+    targets: &[&'a ReplicationTarget],
+    probes: &[Option<TargetProbe>],
+    config: &ProbeConfig,
+) -> Vec<&'a ReplicationTarget> {
+    let mut scored: Vec<(f64, &ReplicationTarget)> = targets
+        .iter()
+        .enumerate()
+        .filter_map(|(i, target)| {
+            let probe = probes.get(i).and_then(Option::as_ref);
+            score(target, probe, config).map(|score| (score, *target))
+        })
+        .collect();
+    scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+    scored.into_iter().map(|(_, target)| target).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn target(ip: &str, priority: u8, success_count: u32, failure_count: u32) -> ReplicationTarget {
+        ReplicationTarget {
+            ip: ip.to_string(),
+            user: "root".to_string(),
+            ssh_key_path: PathBuf::from("~/.ssh/id_rsa"),
+            remote_path: PathBuf::from("/opt/aurelia"),
+            priority,
+            last_attempt: None,
+            success_count,
+            failure_count,
+        }
+    }
+
+    fn reached(latency_ms: f64) -> Option<TargetProbe> {
+        Some(TargetProbe {
+            reachable: true,
+            dns_ms: Some(1.0),
+            connect_ms: Some(latency_ms / 2.0),
+            banner_ms: Some(latency_ms / 2.0),
+            ping_ms: None,
+            error: None,
+            probed_at: Utc::now(),
+        })
+    }
+
+    #[test]
+    fn test_candidates_are_ranked_by_priority_latency_and_failures() {
+        let config = ProbeConfig::default();
+        let near = target("10.0.0.1", 2, 0, 0);
+        let far = target("10.0.0.2", 1, 0, 0);
+        let flaky = target("10.0.0.3", 1, 1, 3);
+        let down = target("10.0.0.4", 0, 0, 0);
+        let unprobed = target("10.0.0.5", 3, 0, 0);
+
+        let ranked = rank(
+            &[&near, &far, &flaky, &down, &unprobed],
+            &[
+                reached(20.0),
+                // 250ms costs two and a half priority steps
+                reached(250.0),
+                reached(20.0),
+                Some(TargetProbe::unreachable("connection refused")),
+                None,
+            ],
+            &config,
+        );
+        let ips: Vec<_> = ranked.iter().map(|t| t.ip.as_str()).collect();
+        // The unreachable target is left out despite its priority
+        assert_eq!(ips, ["10.0.0.1", "10.0.0.5", "10.0.0.2", "10.0.0.3"]);
+    }
+
+    #[test]
+    fn test_ping_output_is_parsed() {
+        let linux = "64 bytes from 10.0.0.1: icmp_seq=1 ttl=64 time=0.045 ms";
+        assert_eq!(parse_ping_time(linux), Some(0.045));
+        assert_eq!(parse_ping_time("1 packets transmitted, 0 received"), None);
+    }
+
+    #[tokio::test]
+    async fn test_refused_port_is_unreachable_and_cached() {
+        // Bind and drop a listener so the port is very likely closed
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let prober = TargetProber::new();
+        let config = ProbeConfig {
+            timeout_ms: 500,
+            ..ProbeConfig::default()
+        };
+        let probe = prober.probe("127.0.0.1", port, &config).await;
+        assert!(!probe.reachable);
+        assert!(probe.dns_ms.is_some());
+        assert_eq!(prober.probe("127.0.0.1", port, &config).await, probe);
+    }
+}
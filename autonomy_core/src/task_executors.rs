@@ -0,0 +1,321 @@
+use crate::task_scheduler::{Task, TaskExecutor, TaskResult};
+use anyhow::{anyhow, Result};
+use common::ProcessPriority;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+
+/// A custom task executor declared in the autonomy config, registered for
+/// `TaskType::Custom(name)`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutorConfig {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: ExecutorKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExecutorKind {
+    Shell(ShellCommandExecutor),
+    Http(HttpCallbackExecutor),
+}
+
+impl ExecutorConfig {
+    /// Shell commands run with `priority`, as they may be as heavy as a backtest
+    pub fn build(&self, priority: &ProcessPriority) -> Result<Box<dyn TaskExecutor>> {
+        match &self.kind {
+            ExecutorKind::Shell(shell) => {
+                Ok(Box::new(shell.clone().with_priority(priority.clone())))
+            }
+            ExecutorKind::Http(http) => Ok(Box::new(http.clone().connect()?)),
+        }
+    }
+}
+
+/// Resource limits applied to shell command tasks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SandboxLimits {
+    /// CPU time the command may use, enforced with `ulimit -t`
+    pub cpu_seconds: u64,
+    /// Virtual memory the command may use, enforced with `ulimit -v`
+    pub memory_mb: u64,
+    /// Output kept from stdout and stderr each; the rest is discarded
+    pub max_output_bytes: usize,
+    /// Environment variables passed through; everything else is cleared
+    pub allowed_env: Vec<String>,
+}
+
+impl Default for SandboxLimits {
+    fn default() -> Self {
+        Self {
+            cpu_seconds: 60,
+            memory_mb: 512,
+            max_output_bytes: 64 * 1024,
+            allowed_env: vec!["PATH".to_string(), "HOME".to_string()],
+        }
+    }
+}
+
+/// Runs a program for each task. The task itself is available to the program as
+/// JSON in `AURELIA_TASK`. The task succeeds if the program exits with status 0.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShellCommandExecutor {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub working_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub limits: SandboxLimits,
+    #[serde(skip)]
+    pub priority: ProcessPriority,
+}
+
+impl ShellCommandExecutor {
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            args: Vec::new(),
+            working_dir: None,
+            limits: SandboxLimits::default(),
+            priority: ProcessPriority::default(),
+        }
+    }
+
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: SandboxLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_priority(mut self, priority: ProcessPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    fn command(&self, task: &Task) -> Result<Command> {
+        self.command_with_env(task, |key| std::env::var(key).ok())
+    }
+
+    /// The command for `task`, passing on the variables of [`SandboxLimits::allowed_env`]
+    /// that `agent_env` has a value for.
+    fn command_with_env(
+        &self,
+        task: &Task,
+        agent_env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Command> {
+        // `sh` applies the limits and then execs the program with its arguments
+        // untouched, so nothing from the config is ever interpreted by the shell
+        let script = format!(
+            "ulimit -t {} && ulimit -v {} && exec \"$0\" \"$@\"",
+            self.limits.cpu_seconds,
+            self.limits.memory_mb * 1024
+        );
+
+        let argv = self.priority.wrap("sh");
+        let mut cmd = Command::new(&argv[0]);
+        cmd.args(&argv[1..])
+            .arg("-c")
+            .arg(script)
+            .arg(&self.command)
+            .args(&self.args)
+            .env_clear()
+            .env("AURELIA_TASK", serde_json::to_string(task)?)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        for key in &self.limits.allowed_env {
+            if let Some(value) = agent_env(key) {
+                cmd.env(key, value);
+            }
+        }
+        if let Some(dir) = &self.working_dir {
+            cmd.current_dir(dir);
+        }
+        Ok(cmd)
+    }
+}
+
+#[async_trait::async_trait]
+impl TaskExecutor for ShellCommandExecutor {
+    async fn execute(&self, task: &Task) -> Result<TaskResult> {
+        let start = Instant::now();
+        let mut child = self.command(task)?.spawn()?;
+
+        let limit = self.limits.max_output_bytes as u64;
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("no stdout"))?;
+        let stderr = child.stderr.take().ok_or_else(|| anyhow!("no stderr"))?;
+        // The scheduler's timeout drops this future, and with it the child
+        let (stdout, stderr, status) = tokio::join!(
+            read_limited(stdout, limit),
+            read_limited(stderr, limit),
+            child.wait()
+        );
+        let status = status?;
+
+        Ok(TaskResult {
+            success: status.success(),
+            message: format!("{} exited with {}", self.command, status),
+            data: Some(serde_json::json!({
+                "exit_code": status.code(),
+                "stdout": stdout?,
+                "stderr": stderr?,
+            })),
+            execution_time_seconds: start.elapsed().as_secs(),
+        })
+    }
+}
+
+/// Read at most `limit` bytes, then drain the rest so the child never blocks on a
+/// full pipe.
+async fn read_limited(reader: impl tokio::io::AsyncRead + Unpin, limit: u64) -> Result<String> {
+    let mut kept = Vec::new();
+    let mut reader = reader.take(limit);
+    reader.read_to_end(&mut kept).await?;
+    tokio::io::copy(&mut reader.into_inner(), &mut tokio::io::sink()).await?;
+    Ok(String::from_utf8_lossy(&kept).into_owned())
+}
+
+/// POSTs each task as JSON to a URL. The task succeeds on a 2xx response; a JSON
+/// response body is kept as the task result's data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpCallbackExecutor {
+    pub url: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default = "default_http_timeout_seconds")]
+    pub timeout_seconds: u64,
+    #[serde(skip)]
+    client: Option<reqwest::Client>,
+}
+
+fn default_http_timeout_seconds() -> u64 {
+    30
+}
+
+impl HttpCallbackExecutor {
+    pub fn new(url: impl Into<String>) -> Result<Self> {
+        Self {
+            url: url.into(),
+            headers: HashMap::new(),
+            timeout_seconds: default_http_timeout_seconds(),
+            client: None,
+        }
+        .connect()
+    }
+
+    fn connect(mut self) -> Result<Self> {
+        self.client = Some(
+            reqwest::Client::builder()
+                .timeout(Duration::from_secs(self.timeout_seconds))
+                .build()?,
+        );
+        Ok(self)
+    }
+}
+
+#[async_trait::async_trait]
+impl TaskExecutor for HttpCallbackExecutor {
+    async fn execute(&self, task: &Task) -> Result<TaskResult> {
+        let start = Instant::now();
+        let client = self
+            .client
+            .as_ref()
+            .ok_or_else(|| anyhow!("HTTP executor for {} is not connected", self.url))?;
+
+        let mut request = client.post(&self.url).json(task);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+
+        Ok(TaskResult {
+            success: status.is_success(),
+            message: format!("{} answered {}", self.url, status),
+            data: serde_json::from_str(&body).ok(),
+            execution_time_seconds: start.elapsed().as_secs(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task_scheduler::{DependencyMode, TaskStatus, TaskType};
+    use chrono::Utc;
+
+    fn task() -> Task {
+        Task {
+            id: "custom-1".to_string(),
+            name: "custom".to_string(),
+            task_type: TaskType::Custom("echo".to_string()),
+            priority: 5,
+            scheduled_time: Utc::now(),
+            dependencies: vec![],
+            dependency_mode: DependencyMode::All,
+            max_retries: 0,
+            retry_count: 0,
+            timeout_seconds: 30,
+            status: TaskStatus::Pending,
+            result: None,
+            schedule: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shell_executor_runs_in_a_clean_environment() {
+        let config: ExecutorConfig = serde_json::from_str(
+            r#"{"name": "env", "type": "shell", "command": "sh",
+                "args": ["-c", "echo $AURELIA_TASK"],
+                "limits": {"max_output_bytes": 4096}}"#,
+        )
+        .unwrap();
+
+        let result = config
+            .build(&ProcessPriority::default())
+            .unwrap()
+            .execute(&task())
+            .await
+            .unwrap();
+        assert!(result.success);
+        let stdout = result.data.unwrap()["stdout"].as_str().unwrap().to_string();
+        assert!(stdout.contains("custom-1"));
+
+        // Only the allowed variables of the agent's environment reach the program
+        let executor = ShellCommandExecutor::new("sh").with_args(vec![
+            "-c".to_string(),
+            "echo ${SECRET:-unset} ${HOME:-unset}".to_string(),
+        ]);
+        let agent_env = |key: &str| match key {
+            "SECRET" => Some("leaked".to_string()),
+            "HOME" => Some("/home/aurelia".to_string()),
+            _ => std::env::var(key).ok(),
+        };
+        let output = executor
+            .command_with_env(&task(), agent_env)
+            .unwrap()
+            .output()
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "unset /home/aurelia\n"
+        );
+
+        let failing = ShellCommandExecutor::new("false");
+        assert!(!failing.execute(&task()).await.unwrap().success);
+    }
+}
@@ -1,11 +1,19 @@
+use crate::cron::CronSchedule;
 use anyhow::Result;
 use chrono::{DateTime, Duration, Utc};
+pub use common::SchedulerStatus;
+use common::{clock, SharedClock};
 use serde::{Deserialize, Serialize};
 use std::collections::{BinaryHeap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
 
+/// Where the agent keeps its task queue, relative to the deployment directory
+pub const TASK_QUEUE_PATH: &str = "data/tasks.json";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
     pub id: String,
@@ -14,11 +22,88 @@ pub struct Task {
     pub priority: u8,
     pub scheduled_time: DateTime<Utc>,
     pub dependencies: Vec<String>,
+    /// Whether the task waits for all of its dependencies or just one of them
+    #[serde(default)]
+    pub dependency_mode: DependencyMode,
     pub max_retries: u32,
     pub retry_count: u32,
     pub timeout_seconds: u64,
     pub status: TaskStatus,
     pub result: Option<TaskResult>,
+    /// When set, the task is scheduled again after each run
+    #[serde(default)]
+    pub schedule: Option<TaskSchedule>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyMode {
+    /// Run after all dependencies completed; give up once any of them failed
+    #[default]
+    All,
+    /// Run after any dependency completed; give up once all of them failed
+    Any,
+}
+
+/// Where a task stands with respect to its dependencies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Readiness {
+    Ready,
+    Blocked,
+    Unsatisfiable,
+}
+
+impl Task {
+    fn readiness(&self, finished: &[Task]) -> Readiness {
+        if self.dependencies.is_empty() {
+            return Readiness::Ready;
+        }
+
+        let (mut completed, mut failed) = (0, 0);
+        for dep_id in &self.dependencies {
+            // The latest run counts for recurring dependencies
+            match finished
+                .iter()
+                .rev()
+                .find(|t| t.id == *dep_id)
+                .map(|t| &t.status)
+            {
+                Some(TaskStatus::Completed) => completed += 1,
+                Some(TaskStatus::Failed) | Some(TaskStatus::Cancelled) => failed += 1,
+                _ => {}
+            }
+        }
+
+        let total = self.dependencies.len();
+        match self.dependency_mode {
+            DependencyMode::All if completed == total => Readiness::Ready,
+            DependencyMode::All if failed > 0 => Readiness::Unsatisfiable,
+            DependencyMode::Any if completed > 0 => Readiness::Ready,
+            DependencyMode::Any if failed == total => Readiness::Unsatisfiable,
+            _ => Readiness::Blocked,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskSchedule {
+    Every { seconds: u64 },
+    Cron(CronSchedule),
+}
+
+impl TaskSchedule {
+    pub fn cron(expression: &str) -> Result<Self> {
+        Ok(TaskSchedule::Cron(expression.parse()?))
+    }
+
+    /// The first run strictly after `after`
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            TaskSchedule::Every { seconds } => Some(after + Duration::seconds(*seconds as i64)),
+            TaskSchedule::Cron(cron) => cron.next_after(after),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -35,6 +120,8 @@ pub enum TaskType {
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum TaskStatus {
+    /// Held back until its dependencies allow it to run
+    Waiting,
     Pending,
     Running,
     Completed,
@@ -77,11 +164,23 @@ impl Ord for Task {
 
 pub struct TaskScheduler {
     task_queue: Arc<RwLock<BinaryHeap<Task>>>,
+    waiting_tasks: Arc<RwLock<HashMap<String, Task>>>,
     running_tasks: Arc<RwLock<HashMap<String, Task>>>,
     completed_tasks: Arc<RwLock<Vec<Task>>>,
     task_executors: Arc<RwLock<HashMap<TaskType, Box<dyn TaskExecutor>>>>,
     max_concurrent_tasks: usize,
     default_retry_delay_seconds: u64,
+    store: Option<PathBuf>,
+    dirty: Arc<AtomicBool>,
+    clock: SharedClock,
+}
+
+/// On-disk form of the queue; tasks that were running are saved as pending so they
+/// run again after a restart
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PersistedQueue {
+    pending: Vec<Task>,
+    completed: Vec<Task>,
 }
 
 #[async_trait::async_trait]
@@ -99,14 +198,72 @@ impl TaskScheduler {
     pub fn new() -> Self {
         Self {
             task_queue: Arc::new(RwLock::new(BinaryHeap::new())),
+            waiting_tasks: Arc::new(RwLock::new(HashMap::new())),
             running_tasks: Arc::new(RwLock::new(HashMap::new())),
             completed_tasks: Arc::new(RwLock::new(Vec::new())),
             task_executors: Arc::new(RwLock::new(HashMap::new())),
             max_concurrent_tasks: 5,
             default_retry_delay_seconds: 30,
+            store: None,
+            dirty: Arc::new(AtomicBool::new(false)),
+            clock: clock::system(),
         }
     }
 
+    /// Decide which tasks are due, and when retries and repeats run, by `clock`
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// A scheduler that saves its queue to `path` and restores whatever was saved there
+    pub fn with_persistence(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let persisted: PersistedQueue = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?
+        } else {
+            PersistedQueue::default()
+        };
+        info!(
+            "Restored {} pending and {} completed tasks from {:?}",
+            persisted.pending.len(),
+            persisted.completed.len(),
+            path
+        );
+
+        let (waiting, pending): (Vec<_>, Vec<_>) = persisted
+            .pending
+            .into_iter()
+            .partition(|task| task.status == TaskStatus::Waiting);
+
+        let mut scheduler = Self::new();
+        scheduler.task_queue = Arc::new(RwLock::new(
+            pending
+                .into_iter()
+                .map(|mut task| {
+                    task.status = TaskStatus::Pending;
+                    task
+                })
+                .collect(),
+        ));
+        scheduler.waiting_tasks = Arc::new(RwLock::new(
+            waiting
+                .into_iter()
+                .map(|task| (task.id.clone(), task))
+                .collect(),
+        ));
+        scheduler.completed_tasks = Arc::new(RwLock::new(persisted.completed));
+        scheduler.store = Some(path);
+        Ok(scheduler)
+    }
+
+    /// Whether a task with `task_id` is waiting to run or running
+    pub async fn is_scheduled(&self, task_id: &str) -> bool {
+        self.task_queue.read().await.iter().any(|t| t.id == task_id)
+            || self.waiting_tasks.read().await.contains_key(task_id)
+            || self.running_tasks.read().await.contains_key(task_id)
+    }
+
     pub async fn register_executor(&self, task_type: TaskType, executor: Box<dyn TaskExecutor>) {
         self.task_executors
             .write()
@@ -114,20 +271,61 @@ impl TaskScheduler {
             .insert(task_type, executor);
     }
 
+    /// Register the executor for tasks of type `TaskType::Custom(name)`, replacing
+    /// any executor registered under that name before
+    pub async fn register_custom_executor(
+        &self,
+        name: impl Into<String>,
+        executor: Box<dyn TaskExecutor>,
+    ) {
+        let name = name.into();
+        info!("Registering executor for custom task type {}", name);
+        self.register_executor(TaskType::Custom(name), executor)
+            .await;
+    }
+
+    /// Queue `task`. A task whose dependencies have not completed yet waits and is
+    /// released as soon as they have; one whose dependencies can no longer be met is
+    /// cancelled.
     pub async fn schedule_task(&self, mut task: Task) -> Result<()> {
         info!("Scheduling task: {} ({})", task.name, task.id);
 
-        // Validate dependencies
-        if !self.validate_dependencies(&task).await {
-            return Err(anyhow::anyhow!("Task has unmet dependencies"));
+        let readiness = task.readiness(&self.completed_tasks.read().await);
+        match readiness {
+            Readiness::Ready => {
+                task.status = TaskStatus::Pending;
+                self.task_queue.write().await.push(task);
+            }
+            Readiness::Blocked => {
+                debug!("Task {} waits for {:?}", task.id, task.dependencies);
+                task.status = TaskStatus::Waiting;
+                self.waiting_tasks
+                    .write()
+                    .await
+                    .insert(task.id.clone(), task);
+            }
+            Readiness::Unsatisfiable => {
+                warn!("Task {} cannot run: its dependencies failed", task.id);
+                task.status = TaskStatus::Cancelled;
+                self.completed_tasks.write().await.push(task);
+            }
         }
-
-        task.status = TaskStatus::Pending;
-        self.task_queue.write().await.push(task);
+        self.dirty.store(true, Ordering::Relaxed);
 
         Ok(())
     }
 
+    /// Schedule `task` to run at every match of a cron expression, starting with the
+    /// next one
+    pub async fn schedule_cron_task(&self, mut task: Task, expression: &str) -> Result<()> {
+        let schedule = TaskSchedule::cron(expression)?;
+        task.scheduled_time = schedule
+            .next_after(self.clock.now())
+            .ok_or_else(|| anyhow::anyhow!("cron expression '{}' never matches", expression))?;
+        task.schedule = Some(schedule);
+        self.schedule_task(task).await
+    }
+
     pub async fn schedule_recurring_task(
         &self,
         base_task: Task,
@@ -148,30 +346,51 @@ impl TaskScheduler {
         Ok(())
     }
 
-    async fn validate_dependencies(&self, task: &Task) -> bool {
-        if task.dependencies.is_empty() {
-            return true;
+    /// Move waiting tasks whose dependencies have settled into the queue, or cancel
+    /// them if they can no longer run. Cancellations are repeated until nothing
+    /// changes so they propagate down chains of dependents.
+    async fn release_waiting_tasks(&self) {
+        let mut waiting = self.waiting_tasks.write().await;
+        if waiting.is_empty() {
+            return;
         }
 
-        let completed = self.completed_tasks.read().await;
-        for dep_id in &task.dependencies {
-            let dep_completed = completed
-                .iter()
-                .any(|t| t.id == *dep_id && t.status == TaskStatus::Completed);
+        loop {
+            let mut completed = self.completed_tasks.write().await;
+            let settled: Vec<(String, Readiness)> = waiting
+                .values()
+                .map(|task| (task.id.clone(), task.readiness(&completed)))
+                .filter(|(_, readiness)| *readiness != Readiness::Blocked)
+                .collect();
+            if settled.is_empty() {
+                break;
+            }
 
-            if !dep_completed {
-                debug!("Task {} has unmet dependency: {}", task.id, dep_id);
-                return false;
+            for (id, readiness) in settled {
+                let Some(mut task) = waiting.remove(&id) else {
+                    continue;
+                };
+                if readiness == Readiness::Ready {
+                    info!("Dependencies of task {} completed, releasing it", id);
+                    task.status = TaskStatus::Pending;
+                    self.task_queue.write().await.push(task);
+                } else {
+                    warn!("Cancelling task {}: its dependencies failed", id);
+                    task.status = TaskStatus::Cancelled;
+                    completed.push(task);
+                }
             }
+            self.dirty.store(true, Ordering::Relaxed);
         }
-
-        true
     }
 
     pub async fn run(&self) {
         info!("Starting autonomous task scheduler");
 
         loop {
+            // Release tasks whose dependencies have completed
+            self.release_waiting_tasks().await;
+
             // Process pending tasks
             self.process_pending_tasks().await;
 
@@ -181,13 +400,20 @@ impl TaskScheduler {
             // Clean up completed tasks
             self.cleanup_completed_tasks().await;
 
+            if self.dirty.swap(false, Ordering::Relaxed) {
+                if let Err(e) = self.persist().await {
+                    error!("Failed to persist task queue: {}", e);
+                    self.dirty.store(true, Ordering::Relaxed);
+                }
+            }
+
             // Wait before next cycle
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+            self.clock.sleep(std::time::Duration::from_secs(1)).await;
         }
     }
 
     async fn process_pending_tasks(&self) {
-        let now = Utc::now();
+        let now = self.clock.now();
         let running_count = self.running_tasks.read().await.len();
 
         if running_count >= self.max_concurrent_tasks {
@@ -214,6 +440,9 @@ impl TaskScheduler {
         }
 
         drop(queue); // Release lock
+        if !tasks_to_run.is_empty() {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
 
         // Execute tasks
         for task in tasks_to_run {
@@ -237,9 +466,11 @@ impl TaskScheduler {
         let completed_tasks = self.completed_tasks.clone();
         let task_queue = self.task_queue.clone();
         let retry_delay = self.default_retry_delay_seconds;
+        let dirty = self.dirty.clone();
+        let clock = self.clock.clone();
 
         tokio::spawn(async move {
-            let start_time = Utc::now();
+            let start_time = clock.now();
 
             // Get executor for task type
             // Get executor for task type (cannot clone Box<dyn TaskExecutor>)
@@ -275,7 +506,7 @@ impl TaskScheduler {
                 None
             };
 
-            let execution_time = (Utc::now() - start_time).num_seconds() as u64;
+            let execution_time = (clock.now() - start_time).num_seconds() as u64;
 
             // Update task result
             if let Some(result) = result {
@@ -302,7 +533,7 @@ impl TaskScheduler {
             if task.status == TaskStatus::Failed && task.retry_count < task.max_retries {
                 task.retry_count += 1;
                 task.status = TaskStatus::Retrying;
-                task.scheduled_time = Utc::now() + Duration::seconds(retry_delay as i64);
+                task.scheduled_time = clock.now() + Duration::seconds(retry_delay as i64);
 
                 info!(
                     "Retrying task {} (attempt {}/{})",
@@ -311,17 +542,22 @@ impl TaskScheduler {
 
                 task_queue.write().await.push(task.clone());
             } else {
+                // Queue the next run of a scheduled task
+                if let Some(next) = next_run(&task, clock.now()) {
+                    task_queue.write().await.push(next);
+                }
                 // Move to completed
                 completed_tasks.write().await.push(task);
             }
 
             // Remove from running
             running_tasks.write().await.remove(&task_id);
+            dirty.store(true, Ordering::Relaxed);
         });
     }
 
     async fn check_running_tasks(&self) {
-        let now = Utc::now();
+        let now = self.clock.now();
         let mut tasks_to_cancel = Vec::new();
 
         {
@@ -346,28 +582,60 @@ impl TaskScheduler {
                     data: None,
                     execution_time_seconds: task.timeout_seconds,
                 });
+                if let Some(next) = next_run(&task, now) {
+                    self.task_queue.write().await.push(next);
+                }
                 self.completed_tasks.write().await.push(task);
+                self.dirty.store(true, Ordering::Relaxed);
             }
         }
     }
 
+    async fn persist(&self) -> Result<()> {
+        let Some(path) = &self.store else {
+            return Ok(());
+        };
+
+        let mut pending: Vec<Task> = self.task_queue.read().await.iter().cloned().collect();
+        pending.extend(self.waiting_tasks.read().await.values().cloned());
+        pending.extend(self.running_tasks.read().await.values().cloned());
+        let snapshot = PersistedQueue {
+            pending,
+            completed: self.completed_tasks.read().await.clone(),
+        };
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Write then rename so a crash never leaves a truncated queue behind
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&snapshot)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
     async fn cleanup_completed_tasks(&self) {
         let mut completed = self.completed_tasks.write().await;
 
         // Keep only last 1000 tasks or tasks from last 24 hours
-        let cutoff = Utc::now() - Duration::hours(24);
+        let cutoff = self.clock.now() - Duration::hours(24);
 
         if completed.len() > 1000 {
             let drain_count = completed.len() - 1000;
             completed.drain(0..drain_count);
         }
 
+        let before = completed.len();
         completed.retain(|t| t.scheduled_time > cutoff);
+        if completed.len() != before {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
     }
 
     pub async fn get_status(&self) -> SchedulerStatus {
         SchedulerStatus {
             pending_tasks: self.task_queue.read().await.len(),
+            waiting_tasks: self.waiting_tasks.read().await.len(),
             running_tasks: self.running_tasks.read().await.len(),
             completed_tasks: self.completed_tasks.read().await.len(),
             next_task_time: self
@@ -391,22 +659,32 @@ impl TaskScheduler {
             }
         }
 
+        // Check if task is waiting for dependencies
+        if let Some(mut task) = self.waiting_tasks.write().await.remove(task_id) {
+            task.status = TaskStatus::Cancelled;
+            self.completed_tasks.write().await.push(task);
+        }
+
         // Check if task is running
         if let Some(mut task) = self.running_tasks.write().await.remove(task_id) {
             task.status = TaskStatus::Cancelled;
             self.completed_tasks.write().await.push(task);
         }
+        self.dirty.store(true, Ordering::Relaxed);
 
         Ok(())
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SchedulerStatus {
-    pub pending_tasks: usize,
-    pub running_tasks: usize,
-    pub completed_tasks: usize,
-    pub next_task_time: Option<DateTime<Utc>>,
+/// The next run of a scheduled task, fresh as if newly scheduled
+fn next_run(task: &Task, now: DateTime<Utc>) -> Option<Task> {
+    let schedule = task.schedule.as_ref()?;
+    let mut next = task.clone();
+    next.scheduled_time = schedule.next_after(now.max(task.scheduled_time))?;
+    next.status = TaskStatus::Pending;
+    next.retry_count = 0;
+    next.result = None;
+    Some(next)
 }
 
 // Example executor implementations
@@ -442,3 +720,149 @@ impl TaskExecutor for ReplicationExecutor {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::Clock;
+
+    fn task(id: &str) -> Task {
+        Task {
+            id: id.to_string(),
+            name: id.to_string(),
+            task_type: TaskType::HealthCheck,
+            priority: 5,
+            scheduled_time: Utc::now(),
+            dependencies: vec![],
+            dependency_mode: DependencyMode::All,
+            max_retries: 0,
+            retry_count: 0,
+            timeout_seconds: 30,
+            status: TaskStatus::Pending,
+            result: None,
+            schedule: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_queue_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tasks.json");
+
+        let scheduler = TaskScheduler::with_persistence(&path).unwrap();
+        scheduler
+            .schedule_cron_task(task("nightly-backup"), "0 3 * * *")
+            .await
+            .unwrap();
+        scheduler.persist().await.unwrap();
+
+        let restored = TaskScheduler::with_persistence(&path).unwrap();
+        assert!(restored.is_scheduled("nightly-backup").await);
+        let queue = restored.task_queue.read().await;
+        let backup = queue.peek().unwrap();
+        assert_eq!(
+            backup.schedule,
+            Some(TaskSchedule::cron("0 3 * * *").unwrap())
+        );
+
+        let next = next_run(backup, Utc::now()).unwrap();
+        assert_eq!(
+            next.scheduled_time,
+            backup.scheduled_time + Duration::days(1)
+        );
+    }
+
+    /// Fails its first run and succeeds afterwards
+    struct FlakyExecutor(Arc<std::sync::atomic::AtomicUsize>);
+
+    #[async_trait::async_trait]
+    impl TaskExecutor for FlakyExecutor {
+        async fn execute(&self, _task: &Task) -> Result<TaskResult> {
+            let runs = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(TaskResult {
+                success: runs > 1,
+                message: format!("run {}", runs),
+                data: None,
+                execution_time_seconds: 0,
+            })
+        }
+    }
+
+    async fn settle(scheduler: &TaskScheduler) {
+        while !scheduler.running_tasks.read().await.is_empty() {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_due_tasks_and_retries_follow_the_clock() {
+        let clock = common::MockClock::default();
+        let scheduler = TaskScheduler::new().with_clock(Arc::new(clock.clone()));
+        let runs = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        scheduler
+            .register_executor(TaskType::HealthCheck, Box::new(FlakyExecutor(runs.clone())))
+            .await;
+
+        let mut later = task("later");
+        later.scheduled_time = clock.now() + Duration::hours(1);
+        later.max_retries = 1;
+        scheduler.schedule_task(later).await.unwrap();
+
+        scheduler.process_pending_tasks().await;
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+
+        clock.advance(std::time::Duration::from_secs(3600));
+        scheduler.process_pending_tasks().await;
+        settle(&scheduler).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        // The retry waits for the retry delay on the same clock
+        scheduler.process_pending_tasks().await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        clock.advance(std::time::Duration::from_secs(30));
+        scheduler.process_pending_tasks().await;
+        settle(&scheduler).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        let completed = scheduler.completed_tasks.read().await;
+        assert_eq!(completed[0].status, TaskStatus::Completed);
+    }
+
+    async fn finish(scheduler: &TaskScheduler, id: &str, status: TaskStatus) {
+        let mut done = task(id);
+        done.status = status;
+        scheduler.completed_tasks.write().await.push(done);
+        scheduler.release_waiting_tasks().await;
+    }
+
+    #[tokio::test]
+    async fn test_dependents_wait_and_are_released() {
+        let scheduler = TaskScheduler::new();
+
+        // fan-out from "fetch", fan-in to "report"
+        for (id, deps, mode) in [
+            ("analyse", vec!["fetch"], DependencyMode::All),
+            ("backup", vec!["fetch"], DependencyMode::All),
+            ("report", vec!["analyse", "backup"], DependencyMode::All),
+            ("notify", vec!["analyse", "backup"], DependencyMode::Any),
+        ] {
+            let mut t = task(id);
+            t.dependencies = deps.into_iter().map(String::from).collect();
+            t.dependency_mode = mode;
+            scheduler.schedule_task(t).await.unwrap();
+        }
+        assert_eq!(scheduler.get_status().await.waiting_tasks, 4);
+
+        finish(&scheduler, "fetch", TaskStatus::Completed).await;
+        assert_eq!(scheduler.get_status().await.pending_tasks, 2);
+
+        finish(&scheduler, "analyse", TaskStatus::Completed).await;
+        assert!(!scheduler.waiting_tasks.read().await.contains_key("notify"));
+        assert!(scheduler.waiting_tasks.read().await.contains_key("report"));
+
+        finish(&scheduler, "backup", TaskStatus::Failed).await;
+        assert_eq!(scheduler.get_status().await.waiting_tasks, 0);
+        let completed = scheduler.completed_tasks.read().await;
+        let report = completed.iter().find(|t| t.id == "report").unwrap();
+        assert_eq!(report.status, TaskStatus::Cancelled);
+    }
+}
@@ -0,0 +1,10 @@
+fn main() {
+    // The gRPC types are only generated for agents built with the `grpc` feature
+    #[cfg(feature = "grpc")]
+    {
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("vendored protoc is available");
+        std::env::set_var("PROTOC", protoc);
+        tonic_build::compile_protos("proto/aurelia.proto")
+            .expect("failed to compile aurelia.proto");
+    }
+}
@@ -0,0 +1,366 @@
+//! Append-only, hash-chained record of every privileged action.
+//!
+//! Each entry carries the SHA-256 of the entry before it, so editing, removing
+//! or reordering past entries breaks the chain and shows up in [`AuditLog::verify`].
+//! The kernel opens the log at startup and [`install`]s it for the whole process;
+//! SSH deployers, the order manager, the config endpoints and the recovery
+//! manager then [`record`] what they do without the log being threaded through
+//! every constructor. Before installation, recording is a no-op.
+//!
+//! CLI commands such as `kernel deploy` append to the same file as a running
+//! kernel; a log that finds the file grown by another process continues the
+//! chain from the other process's last entry.
+
+use crate::{AureliaError, AureliaResult};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Default location of the audit log, relative to the deployment directory
+pub const AUDIT_LOG_PATH: &str = "data/audit.jsonl";
+
+/// `prev_hash` of the first entry
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+static AUDIT_LOG: OnceLock<AuditLog> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditCategory {
+    SshCommand,
+    Order,
+    ConfigChange,
+    RecoveryAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Position in the log, starting at 0
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    pub category: AuditCategory,
+    pub action: String,
+    pub detail: serde_json::Value,
+    pub prev_hash: String,
+    /// SHA-256 over `prev_hash` and the fields above
+    pub hash: String,
+}
+
+impl AuditEntry {
+    fn compute_hash(&self) -> String {
+        let fields = serde_json::json!([
+            self.seq,
+            self.timestamp,
+            self.category,
+            self.action,
+            self.detail,
+        ]);
+        let mut hasher = Sha256::new();
+        hasher.update(self.prev_hash.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(fields.to_string().as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+#[derive(Debug)]
+struct Head {
+    next_seq: u64,
+    hash: String,
+    /// Length of the file after our last write
+    len: u64,
+    /// Entries of a log without a file
+    memory: Vec<AuditEntry>,
+}
+
+impl Head {
+    /// Continue the chain from the last entry in `path`. Lines that do not parse
+    /// are left for [`AuditLog::verify`] to report, so auditing goes on.
+    fn resume(&mut self, path: &Path) -> AureliaResult<()> {
+        let last = BufReader::new(File::open(path)?)
+            .lines()
+            .map_while(|line| line.ok())
+            .filter_map(|line| serde_json::from_str::<AuditEntry>(&line).ok())
+            .last();
+        if let Some(last) = last {
+            self.next_seq = last.seq + 1;
+            self.hash = last.hash;
+        }
+        self.len = std::fs::metadata(path)?.len();
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: Option<PathBuf>,
+    file: Option<Arc<Mutex<File>>>,
+    head: Arc<Mutex<Head>>,
+}
+
+impl AuditLog {
+    /// Open the log at `path`, continuing the chain from its last entry.
+    pub fn open(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut head = Head {
+            next_seq: 0,
+            hash: GENESIS_HASH.to_string(),
+            len: 0,
+            memory: Vec::new(),
+        };
+        if path.exists() {
+            head.resume(path)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            file: Some(Arc::new(Mutex::new(file))),
+            head: Arc::new(Mutex::new(head)),
+        })
+    }
+
+    /// A log that only keeps entries in memory.
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            file: None,
+            head: Arc::new(Mutex::new(Head {
+                next_seq: 0,
+                hash: GENESIS_HASH.to_string(),
+                len: 0,
+                memory: Vec::new(),
+            })),
+        }
+    }
+
+    /// Append an entry chained to the previous one.
+    ///
+    /// Other processes may append to the same file, so it is locked from reading
+    /// the last hash until the entry is written; otherwise the chain could fork.
+    pub fn record(
+        &self,
+        category: AuditCategory,
+        action: impl Into<String>,
+        detail: serde_json::Value,
+    ) -> AureliaResult<AuditEntry> {
+        let mut head = self.head.lock().expect("audit log lock poisoned");
+        let file = self
+            .file
+            .as_ref()
+            .map(|file| file.lock().expect("audit log lock poisoned"));
+        if let Some(file) = &file {
+            File::lock(file)?;
+        }
+        let result = self.append(&mut head, file.as_deref(), category, action.into(), detail);
+        if let Some(file) = &file {
+            File::unlock(file)?;
+        }
+        result
+    }
+
+    fn append(
+        &self,
+        head: &mut Head,
+        file: Option<&File>,
+        category: AuditCategory,
+        action: String,
+        detail: serde_json::Value,
+    ) -> AureliaResult<AuditEntry> {
+        if let Some(path) = &self.path {
+            if std::fs::metadata(path)?.len() != head.len {
+                head.resume(path)?;
+            }
+        }
+        let mut entry = AuditEntry {
+            seq: head.next_seq,
+            timestamp: Utc::now(),
+            category,
+            action,
+            detail,
+            prev_hash: head.hash.clone(),
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+        match file {
+            Some(mut file) => {
+                let line = serde_json::to_string(&entry)?;
+                writeln!(file, "{}", line)?;
+                file.flush()?;
+                head.len = file.metadata()?.len();
+            }
+            None => head.memory.push(entry.clone()),
+        }
+        head.next_seq += 1;
+        head.hash = entry.hash.clone();
+        Ok(entry)
+    }
+
+    /// Entries from `since_seq` on, oldest first.
+    pub fn entries(&self, since_seq: u64) -> AureliaResult<Vec<AuditEntry>> {
+        let entries = match &self.path {
+            Some(path) => read_entries(path)?,
+            None => self
+                .head
+                .lock()
+                .expect("audit log lock poisoned")
+                .memory
+                .clone(),
+        };
+        Ok(entries
+            .into_iter()
+            .filter(|entry| entry.seq >= since_seq)
+            .collect())
+    }
+
+    /// Check the whole chain, returning the number of entries.
+    pub fn verify(&self) -> AureliaResult<u64> {
+        verify_chain(&self.entries(0)?)
+    }
+}
+
+fn read_entries(path: &Path) -> AureliaResult<Vec<AuditEntry>> {
+    let mut entries = Vec::new();
+    for (number, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // A line that does not parse is tampering or corruption, never skipped
+        let entry = serde_json::from_str(&line).map_err(|e| {
+            AureliaError::Storage(format!("audit log line {} is invalid: {}", number + 1, e))
+        })?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Check that `entries` form an unbroken chain from the first entry.
+pub fn verify_chain(entries: &[AuditEntry]) -> AureliaResult<u64> {
+    let mut prev_hash = GENESIS_HASH.to_string();
+    for (expected_seq, entry) in entries.iter().enumerate() {
+        if entry.seq != expected_seq as u64 || entry.prev_hash != prev_hash {
+            return Err(AureliaError::Storage(format!(
+                "audit chain broken at entry {}",
+                expected_seq
+            )));
+        }
+        if entry.compute_hash() != entry.hash {
+            return Err(AureliaError::Storage(format!(
+                "audit entry {} was modified",
+                entry.seq
+            )));
+        }
+        prev_hash = entry.hash.clone();
+    }
+    Ok(entries.len() as u64)
+}
+
+/// Hex SHA-256 of `contents`, to identify files in audit details without copying
+/// secrets they may hold into the log.
+pub fn digest(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
+
+/// Make `log` the process-wide audit log. Only the first call has an effect.
+pub fn install(log: AuditLog) -> bool {
+    AUDIT_LOG.set(log).is_ok()
+}
+
+/// The process-wide audit log, if one was installed.
+pub fn global() -> Option<&'static AuditLog> {
+    AUDIT_LOG.get()
+}
+
+/// Record a privileged action in the process-wide audit log. Failing to write the
+/// log is reported but does not stop the action.
+pub fn record(category: AuditCategory, action: impl Into<String>, detail: impl Serialize) {
+    let Some(log) = global() else {
+        return;
+    };
+    let detail = serde_json::to_value(detail).unwrap_or_default();
+    if let Err(e) = log.record(category, action, detail) {
+        tracing::error!("Failed to write audit log: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_survives_reopening_and_detects_tampering() {
+        let path = std::env::temp_dir()
+            .join(format!("aurelia-audit-{}", uuid::Uuid::new_v4()))
+            .join("audit.jsonl");
+        let log = AuditLog::open(&path).unwrap();
+        log.record(
+            AuditCategory::SshCommand,
+            "mkdir -p /opt/aurelia",
+            serde_json::json!({"remote": "ubuntu@10.0.0.1:22", "exit_status": 0}),
+        )
+        .unwrap();
+        log.record(
+            AuditCategory::Order,
+            "submit",
+            serde_json::json!({"symbol": "BTCUSDT", "quantity": 0.001}),
+        )
+        .unwrap();
+        drop(log);
+
+        let reopened = AuditLog::open(&path).unwrap();
+        let entry = reopened
+            .record(
+                AuditCategory::RecoveryAction,
+                "RestartProcess",
+                serde_json::json!(null),
+            )
+            .unwrap();
+        assert_eq!(entry.seq, 2);
+        assert_eq!(reopened.verify().unwrap(), 3);
+        assert_eq!(reopened.entries(1).unwrap().len(), 2);
+
+        let tampered = std::fs::read_to_string(&path)
+            .unwrap()
+            .replace("BTCUSDT", "ETHUSDT");
+        std::fs::write(&path, tampered).unwrap();
+        assert!(reopened.verify().is_err());
+
+        let mut entries = AuditLog::open(&path).unwrap().entries(0).unwrap();
+        entries.remove(0);
+        assert!(verify_chain(&entries).is_err());
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_writers_sharing_a_file_keep_one_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        // Separately opened logs only coordinate through the file lock, like processes
+        let writers: Vec<_> = (0..4)
+            .map(|writer| {
+                let log = AuditLog::open(&path).unwrap();
+                std::thread::spawn(move || {
+                    for i in 0..25 {
+                        log.record(
+                            AuditCategory::ConfigChange,
+                            "write",
+                            serde_json::json!({"writer": writer, "i": i}),
+                        )
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(AuditLog::open(&path).unwrap().verify().unwrap(), 100);
+    }
+}
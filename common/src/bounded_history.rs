@@ -0,0 +1,222 @@
+//! In-memory histories with a size and age limit.
+//!
+//! Long-running agents record decisions, health samples, failures and
+//! replication attempts for as long as they live. [`BoundedHistory`] keeps the
+//! most recent entries in memory and drops the rest once there are more than
+//! `max_len` or they are older than `max_age`. With a spill file, dropped
+//! entries are appended to it as JSON lines instead of being lost; the file is
+//! rotated to `<path>.1` once it reaches [`SPILL_FILE_LIMIT`] bytes.
+
+use crate::AureliaResult;
+use chrono::{DateTime, Duration, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Directory the agent's histories spill to
+pub const HISTORY_SPILL_DIR: &str = "data/history";
+
+/// Size at which a spill file is rotated
+pub const SPILL_FILE_LIMIT: u64 = 16 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize)]
+struct SpilledEntry<T> {
+    recorded_at: DateTime<Utc>,
+    entry: T,
+}
+
+#[derive(Debug, Clone)]
+pub struct BoundedHistory<T> {
+    entries: VecDeque<(DateTime<Utc>, T)>,
+    max_len: usize,
+    max_age: Option<Duration>,
+    spill_path: Option<PathBuf>,
+}
+
+impl<T> BoundedHistory<T> {
+    /// Keep at most `max_len` entries
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            max_len: max_len.max(1),
+            max_age: None,
+            spill_path: None,
+        }
+    }
+
+    /// Also drop entries recorded longer than `max_age` ago
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Append dropped entries to `path` instead of discarding them
+    pub fn with_spill_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.spill_path = Some(path.into());
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries in memory, oldest first
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> + ExactSizeIterator {
+        self.entries.iter().map(|(_, entry)| entry)
+    }
+
+    /// Entries in memory with the time they were recorded, oldest first
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = (DateTime<Utc>, &T)> {
+        self.entries.iter().map(|(at, entry)| (*at, entry))
+    }
+
+    /// Entries recorded after `cutoff`, oldest first
+    pub fn since(&self, cutoff: DateTime<Utc>) -> impl Iterator<Item = &T> {
+        self.entries
+            .iter()
+            .filter(move |(at, _)| *at > cutoff)
+            .map(|(_, entry)| entry)
+    }
+
+    pub fn latest(&self) -> Option<&T> {
+        self.entries.back().map(|(_, entry)| entry)
+    }
+}
+
+impl<T: Serialize> BoundedHistory<T> {
+    pub fn push(&mut self, entry: T) {
+        self.push_at(Utc::now(), entry);
+    }
+
+    /// Record `entry` as of `recorded_at`, e.g. the timestamp it carries
+    pub fn push_at(&mut self, recorded_at: DateTime<Utc>, entry: T) {
+        self.entries.push_back((recorded_at, entry));
+        self.prune_at(Utc::now());
+    }
+
+    /// Drop entries that have outlived `max_age`
+    pub fn prune(&mut self) {
+        self.prune_at(Utc::now());
+    }
+
+    fn prune_at(&mut self, now: DateTime<Utc>) {
+        let mut dropped = Vec::new();
+        while self.entries.len() > self.max_len {
+            dropped.extend(self.entries.pop_front());
+        }
+        if let Some(max_age) = self.max_age {
+            let cutoff = now - max_age;
+            while self.entries.front().is_some_and(|(at, _)| *at <= cutoff) {
+                dropped.extend(self.entries.pop_front());
+            }
+        }
+        if dropped.is_empty() {
+            return;
+        }
+        if let Some(path) = &self.spill_path {
+            if let Err(e) = spill(path, &dropped) {
+                warn!("Failed to spill history to {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+impl<T: DeserializeOwned> BoundedHistory<T> {
+    /// Entries spilled to disk, oldest first, including the rotated file
+    pub fn spilled(&self) -> AureliaResult<Vec<(DateTime<Utc>, T)>> {
+        let Some(path) = &self.spill_path else {
+            return Ok(Vec::new());
+        };
+        let mut entries = read_spilled(&rotated(path))?;
+        entries.extend(read_spilled(path)?);
+        Ok(entries)
+    }
+}
+
+fn rotated(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+fn spill<T: Serialize>(path: &Path, dropped: &[(DateTime<Utc>, T)]) -> AureliaResult<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    if fs::metadata(path).is_ok_and(|meta| meta.len() >= SPILL_FILE_LIMIT) {
+        fs::rename(path, rotated(path))?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    for (recorded_at, entry) in dropped {
+        let line = serde_json::to_string(&SpilledEntry {
+            recorded_at: *recorded_at,
+            entry,
+        })?;
+        writeln!(file, "{}", line)?;
+    }
+    Ok(())
+}
+
+fn read_spilled<T: DeserializeOwned>(path: &Path) -> AureliaResult<Vec<(DateTime<Utc>, T)>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut entries = Vec::new();
+    for line in BufReader::new(fs::File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let spilled: SpilledEntry<T> = serde_json::from_str(&line)?;
+        entries.push((spilled.recorded_at, spilled.entry));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_and_age_bounds() {
+        let mut history = BoundedHistory::new(3);
+        for i in 0..5 {
+            history.push(i);
+        }
+        assert_eq!(history.iter().copied().collect::<Vec<_>>(), vec![2, 3, 4]);
+        assert_eq!(history.latest(), Some(&4));
+
+        let mut history = BoundedHistory::new(10).with_max_age(Duration::hours(1));
+        history.push_at(Utc::now() - Duration::hours(2), "stale");
+        history.push_at(Utc::now() - Duration::minutes(30), "recent");
+        assert_eq!(history.iter().copied().collect::<Vec<_>>(), vec!["recent"]);
+        assert_eq!(history.since(Utc::now() - Duration::minutes(10)).count(), 0);
+    }
+
+    #[test]
+    fn test_dropped_entries_are_spilled() {
+        let dir = std::env::temp_dir().join(format!("aurelia-history-{}", uuid::Uuid::new_v4()));
+        let mut history = BoundedHistory::new(2).with_spill_file(dir.join("numbers.jsonl"));
+        for i in 0..5u32 {
+            history.push(i);
+        }
+
+        let spilled: Vec<u32> = history
+            .spilled()
+            .unwrap()
+            .into_iter()
+            .map(|(_, entry)| entry)
+            .collect();
+        assert_eq!(spilled, vec![0, 1, 2]);
+        assert_eq!(history.iter().copied().collect::<Vec<_>>(), vec![3, 4]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
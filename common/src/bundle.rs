@@ -0,0 +1,325 @@
+use crate::signing::{ReleaseSigner, SIGNATURES_PATH, TRUSTED_KEY_PATH};
+use crate::{AureliaError, AureliaResult};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const MODE_FILE: i32 = 0o644;
+const MODE_EXECUTABLE: i32 = 0o755;
+
+#[derive(Debug, Clone)]
+enum Source {
+    File(PathBuf),
+    Template(String),
+    TemplateFile(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    source: Source,
+    destination: String,
+    mode: i32,
+}
+
+/// A file ready to be uploaded, with templates already rendered.
+#[derive(Debug, Clone)]
+pub struct RenderedFile {
+    /// Path relative to the remote deployment directory.
+    pub destination: String,
+    pub contents: Vec<u8>,
+    pub mode: i32,
+}
+
+impl RenderedFile {
+    pub fn is_executable(&self) -> bool {
+        self.mode & 0o111 != 0
+    }
+}
+
+/// One entry of a bundle manifest; exactly one of `source` and `template` is set.
+#[derive(Debug, Deserialize)]
+struct ManifestEntry {
+    source: Option<PathBuf>,
+    template: Option<PathBuf>,
+    destination: String,
+    #[serde(default)]
+    executable: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    files: Vec<ManifestEntry>,
+}
+
+/// The set of files shipped to a server in one deployment.
+///
+/// Templates may reference per-server values as `{{name}}`, set with
+/// [`DeploymentBundle::var`] before rendering. Referencing an unset value is an error
+/// rather than silently producing an empty string. Adding a file for a destination
+/// that is already in the bundle replaces the earlier entry.
+///
+/// A bundle signed with [`DeploymentBundle::signed_by`] also ships the signer's
+/// public key and the signature of every file, see [`crate::signing`].
+#[derive(Debug, Clone, Default)]
+pub struct DeploymentBundle {
+    entries: Vec<Entry>,
+    vars: BTreeMap<String, String>,
+    signer: Option<ReleaseSigner>,
+}
+
+impl DeploymentBundle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a bundle from a JSON manifest of the form
+    /// `{"files": [{"source": "...", "destination": "...", "executable": true}, {"template": "...", "destination": "..."}]}`.
+    /// Relative local paths are resolved against the manifest's directory.
+    pub fn from_manifest(path: &Path) -> AureliaResult<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let manifest: Manifest = serde_json::from_str(&content)?;
+        let base = path.parent().unwrap_or(Path::new("."));
+
+        let mut bundle = Self::new();
+        for entry in manifest.files {
+            let mode = if entry.executable {
+                MODE_EXECUTABLE
+            } else {
+                MODE_FILE
+            };
+            let source = match (entry.source, entry.template) {
+                (Some(source), None) => Source::File(base.join(source)),
+                (None, Some(template)) => Source::TemplateFile(base.join(template)),
+                _ => {
+                    return Err(AureliaError::Config(format!(
+                        "manifest entry for {} must have exactly one of source or template",
+                        entry.destination
+                    )))
+                }
+            };
+            bundle.push(source, entry.destination, mode);
+        }
+        Ok(bundle)
+    }
+
+    /// Ship a local file as is.
+    pub fn file(mut self, local: impl Into<PathBuf>, destination: impl Into<String>) -> Self {
+        self.push(Source::File(local.into()), destination, MODE_FILE);
+        self
+    }
+
+    /// Ship a local file and mark it executable.
+    pub fn executable(mut self, local: impl Into<PathBuf>, destination: impl Into<String>) -> Self {
+        self.push(Source::File(local.into()), destination, MODE_EXECUTABLE);
+        self
+    }
+
+    /// Ship an inline template.
+    pub fn template(mut self, template: impl Into<String>, destination: impl Into<String>) -> Self {
+        self.push(Source::Template(template.into()), destination, MODE_FILE);
+        self
+    }
+
+    /// Ship an inline template and mark the result executable.
+    pub fn executable_template(
+        mut self,
+        template: impl Into<String>,
+        destination: impl Into<String>,
+    ) -> Self {
+        self.push(
+            Source::Template(template.into()),
+            destination,
+            MODE_EXECUTABLE,
+        );
+        self
+    }
+
+    /// Ship a template read from a local file.
+    pub fn template_file(
+        mut self,
+        local: impl Into<PathBuf>,
+        destination: impl Into<String>,
+    ) -> Self {
+        self.push(Source::TemplateFile(local.into()), destination, MODE_FILE);
+        self
+    }
+
+    /// Set a value available to templates as `{{name}}`.
+    pub fn var(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.vars.insert(name.into(), value.to_string());
+        self
+    }
+
+    /// Set a value available to templates unless the bundle already has one.
+    pub fn default_var(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.vars
+            .entry(name.into())
+            .or_insert_with(|| value.to_string());
+        self
+    }
+
+    /// Sign every file when rendering.
+    pub fn signed_by(mut self, signer: ReleaseSigner) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Hex public key of the signer, if the bundle is signed.
+    pub fn public_key(&self) -> Option<String> {
+        self.signer.as_ref().map(ReleaseSigner::public_key)
+    }
+
+    fn push(&mut self, source: Source, destination: impl Into<String>, mode: i32) {
+        let destination = destination.into();
+        self.entries
+            .retain(|entry| entry.destination != destination);
+        self.entries.push(Entry {
+            source,
+            destination,
+            mode,
+        });
+    }
+
+    /// Read all files and render all templates. A signed bundle ends with its
+    /// trusted key and signatures.
+    pub fn render(&self) -> AureliaResult<Vec<RenderedFile>> {
+        let mut files = self.render_entries()?;
+        if let Some(signer) = &self.signer {
+            files.retain(|file| {
+                file.destination != TRUSTED_KEY_PATH && file.destination != SIGNATURES_PATH
+            });
+            files.push(RenderedFile {
+                destination: TRUSTED_KEY_PATH.to_string(),
+                contents: signer.public_key().into_bytes(),
+                mode: MODE_FILE,
+            });
+            let signatures = signer.sign_files(&files);
+            files.push(RenderedFile {
+                destination: SIGNATURES_PATH.to_string(),
+                contents: serde_json::to_vec_pretty(&signatures)?,
+                mode: MODE_FILE,
+            });
+        }
+        Ok(files)
+    }
+
+    fn render_entries(&self) -> AureliaResult<Vec<RenderedFile>> {
+        self.entries
+            .iter()
+            .map(|entry| {
+                let contents = match &entry.source {
+                    Source::File(path) => std::fs::read(path).map_err(|e| {
+                        AureliaError::Config(format!("failed to read {:?}: {}", path, e))
+                    })?,
+                    Source::Template(template) => {
+                        render_template(template, &self.vars)?.into_bytes()
+                    }
+                    Source::TemplateFile(path) => {
+                        let template = std::fs::read_to_string(path).map_err(|e| {
+                            AureliaError::Config(format!("failed to read {:?}: {}", path, e))
+                        })?;
+                        render_template(&template, &self.vars)?.into_bytes()
+                    }
+                };
+                Ok(RenderedFile {
+                    destination: entry.destination.clone(),
+                    contents,
+                    mode: entry.mode,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Replace every `{{name}}` in `template` with its value in `vars`. Unterminated
+/// placeholders and names without a value are errors.
+pub fn render_template(template: &str, vars: &BTreeMap<String, String>) -> AureliaResult<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| AureliaError::Config("unterminated {{ in template".to_string()))?;
+        let name = after[..end].trim();
+        let value = vars
+            .get(name)
+            .ok_or_else(|| AureliaError::Config(format!("template uses unset value {}", name)))?;
+        output.push_str(value);
+        rest = &after[end + 2..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_templates_with_server_values() {
+        let bundle = DeploymentBundle::new()
+            .template("AGENT_ID={{ agent_id }}\nPORT={{port}}\n", ".env")
+            .executable_template("#!/bin/sh\ncd {{remote_path}}\n", "start.sh")
+            .var("agent_id", "replica-1")
+            .var("port", 8080)
+            .var("remote_path", "/opt/aurelia");
+
+        let files = bundle.render().unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].destination, ".env");
+        assert_eq!(files[0].contents, b"AGENT_ID=replica-1\nPORT=8080\n");
+        assert!(!files[0].is_executable());
+        assert!(files[1].is_executable());
+
+        let replaced = bundle.template("PORT=9090\n", ".env").render().unwrap();
+        assert_eq!(replaced.len(), 2);
+        assert_eq!(replaced[1].contents, b"PORT=9090\n");
+
+        let missing = DeploymentBundle::new().template("{{primary_address}}", "x");
+        assert!(matches!(missing.render(), Err(AureliaError::Config(_))));
+
+        let defaulted = DeploymentBundle::new()
+            .template("{{agent_id}}@{{port}}", "x")
+            .var("agent_id", "replica-1")
+            .default_var("agent_id", "server-7")
+            .default_var("port", 8080);
+        assert_eq!(defaulted.render().unwrap()[0].contents, b"replica-1@8080");
+    }
+
+    #[test]
+    fn test_bundle_from_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("kernel"), b"binary").unwrap();
+        std::fs::create_dir(dir.path().join("templates")).unwrap();
+        std::fs::write(dir.path().join("templates/env"), "AGENT_ID={{agent_id}}\n").unwrap();
+        let manifest = dir.path().join("bundle.json");
+        std::fs::write(
+            &manifest,
+            r#"{"files": [
+                {"source": "kernel", "destination": "kernel", "executable": true},
+                {"template": "templates/env", "destination": ".env"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let files = DeploymentBundle::from_manifest(&manifest)
+            .unwrap()
+            .var("agent_id", "replica-1")
+            .render()
+            .unwrap();
+        assert_eq!(files[0].contents, b"binary");
+        assert!(files[0].is_executable());
+        assert_eq!(files[1].destination, ".env");
+        assert_eq!(files[1].contents, b"AGENT_ID=replica-1\n");
+
+        std::fs::write(
+            &manifest,
+            r#"{"files": [{"source": "kernel", "template": "templates/env", "destination": "x"}]}"#,
+        )
+        .unwrap();
+        assert!(DeploymentBundle::from_manifest(&manifest).is_err());
+    }
+}
@@ -0,0 +1,513 @@
+use crate::bus_metrics::{subscriber_snapshot, BusMetrics, BusMetricsSnapshot, SubscriberMetrics};
+use crate::{AppEvent, ControlReceiver};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use tokio::sync::broadcast::{
+    self,
+    error::{RecvError, SendError, TryRecvError},
+};
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+/// Coarse event categories used to route `AppEvent`s to interested subscribers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Topic {
+    System,
+    Market,
+    /// Unsampled trades, for consumers that must see every one
+    MarketTicks,
+    Strategy,
+    Financial,
+    Reasoning,
+    Deployment,
+    Control,
+}
+
+impl Topic {
+    pub const ALL: [Topic; 8] = [
+        Topic::System,
+        Topic::Market,
+        Topic::MarketTicks,
+        Topic::Strategy,
+        Topic::Financial,
+        Topic::Reasoning,
+        Topic::Deployment,
+        Topic::Control,
+    ];
+}
+
+impl Topic {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Topic::System => "system",
+            Topic::Market => "market",
+            Topic::MarketTicks => "market_ticks",
+            Topic::Strategy => "strategy",
+            Topic::Financial => "financial",
+            Topic::Reasoning => "reasoning",
+            Topic::Deployment => "deployment",
+            Topic::Control => "control",
+        }
+    }
+
+    /// The topic named `label`, as returned by [`Topic::label`].
+    pub fn from_label(label: &str) -> Option<Topic> {
+        Topic::ALL.into_iter().find(|topic| topic.label() == label)
+    }
+}
+
+impl AppEvent {
+    /// Name of the event's variant, used to label bus metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppEvent::SystemVitals(_) => "system_vitals",
+            AppEvent::MarketData(_) => "market_data",
+            AppEvent::MarketTick(_) => "market_tick",
+            AppEvent::Candle(_) => "candle",
+            AppEvent::MarketConditions(_) => "market_conditions",
+            AppEvent::StrategyDecision(..) => "strategy_decision",
+            AppEvent::ReloadConfig => "reload_config",
+            AppEvent::SystemStateChange(_) => "system_state_change",
+            AppEvent::FinancialUpdate(_) => "financial_update",
+            AppEvent::WebSearchQuery(_) => "web_search_query",
+            AppEvent::WebSearchResponse(_) => "web_search_response",
+            AppEvent::LlmQuery(..) => "llm_query",
+            AppEvent::LlmResponse(..) => "llm_response",
+            AppEvent::ModuleReadyForHotSwap(_) => "module_ready_for_hot_swap",
+            AppEvent::Deploy(_) => "deploy",
+            AppEvent::SetDecisionPolicy(_) => "set_decision_policy",
+            AppEvent::StrategyParamUpdate(_) => "strategy_param_update",
+            AppEvent::CandidateModuleReady(_) => "candidate_module_ready",
+            AppEvent::ShadowTrialCompleted(_) => "shadow_trial_completed",
+            AppEvent::MutationRejected(_) => "mutation_rejected",
+            AppEvent::SentimentUpdate(_) => "sentiment_update",
+            AppEvent::NewsItem(_) => "news_item",
+            AppEvent::FundingRate(_) => "funding_rate",
+            AppEvent::OpenInterest(_) => "open_interest",
+            AppEvent::TickerStats(_) => "ticker_stats",
+            AppEvent::CrossExchangeSpread(_) => "cross_exchange_spread",
+            AppEvent::OrderUpdate(_) => "order_update",
+            AppEvent::ExecutionProgress(_) => "execution_progress",
+            AppEvent::NetAssetValue(_) => "net_asset_value",
+            AppEvent::ProtectionTriggered(_) => "protection_triggered",
+            AppEvent::FleetValidation(_) => "fleet_validation",
+            AppEvent::FleetFunds(_) => "fleet_funds",
+            AppEvent::StrategyPerformance(_) => "strategy_performance",
+            AppEvent::DeploymentStatusChanged(_) => "deployment_status_changed",
+            AppEvent::ReplicationCompleted(_) => "replication_completed",
+            AppEvent::MigrationProgress(_) => "migration_progress",
+            AppEvent::SubscribeSymbol(_) => "subscribe_symbol",
+            AppEvent::UnsubscribeSymbol(_) => "unsubscribe_symbol",
+            AppEvent::SchedulerStatus(_) => "scheduler_status",
+            AppEvent::RecoveryStats(_) => "recovery_stats",
+            AppEvent::HealthSummary(_) => "health_summary",
+            AppEvent::EmergencyFlatten(_) => "emergency_flatten",
+            AppEvent::PauseTrading(_) => "pause_trading",
+            AppEvent::ResumeTrading => "resume_trading",
+            AppEvent::FlattenCompleted(_) => "flatten_completed",
+            AppEvent::CircuitOpen(_) => "circuit_open",
+            AppEvent::CircuitClosed(_) => "circuit_closed",
+            AppEvent::ConsumerLagged(_) => "consumer_lagged",
+        }
+    }
+
+    /// The topic this event is published under.
+    pub fn topic(&self) -> Topic {
+        match self {
+            AppEvent::SystemVitals(_)
+            | AppEvent::SystemStateChange(_)
+            | AppEvent::ShadowTrialCompleted(_)
+            | AppEvent::MutationRejected(_)
+            | AppEvent::FleetValidation(_)
+            | AppEvent::SchedulerStatus(_)
+            | AppEvent::RecoveryStats(_)
+            | AppEvent::HealthSummary(_)
+            | AppEvent::CircuitOpen(_)
+            | AppEvent::CircuitClosed(_)
+            | AppEvent::ConsumerLagged(_) => Topic::System,
+            AppEvent::MarketData(_)
+            | AppEvent::Candle(_)
+            | AppEvent::MarketConditions(_)
+            | AppEvent::SentimentUpdate(_)
+            | AppEvent::FundingRate(_)
+            | AppEvent::OpenInterest(_)
+            | AppEvent::TickerStats(_)
+            | AppEvent::CrossExchangeSpread(_) => Topic::Market,
+            AppEvent::MarketTick(_) => Topic::MarketTicks,
+            AppEvent::StrategyDecision(..) => Topic::Strategy,
+            AppEvent::FinancialUpdate(_)
+            | AppEvent::OrderUpdate(_)
+            | AppEvent::ExecutionProgress(_)
+            | AppEvent::NetAssetValue(_)
+            | AppEvent::ProtectionTriggered(_)
+            | AppEvent::FlattenCompleted(_)
+            | AppEvent::FleetFunds(_)
+            | AppEvent::StrategyPerformance(_) => Topic::Financial,
+            AppEvent::WebSearchQuery(_)
+            | AppEvent::WebSearchResponse(_)
+            | AppEvent::LlmQuery(..)
+            | AppEvent::LlmResponse(..)
+            | AppEvent::NewsItem(_) => Topic::Reasoning,
+            AppEvent::Deploy(_)
+            | AppEvent::DeploymentStatusChanged(_)
+            | AppEvent::ReplicationCompleted(_)
+            | AppEvent::MigrationProgress(_) => Topic::Deployment,
+            AppEvent::ReloadConfig
+            | AppEvent::ModuleReadyForHotSwap(_)
+            | AppEvent::SetDecisionPolicy(_)
+            | AppEvent::StrategyParamUpdate(_)
+            | AppEvent::CandidateModuleReady(_)
+            | AppEvent::SubscribeSymbol(_)
+            | AppEvent::UnsubscribeSymbol(_)
+            | AppEvent::EmergencyFlatten(_)
+            | AppEvent::PauseTrading(_)
+            | AppEvent::ResumeTrading => Topic::Control,
+        }
+    }
+
+    /// Control-plane events must not be lost when telemetry backs up.
+    pub fn is_control_plane(&self) -> bool {
+        matches!(
+            self,
+            AppEvent::Deploy(_)
+                | AppEvent::ModuleReadyForHotSwap(_)
+                | AppEvent::SystemStateChange(_)
+                | AppEvent::ReloadConfig
+                | AppEvent::SetDecisionPolicy(_)
+                | AppEvent::StrategyParamUpdate(_)
+                | AppEvent::CandidateModuleReady(_)
+                | AppEvent::EmergencyFlatten(_)
+                | AppEvent::PauseTrading(_)
+                | AppEvent::ResumeTrading
+        )
+    }
+}
+
+/// An event as it travels through a subscription's channel.
+#[derive(Clone)]
+struct Envelope {
+    event: AppEvent,
+    published_at: Instant,
+}
+
+/// Receiving end of one subscription.
+///
+/// Used like a `broadcast::Receiver<AppEvent>`; it also records how long each
+/// event waited to be received, see [`EventBus::metrics`].
+pub struct EventReceiver {
+    inner: broadcast::Receiver<Envelope>,
+    metrics: Arc<SubscriberMetrics>,
+}
+
+impl EventReceiver {
+    pub async fn recv(&mut self) -> Result<AppEvent, RecvError> {
+        match self.inner.recv().await {
+            Ok(envelope) => Ok(self.received(envelope)),
+            Err(RecvError::Lagged(n)) => {
+                self.metrics.record_lagged(n);
+                Err(RecvError::Lagged(n))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn try_recv(&mut self) -> Result<AppEvent, TryRecvError> {
+        match self.inner.try_recv() {
+            Ok(envelope) => Ok(self.received(envelope)),
+            Err(TryRecvError::Lagged(n)) => {
+                self.metrics.record_lagged(n);
+                Err(TryRecvError::Lagged(n))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn received(&self, envelope: Envelope) -> AppEvent {
+        self.metrics
+            .record_received(envelope.published_at.elapsed());
+        envelope.event
+    }
+}
+
+struct Subscription {
+    name: String,
+    topics: Vec<Topic>,
+    sender: broadcast::Sender<Envelope>,
+    metrics: Arc<SubscriberMetrics>,
+}
+
+/// Topic-aware event bus.
+///
+/// Every subscription gets its own bounded channel and only receives events whose
+/// topic it asked for, so a slow consumer of one category no longer lags behind
+/// the high-frequency traffic of another.
+///
+/// A bus created with [`EventBus::with_control_channel`] additionally routes
+/// control-plane events through a bounded mpsc channel. Its owner (the kernel)
+/// drains that channel and hands each event to [`EventBus::publish`].
+#[derive(Clone)]
+pub struct EventBus {
+    capacity: usize,
+    subscriptions: Arc<RwLock<Vec<Subscription>>>,
+    control: Option<mpsc::Sender<AppEvent>>,
+    metrics: BusMetrics,
+}
+
+impl EventBus {
+    /// Create a bus whose subscriptions each buffer up to `capacity` events.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            subscriptions: Arc::new(RwLock::new(Vec::new())),
+            control: None,
+            metrics: BusMetrics::default(),
+        }
+    }
+
+    /// Create a bus with a reliable control-plane channel of `control_capacity` events.
+    pub fn with_control_channel(
+        capacity: usize,
+        control_capacity: usize,
+    ) -> (Self, ControlReceiver) {
+        let (control_tx, control_rx) = mpsc::channel(control_capacity);
+        let mut bus = Self::new(capacity);
+        bus.control = Some(control_tx);
+        (bus, control_rx)
+    }
+
+    /// Subscribe to every topic.
+    pub fn subscribe(&self) -> EventReceiver {
+        self.subscribe_to(&Topic::ALL)
+    }
+
+    /// Subscribe to the given topics only. The subscription is reported in the
+    /// bus metrics under the names of its topics.
+    pub fn subscribe_to(&self, topics: &[Topic]) -> EventReceiver {
+        let name = topics
+            .iter()
+            .map(Topic::label)
+            .collect::<Vec<_>>()
+            .join("+");
+        self.subscribe_as(&name, topics)
+    }
+
+    /// Subscribe to the given topics, reporting the subscription in the bus
+    /// metrics as `name`, typically the consuming engine.
+    pub fn subscribe_as(&self, name: &str, topics: &[Topic]) -> EventReceiver {
+        let (sender, inner) = broadcast::channel(self.capacity);
+        let metrics = Arc::new(SubscriberMetrics::default());
+        let mut subscriptions = self.subscriptions.write().expect("event bus lock poisoned");
+        // Drop subscriptions whose receiver has gone away.
+        subscriptions.retain(|s| s.sender.receiver_count() > 0);
+        subscriptions.push(Subscription {
+            name: name.to_string(),
+            topics: topics.to_vec(),
+            sender,
+            metrics: metrics.clone(),
+        });
+        EventReceiver { inner, metrics }
+    }
+
+    /// Send an event.
+    ///
+    /// Mirrors `broadcast::Sender::send`: returns the number of subscribers the
+    /// event was delivered to, or the event back if nobody is listening. Control-plane
+    /// events are queued on the control channel when there is one; if it is full the
+    /// event is handed back rather than dropped, see [`EventBus::send_control`].
+    pub fn send(&self, event: AppEvent) -> Result<usize, SendError<AppEvent>> {
+        match &self.control {
+            Some(control) if event.is_control_plane() => match control.try_send(event) {
+                Ok(()) => Ok(1),
+                Err(TrySendError::Full(event)) | Err(TrySendError::Closed(event)) => {
+                    Err(SendError(event))
+                }
+            },
+            _ => self.publish(event),
+        }
+    }
+
+    /// Send a control-plane event, waiting for room on the control channel.
+    pub async fn send_control(&self, event: AppEvent) -> Result<usize, SendError<AppEvent>> {
+        match &self.control {
+            Some(control) => control
+                .send(event)
+                .await
+                .map(|_| 1)
+                .map_err(|e| SendError(e.0)),
+            None => self.publish(event),
+        }
+    }
+
+    /// Deliver an event straight to the subscribers of its topic, bypassing the
+    /// control channel.
+    pub fn publish(&self, event: AppEvent) -> Result<usize, SendError<AppEvent>> {
+        let topic = event.topic();
+        let kind = event.kind();
+        let subscriptions = self.subscriptions.read().expect("event bus lock poisoned");
+
+        let envelope = Envelope {
+            event,
+            published_at: Instant::now(),
+        };
+        let mut delivered = 0;
+        for subscription in subscriptions.iter() {
+            if subscription.topics.contains(&topic)
+                && subscription.sender.send(envelope.clone()).is_ok()
+            {
+                delivered += 1;
+            }
+        }
+        self.metrics.record_publish(kind, delivered);
+
+        let event = envelope.event;
+
+        if delivered == 0 {
+            Err(SendError(event))
+        } else {
+            Ok(delivered)
+        }
+    }
+
+    /// Most events waiting in any one subscription, i.e. how far the slowest
+    /// consumer is behind. A subscription that reaches `capacity` starts losing events.
+    pub fn backlog(&self) -> usize {
+        self.subscriptions
+            .read()
+            .expect("event bus lock poisoned")
+            .iter()
+            .filter(|s| s.sender.receiver_count() > 0)
+            .map(|s| s.sender.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Event counts by type and the backlog, losses and latency of every live
+    /// subscription.
+    pub fn metrics(&self) -> BusMetricsSnapshot {
+        let subscribers = self
+            .subscriptions
+            .read()
+            .expect("event bus lock poisoned")
+            .iter()
+            .filter(|s| s.sender.receiver_count() > 0)
+            .map(|s| subscriber_snapshot(&s.name, s.sender.len(), self.capacity, &s.metrics))
+            .collect();
+        BusMetricsSnapshot {
+            events: self.metrics.events(),
+            subscribers,
+        }
+    }
+
+    /// Number of live subscriptions.
+    pub fn receiver_count(&self) -> usize {
+        self.subscriptions
+            .read()
+            .expect("event bus lock poisoned")
+            .iter()
+            .filter(|s| s.sender.receiver_count() > 0)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MarketData, SystemState};
+
+    fn market_tick() -> AppEvent {
+        AppEvent::MarketData(MarketData {
+            symbol: "BTCUSDT".to_string(),
+            price: 70_000.0,
+            quantity: 0.1,
+            timestamp: 0,
+            meta: Default::default(),
+        })
+    }
+
+    #[test]
+    fn test_subscribers_only_receive_their_topics() {
+        let bus = EventBus::new(16);
+        let mut market_rx = bus.subscribe_to(&[Topic::Market]);
+        let mut system_rx = bus.subscribe_to(&[Topic::System]);
+
+        assert_eq!(bus.send(market_tick()).unwrap(), 1);
+        bus.send(AppEvent::SystemStateChange(SystemState::Conservation))
+            .unwrap();
+
+        assert!(matches!(market_rx.try_recv(), Ok(AppEvent::MarketData(_))));
+        assert!(market_rx.try_recv().is_err());
+        assert!(matches!(
+            system_rx.try_recv(),
+            Ok(AppEvent::SystemStateChange(SystemState::Conservation))
+        ));
+        assert!(system_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_control_events_go_through_control_channel() {
+        let (bus, mut control_rx) = EventBus::with_control_channel(1, 4);
+        let mut system_rx = bus.subscribe_to(&[Topic::System]);
+
+        bus.send(AppEvent::SystemStateChange(SystemState::Conservation))
+            .unwrap();
+        assert!(system_rx.try_recv().is_err());
+
+        let event = control_rx.recv().await.unwrap();
+        bus.publish(event).unwrap();
+        assert!(matches!(
+            system_rx.try_recv(),
+            Ok(AppEvent::SystemStateChange(SystemState::Conservation))
+        ));
+    }
+
+    #[test]
+    fn test_backlog_tracks_slowest_subscriber() {
+        let bus = EventBus::new(16);
+        let mut fast_rx = bus.subscribe_to(&[Topic::Market]);
+        let _slow_rx = bus.subscribe_to(&[Topic::Market]);
+
+        for _ in 0..3 {
+            bus.send(market_tick()).unwrap();
+        }
+        fast_rx.try_recv().unwrap();
+        assert_eq!(bus.backlog(), 3);
+    }
+
+    #[test]
+    fn test_metrics_count_events_and_subscriber_lag() {
+        let bus = EventBus::new(2);
+        let mut strategy_rx = bus.subscribe_as("strategy_engine", &[Topic::Market]);
+        let _system_rx = bus.subscribe_to(&[Topic::System, Topic::Control]);
+
+        for _ in 0..3 {
+            bus.send(market_tick()).unwrap();
+        }
+        assert!(matches!(
+            strategy_rx.try_recv(),
+            Err(TryRecvError::Lagged(1))
+        ));
+        strategy_rx.try_recv().unwrap();
+
+        let metrics = bus.metrics();
+        let market = &metrics.events["market_data"];
+        assert_eq!((market.published, market.delivered), (3, 3));
+
+        let strategy = &metrics.subscribers[0];
+        assert_eq!(strategy.name, "strategy_engine");
+        assert_eq!((strategy.received, strategy.lagged), (1, 1));
+        assert_eq!(strategy.backlog, 1);
+        assert_eq!(strategy.fill_ratio(), 0.5);
+        assert_eq!(strategy.latency.count, 1);
+        assert_eq!(metrics.subscribers[1].name, "system+control");
+    }
+
+    #[test]
+    fn test_send_without_subscribers_returns_event() {
+        let bus = EventBus::new(16);
+        let rx = bus.subscribe_to(&[Topic::Market]);
+        drop(rx);
+
+        assert!(bus.send(market_tick()).is_err());
+        assert_eq!(bus.receiver_count(), 0);
+    }
+}
@@ -0,0 +1,169 @@
+//! Throughput and latency counters for the event bus.
+//!
+//! The bus counts every event it publishes by type, and every subscription
+//! records how long its events waited between publish and receive. Together
+//! with each subscription's backlog this shows which engine is falling behind
+//! before its channel fills up and it starts losing events.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Upper bounds of the latency histogram buckets, in seconds
+pub const LATENCY_BUCKETS: [f64; 14] = [
+    0.000_05, 0.000_1, 0.000_25, 0.000_5, 0.001, 0.002_5, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5,
+    1.0,
+];
+
+/// Publish → receive latency of one subscription
+#[derive(Debug, Default)]
+struct LatencyHistogram {
+    /// Events at or below each bound; events above the last bound only count in `count`
+    buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+    max_micros: AtomicU64,
+}
+
+impl LatencyHistogram {
+    fn observe(&self, latency: Duration) {
+        let seconds = latency.as_secs_f64();
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|&bound| seconds <= bound) {
+            self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+        let micros = latency.as_micros() as u64;
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+        self.max_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> LatencySnapshot {
+        let mut cumulative = 0;
+        let buckets = LATENCY_BUCKETS
+            .iter()
+            .zip(&self.buckets)
+            .map(|(&le, count)| {
+                cumulative += count.load(Ordering::Relaxed);
+                (le, cumulative)
+            })
+            .collect();
+        LatencySnapshot {
+            count: self.count.load(Ordering::Relaxed),
+            sum_seconds: self.sum_micros.load(Ordering::Relaxed) as f64 / 1e6,
+            max_seconds: self.max_micros.load(Ordering::Relaxed) as f64 / 1e6,
+            buckets,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencySnapshot {
+    pub count: u64,
+    pub sum_seconds: f64,
+    pub max_seconds: f64,
+    /// Cumulative `(upper bound in seconds, events)` pairs, as in a Prometheus histogram
+    pub buckets: Vec<(f64, u64)>,
+}
+
+/// Counters kept by one subscription's receiver
+#[derive(Debug, Default)]
+pub(crate) struct SubscriberMetrics {
+    received: AtomicU64,
+    lagged: AtomicU64,
+    latency: LatencyHistogram,
+}
+
+impl SubscriberMetrics {
+    pub(crate) fn record_received(&self, latency: Duration) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+        self.latency.observe(latency);
+    }
+
+    pub(crate) fn record_lagged(&self, skipped: u64) {
+        self.lagged.fetch_add(skipped, Ordering::Relaxed);
+    }
+}
+
+/// State of one subscription at the time of the snapshot
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscriberSnapshot {
+    pub name: String,
+    /// Events waiting to be received
+    pub backlog: usize,
+    /// Backlog at which the subscription starts losing events
+    pub capacity: usize,
+    pub received: u64,
+    /// Events lost because the backlog overflowed
+    pub lagged: u64,
+    pub latency: LatencySnapshot,
+}
+
+impl SubscriberSnapshot {
+    /// How full the subscription's channel is, from 0 to 1
+    pub fn fill_ratio(&self) -> f64 {
+        if self.capacity == 0 {
+            0.0
+        } else {
+            self.backlog as f64 / self.capacity as f64
+        }
+    }
+}
+
+/// Counters of one event type
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EventTypeSnapshot {
+    pub published: u64,
+    /// Copies handed to subscriptions
+    pub delivered: u64,
+    /// Events no subscription was interested in
+    pub unrouted: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BusMetricsSnapshot {
+    pub events: BTreeMap<&'static str, EventTypeSnapshot>,
+    pub subscribers: Vec<SubscriberSnapshot>,
+}
+
+/// Per event type counters shared by every clone of a bus
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BusMetrics {
+    events: Arc<Mutex<BTreeMap<&'static str, EventTypeSnapshot>>>,
+}
+
+impl BusMetrics {
+    pub(crate) fn record_publish(&self, kind: &'static str, delivered: usize) {
+        let mut events = self.events.lock().expect("bus metrics lock poisoned");
+        let counters = events.entry(kind).or_default();
+        counters.published += 1;
+        counters.delivered += delivered as u64;
+        if delivered == 0 {
+            counters.unrouted += 1;
+        }
+    }
+
+    pub(crate) fn events(&self) -> BTreeMap<&'static str, EventTypeSnapshot> {
+        self.events
+            .lock()
+            .expect("bus metrics lock poisoned")
+            .clone()
+    }
+}
+
+pub(crate) fn subscriber_snapshot(
+    name: &str,
+    backlog: usize,
+    capacity: usize,
+    metrics: &SubscriberMetrics,
+) -> SubscriberSnapshot {
+    SubscriberSnapshot {
+        name: name.to_string(),
+        backlog,
+        capacity,
+        received: metrics.received.load(Ordering::Relaxed),
+        lagged: metrics.lagged.load(Ordering::Relaxed),
+        latency: metrics.latency.snapshot(),
+    }
+}
@@ -0,0 +1,260 @@
+//! Circuit breakers around external dependencies.
+//!
+//! The exchange, the LLM, web search and SSH hosts all fail from time to time,
+//! and hammering one that is down only adds to the outage. A [`CircuitBreaker`]
+//! counts consecutive failures of one component; after `failure_threshold` of
+//! them it opens and rejects calls without making them, so the caller can serve
+//! cached or degraded results instead. Once `open_duration` has passed a single
+//! probe call is let through: success closes the circuit, failure opens it for
+//! another `open_duration`.
+
+use crate::{AppEvent, EventBus};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+pub const DEFAULT_OPEN_DURATION: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Calls go through
+    Closed,
+    /// Calls are rejected until the open duration has passed
+    Open,
+    /// One probe call is in flight
+    HalfOpen,
+}
+
+#[derive(Debug)]
+pub enum CircuitError<E> {
+    /// The circuit is open and the call was not made
+    Open(String),
+    /// The call was made and failed
+    Failed(E),
+}
+
+impl<E: fmt::Display> fmt::Display for CircuitError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitError::Open(component) => write!(f, "circuit open for {}", component),
+            CircuitError::Failed(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for CircuitError<E> {}
+
+#[derive(Debug)]
+struct Inner {
+    state: CircuitState,
+    consecutive_failures: u32,
+    /// When the circuit opened or the current probe was let through
+    since: Instant,
+}
+
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    component: String,
+    failure_threshold: u32,
+    open_duration: Duration,
+    inner: Arc<Mutex<Inner>>,
+    tx: Option<EventBus>,
+}
+
+impl CircuitBreaker {
+    pub fn new(component: impl Into<String>) -> Self {
+        Self {
+            component: component.into(),
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            open_duration: DEFAULT_OPEN_DURATION,
+            inner: Arc::new(Mutex::new(Inner {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                since: Instant::now(),
+            })),
+            tx: None,
+        }
+    }
+
+    /// Consecutive failures that open the circuit
+    pub fn with_failure_threshold(mut self, failure_threshold: u32) -> Self {
+        self.failure_threshold = failure_threshold.max(1);
+        self
+    }
+
+    /// How long the circuit stays open before a probe is let through
+    pub fn with_open_duration(mut self, open_duration: Duration) -> Self {
+        self.open_duration = open_duration;
+        self
+    }
+
+    /// Publish `CircuitOpen` and `CircuitClosed` on the bus
+    pub fn with_event_bus(mut self, tx: EventBus) -> Self {
+        self.tx = Some(tx);
+        self
+    }
+
+    pub fn component(&self) -> &str {
+        &self.component
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.lock().state
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state() == CircuitState::Open
+    }
+
+    /// Whether a call may be made now. While half-open only the probe is let
+    /// through; a probe whose outcome was never recorded is replaced after
+    /// another `open_duration`.
+    pub fn try_acquire(&self) -> bool {
+        let mut inner = self.lock();
+        match inner.state {
+            CircuitState::Closed => true,
+            CircuitState::Open | CircuitState::HalfOpen
+                if inner.since.elapsed() >= self.open_duration =>
+            {
+                inner.state = CircuitState::HalfOpen;
+                inner.since = Instant::now();
+                true
+            }
+            CircuitState::Open | CircuitState::HalfOpen => false,
+        }
+    }
+
+    pub fn record_success(&self) {
+        let recovered = {
+            let mut inner = self.lock();
+            inner.consecutive_failures = 0;
+            std::mem::replace(&mut inner.state, CircuitState::Closed) != CircuitState::Closed
+        };
+        if recovered {
+            info!(
+                "[Circuit Breaker] {} recovered, circuit closed",
+                self.component
+            );
+            self.publish(AppEvent::CircuitClosed(self.component.clone()));
+        }
+    }
+
+    pub fn record_failure(&self) {
+        let opened = {
+            let mut inner = self.lock();
+            inner.consecutive_failures += 1;
+            let trips = match inner.state {
+                CircuitState::Closed => inner.consecutive_failures >= self.failure_threshold,
+                CircuitState::HalfOpen => true,
+                CircuitState::Open => false,
+            };
+            if trips {
+                inner.state = CircuitState::Open;
+                inner.since = Instant::now();
+            }
+            trips
+        };
+        if opened {
+            warn!(
+                "[Circuit Breaker] {} failing, circuit open for {:?}",
+                self.component, self.open_duration
+            );
+            self.publish(AppEvent::CircuitOpen(self.component.clone()));
+        }
+    }
+
+    /// Run `call` if the circuit allows it and record the outcome.
+    pub async fn call<T, E, F>(&self, call: F) -> Result<T, CircuitError<E>>
+    where
+        F: Future<Output = Result<T, E>>,
+    {
+        if !self.try_acquire() {
+            return Err(CircuitError::Open(self.component.clone()));
+        }
+        match call.await {
+            Ok(value) => {
+                self.record_success();
+                Ok(value)
+            }
+            Err(e) => {
+                self.record_failure();
+                Err(CircuitError::Failed(e))
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().expect("circuit breaker lock poisoned")
+    }
+
+    fn publish(&self, event: AppEvent) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Topic;
+
+    #[tokio::test]
+    async fn test_opens_after_consecutive_failures_and_probes_for_recovery() {
+        let bus = EventBus::new(16);
+        let mut rx = bus.subscribe_to(&[Topic::System]);
+        let breaker = CircuitBreaker::new("exchange_rest")
+            .with_failure_threshold(3)
+            .with_open_duration(Duration::from_millis(20))
+            .with_event_bus(bus);
+
+        // A success in between resets the count
+        breaker.record_failure();
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        let failed: Result<(), _> = breaker.call(async { Err("timeout") }).await;
+        assert!(matches!(failed, Err(CircuitError::Failed("timeout"))));
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(matches!(
+            rx.recv().await.unwrap(),
+            AppEvent::CircuitOpen(component) if component == "exchange_rest"
+        ));
+
+        // Calls are not made while open
+        let mut called = false;
+        let rejected = breaker
+            .call(async {
+                called = true;
+                Ok::<_, &str>(())
+            })
+            .await;
+        assert!(matches!(rejected, Err(CircuitError::Open(_))));
+        assert!(!called);
+
+        // One probe after the open duration; a failed probe reopens at once
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(breaker.try_acquire());
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(!breaker.try_acquire());
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(breaker.call(async { Ok::<_, &str>(7) }).await.unwrap(), 7);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(matches!(rx.recv().await.unwrap(), AppEvent::CircuitOpen(_)));
+        assert!(matches!(
+            rx.recv().await.unwrap(),
+            AppEvent::CircuitClosed(component) if component == "exchange_rest"
+        ));
+    }
+}
@@ -0,0 +1,182 @@
+//! Sources of time.
+//!
+//! Engines that schedule or measure over time take a [`SharedClock`] instead of
+//! calling `Utc::now()` and `tokio::time::sleep` directly. The kernel gives them
+//! the [`SystemClock`]; tests use a [`MockClock`] they move forward by hand, and
+//! simulations an [`AcceleratedClock`] that runs faster than wall-clock time.
+//!
+//! Signed exchange requests are rejected when their timestamp is too far from
+//! the exchange's clock. The clock sync service measures how far this machine
+//! is off and stores the offset here; anything that stamps requests for the
+//! exchange reads [`exchange_now_millis`] instead of the local clock.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+
+static OFFSET_MILLIS: AtomicI64 = AtomicI64::new(0);
+
+/// Milliseconds to add to the local clock to get the exchange's time.
+pub fn offset_millis() -> i64 {
+    OFFSET_MILLIS.load(Ordering::Relaxed)
+}
+
+pub fn set_offset_millis(offset: i64) {
+    OFFSET_MILLIS.store(offset, Ordering::Relaxed);
+}
+
+/// Local Unix time in milliseconds.
+pub fn local_now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
+/// Unix time in milliseconds, corrected by the measured offset.
+pub fn exchange_now_millis() -> u64 {
+    (local_now_millis() + offset_millis()).max(0) as u64
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+pub trait Clock: Send + Sync + fmt::Debug {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Wait until `duration` has passed on this clock.
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send + '_>>;
+}
+
+/// The clock engines use unless told otherwise.
+pub fn system() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+/// Wall-clock time and real sleeps.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// A clock that only moves when told to. Sleepers wake as soon as
+/// [`MockClock::advance`] or [`MockClock::set`] carries the time past their
+/// deadline, so hours of scheduling run in a test without waiting.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<watch::Sender<DateTime<Utc>>>,
+}
+
+impl MockClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            now: Arc::new(watch::Sender::new(start)),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        let delta = TimeDelta::from_std(duration).unwrap_or(TimeDelta::MAX);
+        self.now.send_modify(|now| *now += delta);
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        self.now.send_replace(now);
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new(Utc::now())
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.borrow()
+    }
+
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        let mut changes = self.now.subscribe();
+        let deadline = self.now() + TimeDelta::from_std(duration).unwrap_or(TimeDelta::MAX);
+        Box::pin(async move {
+            while *changes.borrow_and_update() < deadline {
+                if changes.changed().await.is_err() {
+                    return;
+                }
+            }
+        })
+    }
+}
+
+/// Wall-clock time sped up by a constant factor from the moment of creation,
+/// for simulations that should play out hours in minutes.
+#[derive(Debug, Clone)]
+pub struct AcceleratedClock {
+    start: DateTime<Utc>,
+    started: Instant,
+    factor: f64,
+}
+
+impl AcceleratedClock {
+    pub fn new(start: DateTime<Utc>, factor: f64) -> Self {
+        Self {
+            start,
+            started: Instant::now(),
+            factor: factor.max(f64::MIN_POSITIVE),
+        }
+    }
+}
+
+impl Clock for AcceleratedClock {
+    fn now(&self) -> DateTime<Utc> {
+        let elapsed = self.started.elapsed().mul_f64(self.factor);
+        self.start + TimeDelta::from_std(elapsed).unwrap_or(TimeDelta::MAX)
+    }
+
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        Box::pin(tokio::time::sleep(duration.div_f64(self.factor)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_clock_wakes_sleepers_when_advanced() {
+        let start = Utc::now();
+        let clock = MockClock::new(start);
+        let sleeper = {
+            let clock = clock.clone();
+            tokio::spawn(async move {
+                clock.sleep(Duration::from_secs(3600)).await;
+                clock.now()
+            })
+        };
+        tokio::task::yield_now().await;
+
+        clock.advance(Duration::from_secs(1800));
+        tokio::task::yield_now().await;
+        assert!(!sleeper.is_finished());
+
+        clock.advance(Duration::from_secs(1800));
+        let woke_at = tokio::time::timeout(Duration::from_secs(1), sleeper)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(woke_at, start + TimeDelta::hours(1));
+        assert_eq!(clock.now() - start, TimeDelta::hours(1));
+    }
+}
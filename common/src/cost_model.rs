@@ -0,0 +1,158 @@
+//! Trading costs of simulated orders.
+//!
+//! The paper broker and the backtester price their fills with the same model so
+//! that simulated results carry the costs live trading would: exchange fees by
+//! 30-day volume tier and maker or taker liquidity, slippage of a fixed amount
+//! plus a share of the price, and the latency between deciding and filling.
+//! Reports of simulated trades include the model they were priced with.
+
+use crate::AureliaResult;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+pub const COST_MODEL_PATH: &str = "config/cost_model.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Liquidity {
+    /// The order rested on the book
+    Maker,
+    /// The order crossed the spread
+    Taker,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeTier {
+    /// 30-day traded value in quote asset from which the tier applies
+    pub min_volume_30d: f64,
+    pub maker_rate: f64,
+    pub taker_rate: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CostModel {
+    /// Fee tiers in any order; the highest one reached applies
+    pub fee_tiers: Vec<FeeTier>,
+    /// Slippage in quote asset per unit, against the order
+    pub slippage_fixed: f64,
+    /// Slippage in basis points of the price, against the order
+    pub slippage_bps: f64,
+    /// Time between a decision and its fill
+    pub latency_ms: u64,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self {
+            fee_tiers: vec![FeeTier {
+                min_volume_30d: 0.0,
+                maker_rate: 0.001,
+                taker_rate: 0.001,
+            }],
+            slippage_fixed: 0.0,
+            slippage_bps: 0.0,
+            latency_ms: 0,
+        }
+    }
+}
+
+/// Price and fee of a simulated fill.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimulatedFill {
+    pub price: f64,
+    /// In quote asset
+    pub fee: f64,
+}
+
+impl CostModel {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn latency(&self) -> Duration {
+        Duration::from_millis(self.latency_ms)
+    }
+
+    /// Fee rate for an account that traded `volume_30d` over the last 30 days.
+    pub fn fee_rate(&self, volume_30d: f64, liquidity: Liquidity) -> f64 {
+        self.fee_tiers
+            .iter()
+            .filter(|tier| tier.min_volume_30d <= volume_30d)
+            .max_by(|a, b| a.min_volume_30d.total_cmp(&b.min_volume_30d))
+            .map(|tier| match liquidity {
+                Liquidity::Maker => tier.maker_rate,
+                Liquidity::Taker => tier.taker_rate,
+            })
+            .unwrap_or(0.0)
+    }
+
+    /// The price a `side` order at `price` fills at after slippage.
+    pub fn fill_price(&self, side: &str, price: f64) -> f64 {
+        let slippage = self.slippage_fixed + price * self.slippage_bps / 10_000.0;
+        if side.eq_ignore_ascii_case("SELL") {
+            (price - slippage).max(0.0)
+        } else {
+            price + slippage
+        }
+    }
+
+    pub fn simulate(
+        &self,
+        side: &str,
+        price: f64,
+        quantity: f64,
+        volume_30d: f64,
+        liquidity: Liquidity,
+    ) -> SimulatedFill {
+        let price = self.fill_price(side, price);
+        SimulatedFill {
+            price,
+            fee: price * quantity * self.fee_rate(volume_30d, liquidity),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fills_pay_slippage_and_the_fee_of_their_tier() {
+        let model = CostModel {
+            fee_tiers: vec![
+                FeeTier {
+                    min_volume_30d: 1_000_000.0,
+                    maker_rate: 0.0009,
+                    taker_rate: 0.001,
+                },
+                FeeTier {
+                    min_volume_30d: 0.0,
+                    maker_rate: 0.001,
+                    taker_rate: 0.002,
+                },
+            ],
+            slippage_fixed: 0.5,
+            slippage_bps: 10.0,
+            latency_ms: 250,
+        };
+        assert_eq!(model.fee_rate(0.0, Liquidity::Taker), 0.002);
+        assert_eq!(model.fee_rate(2_000_000.0, Liquidity::Maker), 0.0009);
+        assert_eq!(model.latency(), Duration::from_millis(250));
+
+        let buy = model.simulate("BUY", 1000.0, 2.0, 0.0, Liquidity::Taker);
+        assert!((buy.price - 1001.5).abs() < 1e-9);
+        assert!((buy.fee - 1001.5 * 2.0 * 0.002).abs() < 1e-9);
+        assert!((model.fill_price("SELL", 1000.0) - 998.5).abs() < 1e-9);
+
+        let model: CostModel = serde_json::from_str(r#"{"slippage_bps": 2.0}"#).unwrap();
+        assert_eq!(model.fee_rate(0.0, Liquidity::Taker), 0.001);
+        assert_eq!(model.latency_ms, 0);
+    }
+}
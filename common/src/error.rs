@@ -0,0 +1,38 @@
+use thiserror::Error;
+
+/// Shared error type for the engines and the deployment tooling.
+///
+/// Each variant corresponds to a failure domain so that callers such as the
+/// recovery manager can decide how to react without parsing error strings.
+#[derive(Debug, Error)]
+pub enum AureliaError {
+    #[error("deployment error: {0}")]
+    Deployment(String),
+    #[error("SSH error: {0}")]
+    Ssh(String),
+    #[error("exchange error: {0}")]
+    Exchange(String),
+    #[error("configuration error: {0}")]
+    Config(String),
+    #[error("IPC error: {0}")]
+    Ipc(String),
+    #[error("storage error: {0}")]
+    Storage(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+impl AureliaError {
+    /// Whether retrying the same operation later has a reasonable chance of succeeding.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            AureliaError::Ssh(_) | AureliaError::Exchange(_) | AureliaError::Io(_)
+        )
+    }
+}
+
+/// A result alias using [`AureliaError`].
+pub type AureliaResult<T> = Result<T, AureliaError>;
@@ -0,0 +1,87 @@
+//! Versioning of what the kernel and a native strategy module exchange.
+//!
+//! A hot-swapped module may have been built from mutated source long after the
+//! kernel, so the two can disagree on the FFI symbols or on the JSON shape of
+//! [`AppEvent`]. Modules export `strategy_abi_version()`, which the kernel
+//! checks before swapping a module in, and every event crossing the boundary is
+//! wrapped in an envelope carrying [`EVENT_SCHEMA_VERSION`].
+//!
+//! Bump [`STRATEGY_ABI_VERSION`] when a module symbol changes its signature or
+//! meaning, and [`EVENT_SCHEMA_VERSION`] when a change to `AppEvent` would make
+//! an older module misread events, e.g. a renamed variant or field.
+
+use crate::{AppEvent, AureliaError, AureliaResult};
+use serde::{Deserialize, Serialize};
+
+/// Version of the strategy module symbols, returned by `strategy_abi_version()`
+pub const STRATEGY_ABI_VERSION: u32 = 1;
+
+/// Version of the `AppEvent` JSON shape
+pub const EVENT_SCHEMA_VERSION: u32 = 2;
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    schema_version: u32,
+    event: &'a AppEvent,
+}
+
+#[derive(Deserialize)]
+struct ReceivedEnvelope {
+    schema_version: u32,
+    event: serde_json::Value,
+}
+
+/// One event as a line for the other side of the FFI boundary
+pub fn encode(event: &AppEvent) -> AureliaResult<String> {
+    Ok(serde_json::to_string(&Envelope {
+        schema_version: EVENT_SCHEMA_VERSION,
+        event,
+    })?)
+}
+
+/// An event from the other side. Events of another schema version are still
+/// accepted if they parse as the current `AppEvent`, and bare events from
+/// modules that predate the envelope are accepted as they are.
+pub fn decode(line: &str) -> AureliaResult<AppEvent> {
+    let Ok(envelope) = serde_json::from_str::<ReceivedEnvelope>(line) else {
+        return serde_json::from_str(line)
+            .map_err(|e| AureliaError::Ipc(format!("not an event: {}", e)));
+    };
+    serde_json::from_value(envelope.event).map_err(|e| {
+        if envelope.schema_version == EVENT_SCHEMA_VERSION {
+            AureliaError::Ipc(format!("invalid event: {}", e))
+        } else {
+            AureliaError::Ipc(format!(
+                "event schema version {} is incompatible with {}: {}",
+                envelope.schema_version, EVENT_SCHEMA_VERSION, e
+            ))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelopes_and_legacy_events_decode() {
+        let event = AppEvent::FinancialUpdate(42.0);
+        let line = encode(&event).unwrap();
+        assert!(line.contains(&format!("\"schema_version\":{}", EVENT_SCHEMA_VERSION)));
+        assert!(matches!(decode(&line), Ok(AppEvent::FinancialUpdate(f)) if f == 42.0));
+
+        // A module from before the envelope writes bare events
+        let bare = serde_json::to_string(&event).unwrap();
+        assert!(matches!(decode(&bare), Ok(AppEvent::FinancialUpdate(_))));
+
+        // Another version is fine as long as the event still parses
+        let newer = line.replace(
+            &format!("\"schema_version\":{}", EVENT_SCHEMA_VERSION),
+            "\"schema_version\":99",
+        );
+        assert!(decode(&newer).is_ok());
+        let reshaped = r#"{"schema_version":99,"event":{"FinancialUpdated":{"funds":42.0}}}"#;
+        let err = decode(reshaped).unwrap_err().to_string();
+        assert!(err.contains("schema version 99"), "{}", err);
+    }
+}
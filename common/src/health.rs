@@ -0,0 +1,137 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// Readiness component names reported by the kernel.
+pub mod component {
+    pub const EVENT_BUS: &str = "event_bus";
+    pub const PERCEPTION: &str = "perception";
+    pub const STRATEGY_MODULE: &str = "strategy_module";
+    pub const SERVER_CONFIG: &str = "server_config";
+    pub const CLOCK: &str = "clock";
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ComponentHealth {
+    pub name: String,
+    pub ready: bool,
+    pub detail: Option<String>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    components: BTreeMap<String, ComponentHealth>,
+    last_heartbeat: Instant,
+}
+
+/// Shared liveness and readiness state.
+///
+/// The agent is *live* while its main loop keeps calling [`HealthState::heartbeat`],
+/// and *ready* once every registered component reports ready.
+#[derive(Debug, Clone)]
+pub struct HealthState {
+    inner: Arc<RwLock<Inner>>,
+}
+
+impl Default for HealthState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HealthState {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(Inner {
+                components: BTreeMap::new(),
+                last_heartbeat: Instant::now(),
+            })),
+        }
+    }
+
+    /// Register a component that starts out not ready.
+    pub fn register(&self, name: &str) {
+        self.set(name, false, Some("starting".to_string()));
+    }
+
+    /// Record the current state of a component.
+    pub fn set(&self, name: &str, ready: bool, detail: Option<String>) {
+        let mut inner = self.inner.write().expect("health state lock poisoned");
+        inner.components.insert(
+            name.to_string(),
+            ComponentHealth {
+                name: name.to_string(),
+                ready,
+                detail,
+            },
+        );
+    }
+
+    pub fn heartbeat(&self) {
+        self.inner
+            .write()
+            .expect("health state lock poisoned")
+            .last_heartbeat = Instant::now();
+    }
+
+    /// Whether the last heartbeat is at most `max_age` old.
+    pub fn is_live(&self, max_age: Duration) -> bool {
+        self.inner
+            .read()
+            .expect("health state lock poisoned")
+            .last_heartbeat
+            .elapsed()
+            <= max_age
+    }
+
+    /// Whether every registered component is ready.
+    pub fn is_ready(&self) -> bool {
+        self.inner
+            .read()
+            .expect("health state lock poisoned")
+            .components
+            .values()
+            .all(|c| c.ready)
+    }
+
+    /// Whether the named components are all registered and ready.
+    pub fn components_ready(&self, names: &[&str]) -> bool {
+        let inner = self.inner.read().expect("health state lock poisoned");
+        names
+            .iter()
+            .all(|name| inner.components.get(*name).is_some_and(|c| c.ready))
+    }
+
+    pub fn components(&self) -> Vec<ComponentHealth> {
+        self.inner
+            .read()
+            .expect("health state lock poisoned")
+            .components
+            .values()
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ready_only_when_all_components_ready() {
+        let health = HealthState::new();
+        assert!(health.is_ready());
+
+        health.register(component::PERCEPTION);
+        health.register(component::EVENT_BUS);
+        assert!(!health.is_ready());
+
+        health.set(component::PERCEPTION, true, None);
+        assert!(!health.is_ready());
+
+        health.set(component::EVENT_BUS, true, None);
+        assert!(health.is_ready());
+        assert!(health.is_live(Duration::from_secs(30)));
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{AureliaError, AureliaResult};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Where the agent keeps its identity, relative to the deployment directory.
+pub const IDENTITY_PATH: &str = "data/identity.json";
+
+/// Where a deployed agent finds the primary; the primary itself has no such URL.
+pub const PRIMARY_URL_ENV: &str = "AURELIA_PRIMARY_URL";
+
+/// The primary URL this agent was deployed with, if any.
+pub fn primary_url() -> Option<String> {
+    std::env::var(PRIMARY_URL_ENV).ok()
+}
+
+/// Who an agent is and where it came from.
+///
+/// The primary generates its identity on first boot. A replica's identity is
+/// generated by the parent that deploys it and shipped as [`IDENTITY_PATH`], so the
+/// parent knows the replica's ID before it ever starts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentIdentity {
+    pub agent_id: String,
+    /// `None` for the primary.
+    pub parent_id: Option<String>,
+    /// Distance from the primary in the replica tree; the primary is generation 0.
+    pub generation: u32,
+    pub deployed_at: DateTime<Utc>,
+}
+
+impl AgentIdentity {
+    /// A new identity without a parent.
+    pub fn new_root() -> Self {
+        Self {
+            agent_id: uuid::Uuid::new_v4().to_string(),
+            parent_id: None,
+            generation: 0,
+            deployed_at: Utc::now(),
+        }
+    }
+
+    /// The identity of a replica deployed by this agent.
+    pub fn spawn_child(&self) -> Self {
+        Self {
+            agent_id: uuid::Uuid::new_v4().to_string(),
+            parent_id: Some(self.agent_id.clone()),
+            generation: self.generation + 1,
+            deployed_at: Utc::now(),
+        }
+    }
+
+    pub fn is_primary(&self) -> bool {
+        self.parent_id.is_none()
+    }
+
+    /// Load the identity from `path`, creating and persisting a root identity if
+    /// there is none yet. An agent deployed with a primary URL never becomes a
+    /// root: its identity comes from its parent, so a missing one is an error.
+    pub fn load_or_create(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        Self::load_or_create_for(path.as_ref(), primary_url().as_deref())
+    }
+
+    fn load_or_create_for(path: &Path, primary_url: Option<&str>) -> AureliaResult<Self> {
+        if path.exists() {
+            let content = std::fs::read_to_string(path)?;
+            return Ok(serde_json::from_str(&content)?);
+        }
+        if let Some(primary_url) = primary_url {
+            return Err(AureliaError::Config(format!(
+                "no identity at {:?} on an agent deployed by {}; redeploy it from its parent",
+                path, primary_url
+            )));
+        }
+
+        let identity = Self::new_root();
+        identity.save(path)?;
+        Ok(identity)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> AureliaResult<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> AureliaResult<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_persists_and_spawns_children() {
+        let path = std::env::temp_dir()
+            .join(format!("aurelia-identity-{}", uuid::Uuid::new_v4()))
+            .join("identity.json");
+
+        let root = AgentIdentity::load_or_create(&path).unwrap();
+        assert!(root.is_primary());
+        assert_eq!(AgentIdentity::load_or_create(&path).unwrap(), root);
+
+        let child = root.spawn_child();
+        assert_eq!(child.parent_id.as_deref(), Some(root.agent_id.as_str()));
+        assert_eq!(child.generation, 1);
+        assert_ne!(child.agent_id, root.agent_id);
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_deployed_agents_do_not_become_roots() {
+        let path = std::env::temp_dir()
+            .join(format!("aurelia-identity-{}", uuid::Uuid::new_v4()))
+            .join("identity.json");
+
+        let primary = Some("http://primary:8080");
+        assert!(AgentIdentity::load_or_create_for(&path, primary).is_err());
+        assert!(!path.exists());
+
+        let child = AgentIdentity::new_root().spawn_child();
+        child.save(&path).unwrap();
+        assert_eq!(
+            AgentIdentity::load_or_create_for(&path, primary).unwrap(),
+            child
+        );
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
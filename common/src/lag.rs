@@ -0,0 +1,229 @@
+//! What a consumer does when it falls behind the event bus.
+//!
+//! A subscription whose channel fills up loses its oldest events, and its next
+//! `recv` returns `RecvError::Lagged(n)`. Engines hand that to their
+//! [`LagHandler`] rather than logging it themselves: the gap is written to the
+//! audit log and published as `AppEvent::ConsumerLagged`, which monitoring
+//! counts. Consumers that keep state built from the events they receive, such
+//! as the survival protocol's funds, ask for that state to be sent again; the
+//! execution engine answers by republishing the portfolio's balances and value.
+//!
+//! A consumer that keeps falling behind reports at most one gap per
+//! [`LAG_REPORT_INTERVAL`]; the events it loses in between are added up and
+//! reported once the interval is over.
+
+use crate::audit::{self, AuditCategory};
+use crate::{AppEvent, EventBus};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Shortest time between two gaps reported by the same consumer
+pub const LAG_REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Events a consumer lost because it fell behind.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConsumerLag {
+    /// Name of the subscription, as given to [`EventBus::subscribe_as`]
+    pub consumer: String,
+    pub missed: u64,
+    /// Whether the consumer asks for the state it builds from events to be resent
+    pub resync: bool,
+}
+
+/// Gaps not reported yet because the last report is too recent
+#[derive(Default)]
+struct Window {
+    last_report: Option<Instant>,
+    missed: u64,
+    flush_scheduled: bool,
+}
+
+/// Reports the gaps in one consumer's events.
+#[derive(Clone)]
+pub struct LagHandler {
+    consumer: String,
+    tx: Option<EventBus>,
+    resync: bool,
+    interval: Duration,
+    window: Arc<Mutex<Window>>,
+}
+
+impl LagHandler {
+    pub fn new(consumer: impl Into<String>) -> Self {
+        Self {
+            consumer: consumer.into(),
+            tx: None,
+            resync: false,
+            interval: LAG_REPORT_INTERVAL,
+            window: Arc::default(),
+        }
+    }
+
+    /// Publish `ConsumerLagged` on the bus
+    pub fn with_event_bus(mut self, tx: EventBus) -> Self {
+        self.tx = Some(tx);
+        self
+    }
+
+    /// Ask for a resync after every gap, for consumers whose state a lost event
+    /// leaves wrong
+    pub fn with_resync(mut self) -> Self {
+        self.resync = true;
+        self
+    }
+
+    /// Report gaps at most once per `interval` instead of [`LAG_REPORT_INTERVAL`]
+    pub fn with_report_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Report that `missed` events were lost. Returns whether a resync was
+    /// requested now; gaps within the report interval are reported together later.
+    pub fn lagged(&self, missed: u64) -> bool {
+        let due = {
+            let mut window = self.window.lock().expect("lag window lock poisoned");
+            window.missed += missed;
+            let due = window
+                .last_report
+                .is_none_or(|at| at.elapsed() >= self.interval);
+            if !due && !window.flush_scheduled {
+                window.flush_scheduled = self.schedule_flush(window.last_report);
+            }
+            due
+        };
+        if !due {
+            debug!(
+                consumer = %self.consumer,
+                missed = missed,
+                "[Event Bus] {} lost {} more events, reporting them later",
+                self.consumer,
+                missed
+            );
+            return false;
+        }
+        self.report()
+    }
+
+    /// Report the gaps once the interval since the last report is over, if a
+    /// runtime is around to wait for it. Otherwise the next gap reports them.
+    fn schedule_flush(&self, last_report: Option<Instant>) -> bool {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return false;
+        };
+        let wait = last_report
+            .map(|at| self.interval.saturating_sub(at.elapsed()))
+            .unwrap_or_default();
+        let handler = self.clone();
+        runtime.spawn(async move {
+            tokio::time::sleep(wait).await;
+            handler.report();
+        });
+        true
+    }
+
+    /// Report everything lost since the last report
+    fn report(&self) -> bool {
+        let missed = {
+            let mut window = self.window.lock().expect("lag window lock poisoned");
+            window.last_report = Some(Instant::now());
+            window.flush_scheduled = false;
+            std::mem::take(&mut window.missed)
+        };
+        if missed == 0 {
+            return false;
+        }
+        warn!(
+            consumer = %self.consumer,
+            missed = missed,
+            resync = self.resync,
+            "[Event Bus] {} fell behind and lost {} events",
+            self.consumer,
+            missed
+        );
+        let lag = ConsumerLag {
+            consumer: self.consumer.clone(),
+            missed,
+            resync: self.resync,
+        };
+        audit::record(AuditCategory::RecoveryAction, "event_gap", &lag);
+        if let Some(tx) = &self.tx {
+            if tx.send(AppEvent::ConsumerLagged(lag)).is_err() {
+                debug!("[Event Bus] Nobody listens for lagged consumers");
+            }
+        }
+        self.resync
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Topic;
+
+    #[test]
+    fn test_lag_is_published() {
+        let bus = EventBus::new(16);
+        let mut rx = bus.subscribe_as("monitoring", &[Topic::System]);
+
+        let plain = LagHandler::new("accountant").with_event_bus(bus.clone());
+        assert!(!plain.lagged(3));
+        let stateful = LagHandler::new("survival_protocol")
+            .with_event_bus(bus)
+            .with_resync();
+        assert!(stateful.lagged(7));
+
+        let published: Vec<_> = (0..2)
+            .map(|_| match rx.try_recv() {
+                Ok(AppEvent::ConsumerLagged(lag)) => lag,
+                other => panic!("unexpected {:?}", other.map(|e| e.kind())),
+            })
+            .collect();
+        assert_eq!(
+            published,
+            vec![
+                ConsumerLag {
+                    consumer: "accountant".to_string(),
+                    missed: 3,
+                    resync: false,
+                },
+                ConsumerLag {
+                    consumer: "survival_protocol".to_string(),
+                    missed: 7,
+                    resync: true,
+                },
+            ]
+        );
+        // Without a bus the gap is only logged and audited
+        assert!(!LagHandler::new("reasoning_engine").lagged(1));
+    }
+
+    #[tokio::test]
+    async fn test_gaps_are_coalesced() {
+        let bus = EventBus::new(16);
+        let mut rx = bus.subscribe_as("monitoring", &[Topic::System]);
+        let lag = LagHandler::new("survival_protocol")
+            .with_event_bus(bus)
+            .with_resync()
+            .with_report_interval(Duration::from_millis(50));
+
+        assert!(lag.lagged(3));
+        assert!(!lag.lagged(4));
+        assert!(!lag.lagged(5));
+        fn missed(rx: &mut crate::EventReceiver) -> Option<u64> {
+            match rx.try_recv() {
+                Ok(AppEvent::ConsumerLagged(lag)) => Some(lag.missed),
+                _ => None,
+            }
+        }
+        assert_eq!(missed(&mut rx), Some(3));
+        assert_eq!(missed(&mut rx), None);
+
+        // The rest is reported once the interval is over, without another gap
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(missed(&mut rx), Some(9));
+        assert_eq!(missed(&mut rx), None);
+    }
+}
@@ -1,5 +1,67 @@
 use serde::{Deserialize, Serialize};
-use tokio::sync::broadcast;
+use std::collections::BTreeMap;
+use std::fmt;
+use tokio::sync::mpsc;
+
+pub mod audit;
+pub mod bounded_history;
+pub mod bundle;
+pub mod bus;
+pub mod bus_metrics;
+pub mod circuit_breaker;
+pub mod clock;
+pub mod cost_model;
+pub mod error;
+pub mod event_schema;
+pub mod health;
+pub mod identity;
+pub mod lag;
+pub mod performance;
+pub mod priority;
+pub mod rate_limit;
+pub mod sealed_config;
+pub mod secrets;
+pub mod signing;
+pub mod ssh;
+#[cfg(feature = "ssh")]
+pub mod ssh_pool;
+pub mod state_store;
+pub mod strategies;
+pub mod strategy_config;
+pub mod trade_ledger;
+pub mod valuation;
+
+/// Generated types and service definitions of the gRPC API, see `proto/aurelia.proto`
+#[cfg(feature = "grpc")]
+pub mod proto {
+    tonic::include_proto!("aurelia.v1");
+}
+
+pub use audit::{AuditCategory, AuditEntry, AuditLog};
+pub use bounded_history::BoundedHistory;
+pub use bundle::{DeploymentBundle, RenderedFile};
+pub use bus::{EventBus, EventReceiver, Topic};
+pub use bus_metrics::{BusMetricsSnapshot, EventTypeSnapshot, LatencySnapshot, SubscriberSnapshot};
+pub use circuit_breaker::{CircuitBreaker, CircuitError, CircuitState};
+pub use clock::{Clock, MockClock, SharedClock, SystemClock};
+pub use cost_model::{CostModel, Liquidity};
+pub use error::{AureliaError, AureliaResult};
+pub use health::HealthState;
+pub use identity::AgentIdentity;
+pub use lag::{ConsumerLag, LagHandler};
+pub use performance::{PerformanceReport, StrategyPerformance};
+pub use priority::ProcessPriority;
+pub use rate_limit::{EndpointClass, RateLimiter};
+pub use sealed_config::{FleetKey, SealedConfig};
+pub use secrets::SecretStore;
+pub use signing::{BundleSignatures, ReleaseSigner};
+pub use ssh::{host_port, CancellationToken, SshTimeouts};
+#[cfg(feature = "ssh")]
+pub use ssh_pool::{SessionLease, SshConnectionManager};
+pub use state_store::{AgentState, StateStore};
+pub use strategies::{StrategyKind, StrategySet, StrategySpec};
+pub use trade_ledger::{ExplainedTrade, Fill, TradeLedger};
+pub use valuation::{AccountingConfig, NetAssetValue, PriceBook};
 
 /// Information required for deploying the agent to a new server.
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -14,23 +76,671 @@ pub struct DeploymentInfo {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum AppEvent {
     SystemVitals(SystemVitals),
+    /// Sampled trades, see `perception_core::sampling`.
     MarketData(MarketData),
-    StrategyDecision(StrategyDecision),
+    /// Every trade as received from the exchange, before sampling.
+    MarketTick(MarketData),
+    /// A candle whose interval has ended.
+    Candle(Candle),
+    /// A symbol's regime, re-estimated from recent candles on every candle.
+    MarketConditions(MarketConditions),
+    StrategyDecision(StrategyDecision, EventMeta),
     ReloadConfig,
     SystemStateChange(SystemState),
+    /// Net asset value in the accounting currency, see [`valuation`]
     FinancialUpdate(f64),
+    /// The holdings behind the latest `FinancialUpdate`, asset by asset.
+    NetAssetValue(Box<NetAssetValue>),
     WebSearchQuery(String),
     WebSearchResponse(Vec<String>),
-    LlmQuery(String),
-    LlmResponse(String),
+    /// A prompt for the reasoning engine, which answers with an `LlmResponse`
+    /// carrying the same ID
+    LlmQuery(String, CorrelationId),
+    LlmResponse(String, CorrelationId),
     ModuleReadyForHotSwap(String),
     Deploy(DeploymentInfo),
+    SetDecisionPolicy(DecisionPolicyKind),
+    StrategyParamUpdate(StrategyParamUpdate),
+    /// A strategy library to shadow-run before it may replace the live one.
+    CandidateModuleReady(String),
+    ShadowTrialCompleted(ShadowTrialReport),
+    /// A source mutation the metamorphosis policy refused to compile.
+    MutationRejected(MutationRejected),
+    SentimentUpdate(SentimentUpdate),
+    NewsItem(NewsItem),
+    FundingRate(FundingRate),
+    OpenInterest(OpenInterest),
+    TickerStats(TickerStats),
+    /// The best price gap for a symbol between two exchanges.
+    CrossExchangeSpread(Box<CrossExchangeSpread>),
+    OrderUpdate(Box<OrderUpdate>),
+    /// Progress of a decision executed as a series of child orders.
+    ExecutionProgress(Box<ExecutionProgress>),
+    /// A stop-loss or take-profit closed (part of) a position.
+    ProtectionTriggered(ProtectionTriggered),
+    FleetValidation(FleetValidationReport),
+    /// Funds of the live replicas, published by the primary as they report.
+    FleetFunds(FleetFunds),
+    /// Per-strategy scoreboard, published by the allocator on every pass.
+    StrategyPerformance(PerformanceReport),
+    /// A server managed by the deployment commander changed state.
+    DeploymentStatusChanged(DeploymentStatus),
+    /// The self-replicator finished an attempt to deploy a replica.
+    ReplicationCompleted(ReplicationResult),
+    /// A migration moved on to its next stage, finished or failed.
+    MigrationProgress(Box<MigrationStatus>),
+    /// Start streaming trades of a symbol such as `ETHUSDT`.
+    SubscribeSymbol(String),
+    /// Stop streaming trades of a symbol.
+    UnsubscribeSymbol(String),
+    /// Periodic snapshots of the autonomous agent's subsystems.
+    SchedulerStatus(SchedulerStatus),
+    RecoveryStats(RecoveryStats),
+    HealthSummary(Box<HealthSummary>),
+    /// Kill switch: cancel all open orders, close every position at market and
+    /// suspend strategy decisions until `ResumeTrading`. Carries the reason.
+    EmergencyFlatten(String),
+    /// Suspend strategy decisions without touching open orders or positions
+    /// until `ResumeTrading`. Carries the reason.
+    PauseTrading(String),
+    /// Leave the safe state entered by `EmergencyFlatten` or `PauseTrading`.
+    ResumeTrading,
+    /// What the execution engine did in response to `EmergencyFlatten`.
+    FlattenCompleted(FlattenReport),
+    /// Calls to an external dependency are failing and have been stopped, see
+    /// [`circuit_breaker`]. Carries the component, e.g. `exchange_rest`.
+    CircuitOpen(String),
+    /// A probe call succeeded and the component is used normally again.
+    CircuitClosed(String),
+    /// A subscriber fell behind and lost events, see [`lag`].
+    ConsumerLagged(ConsumerLag),
+}
+
+/// Perpetual futures funding, from Binance USDⓈ-M futures.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FundingRate {
+    pub symbol: String,
+    /// Rate paid by longs to shorts each funding interval (negative: shorts pay)
+    pub funding_rate: f64,
+    pub mark_price: f64,
+    pub index_price: f64,
+    /// Unix milliseconds
+    pub next_funding_time: u64,
+    /// Unix milliseconds
+    pub timestamp: u64,
+}
+
+/// Open futures contracts for a symbol, in base asset units.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct OpenInterest {
+    pub symbol: String,
+    pub open_interest: f64,
+    /// Unix milliseconds
+    pub timestamp: u64,
+}
+
+/// Rolling 24 hour spot statistics.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TickerStats {
+    pub symbol: String,
+    pub last_price: f64,
+    pub price_change_percent: f64,
+    pub high_price: f64,
+    pub low_price: f64,
+    /// Base asset volume
+    pub volume: f64,
+    pub quote_volume: f64,
+    /// Unix milliseconds
+    pub timestamp: u64,
+}
+
+/// Buying a symbol on one exchange and selling it on another at their latest
+/// quotes. Both quotes were fresh and taken within the configured skew of each
+/// other, after allowing for each exchange's feed latency.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CrossExchangeSpread {
+    pub symbol: String,
+    /// Exchange with the lower ask
+    pub buy_venue: String,
+    /// Exchange with the higher bid
+    pub sell_venue: String,
+    /// Ask on the buy venue
+    pub buy_price: f64,
+    /// Bid on the sell venue
+    pub sell_price: f64,
+    /// `(sell_price - buy_price) / buy_price` in basis points
+    pub spread_bps: f64,
+    /// `spread_bps` less the taker fees of both venues
+    pub net_spread_bps: f64,
+    /// Whether `net_spread_bps` reaches the configured minimum
+    pub opportunity: bool,
+    /// How far apart the two quotes were taken, in milliseconds
+    pub skew_ms: f64,
+    /// Estimated feed latency of the buy and sell venue, in milliseconds
+    pub buy_latency_ms: f64,
+    pub sell_latency_ms: f64,
+    /// Unix milliseconds
+    pub timestamp: u64,
+}
+
+/// Where a sliced execution stands after a child order or when it ends.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ExecutionProgress {
+    /// Of the decision being executed
+    pub correlation_id: CorrelationId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy_id: Option<String>,
+    pub symbol: String,
+    /// `BUY` or `SELL`
+    pub side: String,
+    /// `twap` or `vwap`
+    pub algorithm: String,
+    pub target_quantity: f64,
+    pub executed_quantity: f64,
+    pub child_orders: u32,
+    pub status: ExecutionStatus,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionStatus {
+    Running,
+    Completed,
+    /// Stopped early, e.g. because the strategy reversed
+    Cancelled,
+}
+
+/// A fill of a protective order, or the trigger of a synthetic one.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ProtectionTriggered {
+    pub symbol: String,
+    pub kind: ProtectionKind,
+    /// The stop-loss or take-profit level that was reached
+    pub trigger_price: f64,
+    /// Price the position was closed at, or the last trade price for a
+    /// synthetic order still being filled
+    pub price: f64,
+    pub quantity: f64,
+    /// Average entry price of the protected position
+    pub entry_price: f64,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProtectionKind {
+    StopLoss,
+    TakeProfit,
+}
+
+/// What the exchange API keys are good for, see `execution_engine::funding_guard`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialStatus {
+    /// `BINANCE_API_KEY` or `BINANCE_API_SECRET` is not set
+    Missing,
+    /// A key is left at an example value such as `test_api_key`
+    Placeholder,
+    /// The exchange rejected the key or the signature
+    Invalid,
+    /// The exchange could not be reached, so the keys are untested
+    Unverified,
+    /// Valid keys that may not place orders
+    ReadOnly,
+    /// Valid keys allowed to trade
+    Trading,
+}
+
+/// Result of checking the exchange credentials.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CredentialReport {
+    pub status: CredentialStatus,
+    #[serde(default)]
+    pub can_trade: bool,
+    #[serde(default)]
+    pub can_withdraw: bool,
+    /// Account permissions reported by the exchange, such as `SPOT`
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub checked_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl CredentialReport {
+    pub fn new(status: CredentialStatus) -> Self {
+        Self {
+            status,
+            can_trade: false,
+            can_withdraw: false,
+            permissions: Vec::new(),
+            error: None,
+            checked_at: chrono::Utc::now(),
+        }
+    }
+
+    /// Whether real orders may be sent with these keys
+    pub fn allows_live_trading(&self) -> bool {
+        self.status == CredentialStatus::Trading
+    }
+}
+
+/// Outcome of an emergency flatten.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FlattenReport {
+    pub reason: String,
+    pub cancelled_orders: usize,
+    pub closed_positions: Vec<ClosedPosition>,
+    /// Orders or positions that could not be cancelled or closed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    /// Whether no live order was involved
+    pub simulated: bool,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ClosedPosition {
+    pub symbol: String,
+    /// `BUY` or `SELL`, the side of the closing order
+    pub side: String,
+    pub quantity: f64,
+    /// Last known price, at which a simulated close fills
+    pub price: f64,
+}
+
+/// An exchange-side change to one of our orders, as reported by the user-data stream.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct OrderUpdate {
+    pub symbol: String,
+    pub order_id: u64,
+    pub client_order_id: String,
+    /// `BUY` or `SELL`
+    pub side: String,
+    /// Exchange order status: `NEW`, `PARTIALLY_FILLED`, `FILLED`, `CANCELED`, ...
+    pub status: String,
+    pub price: f64,
+    pub quantity: f64,
+    /// Cumulative filled quantity
+    pub filled_quantity: f64,
+    /// Price and quantity of the fill that caused this update, zero if none
+    pub last_fill_price: f64,
+    pub last_fill_quantity: f64,
+    pub commission: f64,
+    pub commission_asset: Option<String>,
+    /// Unix milliseconds
+    pub timestamp: u64,
+}
+
+/// A news article picked up by a perception source.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct NewsItem {
+    pub title: String,
+    pub url: String,
+    /// Name of the feed or API it came from
+    pub source: String,
+    /// Publication time in Unix milliseconds, or when it was first seen if the
+    /// source gives none
+    pub timestamp: u64,
+}
+
+/// Aggregated news sentiment for a symbol, from -1 (bearish) to 1 (bullish).
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SentimentUpdate {
+    pub symbol: String,
+    pub score: f64,
+    /// Analyses that contributed to the score
+    pub samples: u32,
+    /// Unix milliseconds, like `MarketData::timestamp`
+    pub timestamp: u64,
+}
+
+/// Broad character of a market, see `perception_core::regime`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarketRegime {
+    /// Too few candles to tell yet
+    #[default]
+    Unknown,
+    TrendingUp,
+    TrendingDown,
+    /// No clear direction and ordinary price swings
+    Ranging,
+    /// Price swings among the widest of the look-back window
+    Volatile,
+    /// Volume far below its recent average
+    Illiquid,
+}
+
+/// Market conditions of one symbol, or of the whole market when `symbol` is
+/// `None`. Scores run from 0 to 1 unless noted otherwise.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct MarketConditions {
+    #[serde(default)]
+    pub symbol: Option<String>,
+    #[serde(default)]
+    pub regime: MarketRegime,
+    /// Percentile of the current price swings within the look-back window
+    pub volatility: f64,
+    /// Net move over recent candles relative to the path travelled, from -1
+    /// (straight down) to 1 (straight up)
+    #[serde(default)]
+    pub trend_strength: f64,
+    /// Recent volume relative to its look-back average, capped at 1
+    #[serde(default)]
+    pub liquidity: f64,
+    pub opportunity_score: f64,
+    pub risk_level: f64,
+    /// Aggregated news sentiment, from -1 (bearish) to 1 (bullish)
+    #[serde(default)]
+    pub sentiment: f64,
+    /// Unix milliseconds of the candle the estimate was made on
+    #[serde(default)]
+    pub timestamp: u64,
+}
+
+/// Paper results of one side of a shadow trial.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ShadowArm {
+    pub decisions: u32,
+    pub paper_pnl: f64,
+}
+
+/// Outcome of shadow-running a candidate strategy next to the live one.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ShadowTrialReport {
+    pub candidate: String,
+    pub duration_seconds: u64,
+    pub active: ShadowArm,
+    pub challenger: ShadowArm,
+    pub promoted: bool,
+    pub reason: String,
+}
+
+/// A mutation of `path` that broke the metamorphosis policy.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MutationRejected {
+    pub path: String,
+    pub violations: Vec<String>,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// Outcome of one check of a fleet validation pass.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FleetCheck {
+    pub name: String,
+    pub passed: bool,
+    pub detail: Option<String>,
+}
+
+/// A replica as seen by the primary's periodic validation pass.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ReplicaValidation {
+    pub agent_id: String,
+    pub ip_address: String,
+    pub passed: bool,
+    pub checks: Vec<FleetCheck>,
+}
+
+/// Result of validating every known replica from the primary.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FleetValidationReport {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub passed: usize,
+    pub failed: usize,
+    pub replicas: Vec<ReplicaValidation>,
+}
+
+/// Net asset value the primary's live replicas last reported, for the
+/// consolidated runway. Excludes the primary's own funds.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FleetFunds {
+    /// Live replicas whose funds are included
+    pub replicas: usize,
+    /// Summed net asset value in the accounting currency
+    pub funds: f64,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// Deployment state of one configured target server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentStatus {
+    pub server_id: String,
+    pub ip: String,
+    pub status: DeploymentState,
+    pub last_attempt: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_success: Option<chrono::DateTime<chrono::Utc>>,
+    pub error_message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DeploymentState {
+    NotDeployed,
+    Deploying,
+    Running,
+    Failed,
+    Stopped,
+}
+
+/// Outcome of one attempt to deploy a replica.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicationResult {
+    pub target: String,
+    pub success: bool,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub duration_seconds: u64,
+    pub error: Option<String>,
+    /// ID assigned to the replica, if the deployment succeeded
+    pub agent_id: Option<String>,
+}
+
+/// Where a move of an agent from one server to another stands.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MigrationStatus {
+    pub id: String,
+    /// Server the agent is moved off
+    pub from: String,
+    /// Server the agent is moved to
+    pub to: String,
+    pub reason: String,
+    pub stage: MigrationStage,
+    /// Files carried over from the source, once the snapshot was taken
+    pub snapshot_files: usize,
+    pub error: Option<String>,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Steps of a migration, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationStage {
+    Snapshotting,
+    Deploying,
+    Restoring,
+    Verifying,
+    StoppingSource,
+    CleaningUp,
+    Completed,
+    /// The source, if it was never stopped, keeps running
+    Failed,
+}
+
+impl MigrationStage {
+    pub fn is_finished(self) -> bool {
+        matches!(self, MigrationStage::Completed | MigrationStage::Failed)
+    }
+}
+
+/// Queue counts of the autonomous agent's task scheduler.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchedulerStatus {
+    pub pending_tasks: usize,
+    pub waiting_tasks: usize,
+    pub running_tasks: usize,
+    pub completed_tasks: usize,
+    pub next_task_time: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Outcomes of the recovery manager's actions so far.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryStats {
+    pub total_recoveries: usize,
+    pub successful_recoveries: usize,
+    pub failed_recoveries: usize,
+    pub success_rate: f64,
+    pub average_recovery_time_seconds: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthMetrics {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub cpu_usage: f64,
+    pub memory_usage: f64,
+    pub disk_usage: f64,
+    pub network_latency_ms: f64,
+    pub process_count: usize,
+    pub error_rate: f64,
+    pub success_rate: f64,
+    pub uptime_seconds: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum HealthStatus {
+    Healthy,
+    Degraded(String),
+    Critical(String),
+    Failed(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthCheck {
+    pub name: String,
+    pub status: HealthStatus,
+    pub last_check: chrono::DateTime<chrono::Utc>,
+    pub consecutive_failures: u32,
+    pub details: std::collections::HashMap<String, String>,
+}
+
+/// The health monitor's latest view of the system.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthSummary {
+    pub status: HealthStatus,
+    pub metrics: HealthMetrics,
+    pub checks: Vec<HealthCheck>,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// Set one of the strategy engine's tunable parameters while it runs.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct StrategyParamUpdate {
+    /// A parameter name, or `<strategy id>.<name>` to change it for one strategy only
+    pub name: String,
+    pub value: f64,
+}
+
+/// The decision policies the autonomous agent can run with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecisionPolicyKind {
+    /// Threshold rules that adapt to decision outcomes.
+    #[default]
+    RuleBased,
+    /// Avoid new servers unless they are needed to recover.
+    CostMinimizing,
+    /// Expand whenever the system is reasonably healthy.
+    AggressiveExpansion,
+    /// Ask the reasoning engine, falling back to the rules.
+    LlmAdvised,
+}
+
+impl AppEvent {
+    /// The correlation ID of events that belong to a trade pipeline.
+    pub fn correlation_id(&self) -> Option<&CorrelationId> {
+        match self {
+            AppEvent::MarketData(data) | AppEvent::MarketTick(data) => {
+                Some(&data.meta.correlation_id)
+            }
+            AppEvent::StrategyDecision(_, meta) => Some(&meta.correlation_id),
+            AppEvent::LlmQuery(_, id) | AppEvent::LlmResponse(_, id) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+/// Identifies every event derived from the same market observation, or an LLM
+/// query and its answer.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct CorrelationId(String);
+
+impl CorrelationId {
+    pub fn new() -> Self {
+        Self(uuid::Uuid::new_v4().simple().to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for CorrelationId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for CorrelationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Metadata carried along the perception → strategy → execution pipeline.
+///
+/// Events derived from another event clone its metadata so that they can be
+/// traced end-to-end by `correlation_id`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct EventMeta {
+    pub correlation_id: CorrelationId,
+    /// The strategy whose decision this is, see [`strategies`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy_id: Option<String>,
+    /// Why the strategy made its decision, boxed as it rides on every tick
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rationale: Option<Box<DecisionRationale>>,
+}
+
+impl EventMeta {
+    /// Explain the decision this metadata is carried by
+    pub fn with_rationale(mut self, rationale: DecisionRationale) -> Self {
+        self.rationale = Some(Box::new(rationale));
+        self
+    }
+}
+
+/// What a strategy saw when it decided to trade, kept with the trade so that it
+/// can be explained later.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct DecisionRationale {
+    /// Indicator values the decision was based on, e.g. `fast_ema`
+    pub indicators: BTreeMap<String, f64>,
+    /// Sentiment score of the symbol, if any was known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sentiment: Option<f64>,
+    pub regime: MarketRegime,
+    /// How far the signal cleared its threshold, between 0 and 1
+    pub confidence: f64,
+    pub reason: String,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub enum SystemState {
     Normal,
     Conservation,
+    /// Positions flattened and trading suspended; left only on `ResumeTrading`
+    Safe,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -53,10 +763,45 @@ pub struct MarketData {
     pub price: f64,
     pub quantity: f64,
     pub timestamp: u64,
+    #[serde(default)]
+    pub meta: EventMeta,
 }
 
-/// A type alias for the broadcast sender.
-pub type EventSender = broadcast::Sender<AppEvent>;
+/// Trades of one symbol summarized over a fixed interval.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Candle {
+    pub symbol: String,
+    /// Unix milliseconds
+    pub open_time: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub trades: u64,
+}
+
+/// The sending side of the event bus; cloned into every engine.
+pub type EventSender = EventBus;
+
+/// Receiving end of the reliable control-plane channel, owned by the kernel.
+pub type ControlReceiver = mpsc::Receiver<AppEvent>;
 
-/// A type alias for the broadcast receiver.
-pub type EventReceiver = broadcast::Receiver<AppEvent>;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_market_data_without_meta_gets_correlation_id() {
+        let json = r#"{"symbol":"BTCUSDT","price":70000.0,"quantity":0.1,"timestamp":0}"#;
+        let data: MarketData = serde_json::from_str(json).unwrap();
+        let meta = data.meta.clone();
+
+        let event = AppEvent::MarketData(data);
+        assert_eq!(event.correlation_id(), Some(&meta.correlation_id));
+
+        let decision = AppEvent::StrategyDecision(StrategyDecision::Hold("BTCUSDT".into()), meta);
+        assert_eq!(decision.correlation_id(), event.correlation_id());
+        assert!(AppEvent::ReloadConfig.correlation_id().is_none());
+    }
+}
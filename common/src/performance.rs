@@ -0,0 +1,158 @@
+//! Per-strategy performance over a rolling window of the trade ledger.
+
+use crate::trade_ledger::{realize, Fill};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How one strategy did over the scoring window.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StrategyPerformance {
+    pub strategy_id: String,
+    /// Fills that realized PnL, i.e. reduced or closed a position
+    pub closed_trades: u32,
+    /// Share of closed trades with a positive net PnL
+    pub hit_rate: Option<f64>,
+    /// Mean over standard deviation of the net PnL of closed trades; needs at least
+    /// two trades that did not all end the same
+    pub sharpe: Option<f64>,
+    /// Largest fall of cumulative net PnL from its running peak, in quote asset
+    pub max_drawdown: f64,
+    /// Realized PnL minus fees, in quote asset
+    pub net_pnl: f64,
+    /// Share of the funds the strategy trades with after this scoring
+    pub allocation: f64,
+}
+
+/// Scoreboard of every strategy, published as `AppEvent::StrategyPerformance`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PerformanceReport {
+    pub timestamp: DateTime<Utc>,
+    pub window_hours: u32,
+    /// Whether allocations were changed based on this report
+    pub rebalanced: bool,
+    pub strategies: Vec<StrategyPerformance>,
+}
+
+#[derive(Default)]
+struct Tally {
+    closed: Vec<f64>,
+    cumulative: f64,
+    peak: f64,
+    max_drawdown: f64,
+}
+
+/// Score `strategies` on their fills in `[now - window, now)`. Earlier fills still
+/// build up positions, so trades closed inside the window realize against their
+/// full cost basis. Allocations are left at zero for the caller to fill in.
+pub fn score(
+    fills: &[Fill],
+    strategies: &[&str],
+    now: DateTime<Utc>,
+    window: Duration,
+) -> Vec<StrategyPerformance> {
+    let since = now - window;
+    let mut tallies: BTreeMap<&str, Tally> = strategies
+        .iter()
+        .map(|id| (*id, Tally::default()))
+        .collect();
+    for (fill, realized) in realize(fills) {
+        if fill.timestamp < since || fill.timestamp >= now {
+            continue;
+        }
+        let Some(tally) = fill
+            .strategy_id
+            .as_deref()
+            .and_then(|id| tallies.get_mut(id))
+        else {
+            continue;
+        };
+        let net = realized - fill.quote_fee();
+        if realized != 0.0 {
+            tally.closed.push(net);
+        }
+        tally.cumulative += net;
+        tally.peak = tally.peak.max(tally.cumulative);
+        tally.max_drawdown = tally.max_drawdown.max(tally.peak - tally.cumulative);
+    }
+
+    strategies
+        .iter()
+        .map(|id| {
+            let tally = &tallies[id];
+            let trades = tally.closed.len();
+            let wins = tally.closed.iter().filter(|net| **net > 0.0).count();
+            StrategyPerformance {
+                strategy_id: id.to_string(),
+                closed_trades: trades as u32,
+                hit_rate: (trades > 0).then(|| wins as f64 / trades as f64),
+                sharpe: sharpe(&tally.closed),
+                max_drawdown: tally.max_drawdown,
+                net_pnl: tally.cumulative,
+                allocation: 0.0,
+            }
+        })
+        .collect()
+}
+
+fn sharpe(returns: &[f64]) -> Option<f64> {
+    if returns.len() < 2 {
+        return None;
+    }
+    let n = returns.len() as f64;
+    let mean = returns.iter().sum::<f64>() / n;
+    let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    (variance > 0.0).then(|| mean / variance.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn fill(hour: u32, side: &str, price: f64, strategy: &str) -> Fill {
+        Fill {
+            timestamp: Utc.with_ymd_and_hms(2024, 1, 1, hour, 0, 0).unwrap(),
+            symbol: "BTCUSDT".to_string(),
+            side: side.to_string(),
+            price,
+            quantity: 1.0,
+            fee: 0.0,
+            fee_asset: None,
+            client_order_id: None,
+            simulated: true,
+            strategy_id: Some(strategy.to_string()),
+            rationale: None,
+        }
+    }
+
+    #[test]
+    fn test_strategies_are_scored_on_their_own_trades() {
+        let fills = [
+            // Opened before the window, closed inside it
+            fill(1, "BUY", 100.0, "momentum"),
+            fill(3, "SELL", 110.0, "momentum"),
+            fill(4, "BUY", 100.0, "momentum"),
+            fill(5, "SELL", 95.0, "momentum"),
+            fill(6, "BUY", 100.0, "momentum"),
+            fill(7, "SELL", 120.0, "momentum"),
+            fill(3, "BUY", 100.0, "sentiment"),
+        ];
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 8, 0, 0).unwrap();
+        let scores = score(&fills, &["momentum", "sentiment"], now, Duration::hours(6));
+
+        let momentum = &scores[0];
+        assert_eq!(momentum.closed_trades, 3);
+        assert_eq!(momentum.hit_rate, Some(2.0 / 3.0));
+        assert_eq!(momentum.net_pnl, 25.0);
+        assert_eq!(momentum.max_drawdown, 5.0);
+        // Returns of 10, -5 and 20: mean 25/3 over a sample deviation of 12.58
+        let sharpe = momentum.sharpe.unwrap();
+        assert!((sharpe - 0.6623).abs() < 1e-3, "{}", sharpe);
+
+        let sentiment = &scores[1];
+        assert_eq!(sentiment.closed_trades, 0);
+        assert_eq!(sentiment.hit_rate, None);
+        assert_eq!(sentiment.sharpe, None);
+    }
+}
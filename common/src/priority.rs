@@ -0,0 +1,191 @@
+//! Lower priority for heavy work the agent starts next to the trading path.
+//!
+//! Recompiling the strategy engine, backtests and shell tasks can saturate a
+//! small server. Child processes are started through `nice` and, on Linux,
+//! `ionice -c 3`, so the perception and execution threads keep the CPU and disk
+//! whenever they need them. With `cpu_quota_percent` set, they are also moved
+//! into a cgroup v2 group whose `cpu.max` caps them, which requires write access
+//! to `cgroup_path` (usually root, or a group delegated to the agent's user).
+//! Wrappers that are not installed are left out rather than failing the command.
+
+use crate::AureliaResult;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub const PRIORITY_CONFIG_PATH: &str = "config/process_priority.json";
+
+/// Period of the cgroup CPU quota in microseconds
+const CPU_PERIOD_US: u64 = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessPriority {
+    /// Run heavy work at normal priority when false
+    pub enabled: bool,
+    /// Niceness from 0 (normal) to 19 (lowest)
+    pub nice: i32,
+    /// Only use the disk when nothing else does (`ionice -c 3`, Linux)
+    pub idle_io: bool,
+    /// Share of one CPU the work may use in total, e.g. 50; `None` for no cap
+    pub cpu_quota_percent: Option<u32>,
+    /// cgroup v2 group the work is moved into when a quota is set
+    pub cgroup_path: PathBuf,
+}
+
+impl Default for ProcessPriority {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            nice: 10,
+            idle_io: true,
+            cpu_quota_percent: None,
+            cgroup_path: PathBuf::from("/sys/fs/cgroup/aurelia-heavy"),
+        }
+    }
+}
+
+impl ProcessPriority {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Program and arguments that run `program` with this priority. Arguments
+    /// for `program` itself go after them.
+    pub fn wrap(&self, program: &str) -> Vec<String> {
+        let mut argv = Vec::new();
+        if self.enabled {
+            if let Some(procs) = self.cgroup_procs() {
+                // The shell joins the group and then execs the rest, keeping its PID
+                argv.extend([
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    format!(
+                        "{{ echo $$ > '{}'; }} 2>/dev/null; exec \"$0\" \"$@\"",
+                        procs.display()
+                    ),
+                ]);
+            }
+            if self.nice > 0 && on_path("nice") {
+                argv.extend([
+                    "nice".to_string(),
+                    "-n".to_string(),
+                    self.nice.min(19).to_string(),
+                ]);
+            }
+            if self.idle_io && cfg!(target_os = "linux") && on_path("ionice") {
+                argv.extend(["ionice".to_string(), "-c".to_string(), "3".to_string()]);
+            }
+        }
+        argv.push(program.to_string());
+        argv
+    }
+
+    /// A command that runs `program` with this priority.
+    pub fn command(&self, program: &str) -> Command {
+        let argv = self.wrap(program);
+        let mut command = Command::new(&argv[0]);
+        command.args(&argv[1..]);
+        command
+    }
+
+    /// Apply this priority to the current process, for heavy work that runs in
+    /// the kernel binary itself such as `kernel backtest`.
+    pub fn lower_current_process(&self) {
+        if !self.enabled {
+            return;
+        }
+        let pid = std::process::id().to_string();
+        if let Some(procs) = self.cgroup_procs() {
+            if let Err(e) = std::fs::write(&procs, &pid) {
+                tracing::warn!("Failed to join cgroup {:?}: {}", self.cgroup_path, e);
+            }
+        }
+        if self.nice > 0 && on_path("renice") {
+            let nice = self.nice.min(19).to_string();
+            run_quietly(Command::new("renice").args(["-n", &nice, "-p", &pid]));
+        }
+        if self.idle_io && cfg!(target_os = "linux") && on_path("ionice") {
+            run_quietly(Command::new("ionice").args(["-c", "3", "-p", &pid]));
+        }
+    }
+
+    /// `cgroup.procs` of the quota group, after setting its `cpu.max`; `None`
+    /// without a quota or where the group cannot be set up.
+    fn cgroup_procs(&self) -> Option<PathBuf> {
+        let percent = self.cpu_quota_percent?;
+        if !cfg!(target_os = "linux") {
+            return None;
+        }
+        let quota = CPU_PERIOD_US * u64::from(percent.max(1)) / 100;
+        let setup = std::fs::create_dir_all(&self.cgroup_path).and_then(|_| {
+            std::fs::write(
+                self.cgroup_path.join("cpu.max"),
+                format!("{} {}", quota, CPU_PERIOD_US),
+            )
+        });
+        match setup {
+            Ok(()) => Some(self.cgroup_path.join("cgroup.procs")),
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to set up cgroup {:?}, running without a CPU quota: {}",
+                    self.cgroup_path,
+                    e
+                );
+                None
+            }
+        }
+    }
+}
+
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+fn run_quietly(command: &mut Command) {
+    match command.output() {
+        Ok(output) if !output.status.success() => tracing::warn!(
+            "{:?} failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Ok(_) => {}
+        Err(e) => tracing::warn!("Failed to run {:?}: {}", command, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrapped_command_runs_the_program_with_its_arguments() {
+        let priority = ProcessPriority::default();
+        let argv = priority.wrap("echo");
+        assert_eq!(argv.last().map(String::as_str), Some("echo"));
+        if on_path("nice") {
+            assert_eq!(&argv[..3], ["nice", "-n", "10"]);
+        }
+
+        let output = priority.command("echo").arg("built").output().unwrap();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "built");
+
+        let disabled = ProcessPriority {
+            enabled: false,
+            ..Default::default()
+        };
+        assert_eq!(disabled.wrap("cargo"), ["cargo"]);
+
+        let config: ProcessPriority = serde_json::from_str(r#"{"nice": 19}"#).unwrap();
+        assert_eq!(config.nice, 19);
+        assert!(config.idle_io);
+    }
+}
@@ -0,0 +1,264 @@
+use crate::AureliaResult;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const RATE_LIMITS_PATH: &str = "config/rate_limits.json";
+
+/// Groups of outbound endpoints that share one request budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointClass {
+    /// Exchange REST market data, budgeted in request weight
+    ExchangeMarketData,
+    /// Order placement and cancellation
+    ExchangeOrders,
+    Llm,
+    WebSearch,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BucketConfig {
+    /// Largest burst allowed
+    pub capacity: f64,
+    pub refill_per_second: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RateLimitConfig {
+    pub buckets: BTreeMap<EndpointClass, BucketConfig>,
+}
+
+impl Default for RateLimitConfig {
+    /// Binance allows 6000 request weight per minute and 100 orders per 10 seconds;
+    /// the defaults stay well under both.
+    fn default() -> Self {
+        let bucket = |capacity, refill_per_second| BucketConfig {
+            capacity,
+            refill_per_second,
+        };
+        Self {
+            buckets: BTreeMap::from([
+                (EndpointClass::ExchangeMarketData, bucket(1200.0, 20.0)),
+                (EndpointClass::ExchangeOrders, bucket(50.0, 5.0)),
+                (EndpointClass::Llm, bucket(10.0, 1.0)),
+                (EndpointClass::WebSearch, bucket(5.0, 0.5)),
+            ]),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    config: BucketConfig,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(config: BucketConfig, now: Instant) -> Self {
+        Self {
+            config,
+            tokens: config.capacity,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * self.config.refill_per_second).min(self.config.capacity);
+        self.updated = now;
+    }
+
+    /// Take `cost` tokens, or say how long until they will be available. A cost above
+    /// the capacity is charged as a full bucket so it can still go through.
+    fn take(&mut self, cost: f64, now: Instant) -> Result<(), Duration> {
+        self.refill(now);
+        let cost = cost.min(self.config.capacity);
+        if self.tokens >= cost {
+            self.tokens -= cost;
+            return Ok(());
+        }
+        if self.config.refill_per_second <= 0.0 {
+            return Err(Duration::from_secs(1));
+        }
+        let missing = cost - self.tokens;
+        Err(Duration::from_secs_f64(
+            missing / self.config.refill_per_second,
+        ))
+    }
+}
+
+/// Throttling counters for one endpoint class.
+#[derive(Debug, Clone, Serialize)]
+pub struct RateLimitStats {
+    pub class: EndpointClass,
+    pub capacity: f64,
+    pub available: f64,
+    /// Permits granted
+    pub permits: u64,
+    /// Permits that had to wait for the bucket to refill
+    pub throttled: u64,
+    pub total_wait_ms: u64,
+}
+
+#[derive(Debug)]
+struct ClassState {
+    bucket: TokenBucket,
+    permits: u64,
+    throttled: u64,
+    total_wait: Duration,
+}
+
+/// Token buckets per endpoint class, shared by every component that calls out.
+///
+/// Callers `acquire` a permit before each request and wait if the budget for its class
+/// is spent. Classes without a configured bucket are not limited.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    classes: Arc<Mutex<BTreeMap<EndpointClass, ClassState>>>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(RateLimitConfig::default())
+    }
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        let now = Instant::now();
+        let classes = config
+            .buckets
+            .into_iter()
+            .map(|(class, bucket)| {
+                let state = ClassState {
+                    bucket: TokenBucket::new(bucket, now),
+                    permits: 0,
+                    throttled: 0,
+                    total_wait: Duration::ZERO,
+                };
+                (class, state)
+            })
+            .collect();
+        Self {
+            classes: Arc::new(Mutex::new(classes)),
+        }
+    }
+
+    /// Wait until `cost` units of `class` budget are available and take them.
+    /// Returns how long the caller was held back.
+    pub async fn acquire(&self, class: EndpointClass, cost: f64) -> Duration {
+        let started = Instant::now();
+        let mut throttled = false;
+        loop {
+            let wait = {
+                let mut classes = self.classes.lock().expect("rate limiter lock poisoned");
+                let Some(state) = classes.get_mut(&class) else {
+                    return Duration::ZERO;
+                };
+                match state.bucket.take(cost, Instant::now()) {
+                    Ok(()) => {
+                        state.permits += 1;
+                        if !throttled {
+                            return Duration::ZERO;
+                        }
+                        let waited = started.elapsed();
+                        state.throttled += 1;
+                        state.total_wait += waited;
+                        return waited;
+                    }
+                    Err(wait) => wait,
+                }
+            };
+            throttled = true;
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    pub fn stats(&self) -> Vec<RateLimitStats> {
+        let mut classes = self.classes.lock().expect("rate limiter lock poisoned");
+        let now = Instant::now();
+        classes
+            .iter_mut()
+            .map(|(class, state)| {
+                state.bucket.refill(now);
+                RateLimitStats {
+                    class: *class,
+                    capacity: state.bucket.config.capacity,
+                    available: state.bucket.tokens,
+                    permits: state.permits,
+                    throttled: state.throttled,
+                    total_wait_ms: state.total_wait.as_millis() as u64,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket_refills() {
+        let start = Instant::now();
+        let config = BucketConfig {
+            capacity: 2.0,
+            refill_per_second: 1.0,
+        };
+        let mut bucket = TokenBucket::new(config, start);
+        assert!(bucket.take(1.0, start).is_ok());
+        assert!(bucket.take(1.0, start).is_ok());
+        assert_eq!(bucket.take(1.0, start), Err(Duration::from_secs(1)));
+
+        let later = start + Duration::from_millis(500);
+        assert_eq!(bucket.take(1.0, later), Err(Duration::from_millis(500)));
+        // Oversized requests wait for a full bucket rather than forever
+        let much_later = start + Duration::from_secs(10);
+        assert!(bucket.take(5.0, much_later).is_ok());
+        assert_eq!(bucket.tokens, 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_acquire_records_throttling() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            buckets: BTreeMap::from([(
+                EndpointClass::Llm,
+                BucketConfig {
+                    capacity: 1.0,
+                    refill_per_second: 50.0,
+                },
+            )]),
+        });
+        assert_eq!(
+            limiter.acquire(EndpointClass::Llm, 1.0).await,
+            Duration::ZERO
+        );
+        assert!(limiter.acquire(EndpointClass::Llm, 1.0).await > Duration::ZERO);
+        // Unconfigured classes pass straight through
+        assert_eq!(
+            limiter.acquire(EndpointClass::WebSearch, 100.0).await,
+            Duration::ZERO
+        );
+
+        let stats = limiter.stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!((stats[0].permits, stats[0].throttled), (2, 1));
+    }
+}
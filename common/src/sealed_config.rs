@@ -0,0 +1,269 @@
+//! Secrets shipped to replicas without ever being on their disk in cleartext.
+//!
+//! The `.env` of the primary, with the exchange API keys, is sealed with a
+//! per-fleet key into [`SEALED_CONFIG_PATH`], which is what the deployer
+//! uploads. At startup a replica unseals it with the fleet key it was given
+//! through the environment or instance metadata and places the values in its
+//! own environment, where the execution engine reads them.
+//!
+//! The fleet key is a ChaCha20-Poly1305 key kept in the [`SecretStore`] as
+//! [`FLEET_KEY_SECRET`], so `AURELIA_SECRET_FLEET_CONFIG_KEY` provides it.
+
+use crate::secrets;
+use crate::{AureliaError, AureliaResult, SecretStore};
+use rand::RngCore;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+pub const SEALED_CONFIG_PATH: &str = "config/secrets.sealed";
+
+/// Name of the fleet key in the [`SecretStore`]
+pub const FLEET_KEY_SECRET: &str = "fleet_config_key";
+
+/// Environment variable with a URL serving the hex fleet key, such as a cloud
+/// provider's instance metadata or user data endpoint
+pub const FLEET_KEY_URL_ENV: &str = "AURELIA_FLEET_KEY_URL";
+
+/// Version of the sealed file format
+pub const SEALED_CONFIG_VERSION: u32 = 1;
+
+/// Bound to every ciphertext so it cannot pass for another kind of message
+const AAD_CONTEXT: &str = "aurelia-sealed-config-v1";
+
+/// Hashed with the key into [`FleetKey::auth_token`]
+const AUTH_TOKEN_CONTEXT: &str = "aurelia-fleet-token-v1";
+
+const KEY_LEN: usize = 32;
+
+/// Key the configuration of one fleet is sealed with.
+#[derive(Clone)]
+pub struct FleetKey {
+    bytes: [u8; KEY_LEN],
+}
+
+impl std::fmt::Debug for FleetKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FleetKey").field("id", &self.id()).finish()
+    }
+}
+
+impl FleetKey {
+    /// The fleet key of this agent, generated on first use.
+    pub fn load_or_create(store: &SecretStore) -> AureliaResult<Self> {
+        if let Some(key) = Self::load(store)? {
+            return Ok(key);
+        }
+        let mut bytes = [0u8; KEY_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut bytes);
+        let key = Self { bytes };
+        store.set(FLEET_KEY_SECRET, &key.to_hex())?;
+        Ok(key)
+    }
+
+    /// The fleet key, if one was stored or provided through the environment.
+    pub fn load(store: &SecretStore) -> AureliaResult<Option<Self>> {
+        store
+            .get(FLEET_KEY_SECRET)?
+            .map(|hex| Self::from_hex(&hex))
+            .transpose()
+    }
+
+    pub fn from_hex(hex: &str) -> AureliaResult<Self> {
+        let bytes = hex::decode(hex.trim())
+            .ok()
+            .and_then(|bytes| <[u8; KEY_LEN]>::try_from(bytes).ok())
+            .ok_or_else(|| {
+                AureliaError::Config(format!("fleet key must be {} hex-encoded bytes", KEY_LEN))
+            })?;
+        Ok(Self { bytes })
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.bytes)
+    }
+
+    /// Fingerprint naming the key in sealed files and logs, not secret.
+    pub fn id(&self) -> String {
+        hex::encode(&Sha256::digest(self.bytes)[..8])
+    }
+
+    /// Bearer token agents of the fleet present to each other. Derived from the
+    /// key so the key itself never crosses the network.
+    pub fn auth_token(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(AUTH_TOKEN_CONTEXT.as_bytes());
+        hasher.update(self.bytes);
+        hex::encode(hasher.finalize())
+    }
+
+    /// Whether `presented` is [`FleetKey::auth_token`], compared in constant time
+    pub fn is_auth_token(&self, presented: Option<&str>) -> bool {
+        secrets::token_matches(&self.auth_token(), presented)
+    }
+
+    fn aead(&self) -> LessSafeKey {
+        LessSafeKey::new(
+            UnboundKey::new(&CHACHA20_POLY1305, &self.bytes).expect("key has the AEAD's length"),
+        )
+    }
+}
+
+/// Environment variables sealed with a [`FleetKey`], as stored on disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedConfig {
+    pub version: u32,
+    /// [`FleetKey::id`] of the key it was sealed with
+    pub key_id: String,
+    pub sealed_at: chrono::DateTime<chrono::Utc>,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl SealedConfig {
+    pub fn seal(key: &FleetKey, values: &BTreeMap<String, String>) -> AureliaResult<Self> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        let key_id = key.id();
+        let mut in_out = serde_json::to_vec(values)?;
+        key.aead()
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(aad(&key_id)),
+                &mut in_out,
+            )
+            .map_err(|_| AureliaError::Config("failed to seal configuration".to_string()))?;
+        Ok(Self {
+            version: SEALED_CONFIG_VERSION,
+            key_id,
+            sealed_at: chrono::Utc::now(),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(in_out),
+        })
+    }
+
+    /// The sealed values, failing unless `key` is the one they were sealed with
+    /// and the file is unmodified.
+    pub fn unseal(&self, key: &FleetKey) -> AureliaResult<BTreeMap<String, String>> {
+        if self.version != SEALED_CONFIG_VERSION {
+            return Err(AureliaError::Config(format!(
+                "sealed config version {} is not supported",
+                self.version
+            )));
+        }
+        if self.key_id != key.id() {
+            return Err(AureliaError::Config(format!(
+                "sealed with fleet key {}, not {}",
+                self.key_id,
+                key.id()
+            )));
+        }
+        let invalid = || AureliaError::Config("sealed config is corrupt".to_string());
+        let nonce = hex::decode(&self.nonce)
+            .ok()
+            .and_then(|nonce| <[u8; NONCE_LEN]>::try_from(nonce).ok())
+            .ok_or_else(invalid)?;
+        let mut in_out = hex::decode(&self.ciphertext).map_err(|_| invalid())?;
+        let plaintext = key
+            .aead()
+            .open_in_place(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(aad(&self.key_id)),
+                &mut in_out,
+            )
+            .map_err(|_| AureliaError::Config("sealed config failed authentication".to_string()))?;
+        Ok(serde_json::from_slice(plaintext)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> AureliaResult<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+fn aad(key_id: &str) -> Vec<u8> {
+    format!("{}\n{}", AAD_CONTEXT, key_id).into_bytes()
+}
+
+/// `KEY=VALUE` lines of a `.env` file. Blank lines, comments and an `export`
+/// prefix are skipped, and quotes around a value are removed.
+pub fn parse_env_file(contents: &str) -> BTreeMap<String, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (name, value) = line.split_once('=')?;
+            let value = value.trim();
+            let value = ['"', '\'']
+                .into_iter()
+                .find_map(|quote| value.strip_prefix(quote)?.strip_suffix(quote))
+                .unwrap_or(value);
+            Some((name.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_round_trip_and_tampering() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let store = SecretStore::new(dir.join("secrets"));
+        let key = FleetKey::load_or_create(&store).unwrap();
+        assert_eq!(FleetKey::load(&store).unwrap().unwrap().id(), key.id());
+
+        let values = parse_env_file(
+            "# exchange\nBINANCE_API_KEY=key-xyz\nexport BINANCE_API_SECRET=\"s=cret\"\n\n",
+        );
+        assert_eq!(values["BINANCE_API_SECRET"], "s=cret");
+        let sealed = SealedConfig::seal(&key, &values).unwrap();
+        let path = dir.join("secrets.sealed");
+        sealed.save(&path).unwrap();
+        let on_disk = std::fs::read_to_string(&path).unwrap();
+        // Neither value could show up in the hex ciphertext by chance
+        assert!(!on_disk.contains("key-xyz") && !on_disk.contains("s=cret"));
+        assert_eq!(
+            SealedConfig::load(&path).unwrap().unseal(&key).unwrap(),
+            values
+        );
+
+        let other = FleetKey::from_hex(&"11".repeat(32)).unwrap();
+        assert!(sealed.unseal(&other).is_err());
+        let mut tampered = sealed.clone();
+        tampered.ciphertext.replace_range(0..2, "00");
+        if tampered.ciphertext == sealed.ciphertext {
+            tampered.ciphertext.replace_range(0..2, "ff");
+        }
+        assert!(tampered.unseal(&key).is_err());
+    }
+
+    #[test]
+    fn test_auth_token_is_derived_from_the_key() {
+        let key = FleetKey::from_hex(&"22".repeat(32)).unwrap();
+        let token = key.auth_token();
+        assert_eq!(token.len(), 64);
+        assert!(!token.contains(&key.to_hex()));
+        assert!(key.is_auth_token(Some(&token)));
+        assert!(!key.is_auth_token(Some(&key.to_hex())));
+        assert!(!key.is_auth_token(None));
+
+        let other = FleetKey::from_hex(&"11".repeat(32)).unwrap();
+        assert!(!other.is_auth_token(Some(&token)));
+    }
+}
@@ -0,0 +1,90 @@
+//! Secrets the agent needs but must never ship or log, such as signing keys.
+//!
+//! Each secret is a file in [`SECRETS_DIR`] readable only by the agent's user.
+//! An environment variable `AURELIA_SECRET_<NAME>` overrides the file, so
+//! secrets can also be injected by a service manager or container runtime.
+
+use crate::{AureliaError, AureliaResult};
+use std::path::{Path, PathBuf};
+
+pub const SECRETS_DIR: &str = "config/secrets";
+
+/// Prefix of the environment variables that override stored secrets.
+pub const SECRET_ENV_PREFIX: &str = "AURELIA_SECRET_";
+
+#[derive(Debug, Clone)]
+pub struct SecretStore {
+    dir: PathBuf,
+}
+
+impl Default for SecretStore {
+    fn default() -> Self {
+        Self::new(SECRETS_DIR)
+    }
+}
+
+impl SecretStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The secret called `name`, or `None` if it was never set.
+    pub fn get(&self, name: &str) -> AureliaResult<Option<String>> {
+        let path = self.path(name)?;
+        if let Ok(value) = std::env::var(format!("{}{}", SECRET_ENV_PREFIX, name.to_uppercase())) {
+            return Ok(Some(value));
+        }
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(std::fs::read_to_string(path)?.trim().to_string()))
+    }
+
+    /// Store a secret, readable only by the current user.
+    pub fn set(&self, name: &str, value: &str) -> AureliaResult<()> {
+        let path = self.path(name)?;
+        std::fs::create_dir_all(&self.dir)?;
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, value)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&self.dir, std::fs::Permissions::from_mode(0o700))?;
+            std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600))?;
+        }
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    fn path(&self, name: &str) -> AureliaResult<PathBuf> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !valid {
+            return Err(AureliaError::Config(format!(
+                "secret name '{}' must consist of a-z, 0-9 and _",
+                name
+            )));
+        }
+        Ok(self.dir.join(name))
+    }
+}
+
+/// Whether `presented` is `token`. The bytes are compared in constant time so
+/// that response times do not reveal how much of a guess was right.
+pub fn token_matches(token: &str, presented: Option<&str>) -> bool {
+    let Some(presented) = presented else {
+        return false;
+    };
+    token.len() == presented.len()
+        && token
+            .bytes()
+            .zip(presented.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
@@ -0,0 +1,220 @@
+//! Ed25519 signatures over kernel releases and deployment bundles.
+//!
+//! Every agent signs what it deploys with a key kept in its [`SecretStore`],
+//! generated on first use. A signed bundle carries the signer's public key as
+//! [`TRUSTED_KEY_PATH`] and the signature of every file in [`SIGNATURES_PATH`].
+//! Before starting a server, the deployer checks the SHA-256 of every uploaded
+//! file against the contents it signed; `kernel verify-bundle` runs the same
+//! check on a server against a key passed on the command line.
+//! The first signed deployment to a server pins its key as [`PINNED_KEY_PATH`];
+//! from then on the server only trusts that key, whatever key a bundle carries.
+//! Release binaries for self-update are signed the same way under the name
+//! [`RELEASE_BINARY_NAME`].
+//!
+//! Files are signed by name and SHA-256, so a valid signature cannot be moved
+//! to another file.
+
+use crate::bundle::RenderedFile;
+use crate::secrets::SecretStore;
+use crate::{AureliaError, AureliaResult};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Secret holding the hex-encoded private signing key.
+pub const SIGNING_KEY_SECRET: &str = "release_signing_key";
+/// Public key signatures are checked against, relative to the deployment directory.
+pub const TRUSTED_KEY_PATH: &str = "config/release_key.pub";
+/// Key pinned by the first signed deployment, relative to the deployment
+/// directory. Bundles never carry it.
+pub const PINNED_KEY_PATH: &str = "config/release_key.pinned";
+/// Signatures of a bundle's files, relative to the deployment directory.
+pub const SIGNATURES_PATH: &str = "signatures.json";
+/// Name under which kernel binaries are signed.
+pub const RELEASE_BINARY_NAME: &str = "kernel";
+
+fn message(name: &str, contents: &[u8]) -> Vec<u8> {
+    format!(
+        "aurelia-signature-v1\n{}\n{:x}",
+        name,
+        Sha256::digest(contents)
+    )
+    .into_bytes()
+}
+
+fn invalid(detail: String) -> AureliaError {
+    AureliaError::Deployment(detail)
+}
+
+fn decode<const N: usize>(what: &str, hex_value: &str) -> AureliaResult<[u8; N]> {
+    hex::decode(hex_value.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| invalid(format!("{} is not {} hex-encoded bytes", what, N)))
+}
+
+fn verifying_key(public_key: &str) -> AureliaResult<VerifyingKey> {
+    VerifyingKey::from_bytes(&decode("public key", public_key)?)
+        .map_err(|e| invalid(format!("invalid public key: {}", e)))
+}
+
+/// Check that `public_key` is a usable hex-encoded public key.
+pub fn check_public_key(public_key: &str) -> AureliaResult<()> {
+    verifying_key(public_key).map(|_| ())
+}
+
+/// Check `signature` of the file `name` against a hex-encoded public key.
+pub fn verify(public_key: &str, name: &str, contents: &[u8], signature: &str) -> AureliaResult<()> {
+    let key = verifying_key(public_key)?;
+    let signature = Signature::from_bytes(&decode("signature", signature)?);
+    key.verify(&message(name, contents), &signature)
+        .map_err(|_| invalid(format!("signature of {} does not match", name)))
+}
+
+/// The public key this agent trusts: the one pinned in `dir`/[`PINNED_KEY_PATH`],
+/// else the one in `dir`/[`TRUSTED_KEY_PATH`]. Fails when the two differ.
+pub fn trusted_key(dir: impl AsRef<Path>) -> AureliaResult<Option<String>> {
+    let read = |path: &str| -> AureliaResult<Option<String>> {
+        let path = dir.as_ref().join(path);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(std::fs::read_to_string(path)?.trim().to_string()))
+    };
+    match (read(PINNED_KEY_PATH)?, read(TRUSTED_KEY_PATH)?) {
+        (Some(pinned), Some(shipped)) if pinned != shipped => Err(invalid(format!(
+            "{} holds release key {}, not the pinned {}",
+            TRUSTED_KEY_PATH, shipped, pinned
+        ))),
+        (Some(pinned), _) => Ok(Some(pinned)),
+        (None, shipped) => Ok(shipped),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReleaseSigner {
+    key: SigningKey,
+}
+
+impl ReleaseSigner {
+    /// The signing key from `store`, generating and storing one if there is none.
+    pub fn load_or_create(store: &SecretStore) -> AureliaResult<Self> {
+        if let Some(secret) = store.get(SIGNING_KEY_SECRET)? {
+            let bytes = decode(SIGNING_KEY_SECRET, &secret)?;
+            return Ok(Self {
+                key: SigningKey::from_bytes(&bytes),
+            });
+        }
+        let key = SigningKey::generate(&mut rand::rngs::OsRng);
+        store.set(SIGNING_KEY_SECRET, &hex::encode(key.to_bytes()))?;
+        Ok(Self { key })
+    }
+
+    /// Hex-encoded public key, as written to [`TRUSTED_KEY_PATH`].
+    pub fn public_key(&self) -> String {
+        hex::encode(self.key.verifying_key().to_bytes())
+    }
+
+    /// Hex-encoded signature of the file `name`.
+    pub fn sign(&self, name: &str, contents: &[u8]) -> String {
+        hex::encode(self.key.sign(&message(name, contents)).to_bytes())
+    }
+
+    /// Sign every rendered file under its destination.
+    pub fn sign_files(&self, files: &[RenderedFile]) -> BundleSignatures {
+        BundleSignatures {
+            public_key: self.public_key(),
+            files: files
+                .iter()
+                .map(|file| {
+                    (
+                        file.destination.clone(),
+                        self.sign(&file.destination, &file.contents),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Contents of [`SIGNATURES_PATH`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleSignatures {
+    pub public_key: String,
+    /// Signature of each file, by path relative to the deployment directory
+    pub files: BTreeMap<String, String>,
+}
+
+impl BundleSignatures {
+    /// Verify the deployment in `dir` against the hex-encoded `trusted` key. Every
+    /// signed file must be present and unchanged, and the kernel must be signed.
+    pub fn verify_dir(dir: impl AsRef<Path>, trusted: &str) -> AureliaResult<Self> {
+        let dir = dir.as_ref();
+        let signatures: Self =
+            serde_json::from_str(&std::fs::read_to_string(dir.join(SIGNATURES_PATH))?)?;
+        if signatures.public_key != trusted.trim() {
+            return Err(invalid(
+                "bundle was signed with a key that is not trusted".to_string(),
+            ));
+        }
+        if !signatures.files.contains_key(RELEASE_BINARY_NAME) {
+            return Err(invalid(format!("{} is not signed", RELEASE_BINARY_NAME)));
+        }
+        for (name, signature) in &signatures.files {
+            let contents = std::fs::read(dir.join(name))
+                .map_err(|e| invalid(format!("failed to read signed file {}: {}", name, e)))?;
+            verify(trusted, name, &contents, signature)?;
+        }
+        Ok(signatures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_bundle_is_verified() {
+        let dir = std::env::temp_dir().join(format!("aurelia-signing-{}", uuid::Uuid::new_v4()));
+        let store = SecretStore::new(dir.join("secrets"));
+        let signer = ReleaseSigner::load_or_create(&store).unwrap();
+        let reloaded = ReleaseSigner::load_or_create(&store).unwrap();
+        assert_eq!(signer.public_key(), reloaded.public_key());
+
+        let files = crate::DeploymentBundle::new()
+            .template("binary", RELEASE_BINARY_NAME)
+            .template("{}", "config/strategy.json")
+            .signed_by(signer.clone())
+            .render()
+            .unwrap();
+        let deployed = dir.join("deployed");
+        for file in &files {
+            let path = deployed.join(&file.destination);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, &file.contents).unwrap();
+        }
+        let key = trusted_key(&deployed).unwrap().unwrap();
+        let signatures = BundleSignatures::verify_dir(&deployed, &key).unwrap();
+        assert_eq!(signatures.files.len(), 3, "kernel, config and trusted key");
+
+        // A signature does not carry over to another file name
+        let signature = signer.sign("kernel", b"binary");
+        assert!(verify(&signer.public_key(), "kernel", b"binary", &signature).is_ok());
+        assert!(verify(&signer.public_key(), "kernel.old", b"binary", &signature).is_err());
+
+        let other = ReleaseSigner::load_or_create(&SecretStore::new(dir.join("other"))).unwrap();
+        assert!(BundleSignatures::verify_dir(&deployed, &other.public_key()).is_err());
+
+        std::fs::write(deployed.join("config/strategy.json"), "{\"x\": 1}").unwrap();
+        assert!(BundleSignatures::verify_dir(&deployed, &key).is_err());
+
+        // Once pinned, a bundle carrying another key is not trusted
+        std::fs::write(deployed.join(PINNED_KEY_PATH), &key).unwrap();
+        assert_eq!(trusted_key(&deployed).unwrap(), Some(key.clone()));
+        std::fs::write(deployed.join(TRUSTED_KEY_PATH), other.public_key()).unwrap();
+        assert!(trusted_key(&deployed).is_err());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
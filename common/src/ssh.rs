@@ -0,0 +1,339 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+pub use tokio_util::sync::CancellationToken;
+
+/// Size of the chunks used when reading or writing over an SSH channel.
+const CHUNK_SIZE: usize = 32 * 1024;
+
+/// Session timeout while waiting on a remote command's output. A command that
+/// stays silent longer is only bounded by its deadline and cancellation.
+pub const READ_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Timeouts applied to SSH connections and remote commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SshTimeouts {
+    /// Establishing the TCP connection.
+    pub connect: Duration,
+    /// Any single blocking read or write on the session.
+    pub read: Duration,
+    /// Total run time of a remote command.
+    pub command: Duration,
+}
+
+impl Default for SshTimeouts {
+    fn default() -> Self {
+        Self {
+            connect: Duration::from_secs(30),
+            read: Duration::from_secs(60),
+            command: Duration::from_secs(300),
+        }
+    }
+}
+
+impl SshTimeouts {
+    /// The read timeout in the milliseconds expected by `ssh2::Session::set_timeout`.
+    pub fn session_timeout_ms(&self) -> u32 {
+        self.read.as_millis().min(u32::MAX as u128) as u32
+    }
+}
+
+fn cancelled() -> io::Error {
+    io::Error::other("operation cancelled")
+}
+
+/// `host:port` with IPv6 literals in brackets, as URLs and socket addresses expect.
+pub fn host_port(host: &str, port: u16) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+/// `value` as a single word of a POSIX shell command.
+pub fn shell_quote(value: &str) -> String {
+    let plain = |b: u8| b.is_ascii_alphanumeric() || b"@%+=:,./_-".contains(&b);
+    if !value.is_empty() && value.bytes().all(plain) {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// A remote path as a single shell word. A leading `~/` is left unquoted so the
+/// remote shell still expands it to the home directory.
+pub fn shell_quote_path(path: &str) -> String {
+    match path.strip_prefix("~/") {
+        Some(rest) => format!("~/{}", shell_quote(rest)),
+        None => shell_quote(path),
+    }
+}
+
+/// Open a TCP connection, trying every resolved address within `timeout`.
+pub fn connect_tcp(host: &str, port: u16, timeout: Duration) -> io::Result<TcpStream> {
+    // Bracketed IPv6 literals such as `[::1]` as written in configs
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let mut last_error = None;
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} did not resolve to any address", host),
+        )
+    }))
+}
+
+/// Run `read` with the blocking calls on `session` returning after
+/// [`READ_POLL_INTERVAL`], then restore the session's own timeout.
+#[cfg(feature = "ssh")]
+pub fn polling<T>(session: &ssh2::Session, read: impl FnOnce() -> T) -> T {
+    let timeout = session.timeout();
+    session.set_timeout(READ_POLL_INTERVAL.as_millis() as u32);
+    let result = read();
+    session.set_timeout(timeout);
+    result
+}
+
+/// Read a remote command's output until EOF, giving up once `timeout` has
+/// elapsed or `cancel` fires. Reads that time out are retried, see [`polling`].
+pub fn read_output<R: Read>(
+    reader: &mut R,
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> io::Result<String> {
+    let deadline = Instant::now() + timeout;
+    let mut output = Vec::new();
+    let mut buf = [0u8; CHUNK_SIZE];
+
+    loop {
+        check_deadline(deadline, timeout, cancel)?;
+        if !read_chunk(reader, &mut buf, &mut output)? {
+            break;
+        }
+    }
+
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+/// Read a remote command's stdout and stderr until both reach EOF, as
+/// [`read_output`] does. The two are read in turns, so a command filling the
+/// window of one while the other is being read does not stall.
+pub fn read_outputs<O: Read, E: Read>(
+    stdout: &mut O,
+    stderr: &mut E,
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> io::Result<(String, String)> {
+    let deadline = Instant::now() + timeout;
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let (mut out_open, mut err_open) = (true, true);
+    let mut buf = [0u8; CHUNK_SIZE];
+
+    while out_open || err_open {
+        check_deadline(deadline, timeout, cancel)?;
+        if out_open {
+            out_open = read_chunk(stdout, &mut buf, &mut out)?;
+        }
+        if err_open {
+            err_open = read_chunk(stderr, &mut buf, &mut err)?;
+        }
+    }
+
+    Ok((
+        String::from_utf8_lossy(&out).into_owned(),
+        String::from_utf8_lossy(&err).into_owned(),
+    ))
+}
+
+fn check_deadline(
+    deadline: Instant,
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> io::Result<()> {
+    if cancel.is_cancelled() {
+        return Err(cancelled());
+    }
+    if Instant::now() >= deadline {
+        return Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("remote command did not finish within {:?}", timeout),
+        ));
+    }
+    Ok(())
+}
+
+/// Append one read of `reader` to `output`; false at EOF. A read that times out
+/// appends nothing.
+fn read_chunk<R: Read>(reader: &mut R, buf: &mut [u8], output: &mut Vec<u8>) -> io::Result<bool> {
+    match reader.read(buf) {
+        Ok(0) => Ok(false),
+        Ok(n) => {
+            output.extend_from_slice(&buf[..n]);
+            Ok(true)
+        }
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::Interrupted | io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+            ) =>
+        {
+            Ok(true)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Write `data` in chunks, stopping early if `cancel` fires.
+pub fn write_all_cancellable<W: Write>(
+    writer: &mut W,
+    data: &[u8],
+    cancel: &CancellationToken,
+) -> io::Result<()> {
+    for chunk in data.chunks(CHUNK_SIZE) {
+        if cancel.is_cancelled() {
+            return Err(cancelled());
+        }
+        writer.write_all(chunk)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_output_honours_cancellation() {
+        let cancel = CancellationToken::new();
+        let mut reader = Cursor::new(b"hello\n".to_vec());
+        let output = read_output(&mut reader, Duration::from_secs(5), &cancel).unwrap();
+        assert_eq!(output, "hello\n");
+
+        cancel.cancel();
+        let mut reader = Cursor::new(b"hello\n".to_vec());
+        assert!(read_output(&mut reader, Duration::from_secs(5), &cancel).is_err());
+
+        let mut sink = Vec::new();
+        assert!(write_all_cancellable(&mut sink, b"data", &cancel).is_err());
+        assert!(sink.is_empty());
+    }
+
+    /// Silent for a number of reads before producing its output
+    struct Silent {
+        polls: usize,
+        output: Cursor<Vec<u8>>,
+    }
+
+    impl Read for Silent {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.polls > 0 {
+                self.polls -= 1;
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "timed out"));
+            }
+            self.output.read(buf)
+        }
+    }
+
+    #[test]
+    fn test_read_output_waits_out_silent_commands() {
+        let cancel = CancellationToken::new();
+        let mut reader = Silent {
+            polls: 3,
+            output: Cursor::new(b"done\n".to_vec()),
+        };
+        let output = read_output(&mut reader, Duration::from_secs(5), &cancel).unwrap();
+        assert_eq!(output, "done\n");
+
+        let mut reader = Silent {
+            polls: usize::MAX,
+            output: Cursor::new(Vec::new()),
+        };
+        let error = read_output(&mut reader, Duration::from_millis(20), &cancel).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+    }
+
+    /// Silent until `until` is set, as a command blocked on its other stream
+    struct Blocked<'a> {
+        until: &'a std::cell::Cell<bool>,
+        output: Cursor<Vec<u8>>,
+    }
+
+    impl Read for Blocked<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if !self.until.get() {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "timed out"));
+            }
+            self.output.read(buf)
+        }
+    }
+
+    /// Sets `drained` once read to the end
+    struct Draining<'a> {
+        drained: &'a std::cell::Cell<bool>,
+        output: Cursor<Vec<u8>>,
+    }
+
+    impl Read for Draining<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.output.read(buf)?;
+            if n == 0 {
+                self.drained.set(true);
+            }
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_stdout_and_stderr_are_read_in_turns() {
+        let cancel = CancellationToken::new();
+        // stdout only goes on once stderr was read, as when the remote command
+        // blocks on a full stderr window
+        let drained = std::cell::Cell::new(false);
+        let mut stdout = Blocked {
+            until: &drained,
+            output: Cursor::new(b"out\n".to_vec()),
+        };
+        let mut stderr = Draining {
+            drained: &drained,
+            output: Cursor::new(b"err\n".to_vec()),
+        };
+        let (out, err) =
+            read_outputs(&mut stdout, &mut stderr, Duration::from_secs(5), &cancel).unwrap();
+        assert_eq!((out.as_str(), err.as_str()), ("out\n", "err\n"));
+
+        let mut stdout = Cursor::new(Vec::new());
+        let mut stderr = Silent {
+            polls: usize::MAX,
+            output: Cursor::new(Vec::new()),
+        };
+        let error =
+            read_outputs(&mut stdout, &mut stderr, Duration::from_millis(20), &cancel).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("/opt/aurelia"), "/opt/aurelia");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("$(reboot)"), "'$(reboot)'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote_path("~/aurelia dir"), "~/'aurelia dir'");
+        assert_eq!(shell_quote_path("~user/x"), "'~user/x'");
+    }
+
+    #[test]
+    fn test_host_port_brackets_ipv6_literals() {
+        assert_eq!(host_port("10.0.0.1", 22), "10.0.0.1:22");
+        assert_eq!(host_port("example.com", 8080), "example.com:8080");
+        assert_eq!(host_port("2001:db8::1", 22), "[2001:db8::1]:22");
+        assert_eq!(host_port("[2001:db8::1]", 22), "[2001:db8::1]:22");
+    }
+}
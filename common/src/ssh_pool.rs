@@ -0,0 +1,361 @@
+//! Pooled SSH sessions.
+//!
+//! Opening a session costs a TCP handshake, a key exchange and authentication.
+//! [`SshConnectionManager`] keeps one authenticated session per server and
+//! leases it to one caller at a time, who sets its own timeouts on it; a caller
+//! finding the session leased connects on its own. The lease returns the session
+//! to the pool when dropped. A pooled session is probed before it is reused and
+//! replaced by a fresh connection when the probe fails.
+//!
+//! The pool also keeps a [`CircuitBreaker`] per server, so that callers stop
+//! connecting to a host that keeps failing and probe it again later.
+
+use crate::circuit_breaker::CircuitBreaker;
+use crate::ssh::host_port;
+use crate::EventBus;
+use ssh2::Session;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Interval between keepalives on pooled sessions
+const DEFAULT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Sessions unused for this long are closed instead of reused
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
+struct PooledSession {
+    session: Session,
+    last_used: Instant,
+    /// ID of the [`SessionLease`] holding it
+    leased: Option<u64>,
+}
+
+/// IDs of leases, so a lease only ever releases the session it was given
+static NEXT_LEASE: AtomicU64 = AtomicU64::new(1);
+
+/// A session used by one caller until dropped, when a pooled session goes back
+/// to its pool.
+pub struct SessionLease {
+    session: Session,
+    /// Pool, key and lease ID to return the session under; `None` for one
+    /// outside the pool
+    pool: Option<(SshConnectionManager, String, u64)>,
+}
+
+impl SessionLease {
+    /// A session that is not pooled, e.g. because pooling is disabled.
+    pub fn unpooled(session: Session) -> Self {
+        Self {
+            session,
+            pool: None,
+        }
+    }
+}
+
+impl Deref for SessionLease {
+    type Target = Session;
+
+    fn deref(&self) -> &Session {
+        &self.session
+    }
+}
+
+impl Drop for SessionLease {
+    fn drop(&mut self) {
+        if let Some((pool, key, id)) = &self.pool {
+            pool.release(key, *id);
+        }
+    }
+}
+
+/// Authenticated SSH sessions keyed by `user@host:port`, shared by clones.
+#[derive(Clone)]
+pub struct SshConnectionManager {
+    sessions: Arc<Mutex<HashMap<String, PooledSession>>>,
+    breakers: Arc<Mutex<HashMap<String, CircuitBreaker>>>,
+    keepalive_interval: Duration,
+    idle_timeout: Duration,
+    events: Option<EventBus>,
+}
+
+impl Default for SshConnectionManager {
+    fn default() -> Self {
+        Self::new(DEFAULT_KEEPALIVE_INTERVAL)
+    }
+}
+
+impl SshConnectionManager {
+    pub fn new(keepalive_interval: Duration) -> Self {
+        Self {
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            breakers: Arc::new(Mutex::new(HashMap::new())),
+            keepalive_interval: keepalive_interval.max(Duration::from_secs(1)),
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            events: None,
+        }
+    }
+
+    /// Publish `CircuitOpen` and `CircuitClosed` for servers' circuit breakers.
+    /// Set before the pool is cloned.
+    pub fn with_event_bus(mut self, bus: EventBus) -> Self {
+        self.events = Some(bus);
+        self
+    }
+
+    /// Close sessions that have not been used for `idle_timeout`.
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// How often pooled sessions should be sent keepalives, see
+    /// [`SshConnectionManager::send_keepalives`].
+    pub fn keepalive_interval(&self) -> Duration {
+        self.keepalive_interval
+    }
+
+    /// The key a server's session is pooled under.
+    pub fn key(user: &str, host: &str, port: u16) -> String {
+        format!("{}@{}", user, host_port(host, port))
+    }
+
+    /// A lease on a healthy pooled session for `key`, or on one opened with
+    /// `connect` and added to the pool. Blocking calls on it time out after
+    /// `timeout_ms`.
+    pub fn get<E>(
+        &self,
+        key: &str,
+        timeout_ms: u32,
+        connect: impl FnOnce() -> Result<Session, E>,
+    ) -> Result<SessionLease, E> {
+        if let Some(lease) = self.checkout(key, timeout_ms) {
+            return Ok(lease);
+        }
+        let session = connect()?;
+        session.set_timeout(timeout_ms);
+        Ok(self.insert(key, session))
+    }
+
+    /// A lease on the pooled session for `key` if there is one, no one else
+    /// holds it and it still answers. Blocking calls on it time out after
+    /// `timeout_ms`.
+    pub fn checkout(&self, key: &str, timeout_ms: u32) -> Option<SessionLease> {
+        let (session, id) = {
+            let mut sessions = self.lock();
+            let pooled = sessions.get_mut(key)?;
+            if pooled.leased.is_some() {
+                debug!("SSH session to {} is in use", key);
+                return None;
+            }
+            if pooled.last_used.elapsed() > self.idle_timeout {
+                debug!("Closing idle SSH session to {}", key);
+                sessions.remove(key);
+                return None;
+            }
+            let id = NEXT_LEASE.fetch_add(1, Ordering::Relaxed);
+            pooled.leased = Some(id);
+            pooled.last_used = Instant::now();
+            (pooled.session.clone(), id)
+        };
+
+        // Probe outside the lock, a dead connection may take a read timeout to notice
+        if probe(&session) {
+            debug!("Reusing SSH session to {}", key);
+            session.set_timeout(timeout_ms);
+            Some(self.lease(key, session, id))
+        } else {
+            warn!("Pooled SSH session to {} is dead, reconnecting", key);
+            self.invalidate(key);
+            None
+        }
+    }
+
+    /// Pool an authenticated session, replacing any previous one for `key`
+    /// unless that is leased, and lease it to the caller.
+    pub fn insert(&self, key: &str, session: Session) -> SessionLease {
+        session.set_keepalive(false, self.keepalive_interval.as_secs() as u32);
+        let mut sessions = self.lock();
+        if sessions
+            .get(key)
+            .is_some_and(|pooled| pooled.leased.is_some())
+        {
+            return SessionLease::unpooled(session);
+        }
+        let id = NEXT_LEASE.fetch_add(1, Ordering::Relaxed);
+        sessions.insert(
+            key.to_string(),
+            PooledSession {
+                session: session.clone(),
+                last_used: Instant::now(),
+                leased: Some(id),
+            },
+        );
+        drop(sessions);
+        self.lease(key, session, id)
+    }
+
+    fn lease(&self, key: &str, session: Session, id: u64) -> SessionLease {
+        SessionLease {
+            session,
+            pool: Some((self.clone(), key.to_string(), id)),
+        }
+    }
+
+    /// Make the session for `key` available again when lease `id` is dropped,
+    /// unless it was replaced in the meantime.
+    fn release(&self, key: &str, id: u64) {
+        if let Some(pooled) = self.lock().get_mut(key) {
+            if pooled.leased == Some(id) {
+                pooled.leased = None;
+                pooled.last_used = Instant::now();
+            }
+        }
+    }
+
+    /// The circuit breaker for the server pooled under `key`, shared by clones.
+    pub fn breaker(&self, key: &str) -> CircuitBreaker {
+        let mut breakers = self.breakers.lock().expect("ssh pool lock poisoned");
+        breakers
+            .entry(key.to_string())
+            .or_insert_with(|| {
+                let breaker = CircuitBreaker::new(format!("ssh:{}", key));
+                match &self.events {
+                    Some(bus) => breaker.with_event_bus(bus.clone()),
+                    None => breaker,
+                }
+            })
+            .clone()
+    }
+
+    /// Drop the pooled session for `key`, e.g. after a transport error on it.
+    pub fn invalidate(&self, key: &str) {
+        self.lock().remove(key);
+    }
+
+    /// Send due keepalives on every pooled session no one holds, closing dead
+    /// and idle ones. Returns the number of sessions left in the pool.
+    pub fn send_keepalives(&self) -> usize {
+        let idle: Vec<(String, Session)> = {
+            let mut sessions = self.lock();
+            sessions.retain(|key, pooled| {
+                let expired =
+                    pooled.leased.is_none() && pooled.last_used.elapsed() > self.idle_timeout;
+                if expired {
+                    debug!("Closing idle SSH session to {}", key);
+                }
+                !expired
+            });
+            sessions
+                .iter()
+                .filter(|(_, pooled)| pooled.leased.is_none())
+                .map(|(key, pooled)| (key.clone(), pooled.session.clone()))
+                .collect()
+        };
+
+        // Sent outside the lock, so a slow server does not hold up checkouts
+        for (key, session) in idle {
+            if let Err(e) = session.keepalive_send() {
+                warn!("SSH keepalive to {} failed, dropping session: {}", key, e);
+                let mut sessions = self.lock();
+                if sessions
+                    .get(&key)
+                    .is_some_and(|pooled| pooled.leased.is_none())
+                {
+                    sessions.remove(&key);
+                }
+            }
+        }
+        self.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, PooledSession>> {
+        self.sessions.lock().expect("ssh pool lock poisoned")
+    }
+}
+
+/// Whether a session still answers: opening and closing a channel takes one
+/// round trip, much less than a new handshake and authentication.
+fn probe(session: &Session) -> bool {
+    if !session.authenticated() {
+        return false;
+    }
+    match session.channel_session() {
+        Ok(mut channel) => {
+            let _ = channel.close();
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failed_connect_leaves_pool_empty() {
+        let pool = SshConnectionManager::default();
+        let key = SshConnectionManager::key("ubuntu", "2001:db8::1", 22);
+        assert_eq!(key, "ubuntu@[2001:db8::1]:22");
+
+        let result = pool.get(&key, 1000, || Err::<Session, _>("unreachable"));
+        assert_eq!(result.err(), Some("unreachable"));
+        assert!(pool.is_empty());
+        assert!(pool.checkout(&key, 1000).is_none());
+    }
+
+    #[test]
+    fn test_unauthenticated_sessions_are_not_reused() {
+        let pool = SshConnectionManager::default();
+        drop(pool.insert("root@10.0.0.1:22", Session::new().unwrap()));
+        assert_eq!(pool.len(), 1);
+
+        // Never connected, so the probe fails and the session is dropped
+        assert!(pool.checkout("root@10.0.0.1:22", 1000).is_none());
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn test_sessions_are_leased_to_one_caller_at_a_time() {
+        let pool = SshConnectionManager::default();
+        let lease = pool.insert("root@10.0.0.1:22", Session::new().unwrap());
+
+        // Held, so neither handed out nor replaced, nor sent keepalives
+        assert!(pool.checkout("root@10.0.0.1:22", 1000).is_none());
+        let other = pool.insert("root@10.0.0.1:22", Session::new().unwrap());
+        assert!(other.pool.is_none());
+        assert_eq!(pool.send_keepalives(), 1);
+
+        // Back in the pool once dropped, where the probe finds it dead
+        drop(lease);
+        assert!(pool.checkout("root@10.0.0.1:22", 1000).is_none());
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn test_breakers_are_per_server_and_shared_by_clones() {
+        let pool = SshConnectionManager::default();
+        let clone = pool.clone();
+        for _ in 0..crate::circuit_breaker::DEFAULT_FAILURE_THRESHOLD {
+            pool.breaker("root@10.0.0.1:22").record_failure();
+        }
+        assert!(clone.breaker("root@10.0.0.1:22").is_open());
+        assert!(!clone.breaker("root@10.0.0.2:22").is_open());
+        assert_eq!(
+            pool.breaker("root@10.0.0.1:22").component(),
+            "ssh:root@10.0.0.1:22"
+        );
+    }
+}
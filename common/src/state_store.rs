@@ -0,0 +1,161 @@
+use crate::AureliaResult;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The agent's persistent state. Each agent keeps its own; deployments never ship it.
+pub const STATE_PATH: &str = "config/state.json";
+
+/// Funds assumed when `state.json` does not say otherwise
+pub const DEFAULT_FUNDS: f64 = 1000.0;
+
+/// Everything the agent keeps across restarts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AgentState {
+    pub funds: f64,
+    /// Share of the funds each strategy trades with, as last set by the allocator;
+    /// strategies not listed use their configured allocation
+    pub allocations: BTreeMap<String, f64>,
+    pub last_update: Option<DateTime<Utc>>,
+}
+
+impl Default for AgentState {
+    fn default() -> Self {
+        Self {
+            funds: DEFAULT_FUNDS,
+            allocations: BTreeMap::new(),
+            last_update: None,
+        }
+    }
+}
+
+/// Shared handle to the agent state.
+///
+/// Changes are kept in memory and written out by [`StateStore::save`], which the
+/// kernel calls on a timer and at shutdown. Writes go to a temporary file that is
+/// then renamed over `state.json`, so a crash never leaves a truncated file.
+#[derive(Debug, Clone)]
+pub struct StateStore {
+    path: Option<PathBuf>,
+    state: Arc<Mutex<AgentState>>,
+    /// Set by every change, cleared by a save
+    dirty: Arc<AtomicBool>,
+}
+
+impl StateStore {
+    /// Load the state at `path`, starting from the default if there is none yet.
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        let state = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            AgentState::default()
+        };
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            state: Arc::new(Mutex::new(state)),
+            dirty: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// A store that never touches the disk.
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            state: Arc::new(Mutex::new(AgentState::default())),
+            dirty: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, AgentState> {
+        self.state.lock().expect("state store lock poisoned")
+    }
+
+    fn update(&self, change: impl FnOnce(&mut AgentState)) {
+        let mut state = self.state();
+        change(&mut state);
+        state.last_update = Some(Utc::now());
+        self.dirty.store(true, Ordering::Release);
+    }
+
+    pub fn snapshot(&self) -> AgentState {
+        self.state().clone()
+    }
+
+    pub fn funds(&self) -> f64 {
+        self.state().funds
+    }
+
+    pub fn set_funds(&self, funds: f64) {
+        self.update(|state| state.funds = funds);
+    }
+
+    pub fn allocation(&self, strategy_id: &str) -> Option<f64> {
+        self.state().allocations.get(strategy_id).copied()
+    }
+
+    pub fn set_allocations(&self, allocations: BTreeMap<String, f64>) {
+        self.update(|state| state.allocations = allocations);
+    }
+
+    /// Write the state out if it changed since the last save.
+    pub fn save(&self) -> AureliaResult<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !self.dirty.swap(false, Ordering::AcqRel) {
+            return Ok(());
+        }
+
+        let result = write_atomically(path, &self.snapshot());
+        if result.is_err() {
+            // Try again on the next save
+            self.dirty.store(true, Ordering::Release);
+        }
+        result
+    }
+}
+
+fn write_atomically(path: &Path, state: &AgentState) -> AureliaResult<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_string_pretty(state)?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_round_trips_through_file() {
+        let dir = std::env::temp_dir().join(format!("aurelia-state-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("state.json");
+        // Files written by older versions also held positions and checkpoints
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            &path,
+            r#"{"funds": 1000.0, "positions": {}, "checkpoints": {}, "last_update": null}"#,
+        )
+        .unwrap();
+
+        let store = StateStore::load(&path).unwrap();
+        assert_eq!(store.funds(), 1000.0);
+        store.set_funds(1250.5);
+        store.set_allocations(BTreeMap::from([("momentum".to_string(), 0.5)]));
+        store.save().unwrap();
+        assert!(!dir.join("state.json.tmp").exists());
+
+        let reloaded = StateStore::load(&path).unwrap();
+        assert_eq!(reloaded.snapshot(), store.snapshot());
+        assert_eq!(reloaded.allocation("momentum"), Some(0.5));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,256 @@
+//! The strategies the agent runs side by side.
+//!
+//! Each strategy trades its own symbols with its own share of the funds, and
+//! tags its decisions with its ID through [`crate::EventMeta::strategy_id`], so
+//! orders, fills and reports can be attributed to it.
+
+use crate::strategy_config::StrategyConfig;
+use crate::{AureliaError, AureliaResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+pub const STRATEGIES_PATH: &str = "config/strategies.json";
+
+/// Longest strategy ID; IDs end up in logs, metrics labels and parameter names.
+pub const MAX_STRATEGY_ID_LEN: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StrategyKind {
+    /// Fast/slow moving average crossovers, held back by bearish sentiment
+    Momentum,
+    /// Fades closes that stray far from their rolling mean
+    MeanReversion,
+    /// Follows the news sentiment score
+    Sentiment,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StrategySpec {
+    /// Carried by every decision of the strategy
+    pub id: String,
+    pub kind: StrategyKind,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Share of the agent's funds the strategy trades with, between 0 and 1
+    pub allocation: f64,
+    /// Symbols the strategy trades; empty means every symbol
+    #[serde(default)]
+    pub symbols: Vec<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl StrategySpec {
+    pub fn trades(&self, symbol: &str) -> bool {
+        self.symbols.is_empty() || self.symbols.iter().any(|s| s == symbol)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StrategySet {
+    /// Share of a strategy's allocation committed by each of its orders
+    pub order_fraction: f64,
+    pub strategies: Vec<StrategySpec>,
+}
+
+impl Default for StrategySet {
+    fn default() -> Self {
+        let spec = |id: &str, kind, allocation| StrategySpec {
+            id: id.to_string(),
+            kind,
+            enabled: true,
+            allocation,
+            symbols: Vec::new(),
+        };
+        Self {
+            order_fraction: 0.1,
+            strategies: vec![
+                spec("momentum", StrategyKind::Momentum, 0.4),
+                spec("mean_reversion", StrategyKind::MeanReversion, 0.3),
+                spec("sentiment", StrategyKind::Sentiment, 0.3),
+            ],
+        }
+    }
+}
+
+impl StrategySet {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let set: Self = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        set.validate()?;
+        Ok(set)
+    }
+
+    /// Load the set at `path`, or without it the single strategy described by
+    /// the `strategy.json` at `legacy`, which agents ran before they could run
+    /// several. With neither file the default set runs.
+    pub fn load_or_legacy(path: impl AsRef<Path>, legacy: impl AsRef<Path>) -> AureliaResult<Self> {
+        let (path, legacy) = (path.as_ref(), legacy.as_ref());
+        if path.exists() || !legacy.exists() {
+            return Self::load(path);
+        }
+        Ok(Self::from(&StrategyConfig::load(legacy)?))
+    }
+
+    /// IDs must be unique and usable in parameter names, and the enabled
+    /// strategies may not be allocated more than all of the funds.
+    pub fn validate(&self) -> AureliaResult<()> {
+        if !(self.order_fraction > 0.0 && self.order_fraction <= 1.0) {
+            return Err(AureliaError::Config(format!(
+                "order_fraction {} is outside (0, 1]",
+                self.order_fraction
+            )));
+        }
+        let mut ids = HashSet::new();
+        for spec in &self.strategies {
+            let valid_id = !spec.id.is_empty()
+                && spec.id.len() <= MAX_STRATEGY_ID_LEN
+                && spec
+                    .id
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+            if !valid_id {
+                return Err(AureliaError::Config(format!(
+                    "strategy ID '{}' must be 1 to {} characters of a-z, 0-9 and _",
+                    spec.id, MAX_STRATEGY_ID_LEN
+                )));
+            }
+            if !ids.insert(spec.id.as_str()) {
+                return Err(AureliaError::Config(format!(
+                    "strategy ID '{}' is used twice",
+                    spec.id
+                )));
+            }
+            if !(0.0..=1.0).contains(&spec.allocation) {
+                return Err(AureliaError::Config(format!(
+                    "allocation {} of strategy '{}' is outside [0, 1]",
+                    spec.allocation, spec.id
+                )));
+            }
+        }
+        let total: f64 = self.enabled().map(|spec| spec.allocation).sum();
+        // Allow for rounding in hand-written fractions such as 0.33
+        if total > 1.0 + 1e-6 {
+            return Err(AureliaError::Config(format!(
+                "strategies are allocated {} of the funds",
+                total
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn enabled(&self) -> impl Iterator<Item = &StrategySpec> {
+        self.strategies.iter().filter(|spec| spec.enabled)
+    }
+
+    /// The enabled strategy with this ID.
+    pub fn get(&self, id: &str) -> Option<&StrategySpec> {
+        self.enabled().find(|spec| spec.id == id)
+    }
+}
+
+impl From<&StrategyConfig> for StrategySet {
+    /// All of the funds go to the one strategy, trading only the configured symbol
+    fn from(config: &StrategyConfig) -> Self {
+        let id = match config.strategy_type {
+            StrategyKind::Momentum => "momentum",
+            StrategyKind::MeanReversion => "mean_reversion",
+            StrategyKind::Sentiment => "sentiment",
+        };
+        Self {
+            strategies: vec![StrategySpec {
+                id: id.to_string(),
+                kind: config.strategy_type,
+                enabled: true,
+                allocation: 1.0,
+                symbols: vec![config.symbol.clone()],
+            }],
+            ..Self::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strategy_set_is_validated() {
+        let set = StrategySet::default();
+        assert!(set.validate().is_ok());
+        assert!(set.get("momentum").unwrap().trades("ETHUSDT"));
+
+        let parsed: StrategySet = serde_json::from_str(
+            r#"{"strategies": [
+                {"id": "btc_trend", "kind": "momentum", "allocation": 0.7, "symbols": ["BTCUSDT"]},
+                {"id": "fade", "kind": "mean_reversion", "allocation": 0.6, "enabled": false}
+            ]}"#,
+        )
+        .unwrap();
+        assert!(
+            parsed.validate().is_ok(),
+            "disabled strategies hold no funds"
+        );
+        assert_eq!(parsed.order_fraction, 0.1);
+        assert!(!parsed.get("btc_trend").unwrap().trades("ETHUSDT"));
+        assert!(parsed.get("fade").is_none());
+
+        let mut over = parsed.clone();
+        over.strategies[1].enabled = true;
+        assert!(over.validate().is_err());
+
+        let mut duplicate = parsed.clone();
+        duplicate.strategies[1].id = "btc_trend".to_string();
+        assert!(duplicate.validate().is_err());
+
+        let mut bad_id = parsed;
+        bad_id.strategies[0].id = "BTC trend".to_string();
+        assert!(bad_id.validate().is_err());
+    }
+
+    #[test]
+    fn test_strategy_json_stands_in_for_a_missing_strategy_set() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, legacy) = (
+            dir.path().join("strategies.json"),
+            dir.path().join("strategy.json"),
+        );
+        assert_eq!(
+            StrategySet::load_or_legacy(&path, &legacy).unwrap(),
+            StrategySet::default()
+        );
+
+        std::fs::write(
+            &legacy,
+            r#"{"version": 1, "strategy_type": "mean_reversion", "symbol": "ETHUSDT",
+                "interval": "1h", "lookback_periods": 20, "threshold": 0.02,
+                "cpu_usage_threshold": 75.0, "price_drop_threshold": 0.05}"#,
+        )
+        .unwrap();
+        let set = StrategySet::load_or_legacy(&path, &legacy).unwrap();
+        assert!(set.validate().is_ok());
+        let [spec] = set.strategies.as_slice() else {
+            panic!("expected a single strategy");
+        };
+        assert_eq!(spec.kind, StrategyKind::MeanReversion);
+        assert_eq!(spec.allocation, 1.0);
+        assert!(spec.trades("ETHUSDT") && !spec.trades("BTCUSDT"));
+
+        // A strategy set, once written, takes precedence
+        std::fs::write(
+            &path,
+            r#"{"strategies": [{"id": "trend", "kind": "momentum", "allocation": 0.5}]}"#,
+        )
+        .unwrap();
+        let set = StrategySet::load_or_legacy(&path, &legacy).unwrap();
+        assert_eq!(set.strategies[0].id, "trend");
+    }
+}
@@ -0,0 +1,273 @@
+//! The versioned schema of `config/strategy.json`.
+//!
+//! Every generation of the agent that wrote this file is still out there, so a
+//! document is upgraded one version at a time through [`MIGRATIONS`] before it
+//! is parsed. Loading rewrites the file in the current version; a document
+//! from a newer agent than this one is refused rather than misread.
+
+use crate::strategies::StrategyKind;
+use crate::{AureliaError, AureliaResult};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::path::Path;
+use tracing::info;
+
+pub const STRATEGY_CONFIG_PATH: &str = "config/strategy.json";
+
+/// Upgrades a document of version `n` to version `n + 1`
+type Migration = fn(Map<String, Value>) -> AureliaResult<Map<String, Value>>;
+
+/// `MIGRATIONS[n]` upgrades version `n`; append one for every schema change.
+const MIGRATIONS: [Migration; 1] = [from_unversioned];
+
+/// Version written by this agent
+pub const STRATEGY_CONFIG_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Kline intervals the exchange serves
+const INTERVALS: [&str; 15] = [
+    "1m", "3m", "5m", "15m", "30m", "1h", "2h", "4h", "6h", "8h", "12h", "1d", "3d", "1w", "1M",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StrategyConfig {
+    pub version: u32,
+    pub strategy_type: StrategyKind,
+    pub symbol: String,
+    /// Kline interval, e.g. `1h`
+    pub interval: String,
+    /// Candles the strategy looks back over
+    pub lookback_periods: u32,
+    /// Relative move that triggers a signal, between 0 and 1
+    pub threshold: f64,
+    /// Host CPU usage in percent above which the agent stops taking on work
+    pub cpu_usage_threshold: f64,
+    /// Relative price drop treated as a crash, between 0 and 1
+    pub price_drop_threshold: f64,
+}
+
+impl Default for StrategyConfig {
+    fn default() -> Self {
+        Self {
+            version: STRATEGY_CONFIG_VERSION,
+            strategy_type: StrategyKind::Momentum,
+            symbol: "BTCUSDT".to_string(),
+            interval: "1h".to_string(),
+            lookback_periods: 20,
+            threshold: 0.02,
+            cpu_usage_threshold: 75.0,
+            price_drop_threshold: 0.05,
+        }
+    }
+}
+
+impl StrategyConfig {
+    /// Load the config, migrating it in place when an older agent wrote it.
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        let (config, from) = Self::parse(&content)
+            .map_err(|e| AureliaError::Config(format!("{}: {}", path.display(), e)))?;
+        if from != STRATEGY_CONFIG_VERSION {
+            info!(
+                "Migrated {} from version {} to {}",
+                path.display(),
+                from,
+                STRATEGY_CONFIG_VERSION
+            );
+            config.save(path)?;
+        }
+        Ok(config)
+    }
+
+    /// Parse and validate a document of any supported version.
+    pub fn from_json(content: &str) -> AureliaResult<Self> {
+        Ok(Self::parse(content)?.0)
+    }
+
+    /// The config and the version the document was written in.
+    fn parse(content: &str) -> AureliaResult<(Self, u32)> {
+        let value: Value = serde_json::from_str(content)?;
+        let (migrated, from) = migrate(value)?;
+        let config: Self = serde_json::from_value(Value::Object(migrated)).map_err(|e| {
+            AureliaError::Config(format!(
+                "does not match version {}: {}",
+                STRATEGY_CONFIG_VERSION, e
+            ))
+        })?;
+        config.validate()?;
+        Ok((config, from))
+    }
+
+    pub fn validate(&self) -> AureliaResult<()> {
+        let invalid = |message: String| Err(AureliaError::Config(message));
+        if self.symbol.is_empty()
+            || !self
+                .symbol
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        {
+            return invalid(format!(
+                "symbol '{}' must be upper-case letters and digits, e.g. BTCUSDT",
+                self.symbol
+            ));
+        }
+        if !INTERVALS.contains(&self.interval.as_str()) {
+            return invalid(format!(
+                "interval '{}' is not one of {}",
+                self.interval,
+                INTERVALS.join(", ")
+            ));
+        }
+        if self.lookback_periods < 2 {
+            return invalid(format!(
+                "lookback_periods {} must be at least 2",
+                self.lookback_periods
+            ));
+        }
+        if !(self.threshold > 0.0 && self.threshold < 1.0) {
+            return invalid(format!("threshold {} is outside (0, 1)", self.threshold));
+        }
+        if !(self.cpu_usage_threshold > 0.0 && self.cpu_usage_threshold <= 100.0) {
+            return invalid(format!(
+                "cpu_usage_threshold {} is outside (0, 100]",
+                self.cpu_usage_threshold
+            ));
+        }
+        if !(0.0..1.0).contains(&self.price_drop_threshold) {
+            return invalid(format!(
+                "price_drop_threshold {} is outside [0, 1)",
+                self.price_drop_threshold
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> AureliaResult<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Upgrade a document to [`STRATEGY_CONFIG_VERSION`], returning it with the
+/// version it was written in.
+fn migrate(value: Value) -> AureliaResult<(Map<String, Value>, u32)> {
+    let Value::Object(mut document) = value else {
+        return Err(AureliaError::Config(
+            "expected a JSON object at the top level".to_string(),
+        ));
+    };
+    let from = match document.get("version") {
+        None => 0,
+        Some(version) => version
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| {
+                AureliaError::Config(format!("version {} is not a whole number", version))
+            })?,
+    };
+    if from > STRATEGY_CONFIG_VERSION {
+        return Err(AureliaError::Config(format!(
+            "version {} was written by a newer agent, this one reads up to version {}",
+            from, STRATEGY_CONFIG_VERSION
+        )));
+    }
+
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(from as usize) {
+        document = migration(document).map_err(|e| {
+            AureliaError::Config(format!("migrating from version {} failed: {}", version, e))
+        })?;
+        document.insert("version".to_string(), Value::from(version as u32 + 1));
+    }
+    Ok((document, from))
+}
+
+/// Version 0 files carry no version and only some of the fields: the first
+/// agents wrote the two guard thresholds, the deployment templates the
+/// strategy settings. Missing fields take the version 1 defaults.
+fn from_unversioned(mut document: Map<String, Value>) -> AureliaResult<Map<String, Value>> {
+    let defaults = [
+        ("strategy_type", json!("momentum")),
+        ("symbol", json!("BTCUSDT")),
+        ("interval", json!("1h")),
+        ("lookback_periods", json!(20)),
+        ("threshold", json!(0.02)),
+        ("cpu_usage_threshold", json!(75.0)),
+        ("price_drop_threshold", json!(0.05)),
+    ];
+    for (field, value) in defaults {
+        document.entry(field).or_insert(value);
+    }
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_previous_generations_are_migrated() {
+        let guards = StrategyConfig::from_json(
+            r#"{"cpu_usage_threshold": 60.0, "price_drop_threshold": 0.1}"#,
+        )
+        .unwrap();
+        assert_eq!(guards.version, STRATEGY_CONFIG_VERSION);
+        assert_eq!(guards.cpu_usage_threshold, 60.0);
+        assert_eq!(guards.symbol, "BTCUSDT");
+
+        let template = StrategyConfig::from_json(
+            r#"{"strategy_type": "mean_reversion", "symbol": "ETHUSDT", "interval": "4h",
+                "lookback_periods": 50, "threshold": 0.03}"#,
+        )
+        .unwrap();
+        assert_eq!(template.strategy_type, StrategyKind::MeanReversion);
+        assert_eq!(template.lookback_periods, 50);
+        assert_eq!(template.cpu_usage_threshold, 75.0);
+
+        // The current version round-trips unchanged
+        let current = serde_json::to_string(&template).unwrap();
+        assert_eq!(StrategyConfig::from_json(&current).unwrap(), template);
+
+        // Loading rewrites an old file in the current version
+        let path = std::env::temp_dir()
+            .join(format!("aurelia-strategy-{}", uuid::Uuid::new_v4()))
+            .join("strategy.json");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, r#"{"cpu_usage_threshold": 60.0}"#).unwrap();
+        assert_eq!(
+            StrategyConfig::load(&path).unwrap().cpu_usage_threshold,
+            60.0
+        );
+        let rewritten: Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(rewritten["version"], STRATEGY_CONFIG_VERSION);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_configs_are_explained() {
+        let error = |json: &str| StrategyConfig::from_json(json).unwrap_err().to_string();
+
+        assert!(error(r#"{"version": 99}"#).contains("newer agent"));
+        assert!(error(r#"{"version": "one"}"#).contains("not a whole number"));
+        assert!(error("[1, 2]").contains("JSON object"));
+        assert!(error(r#"{"interval": "2m"}"#).contains("interval '2m'"));
+        assert!(error(r#"{"threshold": 1.5}"#).contains("threshold 1.5"));
+        assert!(error(r#"{"strategy_type": "arbitrage"}"#).contains("does not match version"));
+
+        // Typos are reported instead of silently falling back to a default
+        let current = serde_json::to_value(StrategyConfig::default()).unwrap();
+        let mut typo = current.as_object().unwrap().clone();
+        typo.insert("treshold".to_string(), Value::from(0.05));
+        assert!(error(&Value::Object(typo).to_string()).contains("treshold"));
+    }
+}
@@ -0,0 +1,585 @@
+use crate::{AureliaResult, CostModel, DecisionRationale, OrderUpdate};
+use chrono::{DateTime, Datelike, Duration, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Default location of the ledger, relative to the deployment directory
+pub const TRADE_LEDGER_PATH: &str = "data/trades.jsonl";
+
+/// One execution, simulated or real.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fill {
+    pub timestamp: DateTime<Utc>,
+    pub symbol: String,
+    /// `BUY` or `SELL`
+    pub side: String,
+    pub price: f64,
+    pub quantity: f64,
+    pub fee: f64,
+    /// Asset the fee was charged in; `None` means the quote asset
+    pub fee_asset: Option<String>,
+    pub client_order_id: Option<String>,
+    pub simulated: bool,
+    /// The strategy the order was placed for; `None` for orders placed by hand
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy_id: Option<String>,
+    /// Why the strategy traded, see [`DecisionRationale`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rationale: Option<DecisionRationale>,
+}
+
+impl Fill {
+    /// The fill reported by an order update, if it reports one.
+    pub fn from_update(update: &OrderUpdate) -> Option<Self> {
+        if update.last_fill_quantity <= 0.0 {
+            return None;
+        }
+        Some(Self {
+            timestamp: Utc
+                .timestamp_millis_opt(update.timestamp as i64)
+                .single()
+                .unwrap_or_else(Utc::now),
+            symbol: update.symbol.clone(),
+            side: update.side.clone(),
+            price: update.last_fill_price,
+            quantity: update.last_fill_quantity,
+            fee: update.commission,
+            fee_asset: update.commission_asset.clone(),
+            client_order_id: Some(update.client_order_id.clone()),
+            simulated: false,
+            strategy_id: None,
+            rationale: None,
+        })
+    }
+
+    /// The fee in quote asset terms. Fees paid in a third asset such as BNB cannot be
+    /// valued from the fill alone and count as zero.
+    pub(crate) fn quote_fee(&self) -> f64 {
+        match self.fee_asset.as_deref() {
+            None => self.fee,
+            Some(asset) if self.symbol.ends_with(asset) => self.fee,
+            Some(asset) if self.symbol.starts_with(asset) => self.fee * self.price,
+            Some(_) => 0.0,
+        }
+    }
+
+    /// Signed base quantity: positive for buys.
+    fn signed_quantity(&self) -> f64 {
+        if self.side.eq_ignore_ascii_case("SELL") {
+            -self.quantity
+        } else {
+            self.quantity
+        }
+    }
+}
+
+/// Append-only JSON Lines ledger of fills.
+#[derive(Debug, Clone)]
+pub struct TradeLedger {
+    file: Option<Arc<Mutex<File>>>,
+    fills: Arc<Mutex<Vec<Fill>>>,
+}
+
+impl TradeLedger {
+    /// Open the ledger at `path`, loading the fills already recorded.
+    pub fn open(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut fills = Vec::new();
+        if path.exists() {
+            for line in BufReader::new(File::open(path)?).lines() {
+                let line = line?;
+                if let Ok(fill) = serde_json::from_str::<Fill>(&line) {
+                    fills.push(fill);
+                }
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Some(Arc::new(Mutex::new(file))),
+            fills: Arc::new(Mutex::new(fills)),
+        })
+    }
+
+    /// A ledger that only keeps fills in memory.
+    pub fn in_memory() -> Self {
+        Self {
+            file: None,
+            fills: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn record(&self, fill: Fill) -> AureliaResult<()> {
+        if let Some(file) = &self.file {
+            let line = serde_json::to_string(&fill)?;
+            writeln!(file.lock().expect("trade ledger lock poisoned"), "{}", line)?;
+        }
+        self.fills
+            .lock()
+            .expect("trade ledger lock poisoned")
+            .push(fill);
+        Ok(())
+    }
+
+    pub fn fills(&self) -> Vec<Fill> {
+        self.fills
+            .lock()
+            .expect("trade ledger lock poisoned")
+            .clone()
+    }
+
+    /// Traded value in quote asset of the fills at or after `since`
+    pub fn volume_since(&self, since: DateTime<Utc>) -> f64 {
+        self.fills
+            .lock()
+            .expect("trade ledger lock poisoned")
+            .iter()
+            .filter(|fill| fill.timestamp >= since)
+            .map(|fill| fill.price * fill.quantity)
+            .sum()
+    }
+
+    /// Net base quantity, positive when long, and last fill price by symbol over
+    /// either the simulated or the live fills; flat symbols are left out.
+    pub fn positions(&self, simulated: bool) -> BTreeMap<String, (f64, f64)> {
+        let mut positions = BTreeMap::new();
+        for fill in self
+            .fills
+            .lock()
+            .expect("trade ledger lock poisoned")
+            .iter()
+            .filter(|fill| fill.simulated == simulated)
+        {
+            let (quantity, price) = positions
+                .entry(fill.symbol.clone())
+                .or_insert((0.0, fill.price));
+            *quantity += fill.signed_quantity();
+            *price = fill.price;
+        }
+        positions.retain(|_, (quantity, _)| quantity.abs() > 1e-9);
+        positions
+    }
+
+    pub fn report(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        period: ReportPeriod,
+    ) -> TradeReport {
+        TradeReport::build(&self.fills(), from, to, period)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportPeriod {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl ReportPeriod {
+    fn start(self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let date = at.date_naive();
+        let date = match self {
+            ReportPeriod::Day => date,
+            ReportPeriod::Week => {
+                date - Duration::days(date.weekday().num_days_from_monday() as i64)
+            }
+            ReportPeriod::Month => date.with_day(1).unwrap_or(date),
+        };
+        date.and_time(NaiveTime::MIN).and_utc()
+    }
+}
+
+impl std::str::FromStr for ReportPeriod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "day" => Ok(ReportPeriod::Day),
+            "week" => Ok(ReportPeriod::Week),
+            "month" => Ok(ReportPeriod::Month),
+            other => Err(format!("unknown report period '{}'", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Summary {
+    pub trades: u32,
+    /// Traded value in quote asset
+    pub volume: f64,
+    pub realized_pnl: f64,
+    /// Fees in quote asset
+    pub fees: f64,
+    pub net_pnl: f64,
+}
+
+impl Summary {
+    fn add(&mut self, fill: &Fill, realized: f64) {
+        let fee = fill.quote_fee();
+        self.trades += 1;
+        self.volume += fill.price * fill.quantity;
+        self.realized_pnl += realized;
+        self.fees += fee;
+        self.net_pnl += realized - fee;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PeriodSummary {
+    pub start: DateTime<Utc>,
+    #[serde(flatten)]
+    pub summary: Summary,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SymbolSummary {
+    pub symbol: String,
+    #[serde(flatten)]
+    pub summary: Summary,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StrategySummary {
+    pub strategy_id: String,
+    #[serde(flatten)]
+    pub summary: Summary,
+}
+
+/// A reported fill together with the reasoning of the decision behind it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExplainedTrade {
+    pub timestamp: DateTime<Utc>,
+    pub symbol: String,
+    pub side: String,
+    pub price: f64,
+    pub quantity: f64,
+    pub strategy_id: Option<String>,
+    pub realized_pnl: f64,
+    pub rationale: DecisionRationale,
+}
+
+/// Realized PnL, fees and volume over a time range, by period, by symbol and by
+/// strategy. Fills without a strategy count towards every section but `strategies`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TradeReport {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub period: ReportPeriod,
+    pub total: Summary,
+    pub periods: Vec<PeriodSummary>,
+    pub symbols: Vec<SymbolSummary>,
+    pub strategies: Vec<StrategySummary>,
+    /// Fills in the report whose decision recorded a rationale, in time order
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub explanations: Vec<ExplainedTrade>,
+    /// Fills in the report that were simulated rather than reported by the exchange
+    pub simulated_trades: u32,
+    /// The model simulated fills were priced with, see [`TradeReport::with_cost_model`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_model: Option<CostModel>,
+}
+
+/// Average-cost position in one symbol; negative quantity is short.
+#[derive(Debug, Default)]
+struct Position {
+    quantity: f64,
+    average_price: f64,
+}
+
+impl Position {
+    /// Apply a fill and return the PnL it realizes.
+    fn apply(&mut self, fill: &Fill) -> f64 {
+        let traded = fill.signed_quantity();
+        if self.quantity == 0.0 || self.quantity.signum() == traded.signum() {
+            let quantity = self.quantity + traded;
+            self.average_price = (self.average_price * self.quantity.abs()
+                + fill.price * traded.abs())
+                / quantity.abs();
+            self.quantity = quantity;
+            return 0.0;
+        }
+
+        let closed = traded.abs().min(self.quantity.abs());
+        let realized = closed * (fill.price - self.average_price) * self.quantity.signum();
+        let remaining = self.quantity + traded;
+        if remaining != 0.0 && remaining.signum() != self.quantity.signum() {
+            // Flipped through flat: the rest opens a new position at the fill price
+            self.average_price = fill.price;
+        }
+        self.quantity = remaining;
+        realized
+    }
+}
+
+/// Fills in time order, each with the PnL it realizes. Each strategy holds its own
+/// positions, so one strategy's buy never closes another's short.
+pub(crate) fn realize(fills: &[Fill]) -> Vec<(&Fill, f64)> {
+    let mut fills: Vec<&Fill> = fills.iter().collect();
+    fills.sort_by_key(|f| f.timestamp);
+
+    let mut positions: BTreeMap<(Option<&str>, &str), Position> = BTreeMap::new();
+    fills
+        .into_iter()
+        .map(|fill| {
+            let realized = positions
+                .entry((fill.strategy_id.as_deref(), fill.symbol.as_str()))
+                .or_default()
+                .apply(fill);
+            (fill, realized)
+        })
+        .collect()
+}
+
+impl TradeReport {
+    /// Fills before `from` still build up positions so that later closes realize
+    /// against the right cost basis; only fills in `[from, to)` are reported.
+    pub fn build(
+        fills: &[Fill],
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        period: ReportPeriod,
+    ) -> Self {
+        let mut total = Summary::default();
+        let mut periods: BTreeMap<DateTime<Utc>, Summary> = BTreeMap::new();
+        let mut symbols: BTreeMap<&str, Summary> = BTreeMap::new();
+        let mut strategies: BTreeMap<&str, Summary> = BTreeMap::new();
+        let mut explanations = Vec::new();
+        let mut simulated_trades = 0;
+        for (fill, realized) in realize(fills) {
+            if to.is_some_and(|to| fill.timestamp >= to) {
+                break;
+            }
+            if from.is_some_and(|from| fill.timestamp < from) {
+                continue;
+            }
+            total.add(fill, realized);
+            simulated_trades += u32::from(fill.simulated);
+            periods
+                .entry(period.start(fill.timestamp))
+                .or_default()
+                .add(fill, realized);
+            symbols.entry(&fill.symbol).or_default().add(fill, realized);
+            if let Some(strategy_id) = &fill.strategy_id {
+                strategies
+                    .entry(strategy_id.as_str())
+                    .or_default()
+                    .add(fill, realized);
+            }
+            if let Some(rationale) = &fill.rationale {
+                explanations.push(ExplainedTrade {
+                    timestamp: fill.timestamp,
+                    symbol: fill.symbol.clone(),
+                    side: fill.side.clone(),
+                    price: fill.price,
+                    quantity: fill.quantity,
+                    strategy_id: fill.strategy_id.clone(),
+                    realized_pnl: realized,
+                    rationale: rationale.clone(),
+                });
+            }
+        }
+
+        Self {
+            from,
+            to,
+            period,
+            total,
+            periods: periods
+                .into_iter()
+                .map(|(start, summary)| PeriodSummary { start, summary })
+                .collect(),
+            symbols: symbols
+                .into_iter()
+                .map(|(symbol, summary)| SymbolSummary {
+                    symbol: symbol.to_string(),
+                    summary,
+                })
+                .collect(),
+            strategies: strategies
+                .into_iter()
+                .map(|(strategy_id, summary)| StrategySummary {
+                    strategy_id: strategy_id.to_string(),
+                    summary,
+                })
+                .collect(),
+            explanations,
+            simulated_trades,
+            cost_model: None,
+        }
+    }
+
+    /// Include the cost model simulated fills are priced with, if the report has any.
+    pub fn with_cost_model(mut self, model: CostModel) -> Self {
+        if self.simulated_trades > 0 {
+            self.cost_model = Some(model);
+        }
+        self
+    }
+
+    /// The report as one CSV table; `section` is `period`, `symbol`, `strategy` or
+    /// `total`.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("section,key,trades,volume,realized_pnl,fees,net_pnl\n");
+        let mut row = |section: &str, key: &str, s: &Summary| {
+            csv.push_str(&format!(
+                "{},{},{},{:.8},{:.8},{:.8},{:.8}\n",
+                section, key, s.trades, s.volume, s.realized_pnl, s.fees, s.net_pnl
+            ));
+        };
+        for p in &self.periods {
+            row(
+                "period",
+                &p.start.format("%Y-%m-%d").to_string(),
+                &p.summary,
+            );
+        }
+        for s in &self.symbols {
+            row("symbol", &s.symbol, &s.summary);
+        }
+        for s in &self.strategies {
+            row("strategy", &s.strategy_id, &s.summary);
+        }
+        row("total", "", &self.total);
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(day: u32, side: &str, price: f64, quantity: f64) -> Fill {
+        Fill {
+            timestamp: Utc.with_ymd_and_hms(2024, 1, day, 12, 0, 0).unwrap(),
+            symbol: "BTCUSDT".to_string(),
+            side: side.to_string(),
+            price,
+            quantity,
+            fee: 1.0,
+            fee_asset: Some("USDT".to_string()),
+            client_order_id: None,
+            simulated: true,
+            strategy_id: None,
+            rationale: None,
+        }
+    }
+
+    #[test]
+    fn test_report_realizes_against_average_cost() {
+        let ledger = TradeLedger::in_memory();
+        for f in [
+            fill(1, "BUY", 100.0, 1.0),
+            fill(2, "BUY", 200.0, 1.0),
+            // Closes one unit at an average cost of 150
+            fill(8, "SELL", 180.0, 1.0),
+            // Closes the other and opens a one unit short at 120
+            fill(9, "SELL", 120.0, 2.0),
+            fill(10, "BUY", 100.0, 1.0),
+        ] {
+            ledger.record(f).unwrap();
+        }
+
+        let from = Utc.with_ymd_and_hms(2024, 1, 8, 0, 0, 0).unwrap();
+        let report = ledger.report(Some(from), None, ReportPeriod::Week);
+        assert_eq!(report.total.trades, 3);
+        assert_eq!(report.total.realized_pnl, 30.0 - 30.0 + 20.0);
+        assert_eq!(report.total.fees, 3.0);
+        assert_eq!(report.total.net_pnl, 17.0);
+        assert_eq!(report.periods.len(), 1);
+        assert_eq!(report.periods[0].start, from);
+        assert_eq!(report.symbols[0].symbol, "BTCUSDT");
+
+        // The short was bought back on the 10th
+        assert!(ledger.positions(true).is_empty());
+        ledger.record(fill(11, "BUY", 90.0, 0.5)).unwrap();
+        assert_eq!(ledger.positions(true)["BTCUSDT"], (0.5, 90.0));
+        assert!(ledger.positions(false).is_empty());
+
+        let daily = ledger.report(None, Some(from), ReportPeriod::Day);
+        assert_eq!(daily.periods.len(), 2);
+        assert_eq!(daily.total.realized_pnl, 0.0);
+        let csv = daily.to_csv();
+        assert!(csv.contains("period,2024-01-02,1,200.00000000,0.00000000,1.00000000,-1.00000000"));
+        assert!(csv.ends_with("total,,2,300.00000000,0.00000000,2.00000000,-2.00000000\n"));
+        assert!(daily.strategies.is_empty());
+    }
+
+    #[test]
+    fn test_strategies_hold_separate_positions() {
+        let ledger = TradeLedger::in_memory();
+        let tagged = |day, side, price, strategy: &str| Fill {
+            strategy_id: Some(strategy.to_string()),
+            ..fill(day, side, price, 1.0)
+        };
+        for f in [
+            tagged(1, "BUY", 100.0, "momentum"),
+            // A short of its own, not a close of the momentum long
+            tagged(2, "SELL", 110.0, "mean_reversion"),
+            tagged(3, "SELL", 120.0, "momentum"),
+            tagged(4, "BUY", 105.0, "mean_reversion"),
+        ] {
+            ledger.record(f).unwrap();
+        }
+
+        let report = ledger.report(None, None, ReportPeriod::Month);
+        let pnl: Vec<_> = report
+            .strategies
+            .iter()
+            .map(|s| (s.strategy_id.as_str(), s.summary.realized_pnl))
+            .collect();
+        assert_eq!(pnl, [("mean_reversion", 5.0), ("momentum", 20.0)]);
+        assert_eq!(report.total.realized_pnl, 25.0);
+        assert!(report
+            .to_csv()
+            .contains("strategy,momentum,2,220.00000000,20.00000000,2.00000000,18.00000000"));
+    }
+
+    #[test]
+    fn test_rationale_is_persisted_and_reported() {
+        let path = std::env::temp_dir()
+            .join(format!("aurelia-ledger-{}", uuid::Uuid::new_v4()))
+            .join("trades.jsonl");
+        let rationale = DecisionRationale {
+            indicators: BTreeMap::from([("fast_ema".to_string(), 101.0)]),
+            sentiment: Some(0.4),
+            confidence: 0.8,
+            reason: "fast EMA crossed above slow EMA".to_string(),
+            ..Default::default()
+        };
+        let ledger = TradeLedger::open(&path).unwrap();
+        // Positions are kept per strategy; the sell only closes a buy of its own strategy
+        ledger
+            .record(Fill {
+                strategy_id: Some("momentum".to_string()),
+                ..fill(1, "BUY", 100.0, 1.0)
+            })
+            .unwrap();
+        ledger
+            .record(Fill {
+                strategy_id: Some("momentum".to_string()),
+                rationale: Some(rationale.clone()),
+                ..fill(2, "SELL", 110.0, 1.0)
+            })
+            .unwrap();
+
+        let report = TradeLedger::open(&path)
+            .unwrap()
+            .report(None, None, ReportPeriod::Day);
+        assert_eq!(report.explanations.len(), 1);
+        let explained = &report.explanations[0];
+        assert_eq!(explained.rationale, rationale);
+        assert_eq!(explained.strategy_id.as_deref(), Some("momentum"));
+        assert_eq!(explained.realized_pnl, 10.0);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,219 @@
+//! Multi-asset accounting.
+//!
+//! Balances are held in several assets, such as USDT and BTC. Each is valued in
+//! the accounting currency at the latest trade price of a market pairing the
+//! two, directly or through a bridge asset, and the values are summed into the
+//! net asset value that `FinancialUpdate` reports. With a fiat currency
+//! configured, the total is also converted to it and compared with the fiat
+//! cost basis, the amount originally paid in.
+
+use crate::AureliaResult;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+pub const ACCOUNTING_CONFIG_PATH: &str = "config/accounting.json";
+
+/// Assets conversions may go through when no market pairs two assets directly.
+const BRIDGE_ASSETS: [&str; 3] = ["USDT", "BTC", "ETH"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccountingConfig {
+    /// Currency funds are reported and budgeted in
+    pub currency: String,
+    /// Fiat currency to also report the net asset value in, e.g. `EUR`
+    pub fiat_currency: Option<String>,
+    /// Amount of `fiat_currency` paid in, against which the fiat PnL is measured
+    pub fiat_cost_basis: f64,
+}
+
+impl Default for AccountingConfig {
+    fn default() -> Self {
+        Self {
+            currency: "USDT".to_string(),
+            fiat_currency: None,
+            fiat_cost_basis: 0.0,
+        }
+    }
+}
+
+impl AccountingConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+/// Latest trade price by exchange symbol, e.g. `BTCUSDT`.
+#[derive(Debug, Clone, Default)]
+pub struct PriceBook {
+    prices: BTreeMap<String, f64>,
+}
+
+impl PriceBook {
+    pub fn update(&mut self, symbol: &str, price: f64) {
+        if price > 0.0 {
+            self.prices.insert(symbol.to_uppercase(), price);
+        }
+    }
+
+    /// Latest trade price of `symbol`, e.g. `BTCUSDT`.
+    pub fn price(&self, symbol: &str) -> Option<f64> {
+        self.prices.get(&symbol.to_uppercase()).copied()
+    }
+
+    /// Price of one `base` in `quote`, from the `base``quote` market or the
+    /// inverse of the `quote``base` one.
+    fn direct(&self, base: &str, quote: &str) -> Option<f64> {
+        if base == quote {
+            return Some(1.0);
+        }
+        if let Some(price) = self.prices.get(&format!("{}{}", base, quote)) {
+            return Some(*price);
+        }
+        self.prices
+            .get(&format!("{}{}", quote, base))
+            .map(|price| 1.0 / price)
+    }
+
+    /// Price of one `asset` in `currency`, through a bridge asset if needed.
+    pub fn rate(&self, asset: &str, currency: &str) -> Option<f64> {
+        let (asset, currency) = (asset.to_uppercase(), currency.to_uppercase());
+        self.direct(&asset, &currency).or_else(|| {
+            BRIDGE_ASSETS.iter().find_map(|bridge| {
+                Some(self.direct(&asset, bridge)? * self.direct(bridge, &currency)?)
+            })
+        })
+    }
+
+    pub fn convert(&self, amount: f64, from: &str, to: &str) -> Option<f64> {
+        self.rate(from, to).map(|rate| amount * rate)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AssetValue {
+    pub asset: String,
+    pub quantity: f64,
+    /// Price of one unit in the accounting currency
+    pub price: f64,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FiatValue {
+    pub currency: String,
+    pub value: f64,
+    pub cost_basis: f64,
+    pub pnl: f64,
+}
+
+/// Holdings valued in one currency.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetAssetValue {
+    pub currency: String,
+    pub total: f64,
+    pub assets: Vec<AssetValue>,
+    /// Assets held without a price to value them at; they are left out of `total`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unpriced: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fiat: Option<FiatValue>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl NetAssetValue {
+    /// Value `holdings`, quantities by asset, at the prices in `prices`.
+    pub fn compute<'a>(
+        holdings: impl IntoIterator<Item = (&'a str, f64)>,
+        prices: &PriceBook,
+        config: &AccountingConfig,
+    ) -> Self {
+        let mut assets = Vec::new();
+        let mut unpriced = Vec::new();
+        for (asset, quantity) in holdings {
+            if quantity == 0.0 {
+                continue;
+            }
+            match prices.rate(asset, &config.currency) {
+                Some(price) => assets.push(AssetValue {
+                    asset: asset.to_string(),
+                    quantity,
+                    price,
+                    value: quantity * price,
+                }),
+                None => unpriced.push(asset.to_string()),
+            }
+        }
+        let total = assets.iter().map(|a| a.value).sum();
+        let fiat = config.fiat_currency.as_ref().and_then(|fiat| {
+            let value = prices.convert(total, &config.currency, fiat)?;
+            Some(FiatValue {
+                currency: fiat.clone(),
+                value,
+                cost_basis: config.fiat_cost_basis,
+                pnl: value - config.fiat_cost_basis,
+            })
+        });
+        Self {
+            currency: config.currency.clone(),
+            total,
+            assets,
+            unpriced,
+            fiat,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_holdings_are_valued_through_market_prices() {
+        let mut prices = PriceBook::default();
+        prices.update("BTCUSDT", 60000.0);
+        prices.update("ETHBTC", 0.05);
+        prices.update("EURUSDT", 1.2);
+
+        assert_eq!(prices.price("btcusdt"), Some(60000.0));
+        assert_eq!(prices.rate("USDT", "USDT"), Some(1.0));
+        assert_eq!(prices.rate("BTC", "USDT"), Some(60000.0));
+        assert_eq!(prices.rate("USDT", "EUR"), Some(1.0 / 1.2));
+        // ETH has no USDT market here, so it goes through BTC
+        assert!((prices.rate("ETH", "USDT").unwrap() - 3000.0).abs() < 1e-9);
+        assert_eq!(prices.rate("DOGE", "USDT"), None);
+
+        let config = AccountingConfig {
+            fiat_currency: Some("EUR".to_string()),
+            fiat_cost_basis: 50000.0,
+            ..Default::default()
+        };
+        let nav = NetAssetValue::compute(
+            [("USDT", 1200.0), ("BTC", 1.0), ("ETH", 2.0), ("DOGE", 10.0)],
+            &prices,
+            &config,
+        );
+        assert_eq!(nav.currency, "USDT");
+        assert!((nav.total - 67200.0).abs() < 1e-6);
+        assert_eq!(nav.unpriced, ["DOGE"]);
+        let fiat = nav.fiat.unwrap();
+        assert!((fiat.value - 56000.0).abs() < 1e-6);
+        assert!((fiat.pnl - 6000.0).abs() < 1e-6);
+
+        // Valued in BTC instead
+        let config = AccountingConfig {
+            currency: "BTC".to_string(),
+            ..Default::default()
+        };
+        let nav = NetAssetValue::compute([("USDT", 6000.0), ("BTC", 0.5)], &prices, &config);
+        assert!((nav.total - 0.6).abs() < 1e-9);
+    }
+}
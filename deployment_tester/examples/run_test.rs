@@ -42,6 +42,13 @@ enum Commands {
         duration: u64,
     },
 
+    /// Replay recorded market data against the primary for hours and check for leaks and lag
+    Soak {
+        /// Duration in minutes, overrides the config
+        #[arg(short, long)]
+        duration: Option<u64>,
+    },
+
     /// Cleanup all deployments
     Cleanup,
 }
@@ -90,6 +97,14 @@ async fn main() -> anyhow::Result<()> {
             let runner = TestRunner::new(config, binary_path);
             runner.run_specific_test("monitor").await?;
         }
+        Commands::Soak { duration } => {
+            let mut config = config;
+            if let Some(duration) = duration {
+                config.soak.duration_minutes = duration;
+            }
+            let runner = TestRunner::new(config, binary_path);
+            runner.run_specific_test("soak").await?;
+        }
         Commands::Cleanup => {
             runner.cleanup().await?;
         }
@@ -0,0 +1,378 @@
+use crate::config::{ServerConfig, ServerRole, TestConfig};
+use crate::monitor::AgentMonitor;
+use crate::validator::ValidationResult;
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tracing::info;
+
+/// 单项检查的配置，按检查名称写在 `TestConfig::checks` 中
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CheckConfig {
+    pub enabled: bool,
+    /// 覆盖检查的默认阈值，键名由各检查定义
+    pub thresholds: BTreeMap<String, f64>,
+}
+
+impl Default for CheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            thresholds: BTreeMap::new(),
+        }
+    }
+}
+
+impl CheckConfig {
+    pub fn threshold(&self, name: &str, default: f64) -> f64 {
+        self.thresholds.get(name).copied().unwrap_or(default)
+    }
+}
+
+/// 检查运行时可用的信息
+pub struct CheckContext<'a> {
+    pub config: &'a TestConfig,
+    pub settings: &'a CheckConfig,
+}
+
+impl CheckContext<'_> {
+    /// 一条尚未通过的结果，`server` 为 `None` 表示不针对单台服务器
+    pub fn result(&self, test_name: &str, server: Option<String>) -> ValidationResult {
+        ValidationResult {
+            test_name: test_name.to_string(),
+            server,
+            timestamp: Utc::now(),
+            passed: false,
+            details: HashMap::new(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+/// 验证套件中的一项检查
+///
+/// 实现后通过 `ValidationSuite::register` 加入套件；名称同时是结果中的
+/// `test_name` 和 `TestConfig::checks` 中的配置键。
+#[async_trait]
+pub trait ValidationCheck: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Vec<ValidationResult>;
+}
+
+/// 默认注册的检查，按运行顺序排列
+pub fn default_checks() -> Vec<Box<dyn ValidationCheck>> {
+    vec![
+        Box::new(AgentRunningCheck),
+        Box::new(ResourceUsageCheck),
+        Box::new(LogActivityCheck),
+        Box::new(AutonomousBehaviorCheck),
+        Box::new(NetworkCommunicationCheck),
+        Box::new(SelfReplicationCheck),
+    ]
+}
+
+pub struct AgentRunningCheck;
+
+#[async_trait]
+impl ValidationCheck for AgentRunningCheck {
+    fn name(&self) -> &str {
+        "agent_running"
+    }
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Vec<ValidationResult> {
+        info!("Validating agent processes...");
+        let mut results = Vec::new();
+
+        for server in &ctx.config.test_environments {
+            let monitor = AgentMonitor::new(server.clone());
+            let mut result = ctx.result(self.name(), Some(server.name.clone()));
+
+            match monitor.check_agent_health() {
+                Ok(health) => {
+                    result.passed = health.is_running;
+                    result.details.insert(
+                        "is_running".to_string(),
+                        serde_json::json!(health.is_running),
+                    );
+                    result
+                        .details
+                        .insert("cpu_usage".to_string(), serde_json::json!(health.cpu_usage));
+                    result
+                        .details
+                        .insert("memory_mb".to_string(), serde_json::json!(health.memory_mb));
+
+                    if !health.errors.is_empty() {
+                        result.errors = health.errors;
+                    }
+                }
+                Err(e) => {
+                    result.errors.push(format!("Health check failed: {}", e));
+                }
+            }
+
+            results.push(result);
+        }
+
+        results
+    }
+}
+
+/// 阈值：`max_cpu_percent`、`max_memory_mb`、`max_disk_gb`，默认取 `test_settings.resource_limits`
+pub struct ResourceUsageCheck;
+
+#[async_trait]
+impl ValidationCheck for ResourceUsageCheck {
+    fn name(&self) -> &str {
+        "resource_usage"
+    }
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Vec<ValidationResult> {
+        info!("Validating resource usage...");
+        let limits = &ctx.config.test_settings.resource_limits;
+        let max_cpu = ctx
+            .settings
+            .threshold("max_cpu_percent", limits.max_cpu_percent);
+        let max_memory = ctx
+            .settings
+            .threshold("max_memory_mb", limits.max_memory_mb as f64);
+        let max_disk = ctx
+            .settings
+            .threshold("max_disk_gb", limits.max_disk_gb as f64);
+        let mut results = Vec::new();
+
+        for server in &ctx.config.test_environments {
+            let monitor = AgentMonitor::new(server.clone());
+            let mut result = ctx.result(self.name(), Some(server.name.clone()));
+
+            match monitor.get_resource_metrics() {
+                Ok(metrics) => {
+                    let cpu_ok = metrics.cpu_percent < max_cpu;
+                    let mem_ok = metrics.memory_mb < max_memory;
+                    let disk_ok = metrics.disk_usage_gb < max_disk;
+
+                    result.passed = cpu_ok && mem_ok && disk_ok;
+                    result.details.insert(
+                        "cpu_percent".to_string(),
+                        serde_json::json!(metrics.cpu_percent),
+                    );
+                    result
+                        .details
+                        .insert("cpu_limit".to_string(), serde_json::json!(max_cpu));
+                    result.details.insert(
+                        "memory_mb".to_string(),
+                        serde_json::json!(metrics.memory_mb),
+                    );
+                    result
+                        .details
+                        .insert("memory_limit_mb".to_string(), serde_json::json!(max_memory));
+                    result.details.insert(
+                        "disk_gb".to_string(),
+                        serde_json::json!(metrics.disk_usage_gb),
+                    );
+                    result
+                        .details
+                        .insert("disk_limit_gb".to_string(), serde_json::json!(max_disk));
+                }
+                Err(e) => {
+                    result.errors.push(format!("Resource check failed: {}", e));
+                }
+            }
+
+            results.push(result);
+        }
+
+        results
+    }
+}
+
+pub struct LogActivityCheck;
+
+#[async_trait]
+impl ValidationCheck for LogActivityCheck {
+    fn name(&self) -> &str {
+        "log_activity"
+    }
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Vec<ValidationResult> {
+        info!("Validating log activity...");
+        let mut results = Vec::new();
+
+        for server in &ctx.config.test_environments {
+            let monitor = AgentMonitor::new(server.clone());
+            let mut result = ctx.result(self.name(), Some(server.name.clone()));
+
+            match monitor.check_log_activity() {
+                Ok((active, logs)) => {
+                    result.passed = active;
+                    result
+                        .details
+                        .insert("has_activity".to_string(), serde_json::json!(active));
+                    result
+                        .details
+                        .insert("log_count".to_string(), serde_json::json!(logs.len()));
+
+                    if !logs.is_empty() {
+                        result.details.insert(
+                            "recent_logs".to_string(),
+                            serde_json::json!(logs.iter().take(5).collect::<Vec<_>>()),
+                        );
+                    }
+                }
+                Err(e) => {
+                    result.errors.push(format!("Log check failed: {}", e));
+                }
+            }
+
+            results.push(result);
+        }
+
+        results
+    }
+}
+
+/// 阈值：`min_behaviors`，至少出现几类自主行为，默认 1
+pub struct AutonomousBehaviorCheck;
+
+#[async_trait]
+impl ValidationCheck for AutonomousBehaviorCheck {
+    fn name(&self) -> &str {
+        "autonomous_behavior"
+    }
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Vec<ValidationResult> {
+        info!("Validating autonomous behavior...");
+        let min_behaviors = ctx.settings.threshold("min_behaviors", 1.0);
+        let mut results = Vec::new();
+
+        for server in &ctx.config.test_environments {
+            let monitor = AgentMonitor::new(server.clone());
+            let mut result = ctx.result(self.name(), Some(server.name.clone()));
+
+            match monitor.check_autonomous_behavior() {
+                Ok(behaviors) => {
+                    let observed = behaviors.values().filter(|&&v| v).count();
+                    result.passed = observed as f64 >= min_behaviors;
+
+                    for (key, value) in behaviors {
+                        result.details.insert(key, serde_json::json!(value));
+                    }
+                }
+                Err(e) => {
+                    result.errors.push(format!("Behavior check failed: {}", e));
+                }
+            }
+
+            results.push(result);
+        }
+
+        results
+    }
+}
+
+/// 阈值：`min_connections`，主节点至少的连接数，默认 1；副本不要求连接
+pub struct NetworkCommunicationCheck;
+
+#[async_trait]
+impl ValidationCheck for NetworkCommunicationCheck {
+    fn name(&self) -> &str {
+        "network_communication"
+    }
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Vec<ValidationResult> {
+        info!("Validating network communication...");
+        let min_connections = ctx.settings.threshold("min_connections", 1.0);
+        let mut results = Vec::new();
+
+        for server in &ctx.config.test_environments {
+            let monitor = AgentMonitor::new(server.clone());
+            let mut result = ctx.result(self.name(), Some(server.name.clone()));
+
+            match monitor.check_network_connections() {
+                Ok(count) => {
+                    result.passed = count as f64 >= min_connections
+                        || matches!(server.role, ServerRole::Replica);
+                    result.details.insert(
+                        "websocket_connections".to_string(),
+                        serde_json::json!(count),
+                    );
+                }
+                Err(e) => {
+                    result.errors.push(format!("Network check failed: {}", e));
+                }
+            }
+
+            results.push(result);
+        }
+
+        results
+    }
+}
+
+/// 检查主节点是否已复制到第一台副本服务器；没有副本服务器时跳过
+pub struct SelfReplicationCheck;
+
+impl SelfReplicationCheck {
+    fn validate(
+        &self,
+        ctx: &CheckContext<'_>,
+        primary: &ServerConfig,
+        replica: &ServerConfig,
+    ) -> ValidationResult {
+        info!(
+            "Validating self-replication from {} to {}",
+            primary.name, replica.name
+        );
+
+        let mut result = ctx.result(
+            self.name(),
+            Some(format!("{} -> {}", primary.name, replica.name)),
+        );
+
+        let replica_monitor = AgentMonitor::new(replica.clone());
+
+        match replica_monitor.verify_replica_deployment() {
+            Ok(exists) => {
+                result.passed = exists;
+                result
+                    .details
+                    .insert("replica_deployed".to_string(), serde_json::json!(exists));
+
+                if exists {
+                    // Check if replica is running
+                    if let Ok(health) = replica_monitor.check_agent_health() {
+                        result.details.insert(
+                            "replica_running".to_string(),
+                            serde_json::json!(health.is_running),
+                        );
+                    }
+                }
+            }
+            Err(e) => {
+                result
+                    .errors
+                    .push(format!("Replication check failed: {}", e));
+            }
+        }
+
+        result
+    }
+}
+
+#[async_trait]
+impl ValidationCheck for SelfReplicationCheck {
+    fn name(&self) -> &str {
+        "self_replication"
+    }
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Vec<ValidationResult> {
+        let primary = ctx.config.get_primary_server();
+        let replicas = ctx.config.get_replica_servers();
+        match (primary, replicas.first()) {
+            (Some(primary), Some(replica)) => vec![self.validate(ctx, primary, replica)],
+            _ => Vec::new(),
+        }
+    }
+}
@@ -1,4 +1,7 @@
+use crate::checks::CheckConfig;
+use crate::report::ReportConfig;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -15,6 +18,13 @@ pub struct TestConfig {
     pub test_environments: Vec<ServerConfig>,
     pub test_settings: TestSettings,
     pub monitoring: MonitoringConfig,
+    #[serde(default)]
+    pub soak: SoakConfig,
+    #[serde(default)]
+    pub report: ReportConfig,
+    /// 按检查名称的启用开关和阈值，未列出的检查使用默认设置
+    #[serde(default)]
+    pub checks: BTreeMap<String, CheckConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -112,6 +122,60 @@ pub struct MonitoringConfig {
     pub alert_endpoints: Vec<String>,
 }
 
+/// 长时间压力测试：本地模拟交易所按固定速率回放录制的行情
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoakConfig {
+    /// 录制的 Binance 逐笔成交，每行一条 JSON
+    pub recording: PathBuf,
+    pub replay_rate_per_second: f64,
+    pub duration_minutes: u64,
+    pub sample_interval_seconds: u64,
+    /// 内存基线在预热结束后才取
+    pub warmup_minutes: u64,
+    pub mock_exchange_port: u16,
+    /// 代理连接模拟交易所时使用的地址
+    pub mock_exchange_host: String,
+    pub agent_api_port: u16,
+    pub thresholds: SoakThresholds,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoakThresholds {
+    /// 最慢的事件订阅者积压的事件数
+    pub max_event_backlog: usize,
+    pub max_memory_growth_mb: f64,
+    /// 每个采样窗口内的平均决策延迟
+    pub max_decision_latency_ms: f64,
+}
+
+impl Default for SoakConfig {
+    fn default() -> Self {
+        Self {
+            recording: PathBuf::from("data/recorded_trades.jsonl"),
+            replay_rate_per_second: 200.0,
+            duration_minutes: 240,
+            sample_interval_seconds: 60,
+            warmup_minutes: 10,
+            mock_exchange_port: 9555,
+            mock_exchange_host: "127.0.0.1".to_string(),
+            agent_api_port: 8080,
+            thresholds: SoakThresholds::default(),
+        }
+    }
+}
+
+impl Default for SoakThresholds {
+    fn default() -> Self {
+        Self {
+            max_event_backlog: 500,
+            max_memory_growth_mb: 128.0,
+            max_decision_latency_ms: 500.0,
+        }
+    }
+}
+
 impl Default for TestConfig {
     fn default() -> Self {
         Self {
@@ -155,6 +219,9 @@ impl Default for TestConfig {
                 log_level: "debug".to_string(),
                 alert_endpoints: vec!["http://localhost:8080/alerts".to_string()],
             },
+            soak: SoakConfig::default(),
+            report: ReportConfig::default(),
+            checks: BTreeMap::new(),
         }
     }
 }
@@ -1,29 +1,112 @@
 use crate::config::{AuthMethod, ServerConfig};
 use anyhow::{Context, Result};
+use common::audit::{self, AuditCategory};
+use common::ssh::{connect_tcp, polling, read_output, write_all_cancellable};
+use common::{
+    host_port, CancellationToken, DeploymentBundle, SessionLease, SshConnectionManager, SshTimeouts,
+};
 use ssh2::Session;
-use std::fs;
-use std::io::{Read, Write};
-use std::net::TcpStream;
 use std::path::{Path, PathBuf};
 use tracing::{error, info};
 
+const ENV_TEMPLATE: &str = "BINANCE_API_KEY=test_api_key\n\
+     BINANCE_API_SECRET=test_api_secret\n\
+     DEPLOYMENT_MODE=test\n";
+
+const STRATEGY_TEMPLATE: &str = r#"{
+    "version": 1,
+    "strategy_type": "momentum",
+    "symbol": "BTCUSDT",
+    "interval": "1h",
+    "lookback_periods": 20,
+    "threshold": 0.02,
+    "cpu_usage_threshold": 75.0,
+    "price_drop_threshold": 0.05
+}"#;
+
+const STATE_TEMPLATE: &str = r#"{
+    "funds": 1000.0,
+    "last_update": null
+}"#;
+
+/// 副本默认不允许继续复制
+const REPLICATION_TEMPLATE: &str = r#"{
+    "enabled": false
+}"#;
+
+const START_SCRIPT_TEMPLATE: &str = "#!/bin/bash\n\
+     cd \"{{remote_path}}\"\n\
+     nohup ./kernel > aurelia.log 2>&1 &\n\
+     echo $! > aurelia.pid\n\
+     echo \"Agent started with PID: $(cat aurelia.pid)\"\n";
+
+/// 压力测试时代理从模拟交易所读取行情
+const SOAK_START_SCRIPT_TEMPLATE: &str = "#!/bin/bash\n\
+     cd \"{{remote_path}}\"\n\
+     AURELIA_MARKET_WS_URL=\"{{market_ws_url}}\" nohup ./kernel > aurelia.log 2>&1 &\n\
+     echo $! > aurelia.pid\n\
+     echo \"Agent started with PID: $(cat aurelia.pid)\"\n";
+
 pub struct DeploymentClient {
     config: ServerConfig,
+    timeouts: SshTimeouts,
+    cancel: CancellationToken,
+    pool: Option<SshConnectionManager>,
 }
 
 impl DeploymentClient {
     pub fn new(config: ServerConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            timeouts: SshTimeouts::default(),
+            cancel: CancellationToken::new(),
+            pool: None,
+        }
+    }
+
+    /// 设置连接、读取和命令超时
+    pub fn with_timeouts(mut self, timeouts: SshTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// 取消令牌触发后中止正在进行的命令和上传
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// 复用连接池中已认证的会话，连接失效时自动重连
+    pub fn with_connection_pool(mut self, pool: SshConnectionManager) -> Self {
+        self.pool = Some(pool);
+        self
     }
 
-    pub fn connect(&self) -> Result<Session> {
-        info!("Connecting to {}:{}...", self.config.ip, self.config.port);
+    pub fn connect(&self) -> Result<SessionLease> {
+        match &self.pool {
+            Some(pool) => pool.get(&self.pool_key(), self.timeouts.session_timeout_ms(), || {
+                self.open_session()
+            }),
+            None => self.open_session().map(SessionLease::unpooled),
+        }
+    }
+
+    fn pool_key(&self) -> String {
+        SshConnectionManager::key(&self.config.user, &self.config.ip, self.config.port)
+    }
+
+    fn open_session(&self) -> Result<Session> {
+        info!(
+            "Connecting to {}...",
+            host_port(&self.config.ip, self.config.port)
+        );
 
-        let tcp = TcpStream::connect(format!("{}:{}", self.config.ip, self.config.port))
+        let tcp = connect_tcp(&self.config.ip, self.config.port, self.timeouts.connect)
             .context("Failed to establish TCP connection")?;
 
         let mut sess = Session::new().context("Failed to create SSH session")?;
         sess.set_tcp_stream(tcp);
+        sess.set_timeout(self.timeouts.session_timeout_ms());
 
         // 执行SSH握手，添加更详细的错误信息
         if let Err(e) = sess.handshake() {
@@ -108,13 +191,19 @@ impl DeploymentClient {
         let sess = self.connect()?;
         let mut channel = sess.channel_session()?;
         channel.exec("echo 'Connection test successful'")?;
-        let mut output = String::new();
-        channel.read_to_string(&mut output)?;
+        let output = polling(&sess, || {
+            read_output(&mut channel, self.timeouts.command, &self.cancel)
+        })?;
         channel.wait_close()?;
         Ok(output.contains("Connection test successful"))
     }
 
     pub fn deploy_agent(&self, local_binary_path: &Path) -> Result<()> {
+        self.deploy_bundle(&self.default_bundle(local_binary_path))
+    }
+
+    /// 上传部署包并启动代理，包中需包含 `start_agent.sh`
+    pub fn deploy_bundle(&self, bundle: &DeploymentBundle) -> Result<()> {
         info!("Starting deployment to {}...", self.config.name);
 
         let sess = self.connect()?;
@@ -122,17 +211,13 @@ impl DeploymentClient {
         // Create remote directory
         self.create_remote_directory(&sess)?;
 
-        // Upload binary
-        self.upload_file(&sess, local_binary_path, "kernel")?;
-
-        // Upload configuration files
-        self.upload_config_files(&sess)?;
-
-        // Make binary executable
-        self.make_executable(&sess, "kernel")?;
+        // Upload binary, configuration files and startup script
+        self.upload_bundle(&sess, bundle)?;
 
-        // Create startup script
-        self.create_startup_script(&sess)?;
+        // 签名的部署包在启动前由新内核按本机公钥校验
+        if let Some(public_key) = bundle.public_key() {
+            self.verify_bundle(&sess, &public_key)?;
+        }
 
         // Start the agent
         self.start_agent(&sess)?;
@@ -141,14 +226,45 @@ impl DeploymentClient {
         Ok(())
     }
 
-    pub fn execute_command(&self, sess: &Session, cmd: &str) -> Result<String> {
-        let mut channel = sess.channel_session()?;
-        channel.exec(cmd)?;
-        let mut output = String::new();
-        channel.read_to_string(&mut output)?;
-        channel.wait_close()?;
+    /// 默认部署包：内核、测试配置和启动脚本
+    pub fn default_bundle(&self, local_binary_path: &Path) -> DeploymentBundle {
+        self.bundle(local_binary_path, START_SCRIPT_TEMPLATE)
+    }
+
+    /// 与默认部署包相同，但行情来自 `market_ws_url`
+    pub fn soak_bundle(&self, local_binary_path: &Path, market_ws_url: &str) -> DeploymentBundle {
+        self.bundle(local_binary_path, SOAK_START_SCRIPT_TEMPLATE)
+            .var("market_ws_url", market_ws_url)
+    }
+
+    fn bundle(&self, local_binary_path: &Path, start_script: &str) -> DeploymentBundle {
+        DeploymentBundle::new()
+            .executable(local_binary_path, "kernel")
+            .template(ENV_TEMPLATE, ".env")
+            .template(STRATEGY_TEMPLATE, "config/strategy.json")
+            .template(STATE_TEMPLATE, "config/state.json")
+            .template(REPLICATION_TEMPLATE, "config/replication.json")
+            .executable_template(start_script, "start_agent.sh")
+            .var("agent_id", &self.config.name)
+            .var("remote_path", self.config.remote_deploy_path.display())
+    }
 
-        let exit_status = channel.exit_status()?;
+    pub fn execute_command(&self, sess: &Session, cmd: &str) -> Result<String> {
+        let result = self.run_command(sess, cmd);
+        audit::record(
+            AuditCategory::SshCommand,
+            cmd,
+            serde_json::json!({
+                "remote": format!("{}@{}", self.config.user, host_port(&self.config.ip, self.config.port)),
+                "exit_status": result.as_ref().ok().map(|(_, status)| *status),
+                "error": result.as_ref().err().map(|e| e.to_string()),
+            }),
+        );
+        // 传输错误后丢弃池中的会话，下次连接时重连
+        if let (Err(_), Some(pool)) = (&result, &self.pool) {
+            pool.invalidate(&self.pool_key());
+        }
+        let (output, exit_status) = result?;
         if exit_status != 0 {
             return Err(anyhow::anyhow!(
                 "Command failed with exit code {}: {}",
@@ -160,6 +276,23 @@ impl DeploymentClient {
         Ok(output)
     }
 
+    fn run_command(&self, sess: &Session, cmd: &str) -> Result<(String, i32)> {
+        let mut channel = sess.channel_session()?;
+        channel.exec(cmd)?;
+        let output = match polling(sess, || {
+            read_output(&mut channel, self.timeouts.command, &self.cancel)
+        }) {
+            Ok(output) => output,
+            Err(e) => {
+                let _ = channel.close();
+                return Err(e).with_context(|| format!("Command '{}' aborted", cmd));
+            }
+        };
+        channel.wait_close()?;
+
+        Ok((output, channel.exit_status()?))
+    }
+
     fn create_remote_directory(&self, sess: &Session) -> Result<()> {
         info!(
             "Creating remote directory: {:?}",
@@ -175,92 +308,30 @@ impl DeploymentClient {
         Ok(())
     }
 
-    fn upload_file(&self, sess: &Session, local_path: &Path, remote_name: &str) -> Result<()> {
-        if !local_path.exists() {
-            return Err(anyhow::anyhow!("Local file not found: {:?}", local_path));
-        }
-
-        let remote_path = self.config.remote_deploy_path.join(remote_name);
-        info!("Uploading {:?} to {:?}", local_path, remote_path);
+    fn upload_bundle(&self, sess: &Session, bundle: &DeploymentBundle) -> Result<()> {
+        for file in bundle.render()? {
+            let remote_path = self.config.remote_deploy_path.join(&file.destination);
+            info!("Uploading {:?}", remote_path);
 
-        let data = fs::read(local_path)?;
-        let mut remote_file = sess.scp_send(&remote_path, 0o644, data.len() as u64, None)?;
-        remote_file.write_all(&data)?;
-
-        Ok(())
-    }
-
-    fn upload_config_files(&self, sess: &Session) -> Result<()> {
-        // Create .env file
-        let env_content = "BINANCE_API_KEY=test_api_key\n\
-             BINANCE_API_SECRET=test_api_secret\n\
-             DEPLOYMENT_MODE=test\n"
-            .to_string();
-
-        let remote_env_path = self.config.remote_deploy_path.join(".env");
-        let mut remote_file =
-            sess.scp_send(&remote_env_path, 0o644, env_content.len() as u64, None)?;
-        remote_file.write_all(env_content.as_bytes())?;
-
-        // Upload strategy.json
-        let strategy_content = r#"{
-            "strategy_type": "momentum",
-            "symbol": "BTCUSDT",
-            "interval": "1h",
-            "lookback_periods": 20,
-            "threshold": 0.02
-        }"#;
-
-        let remote_strategy_path = self.config.remote_deploy_path.join("config/strategy.json");
-        let mut remote_file = sess.scp_send(
-            &remote_strategy_path,
-            0o644,
-            strategy_content.len() as u64,
-            None,
-        )?;
-        remote_file.write_all(strategy_content.as_bytes())?;
-
-        // Upload state.json
-        let state_content = r#"{
-            "funds": 1000.0,
-            "positions": {},
-            "last_update": null
-        }"#;
-
-        let remote_state_path = self.config.remote_deploy_path.join("config/state.json");
-        let mut remote_file =
-            sess.scp_send(&remote_state_path, 0o644, state_content.len() as u64, None)?;
-        remote_file.write_all(state_content.as_bytes())?;
-
-        Ok(())
-    }
+            if let Some(parent) = remote_path.parent() {
+                self.execute_command(sess, &format!("mkdir -p {:?}", parent))?;
+            }
 
-    fn make_executable(&self, sess: &Session, file_name: &str) -> Result<()> {
-        let remote_path = self.config.remote_deploy_path.join(file_name);
-        let cmd = format!("chmod +x {:?}", remote_path);
-        self.execute_command(sess, &cmd)?;
+            let mut remote_file =
+                sess.scp_send(&remote_path, file.mode, file.contents.len() as u64, None)?;
+            write_all_cancellable(&mut remote_file, &file.contents, &self.cancel)?;
+        }
         Ok(())
     }
 
-    fn create_startup_script(&self, sess: &Session) -> Result<()> {
-        let script_content = format!(
-            "#!/bin/bash\n\
-             cd {:?}\n\
-             nohup ./kernel > aurelia.log 2>&1 &\n\
-             echo $! > aurelia.pid\n\
-             echo \"Agent started with PID: $(cat aurelia.pid)\"\n",
-            self.config.remote_deploy_path
+    fn verify_bundle(&self, sess: &Session, public_key: &str) -> Result<()> {
+        info!("Verifying bundle signatures on {}", self.config.name);
+        let cmd = format!(
+            "cd {:?} && ./kernel verify-bundle --public-key {}",
+            self.config.remote_deploy_path, public_key
         );
-
-        let remote_script_path = self.config.remote_deploy_path.join("start_agent.sh");
-        let mut remote_file = sess.scp_send(
-            &remote_script_path,
-            0o755,
-            script_content.len() as u64,
-            None,
-        )?;
-        remote_file.write_all(script_content.as_bytes())?;
-
+        self.execute_command(sess, &cmd)
+            .context("Deployed bundle failed signature verification")?;
         Ok(())
     }
 
@@ -326,7 +397,7 @@ impl DeploymentClient {
 
         let mut remote_file =
             sess.scp_send(&trigger_path, 0o644, deploy_json.len() as u64, None)?;
-        remote_file.write_all(deploy_json.as_bytes())?;
+        write_all_cancellable(&mut remote_file, deploy_json.as_bytes(), &self.cancel)?;
 
         info!("Self-replication trigger sent");
         Ok(())
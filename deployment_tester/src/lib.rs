@@ -1,11 +1,17 @@
+pub mod checks;
 pub mod config;
 pub mod deployer;
 pub mod monitor;
+pub mod report;
+pub mod soak;
 pub mod test_runner;
 pub mod validator;
 
-pub use config::{ServerConfig, TestConfig};
+pub use checks::{CheckConfig, CheckContext, ValidationCheck};
+pub use config::{ServerConfig, SoakConfig, TestConfig};
 pub use deployer::DeploymentClient;
 pub use monitor::AgentMonitor;
+pub use report::{ReportConfig, ValidationReport};
+pub use soak::{MockExchange, SoakReport, SoakTest};
 pub use test_runner::TestRunner;
 pub use validator::ValidationSuite;
@@ -2,6 +2,7 @@ use crate::config::ServerConfig;
 use crate::deployer::DeploymentClient;
 use anyhow::Result;
 use chrono::{DateTime, Utc};
+use common::SshConnectionManager;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -31,8 +32,14 @@ pub struct AgentMonitor {
 }
 
 impl AgentMonitor {
+    /// 各项检查共用一个 SSH 会话，而不是每条命令重新连接
     pub fn new(config: ServerConfig) -> Self {
-        let client = DeploymentClient::new(config.clone());
+        Self::with_connection_pool(config, SshConnectionManager::default())
+    }
+
+    /// 与部署器等共享连接池
+    pub fn with_connection_pool(config: ServerConfig, pool: SshConnectionManager) -> Self {
+        let client = DeploymentClient::new(config.clone()).with_connection_pool(pool);
         Self { client, config }
     }
 
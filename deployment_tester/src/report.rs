@@ -0,0 +1,481 @@
+use crate::validator::{ValidationResult, ValidationSummary};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// 报告输出设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReportConfig {
+    pub markdown: bool,
+    pub html: bool,
+    /// 生成后以 JSON 形式 POST 到该地址
+    pub webhook_url: Option<String>,
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            markdown: true,
+            html: true,
+            webhook_url: None,
+        }
+    }
+}
+
+/// Change in one test's outcome since the previous run
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trend {
+    Fixed,
+    Regressed,
+    New,
+    Unchanged,
+}
+
+/// One resource measurement for the charts
+#[derive(Debug, Clone)]
+struct ResourceBar {
+    server: String,
+    value: f64,
+    limit: Option<f64>,
+}
+
+/// Human-readable view of a validation run, compared against the previous one.
+pub struct ValidationReport<'a> {
+    current: &'a ValidationSummary,
+    previous: Option<&'a ValidationSummary>,
+}
+
+fn result_key(result: &ValidationResult) -> (String, String) {
+    (
+        result.test_name.clone(),
+        result.server.clone().unwrap_or_else(|| "N/A".to_string()),
+    )
+}
+
+fn status(passed: bool) -> &'static str {
+    if passed {
+        "✅ PASS"
+    } else {
+        "❌ FAIL"
+    }
+}
+
+fn trend_label(trend: &Trend) -> &'static str {
+    match trend {
+        Trend::Fixed => "fixed",
+        Trend::Regressed => "regressed",
+        Trend::New => "new",
+        Trend::Unchanged => "",
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Details without the bulky log excerpts, as `key=value` pairs
+fn details_text(result: &ValidationResult) -> String {
+    let details: BTreeMap<_, _> = result
+        .details
+        .iter()
+        .filter(|(key, _)| key.as_str() != "recent_logs")
+        .collect();
+    details
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl<'a> ValidationReport<'a> {
+    pub fn new(current: &'a ValidationSummary, previous: Option<&'a ValidationSummary>) -> Self {
+        Self { current, previous }
+    }
+
+    pub fn trend(&self, result: &ValidationResult) -> Trend {
+        let Some(previous) = self.previous else {
+            return Trend::New;
+        };
+        let key = result_key(result);
+        match previous.results.iter().find(|r| result_key(r) == key) {
+            None => Trend::New,
+            Some(before) if !before.passed && result.passed => Trend::Fixed,
+            Some(before) if before.passed && !result.passed => Trend::Regressed,
+            Some(_) => Trend::Unchanged,
+        }
+    }
+
+    /// Results grouped by test, in the order the suite ran them
+    fn by_test(&self) -> Vec<(&str, Vec<&ValidationResult>)> {
+        let mut groups: Vec<(&str, Vec<&ValidationResult>)> = Vec::new();
+        for result in &self.current.results {
+            match groups
+                .iter_mut()
+                .find(|(name, _)| *name == result.test_name)
+            {
+                Some((_, results)) => results.push(result),
+                None => groups.push((result.test_name.as_str(), vec![result])),
+            }
+        }
+        groups
+    }
+
+    fn resource_bars(&self, metric: &str, limit: &str) -> Vec<ResourceBar> {
+        self.current
+            .results
+            .iter()
+            .filter(|r| r.test_name == "resource_usage")
+            .filter_map(|r| {
+                Some(ResourceBar {
+                    server: r.server.clone().unwrap_or_default(),
+                    value: r.details.get(metric)?.as_f64()?,
+                    limit: r.details.get(limit).and_then(|v| v.as_f64()),
+                })
+            })
+            .collect()
+    }
+
+    fn charts(&self) -> Vec<(&'static str, Vec<ResourceBar>)> {
+        [
+            ("CPU (%)", self.resource_bars("cpu_percent", "cpu_limit")),
+            (
+                "Memory (MB)",
+                self.resource_bars("memory_mb", "memory_limit_mb"),
+            ),
+            ("Disk (GB)", self.resource_bars("disk_gb", "disk_limit_gb")),
+        ]
+        .into_iter()
+        .filter(|(_, bars)| !bars.is_empty())
+        .collect()
+    }
+
+    fn rate_change(&self) -> Option<f64> {
+        self.previous
+            .map(|previous| self.current.success_rate - previous.success_rate)
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let summary = self.current;
+        let mut out = String::new();
+        let _ = writeln!(out, "# Validation Report\n");
+        let _ = writeln!(
+            out,
+            "Run: {} → {}\n",
+            summary.start_time.format("%Y-%m-%d %H:%M:%S UTC"),
+            summary.end_time.format("%Y-%m-%d %H:%M:%S UTC")
+        );
+        let _ = writeln!(out, "| Total | Passed | Failed | Success rate |");
+        let _ = writeln!(out, "|---|---|---|---|");
+        let _ = writeln!(
+            out,
+            "| {} | {} | {} | {:.1}% |\n",
+            summary.total_tests, summary.passed, summary.failed, summary.success_rate
+        );
+
+        match (self.previous, self.rate_change()) {
+            (Some(previous), Some(change)) => {
+                let _ = writeln!(
+                    out,
+                    "Compared with the run of {}: success rate {:+.1} points.\n",
+                    previous.end_time.format("%Y-%m-%d %H:%M:%S UTC"),
+                    change
+                );
+            }
+            _ => {
+                let _ = writeln!(out, "No previous run to compare with.\n");
+            }
+        }
+
+        for (test_name, results) in self.by_test() {
+            let _ = writeln!(out, "## {}\n", test_name);
+            let _ = writeln!(out, "| Server | Status | Trend | Details | Errors |");
+            let _ = writeln!(out, "|---|---|---|---|---|");
+            for result in results {
+                let (_, server) = result_key(result);
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | {} | {} | {} |",
+                    server,
+                    status(result.passed),
+                    trend_label(&self.trend(result)),
+                    details_text(result).replace('|', "\\|"),
+                    result.errors.join("; ").replace('|', "\\|")
+                );
+            }
+            let _ = writeln!(out);
+        }
+
+        let charts = self.charts();
+        if !charts.is_empty() {
+            let _ = writeln!(out, "## Resources\n");
+            for (title, bars) in charts {
+                let _ = writeln!(out, "{}\n\n```", title);
+                let max = bars
+                    .iter()
+                    .map(|bar| bar.value.max(bar.limit.unwrap_or(0.0)))
+                    .fold(0.0, f64::max);
+                for bar in bars {
+                    let width = if max > 0.0 {
+                        (bar.value / max * 40.0).round() as usize
+                    } else {
+                        0
+                    };
+                    let _ = writeln!(
+                        out,
+                        "{:<24} {:<40} {:.1}{}",
+                        bar.server,
+                        "█".repeat(width),
+                        bar.value,
+                        bar.limit
+                            .map(|limit| format!(" / {:.1}", limit))
+                            .unwrap_or_default()
+                    );
+                }
+                let _ = writeln!(out, "```\n");
+            }
+        }
+
+        out
+    }
+
+    pub fn to_html(&self) -> String {
+        let summary = self.current;
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Validation Report</title>\n\
+             <style>body{{font-family:sans-serif;margin:2em}}table{{border-collapse:collapse;margin-bottom:1.5em}}\
+             td,th{{border:1px solid #ccc;padding:4px 8px;text-align:left}}.pass{{color:#2e7d32}}.fail{{color:#c62828}}</style>\n\
+             </head>\n<body>\n<h1>Validation Report</h1>"
+        );
+        let _ = writeln!(
+            out,
+            "<p>Run: {} → {}</p>",
+            summary.start_time.format("%Y-%m-%d %H:%M:%S UTC"),
+            summary.end_time.format("%Y-%m-%d %H:%M:%S UTC")
+        );
+        let _ = writeln!(
+            out,
+            "<table><tr><th>Total</th><th>Passed</th><th>Failed</th><th>Success rate</th></tr>\
+             <tr><td>{}</td><td>{}</td><td>{}</td><td>{:.1}%</td></tr></table>",
+            summary.total_tests, summary.passed, summary.failed, summary.success_rate
+        );
+        match (self.previous, self.rate_change()) {
+            (Some(previous), Some(change)) => {
+                let _ = writeln!(
+                    out,
+                    "<p>Compared with the run of {}: success rate {:+.1} points.</p>",
+                    previous.end_time.format("%Y-%m-%d %H:%M:%S UTC"),
+                    change
+                );
+            }
+            _ => {
+                let _ = writeln!(out, "<p>No previous run to compare with.</p>");
+            }
+        }
+
+        for (test_name, results) in self.by_test() {
+            let _ = writeln!(out, "<h2>{}</h2>", escape_html(test_name));
+            let _ = writeln!(
+                out,
+                "<table><tr><th>Server</th><th>Status</th><th>Trend</th><th>Details</th><th>Errors</th></tr>"
+            );
+            for result in results {
+                let (_, server) = result_key(result);
+                let _ = writeln!(
+                    out,
+                    "<tr><td>{}</td><td class=\"{}\">{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    escape_html(&server),
+                    if result.passed { "pass" } else { "fail" },
+                    status(result.passed),
+                    trend_label(&self.trend(result)),
+                    escape_html(&details_text(result)),
+                    escape_html(&result.errors.join("; "))
+                );
+            }
+            let _ = writeln!(out, "</table>");
+        }
+
+        let charts = self.charts();
+        if !charts.is_empty() {
+            let _ = writeln!(out, "<h2>Resources</h2>");
+            for (title, bars) in charts {
+                let _ = writeln!(out, "<h3>{}</h3>", title);
+                out.push_str(&svg_bar_chart(&bars));
+            }
+        }
+
+        let _ = writeln!(out, "</body>\n</html>");
+        out
+    }
+}
+
+/// Horizontal bar chart with the limit, if any, drawn as a red tick
+fn svg_bar_chart(bars: &[ResourceBar]) -> String {
+    const ROW: usize = 24;
+    const LABEL: f64 = 200.0;
+    const WIDTH: f64 = 400.0;
+
+    let max = bars
+        .iter()
+        .map(|bar| bar.value.max(bar.limit.unwrap_or(0.0)))
+        .fold(0.0, f64::max);
+    let scale = if max > 0.0 { WIDTH / max } else { 0.0 };
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        LABEL + WIDTH + 80.0,
+        bars.len() * ROW
+    );
+    for (i, bar) in bars.iter().enumerate() {
+        let y = i * ROW;
+        let _ = writeln!(
+            svg,
+            "<text x=\"0\" y=\"{}\" font-size=\"12\">{}</text>\
+             <rect x=\"{}\" y=\"{}\" width=\"{:.1}\" height=\"16\" fill=\"#4a90d9\"/>\
+             <text x=\"{:.1}\" y=\"{}\" font-size=\"12\">{:.1}</text>",
+            y + 14,
+            escape_html(&bar.server),
+            LABEL,
+            y + 2,
+            bar.value * scale,
+            LABEL + bar.value * scale + 4.0,
+            y + 14,
+            bar.value
+        );
+        if let Some(limit) = bar.limit {
+            let x = LABEL + limit * scale;
+            let _ = writeln!(
+                svg,
+                "<line x1=\"{:.1}\" y1=\"{}\" x2=\"{:.1}\" y2=\"{}\" stroke=\"#c62828\" stroke-width=\"2\"/>",
+                x,
+                y,
+                x,
+                y + ROW
+            );
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Write the Markdown and HTML reports next to `json_path`, returning the files written
+pub fn write_reports(
+    config: &ReportConfig,
+    json_path: &Path,
+    current: &ValidationSummary,
+    previous: Option<&ValidationSummary>,
+) -> Result<Vec<PathBuf>> {
+    let report = ValidationReport::new(current, previous);
+    let mut written = Vec::new();
+    if config.markdown {
+        let path = json_path.with_extension("md");
+        std::fs::write(&path, report.to_markdown())?;
+        written.push(path);
+    }
+    if config.html {
+        let path = json_path.with_extension("html");
+        std::fs::write(&path, report.to_html())?;
+        written.push(path);
+    }
+    for path in &written {
+        info!("Validation report saved to {:?}", path);
+    }
+    Ok(written)
+}
+
+/// POST the summary and the Markdown report to `url`
+pub async fn post_report(
+    url: &str,
+    current: &ValidationSummary,
+    previous: Option<&ValidationSummary>,
+) -> Result<()> {
+    let report = ValidationReport::new(current, previous);
+    reqwest::Client::new()
+        .post(url)
+        .json(&serde_json::json!({
+            "total_tests": current.total_tests,
+            "passed": current.passed,
+            "failed": current.failed,
+            "success_rate": current.success_rate,
+            "success_rate_change": report.rate_change(),
+            "markdown": report.to_markdown(),
+        }))
+        .send()
+        .await?
+        .error_for_status()?;
+    info!("Validation report posted to {}", url);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use std::collections::HashMap;
+
+    fn result(test_name: &str, passed: bool, details: &[(&str, f64)]) -> ValidationResult {
+        ValidationResult {
+            test_name: test_name.to_string(),
+            server: Some("server-1".to_string()),
+            timestamp: Utc::now(),
+            passed,
+            details: details
+                .iter()
+                .map(|(key, value)| (key.to_string(), serde_json::json!(value)))
+                .collect::<HashMap<_, _>>(),
+            errors: Vec::new(),
+        }
+    }
+
+    fn summary(results: Vec<ValidationResult>) -> ValidationSummary {
+        let passed = results.iter().filter(|r| r.passed).count();
+        ValidationSummary {
+            start_time: Utc::now(),
+            end_time: Utc::now(),
+            total_tests: results.len(),
+            passed,
+            failed: results.len() - passed,
+            success_rate: passed as f64 / results.len() as f64 * 100.0,
+            results,
+        }
+    }
+
+    #[test]
+    fn test_report_compares_with_previous_run() {
+        let previous = summary(vec![
+            result("agent_running", true, &[]),
+            result("log_activity", false, &[]),
+        ]);
+        let current = summary(vec![
+            result("agent_running", false, &[]),
+            result("log_activity", true, &[]),
+            result(
+                "resource_usage",
+                true,
+                &[("cpu_percent", 42.0), ("cpu_limit", 80.0)],
+            ),
+        ]);
+        let report = ValidationReport::new(&current, Some(&previous));
+
+        assert_eq!(report.trend(&current.results[0]), Trend::Regressed);
+        assert_eq!(report.trend(&current.results[1]), Trend::Fixed);
+        assert_eq!(report.trend(&current.results[2]), Trend::New);
+
+        let markdown = report.to_markdown();
+        assert!(markdown.contains("success rate +16.7 points"));
+        assert!(markdown.contains("| server-1 | ❌ FAIL | regressed |"));
+        assert!(markdown.contains("42.0 / 80.0"));
+
+        let html = report.to_html();
+        assert!(html.contains("<svg"));
+        assert!(html.contains("<h2>resource_usage</h2>"));
+    }
+}
@@ -0,0 +1,435 @@
+use crate::config::{ServerConfig, SoakConfig, SoakThresholds};
+use crate::deployer::DeploymentClient;
+use crate::monitor::AgentMonitor;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use common::host_port;
+use futures_util::SinkExt;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{info, warn};
+
+/// Replays a recorded Binance trade stream to every agent that connects.
+///
+/// Each connection cycles through the recording at `rate_per_second`, restamping
+/// the trade and event times so that the agent sees live-looking data.
+pub struct MockExchange {
+    ticks: Arc<Vec<serde_json::Value>>,
+    rate_per_second: f64,
+    sent: Arc<AtomicU64>,
+}
+
+impl MockExchange {
+    pub fn from_recording(path: &Path, rate_per_second: f64) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read recording {:?}", path))?;
+        let ticks = content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<serde_json::Value>, _>>()
+            .with_context(|| format!("Invalid trade in recording {:?}", path))?;
+        if ticks.is_empty() {
+            return Err(anyhow::anyhow!("Recording {:?} has no trades", path));
+        }
+        if rate_per_second <= 0.0 {
+            return Err(anyhow::anyhow!("Replay rate must be positive"));
+        }
+
+        Ok(Self {
+            ticks: Arc::new(ticks),
+            rate_per_second,
+            sent: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    /// Number of trades sent so far, across all connections
+    pub fn sent(&self) -> Arc<AtomicU64> {
+        self.sent.clone()
+    }
+
+    pub async fn serve(self, listener: TcpListener) -> Result<()> {
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            info!("Mock exchange accepted connection from {}", peer);
+            let ticks = self.ticks.clone();
+            let sent = self.sent.clone();
+            let period = Duration::from_secs_f64(1.0 / self.rate_per_second);
+            tokio::spawn(async move {
+                if let Err(e) = replay(stream, ticks, period, sent).await {
+                    warn!("Mock exchange connection from {} closed: {}", peer, e);
+                }
+            });
+        }
+    }
+}
+
+async fn replay(
+    stream: TcpStream,
+    ticks: Arc<Vec<serde_json::Value>>,
+    period: Duration,
+    sent: Arc<AtomicU64>,
+) -> Result<()> {
+    let mut ws = tokio_tungstenite::accept_async(stream).await?;
+    let mut interval = time::interval(period);
+    for tick in ticks.iter().cycle() {
+        interval.tick().await;
+        let text = restamp(tick, Utc::now().timestamp_millis()).to_string();
+        ws.send(Message::Text(text)).await?;
+        sent.fetch_add(1, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+fn restamp(tick: &serde_json::Value, now_ms: i64) -> serde_json::Value {
+    let mut tick = tick.clone();
+    if let Some(fields) = tick.as_object_mut() {
+        for key in ["T", "E"] {
+            if fields.contains_key(key) {
+                fields.insert(key.to_string(), now_ms.into());
+            }
+        }
+    }
+    tick
+}
+
+/// Mirrors the agent's `/api/pipeline` response
+#[derive(Debug, Clone, Default, Deserialize)]
+struct PipelineMetrics {
+    event_backlog: usize,
+    decisions: u64,
+    decision_latency_total_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SoakSample {
+    pub elapsed_seconds: u64,
+    pub ticks_sent: u64,
+    pub memory_mb: Option<f64>,
+    pub event_backlog: usize,
+    pub decisions: u64,
+    /// Average decision latency over the window since the previous sample
+    pub decision_latency_ms: Option<f64>,
+    /// Why the agent's pipeline metrics could not be read, for a failed sample
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SoakReport {
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub samples: Vec<SoakSample>,
+    pub violations: Vec<String>,
+    pub passed: bool,
+}
+
+impl SoakReport {
+    pub fn save(&self, path: &str) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        info!("Soak test results saved to {}", path);
+        Ok(())
+    }
+}
+
+/// Check the samples against the thresholds, reporting the first breach of each kind.
+/// Memory growth is measured from the first sample taken after the warm-up.
+pub fn evaluate(
+    samples: &[SoakSample],
+    warmup_seconds: u64,
+    thresholds: &SoakThresholds,
+) -> Vec<String> {
+    let mut violations = Vec::new();
+
+    if let Some((sample, error)) = samples
+        .iter()
+        .find_map(|s| s.error.as_ref().map(|error| (s, error)))
+    {
+        violations.push(format!(
+            "Agent pipeline unreachable after {}s: {}",
+            sample.elapsed_seconds, error
+        ));
+    }
+
+    if let Some(sample) = samples
+        .iter()
+        .find(|s| s.event_backlog > thresholds.max_event_backlog)
+    {
+        violations.push(format!(
+            "Event backlog {} exceeded {} after {}s",
+            sample.event_backlog, thresholds.max_event_backlog, sample.elapsed_seconds
+        ));
+    }
+
+    if let Some((sample, latency)) = samples.iter().find_map(|s| {
+        s.decision_latency_ms
+            .filter(|latency| *latency > thresholds.max_decision_latency_ms)
+            .map(|latency| (s, latency))
+    }) {
+        violations.push(format!(
+            "Decision latency {:.1}ms exceeded {:.1}ms after {}s",
+            latency, thresholds.max_decision_latency_ms, sample.elapsed_seconds
+        ));
+    }
+
+    let mut after_warmup = samples
+        .iter()
+        .filter(|s| s.elapsed_seconds >= warmup_seconds)
+        .filter_map(|s| s.memory_mb.map(|memory| (s, memory)));
+    if let Some((_, baseline)) = after_warmup.next() {
+        if let Some((sample, memory)) =
+            after_warmup.find(|(_, memory)| memory - baseline > thresholds.max_memory_growth_mb)
+        {
+            violations.push(format!(
+                "Memory grew by {:.1}MB (from {:.1}MB to {:.1}MB) after {}s, limit is {:.1}MB",
+                memory - baseline,
+                baseline,
+                memory,
+                sample.elapsed_seconds,
+                thresholds.max_memory_growth_mb
+            ));
+        }
+    }
+
+    violations
+}
+
+/// Long-running load test: deploys the agent against a local mock exchange and
+/// watches event bus lag, memory growth and decision latency.
+pub struct SoakTest {
+    server: ServerConfig,
+    config: SoakConfig,
+    binary_path: PathBuf,
+}
+
+impl SoakTest {
+    pub fn new(server: ServerConfig, config: SoakConfig, binary_path: PathBuf) -> Self {
+        Self {
+            server,
+            config,
+            binary_path,
+        }
+    }
+
+    pub fn market_ws_url(&self) -> String {
+        format!(
+            "ws://{}/ws",
+            host_port(
+                &self.config.mock_exchange_host,
+                self.config.mock_exchange_port
+            )
+        )
+    }
+
+    pub async fn run(&self) -> Result<SoakReport> {
+        let start_time = Utc::now();
+        let exchange = MockExchange::from_recording(
+            &self.config.recording,
+            self.config.replay_rate_per_second,
+        )?;
+        let sent = exchange.sent();
+        let listener = TcpListener::bind(("0.0.0.0", self.config.mock_exchange_port))
+            .await
+            .context("Failed to bind mock exchange")?;
+        let exchange_handle = tokio::spawn(exchange.serve(listener));
+
+        info!(
+            "Deploying {} against mock exchange at {}",
+            self.server.name,
+            self.market_ws_url()
+        );
+        let client = DeploymentClient::new(self.server.clone());
+        let bundle = client.soak_bundle(&self.binary_path, &self.market_ws_url());
+        tokio::task::spawn_blocking(move || client.deploy_bundle(&bundle)).await??;
+
+        let samples = self.sample(sent).await;
+        exchange_handle.abort();
+
+        let mut violations = evaluate(
+            &samples,
+            self.config.warmup_minutes * 60,
+            &self.config.thresholds,
+        );
+        if samples.last().is_none_or(|s| s.ticks_sent == 0) {
+            violations.push("Agent never consumed market data from the mock exchange".to_string());
+        }
+
+        Ok(SoakReport {
+            start_time,
+            end_time: Utc::now(),
+            passed: violations.is_empty(),
+            samples,
+            violations,
+        })
+    }
+
+    /// Sample until the configured duration is over or a threshold is breached
+    async fn sample(&self, sent: Arc<AtomicU64>) -> Vec<SoakSample> {
+        let http = reqwest::Client::new();
+        let url = format!(
+            "http://{}/api/pipeline",
+            host_port(&self.server.ip, self.config.agent_api_port)
+        );
+        let started = Instant::now();
+        let total_duration = Duration::from_secs(self.config.duration_minutes * 60);
+        let mut interval = time::interval(Duration::from_secs(self.config.sample_interval_seconds));
+        interval.tick().await;
+
+        let mut samples = Vec::new();
+        let mut previous = PipelineMetrics::default();
+        while started.elapsed() < total_duration {
+            interval.tick().await;
+
+            let pipeline = match fetch_pipeline(&http, &url).await {
+                Ok(pipeline) => pipeline,
+                Err(e) => {
+                    warn!(
+                        "Stopping soak test early, failed to read agent pipeline metrics: {}",
+                        e
+                    );
+                    samples.push(SoakSample {
+                        elapsed_seconds: started.elapsed().as_secs(),
+                        ticks_sent: sent.load(Ordering::Relaxed),
+                        memory_mb: None,
+                        event_backlog: 0,
+                        decisions: previous.decisions,
+                        decision_latency_ms: None,
+                        error: Some(e.to_string()),
+                    });
+                    break;
+                }
+            };
+            let monitor = AgentMonitor::new(self.server.clone());
+            let memory_mb =
+                match tokio::task::spawn_blocking(move || monitor.get_resource_metrics()).await {
+                    Ok(Ok(metrics)) => Some(metrics.memory_mb),
+                    Ok(Err(e)) => {
+                        warn!("Failed to read agent memory: {}", e);
+                        None
+                    }
+                    Err(e) => {
+                        warn!("Memory check panicked: {}", e);
+                        None
+                    }
+                };
+
+            let window_decisions = pipeline.decisions.saturating_sub(previous.decisions);
+            let decision_latency_ms = (window_decisions > 0).then(|| {
+                (pipeline.decision_latency_total_ms - previous.decision_latency_total_ms)
+                    / window_decisions as f64
+            });
+            let sample = SoakSample {
+                elapsed_seconds: started.elapsed().as_secs(),
+                ticks_sent: sent.load(Ordering::Relaxed),
+                memory_mb,
+                event_backlog: pipeline.event_backlog,
+                decisions: pipeline.decisions,
+                decision_latency_ms,
+                error: None,
+            };
+            info!(
+                "Soak sample at {}s: {} ticks sent, backlog {}, memory {:?}MB, latency {:?}ms",
+                sample.elapsed_seconds,
+                sample.ticks_sent,
+                sample.event_backlog,
+                sample.memory_mb,
+                sample.decision_latency_ms
+            );
+            samples.push(sample);
+            previous = pipeline;
+
+            let violations = evaluate(
+                &samples,
+                self.config.warmup_minutes * 60,
+                &self.config.thresholds,
+            );
+            if !violations.is_empty() {
+                warn!("Stopping soak test early: {}", violations.join("; "));
+                break;
+            }
+        }
+        samples
+    }
+}
+
+async fn fetch_pipeline(http: &reqwest::Client, url: &str) -> Result<PipelineMetrics> {
+    Ok(http
+        .get(url)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(elapsed_seconds: u64, memory_mb: f64, latency: f64, backlog: usize) -> SoakSample {
+        SoakSample {
+            elapsed_seconds,
+            ticks_sent: elapsed_seconds * 100,
+            memory_mb: Some(memory_mb),
+            event_backlog: backlog,
+            decisions: elapsed_seconds,
+            decision_latency_ms: Some(latency),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_evaluate_flags_leaks_after_warmup() {
+        let thresholds = SoakThresholds {
+            max_event_backlog: 100,
+            max_memory_growth_mb: 50.0,
+            max_decision_latency_ms: 200.0,
+        };
+        // Start-up allocations during the warm-up do not count as growth
+        let healthy = vec![
+            sample(60, 40.0, 20.0, 3),
+            sample(600, 120.0, 25.0, 5),
+            sample(1200, 150.0, 30.0, 2),
+        ];
+        assert!(evaluate(&healthy, 600, &thresholds).is_empty());
+
+        let leaking = vec![sample(600, 120.0, 25.0, 5), sample(1200, 180.0, 250.0, 400)];
+        let violations = evaluate(&leaking, 600, &thresholds);
+        assert_eq!(violations.len(), 3);
+        assert!(violations[2].starts_with("Memory grew by 60.0MB"));
+    }
+
+    #[test]
+    fn test_evaluate_fails_on_unreachable_agent() {
+        let thresholds = SoakThresholds {
+            max_event_backlog: 100,
+            max_memory_growth_mb: 50.0,
+            max_decision_latency_ms: 200.0,
+        };
+        let mut unreachable = sample(1200, 0.0, 0.0, 0);
+        unreachable.memory_mb = None;
+        unreachable.decision_latency_ms = None;
+        unreachable.error = Some("connection refused".to_string());
+        let samples = vec![sample(600, 120.0, 25.0, 5), unreachable];
+        assert_eq!(
+            evaluate(&samples, 600, &thresholds),
+            vec!["Agent pipeline unreachable after 1200s: connection refused".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_restamp_only_touches_timestamps() {
+        let tick = serde_json::json!({"e": "trade", "s": "BTCUSDT", "p": "70000.0", "T": 1});
+        let restamped = restamp(&tick, 42);
+        assert_eq!(restamped["T"], 42);
+        assert_eq!(restamped["p"], "70000.0");
+        assert!(restamped.get("E").is_none());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::config::TestConfig;
 use crate::deployer::DeploymentClient;
 use crate::monitor::AgentMonitor;
+use crate::soak::SoakTest;
 use crate::validator::ValidationSuite;
 use anyhow::{Context, Result};
 use std::path::PathBuf;
@@ -145,7 +146,7 @@ impl TestRunner {
         let summary = validator.run_full_validation().await?;
 
         validator.print_summary();
-        validator.save_results("validation_results.json")?;
+        validator.publish_results("validation_results.json").await?;
 
         if summary.failed > 0 {
             warn!("{} tests failed", summary.failed);
@@ -200,6 +201,39 @@ impl TestRunner {
         Ok(())
     }
 
+    /// 部署到主服务器并回放录制行情数小时，超出积压、内存或延迟阈值即失败
+    pub async fn run_soak_test(&self) -> Result<()> {
+        let primary = self
+            .config
+            .get_primary_server()
+            .ok_or_else(|| anyhow::anyhow!("No primary server configured"))?;
+
+        info!(
+            "Starting soak test on {} for {} minutes...",
+            primary.name, self.config.soak.duration_minutes
+        );
+        let soak = SoakTest::new(
+            primary.clone(),
+            self.config.soak.clone(),
+            self.binary_path.clone(),
+        );
+        let report = soak.run().await?;
+        report.save("soak_results.json")?;
+
+        if !report.passed {
+            for violation in &report.violations {
+                error!("✗ {}", violation);
+            }
+            return Err(anyhow::anyhow!(
+                "Soak test failed with {} violation(s)",
+                report.violations.len()
+            ));
+        }
+
+        info!("✓ Soak test passed ({} samples)", report.samples.len());
+        Ok(())
+    }
+
     pub async fn cleanup(&self) -> Result<()> {
         info!("Cleaning up test deployment...");
 
@@ -229,6 +263,7 @@ impl TestRunner {
             "replication" => self.test_self_replication().await,
             "validation" => self.run_validation().await,
             "monitor" => self.continuous_monitoring().await,
+            "soak" => self.run_soak_test().await,
             _ => Err(anyhow::anyhow!("Unknown test: {}", test_name)),
         }
     }
@@ -1,10 +1,12 @@
-use crate::config::{ServerConfig, ServerRole, TestConfig};
-use crate::monitor::AgentMonitor;
+use crate::checks::{default_checks, CheckConfig, CheckContext, ValidationCheck};
+use crate::config::TestConfig;
+use crate::report;
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use tracing::info;
+use std::path::Path;
+use tracing::{info, warn};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValidationResult {
@@ -29,320 +31,68 @@ pub struct ValidationSummary {
 
 pub struct ValidationSuite {
     config: TestConfig,
+    checks: Vec<Box<dyn ValidationCheck>>,
     results: Vec<ValidationResult>,
 }
 
 impl ValidationSuite {
+    /// 创建包含默认检查的套件
     pub fn new(config: TestConfig) -> Self {
         Self {
             config,
+            checks: default_checks(),
             results: Vec::new(),
         }
     }
 
-    pub async fn run_full_validation(&mut self) -> Result<ValidationSummary> {
-        let start_time = Utc::now();
-        info!("Starting full validation suite");
-
-        // Test 1: Agent Running
-        self.validate_agents_running().await?;
-
-        // Test 2: Resource Usage
-        self.validate_resource_usage().await?;
-
-        // Test 3: Log Activity
-        self.validate_log_activity().await?;
-
-        // Test 4: Autonomous Behavior
-        self.validate_autonomous_behavior().await?;
-
-        // Test 5: Network Communication
-        self.validate_network_communication().await?;
-
-        // Test 6: Self-Replication
-        let primary = self.config.get_primary_server().cloned();
-        let replicas: Vec<_> = self
-            .config
-            .get_replica_servers()
-            .into_iter()
-            .cloned()
-            .collect();
-        if let Some(primary_server) = primary {
-            if !replicas.is_empty() {
-                self.validate_self_replication(&primary_server, &replicas[0])
-                    .await?;
-            }
-        }
-
-        let end_time = Utc::now();
-        let summary = self.generate_summary(start_time, end_time);
-
-        info!(
-            "Validation suite completed: {} passed, {} failed",
-            summary.passed, summary.failed
-        );
-
-        Ok(summary)
+    /// 追加一项检查，在已注册的检查之后运行
+    pub fn register(&mut self, check: Box<dyn ValidationCheck>) {
+        self.checks.push(check);
     }
 
-    async fn validate_agents_running(&mut self) -> Result<()> {
-        info!("Validating agent processes...");
-
-        for server in &self.config.test_environments {
-            let monitor = AgentMonitor::new(server.clone());
-            let mut result = ValidationResult {
-                test_name: "agent_running".to_string(),
-                server: Some(server.name.clone()),
-                timestamp: Utc::now(),
-                passed: false,
-                details: HashMap::new(),
-                errors: Vec::new(),
-            };
-
-            match monitor.check_agent_health() {
-                Ok(health) => {
-                    result.passed = health.is_running;
-                    result.details.insert(
-                        "is_running".to_string(),
-                        serde_json::json!(health.is_running),
-                    );
-                    result
-                        .details
-                        .insert("cpu_usage".to_string(), serde_json::json!(health.cpu_usage));
-                    result
-                        .details
-                        .insert("memory_mb".to_string(), serde_json::json!(health.memory_mb));
-
-                    if !health.errors.is_empty() {
-                        result.errors = health.errors;
-                    }
-                }
-                Err(e) => {
-                    result.errors.push(format!("Health check failed: {}", e));
-                }
-            }
-
-            self.results.push(result);
-        }
-
-        Ok(())
+    pub fn with_check(mut self, check: impl ValidationCheck + 'static) -> Self {
+        self.register(Box::new(check));
+        self
     }
 
-    async fn validate_resource_usage(&mut self) -> Result<()> {
-        info!("Validating resource usage...");
-
-        let limits = &self.config.test_settings.resource_limits;
-
-        for server in &self.config.test_environments {
-            let monitor = AgentMonitor::new(server.clone());
-            let mut result = ValidationResult {
-                test_name: "resource_usage".to_string(),
-                server: Some(server.name.clone()),
-                timestamp: Utc::now(),
-                passed: false,
-                details: HashMap::new(),
-                errors: Vec::new(),
-            };
-
-            match monitor.get_resource_metrics() {
-                Ok(metrics) => {
-                    let cpu_ok = metrics.cpu_percent < limits.max_cpu_percent;
-                    let mem_ok = metrics.memory_mb < limits.max_memory_mb as f64;
-                    let disk_ok = metrics.disk_usage_gb < limits.max_disk_gb as f64;
-
-                    result.passed = cpu_ok && mem_ok && disk_ok;
-                    result.details.insert(
-                        "cpu_percent".to_string(),
-                        serde_json::json!(metrics.cpu_percent),
-                    );
-                    result.details.insert(
-                        "cpu_limit".to_string(),
-                        serde_json::json!(limits.max_cpu_percent),
-                    );
-                    result.details.insert(
-                        "memory_mb".to_string(),
-                        serde_json::json!(metrics.memory_mb),
-                    );
-                    result.details.insert(
-                        "memory_limit_mb".to_string(),
-                        serde_json::json!(limits.max_memory_mb),
-                    );
-                    result.details.insert(
-                        "disk_gb".to_string(),
-                        serde_json::json!(metrics.disk_usage_gb),
-                    );
-                    result.details.insert(
-                        "disk_limit_gb".to_string(),
-                        serde_json::json!(limits.max_disk_gb),
-                    );
-                }
-                Err(e) => {
-                    result.errors.push(format!("Resource check failed: {}", e));
-                }
-            }
-
-            self.results.push(result);
-        }
-
-        Ok(())
+    /// 已注册检查的名称
+    pub fn check_names(&self) -> Vec<&str> {
+        self.checks.iter().map(|check| check.name()).collect()
     }
 
-    async fn validate_log_activity(&mut self) -> Result<()> {
-        info!("Validating log activity...");
-
-        for server in &self.config.test_environments {
-            let monitor = AgentMonitor::new(server.clone());
-            let mut result = ValidationResult {
-                test_name: "log_activity".to_string(),
-                server: Some(server.name.clone()),
-                timestamp: Utc::now(),
-                passed: false,
-                details: HashMap::new(),
-                errors: Vec::new(),
-            };
-
-            match monitor.check_log_activity() {
-                Ok((active, logs)) => {
-                    result.passed = active;
-                    result
-                        .details
-                        .insert("has_activity".to_string(), serde_json::json!(active));
-                    result
-                        .details
-                        .insert("log_count".to_string(), serde_json::json!(logs.len()));
-
-                    if !logs.is_empty() {
-                        result.details.insert(
-                            "recent_logs".to_string(),
-                            serde_json::json!(logs.iter().take(5).collect::<Vec<_>>()),
-                        );
-                    }
-                }
-                Err(e) => {
-                    result.errors.push(format!("Log check failed: {}", e));
-                }
-            }
-
-            self.results.push(result);
-        }
-
-        Ok(())
-    }
-
-    async fn validate_autonomous_behavior(&mut self) -> Result<()> {
-        info!("Validating autonomous behavior...");
-
-        for server in &self.config.test_environments {
-            let monitor = AgentMonitor::new(server.clone());
-            let mut result = ValidationResult {
-                test_name: "autonomous_behavior".to_string(),
-                server: Some(server.name.clone()),
-                timestamp: Utc::now(),
-                passed: false,
-                details: HashMap::new(),
-                errors: Vec::new(),
-            };
-
-            match monitor.check_autonomous_behavior() {
-                Ok(behaviors) => {
-                    let has_any_behavior = behaviors.values().any(|&v| v);
-                    result.passed = has_any_behavior;
+    pub async fn run_full_validation(&mut self) -> Result<ValidationSummary> {
+        let start_time = Utc::now();
+        info!("Starting full validation suite");
 
-                    for (key, value) in behaviors {
-                        result.details.insert(key, serde_json::json!(value));
-                    }
-                }
-                Err(e) => {
-                    result.errors.push(format!("Behavior check failed: {}", e));
-                }
+        let default_settings = CheckConfig::default();
+        for check in &self.checks {
+            let settings = self
+                .config
+                .checks
+                .get(check.name())
+                .unwrap_or(&default_settings);
+            if !settings.enabled {
+                info!("Skipping disabled check {}", check.name());
+                continue;
             }
 
-            self.results.push(result);
-        }
-
-        Ok(())
-    }
-
-    async fn validate_network_communication(&mut self) -> Result<()> {
-        info!("Validating network communication...");
-
-        for server in &self.config.test_environments {
-            let monitor = AgentMonitor::new(server.clone());
-            let mut result = ValidationResult {
-                test_name: "network_communication".to_string(),
-                server: Some(server.name.clone()),
-                timestamp: Utc::now(),
-                passed: false,
-                details: HashMap::new(),
-                errors: Vec::new(),
+            let ctx = CheckContext {
+                config: &self.config,
+                settings,
             };
-
-            match monitor.check_network_connections() {
-                Ok(count) => {
-                    result.passed = count > 0 || matches!(server.role, ServerRole::Replica);
-                    result.details.insert(
-                        "websocket_connections".to_string(),
-                        serde_json::json!(count),
-                    );
-                }
-                Err(e) => {
-                    result.errors.push(format!("Network check failed: {}", e));
-                }
-            }
-
-            self.results.push(result);
+            let results = check.run(&ctx).await;
+            self.results.extend(results);
         }
 
-        Ok(())
-    }
+        let end_time = Utc::now();
+        let summary = self.generate_summary(start_time, end_time);
 
-    async fn validate_self_replication(
-        &mut self,
-        primary: &ServerConfig,
-        replica: &ServerConfig,
-    ) -> Result<()> {
         info!(
-            "Validating self-replication from {} to {}",
-            primary.name, replica.name
+            "Validation suite completed: {} passed, {} failed",
+            summary.passed, summary.failed
         );
 
-        let mut result = ValidationResult {
-            test_name: "self_replication".to_string(),
-            server: Some(format!("{} -> {}", primary.name, replica.name)),
-            timestamp: Utc::now(),
-            passed: false,
-            details: HashMap::new(),
-            errors: Vec::new(),
-        };
-
-        let replica_monitor = AgentMonitor::new(replica.clone());
-
-        match replica_monitor.verify_replica_deployment() {
-            Ok(exists) => {
-                result.passed = exists;
-                result
-                    .details
-                    .insert("replica_deployed".to_string(), serde_json::json!(exists));
-
-                if exists {
-                    // Check if replica is running
-                    if let Ok(health) = replica_monitor.check_agent_health() {
-                        result.details.insert(
-                            "replica_running".to_string(),
-                            serde_json::json!(health.is_running),
-                        );
-                    }
-                }
-            }
-            Err(e) => {
-                result
-                    .errors
-                    .push(format!("Replication check failed: {}", e));
-            }
-        }
-
-        self.results.push(result);
-        Ok(())
+        Ok(summary)
     }
 
     fn generate_summary(
@@ -370,29 +120,55 @@ impl ValidationSuite {
         }
     }
 
-    pub fn save_results(&self, path: &str) -> Result<()> {
-        let summary = self.generate_summary(
+    fn current_summary(&self) -> ValidationSummary {
+        self.generate_summary(
             self.results
                 .first()
                 .map(|r| r.timestamp)
                 .unwrap_or_else(Utc::now),
             Utc::now(),
-        );
+        )
+    }
+
+    /// 上一次运行保存的结果，用于趋势对比
+    fn load_previous(path: &str) -> Option<ValidationSummary> {
+        let content = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// 保存 JSON 结果，并在同一位置生成 Markdown/HTML 报告
+    pub fn save_results(&self, path: &str) -> Result<()> {
+        let previous = Self::load_previous(path);
+        self.save_with_previous(path, previous.as_ref())
+    }
+
+    /// 与 `save_results` 相同，配置了 webhook 时再推送报告
+    pub async fn publish_results(&self, path: &str) -> Result<()> {
+        let previous = Self::load_previous(path);
+        self.save_with_previous(path, previous.as_ref())?;
+
+        if let Some(url) = &self.config.report.webhook_url {
+            let summary = self.current_summary();
+            if let Err(e) = report::post_report(url, &summary, previous.as_ref()).await {
+                warn!("Failed to post validation report to {}: {}", url, e);
+            }
+        }
+        Ok(())
+    }
+
+    fn save_with_previous(&self, path: &str, previous: Option<&ValidationSummary>) -> Result<()> {
+        let summary = self.current_summary();
 
         let json = serde_json::to_string_pretty(&summary)?;
         std::fs::write(path, json)?;
         info!("Validation results saved to {}", path);
+
+        report::write_reports(&self.config.report, Path::new(path), &summary, previous)?;
         Ok(())
     }
 
     pub fn print_summary(&self) {
-        let summary = self.generate_summary(
-            self.results
-                .first()
-                .map(|r| r.timestamp)
-                .unwrap_or_else(Utc::now),
-            Utc::now(),
-        );
+        let summary = self.current_summary();
 
         println!("\n{}", "=".repeat(50));
         println!("VALIDATION SUMMARY");
@@ -1,4 +1,8 @@
-use deployment_tester::{AgentMonitor, DeploymentClient, TestConfig};
+use deployment_tester::validator::ValidationResult;
+use deployment_tester::{
+    AgentMonitor, CheckConfig, CheckContext, DeploymentClient, TestConfig, ValidationCheck,
+    ValidationSuite,
+};
 use std::path::PathBuf;
 use tempfile::TempDir;
 
@@ -26,6 +30,50 @@ async fn test_config_serialization() {
     );
 }
 
+struct JournalGrowing;
+
+#[async_trait::async_trait]
+impl ValidationCheck for JournalGrowing {
+    fn name(&self) -> &str {
+        "event_journal_growing"
+    }
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Vec<ValidationResult> {
+        let mut result = ctx.result(self.name(), None);
+        result.passed = 12.0 >= ctx.settings.threshold("min_new_entries", 1.0);
+        vec![result]
+    }
+}
+
+#[tokio::test]
+async fn test_custom_checks_use_configured_thresholds() {
+    let mut config = TestConfig::default();
+    // Without servers the built-in checks have nothing to connect to
+    config.test_environments.clear();
+    config.checks.insert(
+        "event_journal_growing".to_string(),
+        CheckConfig {
+            enabled: true,
+            thresholds: [("min_new_entries".to_string(), 20.0)].into(),
+        },
+    );
+    config.checks.insert(
+        "agent_running".to_string(),
+        CheckConfig {
+            enabled: false,
+            ..Default::default()
+        },
+    );
+
+    let mut suite = ValidationSuite::new(config).with_check(JournalGrowing);
+    assert_eq!(suite.check_names().len(), 7);
+
+    let summary = suite.run_full_validation().await.unwrap();
+    assert_eq!(summary.total_tests, 1);
+    assert_eq!(summary.results[0].test_name, "event_journal_growing");
+    assert!(!summary.results[0].passed);
+}
+
 #[cfg(test)]
 mod mock_tests {
     use super::*;
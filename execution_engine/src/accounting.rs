@@ -0,0 +1,179 @@
+//! Revaluation of the portfolio as prices move.
+//!
+//! The user-data stream reports the net asset value whenever a balance changes.
+//! Between balance changes the value of non-quote assets still moves with the
+//! market, so the [`Accountant`] keeps the portfolio's prices current from the
+//! trade stream and reports the net asset value again once it has moved by
+//! more than [`REPORT_THRESHOLD`].
+//!
+//! Funds are only reported from a complete valuation: a total that leaves out
+//! assets without a price would read as a loss.
+
+use crate::user_data::SharedPortfolio;
+use common::{
+    AccountingConfig, AppEvent, EventReceiver, EventSender, LagHandler, NetAssetValue, Topic,
+};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, error, warn};
+
+pub const REVALUATION_INTERVAL: Duration = Duration::from_secs(60);
+/// Smallest relative change in net asset value worth reporting.
+pub const REPORT_THRESHOLD: f64 = 0.001;
+
+/// `NetAssetValue` for `nav`, preceded by `FinancialUpdate` unless an asset has
+/// no price.
+pub(crate) fn value_events(nav: NetAssetValue) -> Vec<AppEvent> {
+    let mut events = Vec::new();
+    if nav.unpriced.is_empty() {
+        events.push(AppEvent::FinancialUpdate(nav.total));
+    } else {
+        warn!(
+            unpriced = ?nav.unpriced,
+            "[Execution Engine] Not reporting funds until every asset has a price"
+        );
+    }
+    events.push(AppEvent::NetAssetValue(Box::new(nav)));
+    events
+}
+
+pub struct Accountant {
+    tx: EventSender,
+    rx: EventReceiver,
+    portfolio: SharedPortfolio,
+    config: AccountingConfig,
+    /// Net asset value last reported
+    reported: Option<f64>,
+    lag: LagHandler,
+}
+
+impl Accountant {
+    pub fn new(tx: EventSender, portfolio: SharedPortfolio, config: AccountingConfig) -> Self {
+        let rx = tx.subscribe_as("accountant", &[Topic::MarketTicks]);
+        let lag = LagHandler::new("accountant").with_event_bus(tx.clone());
+        Self {
+            tx,
+            rx,
+            portfolio,
+            config,
+            reported: None,
+            lag,
+        }
+    }
+
+    pub async fn run(mut self) {
+        let mut interval = tokio::time::interval(REVALUATION_INTERVAL);
+        loop {
+            tokio::select! {
+                event = self.rx.recv() => match event {
+                    Ok(AppEvent::MarketTick(data)) => {
+                        self.portfolio.write().await.prices.update(&data.symbol, data.price);
+                    }
+                    Ok(_) => {}
+                    // The next trade of each symbol brings its price up to date
+                    Err(RecvError::Lagged(n)) => {
+                        self.lag.lagged(n);
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                _ = interval.tick() => self.revalue().await,
+            }
+        }
+    }
+
+    /// Report the net asset value if it moved enough since it was last reported.
+    /// Nothing is reported while an asset has no price.
+    async fn revalue(&mut self) {
+        let nav = {
+            let portfolio = self.portfolio.read().await;
+            if portfolio.balances.is_empty() {
+                return;
+            }
+            portfolio.net_asset_value(&self.config)
+        };
+        if !nav.unpriced.is_empty() {
+            debug!(unpriced = ?nav.unpriced, "[Execution Engine] Not revaluing without prices");
+            return;
+        }
+        if let Some(reported) = self.reported {
+            if (nav.total - reported).abs() <= reported.abs() * REPORT_THRESHOLD {
+                return;
+            }
+        }
+        self.reported = Some(nav.total);
+        for event in [
+            AppEvent::FinancialUpdate(nav.total),
+            AppEvent::NetAssetValue(Box::new(nav)),
+        ] {
+            if let Err(e) = self.tx.send(event) {
+                error!(
+                    "[Execution Engine] Failed to publish net asset value: {}",
+                    e
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_data::Balance;
+    use common::EventBus;
+
+    #[tokio::test]
+    async fn test_net_asset_value_is_reported_when_prices_move() {
+        let bus = EventBus::new(16);
+        let mut events = bus.subscribe_as("test", &[Topic::Financial]);
+        let portfolio = SharedPortfolio::default();
+        let mut accountant = Accountant::new(bus, portfolio.clone(), AccountingConfig::default());
+
+        accountant.revalue().await;
+        assert!(events.try_recv().is_err());
+
+        {
+            let mut portfolio = portfolio.write().await;
+            for (asset, free) in [("USDT", 1000.0), ("BTC", 0.1)] {
+                portfolio
+                    .balances
+                    .insert(asset.to_string(), Balance { free, locked: 0.0 });
+            }
+            portfolio.prices.update("BTCUSDT", 60000.0);
+        }
+        accountant.revalue().await;
+        assert!(matches!(events.try_recv(), Ok(AppEvent::FinancialUpdate(f)) if f == 7000.0));
+        assert!(
+            matches!(events.try_recv(), Ok(AppEvent::NetAssetValue(nav)) if nav.assets.len() == 2)
+        );
+
+        // A small move is not worth reporting, a larger one is
+        portfolio.write().await.prices.update("BTCUSDT", 60010.0);
+        accountant.revalue().await;
+        assert!(events.try_recv().is_err());
+        portfolio.write().await.prices.update("BTCUSDT", 50000.0);
+        accountant.revalue().await;
+        assert!(matches!(events.try_recv(), Ok(AppEvent::FinancialUpdate(f)) if f == 6000.0));
+    }
+
+    #[tokio::test]
+    async fn test_nothing_is_reported_while_an_asset_has_no_price() {
+        let bus = EventBus::new(16);
+        let mut events = bus.subscribe_as("test", &[Topic::Financial]);
+        let portfolio = SharedPortfolio::default();
+        let mut accountant = Accountant::new(bus, portfolio.clone(), AccountingConfig::default());
+
+        for (asset, free) in [("USDT", 1000.0), ("BTC", 0.1)] {
+            portfolio
+                .write()
+                .await
+                .balances
+                .insert(asset.to_string(), Balance { free, locked: 0.0 });
+        }
+        accountant.revalue().await;
+        assert!(events.try_recv().is_err());
+
+        portfolio.write().await.prices.update("BTCUSDT", 60000.0);
+        accountant.revalue().await;
+        assert!(matches!(events.try_recv(), Ok(AppEvent::FinancialUpdate(f)) if f == 7000.0));
+    }
+}
@@ -0,0 +1,342 @@
+//! Execution algorithms that work a decision into the market over time.
+//!
+//! Taking a full position with one market order moves the price against us. With
+//! `twap` a decision's quantity is split into `slices` equal child orders spread
+//! over `duration_seconds`; with `vwap` each child order is `participation_rate`
+//! of the volume traded on the symbol since the previous one, so the execution
+//! follows the market's own activity. Either way the last slice takes whatever
+//! is left. Live child orders are rounded down to the symbol's `LOT_SIZE`, and
+//! what that leaves out is made up by later slices. Every child order and the end of the execution are published as
+//! `AppEvent::ExecutionProgress`. An execution stops early when its token is
+//! cancelled, which the engine does when the strategy reverses.
+
+use crate::orders::LotSize;
+use crate::OrderExecutor;
+use common::{
+    AppEvent, AureliaResult, CancellationToken, EventBus, EventMeta, ExecutionProgress,
+    ExecutionStatus, StrategyDecision, Topic,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::MissedTickBehavior;
+use tracing::{error, info, warn};
+
+pub const EXECUTION_ALGO_CONFIG_PATH: &str = "config/execution_algo.json";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Algorithm {
+    /// One order for the full quantity
+    #[default]
+    Immediate,
+    Twap,
+    Vwap,
+}
+
+impl Algorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Immediate => "immediate",
+            Self::Twap => "twap",
+            Self::Vwap => "vwap",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExecutionAlgoConfig {
+    pub algorithm: Algorithm,
+    /// Time over which a decision is executed
+    pub duration_seconds: u64,
+    /// Number of child orders, one per `duration_seconds / slices`
+    pub slices: u32,
+    /// Share of the market volume taken by each `vwap` child order
+    pub participation_rate: f64,
+}
+
+impl Default for ExecutionAlgoConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: Algorithm::Immediate,
+            duration_seconds: 300,
+            slices: 10,
+            participation_rate: 0.1,
+        }
+    }
+}
+
+impl ExecutionAlgoConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn slices(&self) -> u32 {
+        self.slices.max(1)
+    }
+
+    fn slice_interval(&self) -> Duration {
+        Duration::from_secs_f64(self.duration_seconds as f64 / self.slices() as f64)
+            .max(Duration::from_millis(100))
+    }
+}
+
+/// Quantity of the child order for `slice` (counting from zero), given how much
+/// of `target` is already executed and the volume traded since the last slice.
+/// With a `lot_size` the quantity is rounded down to it, and is zero when that
+/// falls below the minimum.
+pub fn child_quantity(
+    config: &ExecutionAlgoConfig,
+    target: f64,
+    executed: f64,
+    slice: u32,
+    market_volume: f64,
+    lot_size: Option<LotSize>,
+) -> f64 {
+    let remaining = (target - executed).max(0.0);
+    let quantity = if slice + 1 >= config.slices() {
+        remaining
+    } else {
+        let quantity = match config.algorithm {
+            Algorithm::Immediate => remaining,
+            // Catches up with the schedule, so rounding never falls behind it
+            Algorithm::Twap => target * (slice + 1) as f64 / config.slices() as f64 - executed,
+            Algorithm::Vwap => market_volume * config.participation_rate,
+        };
+        quantity.clamp(0.0, remaining)
+    };
+    match lot_size {
+        Some(lot_size) => lot_size.round_down(quantity),
+        None => quantity,
+    }
+}
+
+/// One decision being executed as a series of child orders.
+pub struct AlgoExecution {
+    config: ExecutionAlgoConfig,
+    executor: OrderExecutor,
+    bus: EventBus,
+    decision: StrategyDecision,
+    meta: EventMeta,
+    quantity: f64,
+    token: CancellationToken,
+}
+
+impl AlgoExecution {
+    pub(crate) fn new(
+        config: ExecutionAlgoConfig,
+        executor: OrderExecutor,
+        bus: EventBus,
+        decision: StrategyDecision,
+        meta: EventMeta,
+        quantity: f64,
+        token: CancellationToken,
+    ) -> Self {
+        Self {
+            config,
+            executor,
+            bus,
+            decision,
+            meta,
+            quantity,
+            token,
+        }
+    }
+
+    pub async fn run(self) {
+        let (side, symbol, decision_price) = match &self.decision {
+            StrategyDecision::Buy(symbol, price) => ("BUY", symbol.clone(), *price),
+            StrategyDecision::Sell(symbol, price) => ("SELL", symbol.clone(), *price),
+            StrategyDecision::Hold(_) => return,
+        };
+        let lot_size = match self.executor.lot_size(&symbol).await {
+            Ok(lot_size) => lot_size,
+            Err(e) => {
+                warn!(
+                    correlation_id = %self.meta.correlation_id,
+                    "[Execution Engine] No lot size for {}, child orders are not rounded: {}",
+                    symbol,
+                    e
+                );
+                None
+            }
+        };
+        let mut ticks = self
+            .bus
+            .subscribe_as("execution_algo", &[Topic::MarketTicks]);
+        let mut interval = tokio::time::interval(self.config.slice_interval());
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        info!(
+            correlation_id = %self.meta.correlation_id,
+            algorithm = self.config.algorithm.as_str(),
+            quantity = self.quantity,
+            "[Execution Engine] Executing {} {} in {} slices",
+            side,
+            symbol,
+            self.config.slices()
+        );
+
+        let mut progress = ExecutionProgress {
+            correlation_id: self.meta.correlation_id.clone(),
+            strategy_id: self.meta.strategy_id.clone(),
+            symbol: symbol.clone(),
+            side: side.to_string(),
+            algorithm: self.config.algorithm.as_str().to_string(),
+            target_quantity: self.quantity,
+            executed_quantity: 0.0,
+            child_orders: 0,
+            status: ExecutionStatus::Running,
+            timestamp: chrono::Utc::now(),
+        };
+        let mut last_price = decision_price;
+        let mut volume = 0.0;
+        let mut slice = 0;
+        loop {
+            tokio::select! {
+                biased;
+                _ = self.token.cancelled() => {
+                    progress.status = ExecutionStatus::Cancelled;
+                    break;
+                }
+                tick = ticks.recv() => match tick {
+                    Ok(AppEvent::MarketTick(data)) if data.symbol == symbol => {
+                        last_price = data.price;
+                        volume += data.quantity;
+                    }
+                    Err(RecvError::Closed) => {
+                        progress.status = ExecutionStatus::Cancelled;
+                        break;
+                    }
+                    _ => {}
+                },
+                _ = interval.tick() => {
+                    let quantity = child_quantity(
+                        &self.config,
+                        self.quantity,
+                        progress.executed_quantity,
+                        slice,
+                        volume,
+                        lot_size,
+                    );
+                    volume = 0.0;
+                    if quantity > 0.0 {
+                        let child = match side {
+                            "BUY" => StrategyDecision::Buy(symbol.clone(), last_price),
+                            _ => StrategyDecision::Sell(symbol.clone(), last_price),
+                        };
+                        match self.executor.execute(&child, &self.meta, quantity, Some(slice)).await {
+                            Ok(()) => {
+                                progress.executed_quantity += quantity;
+                                progress.child_orders += 1;
+                                progress.timestamp = chrono::Utc::now();
+                                let _ = self.bus.send(AppEvent::ExecutionProgress(Box::new(progress.clone())));
+                            }
+                            Err(e) => error!(
+                                correlation_id = %self.meta.correlation_id,
+                                slice = slice,
+                                "[Execution Engine] Child order failed: {}", e
+                            ),
+                        }
+                    }
+                    slice += 1;
+                    if slice >= self.config.slices() {
+                        progress.status = ExecutionStatus::Completed;
+                        break;
+                    }
+                }
+            }
+        }
+
+        info!(
+            correlation_id = %self.meta.correlation_id,
+            executed = progress.executed_quantity,
+            "[Execution Engine] {} execution of {} {} {:?}",
+            progress.algorithm,
+            side,
+            symbol,
+            progress.status
+        );
+        progress.timestamp = chrono::Utc::now();
+        let _ = self
+            .bus
+            .send(AppEvent::ExecutionProgress(Box::new(progress)));
+        // Lets the engine see that this execution is over
+        self.token.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_child_orders_add_up_to_the_target() {
+        let twap = ExecutionAlgoConfig {
+            algorithm: Algorithm::Twap,
+            slices: 4,
+            ..Default::default()
+        };
+        let mut executed = 0.0;
+        for slice in 0..4 {
+            let quantity = child_quantity(&twap, 1.0, executed, slice, 0.0, None);
+            assert!((quantity - 0.25).abs() < 1e-9);
+            executed += quantity;
+        }
+        assert!((executed - 1.0).abs() < 1e-9);
+
+        let vwap = ExecutionAlgoConfig {
+            algorithm: Algorithm::Vwap,
+            slices: 3,
+            participation_rate: 0.1,
+            ..Default::default()
+        };
+        assert_eq!(child_quantity(&vwap, 1.0, 0.0, 0, 0.0, None), 0.0);
+        assert!((child_quantity(&vwap, 1.0, 0.0, 0, 4.0, None) - 0.4).abs() < 1e-9);
+        // Never more than what is left, and the last slice takes the rest
+        assert!((child_quantity(&vwap, 1.0, 0.8, 1, 50.0, None) - 0.2).abs() < 1e-9);
+        assert!((child_quantity(&vwap, 1.0, 0.4, 2, 0.0, None) - 0.6).abs() < 1e-9);
+
+        let config: ExecutionAlgoConfig = serde_json::from_str(r#"{"algorithm": "vwap"}"#).unwrap();
+        assert_eq!(config.algorithm, Algorithm::Vwap);
+        assert_eq!(config.slices, 10);
+    }
+
+    #[test]
+    fn test_child_orders_are_rounded_to_the_lot_size() {
+        let lot_size = Some(LotSize {
+            step_size: 0.1,
+            min_qty: 0.2,
+        });
+        let twap = ExecutionAlgoConfig {
+            algorithm: Algorithm::Twap,
+            slices: 3,
+            ..Default::default()
+        };
+        // A third of 1.0 is not a whole number of steps; later slices make up for it
+        let mut quantities = Vec::new();
+        let mut executed = 0.0;
+        for slice in 0..3 {
+            let quantity = child_quantity(&twap, 1.0, executed, slice, 0.0, lot_size);
+            quantities.push(quantity);
+            executed += quantity;
+        }
+        assert_eq!(quantities, vec![0.3, 0.3, 0.4]);
+
+        let vwap = ExecutionAlgoConfig {
+            algorithm: Algorithm::Vwap,
+            slices: 3,
+            participation_rate: 0.1,
+            ..Default::default()
+        };
+        // Below the minimum quantity nothing is sent
+        assert_eq!(child_quantity(&vwap, 1.0, 0.0, 0, 1.5, lot_size), 0.0);
+        assert_eq!(child_quantity(&vwap, 1.0, 0.0, 0, 2.57, lot_size), 0.2);
+    }
+}
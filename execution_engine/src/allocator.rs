@@ -0,0 +1,261 @@
+//! Scores the strategies on their recent trades and moves funds toward the ones
+//! doing best.
+//!
+//! Each pass publishes an `AppEvent::StrategyPerformance` scoreboard. When
+//! rebalancing is enabled the pass also writes new allocations to the state
+//! store, which the execution engine sizes orders from. A strategy's target is
+//! its current allocation scaled by `1 + sharpe` (Sharpe clamped to ±1, strategies
+//! with too few trades count as 0) and renormalized to the same total, kept within
+//! `[min_allocation, max_allocation]`; each pass moves it at most `max_step`
+//! toward the target, and the total allocation never changes.
+
+use common::performance::{self, StrategyPerformance};
+use common::{
+    AppEvent, AureliaResult, EventSender, PerformanceReport, StateStore, StrategySet, TradeLedger,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+use tracing::{debug, info};
+
+pub const ALLOCATION_CONFIG_PATH: &str = "config/allocation.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AllocationConfig {
+    /// Change allocations; when off the scoreboard is still published
+    pub rebalance: bool,
+    pub interval_seconds: u64,
+    /// Trades older than this are not scored
+    pub window_hours: u32,
+    /// Closed trades a strategy needs in the window before its Sharpe counts
+    pub min_trades: u32,
+    pub min_allocation: f64,
+    pub max_allocation: f64,
+    /// Largest change of one strategy's allocation per pass
+    pub max_step: f64,
+}
+
+impl Default for AllocationConfig {
+    fn default() -> Self {
+        Self {
+            rebalance: true,
+            interval_seconds: 3600,
+            window_hours: 168,
+            min_trades: 5,
+            min_allocation: 0.05,
+            max_allocation: 0.6,
+            max_step: 0.05,
+        }
+    }
+}
+
+impl AllocationConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+/// New allocations for `current`, in the same order.
+fn rebalance(
+    current: &[f64],
+    scores: &[StrategyPerformance],
+    config: &AllocationConfig,
+) -> Vec<f64> {
+    let total: f64 = current.iter().sum();
+    let weights: Vec<f64> = scores
+        .iter()
+        .map(|score| match score.sharpe {
+            Some(sharpe) if score.closed_trades >= config.min_trades => {
+                1.0 + sharpe.clamp(-1.0, 1.0)
+            }
+            _ => 1.0,
+        })
+        .collect();
+    let weighted: f64 = current.iter().zip(&weights).map(|(a, w)| a * w).sum();
+    if total <= 0.0 || weighted <= 0.0 {
+        return current.to_vec();
+    }
+
+    let mut steps: Vec<f64> = current
+        .iter()
+        .zip(&weights)
+        .map(|(allocation, weight)| {
+            let target = (total * allocation * weight / weighted)
+                .clamp(config.min_allocation, config.max_allocation);
+            (target - allocation).clamp(-config.max_step, config.max_step)
+        })
+        .collect();
+    // Shrink whichever side moves more so that the steps cancel out
+    let gained: f64 = steps.iter().filter(|step| **step > 0.0).sum();
+    let released: f64 = -steps.iter().filter(|step| **step < 0.0).sum::<f64>();
+    if gained > released {
+        let scale = released / gained;
+        steps
+            .iter_mut()
+            .filter(|step| **step > 0.0)
+            .for_each(|step| *step *= scale);
+    } else if released > 0.0 {
+        let scale = gained / released;
+        steps
+            .iter_mut()
+            .filter(|step| **step < 0.0)
+            .for_each(|step| *step *= scale);
+    }
+    current
+        .iter()
+        .zip(steps)
+        .map(|(allocation, step)| allocation + step)
+        .collect()
+}
+
+pub struct Allocator {
+    tx: EventSender,
+    strategies: StrategySet,
+    ledger: TradeLedger,
+    state: StateStore,
+    config: AllocationConfig,
+}
+
+impl Allocator {
+    pub fn new(
+        tx: EventSender,
+        strategies: StrategySet,
+        ledger: TradeLedger,
+        state: StateStore,
+        config: AllocationConfig,
+    ) -> Self {
+        Self {
+            tx,
+            strategies,
+            ledger,
+            state,
+            config,
+        }
+    }
+
+    pub async fn run(self) {
+        info!(
+            "[Allocator] Scoring strategies every {}s",
+            self.config.interval_seconds
+        );
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(self.config.interval_seconds.max(1)));
+        loop {
+            interval.tick().await;
+            let report = self.pass(chrono::Utc::now());
+            if self.tx.send(AppEvent::StrategyPerformance(report)).is_err() {
+                debug!("[Allocator] No subscribers for strategy performance");
+            }
+        }
+    }
+
+    /// Score the strategies and, if enabled, rebalance their allocations.
+    pub fn pass(&self, now: chrono::DateTime<chrono::Utc>) -> PerformanceReport {
+        let specs: Vec<_> = self.strategies.enabled().collect();
+        let ids: Vec<&str> = specs.iter().map(|spec| spec.id.as_str()).collect();
+        let mut scores = performance::score(
+            &self.ledger.fills(),
+            &ids,
+            now,
+            chrono::Duration::hours(self.config.window_hours as i64),
+        );
+        let current: Vec<f64> = specs
+            .iter()
+            .map(|spec| self.state.allocation(&spec.id).unwrap_or(spec.allocation))
+            .collect();
+
+        let allocations = if self.config.rebalance {
+            rebalance(&current, &scores, &self.config)
+        } else {
+            current.clone()
+        };
+        for ((score, before), after) in scores.iter_mut().zip(&current).zip(&allocations) {
+            score.allocation = *after;
+            if (after - before).abs() > 1e-9 {
+                info!(
+                    strategy_id = %score.strategy_id,
+                    sharpe = score.sharpe.unwrap_or_default(),
+                    "[Allocator] Allocation {:.4} -> {:.4}",
+                    before,
+                    after
+                );
+            }
+        }
+        if self.config.rebalance {
+            self.state.set_allocations(
+                ids.iter()
+                    .map(|id| id.to_string())
+                    .zip(allocations)
+                    .collect::<BTreeMap<_, _>>(),
+            );
+        }
+
+        PerformanceReport {
+            timestamp: now,
+            window_hours: self.config.window_hours,
+            rebalanced: self.config.rebalance,
+            strategies: scores,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scored(sharpe: Option<f64>, closed_trades: u32) -> StrategyPerformance {
+        StrategyPerformance {
+            sharpe,
+            closed_trades,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rebalance_moves_funds_within_bounds() {
+        let config = AllocationConfig::default();
+        let current = [0.4, 0.3, 0.3];
+        let scores = [
+            scored(Some(2.0), 10),
+            scored(Some(-0.5), 10),
+            // Too few trades to judge
+            scored(Some(-3.0), 2),
+        ];
+
+        let next = rebalance(&current, &scores, &config);
+        assert!((next.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        // The best strategy gains, the worst loses, and nobody moves more than a step
+        assert!(next[0] > current[0] && next[1] < current[1]);
+        for (before, after) in current.iter().zip(&next) {
+            assert!((after - before).abs() <= config.max_step + 1e-9);
+        }
+
+        // A consistent winner ends up at the cap
+        let mut allocations = next;
+        for _ in 0..50 {
+            allocations = rebalance(&allocations, &scores, &config);
+        }
+        assert!((allocations[0] - config.max_allocation).abs() < 1e-9);
+        assert!((allocations.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+
+        // Without enough trades to judge, nothing moves
+        let unscored = [scored(None, 0), scored(None, 0), scored(Some(1.0), 1)];
+        assert_eq!(rebalance(&current, &unscored, &config), current);
+
+        // The loser is not drained below what the winner may take
+        let scores = [scored(Some(1.0), 10), scored(Some(-1.0), 10)];
+        let mut allocations = vec![0.5, 0.5];
+        for _ in 0..50 {
+            allocations = rebalance(&allocations, &scores, &config);
+        }
+        assert!((allocations[0] - config.max_allocation).abs() < 1e-9);
+        assert!((allocations[1] - 0.4).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,254 @@
+//! Clock drift detection.
+//!
+//! Every `interval_seconds` the service compares the local clock with the
+//! exchange's server time and with NTP. The exchange offset is stored in
+//! [`common::clock`] so that signed requests carry the exchange's time, and the
+//! `clock` readiness component turns unready while any measured drift exceeds
+//! `max_drift_ms`, so the operator sees it on `/health/ready`.
+
+use common::health::component;
+use common::{clock, AureliaError, AureliaResult, HealthState};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time;
+use tracing::{debug, error, info, warn};
+
+pub const CLOCK_SYNC_CONFIG_PATH: &str = "config/clock_sync.json";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Seconds from the NTP epoch (1900) to the Unix epoch
+const NTP_UNIX_OFFSET_SECONDS: i64 = 2_208_988_800;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClockSyncConfig {
+    pub enabled: bool,
+    pub interval_seconds: u64,
+    /// Returns `{"serverTime": <ms>}`
+    pub exchange_time_url: String,
+    /// `host:port` of SNTP servers, queried in order until one answers
+    pub ntp_servers: Vec<String>,
+    /// Drift from either source beyond which the clock is reported unhealthy
+    pub max_drift_ms: i64,
+    /// Stamp signed requests with the exchange's time instead of the local clock
+    pub adjust_timestamps: bool,
+}
+
+impl Default for ClockSyncConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_seconds: 300,
+            exchange_time_url: "https://api.binance.com/api/v3/time".to_string(),
+            ntp_servers: vec![
+                "pool.ntp.org:123".to_string(),
+                "time.google.com:123".to_string(),
+            ],
+            max_drift_ms: 500,
+            adjust_timestamps: true,
+        }
+    }
+}
+
+impl ClockSyncConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerTime {
+    server_time: i64,
+}
+
+pub struct ClockSync {
+    config: ClockSyncConfig,
+    client: reqwest::Client,
+    health: Option<HealthState>,
+}
+
+impl ClockSync {
+    pub fn new(config: ClockSyncConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            health: None,
+        }
+    }
+
+    /// Report the drift as the `clock` readiness component
+    pub fn with_health(mut self, health: HealthState) -> Self {
+        health.register(component::CLOCK);
+        self.health = Some(health);
+        self
+    }
+
+    pub async fn run(self) {
+        info!(
+            max_drift_ms = self.config.max_drift_ms,
+            "[Clock Sync] Checking clock drift every {}s", self.config.interval_seconds
+        );
+        let mut interval = time::interval(Duration::from_secs(self.config.interval_seconds.max(1)));
+        loop {
+            interval.tick().await;
+            self.check().await;
+        }
+    }
+
+    async fn check(&self) {
+        let exchange = match self.exchange_offset().await {
+            Ok(offset) => Some(offset),
+            Err(e) => {
+                warn!("[Clock Sync] Failed to read exchange time: {}", e);
+                None
+            }
+        };
+        let ntp = self.ntp_offset().await;
+        debug!(?exchange, ?ntp, "[Clock Sync] Measured clock offsets");
+
+        if let (Some(offset), true) = (exchange, self.config.adjust_timestamps) {
+            clock::set_offset_millis(offset);
+        }
+
+        let detail = format!(
+            "exchange offset {}, NTP offset {}",
+            describe(exchange),
+            describe(ntp)
+        );
+        let drift = exchange.into_iter().chain(ntp).map(i64::abs).max();
+        let healthy = match drift {
+            Some(drift) if drift > self.config.max_drift_ms => {
+                error!(
+                    "[Clock Sync] Clock drift of {}ms exceeds {}ms: {}",
+                    drift, self.config.max_drift_ms, detail
+                );
+                false
+            }
+            Some(_) => true,
+            None => {
+                // Without any time source the drift is unknown, not proven bad
+                warn!("[Clock Sync] No time source reachable");
+                true
+            }
+        };
+        if let Some(health) = &self.health {
+            health.set(component::CLOCK, healthy, Some(detail));
+        }
+    }
+
+    /// Milliseconds the exchange's clock is ahead of ours, assuming the request
+    /// took as long to get there as the response took to come back.
+    pub async fn exchange_offset(&self) -> AureliaResult<i64> {
+        let sent = clock::local_now_millis();
+        let time: ServerTime = self
+            .client
+            .get(&self.config.exchange_time_url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AureliaError::Exchange(format!("server time: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| AureliaError::Exchange(format!("server time: {}", e)))?;
+        let received = clock::local_now_millis();
+        Ok(time.server_time - (sent + received) / 2)
+    }
+
+    /// Offset from the first NTP server that answers.
+    async fn ntp_offset(&self) -> Option<i64> {
+        for server in &self.config.ntp_servers {
+            match time::timeout(REQUEST_TIMEOUT, query_ntp(server)).await {
+                Ok(Ok(offset)) => return Some(offset),
+                Ok(Err(e)) => warn!("[Clock Sync] NTP query to {} failed: {}", server, e),
+                Err(_) => warn!("[Clock Sync] NTP query to {} timed out", server),
+            }
+        }
+        None
+    }
+}
+
+fn describe(offset: Option<i64>) -> String {
+    offset.map_or_else(|| "unknown".to_string(), |ms| format!("{}ms", ms))
+}
+
+/// Milliseconds the NTP server's clock is ahead of ours, from one SNTP exchange.
+async fn query_ntp(server: &str) -> AureliaResult<i64> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(server).await?;
+    let mut request = [0u8; 48];
+    // Leap indicator 0, version 4, mode 3 (client)
+    request[0] = 0x23;
+    let sent = clock::local_now_millis();
+    socket.send(&request).await?;
+    let mut response = [0u8; 48];
+    let len = socket.recv(&mut response).await?;
+    let received = clock::local_now_millis();
+    ntp_offset(&response[..len], sent, received)
+}
+
+/// Clock offset from an SNTP response, using the server's receive and transmit
+/// timestamps and our own send and receive times.
+fn ntp_offset(response: &[u8], sent: i64, received: i64) -> AureliaResult<i64> {
+    if response.len() < 48 || response[0] & 0x07 != 4 {
+        return Err(AureliaError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "not an NTP server response",
+        )));
+    }
+    let server_received = ntp_timestamp_millis(&response[32..40]);
+    let server_sent = ntp_timestamp_millis(&response[40..48]);
+    Ok(((server_received - sent) + (server_sent - received)) / 2)
+}
+
+/// Unix milliseconds of a 64-bit NTP timestamp.
+fn ntp_timestamp_millis(bytes: &[u8]) -> i64 {
+    let seconds = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as i64;
+    let fraction = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as i64;
+    (seconds - NTP_UNIX_OFFSET_SECONDS) * 1000 + ((fraction * 1000) >> 32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ntp_timestamp(unix_millis: i64) -> [u8; 8] {
+        let seconds = (unix_millis / 1000 + NTP_UNIX_OFFSET_SECONDS) as u32;
+        let fraction = (((unix_millis % 1000) << 32) / 1000) as u32;
+        let mut bytes = [0u8; 8];
+        bytes[..4].copy_from_slice(&seconds.to_be_bytes());
+        bytes[4..].copy_from_slice(&fraction.to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_ntp_offset_cancels_out_network_delay() {
+        // We send at 1000 and receive at 1100; the server's clock is 2000ms ahead
+        // and it answers 20ms after receiving
+        let sent = 1_700_000_001_000;
+        let mut response = [0u8; 48];
+        response[0] = 0x24;
+        response[32..40].copy_from_slice(&ntp_timestamp(sent + 40 + 2000));
+        response[40..48].copy_from_slice(&ntp_timestamp(sent + 60 + 2000));
+        let offset = ntp_offset(&response, sent, sent + 100).unwrap();
+        assert!((offset - 2000).abs() <= 1, "offset {}", offset);
+
+        response[0] = 0x23;
+        assert!(ntp_offset(&response, sent, sent + 100).is_err());
+
+        clock::set_offset_millis(2000);
+        let skew = clock::exchange_now_millis() as i64 - clock::local_now_millis();
+        assert!((1990..=2010).contains(&skew));
+        clock::set_offset_millis(0);
+    }
+}
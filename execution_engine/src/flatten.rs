@@ -0,0 +1,113 @@
+//! The positions an emergency flatten closes.
+//!
+//! On `AppEvent::EmergencyFlatten` the engine cancels the agent's open orders
+//! and closes what is left at market. With live trading the positions are the
+//! long live positions in the trade ledger, each sold against the accounting
+//! currency but never beyond the balance the account holds, so fee assets and
+//! holdings the agent did not buy are left alone. Their quantities are rounded
+//! down to the symbol's lot size before they are sent. Without live trading
+//! they are the net simulated positions in the trade ledger, closed like any
+//! simulated order at the latest trade price, or at the last fill price when
+//! no trade was seen since.
+
+use crate::user_data::{Balance, Portfolio};
+use common::{ClosedPosition, PriceBook};
+use std::collections::BTreeMap;
+
+/// Quantities below this count as no position.
+const DUST: f64 = 1e-9;
+
+/// Orders selling the long live `positions`, net quantity and last fill price
+/// by symbol as [`common::TradeLedger::positions`] reports them. A position's
+/// asset is its symbol without the `currency` suffix; symbols quoted in
+/// anything else are skipped.
+pub fn live_positions(
+    positions: &BTreeMap<String, (f64, f64)>,
+    portfolio: &Portfolio,
+    currency: &str,
+) -> Vec<ClosedPosition> {
+    positions
+        .iter()
+        .filter_map(|(symbol, (quantity, last_fill))| {
+            let asset = symbol.strip_suffix(currency).filter(|a| !a.is_empty())?;
+            let held = portfolio.balances.get(asset).map_or(0.0, Balance::total);
+            let quantity = quantity.min(held);
+            (quantity > DUST).then(|| ClosedPosition {
+                symbol: symbol.clone(),
+                side: "SELL".to_string(),
+                quantity,
+                price: portfolio.prices.price(symbol).unwrap_or(*last_fill),
+            })
+        })
+        .collect()
+}
+
+/// Orders closing simulated `positions`, net quantity and last fill price by
+/// symbol as [`common::TradeLedger::positions`] reports them.
+pub fn paper_positions(
+    positions: &BTreeMap<String, (f64, f64)>,
+    prices: &PriceBook,
+) -> Vec<ClosedPosition> {
+    positions
+        .iter()
+        .filter(|(_, (quantity, _))| quantity.abs() > DUST)
+        .map(|(symbol, (quantity, last_fill))| ClosedPosition {
+            symbol: symbol.clone(),
+            side: if *quantity > 0.0 { "SELL" } else { "BUY" }.to_string(),
+            quantity: quantity.abs(),
+            price: prices.price(symbol).unwrap_or(*last_fill),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_the_agents_positions_are_closed() {
+        let mut portfolio = Portfolio::default();
+        portfolio.prices.update("BTCUSDT", 60000.0);
+        for (asset, free, locked) in [
+            ("USDT", 500.0, 0.0),
+            ("BTC", 0.01, 0.02),
+            ("ETH", 1.0, 0.0),
+            ("BNB", 0.5, 0.0),
+            ("SOL", 3.0, 0.0),
+        ] {
+            portfolio
+                .balances
+                .insert(asset.to_string(), Balance { free, locked });
+        }
+        let live = BTreeMap::from([
+            ("BTCUSDT".to_string(), (0.02, 59000.0)),
+            // Sold more than the ledger saw bought, e.g. holdings from before
+            ("SOLUSDT".to_string(), (-1.0, 150.0)),
+            // Recorded beyond what the account holds
+            ("ETHUSDT".to_string(), (2.0, 3000.0)),
+            ("ETHBTC".to_string(), (1.0, 0.05)),
+        ]);
+        let closed = live_positions(&live, &portfolio, "USDT");
+        assert_eq!(closed.len(), 2);
+        assert_eq!(closed[0].symbol, "BTCUSDT");
+        assert_eq!(closed[0].side, "SELL");
+        assert!((closed[0].quantity - 0.02).abs() < 1e-12);
+        assert_eq!(closed[0].price, 60000.0);
+        // BNB and the SOL held before the ledger are left alone
+        assert_eq!(closed[1].symbol, "ETHUSDT");
+        assert_eq!(closed[1].quantity, 1.0);
+        assert_eq!(closed[1].price, 3000.0);
+
+        let ledger = BTreeMap::from([
+            ("BTCUSDT".to_string(), (0.5, 59000.0)),
+            ("ETHUSDT".to_string(), (-2.0, 3000.0)),
+        ]);
+        let paper = paper_positions(&ledger, &portfolio.prices);
+        assert_eq!(paper[0].side, "SELL");
+        assert_eq!(paper[0].price, 60000.0);
+        // A short is bought back, at its last fill price without a newer trade
+        assert_eq!(paper[1].side, "BUY");
+        assert_eq!(paper[1].quantity, 2.0);
+        assert_eq!(paper[1].price, 3000.0);
+    }
+}
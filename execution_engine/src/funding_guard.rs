@@ -0,0 +1,148 @@
+//! Exchange credential checks before any real order may be sent.
+//!
+//! Missing keys and example values such as `test_api_key` are caught without
+//! contacting the exchange. Other keys are tried on the signed account endpoint:
+//! a rejection marks them invalid, and the account's `canTrade` flag tells
+//! trading keys from read-only ones. Live trading is only enabled with keys
+//! that may trade.
+
+use crate::orders::BinanceOrders;
+use common::{CredentialReport, CredentialStatus, RateLimiter};
+use std::env;
+use tracing::{info, warn};
+
+/// Values shipped in example `.env` files and deployment templates
+const PLACEHOLDERS: [&str; 6] = [
+    "test_api_key",
+    "test_api_secret",
+    "changeme",
+    "placeholder",
+    "xxx",
+    "none",
+];
+
+/// Whether a key is empty or an example value rather than a real credential.
+pub fn is_placeholder(value: &str) -> bool {
+    let value = value.trim().to_ascii_lowercase();
+    value.is_empty()
+        || PLACEHOLDERS.contains(&value.as_str())
+        || value.starts_with("your_")
+        || value.starts_with('<')
+}
+
+pub struct FundingGuard {
+    api_key: Option<String>,
+    api_secret: Option<String>,
+    limiter: RateLimiter,
+}
+
+impl FundingGuard {
+    pub fn new(api_key: Option<String>, api_secret: Option<String>, limiter: RateLimiter) -> Self {
+        Self {
+            api_key,
+            api_secret,
+            limiter,
+        }
+    }
+
+    /// Check the keys in `BINANCE_API_KEY` and `BINANCE_API_SECRET`
+    pub fn from_env(limiter: RateLimiter) -> Self {
+        let _ = dotenvy::dotenv();
+        Self::new(
+            env::var("BINANCE_API_KEY").ok(),
+            env::var("BINANCE_API_SECRET").ok(),
+            limiter,
+        )
+    }
+
+    /// The keys to try on the exchange, or the status that follows from them alone.
+    fn keys(&self) -> Result<(&str, &str), CredentialStatus> {
+        match (self.api_key.as_deref(), self.api_secret.as_deref()) {
+            (Some(key), Some(secret)) if is_placeholder(key) || is_placeholder(secret) => {
+                Err(CredentialStatus::Placeholder)
+            }
+            (Some(key), Some(secret)) => Ok((key, secret)),
+            _ => Err(CredentialStatus::Missing),
+        }
+    }
+
+    pub async fn check(&self) -> CredentialReport {
+        let (key, secret) = match self.keys() {
+            Ok(keys) => keys,
+            Err(status) => {
+                warn!(status = ?status, "[Funding Guard] No usable exchange credentials");
+                return CredentialReport::new(status);
+            }
+        };
+
+        let exchange =
+            BinanceOrders::new(key.to_string(), secret.to_string(), self.limiter.clone());
+        match exchange.account().await {
+            Ok(account) => {
+                let status = if account.can_trade {
+                    CredentialStatus::Trading
+                } else {
+                    CredentialStatus::ReadOnly
+                };
+                info!(
+                    status = ?status,
+                    permissions = ?account.permissions,
+                    "[Funding Guard] Exchange credentials verified"
+                );
+                if account.can_withdraw {
+                    warn!(
+                        "[Funding Guard] API keys allow withdrawals, which the agent never needs"
+                    );
+                }
+                CredentialReport {
+                    can_trade: account.can_trade,
+                    can_withdraw: account.can_withdraw,
+                    permissions: account.permissions,
+                    ..CredentialReport::new(status)
+                }
+            }
+            Err(e) => {
+                let status = if e.is_rejection() {
+                    CredentialStatus::Invalid
+                } else {
+                    CredentialStatus::Unverified
+                };
+                warn!(status = ?status, "[Funding Guard] Credential check failed: {}", e.message);
+                CredentialReport {
+                    error: Some(e.message),
+                    ..CredentialReport::new(status)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_placeholder_and_missing_keys_are_caught_offline() {
+        assert!(is_placeholder("test_api_key"));
+        assert!(is_placeholder("  YOUR_API_KEY "));
+        assert!(is_placeholder("<binance secret>"));
+        assert!(!is_placeholder(
+            "vmPUZE6mv9SD5VNHk4HlWFsOr6aKE2zvsw0MuIgwCIPy6utIco14y7Ju91duEh8A"
+        ));
+
+        let guard = |key: Option<&str>, secret: Option<&str>| {
+            FundingGuard::new(
+                key.map(str::to_string),
+                secret.map(str::to_string),
+                RateLimiter::default(),
+            )
+        };
+        let report = guard(Some("test_api_key"), Some("real-looking-secret"))
+            .check()
+            .await;
+        assert_eq!(report.status, CredentialStatus::Placeholder);
+        assert!(!report.allows_live_trading());
+        let report = guard(Some("real-looking-key"), None).check().await;
+        assert_eq!(report.status, CredentialStatus::Missing);
+    }
+}
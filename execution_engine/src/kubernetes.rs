@@ -0,0 +1,390 @@
+//! Deploy the agent fleet to a Kubernetes cluster instead of SSH targets.
+//!
+//! The deployer renders a ConfigMap with the agent's config files, one with the
+//! replicas' identity, a Deployment running the kernel image and a Service for
+//! the monitoring API, and applies them with server-side apply so repeated
+//! deployments converge on the same objects. Pods are told where the primary
+//! is and take the fleet key from a Secret, so they unseal the shipped exchange
+//! keys as SSH-deployed replicas do.
+
+use crate::Deployer;
+use common::identity::{AgentIdentity, IDENTITY_PATH, PRIMARY_URL_ENV};
+use common::sealed_config::{FLEET_KEY_SECRET, SEALED_CONFIG_PATH};
+use common::secrets::SECRET_ENV_PREFIX;
+use common::state_store::STATE_PATH;
+use common::{AureliaError, AureliaResult, DeploymentInfo};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Present only on agents that should deploy to a cluster
+pub const KUBERNETES_CONFIG_PATH: &str = "config/kubernetes.json";
+
+const SERVICE_ACCOUNT_DIR: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+/// Deployment directory of the kernel in its container
+const CONTAINER_DEPLOY_PATH: &str = "/opt/aurelia";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KubernetesConfig {
+    pub api_server: String,
+    pub namespace: String,
+    /// Name of the Deployment, ConfigMap and Service
+    pub name: String,
+    pub image: String,
+    /// Bearer token file; defaults to the pod's service account
+    pub token_path: PathBuf,
+    /// CA bundle of the API server; defaults to the pod's service account
+    pub ca_cert_path: Option<PathBuf>,
+    /// Local config files shipped in the ConfigMap
+    pub config_files: Vec<PathBuf>,
+    pub api_port: u16,
+    /// Secret in the namespace holding the hex fleet key under `fleet_config_key`
+    pub fleet_key_secret: String,
+}
+
+impl Default for KubernetesConfig {
+    fn default() -> Self {
+        Self {
+            api_server: "https://kubernetes.default.svc".to_string(),
+            namespace: "default".to_string(),
+            name: "aurelia".to_string(),
+            image: "aurelia-kernel:latest".to_string(),
+            token_path: PathBuf::from(SERVICE_ACCOUNT_DIR).join("token"),
+            ca_cert_path: Some(PathBuf::from(SERVICE_ACCOUNT_DIR).join("ca.crt")),
+            config_files: vec![PathBuf::from("config/strategy.json")],
+            api_port: 8080,
+            fleet_key_secret: "aurelia-fleet-key".to_string(),
+        }
+    }
+}
+
+impl KubernetesConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Option<Self>> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+}
+
+/// A [`Deployer`] that runs replicas as pods of one Deployment
+pub struct KubernetesDeployer {
+    config: KubernetesConfig,
+    replicas: u32,
+    /// Generated files added to the ConfigMap next to `config.config_files`
+    extra_files: BTreeMap<String, String>,
+    /// Identity the pods run as; pods of one Deployment share it
+    identity: Option<AgentIdentity>,
+    /// Where the pods find the primary
+    primary_url: Option<String>,
+}
+
+impl KubernetesDeployer {
+    /// `replicas` is the number of pods to run, normally the replication strategy's
+    /// `min_replicas`
+    pub fn new(config: KubernetesConfig, replicas: u32) -> Self {
+        Self {
+            config,
+            replicas,
+            extra_files: BTreeMap::new(),
+            identity: None,
+            primary_url: None,
+        }
+    }
+
+    /// Run the pods as `identity`, normally a child of the deploying agent.
+    /// Without one each pod would boot as a new primary.
+    pub fn with_identity(mut self, identity: AgentIdentity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Tell the pods where the primary is, see [`PRIMARY_URL_ENV`]
+    pub fn with_primary_url(mut self, primary_url: impl Into<String>) -> Self {
+        self.primary_url = Some(primary_url.into());
+        self
+    }
+
+    /// Ship a generated config file, e.g. the replicas' replication strategy
+    pub fn with_config_file(mut self, name: impl Into<String>, contents: String) -> Self {
+        self.extra_files.insert(name.into(), contents);
+        self
+    }
+
+    fn labels(&self) -> Value {
+        json!({
+            "app.kubernetes.io/name": self.config.name,
+            "app.kubernetes.io/managed-by": "aurelia",
+        })
+    }
+
+    fn identity_name(&self) -> String {
+        format!("{}-identity", self.config.name)
+    }
+
+    /// ConfigMaps, Deployment and Service, in the order they are applied
+    pub fn render_manifests(&self) -> AureliaResult<Vec<Value>> {
+        let mut data = BTreeMap::new();
+        // Exchange keys only travel sealed with the fleet key
+        let sealed = Path::new(SEALED_CONFIG_PATH).to_path_buf();
+        let sealed = sealed.exists().then_some(sealed);
+        for path in self.config.config_files.iter().chain(&sealed) {
+            if !path.exists() {
+                continue;
+            }
+            // Pods keep their own funds and allocations, never the primary's
+            if path.file_name() == Path::new(STATE_PATH).file_name() {
+                warn!("[Kubernetes] Not deploying {:?}", path);
+                continue;
+            }
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| AureliaError::Config(format!("Invalid config file {:?}", path)))?;
+            data.insert(name.to_string(), std::fs::read_to_string(path)?);
+        }
+        for (name, contents) in &self.extra_files {
+            let name = name.rsplit('/').next().unwrap_or(name);
+            data.insert(name.to_string(), contents.clone());
+        }
+
+        let name = &self.config.name;
+        let namespace = &self.config.namespace;
+        let port = self.config.api_port;
+        let probe =
+            |path: &str| json!({ "httpGet": { "path": path, "port": port }, "periodSeconds": 10 });
+
+        let mut env = vec![json!({
+            "name": format!("{}{}", SECRET_ENV_PREFIX, FLEET_KEY_SECRET.to_uppercase()),
+            "valueFrom": {
+                "secretKeyRef": { "name": self.config.fleet_key_secret, "key": FLEET_KEY_SECRET },
+            },
+        })];
+        if let Some(primary_url) = &self.primary_url {
+            env.push(json!({ "name": PRIMARY_URL_ENV, "value": primary_url }));
+        }
+        let mut mounts = vec![json!({
+            "name": "config",
+            "mountPath": format!("{}/config", CONTAINER_DEPLOY_PATH),
+        })];
+        let mut volumes = vec![json!({ "name": "config", "configMap": { "name": name } })];
+        let mut manifests = vec![json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": { "name": name, "namespace": namespace, "labels": self.labels() },
+            "data": data,
+        })];
+        if let Some(identity) = &self.identity {
+            let file = IDENTITY_PATH.rsplit('/').next().unwrap_or(IDENTITY_PATH);
+            mounts.push(json!({
+                "name": "identity",
+                "mountPath": format!("{}/{}", CONTAINER_DEPLOY_PATH, IDENTITY_PATH),
+                "subPath": file,
+                "readOnly": true,
+            }));
+            volumes.push(json!({
+                "name": "identity",
+                "configMap": { "name": self.identity_name() },
+            }));
+            manifests.push(json!({
+                "apiVersion": "v1",
+                "kind": "ConfigMap",
+                "metadata": {
+                    "name": self.identity_name(),
+                    "namespace": namespace,
+                    "labels": self.labels(),
+                },
+                "data": { file: identity.to_json()? },
+            }));
+        }
+
+        manifests.extend([
+            json!({
+                "apiVersion": "apps/v1",
+                "kind": "Deployment",
+                "metadata": { "name": name, "namespace": namespace, "labels": self.labels() },
+                "spec": {
+                    "replicas": self.replicas,
+                    "selector": { "matchLabels": self.labels() },
+                    "template": {
+                        "metadata": { "labels": self.labels() },
+                        "spec": {
+                            "containers": [{
+                                "name": "kernel",
+                                "image": self.config.image,
+                                "ports": [{ "name": "monitoring", "containerPort": port }],
+                                "env": env,
+                                "livenessProbe": probe("/live"),
+                                "readinessProbe": probe("/ready"),
+                                "volumeMounts": mounts,
+                            }],
+                            "volumes": volumes,
+                        },
+                    },
+                },
+            }),
+            json!({
+                "apiVersion": "v1",
+                "kind": "Service",
+                "metadata": { "name": name, "namespace": namespace, "labels": self.labels() },
+                "spec": {
+                    "selector": self.labels(),
+                    "ports": [{ "name": "monitoring", "port": port, "targetPort": port }],
+                },
+            }),
+        ]);
+        Ok(manifests)
+    }
+
+    fn resource_path(&self, manifest: &Value) -> AureliaResult<String> {
+        let kind = manifest["kind"].as_str().unwrap_or_default();
+        let (prefix, plural) = match kind {
+            "ConfigMap" => ("/api/v1", "configmaps"),
+            "Service" => ("/api/v1", "services"),
+            "Deployment" => ("/apis/apps/v1", "deployments"),
+            other => {
+                return Err(AureliaError::Deployment(format!(
+                    "Unsupported manifest kind {}",
+                    other
+                )))
+            }
+        };
+        Ok(format!(
+            "{}/namespaces/{}/{}/{}",
+            prefix,
+            self.config.namespace,
+            plural,
+            manifest["metadata"]["name"]
+                .as_str()
+                .unwrap_or(&self.config.name)
+        ))
+    }
+
+    fn client(&self) -> AureliaResult<reqwest::blocking::Client> {
+        let mut builder = reqwest::blocking::Client::builder();
+        if let Some(ca) = self.config.ca_cert_path.as_ref().filter(|ca| ca.exists()) {
+            let certificate = reqwest::Certificate::from_pem(&std::fs::read(ca)?)
+                .map_err(|e| AureliaError::Config(format!("Invalid CA bundle: {}", e)))?;
+            builder = builder.add_root_certificate(certificate);
+        }
+        builder
+            .build()
+            .map_err(|e| AureliaError::Deployment(e.to_string()))
+    }
+
+    fn apply(&self) -> AureliaResult<()> {
+        let token = std::fs::read_to_string(&self.config.token_path)?;
+        let client = self.client()?;
+
+        for manifest in self.render_manifests()? {
+            let url = format!(
+                "{}{}?fieldManager=aurelia&force=true",
+                self.config.api_server,
+                self.resource_path(&manifest)?
+            );
+            let response = client
+                .patch(&url)
+                .bearer_auth(token.trim())
+                .header("Content-Type", "application/apply-patch+yaml")
+                .body(manifest.to_string())
+                .send()
+                .map_err(|e| AureliaError::Deployment(e.to_string()))?;
+            if !response.status().is_success() {
+                let status = response.status();
+                let body = response.text().unwrap_or_default();
+                return Err(AureliaError::Deployment(format!(
+                    "Applying {} {} failed with {}: {}",
+                    manifest["kind"], manifest["metadata"]["name"], status, body
+                )));
+            }
+            info!(kind = %manifest["kind"], name = %manifest["metadata"]["name"], "[Kubernetes] Applied");
+        }
+        Ok(())
+    }
+}
+
+impl Deployer for KubernetesDeployer {
+    /// The cluster schedules the pods, so the SSH details in `info` are unused
+    fn deploy(&self, _info: DeploymentInfo) -> AureliaResult<()> {
+        info!(
+            namespace = %self.config.namespace,
+            replicas = self.replicas,
+            "[Kubernetes] Deploying {}",
+            self.config.name
+        );
+        // The blocking client must not run on an async runtime thread
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| self.apply())
+                .join()
+                .unwrap_or_else(|_| Err(AureliaError::Deployment("Apply panicked".to_string())))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_manifests() {
+        let config = KubernetesConfig {
+            namespace: "trading".to_string(),
+            config_files: Vec::new(),
+            ..KubernetesConfig::default()
+        };
+        let identity = AgentIdentity::new_root().spawn_child();
+        let deployer = KubernetesDeployer::new(config, 3)
+            .with_config_file("config/replication.json", "{\"enabled\":false}".to_string())
+            .with_identity(identity.clone())
+            .with_primary_url("http://10.0.0.1:8080");
+        let manifests = deployer.render_manifests().unwrap();
+
+        let kinds: Vec<_> = manifests
+            .iter()
+            .map(|m| m["kind"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            kinds,
+            vec!["ConfigMap", "ConfigMap", "Deployment", "Service"]
+        );
+        assert_eq!(
+            manifests[0]["data"]["replication.json"],
+            "{\"enabled\":false}"
+        );
+        assert_eq!(
+            deployer.resource_path(&manifests[1]).unwrap(),
+            "/api/v1/namespaces/trading/configmaps/aurelia-identity"
+        );
+        let shipped: AgentIdentity =
+            serde_json::from_str(manifests[1]["data"]["identity.json"].as_str().unwrap()).unwrap();
+        assert_eq!(shipped, identity);
+        assert!(!shipped.is_primary());
+
+        let deployment = &manifests[2];
+        assert_eq!(deployment["spec"]["replicas"], 3);
+        assert_eq!(
+            deployer.resource_path(deployment).unwrap(),
+            "/apis/apps/v1/namespaces/trading/deployments/aurelia"
+        );
+        let container = &deployment["spec"]["template"]["spec"]["containers"][0];
+        assert_eq!(
+            container["volumeMounts"][1]["mountPath"],
+            "/opt/aurelia/data/identity.json"
+        );
+        let env = container["env"].as_array().unwrap();
+        assert_eq!(env[0]["name"], "AURELIA_SECRET_FLEET_CONFIG_KEY");
+        assert_eq!(
+            env[0]["valueFrom"]["secretKeyRef"]["name"],
+            "aurelia-fleet-key"
+        );
+        assert_eq!(env[1]["name"], "AURELIA_PRIMARY_URL");
+        assert_eq!(env[1]["value"], "http://10.0.0.1:8080");
+    }
+}
@@ -1,17 +1,60 @@
-use common::{AppEvent, DeploymentInfo, EventReceiver, EventSender, StrategyDecision};
+use common::audit::{self, AuditCategory};
+use common::lag::LAG_REPORT_INTERVAL;
+use common::sealed_config::SEALED_CONFIG_PATH;
+use common::{
+    host_port, AccountingConfig, AppEvent, AureliaError, AureliaResult, CancellationToken,
+    CircuitBreaker, ConsumerLag, CostModel, CredentialReport, CredentialStatus, DeploymentInfo,
+    EndpointClass, EventMeta, EventReceiver, EventSender, Fill, FlattenReport, LagHandler,
+    Liquidity, RateLimiter, StateStore, StrategyDecision, StrategySet, TradeLedger,
+};
 use dotenvy::dotenv;
 use ssh2::Session;
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::io::{Read, Write};
 use std::net::TcpStream;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::broadcast::error::RecvError;
 use tracing::{error, info, warn};
 
+pub mod accounting;
+pub mod algos;
+pub mod allocator;
+pub mod clock_sync;
+pub mod flatten;
+pub mod funding_guard;
+pub mod kubernetes;
+pub mod orders;
+pub mod protection;
+pub mod reconciliation;
+pub mod user_data;
+
+pub use accounting::Accountant;
+pub use algos::{Algorithm, ExecutionAlgoConfig};
+pub use allocator::{AllocationConfig, Allocator};
+pub use clock_sync::{ClockSync, ClockSyncConfig};
+pub use funding_guard::{is_placeholder, FundingGuard};
+pub use kubernetes::{KubernetesConfig, KubernetesDeployer};
+use orders::{child_order_id, client_order_id, LotSize, ORDER_QUANTITY};
+pub use orders::{IntentStore, OrderManager};
+pub use protection::{ProtectionConfig, ProtectionManager, ProtectionStore};
+pub use reconciliation::PositionDrift;
+use user_data::Balance;
+pub use user_data::{Portfolio, SharedPortfolio, UserDataStream};
+
+/// Trading volume over this period sets the fee tier of simulated fills.
+pub const FEE_TIER_WINDOW: chrono::TimeDelta = chrono::TimeDelta::days(30);
+
 /// A trait for deploying the agent.
 pub trait Deployer: Send + Sync {
-    fn deploy(&self, info: DeploymentInfo) -> Result<(), Box<dyn std::error::Error>>;
+    fn deploy(&self, info: DeploymentInfo) -> AureliaResult<()>;
+}
+
+fn ssh_error(e: ssh2::Error) -> AureliaError {
+    AureliaError::Ssh(e.to_string())
 }
 
 /// The real deployment handler that uses ssh2.
@@ -19,27 +62,28 @@ pub trait Deployer: Send + Sync {
 struct SshDeployer;
 
 impl Deployer for SshDeployer {
-    fn deploy(&self, info: DeploymentInfo) -> Result<(), Box<dyn std::error::Error>> {
+    fn deploy(&self, info: DeploymentInfo) -> AureliaResult<()> {
         info!(
             ip = %info.ip,
             user = %info.remote_user,
             "[Deployment] Starting deployment with ssh2."
         );
 
-        let tcp = TcpStream::connect(format!("{}:22", info.ip))?;
-        let mut sess = Session::new()?;
+        let tcp = TcpStream::connect(host_port(&info.ip, 22))?;
+        let mut sess = Session::new().map_err(ssh_error)?;
         sess.set_tcp_stream(tcp);
-        sess.handshake()?;
+        sess.handshake().map_err(ssh_error)?;
 
         sess.userauth_pubkey_file(
             &info.remote_user,
             None,
             Path::new(&info.private_key_path),
             None,
-        )?;
+        )
+        .map_err(ssh_error)?;
 
         if !sess.authenticated() {
-            return Err("SSH authentication failed.".into());
+            return Err(AureliaError::Ssh("SSH authentication failed.".to_string()));
         }
 
         info!("[Deployment] SSH connection and authentication successful.");
@@ -72,37 +116,31 @@ impl Deployer for SshDeployer {
 
 impl SshDeployer {
     #[allow(dead_code)]
-    fn exec_command(
-        &self,
-        sess: &mut Session,
-        cmd: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut channel = sess.channel_session()?;
-        channel.exec(cmd)?;
+    fn exec_command(&self, sess: &mut Session, cmd: &str) -> AureliaResult<()> {
+        let mut channel = sess.channel_session().map_err(ssh_error)?;
+        channel.exec(cmd).map_err(ssh_error)?;
         // Reading the output is important to ensure the command has finished.
         let mut output = String::new();
         channel.read_to_string(&mut output)?;
-        channel.wait_close()?;
+        channel.wait_close().map_err(ssh_error)?;
         Ok(())
     }
 
     #[allow(dead_code)]
-    fn upload_files(
-        &self,
-        sess: &mut Session,
-        info: &DeploymentInfo,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    fn upload_files(&self, sess: &mut Session, info: &DeploymentInfo) -> AureliaResult<()> {
         let current_exe = env::current_exe()?;
         let base_path = current_exe
             .parent()
             .and_then(|p| p.parent()) // Move up from target/debug
-            .ok_or("Could not determine project root")?;
+            .ok_or_else(|| {
+                AureliaError::Deployment("Could not determine project root".to_string())
+            })?;
 
+        // The exchange keys go sealed with the fleet key, never as the plaintext .env
         let files_to_upload = vec![
             ("kernel", "kernel"),
-            (".env", ".env"),
+            (SEALED_CONFIG_PATH, SEALED_CONFIG_PATH),
             ("config/strategy.json", "config/strategy.json"),
-            ("config/state.json", "config/state.json"),
         ];
 
         for (local_suffix, remote_suffix) in files_to_upload {
@@ -119,24 +157,146 @@ impl SshDeployer {
                 local_path, remote_path_str
             );
             let data = fs::read(&local_path)?;
-            let mut remote_file =
-                sess.scp_send(Path::new(&remote_path_str), 0o644, data.len() as u64, None)?;
+            let mut remote_file = sess
+                .scp_send(Path::new(&remote_path_str), 0o644, data.len() as u64, None)
+                .map_err(ssh_error)?;
             remote_file.write_all(&data)?;
         }
         Ok(())
     }
 }
 
+/// Sends orders live when trading is enabled and otherwise records simulated
+/// fills; shared between the engine and the executions it hands to [`algos`].
+#[derive(Clone)]
+pub(crate) struct OrderExecutor {
+    orders: Option<Arc<OrderManager>>,
+    ledger: Option<TradeLedger>,
+    limiter: RateLimiter,
+    /// Prices simulated fills
+    costs: CostModel,
+    /// Told about simulated fills; live fills reach it through the user-data stream
+    protection: Option<Arc<ProtectionManager>>,
+    /// Prices simulated fills once the model's latency has passed
+    portfolio: SharedPortfolio,
+}
+
+impl OrderExecutor {
+    /// The `LOT_SIZE` of `symbol` when orders go to the exchange; simulated fills
+    /// take any quantity.
+    async fn lot_size(&self, symbol: &str) -> AureliaResult<Option<LotSize>> {
+        match &self.orders {
+            Some(orders) => Ok(Some(orders.filters(symbol).await?.lot_size)),
+            None => Ok(None),
+        }
+    }
+
+    /// Execute `quantity` of a decision, or of its `slice`-th child order.
+    async fn execute(
+        &self,
+        decision: &StrategyDecision,
+        meta: &EventMeta,
+        quantity: f64,
+        slice: Option<u32>,
+    ) -> AureliaResult<()> {
+        let (side, symbol, price) = match decision {
+            StrategyDecision::Buy(symbol, price) => ("BUY", symbol, price),
+            StrategyDecision::Sell(symbol, price) => ("SELL", symbol, price),
+            StrategyDecision::Hold(_) => return Ok(()),
+        };
+        if let Some(orders) = &self.orders {
+            return orders.submit(decision, meta, quantity, slice).await;
+        }
+
+        self.limiter
+            .acquire(EndpointClass::ExchangeOrders, 1.0)
+            .await;
+        let client_order_id = client_order_id(decision, meta).map(|id| match slice {
+            Some(slice) => child_order_id(&id, slice),
+            None => id,
+        });
+        info!(
+            correlation_id = %meta.correlation_id,
+            client_order_id = %client_order_id.as_deref().unwrap_or_default(),
+            strategy_id = meta.strategy_id.as_deref().unwrap_or_default(),
+            symbol = symbol,
+            price = price,
+            quantity = quantity,
+            "[Execution Engine] PREPARING REAL {} ORDER (live trading disabled)",
+            side
+        );
+
+        // Without live trading every order is assumed to cross the spread, filling
+        // after the model's latency at the price by then plus slippage. The
+        // decision price only stands in while nothing has traded on the symbol.
+        tokio::time::sleep(self.costs.latency()).await;
+        let price = self
+            .portfolio
+            .read()
+            .await
+            .prices
+            .price(symbol)
+            .unwrap_or(*price);
+        let volume_30d = self.ledger.as_ref().map_or(0.0, |ledger| {
+            ledger.volume_since(chrono::Utc::now() - FEE_TIER_WINDOW)
+        });
+        let simulated = self
+            .costs
+            .simulate(side, price, quantity, volume_30d, Liquidity::Taker);
+        let fill = Fill {
+            timestamp: chrono::Utc::now(),
+            symbol: symbol.clone(),
+            side: side.to_string(),
+            price: simulated.price,
+            quantity,
+            fee: simulated.fee,
+            fee_asset: None,
+            client_order_id,
+            simulated: true,
+            strategy_id: meta.strategy_id.clone(),
+            rationale: meta.rationale.as_deref().cloned(),
+        };
+        if let Some(protection) = &self.protection {
+            protection.on_fill(&fill).await;
+        }
+        if let Some(ledger) = &self.ledger {
+            ledger.record(fill)?;
+        }
+        Ok(())
+    }
+}
+
 pub struct ExecutionEngine {
-    #[allow(dead_code)]
     tx: EventSender,
     rx: EventReceiver,
-    #[allow(dead_code)]
-    api_key: String,
-    #[allow(dead_code)]
-    api_secret: String,
-    #[allow(dead_code)]
-    client: reqwest::Client,
+    /// API key and secret from the environment, `None` if either is missing
+    credentials: Option<(String, String)>,
+    /// Outcome of [`ExecutionEngine::verify_credentials`]
+    credential_report: Option<CredentialReport>,
+    limiter: RateLimiter,
+    /// Set when live trading is enabled; otherwise decisions are only logged
+    orders: Option<Arc<OrderManager>>,
+    ledger: Option<TradeLedger>,
+    /// Sizes the orders of strategy decisions; see [`ExecutionEngine::with_strategies`]
+    allocation: Option<(StrategySet, StateStore)>,
+    /// How decisions are worked into the market; see [`algos`]
+    algo: ExecutionAlgoConfig,
+    /// Balances and open orders, kept by the user-data stream
+    portfolio: SharedPortfolio,
+    /// How the portfolio is valued and reported as funds
+    accounting: AccountingConfig,
+    protection: Option<Arc<ProtectionManager>>,
+    costs: CostModel,
+    /// Running executions by strategy and symbol, with their side
+    executions: HashMap<(String, String), (&'static str, CancellationToken)>,
+    /// Why decisions are being dropped, after an emergency flatten
+    suspended: Option<String>,
+    /// Set once [`ExecutionEngine::reconcile_account`] succeeded, so `run` does not
+    /// reconcile the orders again
+    reconciled: bool,
+    /// When a resync last reconciled the account, so consumers lagging together
+    /// do not each cost a round of exchange requests
+    last_resync: Option<Instant>,
     deployer: Box<dyn Deployer>,
 }
 
@@ -145,41 +305,338 @@ impl ExecutionEngine {
         // Try to load .env file but don't panic if it doesn't exist
         let _ = dotenv();
 
-        // Use default values for tests or when env vars are not set
-        let api_key = env::var("BINANCE_API_KEY").unwrap_or_else(|_| {
-            warn!("BINANCE_API_KEY not set, using placeholder");
-            "test_api_key".to_string()
-        });
-        let api_secret = env::var("BINANCE_API_SECRET").unwrap_or_else(|_| {
-            warn!("BINANCE_API_SECRET not set, using placeholder");
-            "test_api_secret".to_string()
-        });
+        // Without both keys trading is simulated and no account stream is opened
+        let credentials = match (env::var("BINANCE_API_KEY"), env::var("BINANCE_API_SECRET")) {
+            (Ok(key), Ok(secret)) => Some((key, secret)),
+            _ => {
+                warn!("BINANCE_API_KEY or BINANCE_API_SECRET not set, trading is simulated");
+                None
+            }
+        };
 
         info!("[Execution Engine] Initialized.");
 
         Self {
             tx,
             rx,
-            api_key,
-            api_secret,
-            client: reqwest::Client::new(),
+            credentials,
+            credential_report: None,
+            limiter: RateLimiter::default(),
+            orders: None,
+            ledger: None,
+            allocation: None,
+            algo: ExecutionAlgoConfig::default(),
+            portfolio: SharedPortfolio::default(),
+            accounting: AccountingConfig::default(),
+            costs: CostModel::default(),
+            protection: None,
+            executions: HashMap::new(),
+            suspended: None,
+            reconciled: false,
+            last_resync: None,
             deployer,
         }
     }
 
+    /// Draw order requests from a budget shared with the rest of the agent
+    pub fn with_rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    /// Check the API keys against the exchange, see [`FundingGuard`]. Uses the rate
+    /// limiter set so far.
+    pub async fn verify_credentials(&mut self) -> CredentialReport {
+        let (api_key, api_secret) = self.credentials.clone().unzip();
+        let report = FundingGuard::new(api_key, api_secret, self.limiter.clone())
+            .check()
+            .await;
+        audit::record(AuditCategory::ConfigChange, "credential_check", &report);
+        self.credential_report = Some(report.clone());
+        report
+    }
+
+    /// Send real orders, tracked in `intents`. Takes the rate limiter set so far, and
+    /// refuses unless [`ExecutionEngine::verify_credentials`] found keys that may trade.
+    pub fn with_live_trading(mut self, intents: IntentStore) -> Self {
+        let report = self.credential_report.as_ref();
+        let Some((api_key, api_secret)) = self
+            .credentials
+            .clone()
+            .filter(|_| report.is_some_and(CredentialReport::allows_live_trading))
+        else {
+            error!(
+                status = ?report.map(|r| r.status),
+                "[Execution Engine] Refusing live trading without verified trading credentials"
+            );
+            return self;
+        };
+        let breaker = CircuitBreaker::new("exchange_rest").with_event_bus(self.tx.clone());
+        self.orders = Some(Arc::new(
+            OrderManager::new(intents, api_key, api_secret, self.limiter.clone())
+                .with_circuit_breaker(breaker),
+        ));
+        self
+    }
+
+    /// Record every fill, simulated or live
+    pub fn with_ledger(mut self, ledger: TradeLedger) -> Self {
+        self.ledger = Some(ledger);
+        self
+    }
+
+    /// Size each strategy's orders from its allocation of the funds in `state`; the
+    /// allocator's latest allocation in `state` takes precedence over the configured
+    /// one. Decisions of strategies the set does not enable are dropped.
+    pub fn with_strategies(mut self, strategies: StrategySet, state: StateStore) -> Self {
+        self.allocation = Some((strategies, state));
+        self
+    }
+
+    /// Price simulated fills with `model` rather than at the decision price with
+    /// the default fee
+    pub fn with_cost_model(mut self, model: CostModel) -> Self {
+        self.costs = model;
+        self
+    }
+
+    /// Value the account's balances in the configured currency
+    pub fn with_accounting(mut self, config: AccountingConfig) -> Self {
+        self.accounting = config;
+        self
+    }
+
+    /// Execute decisions with the configured algorithm instead of one order each
+    pub fn with_execution_algo(mut self, config: ExecutionAlgoConfig) -> Self {
+        self.algo = config;
+        self
+    }
+
+    /// Protect positions with stop-loss and take-profit orders. Places them through
+    /// the order manager and ledger set so far, so it comes after
+    /// [`ExecutionEngine::with_live_trading`] and [`ExecutionEngine::with_ledger`].
+    pub fn with_protection(mut self, config: ProtectionConfig, store: ProtectionStore) -> Self {
+        self.protection = Some(Arc::new(ProtectionManager::new(
+            config,
+            self.tx.clone(),
+            self.executor(),
+            self.portfolio.clone(),
+            store,
+        )));
+        self
+    }
+
+    /// The protection manager, to be run alongside the engine
+    pub fn protection(&self) -> Option<Arc<ProtectionManager>> {
+        self.protection.clone()
+    }
+
+    fn executor(&self) -> OrderExecutor {
+        OrderExecutor {
+            orders: self.orders.clone(),
+            ledger: self.ledger.clone(),
+            limiter: self.limiter.clone(),
+            costs: self.costs.clone(),
+            protection: self.protection.clone(),
+            portfolio: self.portfolio.clone(),
+        }
+    }
+
+    /// Base asset quantity for a decision at `price`, or `None` if its strategy
+    /// may not trade.
+    fn order_quantity(&self, meta: &EventMeta, price: f64) -> Option<f64> {
+        let (Some(strategy_id), Some((strategies, state))) = (&meta.strategy_id, &self.allocation)
+        else {
+            return Some(ORDER_QUANTITY);
+        };
+        let spec = strategies.get(strategy_id)?;
+        let allocation = state.allocation(strategy_id).unwrap_or(spec.allocation);
+        let notional = state.funds() * allocation * strategies.order_fraction;
+        (notional > 0.0 && price > 0.0).then(|| notional / price)
+    }
+
+    /// Order and balance updates for the configured account, or `None` without API
+    /// credentials or when the last check rejected them
+    pub fn user_data_stream(&self) -> Option<UserDataStream> {
+        let rejected = self.credential_report.as_ref().is_some_and(|report| {
+            matches!(
+                report.status,
+                CredentialStatus::Missing
+                    | CredentialStatus::Placeholder
+                    | CredentialStatus::Invalid
+            )
+        });
+        let (api_key, api_secret) = self.credentials.as_ref()?;
+        if rejected || is_placeholder(api_key) || is_placeholder(api_secret) {
+            return None;
+        }
+        let stream = UserDataStream::new(self.tx.clone(), api_key.clone(), self.limiter.clone())
+            .with_portfolio(self.portfolio.clone())
+            .with_accounting(self.accounting.clone());
+        let stream = match &self.protection {
+            Some(protection) => stream.with_protection(protection.clone()),
+            None => stream,
+        };
+        let stream = match &self.orders {
+            Some(orders) => stream.with_orders(orders.clone()),
+            None => stream,
+        };
+        Some(match &self.ledger {
+            Some(ledger) => stream.with_ledger(ledger.clone()),
+            None => stream,
+        })
+    }
+
+    /// Revaluation of the account's balances as prices move. Also keeps the prices
+    /// simulated positions are closed at by an emergency flatten.
+    pub fn accountant(&self) -> Accountant {
+        Accountant::new(
+            self.tx.clone(),
+            self.portfolio.clone(),
+            self.accounting.clone(),
+        )
+    }
+
+    /// Catch up with what happened on the exchange while the agent was down:
+    /// reconcile the order intents, take the account's balances as the portfolio,
+    /// report where they disagree with the ledger's live positions and publish the
+    /// account's value. Does nothing without live trading.
+    pub async fn reconcile_account(&mut self) -> AureliaResult<()> {
+        let Some(orders) = &self.orders else {
+            return Ok(());
+        };
+        orders.reconcile().await?;
+        let balances = orders.balances().await?;
+        self.adopt_balances(balances).await;
+        self.reconciled = true;
+        Ok(())
+    }
+
+    /// Take `balances` as the portfolio's, reporting where they disagree with the
+    /// ledger, and publish the account's value.
+    async fn adopt_balances(&mut self, balances: HashMap<String, Balance>) {
+        if let Some(ledger) = &self.ledger {
+            let drift = reconciliation::position_drift(
+                &ledger.positions(false),
+                &balances,
+                &self.accounting.currency,
+            );
+            for position in &drift {
+                warn!(
+                    asset = %position.asset,
+                    recorded = position.recorded,
+                    held = position.held,
+                    "[Execution Engine] Ledger position differs from the exchange balance"
+                );
+            }
+            audit::record(
+                AuditCategory::RecoveryAction,
+                "reconcile_account",
+                serde_json::json!({
+                    "assets": balances.len(),
+                    "drift": drift.iter().map(|p| &p.asset).collect::<Vec<_>>(),
+                }),
+            );
+        }
+        self.portfolio.write().await.balances = balances;
+        info!("[Execution Engine] Account reconciled with the exchange");
+        self.publish_account_value().await;
+    }
+
+    /// Send the portfolio's state again to a consumer that lost events, see
+    /// [`common::lag`]: refreshed from the exchange with live trading, at most
+    /// once per [`LAG_REPORT_INTERVAL`], as last known otherwise.
+    async fn resync(&mut self, lag: &ConsumerLag) {
+        info!(
+            consumer = %lag.consumer,
+            "[Execution Engine] Resyncing the portfolio after lost events"
+        );
+        let recent = self
+            .last_resync
+            .is_some_and(|at| at.elapsed() < LAG_REPORT_INTERVAL);
+        if self.orders.is_some() && !recent {
+            self.last_resync = Some(Instant::now());
+            if let Err(e) = self.reconcile_account().await {
+                error!("[Execution Engine] Account reconciliation failed: {}", e);
+            }
+        } else {
+            self.publish_account_value().await;
+        }
+    }
+
+    /// Publish the portfolio's value as `FinancialUpdate` and `NetAssetValue`,
+    /// unless no balances are known yet. Funds are withheld while an asset has no
+    /// price: a total leaving it out would read as a loss.
+    async fn publish_account_value(&self) {
+        let nav = {
+            let portfolio = self.portfolio.read().await;
+            if portfolio.balances.is_empty() {
+                return;
+            }
+            portfolio.net_asset_value(&self.accounting)
+        };
+        for event in accounting::value_events(nav) {
+            if let Err(e) = self.tx.send(event) {
+                error!("[Execution Engine] Failed to publish account value: {}", e);
+            }
+        }
+    }
+
     pub async fn run(&mut self) {
         info!("[Execution Engine] Starting...");
+        let lag = LagHandler::new("execution_engine")
+            .with_event_bus(self.tx.clone())
+            .with_resync();
+        if let Some(orders) = self.orders.as_ref().filter(|_| !self.reconciled) {
+            if let Err(e) = orders.reconcile().await {
+                error!("[Execution Engine] Order reconciliation failed: {}", e);
+            }
+        }
         loop {
             match self.rx.recv().await {
-                Ok(AppEvent::StrategyDecision(decision)) => self.handle_decision(decision).await,
+                Ok(AppEvent::StrategyDecision(decision, meta)) => {
+                    if let Some(reason) = &self.suspended {
+                        warn!(
+                            correlation_id = %meta.correlation_id,
+                            "[Execution Engine] Trading suspended ({}), dropping decision",
+                            reason
+                        );
+                        continue;
+                    }
+                    if let Err(e) = self.handle_decision(decision, &meta).await {
+                        error!(
+                            correlation_id = %meta.correlation_id,
+                            "[Execution Engine] Failed to handle decision: {}", e
+                        );
+                    }
+                }
+                Ok(AppEvent::EmergencyFlatten(reason)) => {
+                    let report = self.flatten(reason).await;
+                    if let Err(e) = self.tx.send(AppEvent::FlattenCompleted(report)) {
+                        error!("[Execution Engine] Failed to publish flatten report: {}", e);
+                    }
+                }
+                Ok(AppEvent::PauseTrading(reason)) => {
+                    warn!(reason = %reason, "[Execution Engine] Trading paused");
+                    self.suspended = Some(reason);
+                }
+                Ok(AppEvent::ResumeTrading) => {
+                    if self.suspended.take().is_some() {
+                        warn!("[Execution Engine] Trading resumed");
+                    }
+                }
                 Ok(AppEvent::Deploy(info)) => {
                     if let Err(e) = self.deployer.deploy(info) {
                         error!("[Execution Engine] Deployment failed: {}", e);
                     }
                 }
+                // Its own requests included, answered once the gap is reported
+                Ok(AppEvent::ConsumerLagged(request)) if request.resync => {
+                    self.resync(&request).await
+                }
                 Ok(_) => {}
                 Err(RecvError::Lagged(n)) => {
-                    warn!("[Execution Engine] Lagged by {} messages", n)
+                    lag.lagged(n);
                 }
                 Err(RecvError::Closed) => {
                     error!("[Execution Engine] Event channel closed.");
@@ -189,61 +646,352 @@ impl ExecutionEngine {
         }
     }
 
-    async fn handle_decision(&mut self, decision: StrategyDecision) {
-        match decision {
-            StrategyDecision::Buy(symbol, price) => {
-                info!(
-                    symbol = symbol,
-                    price = price,
-                    "[Execution Engine] PREPARING REAL BUY ORDER"
-                );
-                // self.place_order(symbol, "BUY", 1.0, price).await;
+    #[tracing::instrument(
+        name = "handle_decision",
+        skip_all,
+        fields(
+            correlation_id = %meta.correlation_id,
+            strategy_id = meta.strategy_id.as_deref().unwrap_or_default(),
+        )
+    )]
+    async fn handle_decision(
+        &mut self,
+        decision: StrategyDecision,
+        meta: &EventMeta,
+    ) -> AureliaResult<()> {
+        let (side, symbol, price) = match &decision {
+            StrategyDecision::Buy(symbol, price) => ("BUY", symbol, price),
+            StrategyDecision::Sell(symbol, price) => ("SELL", symbol, price),
+            StrategyDecision::Hold(_) => return Ok(()),
+        };
+        let Some(quantity) = self.order_quantity(meta, *price) else {
+            warn!(
+                correlation_id = %meta.correlation_id,
+                strategy_id = meta.strategy_id.as_deref().unwrap_or_default(),
+                "[Execution Engine] Strategy has no allocation, dropping decision"
+            );
+            return Ok(());
+        };
+        if self.algo.algorithm == Algorithm::Immediate {
+            let executor = self.executor();
+            if executor.orders.is_some() {
+                return executor.execute(&decision, meta, quantity, None).await;
             }
-            StrategyDecision::Sell(symbol, price) => {
+            // A simulated fill waits out the model's latency, which must not hold
+            // up the decisions behind it
+            let meta = meta.clone();
+            tokio::spawn(async move {
+                if let Err(e) = executor.execute(&decision, &meta, quantity, None).await {
+                    error!(
+                        correlation_id = %meta.correlation_id,
+                        "[Execution Engine] Simulated fill failed: {}",
+                        e
+                    );
+                }
+            });
+            return Ok(());
+        }
+
+        // A reversal stops the execution still working the other way
+        let key = (meta.strategy_id.clone().unwrap_or_default(), symbol.clone());
+        self.executions
+            .retain(|_, (_, token)| !token.is_cancelled());
+        if let Some((running_side, token)) = self.executions.get(&key) {
+            if *running_side == side {
                 info!(
-                    symbol = symbol,
-                    price = price,
-                    "[Execution Engine] PREPARING REAL SELL ORDER"
+                    correlation_id = %meta.correlation_id,
+                    "[Execution Engine] {} {} is already being executed, skipping decision",
+                    side,
+                    symbol
                 );
-                // self.place_order(symbol, "SELL", 1.0, price).await;
+                return Ok(());
             }
-            StrategyDecision::Hold(_) => {}
+            info!(
+                correlation_id = %meta.correlation_id,
+                "[Execution Engine] Strategy reversed, cancelling {} execution on {}",
+                running_side,
+                symbol
+            );
+            token.cancel();
         }
+        let token = CancellationToken::new();
+        self.executions.insert(key, (side, token.clone()));
+        let execution = algos::AlgoExecution::new(
+            self.algo.clone(),
+            self.executor(),
+            self.tx.clone(),
+            decision,
+            meta.clone(),
+            quantity,
+            token,
+        );
+        tokio::spawn(execution.run());
+        Ok(())
     }
 
-    // This function is ready but commented out for safety.
-    // To enable, you would uncomment the calls in handle_decision.
-    /*
-    async fn place_order(&self, symbol: String, side: &str, quantity: f64, price: f64) {
-        let timestamp = chrono::Utc::now().timestamp_millis();
-        let params = format!(
-            "symbol={}&side={}&type=LIMIT&timeInForce=GTC&quantity={}&price={}&timestamp={}",
-            symbol, side, quantity, price, timestamp
+    /// Cancel every open order and running execution, close every position at
+    /// market and drop decisions until trading is resumed.
+    async fn flatten(&mut self, reason: String) -> FlattenReport {
+        error!(reason = %reason, "[Execution Engine] EMERGENCY FLATTEN");
+        self.suspended = Some(reason.clone());
+        for (_, (_, token)) in self.executions.drain() {
+            token.cancel();
+        }
+
+        let mut cancelled_orders = 0;
+        let mut errors = Vec::new();
+        if let Some(orders) = &self.orders {
+            match orders.cancel_all().await {
+                Ok((cancelled, failures)) => {
+                    cancelled_orders = cancelled;
+                    errors.extend(failures);
+                }
+                Err(e) => errors.push(format!("failed to list open orders: {}", e)),
+            }
+        }
+        if let Some(protection) = &self.protection {
+            protection.clear().await;
+        }
+
+        let positions = {
+            let portfolio = self.portfolio.read().await;
+            match (&self.orders, &self.ledger) {
+                (Some(_), Some(ledger)) => flatten::live_positions(
+                    &ledger.positions(false),
+                    &portfolio,
+                    &self.accounting.currency,
+                ),
+                (None, Some(ledger)) => {
+                    flatten::paper_positions(&ledger.positions(true), &portfolio.prices)
+                }
+                (Some(_), None) => {
+                    errors.push("no trade ledger, positions were left open".to_string());
+                    Vec::new()
+                }
+                (None, None) => Vec::new(),
+            }
+        };
+        // Closing fills must not open new protection
+        let executor = OrderExecutor {
+            protection: None,
+            ..self.executor()
+        };
+        let mut closed_positions = Vec::new();
+        for mut position in positions {
+            let result = match &self.orders {
+                Some(orders) => {
+                    // The exchange rejects quantities off the symbol's lot size
+                    match orders.filters(&position.symbol).await {
+                        Ok(filters) => {
+                            position.quantity = filters.lot_size.round_down(position.quantity)
+                        }
+                        Err(e) => {
+                            errors.push(format!(
+                                "failed to look up the lot size of {}: {}",
+                                position.symbol, e
+                            ));
+                            continue;
+                        }
+                    }
+                    if position.quantity <= 0.0 {
+                        errors.push(format!(
+                            "{} position is below the lot size, left open",
+                            position.symbol
+                        ));
+                        continue;
+                    }
+                    orders
+                        .close_at_market(&position.symbol, &position.side, position.quantity)
+                        .await
+                }
+                None => {
+                    let decision = match position.side.as_str() {
+                        "BUY" => StrategyDecision::Buy(position.symbol.clone(), position.price),
+                        _ => StrategyDecision::Sell(position.symbol.clone(), position.price),
+                    };
+                    executor
+                        .execute(&decision, &EventMeta::default(), position.quantity, None)
+                        .await
+                }
+            };
+            match result {
+                Ok(()) => closed_positions.push(position),
+                Err(e) => errors.push(format!("failed to close {}: {}", position.symbol, e)),
+            }
+        }
+
+        let report = FlattenReport {
+            reason,
+            cancelled_orders,
+            closed_positions,
+            errors,
+            simulated: self.orders.is_none(),
+            timestamp: chrono::Utc::now(),
+        };
+        audit::record(AuditCategory::Order, "emergency_flatten", &report);
+        error!(
+            cancelled_orders = report.cancelled_orders,
+            closed_positions = report.closed_positions.len(),
+            errors = report.errors.len(),
+            "[Execution Engine] Positions flattened, trading suspended"
         );
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{EventBus, ExecutionProgress, ExecutionStatus, Topic};
+    use std::time::Duration;
+
+    struct NoDeployer;
+
+    impl Deployer for NoDeployer {
+        fn deploy(&self, _info: DeploymentInfo) -> AureliaResult<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_funds_are_withheld_while_a_balance_has_no_price() {
+        let bus = EventBus::new(16);
+        let mut events = bus.subscribe_as("test", &[Topic::Financial]);
+        let mut engine = ExecutionEngine::new(bus.clone(), bus.subscribe(), Box::new(NoDeployer));
 
-        let signature = {
-            use hmac::{Hmac, Mac};
-            use sha2::Sha256;
-            type HmacSha256 = Hmac<Sha256>;
-            let mut mac = HmacSha256::new_from_slice(self.api_secret.as_bytes()).unwrap();
-            mac.update(params.as_bytes());
-            hex::encode(mac.finalize().into_bytes())
+        // Nothing has traded yet, so the price book is empty
+        let balances = [("USDT", 1000.0), ("BTC", 0.5)]
+            .into_iter()
+            .map(|(asset, free)| (asset.to_string(), Balance { free, locked: 0.0 }))
+            .collect();
+        engine.adopt_balances(balances).await;
+        assert!(
+            matches!(events.try_recv(), Ok(AppEvent::NetAssetValue(nav)) if nav.unpriced == ["BTC"])
+        );
+        assert!(events.try_recv().is_err());
+
+        engine
+            .portfolio
+            .write()
+            .await
+            .prices
+            .update("BTCUSDT", 60000.0);
+        engine.publish_account_value().await;
+        assert!(matches!(events.try_recv(), Ok(AppEvent::FinancialUpdate(f)) if f == 31000.0));
+    }
+
+    #[tokio::test]
+    async fn test_simulated_fills_take_the_price_after_the_latency() {
+        let portfolio = SharedPortfolio::default();
+        let ledger = TradeLedger::in_memory();
+        let executor = OrderExecutor {
+            orders: None,
+            ledger: Some(ledger.clone()),
+            limiter: RateLimiter::default(),
+            costs: CostModel {
+                latency_ms: 50,
+                ..CostModel::default()
+            },
+            protection: None,
+            portfolio: portfolio.clone(),
         };
+        let decision = StrategyDecision::Buy("BTCUSDT".to_string(), 60000.0);
+        let meta = EventMeta::default();
 
-        let request_url = format!("https://api.binance.com/api/v3/order?{}&signature={}", params, signature);
+        // Nothing has traded yet, so the decision price stands in
+        executor.execute(&decision, &meta, 0.1, None).await.unwrap();
+        assert_eq!(ledger.fills()[0].price, 60000.0);
 
-        match self.client.post(&request_url)
-            .header("X-MBX-APIKEY", &self.api_key)
-            .send()
-            .await {
-                Ok(response) => {
-                    info!("Order placement response: {:?}", response.text().await);
-                    // Here you would update the funds based on the real trade outcome
-                }
-                Err(e) => {
-                    error!("Failed to place order: {}", e);
-                }
+        // The market moves while the order is on its way
+        let fill = tokio::spawn(async move { executor.execute(&decision, &meta, 0.1, None).await });
+        portfolio.write().await.prices.update("BTCUSDT", 60300.0);
+        fill.await.unwrap().unwrap();
+        assert_eq!(ledger.fills()[1].price, 60300.0);
+    }
+
+    async fn next_progress(events: &mut EventReceiver) -> ExecutionProgress {
+        loop {
+            let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+                .await
+                .expect("no execution progress")
+                .unwrap();
+            if let AppEvent::ExecutionProgress(progress) = event {
+                return *progress;
             }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_repeated_decisions_join_the_running_execution_and_reversals_cancel_it() {
+        let bus = EventBus::new(64);
+        let mut events = bus.subscribe_as("test", &[Topic::Financial]);
+        // The second slice is an hour away, so the execution keeps running
+        let mut engine = ExecutionEngine::new(bus.clone(), bus.subscribe(), Box::new(NoDeployer))
+            .with_ledger(TradeLedger::in_memory())
+            .with_execution_algo(ExecutionAlgoConfig {
+                algorithm: Algorithm::Twap,
+                duration_seconds: 7200,
+                slices: 2,
+                ..Default::default()
+            });
+        let meta = |strategy: &str| EventMeta {
+            strategy_id: Some(strategy.to_string()),
+            ..Default::default()
+        };
+        let buy = StrategyDecision::Buy("BTCUSDT".to_string(), 60000.0);
+
+        let first = meta("momentum");
+        engine.handle_decision(buy.clone(), &first).await.unwrap();
+        let progress = next_progress(&mut events).await;
+        assert_eq!(progress.correlation_id, first.correlation_id);
+        assert_eq!(progress.status, ExecutionStatus::Running);
+        assert_eq!(progress.child_orders, 1);
+
+        // The same decision again does not start a second execution
+        engine
+            .handle_decision(buy.clone(), &meta("momentum"))
+            .await
+            .unwrap();
+        assert_eq!(engine.executions.len(), 1);
+        let (side, token) = &engine.executions[&("momentum".to_string(), "BTCUSDT".to_string())];
+        assert_eq!(*side, "BUY");
+        assert!(!token.is_cancelled());
+
+        // Another strategy's decision runs alongside it
+        engine
+            .handle_decision(buy.clone(), &meta("mean_reversion"))
+            .await
+            .unwrap();
+        assert_eq!(engine.executions.len(), 2);
+        assert_eq!(next_progress(&mut events).await.child_orders, 1);
+
+        // A reversal cancels the buy and starts selling
+        let reversal = meta("momentum");
+        engine
+            .handle_decision(
+                StrategyDecision::Sell("BTCUSDT".to_string(), 60100.0),
+                &reversal,
+            )
+            .await
+            .unwrap();
+        let mut seen = Vec::new();
+        while seen.len() < 2 {
+            seen.push(next_progress(&mut events).await);
+        }
+        let cancelled = seen.iter().find(|p| p.side == "BUY").unwrap();
+        assert_eq!(cancelled.correlation_id, first.correlation_id);
+        assert_eq!(cancelled.status, ExecutionStatus::Cancelled);
+        let selling = seen.iter().find(|p| p.side == "SELL").unwrap();
+        assert_eq!(selling.correlation_id, reversal.correlation_id);
+        assert_eq!(selling.status, ExecutionStatus::Running);
+        assert_eq!(
+            engine.executions[&("momentum".to_string(), "BTCUSDT".to_string())].0,
+            "SELL"
+        );
+        assert!(
+            !engine.executions[&("mean_reversion".to_string(), "BTCUSDT".to_string())]
+                .1
+                .is_cancelled()
+        );
     }
-    */
 }
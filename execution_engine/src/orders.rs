@@ -0,0 +1,1582 @@
+//! Order intents: every order is recorded under a client order ID derived from the
+//! decision that caused it before it is sent, so a retried or replayed decision
+//! cannot place a second order, and orders whose outcome was lost to the network are
+//! found again by reconciling against the exchange.
+
+use crate::user_data::Balance;
+use common::audit::{self, AuditCategory};
+use common::clock;
+use common::{
+    AureliaError, AureliaResult, CircuitBreaker, CorrelationId, DecisionRationale, EndpointClass,
+    EventMeta, OrderUpdate, RateLimiter, StrategyDecision,
+};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tracing::{debug, error, info, warn};
+
+pub const ORDER_INTENTS_PATH: &str = "data/order_intents.json";
+
+const REST_API: &str = "https://api.binance.com";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Bounds how long an order waits on the exchange before its outcome is left to
+/// reconciliation
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Base asset quantity of orders not sized by a strategy allocation.
+pub const ORDER_QUANTITY: f64 = 0.001;
+/// Binance's code for a rejected new order; a `newClientOrderId` already in use is
+/// one of its causes.
+const NEW_ORDER_REJECTED: i64 = -2010;
+const UNKNOWN_ORDER: i64 = -2013;
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// The client order ID for a decision: the same decision always maps to the same ID,
+/// so the exchange turns a resubmission into a duplicate error instead of an order.
+pub fn client_order_id(decision: &StrategyDecision, meta: &EventMeta) -> Option<String> {
+    let side = match decision {
+        StrategyDecision::Buy(..) => "b",
+        StrategyDecision::Sell(..) => "s",
+        StrategyDecision::Hold(_) => return None,
+    };
+    let correlation_id = meta.correlation_id.as_str();
+    match &meta.strategy_id {
+        // "au" + side + 32 hex digits stays within Binance's 36 character limit
+        None => Some(format!("au{}{}", side, correlation_id)),
+        // Strategies deciding on the same tick share its correlation ID, so part of
+        // it makes way for a hash of the strategy ID
+        Some(strategy_id) => {
+            let strategy = hex::encode(&Sha256::digest(strategy_id.as_bytes())[..4]);
+            let tail = correlation_id.get(..24).unwrap_or(correlation_id);
+            Some(format!("au{}{}{}", side, strategy, tail))
+        }
+    }
+}
+
+/// The client order ID of the `slice`-th child order when a decision is executed
+/// in slices, see [`crate::algos`]. The last three characters of the decision's
+/// ID make way for the slice number, keeping children of the same decision
+/// distinct and just as replay-safe.
+pub fn child_order_id(parent: &str, slice: u32) -> String {
+    let stem = parent
+        .get(..parent.len().saturating_sub(3))
+        .unwrap_or(parent);
+    format!("{}{:03x}", stem, slice % 0x1000)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntentStatus {
+    /// Recorded, not yet confirmed by the exchange
+    Pending,
+    Open,
+    Filled,
+    Canceled,
+    Rejected,
+}
+
+impl IntentStatus {
+    fn from_exchange(status: &str) -> Self {
+        match status {
+            "NEW" | "PARTIALLY_FILLED" | "PENDING_NEW" => IntentStatus::Open,
+            "FILLED" => IntentStatus::Filled,
+            "REJECTED" => IntentStatus::Rejected,
+            _ => IntentStatus::Canceled,
+        }
+    }
+
+    /// Whether the exchange may still act on the order.
+    pub fn is_unsettled(self) -> bool {
+        matches!(self, IntentStatus::Pending | IntentStatus::Open)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderIntent {
+    pub client_order_id: String,
+    pub symbol: String,
+    /// `BUY` or `SELL`
+    pub side: String,
+    pub quantity: f64,
+    /// Limit price; zero for market orders
+    pub price: f64,
+    pub status: IntentStatus,
+    pub exchange_order_id: Option<u64>,
+    /// Unix milliseconds
+    pub created_at: u64,
+    pub updated_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy_id: Option<String>,
+    /// Why the strategy placed the order, copied to its fills
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rationale: Option<DecisionRationale>,
+}
+
+impl OrderIntent {
+    pub fn new(decision: &StrategyDecision, meta: &EventMeta, quantity: f64) -> Option<Self> {
+        let (symbol, price, side) = match decision {
+            StrategyDecision::Buy(symbol, price) => (symbol, *price, "BUY"),
+            StrategyDecision::Sell(symbol, price) => (symbol, *price, "SELL"),
+            StrategyDecision::Hold(_) => return None,
+        };
+        let now = now_millis();
+        Some(Self {
+            client_order_id: client_order_id(decision, meta)?,
+            symbol: symbol.clone(),
+            side: side.to_string(),
+            quantity,
+            price,
+            status: IntentStatus::Pending,
+            exchange_order_id: None,
+            created_at: now,
+            updated_at: now,
+            strategy_id: meta.strategy_id.clone(),
+            rationale: meta.rationale.as_deref().cloned(),
+        })
+    }
+}
+
+/// A one-cancels-the-other sell protecting a long position: a limit leg at the
+/// take-profit price and a stop-limit leg at the stop-loss; see [`crate::protection`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OcoOrder {
+    pub symbol: String,
+    pub quantity: f64,
+    pub take_profit: f64,
+    pub stop_price: f64,
+    /// Limit price of the stop leg once triggered
+    pub stop_limit_price: f64,
+    pub list_client_order_id: String,
+    pub stop_client_order_id: String,
+    pub limit_client_order_id: String,
+}
+
+impl OcoOrder {
+    pub fn new(
+        symbol: impl Into<String>,
+        quantity: f64,
+        take_profit: f64,
+        stop_price: f64,
+        stop_limit_price: f64,
+    ) -> Self {
+        let id = CorrelationId::new();
+        Self {
+            symbol: symbol.into(),
+            quantity,
+            take_profit,
+            stop_price,
+            stop_limit_price,
+            list_client_order_id: format!("aul{}", id),
+            stop_client_order_id: format!("aup{}", id),
+            limit_client_order_id: format!("aut{}", id),
+        }
+    }
+
+    fn legs(&self) -> [(&str, f64); 2] {
+        [
+            (&self.limit_client_order_id, self.take_profit),
+            (&self.stop_client_order_id, self.stop_limit_price),
+        ]
+    }
+}
+
+/// How long filled, cancelled and rejected intents are kept. A decision replayed
+/// within it is still recognised as sent; older ones only bloat the file.
+const INTENT_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Order intents by client order ID, saved after every change. Settled intents
+/// past [`INTENT_RETENTION`] are dropped whenever the store is saved.
+#[derive(Debug)]
+pub struct IntentStore {
+    path: PathBuf,
+    intents: BTreeMap<String, OrderIntent>,
+    retention: Duration,
+}
+
+impl IntentStore {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let intents = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            path,
+            intents,
+            retention: INTENT_RETENTION,
+        })
+    }
+
+    /// Keep settled intents for `retention` instead of [`INTENT_RETENTION`]
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Drop settled intents last updated more than the retention window before
+    /// `now`, in Unix milliseconds. Returns how many were dropped.
+    fn compact(&mut self, now: u64) -> usize {
+        let cutoff = now.saturating_sub(self.retention.as_millis() as u64);
+        let before = self.intents.len();
+        self.intents
+            .retain(|_, intent| intent.status.is_unsettled() || intent.updated_at >= cutoff);
+        before - self.intents.len()
+    }
+
+    fn save(&mut self) -> AureliaResult<()> {
+        let compacted = self.compact(now_millis());
+        if compacted > 0 {
+            debug!(
+                compacted,
+                "[Execution Engine] Dropped settled order intents"
+            );
+        }
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&self.intents)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    pub fn get(&self, client_order_id: &str) -> Option<&OrderIntent> {
+        self.intents.get(client_order_id)
+    }
+
+    /// Record a new intent. Returns `false`, leaving the store unchanged, if one with
+    /// the same client order ID exists.
+    pub fn insert(&mut self, intent: OrderIntent) -> AureliaResult<bool> {
+        if self.intents.contains_key(&intent.client_order_id) {
+            return Ok(false);
+        }
+        self.intents.insert(intent.client_order_id.clone(), intent);
+        self.save()?;
+        Ok(true)
+    }
+
+    pub fn update(
+        &mut self,
+        client_order_id: &str,
+        status: IntentStatus,
+        exchange_order_id: Option<u64>,
+    ) -> AureliaResult<()> {
+        let Some(intent) = self.intents.get_mut(client_order_id) else {
+            return Ok(());
+        };
+        intent.status = status;
+        intent.exchange_order_id = exchange_order_id.or(intent.exchange_order_id);
+        intent.updated_at = now_millis();
+        self.save()
+    }
+
+    pub fn unsettled(&self) -> impl Iterator<Item = &OrderIntent> {
+        self.intents.values().filter(|i| i.status.is_unsettled())
+    }
+}
+
+/// An order as the exchange reports it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExchangeOrder {
+    pub symbol: String,
+    pub order_id: u64,
+    pub client_order_id: String,
+    pub status: String,
+}
+
+/// The orders of an order list such as an OCO, as the exchange reports them.
+#[derive(Debug, Deserialize)]
+struct OrderList {
+    orders: Vec<OrderListEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OrderListEntry {
+    order_id: u64,
+    client_order_id: String,
+}
+
+/// Trading rules of the symbols asked about, from `/api/v3/exchangeInfo`.
+#[derive(Debug, Deserialize)]
+struct ExchangeInfo {
+    symbols: Vec<SymbolInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SymbolInfo {
+    filters: Vec<SymbolFilter>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SymbolFilter {
+    filter_type: String,
+    #[serde(default)]
+    min_qty: Option<String>,
+    #[serde(default)]
+    step_size: Option<String>,
+    #[serde(default)]
+    tick_size: Option<String>,
+    /// Of the `MIN_NOTIONAL` and `NOTIONAL` filters
+    #[serde(default)]
+    min_notional: Option<String>,
+}
+
+/// Decimals of `step`; steps are powers of ten.
+fn step_decimals(step: f64) -> usize {
+    if step <= 0.0 {
+        return 0;
+    }
+    (-step.log10()).ceil().max(0.0) as usize
+}
+
+/// `value` as a multiple of `step`, the number of steps rounded by `round`.
+fn round_to_step(value: f64, step: f64, round: fn(f64) -> f64) -> f64 {
+    if step <= 0.0 {
+        return value;
+    }
+    // Rounding to the step's decimals keeps `0.3` from being sent as
+    // `0.30000000000000004`
+    let scale = 10f64.powi(step_decimals(step) as i32);
+    let steps = round(value / step + 1e-9);
+    (steps * step * scale).round() / scale
+}
+
+/// `value` written with the decimals of `step`, as the exchange expects it.
+fn format_step(value: f64, step: f64) -> String {
+    if step <= 0.0 {
+        return value.to_string();
+    }
+    format!("{:.*}", step_decimals(step), value)
+}
+
+/// A symbol's `LOT_SIZE` filter: order quantities must be a multiple of
+/// `step_size` and at least `min_qty`, or the exchange rejects the order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LotSize {
+    pub step_size: f64,
+    pub min_qty: f64,
+}
+
+impl LotSize {
+    /// `quantity` rounded down to a multiple of the step, or zero when that is
+    /// below the minimum.
+    pub fn round_down(&self, quantity: f64) -> f64 {
+        let rounded = round_to_step(quantity, self.step_size, f64::floor);
+        if rounded < self.min_qty {
+            0.0
+        } else {
+            rounded
+        }
+    }
+}
+
+/// The filters of a symbol its orders have to pass.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SymbolFilters {
+    pub lot_size: LotSize,
+    /// `PRICE_FILTER` tick size prices must be a multiple of; zero without one
+    pub tick_size: f64,
+    /// Smallest price times quantity of an order; zero without a notional filter
+    pub min_notional: f64,
+}
+
+impl SymbolFilters {
+    fn from_info(info: ExchangeInfo) -> Option<Self> {
+        let filters = info.symbols.into_iter().next()?.filters;
+        let find = |filter_type: &str| {
+            filters
+                .iter()
+                .find(|filter| filter.filter_type == filter_type)
+        };
+        let lot_size = find("LOT_SIZE")?;
+        let lot_size = LotSize {
+            step_size: lot_size.step_size.as_deref()?.parse().ok()?,
+            min_qty: lot_size.min_qty.as_deref()?.parse().ok()?,
+        };
+        let tick_size = match find("PRICE_FILTER") {
+            Some(filter) => filter.tick_size.as_deref()?.parse().ok()?,
+            None => 0.0,
+        };
+        let min_notional = match find("NOTIONAL").or_else(|| find("MIN_NOTIONAL")) {
+            Some(filter) => filter.min_notional.as_deref()?.parse().ok()?,
+            None => 0.0,
+        };
+        Some(Self {
+            lot_size,
+            tick_size,
+            min_notional,
+        })
+    }
+
+    /// `price` rounded to the nearest tick.
+    pub fn round_price(&self, price: f64) -> f64 {
+        round_to_step(price, self.tick_size, f64::round)
+    }
+
+    /// `quantity` as sent to the exchange.
+    pub fn format_quantity(&self, quantity: f64) -> String {
+        format_step(quantity, self.lot_size.step_size)
+    }
+
+    /// `price` as sent to the exchange.
+    pub fn format_price(&self, price: f64) -> String {
+        format_step(price, self.tick_size)
+    }
+
+    /// Round a limit order to the filters, or explain why the exchange would
+    /// reject it anyway.
+    fn fit(&self, intent: &mut OrderIntent) -> Result<(), String> {
+        intent.quantity = self.lot_size.round_down(intent.quantity);
+        intent.price = self.round_price(intent.price);
+        if intent.quantity <= 0.0 {
+            return Err(format!(
+                "{} quantity is below the lot size of {}",
+                intent.symbol, self.lot_size.min_qty
+            ));
+        }
+        if intent.quantity * intent.price < self.min_notional {
+            return Err(format!(
+                "{} order of {} is below the minimum notional of {}",
+                intent.symbol,
+                intent.quantity * intent.price,
+                self.min_notional
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// What the account behind the API keys may do.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AccountInfo {
+    pub can_trade: bool,
+    pub can_withdraw: bool,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    #[serde(default)]
+    pub balances: Vec<AccountBalance>,
+}
+
+/// One asset of [`AccountInfo`], with amounts as the exchange's decimal strings.
+#[derive(Debug, Deserialize)]
+pub(crate) struct AccountBalance {
+    pub asset: String,
+    pub free: String,
+    pub locked: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    code: i64,
+    msg: String,
+}
+
+/// A failed exchange request. `status` and `code` are set when the exchange answered.
+#[derive(Debug)]
+pub(crate) struct RequestError {
+    status: Option<u16>,
+    code: Option<i64>,
+    pub message: String,
+    /// Held back by the circuit breaker without reaching the exchange
+    not_sent: bool,
+}
+
+impl RequestError {
+    /// The exchange refused the request, as opposed to failing to process it; after a
+    /// 5xx the order may still have been placed.
+    pub fn is_rejection(&self) -> bool {
+        self.status.is_some_and(|s| (400..500).contains(&s))
+    }
+
+    /// The request certainly did not place an order: it was refused or never sent.
+    fn is_not_placed(&self) -> bool {
+        self.not_sent || self.is_rejection()
+    }
+
+    /// The exchange is unreachable or overloaded rather than refusing the request;
+    /// these count towards opening the circuit.
+    fn is_outage(&self) -> bool {
+        match self.status {
+            None => !self.not_sent,
+            Some(status) => status >= 500 || status == 429 || status == 418,
+        }
+    }
+
+    fn is_duplicate(&self) -> bool {
+        self.code == Some(NEW_ORDER_REJECTED) && self.message.contains("Duplicate order")
+    }
+}
+
+impl From<RequestError> for AureliaError {
+    fn from(e: RequestError) -> Self {
+        AureliaError::Exchange(e.message)
+    }
+}
+
+/// Client order ID prefix of market sells closing a position at its stop-loss
+/// or take-profit, kept apart from strategy sells (`aus`)
+const PROTECTIVE_CLOSE_PREFIX: &str = "auc";
+
+/// Client order ID prefix of market orders flattening positions
+const FLATTEN_PREFIX: &str = "auf";
+
+/// Client order ID prefixes of the orders the agent places: decisions (`aub`,
+/// `aus`), OCO protection (`aul` list, `aut` take-profit and `aup` stop leg),
+/// protective closes (`auc`) and flattening (`auf`).
+const OWN_ORDER_PREFIXES: [&str; 7] = [
+    "aub",
+    "aus",
+    "aul",
+    "aut",
+    "aup",
+    PROTECTIVE_CLOSE_PREFIX,
+    FLATTEN_PREFIX,
+];
+
+/// Whether the agent placed the order with this client ID. Orders placed by hand
+/// or by another bot on the same account are none of reconciliation's business.
+fn is_own_order(client_order_id: &str) -> bool {
+    OWN_ORDER_PREFIXES
+        .iter()
+        .any(|prefix| client_order_id.starts_with(prefix))
+}
+
+/// What reconciliation should do about each order.
+#[derive(Debug, Default, PartialEq)]
+struct Reconciliation {
+    /// Placed by the agent and open on the exchange with no intent behind them
+    cancel: Vec<(String, u64)>,
+    /// Open on the exchange but not placed by the agent; left alone
+    foreign: Vec<(String, u64)>,
+    /// Open on the exchange and known: (client order ID, exchange order ID)
+    retrack: Vec<(String, u64)>,
+    /// Unsettled locally but not open on the exchange; their outcome must be looked up
+    resolve: Vec<(String, String)>,
+}
+
+fn plan(store: &IntentStore, open: &[ExchangeOrder]) -> Reconciliation {
+    let mut reconciliation = Reconciliation::default();
+    let open_ids: HashSet<&str> = open.iter().map(|o| o.client_order_id.as_str()).collect();
+    for order in open {
+        if store.get(&order.client_order_id).is_some() {
+            reconciliation
+                .retrack
+                .push((order.client_order_id.clone(), order.order_id));
+        } else if !is_own_order(&order.client_order_id) {
+            reconciliation
+                .foreign
+                .push((order.symbol.clone(), order.order_id));
+        } else {
+            reconciliation
+                .cancel
+                .push((order.symbol.clone(), order.order_id));
+        }
+    }
+    for intent in store.unsettled() {
+        if !open_ids.contains(intent.client_order_id.as_str()) {
+            reconciliation
+                .resolve
+                .push((intent.symbol.clone(), intent.client_order_id.clone()));
+        }
+    }
+    reconciliation
+}
+
+/// Signed Binance spot order endpoints.
+pub(crate) struct BinanceOrders {
+    client: reqwest::Client,
+    api_key: String,
+    api_secret: String,
+    limiter: RateLimiter,
+    breaker: CircuitBreaker,
+}
+
+impl BinanceOrders {
+    pub fn new(api_key: String, api_secret: String, limiter: RateLimiter) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .connect_timeout(CONNECT_TIMEOUT)
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            api_key,
+            api_secret,
+            limiter,
+            breaker: CircuitBreaker::new("exchange_rest"),
+        }
+    }
+
+    fn sign(&self, params: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.api_secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(params.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    async fn request<T: serde::de::DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        params: &[(&str, String)],
+        class: EndpointClass,
+        weight: f64,
+    ) -> Result<T, RequestError> {
+        self.request_as(method, path, params, class, weight, true)
+            .await
+    }
+
+    /// A request that is signed with the API secret when `signed`, as account
+    /// and order endpoints require.
+    #[tracing::instrument(name = "exchange_request", skip(self, params, class, weight, signed))]
+    async fn request_as<T: serde::de::DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        params: &[(&str, String)],
+        class: EndpointClass,
+        weight: f64,
+        signed: bool,
+    ) -> Result<T, RequestError> {
+        if !self.breaker.try_acquire() {
+            return Err(RequestError {
+                status: None,
+                code: None,
+                message: format!("{} {}: circuit open for exchange REST", method, path),
+                not_sent: true,
+            });
+        }
+        let result = self.send(method, path, params, class, weight, signed).await;
+        match &result {
+            Err(e) if e.is_outage() => self.breaker.record_failure(),
+            _ => self.breaker.record_success(),
+        }
+        result
+    }
+
+    async fn send<T: serde::de::DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        params: &[(&str, String)],
+        class: EndpointClass,
+        weight: f64,
+        signed: bool,
+    ) -> Result<T, RequestError> {
+        self.limiter.acquire(class, weight).await;
+        let mut query: Vec<String> = params.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        let url = if signed {
+            // Stamped with the exchange's time so that a drifting local clock is not rejected
+            query.push(format!("timestamp={}", clock::exchange_now_millis()));
+            let query = query.join("&");
+            format!(
+                "{}{}?{}&signature={}",
+                REST_API,
+                path,
+                query,
+                self.sign(&query)
+            )
+        } else {
+            format!("{}{}?{}", REST_API, path, query.join("&"))
+        };
+
+        let transport = |e: reqwest::Error| RequestError {
+            status: None,
+            code: None,
+            message: format!("{} {}: {}", method, path, e),
+            not_sent: false,
+        };
+        let response = self
+            .client
+            .request(method.clone(), &url)
+            .header("X-MBX-APIKEY", &self.api_key)
+            .send()
+            .await
+            .map_err(transport)?;
+        if response.status().is_success() {
+            return response.json().await.map_err(transport);
+        }
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        let api_error = serde_json::from_str::<ApiError>(&body).ok();
+        Err(RequestError {
+            status: Some(status.as_u16()),
+            code: api_error.as_ref().map(|e| e.code),
+            message: format!(
+                "{} {}: {} {}",
+                method,
+                path,
+                status,
+                api_error.map(|e| e.msg).unwrap_or(body)
+            ),
+            not_sent: false,
+        })
+    }
+
+    async fn submit(
+        &self,
+        intent: &OrderIntent,
+        filters: &SymbolFilters,
+    ) -> Result<ExchangeOrder, RequestError> {
+        let params = [
+            ("symbol", intent.symbol.clone()),
+            ("side", intent.side.clone()),
+            ("type", "LIMIT".to_string()),
+            ("timeInForce", "GTC".to_string()),
+            ("quantity", filters.format_quantity(intent.quantity)),
+            ("price", filters.format_price(intent.price)),
+            ("newClientOrderId", intent.client_order_id.clone()),
+            ("newOrderRespType", "RESULT".to_string()),
+        ];
+        self.request(
+            reqwest::Method::POST,
+            "/api/v3/order",
+            &params,
+            EndpointClass::ExchangeOrders,
+            1.0,
+        )
+        .await
+    }
+
+    async fn submit_market(
+        &self,
+        intent: &OrderIntent,
+        filters: &SymbolFilters,
+    ) -> Result<ExchangeOrder, RequestError> {
+        let params = [
+            ("symbol", intent.symbol.clone()),
+            ("side", intent.side.clone()),
+            ("type", "MARKET".to_string()),
+            ("quantity", filters.format_quantity(intent.quantity)),
+            ("newClientOrderId", intent.client_order_id.clone()),
+            ("newOrderRespType", "RESULT".to_string()),
+        ];
+        self.request(
+            reqwest::Method::POST,
+            "/api/v3/order",
+            &params,
+            EndpointClass::ExchangeOrders,
+            1.0,
+        )
+        .await
+    }
+
+    async fn submit_oco(
+        &self,
+        oco: &OcoOrder,
+        filters: &SymbolFilters,
+    ) -> Result<OrderList, RequestError> {
+        let params = [
+            ("symbol", oco.symbol.clone()),
+            ("side", "SELL".to_string()),
+            ("quantity", filters.format_quantity(oco.quantity)),
+            ("price", filters.format_price(oco.take_profit)),
+            ("stopPrice", filters.format_price(oco.stop_price)),
+            ("stopLimitPrice", filters.format_price(oco.stop_limit_price)),
+            ("stopLimitTimeInForce", "GTC".to_string()),
+            ("listClientOrderId", oco.list_client_order_id.clone()),
+            ("limitClientOrderId", oco.limit_client_order_id.clone()),
+            ("stopClientOrderId", oco.stop_client_order_id.clone()),
+            ("newOrderRespType", "RESULT".to_string()),
+        ];
+        self.request(
+            reqwest::Method::POST,
+            "/api/v3/order/oco",
+            &params,
+            EndpointClass::ExchangeOrders,
+            1.0,
+        )
+        .await
+    }
+
+    async fn cancel_oco(&self, oco: &OcoOrder) -> Result<OrderList, RequestError> {
+        let params = [
+            ("symbol", oco.symbol.clone()),
+            ("listClientOrderId", oco.list_client_order_id.clone()),
+        ];
+        self.request(
+            reqwest::Method::DELETE,
+            "/api/v3/orderList",
+            &params,
+            EndpointClass::ExchangeOrders,
+            1.0,
+        )
+        .await
+    }
+
+    /// The account's permissions; fails when the keys or the signature are rejected.
+    pub async fn account(&self) -> Result<AccountInfo, RequestError> {
+        self.request(
+            reqwest::Method::GET,
+            "/api/v3/account",
+            &[("omitZeroBalances", "true".to_string())],
+            EndpointClass::ExchangeMarketData,
+            20.0,
+        )
+        .await
+    }
+
+    async fn open_orders(&self) -> Result<Vec<ExchangeOrder>, RequestError> {
+        self.request(
+            reqwest::Method::GET,
+            "/api/v3/openOrders",
+            &[],
+            EndpointClass::ExchangeMarketData,
+            80.0,
+        )
+        .await
+    }
+
+    async fn query(
+        &self,
+        symbol: &str,
+        client_order_id: &str,
+    ) -> Result<ExchangeOrder, RequestError> {
+        let params = [
+            ("symbol", symbol.to_string()),
+            ("origClientOrderId", client_order_id.to_string()),
+        ];
+        self.request(
+            reqwest::Method::GET,
+            "/api/v3/order",
+            &params,
+            EndpointClass::ExchangeMarketData,
+            4.0,
+        )
+        .await
+    }
+
+    async fn filters(&self, symbol: &str) -> Result<Option<SymbolFilters>, RequestError> {
+        let info: ExchangeInfo = self
+            .request_as(
+                reqwest::Method::GET,
+                "/api/v3/exchangeInfo",
+                &[("symbol", symbol.to_string())],
+                EndpointClass::ExchangeMarketData,
+                2.0,
+                false,
+            )
+            .await?;
+        Ok(SymbolFilters::from_info(info))
+    }
+
+    async fn cancel(&self, symbol: &str, order_id: u64) -> Result<ExchangeOrder, RequestError> {
+        let params = [
+            ("symbol", symbol.to_string()),
+            ("orderId", order_id.to_string()),
+        ];
+        self.request(
+            reqwest::Method::DELETE,
+            "/api/v3/order",
+            &params,
+            EndpointClass::ExchangeOrders,
+            1.0,
+        )
+        .await
+    }
+}
+
+/// Client order IDs sent and still waiting for the exchange's answer, so that
+/// reconciliation does not take them for orders that never arrived. Removed
+/// again on drop.
+struct InFlight<'a> {
+    ids: &'a std::sync::Mutex<HashSet<String>>,
+    sent: Vec<String>,
+}
+
+impl<'a> InFlight<'a> {
+    fn new(ids: &'a std::sync::Mutex<HashSet<String>>, sent: Vec<String>) -> Self {
+        ids.lock()
+            .expect("in-flight lock poisoned")
+            .extend(sent.iter().cloned());
+        Self { ids, sent }
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        let mut ids = self.ids.lock().expect("in-flight lock poisoned");
+        for id in &self.sent {
+            ids.remove(id);
+        }
+    }
+}
+
+/// Submits orders at most once per decision and keeps their intents in step with the
+/// exchange. The intent store is only locked to record and update intents, never
+/// across an exchange request, so a slow request holds up no other order.
+pub struct OrderManager {
+    store: Mutex<IntentStore>,
+    exchange: BinanceOrders,
+    in_flight: std::sync::Mutex<HashSet<String>>,
+    /// Filters by symbol; they change too rarely to look up per order
+    filters: std::sync::Mutex<HashMap<String, SymbolFilters>>,
+}
+
+impl OrderManager {
+    pub fn new(
+        store: IntentStore,
+        api_key: String,
+        api_secret: String,
+        limiter: RateLimiter,
+    ) -> Self {
+        Self {
+            store: Mutex::new(store),
+            exchange: BinanceOrders::new(api_key, api_secret, limiter),
+            filters: std::sync::Mutex::new(HashMap::new()),
+            in_flight: std::sync::Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Guard exchange requests with `breaker` instead of a private one
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.exchange.breaker = breaker;
+        self
+    }
+
+    /// Record the intent for a decision, or for one `slice` of it, and send it
+    /// unless it was sent before.
+    #[tracing::instrument(
+        name = "order_round_trip",
+        skip_all,
+        fields(correlation_id = %meta.correlation_id, quantity = quantity, slice = slice)
+    )]
+    pub async fn submit(
+        &self,
+        decision: &StrategyDecision,
+        meta: &EventMeta,
+        quantity: f64,
+        slice: Option<u32>,
+    ) -> AureliaResult<()> {
+        let Some(mut intent) = OrderIntent::new(decision, meta, quantity) else {
+            return Ok(());
+        };
+        if let Some(slice) = slice {
+            intent.client_order_id = child_order_id(&intent.client_order_id, slice);
+        }
+        let filters = self.filters(&intent.symbol).await?;
+        filters.fit(&mut intent).map_err(AureliaError::Exchange)?;
+        let mut store = self.store.lock().await;
+        if !store.insert(intent.clone())? {
+            warn!(
+                client_order_id = %intent.client_order_id,
+                "[Execution Engine] Order already submitted for this decision, skipping"
+            );
+            return Ok(());
+        }
+        // Marked before the store is released, so reconciliation never sees it unsent
+        let in_flight = InFlight::new(&self.in_flight, vec![intent.client_order_id.clone()]);
+        drop(store);
+
+        let result = self.exchange.submit(&intent, &filters).await;
+        let mut store = self.store.lock().await;
+        drop(in_flight);
+        audit::record(
+            AuditCategory::Order,
+            "submit",
+            serde_json::json!({
+                "intent": intent,
+                "exchange_order_id": result.as_ref().ok().map(|order| order.order_id),
+                "error": result.as_ref().err().map(|e| e.message.clone()),
+            }),
+        );
+        match result {
+            Ok(order) => {
+                info!(
+                    client_order_id = %intent.client_order_id,
+                    order_id = order.order_id,
+                    "[Execution Engine] Order accepted"
+                );
+                let status = IntentStatus::from_exchange(&order.status);
+                store.update(&intent.client_order_id, status, Some(order.order_id))
+            }
+            Err(e) if e.is_duplicate() => {
+                // The exchange already has it; reconciliation will pick up its id
+                warn!(
+                    client_order_id = %intent.client_order_id,
+                    "[Execution Engine] Exchange reports a duplicate order"
+                );
+                Ok(())
+            }
+            Err(e) if e.is_not_placed() => {
+                store.update(&intent.client_order_id, IntentStatus::Rejected, None)?;
+                Err(e.into())
+            }
+            // The order may or may not exist, so it stays pending until the next
+            // reconciliation finds out
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Place an OCO sell. Both legs are recorded as intents before it is sent, so
+    /// reconciliation keeps them rather than cancelling them as unknown orders.
+    pub async fn submit_oco(&self, oco: &OcoOrder) -> AureliaResult<()> {
+        let filters = self.filters(&oco.symbol).await?;
+        let mut store = self.store.lock().await;
+        let now = now_millis();
+        let mut legs = Vec::new();
+        for (client_order_id, price) in oco.legs() {
+            legs.push(client_order_id.to_string());
+            store.insert(OrderIntent {
+                client_order_id: client_order_id.to_string(),
+                symbol: oco.symbol.clone(),
+                side: "SELL".to_string(),
+                quantity: oco.quantity,
+                price,
+                status: IntentStatus::Pending,
+                exchange_order_id: None,
+                created_at: now,
+                updated_at: now,
+                strategy_id: None,
+                rationale: None,
+            })?;
+        }
+        let in_flight = InFlight::new(&self.in_flight, legs);
+        drop(store);
+
+        let result = self.exchange.submit_oco(oco, &filters).await;
+        let mut store = self.store.lock().await;
+        drop(in_flight);
+        audit::record(
+            AuditCategory::Order,
+            "submit_oco",
+            serde_json::json!({
+                "oco": oco,
+                "error": result.as_ref().err().map(|e| e.message.clone()),
+            }),
+        );
+        match result {
+            Ok(list) => {
+                for order in list.orders {
+                    store.update(
+                        &order.client_order_id,
+                        IntentStatus::Open,
+                        Some(order.order_id),
+                    )?;
+                }
+                Ok(())
+            }
+            Err(e) if e.is_not_placed() => {
+                for (client_order_id, _) in oco.legs() {
+                    store.update(client_order_id, IntentStatus::Rejected, None)?;
+                }
+                Err(e.into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Cancel both legs of an OCO sell.
+    pub async fn cancel_oco(&self, oco: &OcoOrder) -> AureliaResult<()> {
+        let result = self.exchange.cancel_oco(oco).await;
+        audit::record(
+            AuditCategory::Order,
+            "cancel_oco",
+            serde_json::json!({
+                "list_client_order_id": oco.list_client_order_id,
+                "error": result.as_ref().err().map(|e| e.message.clone()),
+            }),
+        );
+        result?;
+        let mut store = self.store.lock().await;
+        for (client_order_id, _) in oco.legs() {
+            store.update(client_order_id, IntentStatus::Canceled, None)?;
+        }
+        Ok(())
+    }
+
+    /// Cancel every open order this agent placed; orders placed by hand or by
+    /// another bot on the same account are left open. Returns how many were
+    /// cancelled and a description of each that could not be.
+    pub async fn cancel_all(&self) -> AureliaResult<(usize, Vec<String>)> {
+        let open = self.exchange.open_orders().await?;
+        let own: Vec<&ExchangeOrder> = open
+            .iter()
+            .filter(|order| is_own_order(&order.client_order_id))
+            .collect();
+        // Cancelled without holding the store, which order submission needs
+        let mut settled = Vec::new();
+        let mut cancelled = 0;
+        let mut failures = Vec::new();
+        for order in &own {
+            match self.exchange.cancel(&order.symbol, order.order_id).await {
+                Ok(_) => {
+                    cancelled += 1;
+                    settled.push((order.client_order_id.as_str(), Some(order.order_id)));
+                }
+                // Gone already, e.g. the other leg of an OCO cancelled just before
+                Err(e) if e.code == Some(UNKNOWN_ORDER) => {
+                    settled.push((order.client_order_id.as_str(), None))
+                }
+                Err(e) => failures.push(format!(
+                    "failed to cancel {} order {}: {}",
+                    order.symbol, order.order_id, e.message
+                )),
+            }
+        }
+        let mut store = self.store.lock().await;
+        for (client_order_id, order_id) in settled {
+            store.update(client_order_id, IntentStatus::Canceled, order_id)?;
+        }
+        drop(store);
+        audit::record(
+            AuditCategory::Order,
+            "cancel_all",
+            serde_json::json!({
+                "open": open.len(),
+                "foreign": open.len() - own.len(),
+                "cancelled": cancelled,
+                "errors": failures,
+            }),
+        );
+        Ok((cancelled, failures))
+    }
+
+    /// Sell or buy `quantity` of `symbol` at market, as when flattening positions.
+    pub async fn close_at_market(
+        &self,
+        symbol: &str,
+        side: &str,
+        quantity: f64,
+    ) -> AureliaResult<()> {
+        self.market_order(FLATTEN_PREFIX, symbol, side, quantity)
+            .await
+            .map(|_| ())
+    }
+
+    /// Sell `quantity` of `symbol` at market because a protective level was
+    /// reached. Returns the order's client order ID, which its fills carry.
+    pub async fn close_protected(&self, symbol: &str, quantity: f64) -> AureliaResult<String> {
+        self.market_order(PROTECTIVE_CLOSE_PREFIX, symbol, "SELL", quantity)
+            .await
+    }
+
+    async fn market_order(
+        &self,
+        prefix: &str,
+        symbol: &str,
+        side: &str,
+        quantity: f64,
+    ) -> AureliaResult<String> {
+        let filters = self.filters(symbol).await?;
+        let quantity = filters.lot_size.round_down(quantity);
+        if quantity <= 0.0 {
+            return Err(AureliaError::Exchange(format!(
+                "{} quantity is below the lot size of {}",
+                symbol, filters.lot_size.min_qty
+            )));
+        }
+        let now = now_millis();
+        let intent = OrderIntent {
+            client_order_id: format!("{}{}", prefix, CorrelationId::new()),
+            symbol: symbol.to_string(),
+            side: side.to_string(),
+            quantity,
+            price: 0.0,
+            status: IntentStatus::Pending,
+            exchange_order_id: None,
+            created_at: now,
+            updated_at: now,
+            strategy_id: None,
+            rationale: None,
+        };
+        let mut store = self.store.lock().await;
+        store.insert(intent.clone())?;
+        let in_flight = InFlight::new(&self.in_flight, vec![intent.client_order_id.clone()]);
+        drop(store);
+
+        let result = self.exchange.submit_market(&intent, &filters).await;
+        let mut store = self.store.lock().await;
+        drop(in_flight);
+        audit::record(
+            AuditCategory::Order,
+            "close_at_market",
+            serde_json::json!({
+                "intent": intent,
+                "exchange_order_id": result.as_ref().ok().map(|order| order.order_id),
+                "error": result.as_ref().err().map(|e| e.message.clone()),
+            }),
+        );
+        match result {
+            Ok(order) => {
+                store.update(
+                    &intent.client_order_id,
+                    IntentStatus::from_exchange(&order.status),
+                    Some(order.order_id),
+                )?;
+                Ok(intent.client_order_id)
+            }
+            Err(e) if e.is_not_placed() => {
+                store.update(&intent.client_order_id, IntentStatus::Rejected, None)?;
+                Err(e.into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The `LOT_SIZE` and `PRICE_FILTER` filters of `symbol`, looked up once.
+    pub async fn filters(&self, symbol: &str) -> AureliaResult<SymbolFilters> {
+        if let Some(filters) = self
+            .filters
+            .lock()
+            .expect("filter lock poisoned")
+            .get(symbol)
+        {
+            return Ok(*filters);
+        }
+        let filters =
+            self.exchange.filters(symbol).await?.ok_or_else(|| {
+                AureliaError::Exchange(format!("{} has no LOT_SIZE filter", symbol))
+            })?;
+        self.filters
+            .lock()
+            .expect("filter lock poisoned")
+            .insert(symbol.to_string(), filters);
+        Ok(filters)
+    }
+
+    /// The strategy an order was placed for and why, if this agent placed it for one.
+    pub async fn origin_of(
+        &self,
+        client_order_id: &str,
+    ) -> (Option<String>, Option<DecisionRationale>) {
+        let store = self.store.lock().await;
+        store.get(client_order_id).map_or((None, None), |intent| {
+            (intent.strategy_id.clone(), intent.rationale.clone())
+        })
+    }
+
+    /// Apply an order update from the user-data stream.
+    pub async fn track(&self, update: &OrderUpdate) {
+        let mut store = self.store.lock().await;
+        if store.get(&update.client_order_id).is_none() {
+            return;
+        }
+        let status = IntentStatus::from_exchange(&update.status);
+        if let Err(e) = store.update(&update.client_order_id, status, Some(update.order_id)) {
+            error!("[Execution Engine] Failed to save order intent: {}", e);
+        }
+    }
+
+    /// Balances of every asset the account holds, as the exchange reports them now.
+    /// An amount that does not parse fails the whole lookup rather than being read
+    /// as an empty balance.
+    pub async fn balances(&self) -> AureliaResult<HashMap<String, Balance>> {
+        let account = self.exchange.account().await?;
+        account
+            .balances
+            .into_iter()
+            .map(|balance| {
+                let amount = |value: &str| {
+                    value.parse().map_err(|_| {
+                        AureliaError::Exchange(format!(
+                            "Unreadable {} balance: {:?}",
+                            balance.asset, value
+                        ))
+                    })
+                };
+                let free = amount(&balance.free)?;
+                let locked = amount(&balance.locked)?;
+                Ok((balance.asset.clone(), Balance { free, locked }))
+            })
+            .collect()
+    }
+
+    /// Bring intents in line with the exchange: re-track open orders we know, cancel
+    /// open orders of ours we don't, and look up the outcome of anything else left
+    /// unsettled. Orders the agent did not place are left open.
+    pub async fn reconcile(&self) -> AureliaResult<()> {
+        let open = self.exchange.open_orders().await?;
+        let reconciliation = {
+            let mut store = self.store.lock().await;
+            let mut reconciliation = plan(&store, &open);
+            // Still on their way to the exchange, so not open there yet
+            let in_flight = self.in_flight.lock().expect("in-flight lock poisoned");
+            reconciliation
+                .resolve
+                .retain(|(_, client_order_id)| !in_flight.contains(client_order_id));
+            drop(in_flight);
+            for (client_order_id, order_id) in &reconciliation.retrack {
+                store.update(client_order_id, IntentStatus::Open, Some(*order_id))?;
+            }
+            reconciliation
+        };
+
+        for (symbol, order_id) in &reconciliation.foreign {
+            debug!(
+                symbol = %symbol,
+                order_id = order_id,
+                "[Execution Engine] Leaving open order placed outside the agent alone"
+            );
+        }
+        for (symbol, order_id) in &reconciliation.cancel {
+            warn!(
+                symbol = %symbol,
+                order_id = order_id,
+                "[Execution Engine] Cancelling unknown open order"
+            );
+            if let Err(e) = self.exchange.cancel(symbol, *order_id).await {
+                error!(
+                    "[Execution Engine] Failed to cancel order {}: {}",
+                    order_id, e.message
+                );
+            }
+        }
+        let mut resolved = Vec::new();
+        for (symbol, client_order_id) in &reconciliation.resolve {
+            match self.exchange.query(symbol, client_order_id).await {
+                Ok(order) => resolved.push((
+                    client_order_id,
+                    IntentStatus::from_exchange(&order.status),
+                    Some(order.order_id),
+                )),
+                // The request never reached the exchange
+                Err(e) if e.code == Some(UNKNOWN_ORDER) => {
+                    resolved.push((client_order_id, IntentStatus::Rejected, None))
+                }
+                Err(e) => warn!(
+                    client_order_id = %client_order_id,
+                    "[Execution Engine] Could not resolve order: {}", e.message
+                ),
+            }
+        }
+        let mut store = self.store.lock().await;
+        for (client_order_id, status, order_id) in resolved {
+            store.update(client_order_id, status, order_id)?;
+        }
+        drop(store);
+        info!(
+            retracked = reconciliation.retrack.len(),
+            cancelled = reconciliation.cancel.len(),
+            foreign = reconciliation.foreign.len(),
+            resolved = reconciliation.resolve.len(),
+            "[Execution Engine] Orders reconciled"
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange_order(symbol: &str, order_id: u64, client_order_id: &str) -> ExchangeOrder {
+        ExchangeOrder {
+            symbol: symbol.to_string(),
+            order_id,
+            client_order_id: client_order_id.to_string(),
+            status: "NEW".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_intents_are_idempotent_and_reconciled() {
+        let dir = std::env::temp_dir().join(format!("aurelia-intents-{}", now_millis()));
+        let path = dir.join("order_intents.json");
+        let meta = EventMeta::default();
+        let buy = StrategyDecision::Buy("BTCUSDT".to_string(), 65000.0);
+        let sell = StrategyDecision::Sell("ETHUSDT".to_string(), 3000.0);
+
+        let id = client_order_id(&buy, &meta).unwrap();
+        assert_eq!(client_order_id(&buy, &meta), Some(id.clone()));
+        assert!(id.len() <= 36);
+        assert_ne!(client_order_id(&sell, &meta), Some(id.clone()));
+        assert!(client_order_id(&StrategyDecision::Hold("BTCUSDT".to_string()), &meta).is_none());
+
+        // Two strategies buying on the same tick place two orders
+        let tagged = |strategy: &str| EventMeta {
+            strategy_id: Some(strategy.to_string()),
+            ..meta.clone()
+        };
+        let momentum = client_order_id(&buy, &tagged("momentum")).unwrap();
+        let reversion = client_order_id(&buy, &tagged("mean_reversion")).unwrap();
+        assert!(momentum.len() <= 36 && reversion.len() <= 36);
+        assert_ne!(momentum, reversion);
+        assert_ne!(momentum, id);
+
+        let mut store = IntentStore::load(&path).unwrap();
+        assert!(store
+            .insert(OrderIntent::new(&buy, &meta, ORDER_QUANTITY).unwrap())
+            .unwrap());
+        assert!(!store
+            .insert(OrderIntent::new(&buy, &meta, ORDER_QUANTITY).unwrap())
+            .unwrap());
+        let sell_intent = OrderIntent::new(&sell, &EventMeta::default(), ORDER_QUANTITY).unwrap();
+        let sell_id = sell_intent.client_order_id.clone();
+        store.insert(sell_intent).unwrap();
+
+        // Intents survive a restart
+        let store = IntentStore::load(&path).unwrap();
+        assert_eq!(store.unsettled().count(), 2);
+
+        // An order of ours whose intent was lost is cancelled
+        let stale = client_order_id(&buy, &tagged("momentum")).unwrap();
+        let open = [
+            exchange_order("BTCUSDT", 7, &id),
+            exchange_order("BTCUSDT", 8, &stale),
+        ];
+        assert_eq!(
+            plan(&store, &open),
+            Reconciliation {
+                cancel: vec![("BTCUSDT".to_string(), 8)],
+                retrack: vec![(id, 7)],
+                resolve: vec![("ETHUSDT".to_string(), sell_id)],
+                ..Default::default()
+            }
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_foreign_orders_survive_reconciliation() {
+        let dir = std::env::temp_dir().join(format!("aurelia-foreign-{}", now_millis()));
+        let store = IntentStore::load(dir.join("order_intents.json")).unwrap();
+        let ours = client_order_id(
+            &StrategyDecision::Buy("BTCUSDT".to_string(), 65000.0),
+            &EventMeta::default(),
+        )
+        .unwrap();
+        // Placed in the web interface, by another bot and through the API by hand
+        let open = [
+            exchange_order("BTCUSDT", 1, "web_4f2a9c"),
+            exchange_order("ETHUSDT", 2, "grid-bot-17"),
+            exchange_order("BTCUSDT", 3, "x-AUTO"),
+            exchange_order("BTCUSDT", 4, &ours),
+        ];
+        assert_eq!(
+            plan(&store, &open),
+            Reconciliation {
+                cancel: vec![("BTCUSDT".to_string(), 4)],
+                foreign: vec![
+                    ("BTCUSDT".to_string(), 1),
+                    ("ETHUSDT".to_string(), 2),
+                    ("BTCUSDT".to_string(), 3),
+                ],
+                ..Default::default()
+            }
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_settled_intents_are_compacted_after_retention() {
+        let dir = std::env::temp_dir().join(format!("aurelia-compact-{}", now_millis()));
+        let path = dir.join("order_intents.json");
+        let mut store = IntentStore::load(&path)
+            .unwrap()
+            .with_retention(Duration::from_secs(60));
+        let oco = OcoOrder::new("BTCUSDT", 0.01, 70000.0, 60000.0, 59900.0);
+        assert!(oco.stop_client_order_id.starts_with("aup"));
+        assert!(is_own_order(&oco.stop_client_order_id));
+
+        let decision = |symbol: &str| StrategyDecision::Buy(symbol.to_string(), 100.0);
+        for symbol in ["BTCUSDT", "ETHUSDT", "SOLUSDT"] {
+            let intent = OrderIntent::new(&decision(symbol), &EventMeta::default(), 1.0).unwrap();
+            store.insert(intent).unwrap();
+        }
+        let ids: Vec<String> = store.intents.keys().cloned().collect();
+        store
+            .update(&ids[0], IntentStatus::Filled, Some(1))
+            .unwrap();
+        store
+            .update(&ids[1], IntentStatus::Canceled, Some(2))
+            .unwrap();
+
+        // Settled within the window: kept
+        assert_eq!(store.compact(now_millis()), 0);
+        // Past it only the unsettled intent remains
+        assert_eq!(store.compact(now_millis() + 120_000), 2);
+        assert_eq!(store.intents.len(), 1);
+        assert!(store.get(&ids[2]).is_some());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_in_flight_orders_are_released_on_drop() {
+        let ids = std::sync::Mutex::new(HashSet::new());
+        let in_flight = InFlight::new(&ids, vec!["aubx".to_string(), "aufy".to_string()]);
+        assert!(ids.lock().unwrap().contains("aufy"));
+        drop(in_flight);
+        assert!(ids.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_only_outages_count_towards_the_circuit() {
+        let error = |status: Option<u16>, not_sent: bool| RequestError {
+            status,
+            code: None,
+            message: String::new(),
+            not_sent,
+        };
+        assert!(error(None, false).is_outage());
+        assert!(error(Some(503), false).is_outage());
+        assert!(error(Some(429), false).is_outage());
+        assert!(!error(Some(400), false).is_outage());
+
+        // Held back by an open circuit: no order, and not another failure
+        let held_back = error(None, true);
+        assert!(!held_back.is_outage());
+        assert!(held_back.is_not_placed());
+        assert!(!held_back.is_rejection());
+        assert!(!error(Some(502), false).is_not_placed());
+    }
+
+    #[test]
+    fn test_orders_are_rounded_to_the_symbol_filters() {
+        let info: ExchangeInfo = serde_json::from_str(
+            r#"{"symbols": [{"symbol": "BTCUSDT", "filters": [
+                {"filterType": "PRICE_FILTER", "tickSize": "0.01000000"},
+                {"filterType": "LOT_SIZE", "minQty": "0.00001000",
+                 "maxQty": "9000.00000000", "stepSize": "0.00001000"},
+                {"filterType": "NOTIONAL", "minNotional": "5.00000000",
+                 "applyMinToMarket": true}
+            ]}]}"#,
+        )
+        .unwrap();
+        let filters = SymbolFilters::from_info(info).unwrap();
+        let lot_size = filters.lot_size;
+        assert_eq!(lot_size.round_down(0.0300049), 0.03);
+        assert_eq!(lot_size.round_down(0.1 + 0.2).to_string(), "0.3");
+        // Dust below the minimum cannot be sold at all
+        assert_eq!(lot_size.round_down(0.000009), 0.0);
+
+        assert_eq!(filters.round_price(102.899999), 102.9);
+        assert_eq!(filters.round_price(109.2049), 109.2);
+        assert_eq!(filters.round_price(0.1 + 0.2).to_string(), "0.3");
+        assert_eq!(filters.format_quantity(0.03), "0.03000");
+        assert_eq!(filters.format_price(65000.0), "65000.00");
+
+        let buy = StrategyDecision::Buy("BTCUSDT".to_string(), 65000.004);
+        let mut intent = OrderIntent::new(&buy, &EventMeta::default(), 0.0300049).unwrap();
+        filters.fit(&mut intent).unwrap();
+        assert_eq!((intent.quantity, intent.price), (0.03, 65000.0));
+        // 0.00007 BTC is worth less than the minimum notional
+        let mut intent = OrderIntent::new(&buy, &EventMeta::default(), 0.00007).unwrap();
+        assert!(filters.fit(&mut intent).is_err());
+        let mut intent = OrderIntent::new(&buy, &EventMeta::default(), 0.000009).unwrap();
+        assert!(filters.fit(&mut intent).is_err());
+
+        // Protective closes are ours, and not mistaken for strategy sells
+        assert!(is_own_order("auc0123"));
+        assert_eq!(
+            OWN_ORDER_PREFIXES
+                .iter()
+                .collect::<std::collections::HashSet<_>>()
+                .len(),
+            OWN_ORDER_PREFIXES.len()
+        );
+    }
+}
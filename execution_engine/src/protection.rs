@@ -0,0 +1,633 @@
+//! Stop-loss and take-profit protection of open positions.
+//!
+//! After every buy fill the position in its symbol is protected by a stop-loss
+//! `stop_loss_pct` below and a take-profit `take_profit_pct` above the average
+//! entry price. In `oco` mode with live trading the protection rests on the
+//! exchange as an OCO sell, replaced whenever the position changes; its legs are
+//! order intents, so reconciliation after a reconnect keeps them. In `synthetic`
+//! mode, when orders are only simulated, or when the OCO could not be placed, the
+//! manager watches trades itself and closes the position once a level is
+//! reached. OCO prices and quantities are rounded to the symbol's `PRICE_FILTER`
+//! and `LOT_SIZE`; a resting OCO is cancelled before the manager closes the
+//! position itself. Positions are saved after every change and survive
+//! restarts. Every close is reported as `AppEvent::ProtectionTriggered`. Only
+//! long positions are protected, as on a spot account.
+
+use crate::orders::{OcoOrder, OrderManager};
+use crate::user_data::SharedPortfolio;
+use crate::OrderExecutor;
+use common::{
+    AppEvent, AureliaResult, EventMeta, EventSender, Fill, LagHandler, OrderUpdate, ProtectionKind,
+    ProtectionTriggered, StrategyDecision, Topic,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+pub const PROTECTION_CONFIG_PATH: &str = "config/protection.json";
+pub const PROTECTION_STATE_PATH: &str = "data/protection.json";
+
+/// Quantities below this count as a closed position.
+const DUST: f64 = 1e-9;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProtectionMode {
+    /// Resting OCO sells on the exchange
+    #[default]
+    Oco,
+    /// Watched by the agent, closed with an ordinary sell
+    Synthetic,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProtectionConfig {
+    pub enabled: bool,
+    pub mode: ProtectionMode,
+    /// Distance of the stop-loss below the entry price, e.g. 0.02 for 2%
+    pub stop_loss_pct: f64,
+    /// Distance of the take-profit above the entry price
+    pub take_profit_pct: f64,
+    /// How far below the stop price the stop leg of an OCO may fill
+    pub stop_limit_offset_pct: f64,
+    /// Symbols are `<base><quote_asset>`; the base balance caps what is protected
+    pub quote_asset: String,
+}
+
+impl Default for ProtectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: ProtectionMode::Oco,
+            stop_loss_pct: 0.02,
+            take_profit_pct: 0.04,
+            stop_limit_offset_pct: 0.002,
+            quote_asset: "USDT".to_string(),
+        }
+    }
+}
+
+impl ProtectionConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProtectedPosition {
+    pub symbol: String,
+    pub quantity: f64,
+    /// Average price of the buys making up the position
+    pub entry_price: f64,
+    pub stop_loss: f64,
+    pub take_profit: f64,
+    /// The resting OCO sell, when protected on the exchange
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oco: Option<OcoOrder>,
+    /// A synthetic close in flight: what triggered it and its client order ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub closing: Option<(ProtectionKind, String)>,
+}
+
+impl ProtectedPosition {
+    fn new(symbol: &str) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            quantity: 0.0,
+            entry_price: 0.0,
+            stop_loss: 0.0,
+            take_profit: 0.0,
+            oco: None,
+            closing: None,
+        }
+    }
+
+    /// Add a buy of `quantity` at `price`, moving the levels with the entry price.
+    fn add(&mut self, quantity: f64, price: f64, config: &ProtectionConfig) {
+        let total = self.quantity + quantity;
+        self.entry_price = (self.entry_price * self.quantity + price * quantity) / total;
+        self.quantity = total;
+        self.stop_loss = self.entry_price * (1.0 - config.stop_loss_pct);
+        self.take_profit = self.entry_price * (1.0 + config.take_profit_pct);
+    }
+
+    /// The level reached at `price`, if any.
+    pub fn triggered(&self, price: f64) -> Option<ProtectionKind> {
+        if price <= self.stop_loss {
+            Some(ProtectionKind::StopLoss)
+        } else if price >= self.take_profit {
+            Some(ProtectionKind::TakeProfit)
+        } else {
+            None
+        }
+    }
+
+    fn level(&self, kind: ProtectionKind) -> f64 {
+        match kind {
+            ProtectionKind::StopLoss => self.stop_loss,
+            ProtectionKind::TakeProfit => self.take_profit,
+        }
+    }
+
+    /// Which protective order of this position `client_order_id` belongs to.
+    fn protective_order(&self, client_order_id: &str) -> Option<ProtectionKind> {
+        if let Some(oco) = &self.oco {
+            if client_order_id == oco.stop_client_order_id {
+                return Some(ProtectionKind::StopLoss);
+            }
+            if client_order_id == oco.limit_client_order_id {
+                return Some(ProtectionKind::TakeProfit);
+            }
+        }
+        match &self.closing {
+            Some((kind, id)) if id == client_order_id => Some(*kind),
+            _ => None,
+        }
+    }
+}
+
+/// Protected positions by symbol, saved after every change.
+#[derive(Debug)]
+pub struct ProtectionStore {
+    path: PathBuf,
+    positions: BTreeMap<String, ProtectedPosition>,
+}
+
+impl ProtectionStore {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let positions = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self { path, positions })
+    }
+
+    fn save(&self) -> AureliaResult<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&self.positions)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// Store `position`, or drop it once closed.
+    fn put(&mut self, position: ProtectedPosition) {
+        if position.quantity <= DUST {
+            self.positions.remove(&position.symbol);
+        } else {
+            self.positions.insert(position.symbol.clone(), position);
+        }
+        if let Err(e) = self.save() {
+            error!(
+                "[Execution Engine] Failed to save protected positions: {}",
+                e
+            );
+        }
+    }
+}
+
+/// Places and updates the protection of each position as fills come in, and
+/// closes positions whose protection is watched by the agent.
+pub struct ProtectionManager {
+    config: ProtectionConfig,
+    tx: EventSender,
+    executor: OrderExecutor,
+    portfolio: SharedPortfolio,
+    store: Mutex<ProtectionStore>,
+}
+
+impl ProtectionManager {
+    pub(crate) fn new(
+        config: ProtectionConfig,
+        tx: EventSender,
+        executor: OrderExecutor,
+        portfolio: SharedPortfolio,
+        store: ProtectionStore,
+    ) -> Self {
+        Self {
+            config,
+            tx,
+            executor,
+            portfolio,
+            store: Mutex::new(store),
+        }
+    }
+
+    pub async fn positions(&self) -> Vec<ProtectedPosition> {
+        let store = self.store.lock().await;
+        store.positions.values().cloned().collect()
+    }
+
+    /// Forget every position once an emergency flatten closes them all; their
+    /// OCO sells are cancelled with the rest of the open orders.
+    pub async fn clear(&self) {
+        let mut store = self.store.lock().await;
+        store.positions.clear();
+        if let Err(e) = store.save() {
+            error!(
+                "[Execution Engine] Failed to save protected positions: {}",
+                e
+            );
+        }
+    }
+
+    /// The order manager to place OCO sells through, when protection rests on the
+    /// exchange.
+    fn exchange(&self) -> Option<&Arc<OrderManager>> {
+        match self.config.mode {
+            ProtectionMode::Oco => self.executor.orders.as_ref(),
+            ProtectionMode::Synthetic => None,
+        }
+    }
+
+    /// Follow trades to close positions watched by the agent.
+    pub async fn run(self: Arc<Self>) {
+        let mut rx = self.tx.subscribe_as("protection", &[Topic::MarketTicks]);
+        let lag = LagHandler::new("protection").with_event_bus(self.tx.clone());
+        let positions = self.store.lock().await.positions.len();
+        info!(
+            mode = ?self.config.mode,
+            positions = positions,
+            "[Execution Engine] Protecting positions"
+        );
+        loop {
+            match rx.recv().await {
+                Ok(AppEvent::MarketTick(data)) => self.on_price(&data.symbol, data.price).await,
+                Ok(_) => {}
+                Err(RecvError::Lagged(n)) => {
+                    lag.lagged(n);
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// Update the position a fill belongs to and its protection.
+    pub async fn on_fill(&self, fill: &Fill) {
+        let mut store = self.store.lock().await;
+        let mut position = store
+            .positions
+            .get(&fill.symbol)
+            .cloned()
+            .unwrap_or_else(|| ProtectedPosition::new(&fill.symbol));
+
+        let protective = fill
+            .client_order_id
+            .as_deref()
+            .and_then(|id| position.protective_order(id));
+        if let Some(kind) = protective {
+            // The exchange cancels the other leg of an OCO by itself
+            self.publish(&position, kind, fill.price, fill.quantity);
+            position.quantity -= fill.quantity;
+            store.put(position);
+            return;
+        }
+
+        match fill.side.as_str() {
+            "BUY" => position.add(fill.quantity, fill.price, &self.config),
+            "SELL" if position.quantity > DUST => {
+                position.quantity = (position.quantity - fill.quantity).max(0.0)
+            }
+            _ => return,
+        }
+        self.protect(&mut position).await;
+        store.put(position);
+    }
+
+    /// Forget a synthetic close the exchange did not carry out, so the position
+    /// is closed again at the next trade past its level.
+    pub async fn on_order_update(&self, update: &OrderUpdate) {
+        if !matches!(
+            update.status.as_str(),
+            "REJECTED" | "CANCELED" | "EXPIRED" | "EXPIRED_IN_MATCH"
+        ) {
+            return;
+        }
+        let mut store = self.store.lock().await;
+        let Some(position) = store.positions.get(&update.symbol) else {
+            return;
+        };
+        if !matches!(&position.closing, Some((_, id)) if *id == update.client_order_id) {
+            return;
+        }
+        let mut position = position.clone();
+        warn!(
+            symbol = %position.symbol,
+            status = %update.status,
+            "[Execution Engine] Protective close did not go through, watching the position again"
+        );
+        position.closing = None;
+        store.put(position);
+    }
+
+    /// Replace the OCO sell of a position after it changed.
+    async fn protect(&self, position: &mut ProtectedPosition) {
+        let Some(orders) = self.exchange() else {
+            return;
+        };
+        if let Some(oco) = position.oco.take() {
+            if let Err(e) = orders.cancel_oco(&oco).await {
+                // A second OCO would sell the position twice
+                error!(
+                    symbol = %position.symbol,
+                    "[Execution Engine] Failed to cancel OCO sell, keeping it: {}", e
+                );
+                position.oco = Some(oco);
+                return;
+            }
+        }
+        let filters = match orders.filters(&position.symbol).await {
+            Ok(filters) => filters,
+            Err(e) => {
+                error!(
+                    symbol = %position.symbol,
+                    "[Execution Engine] Failed to look up the filters, watching the position instead: {}", e
+                );
+                return;
+            }
+        };
+        let quantity = filters.lot_size.round_down(self.sellable(position).await);
+        if quantity <= DUST {
+            return;
+        }
+        let oco = OcoOrder::new(
+            &position.symbol,
+            quantity,
+            filters.round_price(position.take_profit),
+            filters.round_price(position.stop_loss),
+            filters.round_price(position.stop_loss * (1.0 - self.config.stop_limit_offset_pct)),
+        );
+        match orders.submit_oco(&oco).await {
+            Ok(()) => position.oco = Some(oco),
+            Err(e) => error!(
+                symbol = %position.symbol,
+                "[Execution Engine] Failed to place OCO sell, watching the position instead: {}", e
+            ),
+        }
+    }
+
+    /// The part of a position the account can sell, as far as the portfolio knows.
+    async fn sellable(&self, position: &ProtectedPosition) -> f64 {
+        let base = position
+            .symbol
+            .strip_suffix(&self.config.quote_asset)
+            .unwrap_or(&position.symbol);
+        let portfolio = self.portfolio.read().await;
+        match portfolio.balances.get(base) {
+            Some(balance) => position.quantity.min(balance.total()),
+            None => position.quantity,
+        }
+    }
+
+    /// Close a position watched by the agent if `price` reaches one of its levels.
+    pub async fn on_price(&self, symbol: &str, price: f64) {
+        let mut store = self.store.lock().await;
+        let Some(position) = store.positions.get(symbol).cloned() else {
+            return;
+        };
+        if position.closing.is_some() || (position.oco.is_some() && self.exchange().is_some()) {
+            return;
+        }
+        let Some(kind) = position.triggered(price) else {
+            return;
+        };
+        info!(
+            symbol = symbol,
+            price = price,
+            "[Execution Engine] {:?} reached, closing position",
+            kind
+        );
+        let mut position = position;
+        let Some(orders) = &self.executor.orders else {
+            let decision = StrategyDecision::Sell(symbol.to_string(), price);
+            if let Err(e) = self
+                .executor
+                .execute(&decision, &EventMeta::default(), position.quantity, None)
+                .await
+            {
+                error!(
+                    symbol = symbol,
+                    "[Execution Engine] Failed to close position: {}", e
+                );
+                return;
+            }
+            self.publish(&position, kind, price, position.quantity);
+            position.quantity = 0.0;
+            store.put(position);
+            return;
+        };
+
+        // An OCO left from before switching to synthetic mode would sell the
+        // position a second time
+        if let Some(oco) = position.oco.take() {
+            if let Err(e) = orders.cancel_oco(&oco).await {
+                error!(
+                    symbol = symbol,
+                    "[Execution Engine] Failed to cancel OCO sell, not closing the position: {}", e
+                );
+                return;
+            }
+            store.put(position.clone());
+        }
+        let quantity = match orders.filters(symbol).await {
+            Ok(filters) => filters.lot_size.round_down(position.quantity),
+            Err(e) => {
+                error!(
+                    symbol = symbol,
+                    "[Execution Engine] Failed to look up the lot size, not closing the position: {}", e
+                );
+                return;
+            }
+        };
+        if quantity <= DUST {
+            warn!(
+                symbol = symbol,
+                "[Execution Engine] Position is below the lot size, left open"
+            );
+            return;
+        }
+        match orders.close_protected(symbol, quantity).await {
+            // Reported through the user-data stream once it fills
+            Ok(id) => {
+                position.closing = Some((kind, id));
+                store.put(position);
+            }
+            Err(e) => error!(
+                symbol = symbol,
+                "[Execution Engine] Failed to close position: {}", e
+            ),
+        }
+    }
+
+    fn publish(
+        &self,
+        position: &ProtectedPosition,
+        kind: ProtectionKind,
+        price: f64,
+        quantity: f64,
+    ) {
+        let event = ProtectionTriggered {
+            symbol: position.symbol.clone(),
+            kind,
+            trigger_price: position.level(kind),
+            price,
+            quantity,
+            entry_price: position.entry_price,
+            timestamp: chrono::Utc::now(),
+        };
+        info!(
+            symbol = %event.symbol,
+            price = price,
+            quantity = quantity,
+            "[Execution Engine] {:?} triggered", kind
+        );
+        let _ = self.tx.send(AppEvent::ProtectionTriggered(event));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{CostModel, EventBus, RateLimiter};
+
+    fn fill(side: &str, price: f64, quantity: f64) -> Fill {
+        Fill {
+            timestamp: chrono::Utc::now(),
+            symbol: "BTCUSDT".to_string(),
+            side: side.to_string(),
+            price,
+            quantity,
+            fee: 0.0,
+            fee_asset: None,
+            client_order_id: None,
+            simulated: true,
+            strategy_id: None,
+            rationale: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_simulated_positions_close_at_their_levels() {
+        let dir = std::env::temp_dir().join(format!("aurelia-protection-{}", std::process::id()));
+        let path = dir.join("protection.json");
+        let bus = EventBus::new(16);
+        let mut events = bus.subscribe_as("test", &[Topic::Financial]);
+        let executor = OrderExecutor {
+            orders: None,
+            ledger: None,
+            limiter: RateLimiter::default(),
+            costs: CostModel::default(),
+            protection: None,
+            portfolio: SharedPortfolio::default(),
+        };
+        let manager = ProtectionManager::new(
+            ProtectionConfig::default(),
+            bus.clone(),
+            executor,
+            SharedPortfolio::default(),
+            ProtectionStore::load(&path).unwrap(),
+        );
+
+        manager.on_fill(&fill("BUY", 100.0, 1.0)).await;
+        manager.on_fill(&fill("BUY", 110.0, 1.0)).await;
+        let [position] = manager.positions().await.try_into().unwrap();
+        assert_eq!(position.quantity, 2.0);
+        assert!((position.entry_price - 105.0).abs() < 1e-9);
+        assert!((position.stop_loss - 102.9).abs() < 1e-9);
+        assert!((position.take_profit - 109.2).abs() < 1e-9);
+        assert!(position.oco.is_none());
+
+        // Positions survive a restart
+        // JSON does not round-trip every last bit of a float
+        let store = ProtectionStore::load(&path).unwrap();
+        let restored = &store.positions["BTCUSDT"];
+        assert_eq!(restored.quantity, position.quantity);
+        assert!((restored.stop_loss - position.stop_loss).abs() < 1e-9);
+        assert!((restored.take_profit - position.take_profit).abs() < 1e-9);
+
+        manager.on_price("BTCUSDT", 105.0).await;
+        assert_eq!(manager.positions().await.len(), 1);
+        manager.on_price("BTCUSDT", 102.0).await;
+        assert!(manager.positions().await.is_empty());
+        let Ok(AppEvent::ProtectionTriggered(event)) = events.try_recv() else {
+            panic!("expected a protection event");
+        };
+        assert_eq!(event.kind, ProtectionKind::StopLoss);
+        assert_eq!(event.quantity, 2.0);
+
+        // Selling without a position protects nothing
+        manager.on_fill(&fill("SELL", 100.0, 1.0)).await;
+        assert!(manager.positions().await.is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_closes_are_retried() {
+        let dir = std::env::temp_dir().join(format!(
+            "aurelia-protection-rejected-{}",
+            std::process::id()
+        ));
+        let mut position = ProtectedPosition::new("BTCUSDT");
+        position.add(1.0, 100.0, &ProtectionConfig::default());
+        position.closing = Some((ProtectionKind::StopLoss, "auc0123".to_string()));
+        let mut store = ProtectionStore::load(dir.join("protection.json")).unwrap();
+        store.put(position);
+        let executor = OrderExecutor {
+            orders: None,
+            ledger: None,
+            limiter: RateLimiter::default(),
+            costs: CostModel::default(),
+            protection: None,
+            portfolio: SharedPortfolio::default(),
+        };
+        let manager = ProtectionManager::new(
+            ProtectionConfig::default(),
+            EventBus::new(16),
+            executor,
+            SharedPortfolio::default(),
+            store,
+        );
+        let update = |client_order_id: &str, status: &str| OrderUpdate {
+            symbol: "BTCUSDT".to_string(),
+            order_id: 1,
+            client_order_id: client_order_id.to_string(),
+            side: "SELL".to_string(),
+            status: status.to_string(),
+            price: 0.0,
+            quantity: 1.0,
+            filled_quantity: 0.0,
+            last_fill_price: 0.0,
+            last_fill_quantity: 0.0,
+            commission: 0.0,
+            commission_asset: None,
+            timestamp: 0,
+        };
+
+        // Other orders and accepted closes leave the close in flight
+        manager
+            .on_order_update(&update("aus0456", "REJECTED"))
+            .await;
+        manager.on_order_update(&update("auc0123", "NEW")).await;
+        assert!(manager.positions().await[0].closing.is_some());
+
+        manager
+            .on_order_update(&update("auc0123", "REJECTED"))
+            .await;
+        assert!(manager.positions().await[0].closing.is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
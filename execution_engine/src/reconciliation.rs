@@ -0,0 +1,105 @@
+//! Account reconciliation after downtime.
+//!
+//! Orders may have filled, or been cancelled, while the agent was not
+//! listening. Before decisions are acted on again the order intents are
+//! reconciled, the portfolio's balances are replaced with the exchange's, and
+//! the live positions recorded in the trade ledger are checked against them.
+//! Differences are reported, not corrected: the exchange's balances are what
+//! the engines work from, the ledger stays a record of the agent's own fills.
+
+use crate::user_data::Balance;
+use std::collections::{BTreeMap, HashMap};
+
+/// Differences smaller than this, relative to the larger quantity, are rounding
+const TOLERANCE: f64 = 1e-6;
+
+/// A position of the ledger the exchange's balances disagree with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionDrift {
+    pub asset: String,
+    /// Net quantity the ledger's live fills add up to
+    pub recorded: f64,
+    /// Total balance the exchange reports
+    pub held: f64,
+}
+
+/// The live ledger `positions`, by symbol, that `balances` do not match. A
+/// position's asset is its symbol without the `currency` suffix; symbols
+/// quoted in anything else are skipped. Balances held beyond a position, such
+/// as funds deposited before the ledger was started, only count as drift when
+/// the ledger records a position in that asset.
+pub fn position_drift(
+    positions: &BTreeMap<String, (f64, f64)>,
+    balances: &HashMap<String, Balance>,
+    currency: &str,
+) -> Vec<PositionDrift> {
+    positions
+        .iter()
+        .filter_map(|(symbol, (recorded, _))| {
+            let asset = symbol.strip_suffix(currency).filter(|a| !a.is_empty())?;
+            let held = balances.get(asset).map_or(0.0, Balance::total);
+            let scale = recorded.abs().max(held.abs()).max(1.0);
+            ((recorded - held).abs() > TOLERANCE * scale).then(|| PositionDrift {
+                asset: asset.to_string(),
+                recorded: *recorded,
+                held,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_drift() {
+        let positions = BTreeMap::from([
+            ("BTCUSDT".to_string(), (0.5, 65000.0)),
+            ("ETHUSDT".to_string(), (2.0, 3000.0)),
+            ("SOLUSDT".to_string(), (10.0, 150.0)),
+            ("ETHBTC".to_string(), (1.0, 0.05)),
+        ]);
+        let balances = HashMap::from([
+            (
+                "BTC".to_string(),
+                Balance {
+                    free: 0.3,
+                    locked: 0.2,
+                },
+            ),
+            (
+                "ETH".to_string(),
+                Balance {
+                    free: 1.5,
+                    locked: 0.0,
+                },
+            ),
+            (
+                "BNB".to_string(),
+                Balance {
+                    free: 4.0,
+                    locked: 0.0,
+                },
+            ),
+        ]);
+
+        let drift = position_drift(&positions, &balances, "USDT");
+        assert_eq!(
+            drift,
+            vec![
+                PositionDrift {
+                    asset: "ETH".to_string(),
+                    recorded: 2.0,
+                    held: 1.5,
+                },
+                // Sold while the agent was down
+                PositionDrift {
+                    asset: "SOL".to_string(),
+                    recorded: 10.0,
+                    held: 0.0,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,494 @@
+//! Binance spot user-data stream: order executions and balance changes pushed by
+//! the exchange, so fills are observed without polling.
+
+use crate::accounting::value_events;
+use crate::orders::OrderManager;
+use crate::protection::ProtectionManager;
+use common::{
+    AccountingConfig, AppEvent, AureliaError, AureliaResult, EndpointClass, EventSender, Fill,
+    NetAssetValue, OrderUpdate, PriceBook, RateLimiter, TradeLedger,
+};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+use tracing::{error, info, warn};
+
+const REST_API: &str = "https://api.binance.com";
+const WS_API: &str = "wss://stream.binance.com:9443/ws";
+
+/// Listen keys expire after 60 minutes without a keepalive.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30 * 60);
+/// Longest wait between reconnection attempts.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+/// Request weight of the listen key endpoints.
+const LISTEN_KEY_WEIGHT: f64 = 2.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Balance {
+    pub free: f64,
+    pub locked: f64,
+}
+
+impl Balance {
+    pub fn total(&self) -> f64 {
+        self.free + self.locked
+    }
+}
+
+/// Account balances and open orders as last reported by the exchange.
+#[derive(Debug, Default)]
+pub struct Portfolio {
+    pub balances: HashMap<String, Balance>,
+    /// Open orders by order id; removed once filled, canceled, rejected or expired
+    pub open_orders: HashMap<u64, OrderUpdate>,
+    /// Latest prices to value the balances at
+    pub prices: PriceBook,
+}
+
+impl Portfolio {
+    /// All balances valued in the accounting currency.
+    pub fn net_asset_value(&self, config: &AccountingConfig) -> NetAssetValue {
+        NetAssetValue::compute(
+            self.balances
+                .iter()
+                .map(|(asset, balance)| (asset.as_str(), balance.total())),
+            &self.prices,
+            config,
+        )
+    }
+}
+
+pub type SharedPortfolio = Arc<RwLock<Portfolio>>;
+
+/// Binance sends decimals as strings. One that is not a finite number makes the
+/// whole message unreadable, so it is skipped rather than read as zero.
+fn number<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    match value.parse::<f64>() {
+        Ok(number) if number.is_finite() => Ok(number),
+        _ => Err(serde::de::Error::custom(format!(
+            "invalid number {:?}",
+            value
+        ))),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListenKey {
+    listen_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "e")]
+enum UserDataEvent {
+    #[serde(rename = "executionReport")]
+    ExecutionReport(Box<ExecutionReport>),
+    #[serde(rename = "outboundAccountPosition")]
+    AccountPosition(AccountPosition),
+    #[serde(rename = "listenKeyExpired")]
+    ListenKeyExpired,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExecutionReport {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "i")]
+    order_id: u64,
+    #[serde(rename = "c")]
+    client_order_id: String,
+    #[serde(rename = "S")]
+    side: String,
+    #[serde(rename = "X")]
+    status: String,
+    #[serde(rename = "p", deserialize_with = "number")]
+    price: f64,
+    #[serde(rename = "q", deserialize_with = "number")]
+    quantity: f64,
+    #[serde(rename = "z", deserialize_with = "number")]
+    filled_quantity: f64,
+    #[serde(rename = "L", deserialize_with = "number")]
+    last_fill_price: f64,
+    #[serde(rename = "l", deserialize_with = "number")]
+    last_fill_quantity: f64,
+    #[serde(rename = "n", deserialize_with = "number")]
+    commission: f64,
+    #[serde(rename = "N")]
+    commission_asset: Option<String>,
+    #[serde(rename = "T")]
+    transaction_time: u64,
+}
+
+impl From<ExecutionReport> for OrderUpdate {
+    fn from(report: ExecutionReport) -> Self {
+        Self {
+            symbol: report.symbol,
+            order_id: report.order_id,
+            client_order_id: report.client_order_id,
+            side: report.side,
+            status: report.status,
+            price: report.price,
+            quantity: report.quantity,
+            filled_quantity: report.filled_quantity,
+            last_fill_price: report.last_fill_price,
+            last_fill_quantity: report.last_fill_quantity,
+            commission: report.commission,
+            commission_asset: report.commission_asset,
+            timestamp: report.transaction_time,
+        }
+    }
+}
+
+/// Balances of the assets that changed in an account update.
+#[derive(Debug, Deserialize)]
+struct AccountPosition {
+    #[serde(rename = "B")]
+    balances: Vec<AssetBalance>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AssetBalance {
+    #[serde(rename = "a")]
+    asset: String,
+    #[serde(rename = "f", deserialize_with = "number")]
+    free: f64,
+    #[serde(rename = "l", deserialize_with = "number")]
+    locked: f64,
+}
+
+fn is_open(status: &str) -> bool {
+    matches!(status, "NEW" | "PARTIALLY_FILLED" | "PENDING_NEW")
+}
+
+/// What to do with the stream after a message.
+#[derive(Debug, PartialEq)]
+enum Next {
+    Continue,
+    Reconnect,
+}
+
+/// Apply one stream message to the portfolio and return the events it produces.
+///
+/// Balance updates are reported as `FinancialUpdate` with the net asset value of
+/// all balances, the same funds figure the survival protocol budgets against, and
+/// as `NetAssetValue` with the value of each asset.
+fn apply(
+    portfolio: &mut Portfolio,
+    accounting: &AccountingConfig,
+    text: &str,
+) -> (Vec<AppEvent>, Next) {
+    let event = match serde_json::from_str::<UserDataEvent>(text) {
+        Ok(event) => event,
+        Err(e) => {
+            warn!("[Execution Engine] Unreadable user-data message: {}", e);
+            return (Vec::new(), Next::Continue);
+        }
+    };
+    match event {
+        UserDataEvent::ExecutionReport(report) => {
+            let update = OrderUpdate::from(*report);
+            if is_open(&update.status) {
+                portfolio
+                    .open_orders
+                    .insert(update.order_id, update.clone());
+            } else {
+                portfolio.open_orders.remove(&update.order_id);
+            }
+            (
+                vec![AppEvent::OrderUpdate(Box::new(update))],
+                Next::Continue,
+            )
+        }
+        UserDataEvent::AccountPosition(position) => {
+            for balance in position.balances {
+                portfolio.balances.insert(
+                    balance.asset,
+                    Balance {
+                        free: balance.free,
+                        locked: balance.locked,
+                    },
+                );
+            }
+            let nav = portfolio.net_asset_value(accounting);
+            (value_events(nav), Next::Continue)
+        }
+        UserDataEvent::ListenKeyExpired => (Vec::new(), Next::Reconnect),
+        UserDataEvent::Other => (Vec::new(), Next::Continue),
+    }
+}
+
+/// Keeps a listen key alive and follows its WebSocket, publishing `OrderUpdate` and
+/// `FinancialUpdate` events and keeping a [`Portfolio`] current.
+pub struct UserDataStream {
+    tx: EventSender,
+    client: reqwest::Client,
+    api_key: String,
+    limiter: RateLimiter,
+    accounting: AccountingConfig,
+    portfolio: SharedPortfolio,
+    orders: Option<Arc<OrderManager>>,
+    ledger: Option<TradeLedger>,
+    protection: Option<Arc<ProtectionManager>>,
+}
+
+impl UserDataStream {
+    pub fn new(tx: EventSender, api_key: String, limiter: RateLimiter) -> Self {
+        Self {
+            tx,
+            client: reqwest::Client::new(),
+            api_key,
+            limiter,
+            accounting: AccountingConfig::default(),
+            portfolio: SharedPortfolio::default(),
+            orders: None,
+            ledger: None,
+            protection: None,
+        }
+    }
+
+    /// Keep order intents up to date, reconciling them whenever the stream reconnects
+    pub fn with_orders(mut self, orders: Arc<OrderManager>) -> Self {
+        self.orders = Some(orders);
+        self
+    }
+
+    /// The currency balances are valued in when reported as funds
+    pub fn with_accounting(mut self, config: AccountingConfig) -> Self {
+        self.accounting = config;
+        self
+    }
+
+    /// Record every fill reported by the exchange
+    pub fn with_ledger(mut self, ledger: TradeLedger) -> Self {
+        self.ledger = Some(ledger);
+        self
+    }
+
+    /// Keep balances and open orders in a portfolio shared with others
+    pub fn with_portfolio(mut self, portfolio: SharedPortfolio) -> Self {
+        self.portfolio = portfolio;
+        self
+    }
+
+    /// Update the protection of positions with every fill and failed close
+    pub fn with_protection(mut self, protection: Arc<ProtectionManager>) -> Self {
+        self.protection = Some(protection);
+        self
+    }
+
+    pub fn portfolio(&self) -> SharedPortfolio {
+        self.portfolio.clone()
+    }
+
+    async fn listen_key_request(
+        &self,
+        method: reqwest::Method,
+        listen_key: Option<&str>,
+    ) -> AureliaResult<reqwest::Response> {
+        self.limiter
+            .acquire(EndpointClass::ExchangeMarketData, LISTEN_KEY_WEIGHT)
+            .await;
+        let mut request = self
+            .client
+            .request(method, format!("{}/api/v3/userDataStream", REST_API))
+            .header("X-MBX-APIKEY", &self.api_key);
+        if let Some(listen_key) = listen_key {
+            request = request.query(&[("listenKey", listen_key)]);
+        }
+        request
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AureliaError::Exchange(format!("user data stream: {}", e)))
+    }
+
+    async fn create_listen_key(&self) -> AureliaResult<String> {
+        let response = self.listen_key_request(reqwest::Method::POST, None).await?;
+        let key: ListenKey = response
+            .json()
+            .await
+            .map_err(|e| AureliaError::Exchange(format!("user data stream: {}", e)))?;
+        Ok(key.listen_key)
+    }
+
+    async fn keepalive(&self, listen_key: &str) -> AureliaResult<()> {
+        self.listen_key_request(reqwest::Method::PUT, Some(listen_key))
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn run(self) {
+        info!("[Execution Engine] Starting user data stream...");
+        let mut delay = Duration::from_secs(1);
+        loop {
+            match self.follow().await {
+                Ok(()) => delay = Duration::from_secs(1),
+                Err(e) => error!("[Execution Engine] User data stream failed: {}", e),
+            }
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+        }
+    }
+
+    async fn record(&self, update: &OrderUpdate) {
+        let (mut strategy_id, mut rationale) = (None, None);
+        if let Some(orders) = &self.orders {
+            orders.track(update).await;
+            (strategy_id, rationale) = orders.origin_of(&update.client_order_id).await;
+        }
+        if let Some(protection) = &self.protection {
+            protection.on_order_update(update).await;
+        }
+        let Some(fill) = Fill::from_update(update) else {
+            return;
+        };
+        let fill = Fill {
+            strategy_id,
+            rationale,
+            ..fill
+        };
+        if let Some(protection) = &self.protection {
+            protection.on_fill(&fill).await;
+        }
+        if let Some(ledger) = &self.ledger {
+            if let Err(e) = ledger.record(fill) {
+                error!("[Execution Engine] Failed to record fill: {}", e);
+            }
+        }
+    }
+
+    /// Follow one listen key until its connection ends or the key expires.
+    async fn follow(&self) -> AureliaResult<()> {
+        let listen_key = self.create_listen_key().await?;
+        let (ws, _) = connect_async(format!("{}/{}", WS_API, listen_key))
+            .await
+            .map_err(|e| AureliaError::Exchange(format!("user data stream: {}", e)))?;
+        info!("[Execution Engine] User data stream connected");
+        // Updates missed while disconnected are only visible by asking the exchange
+        if let Some(orders) = &self.orders {
+            if let Err(e) = orders.reconcile().await {
+                error!("[Execution Engine] Order reconciliation failed: {}", e);
+            }
+        }
+        let (_write, mut read) = ws.split();
+
+        let mut keepalive = tokio::time::interval(KEEPALIVE_INTERVAL);
+        keepalive.tick().await;
+        loop {
+            tokio::select! {
+                _ = keepalive.tick() => {
+                    if let Err(e) = self.keepalive(&listen_key).await {
+                        warn!("[Execution Engine] Listen key keepalive failed: {}", e);
+                    }
+                }
+                message = read.next() => {
+                    let text = match message {
+                        Some(Ok(Message::Text(text))) => text,
+                        Some(Ok(_)) => continue,
+                        Some(Err(e)) => {
+                            return Err(AureliaError::Exchange(format!("user data stream: {}", e)))
+                        }
+                        None => {
+                            warn!("[Execution Engine] User data stream closed, reconnecting");
+                            return Ok(());
+                        }
+                    };
+                    let (events, next) = {
+                        let mut portfolio = self.portfolio.write().await;
+                        apply(&mut portfolio, &self.accounting, &text)
+                    };
+                    for event in events {
+                        if let AppEvent::OrderUpdate(update) = &event {
+                            self.record(update).await;
+                        }
+                        if let Err(e) = self.tx.send(event) {
+                            error!("[Execution Engine] Failed to publish account update: {}", e);
+                        }
+                    }
+                    if next == Next::Reconnect {
+                        info!("[Execution Engine] Listen key expired, renewing");
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_execution_reports_and_balances() {
+        let mut portfolio = Portfolio::default();
+        let accounting = AccountingConfig::default();
+        let report = |status: &str, filled: &str| {
+            format!(
+                r#"{{"e":"executionReport","E":1700000000100,"s":"BTCUSDT","c":"aurelia-1",
+                "S":"BUY","o":"LIMIT","f":"GTC","q":"0.01000000","p":"65000.00","P":"0.00",
+                "F":"0.00","g":-1,"C":"","x":"TRADE","X":"{}","r":"NONE","i":42,
+                "l":"0.00500000","z":"{}","L":"64990.00","n":"0.00000500","N":"BNB",
+                "T":1700000000000,"t":7,"w":false,"m":false,"O":1699999999000}}"#,
+                status, filled
+            )
+        };
+
+        let (events, next) = apply(
+            &mut portfolio,
+            &accounting,
+            &report("PARTIALLY_FILLED", "0.005"),
+        );
+        assert_eq!(next, Next::Continue);
+        let [AppEvent::OrderUpdate(update)] = events.as_slice() else {
+            panic!("expected one order update, got {:?}", events);
+        };
+        assert_eq!(update.order_id, 42);
+        assert_eq!(update.last_fill_price, 64990.0);
+        assert_eq!(update.commission_asset.as_deref(), Some("BNB"));
+        assert!(portfolio.open_orders.contains_key(&42));
+
+        apply(&mut portfolio, &accounting, &report("FILLED", "0.01"));
+        assert!(portfolio.open_orders.is_empty());
+
+        let position = r#"{"e":"outboundAccountPosition","E":1700000000200,"u":1700000000000,
+            "B":[{"a":"USDT","f":"350.50","l":"100.00"},{"a":"BTC","f":"0.01","l":"0.00"}]}"#;
+        let (events, _) = apply(&mut portfolio, &accounting, position);
+        // BTC has no price yet, so funds are not reported
+        assert!(matches!(
+            events.as_slice(),
+            [AppEvent::NetAssetValue(nav)] if nav.total == 450.5 && nav.unpriced == ["BTC"]
+        ));
+        portfolio.prices.update("BTCUSDT", 65000.0);
+        let (events, _) = apply(&mut portfolio, &accounting, position);
+        assert!(matches!(
+            events.as_slice(),
+            [AppEvent::FinancialUpdate(f), AppEvent::NetAssetValue(_)] if *f == 1100.5
+        ));
+        assert_eq!(portfolio.balances["BTC"].free, 0.01);
+
+        // A malformed balance is skipped, not taken for an empty one
+        let (events, _) = apply(
+            &mut portfolio,
+            &accounting,
+            &position.replace(r#""f":"0.01""#, r#""f":"""#),
+        );
+        assert!(events.is_empty());
+        assert_eq!(portfolio.balances["BTC"].free, 0.01);
+
+        let (events, next) = apply(
+            &mut portfolio,
+            &accounting,
+            r#"{"e":"listenKeyExpired","E":1700000000300,"listenKey":"abc"}"#,
+        );
+        assert!(events.is_empty());
+        assert_eq!(next, Next::Reconnect);
+    }
+}
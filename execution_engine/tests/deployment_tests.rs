@@ -1,15 +1,14 @@
-use common::{AppEvent, DeploymentInfo};
+use common::{AppEvent, AureliaResult, DeploymentInfo, EventBus};
 use execution_engine::{Deployer, ExecutionEngine};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use tokio::sync::broadcast;
 
 struct MockDeployer {
     was_called: Arc<Mutex<bool>>,
 }
 
 impl Deployer for MockDeployer {
-    fn deploy(&self, _info: DeploymentInfo) -> Result<(), Box<dyn std::error::Error>> {
+    fn deploy(&self, _info: DeploymentInfo) -> AureliaResult<()> {
         let mut was_called = self.was_called.lock().unwrap();
         *was_called = true;
         Ok(())
@@ -18,7 +17,8 @@ impl Deployer for MockDeployer {
 
 #[test]
 fn test_deployment_event_is_handled() {
-    let (tx, rx) = broadcast::channel(16);
+    let tx = EventBus::new(16);
+    let rx = tx.subscribe();
     let was_called = Arc::new(Mutex::new(false));
     let mock_deployer = MockDeployer {
         was_called: was_called.clone(),
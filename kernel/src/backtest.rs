@@ -0,0 +1,370 @@
+//! Replay of recorded trades through the strategies.
+//!
+//! Ticks are fed to a [`StrategyBook`] as market data and as the candles the
+//! perception engine would have built from them, and the book decides once per
+//! analysis interval on a [`MockClock`] that follows the tick timestamps. Like
+//! the paper broker, every decision is an order of [`ORDER_QUANTITY`] priced by
+//! the [`CostModel`]; it fills at the first trade of its symbol after the model's
+//! latency. The fills are scored like live performance and compared with buying
+//! the same quantity at the start and holding it to the end.
+
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use common::performance;
+use common::{
+    AppEvent, Clock, CostModel, EventMeta, Fill, Liquidity, MarketData, MockClock,
+    StrategyDecision, StrategyPerformance, StrategySet, TradeLedger,
+};
+use execution_engine::orders::ORDER_QUANTITY;
+use execution_engine::FEE_TIER_WINDOW;
+use perception_core::candles::{CandleBuilder, CANDLE_INTERVAL_SECONDS};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use strategy_engine::indicators::IndicatorParams;
+use strategy_engine::strategies::StrategyBook;
+
+pub struct Backtest {
+    strategies: StrategySet,
+    costs: CostModel,
+    /// Time between two decision cycles, like the engine's `interval_seconds`
+    interval: Duration,
+    params: fn(&str) -> IndicatorParams,
+}
+
+/// Outcome of a [`Backtest`], in quote asset.
+#[derive(Debug, Clone)]
+pub struct BacktestReport {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Longest stretch of the replay without a trade
+    pub longest_gap: TimeDelta,
+    /// Decisions with the simulated time of the analysis cycle that made them
+    pub decisions: Vec<(DateTime<Utc>, StrategyDecision)>,
+    pub fills: Vec<Fill>,
+    /// Positions still open at the end count as closed at the last price
+    pub strategies: Vec<StrategyPerformance>,
+    /// Net PnL of buying [`ORDER_QUANTITY`] of the first symbol and holding it
+    pub buy_and_hold: f64,
+    /// What the buy and hold position cost, fees included
+    pub cost_basis: f64,
+}
+
+impl BacktestReport {
+    pub fn net_pnl(&self) -> f64 {
+        self.strategies.iter().map(|s| s.net_pnl).sum()
+    }
+}
+
+fn tick_time(tick: &MarketData) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(tick.timestamp as i64).unwrap_or_default()
+}
+
+fn order_of(decision: &StrategyDecision) -> Option<(&'static str, &str)> {
+    match decision {
+        StrategyDecision::Buy(symbol, _) => Some(("BUY", symbol)),
+        StrategyDecision::Sell(symbol, _) => Some(("SELL", symbol)),
+        StrategyDecision::Hold(_) => None,
+    }
+}
+
+impl Backtest {
+    /// Run `strategies` with the engine's current parameters.
+    pub fn new(strategies: StrategySet, costs: CostModel) -> Self {
+        let interval = strategy_engine::params::current(strategy_engine::params::INTERVAL_SECONDS);
+        Self {
+            strategies,
+            costs,
+            interval: Duration::from_secs_f64(interval),
+            params: strategy_engine::indicator_params,
+        }
+    }
+
+    /// Replay `ticks`, which must be in time order.
+    pub fn run(&self, ticks: &[MarketData]) -> Result<BacktestReport> {
+        let Some(first) = ticks.first() else {
+            anyhow::bail!("No market data to replay");
+        };
+        let interval = TimeDelta::from_std(self.interval)
+            .unwrap_or(TimeDelta::MAX)
+            .max(TimeDelta::milliseconds(1));
+        let latency = TimeDelta::milliseconds(self.costs.latency_ms as i64);
+
+        // The replay runs on a clock that follows the recorded timestamps, and the
+        // analysis cycles are scheduled on it like the engine's timer
+        let clock = MockClock::new(tick_time(first));
+        let start = clock.now();
+        let mut next_analysis = start + interval;
+        let mut longest_gap = TimeDelta::zero();
+        let mut book = StrategyBook::new(&self.strategies);
+        let mut candles = CandleBuilder::new(CANDLE_INTERVAL_SECONDS);
+        let ledger = TradeLedger::in_memory();
+        let mut decisions = Vec::new();
+        // Orders waiting for their fill, with the earliest time they can fill at
+        let mut pending: Vec<(DateTime<Utc>, StrategyDecision, EventMeta)> = Vec::new();
+        let mut last_prices = BTreeMap::new();
+
+        for tick in ticks {
+            let previous = clock.now();
+            let arrived = tick_time(tick).max(previous);
+            // Cycles due before the tick run on what was seen so far; one at the
+            // tick's own time sees it as well
+            while next_analysis < arrived {
+                clock.set(next_analysis);
+                for (decision, meta) in book.decide(self.params) {
+                    decisions.push((clock.now(), decision.clone()));
+                    pending.push((clock.now() + latency, decision, meta));
+                }
+                next_analysis += interval;
+            }
+            longest_gap = longest_gap.max(arrived - previous);
+            clock.set(arrived);
+
+            let (due, waiting): (Vec<_>, Vec<_>) =
+                pending.into_iter().partition(|(fill_at, decision, _)| {
+                    clock.now() >= *fill_at
+                        && order_of(decision).is_some_and(|(_, symbol)| symbol == tick.symbol)
+                });
+            pending = waiting;
+            for (_, decision, meta) in due {
+                self.fill(
+                    &ledger,
+                    &decision,
+                    &meta,
+                    ORDER_QUANTITY,
+                    tick.price,
+                    clock.now(),
+                )?;
+            }
+
+            last_prices.insert(tick.symbol.clone(), tick.price);
+            if let Some(candle) = candles.update(tick) {
+                book.observe(&AppEvent::Candle(candle), self.params);
+            }
+            book.observe(&AppEvent::MarketData(tick.clone()), self.params);
+        }
+
+        // Close what the strategies still hold so that it shows in their PnL
+        let end = clock.now();
+        let mut open: BTreeMap<(String, String), f64> = BTreeMap::new();
+        for fill in ledger.fills() {
+            let quantity = if fill.side == "SELL" {
+                -fill.quantity
+            } else {
+                fill.quantity
+            };
+            *open
+                .entry((fill.strategy_id.unwrap_or_default(), fill.symbol))
+                .or_default() += quantity;
+        }
+        for ((strategy_id, symbol), quantity) in open {
+            if quantity.abs() < f64::EPSILON {
+                continue;
+            }
+            let side = if quantity > 0.0 { "SELL" } else { "BUY" };
+            let price = last_prices[&symbol];
+            let decision = match side {
+                "SELL" => StrategyDecision::Sell(symbol, price),
+                _ => StrategyDecision::Buy(symbol, price),
+            };
+            let meta = EventMeta {
+                strategy_id: Some(strategy_id),
+                ..EventMeta::default()
+            };
+            self.fill(&ledger, &decision, &meta, quantity.abs(), price, end)?;
+        }
+
+        let fills = ledger.fills();
+        let ids: Vec<_> = book.ids().collect();
+        let now = end + TimeDelta::milliseconds(1);
+        let strategies = performance::score(&fills, &ids, now, now - start);
+
+        // Buy and hold is priced like the strategies' orders
+        let held: Vec<_> = ticks.iter().filter(|t| t.symbol == first.symbol).collect();
+        let filled_at = start + latency;
+        let entry_price = held
+            .iter()
+            .find(|tick| tick_time(tick) >= filled_at)
+            .unwrap_or(&held[held.len() - 1])
+            .price;
+        let exit_price = held[held.len() - 1].price;
+        let entry = self
+            .costs
+            .simulate("BUY", entry_price, ORDER_QUANTITY, 0.0, Liquidity::Taker);
+        let exit = self
+            .costs
+            .simulate("SELL", exit_price, ORDER_QUANTITY, 0.0, Liquidity::Taker);
+        let cost_basis = entry.price * ORDER_QUANTITY + entry.fee;
+        let buy_and_hold = exit.price * ORDER_QUANTITY - exit.fee - cost_basis;
+
+        Ok(BacktestReport {
+            start,
+            end,
+            longest_gap,
+            decisions,
+            fills,
+            strategies,
+            buy_and_hold,
+            cost_basis,
+        })
+    }
+
+    fn fill(
+        &self,
+        ledger: &TradeLedger,
+        decision: &StrategyDecision,
+        meta: &EventMeta,
+        quantity: f64,
+        price: f64,
+        at: DateTime<Utc>,
+    ) -> Result<()> {
+        let Some((side, symbol)) = order_of(decision) else {
+            return Ok(());
+        };
+        let volume_30d = ledger.volume_since(at - FEE_TIER_WINDOW);
+        let simulated = self
+            .costs
+            .simulate(side, price, quantity, volume_30d, Liquidity::Taker);
+        ledger.record(Fill {
+            timestamp: at,
+            symbol: symbol.to_string(),
+            side: side.to_string(),
+            price: simulated.price,
+            quantity,
+            fee: simulated.fee,
+            fee_asset: None,
+            client_order_id: None,
+            simulated: true,
+            strategy_id: meta.strategy_id.clone(),
+            rationale: meta.rationale.as_deref().cloned(),
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::strategies::{StrategyKind, StrategySpec};
+
+    fn params(_strategy: &str) -> IndicatorParams {
+        IndicatorParams {
+            fast_period: 2.0,
+            slow_period: 4.0,
+            min_sentiment: -0.5,
+            reversion_window: 4.0,
+            reversion_threshold: 1.5,
+            sentiment_entry: 0.5,
+            sentiment_exit: -0.2,
+        }
+    }
+
+    fn momentum() -> StrategySet {
+        StrategySet {
+            order_fraction: 0.1,
+            strategies: vec![StrategySpec {
+                id: "momentum".to_string(),
+                kind: StrategyKind::Momentum,
+                enabled: true,
+                allocation: 1.0,
+                symbols: Vec::new(),
+            }],
+        }
+    }
+
+    fn backtest() -> Backtest {
+        Backtest {
+            interval: Duration::from_secs(60),
+            params,
+            ..Backtest::new(momentum(), CostModel::default())
+        }
+    }
+
+    /// One trade a minute, a second into the minute
+    fn ticks(prices: &[f64]) -> Vec<MarketData> {
+        prices
+            .iter()
+            .enumerate()
+            .map(|(minute, price)| MarketData {
+                symbol: "BTCUSDT".to_string(),
+                price: *price,
+                quantity: 1.0,
+                timestamp: minute as u64 * 60_000 + 1_000,
+                meta: EventMeta::default(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_decisions_are_filled_and_scored_against_buy_and_hold() {
+        let prices = [
+            100.0, 100.0, 100.0, 100.0, 100.0, 100.0, 110.0, 120.0, 130.0, 140.0, 130.0, 120.0,
+            110.0, 100.0, 90.0, 80.0,
+        ];
+        let report = backtest().run(&ticks(&prices)).unwrap();
+
+        // The crossovers are decided on the candles of minutes 6 and 11 and fill
+        // at the next trade
+        let trades: Vec<_> = report
+            .fills
+            .iter()
+            .map(|fill| (fill.side.as_str(), fill.price, fill.timestamp.timestamp()))
+            .collect();
+        assert_eq!(trades, vec![("BUY", 130.0, 481), ("SELL", 100.0, 781)]);
+
+        let momentum = &report.strategies[0];
+        assert_eq!(momentum.strategy_id, "momentum");
+        assert_eq!(momentum.closed_trades, 1);
+        let fees: f64 = report.fills.iter().map(|fill| fill.fee).sum();
+        assert!((momentum.net_pnl - (-30.0 * ORDER_QUANTITY - fees)).abs() < 1e-9);
+        assert!((momentum.max_drawdown + momentum.net_pnl).abs() < 1e-9);
+        // Bought at the first trade, sold at the last
+        let held = 80.0 * ORDER_QUANTITY * 0.999 - 100.0 * ORDER_QUANTITY * 1.001;
+        assert!((report.buy_and_hold - held).abs() < 1e-9);
+        assert!(report.net_pnl() < report.buy_and_hold);
+    }
+
+    #[test]
+    fn test_decisions_are_made_at_simulated_analysis_times() {
+        let prices = [
+            100.0, 100.0, 100.0, 100.0, 100.0, 100.0, 110.0, 120.0, 130.0, 140.0, 130.0, 120.0,
+            110.0, 100.0, 90.0, 80.0,
+        ];
+        let report = Backtest {
+            interval: Duration::from_secs(90),
+            ..backtest()
+        }
+        .run(&ticks(&prices))
+        .unwrap();
+
+        // Cycles run every 90s from the first trade, between the trades of a
+        // minute apart, and orders fill at the next trade
+        let decided: Vec<_> = report
+            .decisions
+            .iter()
+            .map(|(at, decision)| (at.timestamp(), order_of(decision).unwrap().0))
+            .collect();
+        assert_eq!(decided, vec![(451, "BUY"), (721, "SELL")]);
+        let filled: Vec<_> = report
+            .fills
+            .iter()
+            .map(|fill| fill.timestamp.timestamp())
+            .collect();
+        assert_eq!(filled, vec![481, 781]);
+        assert_eq!(report.start.timestamp(), 1);
+        assert_eq!(report.end.timestamp(), 901);
+        assert_eq!(report.longest_gap, TimeDelta::seconds(60));
+    }
+
+    #[test]
+    fn test_open_positions_are_closed_at_the_last_price() {
+        let prices = [
+            100.0, 100.0, 100.0, 100.0, 100.0, 100.0, 110.0, 120.0, 130.0, 140.0,
+        ];
+        let report = backtest().run(&ticks(&prices)).unwrap();
+
+        let sides: Vec<_> = report.fills.iter().map(|fill| fill.side.as_str()).collect();
+        assert_eq!(sides, vec!["BUY", "SELL"]);
+        assert_eq!(report.fills[1].price, 140.0);
+        assert_eq!(report.strategies[0].closed_trades, 1);
+    }
+}
@@ -0,0 +1,192 @@
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use common::trade_ledger::ReportPeriod;
+use std::path::PathBuf;
+
+/// Aurelia kernel
+#[derive(Debug, Parser)]
+#[command(name = "kernel", version, about = "Aurelia autonomous agent kernel")]
+pub struct Cli {
+    /// Path to the target server configuration
+    #[arg(long, global = true, default_value = "config/target_servers.json")]
+    pub servers_config: PathBuf,
+
+    /// Log output format
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the agent (default when no subcommand is given)
+    Run,
+    /// Deploy this binary to a configured server
+    Deploy {
+        /// Server ID from the server configuration
+        server_id: String,
+    },
+    /// Show the kernel status of every enabled server
+    Status,
+    /// Run one self-replication round against the configured targets
+    Replicate,
+    /// Replay recorded market data through the strategies and report their
+    /// performance against buy and hold
+    Backtest {
+        /// File with one JSON-encoded MarketData record per line; without it the
+        /// market history recorded by the agent is replayed
+        data_file: Option<PathBuf>,
+        /// Only replay this symbol; may be repeated. The market history defaults
+        /// to the symbols in the symbol universe
+        #[arg(long)]
+        symbol: Vec<String>,
+        /// Only replay market data at or after this time (RFC 3339)
+        #[arg(long)]
+        from: Option<DateTime<Utc>>,
+        /// Only replay market data before this time (RFC 3339)
+        #[arg(long)]
+        to: Option<DateTime<Utc>>,
+    },
+    /// Validate the server and strategy configuration files
+    ValidateConfig,
+    /// Check the exchange API keys and report their permissions; fails unless
+    /// live trading would be allowed with them
+    CheckCredentials,
+    /// Run each subsystem's diagnostic and print a pass/fail table; fails if
+    /// any check fails
+    SelfTest,
+    /// Follow the kernel log of a configured server
+    Logs {
+        /// Server ID from the server configuration
+        server_id: String,
+    },
+    /// Run a shell command on every enabled server; `{{id}}`, `{{ip}}` and other
+    /// server fields in the command are filled in per server
+    Exec {
+        command: String,
+        /// Only run on servers carrying this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Seconds to wait for each server, defaults to the configured command timeout
+        #[arg(long)]
+        timeout: Option<u64>,
+    },
+    /// Accept the current SSH host key of a configured server
+    TrustHost {
+        /// Server ID from the server configuration
+        server_id: String,
+    },
+    /// Summarize recorded fills: PnL and fees by period and by symbol
+    Report {
+        /// Only include fills at or after this time (RFC 3339)
+        #[arg(long)]
+        from: Option<DateTime<Utc>>,
+        /// Only include fills before this time (RFC 3339)
+        #[arg(long)]
+        to: Option<DateTime<Utc>>,
+        /// day, week or month
+        #[arg(long, default_value = "day")]
+        period: ReportPeriod,
+        #[arg(long, value_enum, default_value_t = ReportFormat::Json)]
+        format: ReportFormat,
+        /// Write the report here instead of standard output
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Stop the kernel on a configured server
+    StopRemote {
+        /// Server ID from the server configuration
+        server_id: String,
+    },
+    /// Move the agent on one configured server to another, carrying over its
+    /// config, state and recent decisions
+    Migrate {
+        /// Server ID the agent currently runs on
+        from: String,
+        /// Server ID to move it to
+        to: String,
+        #[arg(long, default_value = "manual migration")]
+        reason: String,
+    },
+    /// Print a signed self-update manifest for a kernel binary
+    SignRelease {
+        /// Kernel binary to sign
+        binary: PathBuf,
+        /// Release version recorded in the manifest
+        #[arg(long)]
+        version: String,
+        /// URL the binary will be downloaded from
+        #[arg(long)]
+        url: String,
+    },
+    /// Print a signed manifest for a strategy plugin, to publish next to it in
+    /// a plugin directory or registry
+    SignPlugin {
+        /// Native strategy library or `.wasm` strategy
+        file: PathBuf,
+        /// Plugin name
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        version: String,
+        /// URL the plugin will be downloaded from, for a registry
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// Seal a `.env` file with the fleet key for deployment, so the exchange
+    /// keys never reach replicas in cleartext
+    SealConfig {
+        /// Environment file to seal
+        #[arg(long, default_value = ".env")]
+        env_file: PathBuf,
+        /// Where the sealed file is written
+        #[arg(long, default_value = common::sealed_config::SEALED_CONFIG_PATH)]
+        output: PathBuf,
+    },
+    /// Check the signatures of a deployed bundle before it is started
+    VerifyBundle {
+        /// Deployment directory
+        #[arg(long, default_value = ".")]
+        dir: PathBuf,
+        /// Hex Ed25519 key the bundle must be signed with, defaults to
+        /// config/release_key.pub in the deployment directory
+        #[arg(long)]
+        public_key: Option<String>,
+    },
+    /// Run a fleet of in-process agents against a mock exchange to exercise
+    /// replication limits
+    Simulate {
+        /// Agents running at the start, including the primary
+        #[arg(long, default_value_t = 1)]
+        agents: usize,
+        /// Free servers replicas can be deployed onto
+        #[arg(long, default_value_t = 5)]
+        servers: usize,
+        #[arg(long, default_value_t = 30)]
+        rounds: u64,
+        /// Replication strategy of the primary, defaults to config/replication.json
+        #[arg(long)]
+        replication_config: Option<PathBuf>,
+    },
+}
+
+impl Cli {
+    /// The subcommand to execute, defaulting to `run`.
+    pub fn command(&self) -> &Command {
+        self.command.as_ref().unwrap_or(&Command::Run)
+    }
+}
@@ -0,0 +1,540 @@
+use crate::backtest::Backtest;
+use crate::cli::ReportFormat;
+use crate::self_test;
+use crate::simulation::{self, SimulationConfig};
+use crate::strategy_plugins::PluginManifest;
+use anyhow::{Context, Result};
+use autonomy_core::approvals::{load_or_create_token, APPROVAL_TOKEN_SECRET};
+use autonomy_core::self_replicator::{LINEAGE_PATH, REPLICATION_CONFIG_PATH};
+use autonomy_core::self_updater::ReleaseManifest;
+use autonomy_core::{
+    AgentMigrator, DeploymentCommander, FleetCommand, ReplicationStrategy, SelfReplicator,
+    ServerConfig,
+};
+use chrono::{DateTime, Utc};
+use common::cost_model::COST_MODEL_PATH;
+use common::event_schema::STRATEGY_ABI_VERSION;
+use common::identity::{AgentIdentity, IDENTITY_PATH};
+use common::sealed_config;
+use common::secrets::SECRET_ENV_PREFIX;
+use common::signing::{self, RELEASE_BINARY_NAME, TRUSTED_KEY_PATH};
+use common::strategies::STRATEGIES_PATH;
+use common::strategy_config::{StrategyConfig, STRATEGY_CONFIG_PATH};
+use common::trade_ledger::{ReportPeriod, TRADE_LEDGER_PATH};
+use common::{
+    BundleSignatures, CostModel, FleetKey, Liquidity, MarketData, RateLimiter, ReleaseSigner,
+    SealedConfig, SecretStore, StrategySet, TradeLedger,
+};
+use execution_engine::orders::ORDER_QUANTITY;
+use execution_engine::FundingGuard;
+use monitoring_service::{MonitoringConfig, MONITORING_CONFIG_PATH};
+use perception_core::market_store::MARKET_STORE_CONFIG_PATH;
+use perception_core::universe::SYMBOL_UNIVERSE_PATH;
+use perception_core::{MarketStore, MarketStoreConfig, UniverseConfig};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const CONFIG_FILES: [&str; 2] = [STRATEGY_CONFIG_PATH, "config/state.json"];
+
+fn current_binary() -> PathBuf {
+    std::env::current_exe().unwrap_or_else(|_| PathBuf::from("./kernel"))
+}
+
+fn signer() -> Result<ReleaseSigner> {
+    ReleaseSigner::load_or_create(&SecretStore::default())
+        .context("Failed to load the release signing key")
+}
+
+fn load_commander(servers_config: &Path) -> Result<DeploymentCommander> {
+    let config = ServerConfig::from_file(servers_config)?;
+    let monitoring = MonitoringConfig::load(MONITORING_CONFIG_PATH)?;
+    Ok(DeploymentCommander::with_config(current_binary(), config)
+        .with_signer(signer()?)
+        .with_api_port(monitoring.port))
+}
+
+pub async fn deploy(servers_config: &Path, server_id: &str) -> Result<()> {
+    let commander = load_commander(servers_config)?;
+    commander.deploy_to_server(server_id).await?;
+    println!("✅ Deployed to {}", server_id);
+    Ok(())
+}
+
+pub async fn status(servers_config: &Path) -> Result<()> {
+    let config = ServerConfig::from_file(servers_config)?;
+    let servers: Vec<_> = config
+        .get_servers_by_priority()
+        .into_iter()
+        .cloned()
+        .collect();
+    let commander = DeploymentCommander::with_config(current_binary(), config);
+
+    if servers.is_empty() {
+        println!("No enabled servers in {:?}", servers_config);
+        return Ok(());
+    }
+
+    for server in servers {
+        let state = match commander.check_server_status(&server.id).await {
+            Ok(true) => "running".to_string(),
+            Ok(false) => "stopped".to_string(),
+            Err(e) => format!("unreachable ({})", e),
+        };
+        println!("{:<20} {:<16} {}", server.id, server.ip, state);
+    }
+    Ok(())
+}
+
+pub async fn replicate(servers_config: &Path) -> Result<()> {
+    let config = ServerConfig::from_file(servers_config)?;
+    let identity = AgentIdentity::load_or_create(IDENTITY_PATH)?;
+    let strategy = ReplicationStrategy::load(Path::new(REPLICATION_CONFIG_PATH), &identity)?;
+    let replicator = SelfReplicator::with_server_config(current_binary(), Some(config))
+        .with_identity(identity)
+        .with_strategy(strategy)
+        .with_lineage_file(LINEAGE_PATH)
+        .with_signer(signer()?);
+    let results = replicator.replicate().await?;
+
+    if results.is_empty() {
+        println!("No replication performed");
+    }
+    for result in results {
+        match result.error {
+            None => println!(
+                "✅ {} replicated in {}s as {}",
+                result.target,
+                result.duration_seconds,
+                result.agent_id.unwrap_or_default()
+            ),
+            Some(e) => println!("❌ {} failed: {}", result.target, e),
+        }
+    }
+    Ok(())
+}
+
+/// Market data read from a file of JSON records, with the number of lines skipped
+fn recorded_ticks(data_file: &Path) -> Result<(Vec<MarketData>, usize)> {
+    let file = File::open(data_file).with_context(|| format!("Failed to open {:?}", data_file))?;
+
+    let mut ticks = Vec::new();
+    let mut skipped = 0;
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<MarketData>(&line) {
+            Ok(data) => ticks.push(data),
+            Err(_) => skipped += 1,
+        }
+    }
+    Ok((ticks, skipped))
+}
+
+/// The agent's market history of `symbols` in `[from_ms, to_ms)`, in time order
+fn stored_ticks(symbols: &[String], from_ms: u64, to_ms: u64) -> Result<Vec<MarketData>> {
+    let config = MarketStoreConfig::load(MARKET_STORE_CONFIG_PATH)?;
+    anyhow::ensure!(
+        config.path.exists(),
+        "No market history at {:?}",
+        config.path
+    );
+    let store = MarketStore::open(&config.path, config.candle_interval_seconds)?;
+    let mut ticks = Vec::new();
+    for symbol in symbols {
+        ticks.extend(store.replay(symbol, from_ms, to_ms)?);
+    }
+    ticks.sort_by_key(|tick| tick.timestamp);
+    Ok(ticks)
+}
+
+/// Replay `data_file`, or without one the market history the agent recorded,
+/// limited to `[from, to)` and to `symbols` when any are given.
+pub fn backtest(
+    data_file: Option<&Path>,
+    symbols: &[String],
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<()> {
+    let from_ms = from.map_or(0, |from| from.timestamp_millis().max(0) as u64);
+    let to_ms = to.map_or(i64::MAX as u64, |to| to.timestamp_millis().max(0) as u64);
+    let (mut ticks, skipped, source) = match data_file {
+        Some(data_file) => {
+            let (ticks, skipped) = recorded_ticks(data_file)?;
+            (ticks, skipped, format!("{:?}", data_file))
+        }
+        None => {
+            let symbols = match symbols {
+                [] => UniverseConfig::load(SYMBOL_UNIVERSE_PATH)?.symbols,
+                symbols => symbols.to_vec(),
+            };
+            let ticks = stored_ticks(&symbols, from_ms, to_ms)?;
+            (ticks, 0, "the market history".to_string())
+        }
+    };
+    ticks.retain(|tick| {
+        (from_ms..to_ms).contains(&tick.timestamp)
+            && (symbols.is_empty() || symbols.contains(&tick.symbol))
+    });
+
+    let (first, last) = match (ticks.first(), ticks.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => anyhow::bail!("No market data records found in {}", source),
+    };
+    let min_price = ticks.iter().map(|t| t.price).fold(f64::MAX, f64::min);
+    let max_price = ticks.iter().map(|t| t.price).fold(f64::MIN, f64::max);
+    let volume: f64 = ticks.iter().map(|t| t.quantity).sum();
+    let change_percent = if first.price > 0.0 {
+        (last.price - first.price) / first.price * 100.0
+    } else {
+        0.0
+    };
+
+    let costs = CostModel::load(COST_MODEL_PATH)?;
+    let strategies = StrategySet::load_or_legacy(STRATEGIES_PATH, STRATEGY_CONFIG_PATH)?;
+    let report = Backtest::new(strategies, costs.clone()).run(&ticks)?;
+
+    println!("Records:      {} ({} skipped)", ticks.len(), skipped);
+    println!("Symbol:       {}", first.symbol);
+    println!("Period:       {} - {}", report.start, report.end);
+    println!("Longest gap:  {}s", report.longest_gap.num_seconds());
+    println!("First price:  {:.2}", first.price);
+    println!("Last price:   {:.2}", last.price);
+    println!("Range:        {:.2} - {:.2}", min_price, max_price);
+    println!("Volume:       {:.4}", volume);
+    println!("Change:       {:.2}%", change_percent);
+    println!(
+        "Costs:        taker {:.3}%, slippage {} + {} bps, latency {}ms",
+        costs.fee_rate(0.0, Liquidity::Taker) * 100.0,
+        costs.slippage_fixed,
+        costs.slippage_bps,
+        costs.latency_ms
+    );
+    println!("Order size:   {}", ORDER_QUANTITY);
+    println!("Decisions:    {}", report.decisions.len());
+    println!("Fills:        {}", report.fills.len());
+    println!();
+    println!(
+        "{:<20} {:>8} {:>12} {:>12} {:>9}",
+        "Strategy", "Trades", "Net PnL", "Drawdown", "Return"
+    );
+    let percent = |pnl: f64| {
+        if report.cost_basis > 0.0 {
+            pnl / report.cost_basis * 100.0
+        } else {
+            0.0
+        }
+    };
+    for strategy in &report.strategies {
+        println!(
+            "{:<20} {:>8} {:>12.4} {:>12.4} {:>8.2}%",
+            strategy.strategy_id,
+            strategy.closed_trades,
+            strategy.net_pnl,
+            strategy.max_drawdown,
+            percent(strategy.net_pnl)
+        );
+    }
+    println!(
+        "{:<20} {:>8} {:>12.4} {:>12} {:>8.2}%",
+        "all strategies",
+        report
+            .strategies
+            .iter()
+            .map(|strategy| strategy.closed_trades)
+            .sum::<u32>(),
+        report.net_pnl(),
+        "",
+        percent(report.net_pnl())
+    );
+    println!(
+        "{:<20} {:>8} {:>12.4} {:>12} {:>8.2}%",
+        "buy and hold",
+        1,
+        report.buy_and_hold,
+        "",
+        percent(report.buy_and_hold)
+    );
+    Ok(())
+}
+
+pub fn validate_config(servers_config: &Path) -> Result<()> {
+    let problems = config_problems(servers_config);
+    if problems.is_empty() {
+        println!("✅ Configuration is valid");
+        return Ok(());
+    }
+
+    for problem in &problems {
+        println!("❌ {}", problem);
+    }
+    anyhow::bail!("{} configuration problem(s) found", problems.len())
+}
+
+/// Everything wrong with the server list and the configuration files
+pub(crate) fn config_problems(servers_config: &Path) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    match ServerConfig::from_file(servers_config) {
+        Ok(config) => problems.extend(config.validate()),
+        Err(e) => problems.push(format!("{:#}", e)),
+    }
+
+    for path in CONFIG_FILES {
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                if path == STRATEGY_CONFIG_PATH {
+                    // Older versions are fine, the agent migrates them on startup
+                    if let Err(e) = StrategyConfig::from_json(&content) {
+                        problems.push(format!("{}: {}", path, e));
+                    }
+                } else if let Err(e) = serde_json::from_str::<serde_json::Value>(&content) {
+                    problems.push(format!("{}: invalid JSON: {}", path, e));
+                }
+            }
+            Err(e) => problems.push(format!("{}: {}", path, e)),
+        }
+    }
+
+    problems
+}
+
+pub async fn self_test(servers_config: &Path) -> Result<()> {
+    let diagnostics = self_test::run(servers_config).await;
+    print!("{}", self_test::render(&diagnostics));
+    let failed = diagnostics.iter().filter(|d| !d.passed).count();
+    if failed > 0 {
+        anyhow::bail!("{} self-test check(s) failed", failed);
+    }
+    Ok(())
+}
+
+pub async fn check_credentials() -> Result<()> {
+    let report = FundingGuard::from_env(RateLimiter::default()).check().await;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    if !report.allows_live_trading() {
+        anyhow::bail!(
+            "Live trading would be refused, credentials are {:?}",
+            report.status
+        );
+    }
+    Ok(())
+}
+
+pub async fn logs(servers_config: &Path, server_id: &str) -> Result<()> {
+    let commander = load_commander(servers_config)?;
+    let mut stream = commander.stream_logs(server_id).await?;
+    while let Some(line) = stream.next_line().await {
+        println!("{}", line?);
+    }
+    Ok(())
+}
+
+pub async fn exec(
+    servers_config: &Path,
+    command: &str,
+    tag: Option<&str>,
+    timeout_seconds: Option<u64>,
+) -> Result<()> {
+    let commander = load_commander(servers_config)?;
+    let request = FleetCommand {
+        command: command.to_string(),
+        tag: tag.map(str::to_string),
+        timeout_seconds,
+    };
+    let results = commander.execute_on_all(&request).await?;
+    if results.is_empty() {
+        println!("No matching servers in {:?}", servers_config);
+        return Ok(());
+    }
+
+    let failed = results.iter().filter(|r| !r.succeeded()).count();
+    for result in &results {
+        let state = match (&result.error, result.exit_code) {
+            (Some(e), _) => format!("error: {}", e),
+            (None, Some(code)) => format!("exit {}", code),
+            (None, None) => "no exit status".to_string(),
+        };
+        println!(
+            "==> {} ({}, {} ms)",
+            result.server_id, state, result.duration_ms
+        );
+        if !result.stdout.is_empty() {
+            print!("{}", result.stdout);
+        }
+        if !result.stderr.is_empty() {
+            eprint!("{}", result.stderr);
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!(
+            "Command failed on {} of {} server(s)",
+            failed,
+            results.len()
+        );
+    }
+    Ok(())
+}
+
+pub async fn trust_host(servers_config: &Path, server_id: &str) -> Result<()> {
+    let commander = load_commander(servers_config)?;
+    let fingerprint = commander.trust_host(server_id).await?;
+    println!("✅ Trusted host key {} for {}", fingerprint, server_id);
+    Ok(())
+}
+
+pub async fn stop_remote(servers_config: &Path, server_id: &str) -> Result<()> {
+    let commander = load_commander(servers_config)?;
+    commander.stop_server(server_id).await?;
+    println!("✅ Stopped kernel on {}", server_id);
+    Ok(())
+}
+
+pub async fn migrate(servers_config: &Path, from: &str, to: &str, reason: &str) -> Result<()> {
+    let commander = load_commander(servers_config)?;
+    let status = AgentMigrator::new(Arc::new(commander))
+        .migrate(from, to, reason)
+        .await;
+    match status.error {
+        None => {
+            println!(
+                "✅ Migrated {} to {} with {} files",
+                from, to, status.snapshot_files
+            );
+            Ok(())
+        }
+        Some(e) => anyhow::bail!("Migration from {} to {} failed: {}", from, to, e),
+    }
+}
+
+pub fn report(
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    period: ReportPeriod,
+    format: ReportFormat,
+    output: Option<&Path>,
+) -> Result<()> {
+    let ledger = TradeLedger::open(TRADE_LEDGER_PATH)
+        .with_context(|| format!("Failed to open {}", TRADE_LEDGER_PATH))?;
+    let costs = CostModel::load(COST_MODEL_PATH)?;
+    let report = ledger.report(from, to, period).with_cost_model(costs);
+    let rendered = match format {
+        ReportFormat::Json => serde_json::to_string_pretty(&report)?,
+        ReportFormat::Csv => report.to_csv(),
+    };
+    match output {
+        Some(path) => {
+            fs::write(path, rendered).with_context(|| format!("Failed to write {:?}", path))?;
+            println!(
+                "✅ Wrote report of {} trades to {:?}",
+                report.total.trades, path
+            );
+        }
+        None => println!("{}", rendered),
+    }
+    Ok(())
+}
+
+pub fn sign_release(binary: &Path, version: &str, url: &str) -> Result<()> {
+    let contents = fs::read(binary).with_context(|| format!("Failed to read {:?}", binary))?;
+    let signer = signer()?;
+    let manifest = ReleaseManifest {
+        version: version.to_string(),
+        binary_url: url.to_string(),
+        sha256: format!("{:x}", Sha256::digest(&contents)),
+        signature: Some(signer.sign(RELEASE_BINARY_NAME, &contents)),
+    };
+    eprintln!("Signed with public key {}", signer.public_key());
+    println!("{}", serde_json::to_string_pretty(&manifest)?);
+    Ok(())
+}
+
+pub fn sign_plugin(file: &Path, name: &str, version: &str, url: Option<&str>) -> Result<()> {
+    let contents = fs::read(file).with_context(|| format!("Failed to read {:?}", file))?;
+    let file_name = file
+        .file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("{:?} has no file name", file))?;
+    let signer = signer()?;
+    let manifest = PluginManifest {
+        name: name.to_string(),
+        version: version.to_string(),
+        file: file_name.to_string(),
+        url: url.map(str::to_string),
+        sha256: format!("{:x}", Sha256::digest(&contents)),
+        signature: Some(signer.sign(&PluginManifest::signed_name(name, version), &contents)),
+        abi_version: Some(STRATEGY_ABI_VERSION),
+    };
+    eprintln!("Signed with public key {}", signer.public_key());
+    println!("{}", serde_json::to_string_pretty(&manifest)?);
+    Ok(())
+}
+
+pub fn seal_config(env_file: &Path, output: &Path) -> Result<()> {
+    let contents =
+        fs::read_to_string(env_file).with_context(|| format!("Failed to read {:?}", env_file))?;
+    let mut values = sealed_config::parse_env_file(&contents);
+    if values.is_empty() {
+        anyhow::bail!("{:?} has no KEY=VALUE lines", env_file);
+    }
+    let store = SecretStore::default();
+    // Replicas share the approval token so they accept config the primary pushes
+    let token_var = format!(
+        "{}{}",
+        SECRET_ENV_PREFIX,
+        APPROVAL_TOKEN_SECRET.to_uppercase()
+    );
+    if let std::collections::btree_map::Entry::Vacant(entry) = values.entry(token_var) {
+        entry.insert(load_or_create_token(&store)?);
+    }
+    let key = FleetKey::load_or_create(&store)?;
+    SealedConfig::seal(&key, &values)?.save(output)?;
+    println!(
+        "✅ Sealed {} variable(s) into {:?} with fleet key {}",
+        values.len(),
+        output,
+        key.id()
+    );
+    eprintln!(
+        "Replicas need the key in AURELIA_SECRET_FLEET_CONFIG_KEY or at the URL in AURELIA_FLEET_KEY_URL"
+    );
+    Ok(())
+}
+
+pub fn verify_bundle(dir: &Path, public_key: Option<&str>) -> Result<()> {
+    let trusted = match public_key {
+        Some(key) => key.to_string(),
+        None => signing::trusted_key(dir)?
+            .with_context(|| format!("No public key given and no {}", TRUSTED_KEY_PATH))?,
+    };
+    let signatures = BundleSignatures::verify_dir(dir, &trusted)?;
+    println!("✅ {} signed files verified", signatures.files.len());
+    Ok(())
+}
+
+pub fn simulate(
+    agents: usize,
+    servers: usize,
+    rounds: u64,
+    replication_config: Option<&Path>,
+) -> Result<()> {
+    if agents == 0 {
+        anyhow::bail!("The simulation needs at least one agent");
+    }
+    let path = replication_config.unwrap_or(Path::new(REPLICATION_CONFIG_PATH));
+    let strategy = ReplicationStrategy::load(path, &AgentIdentity::new_root())
+        .with_context(|| format!("Failed to load replication strategy from {:?}", path))?;
+
+    let report = simulation::run(&SimulationConfig {
+        agents,
+        servers,
+        rounds,
+        strategy,
+    });
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
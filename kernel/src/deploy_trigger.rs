@@ -0,0 +1,154 @@
+//! Deployment triggers dropped into the deployment directory.
+//!
+//! `DeploymentClient::trigger_self_replication` copies a `deploy_trigger.json` with
+//! a [`DeploymentInfo`] next to the kernel. The watcher validates it, turns it into
+//! an `AppEvent::Deploy` for the execution engine and moves the file into
+//! [`TRIGGER_ARCHIVE_DIR`] together with the outcome, so a trigger runs only once.
+
+use chrono::{DateTime, Utc};
+use common::{AppEvent, AureliaResult, DeploymentInfo, EventBus};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const DEPLOY_TRIGGER_PATH: &str = "deploy_trigger.json";
+pub const TRIGGER_ARCHIVE_DIR: &str = "data/deploy_triggers";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum TriggerOutcome {
+    /// Handed to the execution engine as `AppEvent::Deploy`
+    Dispatched,
+    Rejected {
+        reason: String,
+    },
+}
+
+/// What is left in the archive for each trigger
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedTrigger {
+    pub received_at: DateTime<Utc>,
+    pub outcome: TriggerOutcome,
+    /// The trigger file as received, which may not be valid JSON
+    pub trigger: String,
+}
+
+/// Reject triggers that could not possibly be deployed to
+pub fn validate(info: &DeploymentInfo) -> Result<(), String> {
+    let host_ok = info.ip.parse::<IpAddr>().is_ok()
+        || (!info.ip.is_empty()
+            && info
+                .ip
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.'));
+    if !host_ok {
+        return Err(format!("invalid target host '{}'", info.ip));
+    }
+    if info.remote_user.trim().is_empty() {
+        return Err("remote_user is empty".to_string());
+    }
+    if info.private_key_path.trim().is_empty() {
+        return Err("private_key_path is empty".to_string());
+    }
+    if !(info.remote_path.starts_with('/') || info.remote_path.starts_with('~')) {
+        return Err(format!(
+            "remote_path '{}' must be absolute or start with ~",
+            info.remote_path
+        ));
+    }
+    Ok(())
+}
+
+/// Handle the trigger at `path` if there is one, archiving it into `archive_dir`
+pub async fn process_trigger(
+    path: &Path,
+    archive_dir: &Path,
+    bus: &EventBus,
+) -> AureliaResult<Option<TriggerOutcome>> {
+    let trigger = match std::fs::read_to_string(path) {
+        Ok(trigger) => trigger,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let parsed = serde_json::from_str::<DeploymentInfo>(&trigger)
+        .map_err(|e| format!("invalid trigger: {}", e))
+        .and_then(|info| validate(&info).map(|_| info));
+    let outcome = match parsed {
+        Ok(info) => {
+            tracing::info!(target_ip = %info.ip, "Deployment trigger received");
+            match bus.send_control(AppEvent::Deploy(info)).await {
+                Ok(_) => TriggerOutcome::Dispatched,
+                Err(_) => TriggerOutcome::Rejected {
+                    reason: "kernel is not accepting control events".to_string(),
+                },
+            }
+        }
+        Err(reason) => {
+            tracing::warn!("Rejected deployment trigger: {}", reason);
+            TriggerOutcome::Rejected { reason }
+        }
+    };
+
+    let received_at = Utc::now();
+    let archived = ArchivedTrigger {
+        received_at,
+        outcome: outcome.clone(),
+        trigger,
+    };
+    std::fs::create_dir_all(archive_dir)?;
+    let archive_path =
+        archive_dir.join(format!("{}.json", received_at.format("%Y%m%dT%H%M%S%.9fZ")));
+    std::fs::write(&archive_path, serde_json::to_string_pretty(&archived)?)?;
+    std::fs::remove_file(path)?;
+    Ok(Some(outcome))
+}
+
+/// Poll for trigger files for as long as the kernel runs
+pub async fn run(bus: EventBus, path: PathBuf, archive_dir: PathBuf) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = process_trigger(&path, &archive_dir, &bus).await {
+            tracing::error!("Failed to process deployment trigger {:?}: {}", path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::Topic;
+
+    #[tokio::test]
+    async fn test_triggers_are_dispatched_and_archived() {
+        let dir = std::env::temp_dir().join(format!("deploy-trigger-{}", std::process::id()));
+        let archive = dir.join("archive");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(DEPLOY_TRIGGER_PATH);
+
+        let bus = EventBus::new(8);
+        let mut rx = bus.subscribe_as("deploy_trigger", &[Topic::Deployment]);
+        assert_eq!(process_trigger(&path, &archive, &bus).await.unwrap(), None);
+
+        let trigger = r#"{"ip": "10.0.0.2", "remote_user": "aurelia",
+            "private_key_path": "~/.ssh/id_rsa", "remote_path": "/opt/aurelia",
+            "local_exe_path": "./kernel"}"#;
+        std::fs::write(&path, trigger).unwrap();
+        let outcome = process_trigger(&path, &archive, &bus).await.unwrap();
+        assert_eq!(outcome, Some(TriggerOutcome::Dispatched));
+        assert!(matches!(rx.try_recv(), Ok(AppEvent::Deploy(info)) if info.ip == "10.0.0.2"));
+        assert!(!path.exists());
+
+        std::fs::write(&path, trigger.replace("/opt/aurelia", "opt")).unwrap();
+        let outcome = process_trigger(&path, &archive, &bus).await.unwrap();
+        assert!(matches!(outcome, Some(TriggerOutcome::Rejected { .. })));
+        assert!(rx.try_recv().is_err());
+        assert_eq!(std::fs::read_dir(&archive).unwrap().count(), 2);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,456 @@
+//! Bridge between the event bus and an external message broker.
+//!
+//! Dashboards, alerting and other bots integrate through NATS or MQTT instead of
+//! linking against the crate. The events listed in `events` are published as
+//! JSON to `{topic_prefix}.{agent_id}.{kind}` on NATS and
+//! `{topic_prefix}/{agent_id}/{kind}` on MQTT, where `kind` is
+//! [`AppEvent::kind`]. Messages on the command topic are parsed as
+//! [`BridgeCommand`]s and their event put on the control channel, but only when
+//! they carry the approval token and their kind is in `allowed_commands`;
+//! everything else is logged and dropped. No command is accepted by default.
+//!
+//! The broker clients are behind the `nats` and `mqtt` features.
+
+use autonomy_core::ApprovalGate;
+use common::audit::{self, AuditCategory};
+use common::{AppEvent, AureliaResult, EventBus, EventReceiver, LagHandler, Topic};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+
+pub const EVENT_BRIDGE_CONFIG_PATH: &str = "config/event_bridge.json";
+
+/// Commands waiting to be handled before the broker client stops reading
+const COMMAND_BUFFER: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BrokerConfig {
+    Nats {
+        url: String,
+    },
+    Mqtt {
+        host: String,
+        port: u16,
+        /// Defaults to `aurelia-{agent_id}`
+        client_id: Option<String>,
+        username: Option<String>,
+        /// Name of the secret holding the password, e.g. `mqtt_password`
+        password_secret: Option<String>,
+    },
+}
+
+impl Default for BrokerConfig {
+    fn default() -> Self {
+        Self::Nats {
+            url: "nats://127.0.0.1:4222".to_string(),
+        }
+    }
+}
+
+impl BrokerConfig {
+    fn separator(&self) -> char {
+        match self {
+            Self::Nats { .. } => '.',
+            Self::Mqtt { .. } => '/',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventBridgeConfig {
+    pub enabled: bool,
+    pub broker: BrokerConfig,
+    pub topic_prefix: String,
+    /// Kinds of events to publish, as named by `AppEvent::kind`
+    pub events: Vec<String>,
+    /// Name under the agent's prefix to read commands from, e.g. `commands`;
+    /// `None` to only publish
+    pub command_topic: Option<String>,
+    /// Kinds of events accepted on the command topic
+    pub allowed_commands: Vec<String>,
+}
+
+impl Default for EventBridgeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            broker: BrokerConfig::default(),
+            topic_prefix: "aurelia".to_string(),
+            events: [
+                "strategy_decision",
+                "order_update",
+                "financial_update",
+                "system_state_change",
+                "deployment_status_changed",
+            ]
+            .map(String::from)
+            .to_vec(),
+            command_topic: None,
+            allowed_commands: Vec::new(),
+        }
+    }
+}
+
+impl EventBridgeConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Broker topic for `name` under this agent's prefix
+    pub fn topic(&self, agent_id: &str, name: &str) -> String {
+        let separator = self.broker.separator();
+        format!(
+            "{}{}{}{}{}",
+            self.topic_prefix, separator, agent_id, separator, name
+        )
+    }
+
+    /// Bus topics that can carry the selected events. Market ticks are only
+    /// subscribed to when asked for, since they outnumber everything else.
+    fn bus_topics(&self) -> Vec<Topic> {
+        let ticks = self.events.iter().any(|kind| kind == "market_tick");
+        Topic::ALL
+            .into_iter()
+            .filter(|topic| ticks || *topic != Topic::MarketTicks)
+            .collect()
+    }
+}
+
+/// A message on the command topic: the event to put on the control channel and
+/// the approval token that authorizes it, as the REST and gRPC APIs require.
+#[derive(Debug, Deserialize)]
+pub struct BridgeCommand {
+    pub token: Option<String>,
+    pub event: AppEvent,
+}
+
+type Publish = Box<
+    dyn Fn(String, Vec<u8>) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// A connected broker: publishes to a topic and yields command payloads.
+struct Connection {
+    publish: Publish,
+    commands: mpsc::Receiver<Vec<u8>>,
+}
+
+pub struct EventBridge {
+    config: EventBridgeConfig,
+    agent_id: String,
+    bus: EventBus,
+    rx: EventReceiver,
+    approvals: Option<ApprovalGate>,
+}
+
+impl EventBridge {
+    pub fn new(config: EventBridgeConfig, agent_id: impl Into<String>, bus: EventBus) -> Self {
+        let rx = bus.subscribe_as("event_bridge", &config.bus_topics());
+        Self {
+            config,
+            agent_id: agent_id.into(),
+            bus,
+            rx,
+            approvals: None,
+        }
+    }
+
+    /// Accept commands carrying the approval token of `gate`; without it every
+    /// command is dropped
+    pub fn with_approval_gate(mut self, gate: ApprovalGate) -> Self {
+        self.approvals = Some(gate);
+        self
+    }
+
+    pub async fn run(mut self) {
+        let command_topic = self
+            .config
+            .command_topic
+            .as_ref()
+            .map(|name| self.config.topic(&self.agent_id, name));
+        let mut connection = match connect(&self.config.broker, &self.agent_id, command_topic).await
+        {
+            Ok(connection) => connection,
+            Err(e) => {
+                tracing::error!("[Event Bridge] Failed to connect to the broker: {}", e);
+                return;
+            }
+        };
+        tracing::info!(
+            events = ?self.config.events,
+            command_topic = ?self.config.command_topic,
+            "[Event Bridge] Connected to {:?}", self.config.broker
+        );
+
+        let lag = LagHandler::new("event_bridge").with_event_bus(self.bus.clone());
+        loop {
+            tokio::select! {
+                event = self.rx.recv() => match event {
+                    Ok(event) => self.publish(&connection.publish, &event).await,
+                    Err(RecvError::Lagged(skipped)) => {
+                        lag.lagged(skipped);
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                Some(payload) = connection.commands.recv() => {
+                    self.handle_command(&payload).await;
+                }
+            }
+        }
+    }
+
+    async fn publish(&self, publish: &Publish, event: &AppEvent) {
+        let kind = event.kind();
+        if !self.config.events.iter().any(|selected| selected == kind) {
+            return;
+        }
+        let payload = match serde_json::to_vec(event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                tracing::error!("[Event Bridge] Failed to serialize {}: {}", kind, e);
+                return;
+            }
+        };
+        let topic = self.config.topic(&self.agent_id, kind);
+        if let Err(e) = publish(topic, payload).await {
+            tracing::warn!("[Event Bridge] Failed to publish {}: {}", kind, e);
+        }
+    }
+
+    /// Forward a command from the broker to the control channel if it carries
+    /// the approval token and its kind is allowed. Returns whether it was forwarded.
+    async fn handle_command(&self, payload: &[u8]) -> bool {
+        let command: BridgeCommand = match serde_json::from_slice(payload) {
+            Ok(command) => command,
+            Err(e) => {
+                tracing::warn!("[Event Bridge] Ignoring malformed command: {}", e);
+                return false;
+            }
+        };
+        let event = command.event;
+        let authorized = self
+            .approvals
+            .as_ref()
+            .is_some_and(|gate| gate.is_authorized(command.token.as_deref()));
+        if !authorized {
+            tracing::warn!(
+                "[Event Bridge] Rejected command {}: no valid approval token",
+                event.kind()
+            );
+            return false;
+        }
+        let kind = event.kind();
+        if !self.config.allowed_commands.iter().any(|k| k == kind) {
+            tracing::warn!("[Event Bridge] Rejected command {}: not allowed", kind);
+            return false;
+        }
+        audit::record(AuditCategory::ConfigChange, "bridge_command", &event);
+        match self.bus.send_control(event).await {
+            Ok(_) => true,
+            Err(_) => {
+                tracing::error!("[Event Bridge] Kernel is not accepting control events");
+                false
+            }
+        }
+    }
+}
+
+async fn connect(
+    broker: &BrokerConfig,
+    agent_id: &str,
+    command_topic: Option<String>,
+) -> anyhow::Result<Connection> {
+    match broker {
+        #[cfg(feature = "nats")]
+        BrokerConfig::Nats { url } => connect_nats(url, command_topic).await,
+        #[cfg(feature = "mqtt")]
+        BrokerConfig::Mqtt {
+            host,
+            port,
+            client_id,
+            username,
+            password_secret,
+        } => {
+            let client_id = client_id
+                .clone()
+                .unwrap_or_else(|| format!("aurelia-{}", agent_id));
+            let password = match password_secret {
+                Some(name) => common::SecretStore::default().get(name)?,
+                None => None,
+            };
+            let credentials = username
+                .clone()
+                .map(|user| (user, password.unwrap_or_default()));
+            connect_mqtt(host, *port, client_id, credentials, command_topic).await
+        }
+        #[allow(unreachable_patterns)]
+        other => {
+            let _ = (agent_id, command_topic);
+            anyhow::bail!("kernel was built without support for {:?}", other)
+        }
+    }
+}
+
+#[cfg(feature = "nats")]
+async fn connect_nats(url: &str, command_topic: Option<String>) -> anyhow::Result<Connection> {
+    use futures_util::StreamExt;
+
+    let client = async_nats::connect(url).await?;
+    let (command_tx, commands) = mpsc::channel(COMMAND_BUFFER);
+    if let Some(subject) = command_topic {
+        let mut subscriber = client.subscribe(subject).await?;
+        tokio::spawn(async move {
+            while let Some(message) = subscriber.next().await {
+                if command_tx.send(message.payload.to_vec()).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    let publish: Publish = Box::new(move |subject, payload| {
+        let client = client.clone();
+        Box::pin(async move {
+            client.publish(subject, payload.into()).await?;
+            Ok(())
+        })
+    });
+    Ok(Connection { publish, commands })
+}
+
+#[cfg(feature = "mqtt")]
+async fn connect_mqtt(
+    host: &str,
+    port: u16,
+    client_id: String,
+    credentials: Option<(String, String)>,
+    command_topic: Option<String>,
+) -> anyhow::Result<Connection> {
+    use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+    use std::time::Duration;
+
+    let mut options = MqttOptions::new(client_id, host, port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if let Some((username, password)) = credentials {
+        options.set_credentials(username, password);
+    }
+    let (client, mut event_loop) = AsyncClient::new(options, COMMAND_BUFFER);
+    let (command_tx, commands) = mpsc::channel(COMMAND_BUFFER);
+    let subscriber = client.clone();
+    tokio::spawn(async move {
+        loop {
+            match event_loop.poll().await {
+                // Subscriptions do not survive a reconnect with a clean session
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    if let Some(topic) = &command_topic {
+                        if let Err(e) = subscriber.try_subscribe(topic, QoS::AtLeastOnce) {
+                            tracing::error!(
+                                "[Event Bridge] Failed to subscribe to {}: {}",
+                                topic,
+                                e
+                            );
+                        }
+                    }
+                }
+                Ok(Event::Incoming(Packet::Publish(message))) => {
+                    if command_tx.send(message.payload.to_vec()).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!("[Event Bridge] MQTT connection error: {}", e);
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            }
+        }
+    });
+    let publish: Publish = Box::new(move |topic, payload| {
+        let client = client.clone();
+        Box::pin(async move {
+            client
+                .publish(topic, QoS::AtLeastOnce, false, payload)
+                .await?;
+            Ok(())
+        })
+    });
+    Ok(Connection { publish, commands })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_only_allowed_commands_reach_the_control_channel() {
+        assert!(EventBridgeConfig::default().allowed_commands.is_empty());
+        let config = EventBridgeConfig {
+            command_topic: Some("commands".to_string()),
+            allowed_commands: vec!["strategy_param_update".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            config.topic("agent-1", "order_update"),
+            "aurelia.agent-1.order_update"
+        );
+        assert!(!config.bus_topics().contains(&Topic::MarketTicks));
+
+        let bus = EventBus::new(8);
+        let mut control = bus.subscribe_as("test", &[Topic::Control]);
+        let bridge = EventBridge::new(config, "agent-1", bus.clone())
+            .with_approval_gate(ApprovalGate::default().with_token("secret".to_string()));
+
+        let update = br#"{"token": "secret", "event": {"StrategyParamUpdate": {"name": "interval_seconds", "value": 30.0}}}"#;
+        assert!(bridge.handle_command(update).await);
+        assert!(matches!(
+            control.try_recv(),
+            Ok(AppEvent::StrategyParamUpdate(update)) if update.value == 30.0
+        ));
+
+        let reload = br#"{"token": "secret", "event": "ReloadConfig"}"#;
+        assert!(!bridge.handle_command(reload).await);
+        assert!(!bridge.handle_command(b"not json").await);
+        assert!(control.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_commands_without_the_approval_token_are_dropped() {
+        let config = EventBridgeConfig {
+            command_topic: Some("commands".to_string()),
+            allowed_commands: vec!["reload_config".to_string()],
+            ..Default::default()
+        };
+        let bus = EventBus::new(8);
+        let mut control = bus.subscribe_as("test", &[Topic::Control]);
+
+        // Without an approval gate nothing is forwarded, token or not
+        let ungated = EventBridge::new(config.clone(), "agent-1", bus.clone());
+        assert!(
+            !ungated
+                .handle_command(br#"{"token": "secret", "event": "ReloadConfig"}"#)
+                .await
+        );
+
+        let bridge = EventBridge::new(config, "agent-1", bus.clone())
+            .with_approval_gate(ApprovalGate::default().with_token("secret".to_string()));
+        for payload in [
+            &br#"{"event": "ReloadConfig"}"#[..],
+            br#"{"token": "wrong", "event": "ReloadConfig"}"#,
+            // A bare event, as accepted before commands were authenticated
+            br#""ReloadConfig""#,
+        ] {
+            assert!(!bridge.handle_command(payload).await);
+        }
+        assert!(control.try_recv().is_err());
+    }
+}
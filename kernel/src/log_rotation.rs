@@ -0,0 +1,312 @@
+//! The kernel's own log file and the files it leaves behind.
+//!
+//! With `file` set in `config/logging.json` the kernel writes its log there
+//! instead of relying on whatever its stdout is redirected to, and rotates it
+//! once it reaches `max_size_mb` or has been written to for
+//! `rotate_every_hours`. Rotated files are renamed to `<file>.<timestamp>`,
+//! gzipped when `compress` is set, and only the newest `retention` are kept.
+//! Archived deployment triggers, leftover shadow candidates and strategy output
+//! nobody read are deleted once older than `artifact_max_age_hours`.
+
+use crate::deploy_trigger::TRIGGER_ARCHIVE_DIR;
+use crate::strategy_module::SHADOW_DIR;
+use chrono::Utc;
+use common::AureliaResult;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use strategy_engine::OUTPUT_FILE;
+use tracing::warn;
+
+pub const LOGGING_CONFIG_PATH: &str = "config/logging.json";
+
+/// How often stale artifacts are looked for
+pub const ARTIFACT_CLEANUP_INTERVAL: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Log file the kernel writes itself; `None` logs to stdout only
+    pub file: Option<PathBuf>,
+    /// Size at which the log file is rotated
+    pub max_size_mb: u64,
+    /// Rotate after this long even if the size was not reached
+    pub rotate_every_hours: Option<u64>,
+    /// Rotated files kept, oldest deleted first
+    pub retention: usize,
+    /// Gzip rotated files
+    pub compress: bool,
+    /// Age at which trigger archives, shadow leftovers and unread strategy
+    /// output are deleted
+    pub artifact_max_age_hours: u64,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            file: Some(PathBuf::from("logs/aurelia.log")),
+            max_size_mb: 100,
+            rotate_every_hours: Some(24),
+            retention: 7,
+            compress: true,
+            artifact_max_age_hours: 72,
+        }
+    }
+}
+
+impl LoggingConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn artifact_max_age(&self) -> Duration {
+        Duration::from_secs(self.artifact_max_age_hours * 3600)
+    }
+}
+
+/// A log file that rotates itself as it is written.
+pub struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_age: Option<Duration>,
+    retention: usize,
+    compress: bool,
+    file: File,
+    written: u64,
+    opened_at: Instant,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, config: &LoggingConfig) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes: config.max_size_mb.max(1) * 1024 * 1024,
+            max_age: config
+                .rotate_every_hours
+                .map(|hours| Duration::from_secs(hours * 3600)),
+            retention: config.retention,
+            compress: config.compress,
+            written: file.metadata()?.len(),
+            file,
+            opened_at: Instant::now(),
+        })
+    }
+
+    fn due(&self, incoming: usize) -> bool {
+        self.written > 0
+            && (self.written + incoming as u64 > self.max_bytes
+                || self
+                    .max_age
+                    .is_some_and(|age| self.opened_at.elapsed() >= age))
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let stamp = Utc::now().format("%Y%m%d-%H%M%S%.3f");
+        let rotated = suffixed(&self.path, &stamp.to_string());
+        fs::rename(&self.path, &rotated)?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.written = 0;
+        self.opened_at = Instant::now();
+
+        let path = self.path.clone();
+        let retention = self.retention;
+        if self.compress {
+            // Compressing a full file takes a while, keep it off the logging path
+            std::thread::spawn(move || {
+                if let Err(e) = gzip(&rotated) {
+                    eprintln!("Failed to compress {}: {}", rotated.display(), e);
+                }
+                prune_rotated(&path, retention);
+            });
+        } else {
+            prune_rotated(&path, retention);
+        }
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.due(buf.len()) {
+            // Keep logging to the current file rather than losing lines
+            if let Err(e) = self.rotate() {
+                eprintln!("Failed to rotate {}: {}", self.path.display(), e);
+                self.opened_at = Instant::now();
+            }
+        }
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn gzip(path: &Path) -> io::Result<()> {
+    let compressed = suffixed(path, "gz");
+    let mut encoder = GzEncoder::new(File::create(&compressed)?, Compression::default());
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?;
+    fs::remove_file(path)
+}
+
+/// Rotated copies of `path`, oldest first; the timestamp suffix sorts by age.
+fn rotated_files(path: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Vec::new();
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let prefix = format!("{}.", name.to_string_lossy());
+    let mut rotated: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+        .map(|entry| entry.path())
+        .collect();
+    rotated.sort();
+    rotated
+}
+
+fn prune_rotated(path: &Path, retention: usize) {
+    let rotated = rotated_files(path);
+    let excess = rotated.len().saturating_sub(retention);
+    for old in &rotated[..excess] {
+        if let Err(e) = fs::remove_file(old) {
+            eprintln!("Failed to remove {}: {}", old.display(), e);
+        }
+    }
+}
+
+/// Delete archived triggers and unread strategy output older than `max_age`.
+/// With `at_startup` leftover shadow candidates go too, none can be running yet.
+pub fn cleanup_artifacts(max_age: Duration, at_startup: bool) -> usize {
+    let mut removed = remove_older_than(Path::new(TRIGGER_ARCHIVE_DIR), max_age);
+    if at_startup {
+        removed += remove_older_than(Path::new(SHADOW_DIR), max_age);
+    }
+    if is_older_than(Path::new(OUTPUT_FILE), max_age) && fs::remove_file(OUTPUT_FILE).is_ok() {
+        removed += 1;
+    }
+    removed
+}
+
+fn is_older_than(path: &Path, max_age: Duration) -> bool {
+    fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age > max_age)
+}
+
+fn remove_older_than(dir: &Path, max_age: Duration) -> usize {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    let mut removed = 0;
+    for path in entries.filter_map(Result::ok).map(|entry| entry.path()) {
+        if !path.is_file() || !is_older_than(&path, max_age) {
+            continue;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => removed += 1,
+            Err(e) => warn!("Failed to remove {}: {}", path.display(), e),
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("aurelia-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_rotates_by_size_and_keeps_the_newest() {
+        let dir = temp_dir("log-rotation");
+        let path = dir.join("aurelia.log");
+        let config = LoggingConfig {
+            max_size_mb: 1,
+            rotate_every_hours: None,
+            retention: 2,
+            compress: false,
+            ..LoggingConfig::default()
+        };
+        let mut log = RotatingFile::open(&path, &config).unwrap();
+        let line = vec![b'x'; 400 * 1024];
+        for _ in 0..10 {
+            log.write_all(&line).unwrap();
+            // Distinct timestamps for each rotated file
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        log.flush().unwrap();
+
+        assert!(fs::metadata(&path).unwrap().len() <= 1024 * 1024);
+        let rotated = rotated_files(&path);
+        assert_eq!(rotated.len(), 2);
+        for file in rotated {
+            assert_eq!(fs::metadata(file).unwrap().len(), 800 * 1024);
+        }
+
+        gzip(&path).unwrap();
+        assert!(!path.exists());
+        assert!(suffixed(&path, "gz").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_only_stale_files_are_removed() {
+        let dir = temp_dir("artifact-cleanup");
+        let stale = dir.join("stale.json");
+        let fresh = dir.join("fresh.json");
+        fs::write(&stale, "{}").unwrap();
+        fs::write(&fresh, "{}").unwrap();
+        File::options()
+            .write(true)
+            .open(&stale)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(7200))
+            .unwrap();
+
+        assert_eq!(remove_older_than(&dir, Duration::from_secs(3600)), 1);
+        assert!(!stale.exists());
+        assert!(fresh.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,214 +1,1409 @@
-use autonomy_core::AutonomousAgent;
-use common::AppEvent;
-use execution_engine::ExecutionEngine;
-use libloading::{Library, Symbol};
-use metamorphosis_engine::MetamorphosisEngine;
-use monitoring_service::{MonitoringConfig, MonitoringService};
-use perception_core::run as run_perception_core;
-use reasoning_engine::ReasoningEngine;
+mod backtest;
+mod cli;
+mod commands;
+mod deploy_trigger;
+#[cfg(any(feature = "nats", feature = "mqtt"))]
+mod event_bridge;
+mod log_rotation;
+mod recovery;
+mod sealed_env;
+mod self_test;
+mod shadow;
+mod simulation;
+mod strategy_module;
+mod strategy_plugins;
+mod systemd;
+mod telemetry;
+#[cfg(feature = "wasm")]
+mod wasm_strategy;
+
+use autonomy_core::approvals::{load_or_create_token, APPROVALS_CONFIG_PATH};
+use autonomy_core::autonomy_config::AUTONOMY_CONFIG_PATH;
+use autonomy_core::cloud_provisioner::{CLOUD_CONFIG_PATH, CLOUD_SERVERS_PATH};
+use autonomy_core::decision_journal::DECISION_JOURNAL_PATH;
+use autonomy_core::deployment_queue::DEPLOYMENT_QUEUE_PATH;
+use autonomy_core::health_monitor::HEALTH_CONFIG_PATH;
+use autonomy_core::self_replicator::{LINEAGE_PATH, REPLICATION_CONFIG_PATH};
+use autonomy_core::self_updater::SELF_UPDATE_CONFIG_PATH;
+use autonomy_core::task_scheduler::TASK_QUEUE_PATH;
+use autonomy_core::{
+    build_policy, AgentMigrator, ApprovalConfig, ApprovalGate, AutonomousAgent, AutonomyConfig,
+    CloudConfig, CloudFleet, ClusterRegistry, DecisionJournal, DeploymentCommander,
+    DeploymentQueue, HealthThresholds, ReplicationStrategy, SelfReplicator, SelfUpdateConfig,
+    SelfUpdater, ServerConfig, TaskScheduler,
+};
+use clap::Parser;
+use cli::{Cli, Command};
+use common::audit::{self, AUDIT_LOG_PATH};
+use common::cost_model::COST_MODEL_PATH;
+use common::event_schema;
+use common::health::component;
+use common::identity::{AgentIdentity, IDENTITY_PATH};
+use common::priority::PRIORITY_CONFIG_PATH;
+use common::rate_limit::{RateLimitConfig, RATE_LIMITS_PATH};
+use common::state_store::STATE_PATH;
+use common::strategies::STRATEGIES_PATH;
+use common::strategy_config::STRATEGY_CONFIG_PATH;
+use common::trade_ledger::TRADE_LEDGER_PATH;
+use common::valuation::ACCOUNTING_CONFIG_PATH;
+use common::{
+    AccountingConfig, AppEvent, AuditLog, AureliaError, AureliaResult, CostModel, EventBus,
+    FleetKey, HealthState, LagHandler, ProcessPriority, RateLimiter, ReleaseSigner, SecretStore,
+    StateStore, StrategyParamUpdate, StrategySet, Topic, TradeLedger,
+};
+use deploy_trigger::{DEPLOY_TRIGGER_PATH, TRIGGER_ARCHIVE_DIR};
+use execution_engine::algos::EXECUTION_ALGO_CONFIG_PATH;
+use execution_engine::allocator::ALLOCATION_CONFIG_PATH;
+use execution_engine::clock_sync::CLOCK_SYNC_CONFIG_PATH;
+use execution_engine::kubernetes::KUBERNETES_CONFIG_PATH;
+use execution_engine::orders::ORDER_INTENTS_PATH;
+use execution_engine::protection::{PROTECTION_CONFIG_PATH, PROTECTION_STATE_PATH};
+use execution_engine::{
+    AllocationConfig, Allocator, ClockSync, ClockSyncConfig, ExecutionAlgoConfig, ExecutionEngine,
+    IntentStore, KubernetesConfig, KubernetesDeployer, ProtectionConfig, ProtectionStore,
+};
+use log_rotation::{LoggingConfig, LOGGING_CONFIG_PATH};
+use metamorphosis_engine::compile_farm::BUILD_CONFIG_PATH;
+use metamorphosis_engine::policy::MUTATION_POLICY_PATH;
+use metamorphosis_engine::{BuildConfig, MetamorphosisEngine, MutationPolicy};
+use monitoring_service::{
+    FleetValidationConfig, FleetValidator, HttpClusterRegistry, LogShipper, LogShipperConfig,
+    MonitoringConfig, MonitoringService, FLEET_VALIDATION_CONFIG_PATH, MONITORING_CONFIG_PATH,
+};
+use perception_core::cross_exchange::CROSS_EXCHANGE_CONFIG_PATH;
+use perception_core::derivatives::DERIVATIVES_CONFIG_PATH;
+use perception_core::market_store::MARKET_STORE_CONFIG_PATH;
+use perception_core::news::NEWS_CONFIG_PATH;
+use perception_core::sampling::SAMPLING_CONFIG_PATH;
+use perception_core::universe::SYMBOL_UNIVERSE_PATH;
+use perception_core::{
+    run as run_perception_core, CrossExchangeCollector, CrossExchangeConfig, DerivativesCollector,
+    DerivativesConfig, MarketRecorder, MarketStore, MarketStoreConfig, NewsConfig, NewsPoller,
+    SamplingConfig, SymbolUniverse, UniverseConfig,
+};
+use reasoning_engine::{ReasoningEngine, SentimentAggregator};
+use recovery::{Recovery, RecoveryConfig, RECOVERY_CONFIG_PATH};
 use resource_monitor::run as run_resource_monitor;
+use shadow::{ShadowConfig, ShadowTrial, SHADOW_CONFIG_PATH};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use survival_protocol::SurvivalProtocol;
+use std::time::Instant;
+use strategy_engine::OUTPUT_FILE;
+use strategy_module::StrategySupervisor;
+use strategy_plugins::{PluginLoader, PluginLoads, PluginsConfig, PLUGINS_CONFIG_PATH};
+use survival_protocol::{
+    DeadMansSwitch, DeadMansSwitchConfig, SurvivalProtocol, DEAD_MANS_SWITCH_CONFIG_PATH,
+};
+use telemetry::{TracingConfig, TRACING_CONFIG_PATH};
+use tokio::sync::broadcast::error::RecvError;
 use tokio::{
-    sync::broadcast,
-    task::{self, JoinHandle},
+    task,
     time::{self, Duration},
 };
+use tracing::Instrument;
+
+/// Ticks remembered for decision latency; most never lead to a decision
+const MAX_PENDING_TICKS: usize = 10_000;
 
-type ModuleRunFn = unsafe extern "C" fn();
+/// Components that must be healthy for the systemd watchdog to be fed. Perception is
+/// left out on purpose: a restart does not fix an exchange outage.
+const WATCHDOG_COMPONENTS: [&str; 2] = [component::EVENT_BUS, component::STRATEGY_MODULE];
 
-struct DynamicModule {
-    task_handle: JoinHandle<()>,
+/// How often changes to `config/state.json` are written out
+const STATE_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(60);
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    // Writes the process environment, so it runs before any other thread exists
+    let unsealed = matches!(cli.command(), Command::Run).then(sealed_env::unseal);
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run(cli, unsealed))
 }
 
-impl DynamicModule {
-    fn new(lib_path: PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
-        let handle = task::spawn_blocking(move || {
-            let lib =
-                unsafe { Library::new(&lib_path).expect("Library loading failed inside thread") };
-            unsafe {
-                let run_func: Symbol<ModuleRunFn> = lib
-                    .get(b"run_strategy_engine")
-                    .expect("Symbol loading failed inside thread");
-                run_func();
+async fn run(cli: Cli, unsealed: Option<anyhow::Result<Option<usize>>>) -> anyhow::Result<()> {
+    // Spans are exported to an OTLP collector when config/tracing.json enables it
+    let tracing_config = TracingConfig::load(TRACING_CONFIG_PATH);
+    // The log file is rotated as config/logging.json says. Only the running agent
+    // writes it, so a one-off command never rotates the file under it.
+    let logging_config = LoggingConfig::load(LOGGING_CONFIG_PATH);
+    let mut logging = logging_config.as_ref().cloned().unwrap_or_default();
+    if !matches!(cli.command(), Command::Run) {
+        logging.file = None;
+    }
+    telemetry::init(
+        cli.log_format,
+        &logging,
+        tracing_config.as_ref().unwrap_or(&TracingConfig::default()),
+    );
+    if let Err(e) = &logging_config {
+        tracing::error!("Invalid logging config, using the defaults: {}", e);
+    }
+    if let Err(e) = &tracing_config {
+        tracing::error!("Invalid tracing config, not exporting traces: {}", e);
+    }
+    // SSH commands, orders, config changes and recovery actions are audited
+    match AuditLog::open(AUDIT_LOG_PATH) {
+        Ok(log) => {
+            if let Err(e) = log.verify() {
+                tracing::error!("Audit log failed verification: {}", e);
             }
-        });
-        Ok(Self {
-            task_handle: handle,
-        })
+            audit::install(log);
+        }
+        Err(e) => tracing::error!("Failed to open audit log, not auditing: {}", e),
     }
 
-    fn shutdown(&self) {
-        self.task_handle.abort();
+    // Builds, backtests and shell tasks run below the trading path's priority
+    let priority = ProcessPriority::load(PRIORITY_CONFIG_PATH).unwrap_or_else(|e| {
+        tracing::error!("Invalid process priority config, using the default: {}", e);
+        ProcessPriority::default()
+    });
+
+    // Exchange keys deployed sealed are unsealed into the environment only
+    match unsealed {
+        Some(Ok(Some(count))) => tracing::info!("Unsealed {} deployed secret(s)", count),
+        Some(Err(e)) => tracing::error!("Failed to unseal deployed secrets: {:#}", e),
+        _ => {}
     }
+
+    let result = match cli.command() {
+        Command::Run => {
+            // Every replica generates its ID on first boot or receives it from its parent
+            let identity = AgentIdentity::load_or_create(IDENTITY_PATH)?;
+            let span = tracing::info_span!(
+                "agent",
+                agent_id = %identity.agent_id,
+                generation = identity.generation
+            );
+            run_kernel(identity, priority).instrument(span).await;
+            Ok(())
+        }
+        Command::Deploy { server_id } => commands::deploy(&cli.servers_config, server_id).await,
+        Command::Status => commands::status(&cli.servers_config).await,
+        Command::Replicate => commands::replicate(&cli.servers_config).await,
+        Command::Backtest {
+            data_file,
+            symbol,
+            from,
+            to,
+        } => {
+            priority.lower_current_process();
+            commands::backtest(data_file.as_deref(), symbol, *from, *to)
+        }
+        Command::ValidateConfig => commands::validate_config(&cli.servers_config),
+        Command::CheckCredentials => commands::check_credentials().await,
+        Command::SelfTest => commands::self_test(&cli.servers_config).await,
+        Command::Logs { server_id } => commands::logs(&cli.servers_config, server_id).await,
+        Command::Exec {
+            command,
+            tag,
+            timeout,
+        } => commands::exec(&cli.servers_config, command, tag.as_deref(), *timeout).await,
+        Command::TrustHost { server_id } => {
+            commands::trust_host(&cli.servers_config, server_id).await
+        }
+        Command::Report {
+            from,
+            to,
+            period,
+            format,
+            output,
+        } => commands::report(*from, *to, *period, *format, output.as_deref()),
+        Command::StopRemote { server_id } => {
+            commands::stop_remote(&cli.servers_config, server_id).await
+        }
+        Command::Migrate { from, to, reason } => {
+            commands::migrate(&cli.servers_config, from, to, reason).await
+        }
+        Command::SignRelease {
+            binary,
+            version,
+            url,
+        } => commands::sign_release(binary, version, url),
+        Command::SignPlugin {
+            file,
+            name,
+            version,
+            url,
+        } => commands::sign_plugin(file, name, version, url.as_deref()),
+        Command::SealConfig { env_file, output } => commands::seal_config(env_file, output),
+        Command::VerifyBundle { dir, public_key } => {
+            commands::verify_bundle(dir, public_key.as_deref())
+        }
+        Command::Simulate {
+            agents,
+            servers,
+            rounds,
+            replication_config,
+        } => commands::simulate(*agents, *servers, *rounds, replication_config.as_deref()),
+    };
+    telemetry::shutdown();
+    result
 }
 
-#[tokio::main]
-async fn main() {
-    tracing_subscriber::fmt::init();
-    tracing::info!("Kernel starting...");
+async fn run_kernel(identity: AgentIdentity, priority: ProcessPriority) {
+    tracing::info!(
+        parent_id = ?identity.parent_id,
+        deployed_at = %identity.deployed_at,
+        "Kernel starting..."
+    );
+    // Market data missed before this moment is backfilled from the exchange
+    let started_ms = common::clock::exchange_now_millis();
 
-    let (tx, mut rx) = broadcast::channel::<AppEvent>(100);
+    // Telemetry fans out through the lossy per-subscriber channels; control-plane
+    // events are queued reliably and bridged onto the bus by the main loop below.
+    let (tx, mut control_rx) = EventBus::with_control_channel(1024, 64);
 
-    let initial_lib_path = PathBuf::from(if cfg!(target_os = "linux") {
-        "target/debug/libstrategy_engine.so"
-    } else if cfg!(target_os = "macos") {
-        "target/debug/libstrategy_engine.dylib"
-    } else {
-        "target/debug/strategy_engine.dll"
-    });
+    // Readiness reflects the components the agent needs to be functional
+    let health = HealthState::new();
+    for name in [
+        component::EVENT_BUS,
+        component::PERCEPTION,
+        component::STRATEGY_MODULE,
+        component::SERVER_CONFIG,
+    ] {
+        health.register(name);
+    }
+    match ServerConfig::from_file("config/target_servers.json") {
+        Ok(_) => health.set(component::SERVER_CONFIG, true, None),
+        Err(e) => health.set(component::SERVER_CONFIG, false, Some(format!("{:#}", e))),
+    }
 
-    let mut strategy_module = Some(DynamicModule::new(initial_lib_path)
-        .expect("Failed to load initial strategy engine. Please run 'cargo build -p strategy_engine' first."));
+    let initial_lib_path = strategy_module::initial_library_path();
+    // Strategies developed out of tree, from the plugin directory or a registry
+    let plugins = match PluginsConfig::load(PLUGINS_CONFIG_PATH) {
+        Ok(config) => Some(config).filter(|config| config.enabled),
+        Err(e) => {
+            tracing::error!("Invalid strategy plugin config: {}", e);
+            None
+        }
+    };
+    let plugin_dir = plugins.as_ref().map(|config| config.install_dir.clone());
+
+    // The supervisor reloads the last known good library if the module stops or hangs
+    let strategy = StrategySupervisor::new(strategy_module::HEARTBEAT_TIMEOUT);
+    #[cfg(feature = "wasm")]
+    let strategy = strategy.with_wasm(tx.clone());
+    let mut strategy = strategy;
+    // The plugin the kernel ran before it restarted takes the place of the built-in engine
+    let plugin_loaded = plugin_dir.as_deref().is_some_and(|dir| {
+        let Some(path) = PluginLoads::active(dir) else {
+            return false;
+        };
+        let outcome = strategy.load(&path).map_err(|e| e.to_string());
+        match &outcome {
+            Ok(()) => tracing::info!("Running strategy plugin {:?}", path),
+            Err(e) => tracing::error!("Failed to load strategy plugin {:?}: {}", path, e),
+        }
+        let loaded = outcome.is_ok();
+        PluginLoads::record(dir, &path, outcome);
+        loaded
+    });
+    if !plugin_loaded {
+        strategy.load(&initial_lib_path)
+            .expect("Failed to load initial strategy engine. Please run 'cargo build -p strategy_engine' first.");
+    }
+    health.set(component::STRATEGY_MODULE, true, None);
     tracing::info!("Strategy Engine (initial) started.");
 
+    // Funds, positions and engine checkpoints survive restarts in config/state.json
+    let state = StateStore::load(STATE_PATH).unwrap_or_else(|e| {
+        tracing::error!("Invalid agent state, starting from defaults: {}", e);
+        StateStore::in_memory()
+    });
+
     // --- Spawn all other modules correctly ---
+    // Outbound HTTP to exchanges and LLM providers shares one budget per endpoint class
+    let rate_limiter = match RateLimitConfig::load(RATE_LIMITS_PATH) {
+        Ok(config) => RateLimiter::new(config),
+        Err(e) => {
+            tracing::error!("Invalid rate limit config, using defaults: {}", e);
+            RateLimiter::default()
+        }
+    };
     let rm_tx = tx.clone();
-    let rm_rx = tx.subscribe();
-    task::spawn(run_resource_monitor(rm_tx, rm_rx));
+    let rm_rx = tx.subscribe_as("resource_monitor", &[Topic::System]);
+    task::spawn(run_resource_monitor(rm_tx, rm_rx).in_current_span());
     let pc_tx = tx.clone();
-    task::spawn(run_perception_core(pc_tx));
-    let mut re = ReasoningEngine::new(tx.clone(), tx.subscribe());
-    task::spawn(async move { re.run().await });
+    let pc_health = health.clone();
+    let sampling = SamplingConfig::load(SAMPLING_CONFIG_PATH).unwrap_or_else(|e| {
+        tracing::error!("Invalid market sampling config, using defaults: {}", e);
+        SamplingConfig::default()
+    });
+    // Symbols can be added and removed at runtime with SubscribeSymbol/UnsubscribeSymbol
+    let universe = UniverseConfig::load(SYMBOL_UNIVERSE_PATH).unwrap_or_else(|e| {
+        tracing::error!("Invalid symbol universe config, using defaults: {}", e);
+        UniverseConfig::default()
+    });
+    let symbols = universe.symbols.clone();
+    let pc_control = tx.subscribe_as("perception_core", &[Topic::Control]);
+    task::spawn(
+        async move {
+            let universe = SymbolUniverse::new(&universe);
+            if let Err(e) =
+                run_perception_core(pc_tx, pc_control, pc_health, sampling, universe).await
+            {
+                tracing::error!("Perception core stopped: {}", e);
+            }
+        }
+        .in_current_span(),
+    );
+    // Trades and candles are kept on disk for the backtester and indicator warm-up
+    let mut market_store = None;
+    match MarketStoreConfig::load(MARKET_STORE_CONFIG_PATH) {
+        Ok(config) if !config.enabled => {}
+        Ok(config) => match MarketStore::open(&config.path, config.candle_interval_seconds) {
+            Ok(store) => {
+                let store = Arc::new(store);
+                market_store = Some(store.clone());
+                let recorder = MarketRecorder::new(store, config).with_event_bus(tx.clone());
+                task::spawn(
+                    recorder
+                        .run(tx.subscribe_as("market_store", &[Topic::MarketTicks, Topic::System]))
+                        .in_current_span(),
+                );
+            }
+            Err(e) => tracing::error!("Market history disabled: {}", e),
+        },
+        Err(e) => tracing::error!(
+            "Invalid market store config, market history disabled: {}",
+            e
+        ),
+    }
+    // Funding, open interest and 24h ticker statistics
+    match DerivativesConfig::load(DERIVATIVES_CONFIG_PATH) {
+        Ok(config) if !config.enabled || config.symbols.is_empty() => {}
+        Ok(config) => match DerivativesCollector::new(config) {
+            Ok(collector) => {
+                let collector = collector.with_rate_limiter(rate_limiter.clone());
+                task::spawn(collector.run(tx.clone()).in_current_span());
+            }
+            Err(e) => tracing::error!("Derivatives data disabled: {}", e),
+        },
+        Err(e) => tracing::error!(
+            "Invalid derivatives config, derivatives data disabled: {}",
+            e
+        ),
+    }
+    // Spreads between exchanges quoting the same symbols
+    match CrossExchangeConfig::load(CROSS_EXCHANGE_CONFIG_PATH) {
+        Ok(config) if !config.enabled || config.venues.len() < 2 => {}
+        Ok(config) => {
+            task::spawn(
+                CrossExchangeCollector::new(config)
+                    .run(tx.clone())
+                    .in_current_span(),
+            );
+        }
+        Err(e) => tracing::error!("Invalid cross-exchange config, spreads disabled: {}", e),
+    }
+    // News articles are published for the reasoning engine to analyze
+    match NewsConfig::load(NEWS_CONFIG_PATH) {
+        Ok(config) if config.sources.is_empty() => {}
+        Ok(config) => match NewsPoller::new(config) {
+            Ok(poller) => {
+                task::spawn(poller.run(tx.clone()).in_current_span());
+            }
+            Err(e) => tracing::error!("News sources disabled: {}", e),
+        },
+        Err(e) => tracing::error!("Invalid news config, news sources disabled: {}", e),
+    }
+    let mut re = ReasoningEngine::new(
+        tx.clone(),
+        tx.subscribe_as("reasoning_engine", &[Topic::Reasoning]),
+    )
+    .with_rate_limiter(rate_limiter.clone());
+    task::spawn(async move { re.run().await }.in_current_span());
+    let mut sa = SentimentAggregator::new(
+        tx.clone(),
+        tx.subscribe_as("sentiment_aggregator", &[Topic::Reasoning]),
+    );
+    task::spawn(async move { sa.run().await }.in_current_span());
     // Note: SshDeployer is private in execution_engine, need to create mock deployer
     struct MockDeployer;
     impl execution_engine::Deployer for MockDeployer {
-        fn deploy(&self, _info: common::DeploymentInfo) -> Result<(), Box<dyn std::error::Error>> {
+        fn deploy(&self, _info: common::DeploymentInfo) -> AureliaResult<()> {
             tracing::info!("[Mock Deployer] Deployment simulated.");
             Ok(())
         }
     }
-    let mut ee = ExecutionEngine::new(tx.clone(), tx.subscribe(), Box::new(MockDeployer));
-    task::spawn(async move { ee.run().await });
-    let mut sp = SurvivalProtocol::new(tx.clone(), tx.subscribe(), 1000.0);
-    task::spawn(async move { sp.run().await });
-    let mut me = MetamorphosisEngine::new(tx.clone());
-    task::spawn(async move { me.run().await });
+    // Metrics retention is sized by the server's resource profile
+    let monitoring_config = MonitoringConfig::load(MONITORING_CONFIG_PATH).unwrap_or_else(|e| {
+        tracing::error!("Invalid monitoring config, using the defaults: {}", e);
+        MonitoringConfig::default()
+    });
+    // Servers a replica deploys to report to the same primary, and the primary's
+    // own replicas to the primary itself
+    let advertised_primary =
+        common::identity::primary_url().or_else(|| monitoring_config.advertised_url());
+    // A configured cluster takes the place of SSH targets
+    let deployer: Box<dyn execution_engine::Deployer> =
+        match KubernetesConfig::load(KUBERNETES_CONFIG_PATH) {
+            Ok(Some(config)) => {
+                let strategy =
+                    ReplicationStrategy::load(Path::new(REPLICATION_CONFIG_PATH), &identity)
+                        .unwrap_or_default();
+                let replication =
+                    serde_json::to_string_pretty(&strategy.for_replica()).unwrap_or_default();
+                let mut kubernetes = KubernetesDeployer::new(config, strategy.min_replicas as u32)
+                    .with_config_file(REPLICATION_CONFIG_PATH, replication)
+                    .with_identity(identity.spawn_child());
+                if let Some(primary) = &advertised_primary {
+                    kubernetes = kubernetes.with_primary_url(primary.clone());
+                }
+                Box::new(kubernetes)
+            }
+            Ok(None) => Box::new(MockDeployer),
+            Err(e) => {
+                tracing::error!("Invalid Kubernetes config, using mock deployer: {}", e);
+                Box::new(MockDeployer)
+            }
+        };
+    // Signed requests carry the exchange's time; the clock turns unready when it drifts
+    match ClockSyncConfig::load(CLOCK_SYNC_CONFIG_PATH) {
+        Ok(config) if !config.enabled => {}
+        Ok(config) => {
+            task::spawn(
+                ClockSync::new(config)
+                    .with_health(health.clone())
+                    .run()
+                    .in_current_span(),
+            );
+        }
+        Err(e) => tracing::error!("Invalid clock sync config: {}", e),
+    }
+    let mut ee = ExecutionEngine::new(
+        tx.clone(),
+        tx.subscribe_as(
+            "execution_engine",
+            // System for resync requests of consumers that lost events
+            &[
+                Topic::Strategy,
+                Topic::Deployment,
+                Topic::Control,
+                Topic::System,
+            ],
+        ),
+        deployer,
+    )
+    .with_rate_limiter(rate_limiter.clone());
+    // Missing, placeholder or rejected exchange keys keep trading simulated
+    let credentials = ee.verify_credentials().await;
+    // Every fill, simulated or live, is kept for reports via /api/reports/trades
+    let trade_ledger = TradeLedger::open(TRADE_LEDGER_PATH).unwrap_or_else(|e| {
+        tracing::error!("Failed to open trade ledger, keeping it in memory: {}", e);
+        TradeLedger::in_memory()
+    });
+    ee = ee.with_ledger(trade_ledger.clone());
+    // Simulated fills pay the fees, slippage and latency live orders would
+    let cost_model = CostModel::load(COST_MODEL_PATH).unwrap_or_else(|e| {
+        tracing::error!("Invalid cost model, using the defaults: {}", e);
+        CostModel::default()
+    });
+    ee = ee.with_cost_model(cost_model.clone());
+    // Each strategy trades its share of the funds; the strategy engine reads the
+    // same files. Without strategies.json, strategy.json names the one strategy
+    // to run, migrated in place if an earlier generation of the agent wrote it.
+    let strategies = StrategySet::load_or_legacy(STRATEGIES_PATH, STRATEGY_CONFIG_PATH)
+        .unwrap_or_else(|e| {
+            tracing::error!("Invalid strategies config, using the defaults: {}", e);
+            StrategySet::default()
+        });
+    tracing::info!(
+        strategies = ?strategies.enabled().map(|s| s.id.as_str()).collect::<Vec<_>>(),
+        "Loaded strategy config"
+    );
+    ee = ee.with_strategies(strategies.clone(), state.clone());
+    // Large decisions can be worked into the market as TWAP or VWAP child orders
+    match ExecutionAlgoConfig::load(EXECUTION_ALGO_CONFIG_PATH) {
+        Ok(config) => ee = ee.with_execution_algo(config),
+        Err(e) => tracing::error!("Invalid execution algorithm config, ignoring: {}", e),
+    }
+    // Scores the strategies on their fills and shifts allocations toward the best
+    match AllocationConfig::load(ALLOCATION_CONFIG_PATH) {
+        Ok(config) => {
+            task::spawn(
+                Allocator::new(
+                    tx.clone(),
+                    strategies,
+                    trade_ledger.clone(),
+                    state.clone(),
+                    config,
+                )
+                .run()
+                .in_current_span(),
+            );
+        }
+        Err(e) => tracing::error!("Invalid allocation config, allocator disabled: {}", e),
+    }
+    // Real orders are only sent when explicitly enabled, and only with keys that may trade
+    if matches!(
+        std::env::var("AURELIA_LIVE_TRADING").as_deref(),
+        Ok("1") | Ok("true")
+    ) {
+        match IntentStore::load(ORDER_INTENTS_PATH) {
+            Ok(intents) => ee = ee.with_live_trading(intents),
+            Err(e) => tracing::error!("Invalid order intents, live trading disabled: {}", e),
+        }
+    }
+    // Stop-loss and take-profit for every position, placed through the orders set up above
+    match ProtectionConfig::load(PROTECTION_CONFIG_PATH) {
+        Ok(config) if config.enabled => match ProtectionStore::load(PROTECTION_STATE_PATH) {
+            Ok(store) => {
+                ee = ee.with_protection(config, store);
+                if let Some(protection) = ee.protection() {
+                    task::spawn(protection.run().in_current_span());
+                }
+            }
+            Err(e) => tracing::error!("Invalid protected positions, protection disabled: {}", e),
+        },
+        Ok(_) => {}
+        Err(e) => tracing::error!("Invalid protection config, protection disabled: {}", e),
+    }
+    // Fills and balance changes are pushed by the exchange when an account is configured
+    // Balances in every asset are valued in one currency and reported as funds
+    let accounting = AccountingConfig::load(ACCOUNTING_CONFIG_PATH).unwrap_or_else(|e| {
+        tracing::error!("Invalid accounting config, using the defaults: {}", e);
+        AccountingConfig::default()
+    });
+    ee = ee.with_accounting(accounting);
+    if let Some(user_data) = ee.user_data_stream() {
+        task::spawn(user_data.run().in_current_span());
+    }
+    // Decisions are only acted on once the gap left by the downtime is closed
+    let recovery_config = RecoveryConfig::load(RECOVERY_CONFIG_PATH).unwrap_or_else(|e| {
+        tracing::error!("Invalid recovery config, using the defaults: {}", e);
+        RecoveryConfig::default()
+    });
+    let recovery =
+        Recovery::new(recovery_config, started_ms).with_rate_limiter(rate_limiter.clone());
+    let recovery = match market_store {
+        Some(store) => recovery.with_market_store(store, symbols),
+        None => recovery,
+    };
+    recovery.run(&mut ee, &strategy).await;
+    task::spawn(ee.accountant().run().in_current_span());
+    task::spawn(async move { ee.run().await }.in_current_span());
+    // Archived triggers, shadow leftovers and unread strategy output do not pile up
+    let artifact_max_age = LoggingConfig::load(LOGGING_CONFIG_PATH)
+        .unwrap_or_default()
+        .artifact_max_age();
+    task::spawn(
+        async move {
+            let mut interval = tokio::time::interval(log_rotation::ARTIFACT_CLEANUP_INTERVAL);
+            let mut at_startup = true;
+            loop {
+                interval.tick().await;
+                let removed = log_rotation::cleanup_artifacts(artifact_max_age, at_startup);
+                if removed > 0 {
+                    tracing::info!("Removed {} stale artifact(s)", removed);
+                }
+                at_startup = false;
+            }
+        }
+        .in_current_span(),
+    );
+    // Deployments requested by dropping a trigger file into the deployment directory
+    task::spawn(
+        deploy_trigger::run(
+            tx.clone(),
+            PathBuf::from(DEPLOY_TRIGGER_PATH),
+            PathBuf::from(TRIGGER_ARCHIVE_DIR),
+        )
+        .in_current_span(),
+    );
+    if let Some(config) = plugins {
+        task::spawn(PluginLoader::new(config).run(tx.clone()).in_current_span());
+    }
+    let mut sp = SurvivalProtocol::new(
+        tx.clone(),
+        tx.subscribe_as("survival_protocol", &[Topic::Financial, Topic::Control]),
+        state.funds(),
+    )
+    .with_state_store(state.clone());
+    let budget = sp.budget();
+    task::spawn(async move { sp.run().await }.in_current_span());
+    // Self-modification stays within config/mutation_policy.json, or the strict default
+    let mutation_policy = MutationPolicy::load(MUTATION_POLICY_PATH).unwrap_or_else(|e| {
+        tracing::error!("Invalid mutation policy, using the default: {}", e);
+        MutationPolicy::default()
+    });
+    // Mutations are built locally unless config/metamorphosis_build.json names a builder
+    let build_config = BuildConfig::load(BUILD_CONFIG_PATH).unwrap_or_else(|e| {
+        tracing::error!("Invalid build config, building locally: {}", e);
+        BuildConfig::default()
+    });
+    if build_config.enabled {
+        let mut me = MetamorphosisEngine::new(tx.clone())
+            .with_policy(mutation_policy)
+            .with_build_config(build_config)
+            .with_priority(priority.clone());
+        task::spawn(async move { me.run().await }.in_current_span());
+    } else {
+        tracing::info!("Metamorphosis disabled by {}", BUILD_CONFIG_PATH);
+    }
 
-    // --- Start Autonomous Agent ---
     let binary_path = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("./kernel"));
-    let autonomous_agent = Arc::new(AutonomousAgent::new(binary_path));
+    // Deployments are signed so that servers can check what they are about to run
+    let signer = match ReleaseSigner::load_or_create(&SecretStore::default()) {
+        Ok(signer) => {
+            tracing::info!(public_key = %signer.public_key(), "Signing deployments");
+            Some(signer)
+        }
+        Err(e) => {
+            tracing::error!(
+                "Failed to load the release signing key, deploying unsigned: {}",
+                e
+            );
+            None
+        }
+    };
+    let mut deployment_commander = DeploymentCommander::new(binary_path.clone())
+        .with_event_bus(tx.clone())
+        .with_api_port(monitoring_config.port);
+    if let Some(signer) = &signer {
+        deployment_commander = deployment_commander.with_signer(signer.clone());
+    }
+    match &advertised_primary {
+        Some(primary) => {
+            tracing::info!("Deployed agents report to {}", primary);
+            deployment_commander = deployment_commander.with_primary_address(primary.clone());
+        }
+        None => tracing::warn!("No address to advertise, deployed agents cannot report back"),
+    }
+    let deployment_commander = Arc::new(deployment_commander);
+
+    // Deployments, status checks and replica monitoring share authenticated SSH sessions
+    let ssh_pool = deployment_commander.connection_pool();
+    {
+        let ssh_pool = ssh_pool.clone();
+        task::spawn(
+            async move {
+                let mut keepalive = time::interval(ssh_pool.keepalive_interval());
+                loop {
+                    keepalive.tick().await;
+                    let pool = ssh_pool.clone();
+                    let _ = task::spawn_blocking(move || pool.send_keepalives()).await;
+                }
+            }
+            .in_current_span(),
+        );
+    }
+
+    // Every autonomous decision is journaled for auditing via /api/decisions
+    let decision_journal = DecisionJournal::open(DECISION_JOURNAL_PATH).unwrap_or_else(|e| {
+        tracing::error!(
+            "Failed to open decision journal, keeping it in memory: {}",
+            e
+        );
+        DecisionJournal::in_memory()
+    });
+
+    // Operators who want a say approve deployments, scaling and shutdowns. The
+    // gate exists with approvals off too: its token guards the operator endpoints.
+    let approvals = ApprovalGate::new(match ApprovalConfig::load(APPROVALS_CONFIG_PATH) {
+        Ok(config) => config,
+        Err(e) => {
+            // An unreadable config must not quietly grant full autonomy
+            tracing::error!(
+                "Invalid approvals config, requiring approval for every action: {}",
+                e
+            );
+            ApprovalConfig {
+                enabled: true,
+                ..Default::default()
+            }
+        }
+    });
+    let approvals = match load_or_create_token(&SecretStore::default()) {
+        Ok(token) => approvals.with_token(token),
+        Err(e) => {
+            tracing::error!(
+                "No approval token, requests can only time out and guarded endpoints refuse every call: {}",
+                e
+            );
+            approvals
+        }
+    };
+
+    // Selected events go out to an external broker, allowed commands carrying the
+    // approval token come back in
+    #[cfg(any(feature = "nats", feature = "mqtt"))]
+    match event_bridge::EventBridgeConfig::load(event_bridge::EVENT_BRIDGE_CONFIG_PATH) {
+        Ok(config) if !config.enabled => {}
+        Ok(config) => {
+            let bridge = event_bridge::EventBridge::new(config, &identity.agent_id, tx.clone())
+                .with_approval_gate(approvals.clone());
+            task::spawn(bridge.run().in_current_span());
+        }
+        Err(e) => tracing::error!("Invalid event bridge config: {}", e),
+    }
+
+    // --- Start Monitoring Service ---
+    let mut monitoring_service = MonitoringService::new(monitoring_config)
+        .with_deployment_commander(deployment_commander.clone())
+        .with_health(health.clone())
+        .with_identity(identity.clone())
+        .with_decision_journal(decision_journal.clone())
+        .with_event_bus(tx.clone())
+        .with_rate_limiter(rate_limiter.clone())
+        .with_trade_ledger(trade_ledger.clone())
+        .with_state_store(state.clone())
+        .with_cost_model(cost_model)
+        .with_credential_report(credentials)
+        .with_budget(budget.clone())
+        .with_approval_gate(approvals.clone());
+    // Replicas ship their logs with a token derived from the fleet key. Only
+    // the primary generates one; a replica must be given the fleet's key.
+    let fleet_key = if identity.is_primary() && common::identity::primary_url().is_none() {
+        FleetKey::load_or_create(&SecretStore::default()).map(Some)
+    } else {
+        FleetKey::load(&SecretStore::default())
+    };
+    match fleet_key {
+        Ok(Some(key)) => monitoring_service = monitoring_service.with_fleet_key(key),
+        Ok(None) => tracing::warn!("No fleet key, replica logs will be refused"),
+        Err(e) => tracing::error!("No fleet key, replica logs will be refused: {}", e),
+    }
+    let monitoring_service = Arc::new(monitoring_service);
+
+    // --- Start Autonomous Agent ---
+    // The fleet size cap is enforced against the primary's view of the cluster
+    let registry: Option<Arc<dyn ClusterRegistry>> = match common::identity::primary_url() {
+        Some(url) => Some(Arc::new(HttpClusterRegistry::new(url))),
+        None => monitoring_service
+            .get_http_service()
+            .map(|http| Arc::new(http.clone()) as Arc<dyn ClusterRegistry>),
+    };
+    let replication = ReplicationStrategy::load(Path::new(REPLICATION_CONFIG_PATH), &identity)
+        .unwrap_or_else(|e| {
+            tracing::error!("Invalid replication config, replication disabled: {}", e);
+            ReplicationStrategy::default().for_replica()
+        });
+    tracing::info!(
+        enabled = replication.enabled,
+        max_generation = replication.max_generation,
+        max_fleet_size = replication.max_fleet_size,
+        "Replication guardrails loaded"
+    );
+    // Retry backoff and quarantined servers survive restarts
+    let deployment_queue =
+        DeploymentQueue::with_persistence(DEPLOYMENT_QUEUE_PATH).unwrap_or_else(|e| {
+            tracing::error!("Failed to restore deployment queue, starting empty: {}", e);
+            DeploymentQueue::new()
+        });
+    let mut replicator = SelfReplicator::new(binary_path.clone())
+        .with_identity(identity.clone())
+        .with_strategy(replication)
+        .with_lineage_file(LINEAGE_PATH)
+        .with_deployment_queue(deployment_queue)
+        .with_budget(budget)
+        .with_connection_pool(ssh_pool)
+        .with_event_bus(tx.clone());
+    if let Some(registry) = registry {
+        replicator = replicator.with_cluster_registry(registry);
+    }
+    if let Some(signer) = signer {
+        replicator = replicator.with_signer(signer);
+    }
+    // Buying servers is opt-in through config/cloud.json
+    match CloudConfig::load(CLOUD_CONFIG_PATH).and_then(|config| {
+        config
+            .map(|config| {
+                let provisioner = config.build_provisioner()?;
+                CloudFleet::new(provisioner, config).with_persistence(CLOUD_SERVERS_PATH)
+            })
+            .transpose()
+    }) {
+        Ok(Some(cloud)) => replicator = replicator.with_cloud_fleet(Arc::new(cloud)),
+        Ok(None) => {}
+        Err(e) => tracing::error!("Cloud provisioning disabled: {}", e),
+    }
+    // The decision policy can later be switched with AppEvent::SetDecisionPolicy
+    let autonomy_config = AutonomyConfig::load(AUTONOMY_CONFIG_PATH).unwrap_or_else(|e| {
+        tracing::error!("Invalid autonomy config, using defaults: {}", e);
+        AutonomyConfig::default()
+    });
+    tracing::info!(policy = ?autonomy_config.decision_policy, "Decision policy loaded");
+    // Scheduled tasks survive restarts
+    let task_scheduler = TaskScheduler::with_persistence(TASK_QUEUE_PATH).unwrap_or_else(|e| {
+        tracing::error!("Failed to restore task queue, starting empty: {}", e);
+        TaskScheduler::new()
+    });
+    let health_thresholds = HealthThresholds::load(HEALTH_CONFIG_PATH).unwrap_or_else(|e| {
+        tracing::error!("Invalid health config, using default thresholds: {}", e);
+        HealthThresholds::default()
+    });
+    let autonomous_agent = AutonomousAgent::with_replicator(replicator)
+        .with_health_thresholds(health_thresholds)
+        .with_task_scheduler(task_scheduler)
+        .with_decision_journal(decision_journal)
+        .with_sentiment_feed(tx.subscribe_as("autonomous_agent", &[Topic::Market]))
+        .with_decision_policy(build_policy(autonomy_config.decision_policy, &tx))
+        .with_migrator(AgentMigrator::new(deployment_commander).with_event_bus(tx.clone()))
+        .with_event_bus(tx.clone())
+        .with_approval_gate(approvals);
+    let autonomous_agent = Arc::new(autonomous_agent);
+
+    if let Some(http_service) = monitoring_service.get_http_service() {
+        http_service
+            .attach_replicator(autonomous_agent.self_replicator())
+            .await;
+    }
 
     // Initialize the autonomous agent
     if let Err(e) = autonomous_agent.initialize().await {
         tracing::error!("Failed to initialize autonomous agent: {}", e);
     }
+    for executor in &autonomy_config.task_executors {
+        match executor.build(&priority) {
+            Ok(built) => {
+                autonomous_agent
+                    .register_custom_executor(&executor.name, built)
+                    .await
+            }
+            Err(e) => tracing::error!("Invalid task executor {}: {}", executor.name, e),
+        }
+    }
 
     // Start autonomous operations
     let _agent_handle = {
         let agent = autonomous_agent.clone();
-        task::spawn(async move {
-            if let Err(e) = agent.run().await {
-                tracing::error!("Autonomous agent error: {}", e);
+        task::spawn(
+            async move {
+                if let Err(e) = agent.run().await {
+                    tracing::error!("Autonomous agent error: {}", e);
+                }
             }
-        })
+            .in_current_span(),
+        )
     };
 
-    // --- Start Monitoring Service ---
-    let monitoring_config = MonitoringConfig {
-        port: 8080,
-        use_http: true,
-    };
-    let monitoring_service = Arc::new(MonitoringService::new(monitoring_config));
+    // Escalate when the engines go quiet: alert the operator, hand over to a
+    // replica and exit for the service manager to restart the kernel
+    match DeadMansSwitchConfig::load(DEAD_MANS_SWITCH_CONFIG_PATH) {
+        Ok(config) if !config.enabled => {}
+        Ok(config) => {
+            let replicator = autonomous_agent.self_replicator();
+            let switch = DeadMansSwitch::new(
+                config,
+                tx.subscribe_as("dead_mans_switch", &[Topic::System, Topic::Market]),
+            )
+            .with_health(health.clone())
+            .with_identity(identity.clone())
+            .with_state_store(state.clone())
+            .with_emergency_replication(move || {
+                let replicator = replicator.clone();
+                async move {
+                    replicator
+                        .trigger_emergency_replication()
+                        .await
+                        .map_err(|e| e.to_string())
+                }
+            });
+            task::spawn(switch.run().in_current_span());
+        }
+        Err(e) => tracing::error!("Invalid dead man's switch config: {}", e),
+    }
 
     // 启动监控服务
     let _monitoring_handle = {
         let service = monitoring_service.clone();
-        task::spawn(async move {
-            tracing::info!("Starting Rust monitoring HTTP API on port 8080");
-            if let Err(e) = service.start().await {
-                tracing::error!("Monitoring service error: {}", e);
+        task::spawn(
+            async move {
+                tracing::info!("Starting Rust monitoring HTTP API on port 8080");
+                if let Err(e) = service.start().await {
+                    tracing::error!("Monitoring service error: {}", e);
+                }
             }
-        })
+            .in_current_span(),
+        )
     };
 
+    // Replicas forward their log to the primary's monitoring API
+    if let Some(config) = LogShipperConfig::from_env(identity.clone()) {
+        let mut shipper = LogShipper::new(config);
+        if let Some(http_service) = monitoring_service.get_http_service() {
+            shipper = shipper.with_trading_status(http_service.trading_status.clone());
+        }
+        task::spawn(shipper.run().in_current_span());
+    }
+
+    // The primary periodically validates every replica reporting to it
+    if identity.is_primary() {
+        if let Some(http_service) = monitoring_service.get_http_service() {
+            let config =
+                FleetValidationConfig::load(FLEET_VALIDATION_CONFIG_PATH).unwrap_or_else(|e| {
+                    tracing::error!("Invalid fleet validation config, using defaults: {}", e);
+                    FleetValidationConfig::default()
+                });
+            task::spawn(
+                FleetValidator::new(http_service.clone(), tx.clone(), config)
+                    .run()
+                    .in_current_span(),
+            );
+        }
+    }
+
     // 订阅事件并更新监控数据
-    let _monitoring_tx = tx.clone();
-    let mut monitoring_rx = tx.subscribe();
+    let monitoring_tx = tx.clone();
+    let mut monitoring_rx = tx.subscribe_as(
+        "monitoring",
+        &[
+            Topic::Market,
+            Topic::Strategy,
+            Topic::Financial,
+            Topic::System,
+            Topic::Deployment,
+        ],
+    );
     let monitoring_service_clone = monitoring_service.clone();
-    task::spawn(async move {
-        while let Ok(event) = monitoring_rx.recv().await {
-            if let Some(http_service) = monitoring_service_clone.get_http_service() {
-                match &event {
-                    AppEvent::MarketData(data) => {
-                        http_service
-                            .update_trading_status(
-                                true,
-                                Some(data.symbol.clone()),
-                                Some(data.price),
-                            )
-                            .await;
+    task::spawn(
+        async move {
+            // Exchange timestamps of ticks still waiting for their decision
+            let mut pending_ticks: HashMap<common::CorrelationId, i64> = HashMap::new();
+            let lag = LagHandler::new("monitoring").with_event_bus(monitoring_tx);
+            loop {
+                let event = match monitoring_rx.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(n)) => {
+                        lag.lagged(n);
+                        continue;
                     }
-                    AppEvent::StrategyDecision(
-                        common::StrategyDecision::Buy(_, _) | common::StrategyDecision::Sell(_, _),
-                    ) => {
-                        http_service.record_trade(true).await;
+                    Err(RecvError::Closed) => break,
+                };
+                if let Some(http_service) = monitoring_service_clone.get_http_service() {
+                    if let AppEvent::StrategyDecision(_, meta) = &event {
+                        if let Some(tick_ms) = pending_ticks.remove(&meta.correlation_id) {
+                            let latency_ms =
+                                (chrono::Utc::now().timestamp_millis() - tick_ms).max(0);
+                            http_service
+                                .record_decision_latency(latency_ms as f64)
+                                .await;
+                        }
                     }
-                    AppEvent::StrategyDecision(_) => {}
-                    AppEvent::FinancialUpdate(pnl) => {
-                        http_service.update_pnl(*pnl).await;
+                    match &event {
+                        AppEvent::MarketData(data) => {
+                            if pending_ticks.len() >= MAX_PENDING_TICKS {
+                                pending_ticks.clear();
+                            }
+                            pending_ticks
+                                .insert(data.meta.correlation_id.clone(), data.timestamp as i64);
+                            http_service
+                                .update_trading_status(
+                                    true,
+                                    Some(data.symbol.clone()),
+                                    Some(data.price),
+                                )
+                                .await;
+                        }
+                        AppEvent::StrategyDecision(
+                            common::StrategyDecision::Buy(_, _)
+                            | common::StrategyDecision::Sell(_, _),
+                            meta,
+                        ) => {
+                            tracing::info!(
+                                correlation_id = %meta.correlation_id,
+                                "Monitoring recorded trade decision"
+                            );
+                            http_service.record_trade(true).await;
+                        }
+                        AppEvent::StrategyDecision(..) => {}
+                        AppEvent::FinancialUpdate(pnl) => {
+                            http_service.update_pnl(*pnl).await;
+                        }
+                        AppEvent::NetAssetValue(nav) => {
+                            http_service.update_nav(*nav.clone()).await;
+                        }
+                        AppEvent::StrategyPerformance(report) => {
+                            http_service
+                                .record_strategy_performance(report.clone())
+                                .await;
+                        }
+                        AppEvent::SchedulerStatus(_)
+                        | AppEvent::RecoveryStats(_)
+                        | AppEvent::HealthSummary(_)
+                        | AppEvent::CircuitOpen(_)
+                        | AppEvent::CircuitClosed(_)
+                        | AppEvent::ConsumerLagged(_) => {
+                            http_service.record_subsystem_status(&event).await;
+                        }
+                        AppEvent::MigrationProgress(status) => {
+                            http_service.record_migration(*status.clone()).await;
+                        }
+                        _ => {}
                     }
-                    _ => {}
                 }
             }
         }
-    });
+        .in_current_span(),
+    );
 
     tracing::info!("📊 Rust Monitoring API available at: http://localhost:8080");
     tracing::info!("📊 API Endpoints:");
     tracing::info!("   - http://localhost:8080/api/status");
     tracing::info!("   - http://localhost:8080/api/agents");
+    tracing::info!("   - http://localhost:8080/api/agents/{{id}}/logs?since=");
     tracing::info!("   - http://localhost:8080/api/cluster/status");
     tracing::info!("   - http://localhost:8080/api/metrics");
     tracing::info!("   - http://localhost:8080/api/trading");
+    tracing::info!("   - http://localhost:8080/api/pipeline");
+    tracing::info!("   - http://localhost:8080/dashboard");
+    tracing::info!("   - http://localhost:8080/api/fleet/validation");
+    tracing::info!("   - http://localhost:8080/api/deployments");
+    tracing::info!("   - http://localhost:8080/api/replication");
+    tracing::info!("   - http://localhost:8080/api/migrations");
+    tracing::info!("   - PATCH http://localhost:8080/api/replication/strategy");
+    tracing::info!("   - PATCH http://localhost:8080/api/health/thresholds");
+    tracing::info!("   - http://localhost:8080/api/config");
+    tracing::info!("   - http://localhost:8080/api/fleet/config");
+    tracing::info!("   - http://localhost:8080/api/fleet/config/rollout");
+    tracing::info!("   - http://localhost:8080/api/decisions?since=");
+    tracing::info!("   - http://localhost:8080/api/subsystems");
+    tracing::info!("   - http://localhost:8080/api/servers/{{server_id}}/logs/stream");
     tracing::info!("   - http://localhost:8080/health");
+    tracing::info!("   - http://localhost:8080/live");
+    tracing::info!("   - http://localhost:8080/ready");
+
+    // Evolved strategies are shadow-run against the live one before they replace it
+    let shadow_config = ShadowConfig::load(SHADOW_CONFIG_PATH).unwrap_or_else(|e| {
+        tracing::error!("Invalid shadow config, using defaults: {}", e);
+        ShadowConfig::default()
+    });
+    let mut shadow: Option<ShadowTrial> = None;
+    let mut strategy_rx = tx.subscribe_as("kernel", &[Topic::Market, Topic::Strategy]);
+    let strategy_lag = LagHandler::new("kernel").with_event_bus(tx.clone());
+
+    // Confirm or roll back an update in progress, then watch for new releases
+    let (staged_tx, mut staged_rx) = tokio::sync::mpsc::channel(1);
+    let self_updater = match SelfUpdateConfig::load(SELF_UPDATE_CONFIG_PATH) {
+        Ok(config) if !config.enabled => None,
+        Ok(config) => {
+            let updater = SelfUpdater::new(config, binary_path).with_state_store(state.clone());
+            {
+                let updater = updater.clone();
+                let health = health.clone();
+                task::spawn(
+                    async move {
+                        if let Err(e) = updater.confirm(&health, &WATCHDOG_COMPONENTS).await {
+                            tracing::error!("Failed to confirm kernel update: {:#}", e);
+                        }
+                    }
+                    .in_current_span(),
+                );
+            }
+            task::spawn(updater.clone().run(staged_tx).in_current_span());
+            Some(updater)
+        }
+        Err(e) => {
+            tracing::error!("Invalid self-update config: {}", e);
+            None
+        }
+    };
 
     // --- Kernel Main Loop (Corrected with select!) ---
     let mut file_reader_interval = time::interval(Duration::from_secs(1));
+    let mut state_interval = time::interval(STATE_SNAPSHOT_INTERVAL);
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    let notifier = systemd::Notifier::from_env();
+    let watchdog_interval = systemd::watchdog_interval();
+    let mut last_watchdog = Instant::now();
+    // Set by an emergency flatten until trading is resumed
+    let mut trading_suspended = false;
+    if let Some(notifier) = &notifier {
+        if let Err(e) = notifier.notify("READY=1") {
+            tracing::warn!("Failed to notify systemd: {}", e);
+        }
+    }
+
     loop {
         tokio::select! {
-            // Branch 1: Handle internal events
-            Ok(event) = rx.recv() => {
-                match event {
+            // Branch 1: Handle control-plane events and bridge them onto the bus
+            Some(event) = control_rx.recv() => {
+                match &event {
                     AppEvent::ModuleReadyForHotSwap(lib_path_str) => {
                         tracing::warn!("Hot-swap event received for: {}", lib_path_str);
-                        if let Some(old_module) = strategy_module.take() {
-                            old_module.shutdown();
-                        }
-
-                        match DynamicModule::new(PathBuf::from(lib_path_str)) {
-                            Ok(new_module) => {
-                                strategy_module = Some(new_module);
+                        let outcome = strategy.load(Path::new(lib_path_str)).map_err(|e| e.to_string());
+                        match &outcome {
+                            Ok(()) => {
+                                health.set(component::STRATEGY_MODULE, true, None);
                                 tracing::info!("New strategy engine started with updated code.");
                             }
-                            Err(e) => tracing::error!("Failed to load new dynamic module: {}", e),
+                            Err(e) => {
+                                health.set(component::STRATEGY_MODULE, false, Some(e.clone()));
+                                tracing::error!("Failed to load new dynamic module: {}", e);
+                            }
+                        }
+                        if let Some(dir) = &plugin_dir {
+                            PluginLoads::record(dir, Path::new(lib_path_str), outcome);
+                        }
+                    }
+                    AppEvent::SetDecisionPolicy(kind) => {
+                        tracing::info!(policy = ?kind, "Switching decision policy");
+                        autonomous_agent
+                            .set_decision_policy(build_policy(*kind, &tx))
+                            .await;
+                    }
+                    AppEvent::CandidateModuleReady(path) => {
+                        if let Some(trial) = &shadow {
+                            tracing::warn!(
+                                "Shadow trial of {:?} still running, ignoring candidate {}",
+                                trial.candidate(),
+                                path
+                            );
+                        } else {
+                            match strategy.spawn_candidate(Path::new(path)) {
+                                Ok(candidate) => {
+                                    tracing::info!(
+                                        window_seconds = shadow_config.window_seconds,
+                                        "Shadow-running strategy candidate {}",
+                                        path
+                                    );
+                                    shadow = Some(ShadowTrial::start(candidate, shadow_config.clone()));
+                                }
+                                Err(e) => {
+                                    tracing::error!("Failed to start strategy candidate: {}", e);
+                                    if let Some(dir) = &plugin_dir {
+                                        PluginLoads::record(dir, Path::new(path), Err(e.to_string()));
+                                    }
+                                }
+                            }
                         }
                     }
+                    AppEvent::StrategyParamUpdate(update) => {
+                        match strategy.set_param(update) {
+                            Ok(()) => tracing::info!(
+                                param = %update.name,
+                                value = update.value,
+                                "Strategy parameter updated"
+                            ),
+                            Err(e) => tracing::error!("Strategy parameter update failed: {}", e),
+                        }
+                    }
+                    AppEvent::ReloadConfig => {
+                        reload_strategy_params(&strategy);
+                        reload_autonomy_config(&autonomous_agent, &identity).await;
+                    }
+                    AppEvent::EmergencyFlatten(reason) => {
+                        tracing::error!("Emergency flatten: {}; suspending strategy decisions", reason);
+                        trading_suspended = true;
+                    }
+                    AppEvent::PauseTrading(reason) => {
+                        tracing::warn!("Trading paused: {}; suspending strategy decisions", reason);
+                        trading_suspended = true;
+                    }
+                    AppEvent::ResumeTrading => {
+                        tracing::warn!("Resuming strategy decisions");
+                        trading_suspended = false;
+                    }
                     _ => {
                         tracing::debug!(?event, "Kernel observed internal event");
                     }
                 }
+                if tx.publish(event).is_err() {
+                    tracing::debug!("No subscribers for control-plane event");
+                }
             }
 
-            // Branch 2: Poll for external events from the dynamic module
+            // Branch 2: Feed market data, candles, sentiment and regimes to the strategy module and any shadow trial
+            event = strategy_rx.recv() => match event {
+                Ok(event) => {
+                    if matches!(
+                        event,
+                        AppEvent::MarketData(_)
+                            | AppEvent::Candle(_)
+                            | AppEvent::SentimentUpdate(_)
+                            | AppEvent::MarketConditions(_)
+                    ) {
+                        strategy.deliver(&event);
+                    }
+                    if let Some(trial) = shadow.as_mut() {
+                        trial.observe(&event);
+                    }
+                }
+                Err(RecvError::Lagged(n)) => {
+                    strategy_lag.lagged(n);
+                }
+                // The kernel holds a sender, so the bus is never closed here
+                Err(RecvError::Closed) => {}
+            },
+
+            // Branch 3: Poll for external events from the dynamic module
             _ = file_reader_interval.tick() => {
-                if let Ok(file) = File::open("strategy_output.log") {
+                health.heartbeat();
+                health.set(component::EVENT_BUS, tx.receiver_count() > 0, None);
+                if let Some((failure, recovery)) = strategy.supervise() {
+                    tracing::error!("{}, reloading last known good library", failure);
+                    match recovery {
+                        Ok(path) => {
+                            health.set(component::STRATEGY_MODULE, true, None);
+                            tracing::warn!("Strategy module restarted from {:?}", path);
+                        }
+                        Err(e) => {
+                            health.set(component::STRATEGY_MODULE, false, Some(e.to_string()));
+                            tracing::error!("Failed to restart strategy module: {}", e);
+                        }
+                    }
+                }
+                if let Some(trial) = shadow.as_mut() {
+                    trial.poll();
+                }
+                if shadow.as_ref().is_some_and(ShadowTrial::is_over) {
+                    let trial = shadow.take().expect("trial checked above");
+                    let source = trial.candidate().to_path_buf();
+                    let (mut report, candidate) = trial.finish();
+                    if let Some(candidate) = candidate {
+                        match strategy.promote(candidate) {
+                            Ok(path) => {
+                                health.set(component::STRATEGY_MODULE, true, None);
+                                tracing::warn!("Promoted strategy candidate, now running {:?}", path);
+                            }
+                            Err(e) => {
+                                tracing::error!("Failed to promote strategy candidate: {}", e);
+                                report.promoted = false;
+                                report.reason = format!("promotion failed: {}", e);
+                            }
+                        }
+                    } else {
+                        tracing::info!("Strategy candidate rejected: {}", report.reason);
+                    }
+                    if let Some(dir) = &plugin_dir {
+                        let outcome = if report.promoted {
+                            Ok(())
+                        } else {
+                            Err(report.reason.clone())
+                        };
+                        PluginLoads::record(dir, &source, outcome);
+                    }
+                    if tx.send(AppEvent::ShadowTrialCompleted(report)).is_err() {
+                        tracing::debug!("No subscribers for shadow trial report");
+                    }
+                }
+                if let (Some(notifier), Some(interval)) = (&notifier, watchdog_interval) {
+                    if last_watchdog.elapsed() >= interval
+                        && health.components_ready(&WATCHDOG_COMPONENTS)
+                    {
+                        let _ = notifier.notify("WATCHDOG=1");
+                        last_watchdog = Instant::now();
+                    }
+                }
+                if let Ok(file) = File::open(OUTPUT_FILE) {
                     let reader = BufReader::new(file);
                     for line in reader.lines().map_while(Result::ok) {
-                        if let Ok(event) = serde_json::from_str::<AppEvent>(&line) {
-                            tracing::info!(?event, "Kernel received event from dynamic module.");
-                            if tx.send(event).is_err() {
-                                tracing::error!("Failed to broadcast event from dynamic module: No active receivers.");
+                        let event = match event_schema::decode(&line) {
+                            Ok(event) => event,
+                            Err(e) => {
+                                tracing::warn!("Dropped event from dynamic module: {}", e);
+                                continue;
                             }
+                        };
+                        if trading_suspended && matches!(event, AppEvent::StrategyDecision(..)) {
+                            tracing::warn!(?event, "Trading suspended, dropping decision from dynamic module.");
+                            continue;
+                        }
+                        tracing::info!(?event, "Kernel received event from dynamic module.");
+                        if tx.send(event).is_err() {
+                            tracing::error!("Failed to broadcast event from dynamic module: No active receivers.");
                         }
                     }
                     // Clear the file after processing to avoid reprocessing events
-                    let _ = std::fs::remove_file("strategy_output.log");
+                    let _ = std::fs::remove_file(OUTPUT_FILE);
+                }
+            }
+
+            // Branch 4: Persist the agent state
+            _ = state_interval.tick() => {
+                if let Err(e) = state.save() {
+                    tracing::error!("Failed to save agent state: {}", e);
+                }
+            }
+
+            // Branch 5: Restart into a verified release
+            Some(update) = staged_rx.recv() => {
+                if let Some(updater) = &self_updater {
+                    if let Some(notifier) = &notifier {
+                        let _ = notifier.notify("RELOADING=1");
+                    }
+                    // Only returns if the new binary could not be exec'd
+                    let Err(e) = updater.activate(update);
+                    tracing::error!("Kernel self-update failed: {:#}", e);
+                }
+            }
+
+            // Branch 6: Save the state one last time and stop
+            _ = &mut shutdown => {
+                tracing::info!("Shutdown requested, saving agent state");
+                if let Some(notifier) = &notifier {
+                    let _ = notifier.notify("STOPPING=1");
+                }
+                if let Err(e) = state.save() {
+                    tracing::error!("Failed to save agent state: {}", e);
+                }
+                break;
+            }
+        }
+    }
+}
+
+/// Resolves on Ctrl-C, or on SIGTERM as sent by systemd and Kubernetes.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
                 }
             }
+            Err(e) => {
+                tracing::warn!("Failed to listen for SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Apply edited replication and health config; invalid files keep the current values
+async fn reload_autonomy_config(agent: &AutonomousAgent, identity: &AgentIdentity) {
+    match ReplicationStrategy::load(Path::new(REPLICATION_CONFIG_PATH), identity) {
+        Ok(strategy) => agent.set_replication_strategy(strategy),
+        Err(e) => tracing::error!("Config reload kept the replication strategy: {}", e),
+    }
+    match HealthThresholds::load(HEALTH_CONFIG_PATH) {
+        Ok(thresholds) => agent.set_health_thresholds(thresholds).await,
+        Err(e) => tracing::error!("Config reload kept the health thresholds: {}", e),
+    }
+}
+
+/// Re-apply the stored strategy parameters after a config push replaced them
+fn reload_strategy_params(strategy: &StrategySupervisor) {
+    let path = Path::new(strategy_engine::params::PARAMS_PATH);
+    let values: std::collections::BTreeMap<String, f64> = match std::fs::read_to_string(path)
+        .map_err(AureliaError::from)
+        .and_then(|content| Ok(serde_json::from_str(&content)?))
+    {
+        Ok(values) => values,
+        Err(e) => {
+            tracing::error!("Config reload failed to read {:?}: {}", path, e);
+            return;
+        }
+    };
+
+    for (name, value) in values {
+        let update = StrategyParamUpdate { name, value };
+        match strategy.set_param(&update) {
+            Ok(()) => tracing::info!(param = %update.name, value, "Strategy parameter reloaded"),
+            Err(e) => tracing::error!("Strategy parameter reload failed: {}", e),
         }
     }
 }
@@ -0,0 +1,234 @@
+//! Catching up after a restart or an outage.
+//!
+//! Before the kernel acts on decisions again it fills the gap in the market
+//! history with candles fetched from the exchange, reconciles the account's
+//! orders and balances with the local portfolio, and replays the recent
+//! candles into the native strategy module so its indicators start warm.
+//! Decisions the module wrote while being replayed to are discarded; live
+//! decisions start flowing once the execution engine and the main loop run.
+//!
+//! The decision journal is not replayed: order intents, protective orders and
+//! `config/state.json` are restored from their own stores as they open, and
+//! candles are all the strategy module keeps state from.
+
+use crate::strategy_module::StrategySupervisor;
+use crate::systemd::Notifier;
+use common::audit::{self, AuditCategory};
+use common::{clock, AppEvent, AureliaResult, RateLimiter};
+use execution_engine::ExecutionEngine;
+use perception_core::candles::CANDLE_INTERVAL_SECONDS;
+use perception_core::{CandleBackfill, MarketStore};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use strategy_engine::OUTPUT_FILE;
+use tokio::time;
+
+pub const RECOVERY_CONFIG_PATH: &str = "config/recovery.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecoveryConfig {
+    pub enabled: bool,
+    /// Candles missed longer ago than this are not fetched
+    pub max_backfill_hours: u64,
+    /// Most recent candles replayed into the strategy module per symbol
+    pub replay_candles: usize,
+    /// Limit on the backfill and on the reconciliation, each
+    pub timeout_seconds: u64,
+}
+
+impl Default for RecoveryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_backfill_hours: 24,
+            replay_candles: 200,
+            timeout_seconds: 60,
+        }
+    }
+}
+
+impl RecoveryConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_seconds)
+    }
+}
+
+/// Head room on top of a phase's own limit before systemd gives up on the start
+const START_TIMEOUT_MARGIN_SECS: u64 = 30;
+
+/// What a recovery did, as recorded in the audit log.
+#[derive(Debug, Default, Serialize)]
+pub struct RecoveryReport {
+    pub backfilled: usize,
+    pub reconciled: bool,
+    pub replayed: usize,
+}
+
+pub struct Recovery {
+    config: RecoveryConfig,
+    /// When the kernel started, in exchange milliseconds; the gap ends here
+    started_ms: u64,
+    store: Option<Arc<MarketStore>>,
+    symbols: Vec<String>,
+    limiter: RateLimiter,
+    /// Recovery runs before `READY=1`; systemd's start timeout is pushed out per phase
+    notifier: Option<Notifier>,
+}
+
+impl Recovery {
+    pub fn new(config: RecoveryConfig, started_ms: u64) -> Self {
+        Self {
+            config,
+            started_ms,
+            store: None,
+            symbols: Vec::new(),
+            limiter: RateLimiter::default(),
+            notifier: Notifier::from_env(),
+        }
+    }
+
+    /// Backfill and replay the candles of `symbols` kept in `store`
+    pub fn with_market_store(mut self, store: Arc<MarketStore>, symbols: Vec<String>) -> Self {
+        self.store = Some(store);
+        self.symbols = symbols;
+        self
+    }
+
+    /// Draw the backfill's requests from a budget shared with the rest of the agent
+    pub fn with_rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    pub async fn run(
+        &self,
+        ee: &mut ExecutionEngine,
+        strategy: &StrategySupervisor,
+    ) -> RecoveryReport {
+        let mut report = RecoveryReport::default();
+        if !self.config.enabled {
+            return report;
+        }
+        tracing::info!("Recovering from downtime before trading...");
+
+        if let Some(store) = &self.store {
+            self.extend_start_timeout();
+            match time::timeout(self.config.timeout(), self.backfill(store)).await {
+                Ok(added) => report.backfilled = added,
+                Err(_) => tracing::warn!(
+                    "Candle backfill timed out after {}s, history may have gaps",
+                    self.config.timeout_seconds
+                ),
+            }
+        }
+
+        // Without it the execution engine still reconciles its orders as it starts
+        self.extend_start_timeout();
+        match time::timeout(self.config.timeout(), ee.reconcile_account()).await {
+            Ok(Ok(())) => report.reconciled = true,
+            Ok(Err(e)) => tracing::error!("Account reconciliation failed: {}", e),
+            Err(_) => tracing::error!(
+                "Account reconciliation timed out after {}s",
+                self.config.timeout_seconds
+            ),
+        }
+
+        if let Some(store) = &self.store {
+            self.extend_start_timeout();
+            report.replayed = self.replay(store, strategy);
+        }
+        // Anything decided on replayed history is not a live decision
+        let _ = std::fs::remove_file(OUTPUT_FILE);
+
+        tracing::info!(
+            backfilled = report.backfilled,
+            reconciled = report.reconciled,
+            replayed = report.replayed,
+            "Recovery complete, live decisions enabled"
+        );
+        audit::record(AuditCategory::RecoveryAction, "startup_recovery", &report);
+        report
+    }
+
+    /// Give the next phase its full limit, whatever the unit's `TimeoutStartSec`
+    fn extend_start_timeout(&self) {
+        if let Some(notifier) = &self.notifier {
+            let usec = (self.config.timeout_seconds + START_TIMEOUT_MARGIN_SECS) * 1_000_000;
+            if let Err(e) = notifier.notify(&format!("EXTEND_TIMEOUT_USEC={}", usec)) {
+                tracing::warn!("Failed to extend the systemd start timeout: {}", e);
+            }
+        }
+    }
+
+    async fn backfill(&self, store: &MarketStore) -> usize {
+        let backfill =
+            match CandleBackfill::new(store.candle_interval_seconds(), self.limiter.clone()) {
+                Ok(backfill) => backfill,
+                Err(e) => {
+                    tracing::warn!("Candle backfill skipped: {}", e);
+                    return 0;
+                }
+            };
+        let max_lookback_ms = self.config.max_backfill_hours * 3_600_000;
+        let mut added = 0;
+        for symbol in &self.symbols {
+            match backfill
+                .run(store, symbol, self.started_ms, max_lookback_ms)
+                .await
+            {
+                Ok(0) => {}
+                Ok(count) => {
+                    tracing::info!(symbol = %symbol, candles = count, "Backfilled missed candles");
+                    added += count;
+                }
+                Err(e) => tracing::warn!(symbol = %symbol, "Candle backfill failed: {}", e),
+            }
+        }
+        added
+    }
+
+    /// Feed the latest stored candles to the native strategy module, oldest first.
+    /// Candles of another interval than the live ones would skew its indicators.
+    fn replay(&self, store: &MarketStore, strategy: &StrategySupervisor) -> usize {
+        if self.config.replay_candles == 0 {
+            return 0;
+        }
+        if store.candle_interval_seconds() != CANDLE_INTERVAL_SECONDS {
+            tracing::info!(
+                "Stored candles are not {}s candles, not replaying them",
+                CANDLE_INTERVAL_SECONDS
+            );
+            return 0;
+        }
+        let interval_ms = CANDLE_INTERVAL_SECONDS * 1000;
+        let now = clock::exchange_now_millis();
+        // The current candle is still open; the live stream delivers it
+        let to = now - now % interval_ms;
+        let from = to.saturating_sub(self.config.replay_candles as u64 * interval_ms);
+        let mut replayed = 0;
+        for symbol in &self.symbols {
+            match store.candles(symbol, from, to) {
+                Ok(candles) => {
+                    replayed += candles.len();
+                    for candle in candles {
+                        strategy.deliver(&AppEvent::Candle(candle));
+                    }
+                }
+                Err(e) => tracing::warn!(symbol = %symbol, "Failed to read candles: {}", e),
+            }
+        }
+        replayed
+    }
+}
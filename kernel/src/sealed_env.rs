@@ -0,0 +1,79 @@
+//! Unsealing the secrets a replica was deployed with.
+//!
+//! The fleet key comes from `AURELIA_SECRET_FLEET_CONFIG_KEY`, else from the
+//! instance metadata URL in `AURELIA_FLEET_KEY_URL`, else from the secret
+//! store on the agent that sealed the file. A fetched key is exported as
+//! `AURELIA_SECRET_FLEET_CONFIG_KEY` so the fleet token is derived from the
+//! same key. The unsealed values only ever live in the process environment.
+
+use anyhow::{Context, Result};
+use common::sealed_config::{FLEET_KEY_SECRET, FLEET_KEY_URL_ENV, SEALED_CONFIG_PATH};
+use common::secrets::SECRET_ENV_PREFIX;
+use common::{FleetKey, SealedConfig, SecretStore};
+use std::path::Path;
+use std::time::Duration;
+
+const METADATA_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Place the values of [`SEALED_CONFIG_PATH`] in the environment, leaving
+/// variables that are already set alone. `None` when nothing was deployed sealed.
+///
+/// Must be called before any other thread is started: writing the environment
+/// races with threads reading it.
+pub fn unseal() -> Result<Option<usize>> {
+    // Resolved even without a sealed file: the fleet token depends on it
+    let key = fleet_key()?;
+    if !Path::new(SEALED_CONFIG_PATH).exists() {
+        return Ok(None);
+    }
+    let sealed = SealedConfig::load(SEALED_CONFIG_PATH)
+        .with_context(|| format!("Failed to read {}", SEALED_CONFIG_PATH))?;
+    let key = key.with_context(|| {
+        format!(
+            "No fleet key: set AURELIA_SECRET_FLEET_CONFIG_KEY or {}",
+            FLEET_KEY_URL_ENV
+        )
+    })?;
+    let values = sealed.unseal(&key)?;
+    let mut applied = 0;
+    for (name, value) in values {
+        if std::env::var_os(&name).is_none() {
+            std::env::set_var(name, value);
+            applied += 1;
+        }
+    }
+    Ok(Some(applied))
+}
+
+fn fleet_key() -> Result<Option<FleetKey>> {
+    let key_env = format!("{}{}", SECRET_ENV_PREFIX, FLEET_KEY_SECRET.to_uppercase());
+    // A key handed to this deployment wins over one left in the local store
+    if std::env::var_os(&key_env).is_none() {
+        if let Ok(url) = std::env::var(FLEET_KEY_URL_ENV) {
+            // Its threads are gone again by the time the environment is written
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+            let key = FleetKey::from_hex(&runtime.block_on(fetch_key(&url))?)?;
+            std::env::set_var(key_env, key.to_hex());
+            return Ok(Some(key));
+        }
+    }
+    Ok(FleetKey::load(&SecretStore::default())?)
+}
+
+async fn fetch_key(url: &str) -> Result<String> {
+    let body = reqwest::Client::builder()
+        .timeout(METADATA_TIMEOUT)
+        .build()?
+        .get(url)
+        // Required by GCE's metadata server, ignored elsewhere
+        .header("Metadata-Flavor", "Google")
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to fetch the fleet key from {}", url))?
+        .text()
+        .await?;
+    Ok(body)
+}
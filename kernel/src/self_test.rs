@@ -0,0 +1,204 @@
+//! `kernel self-test`: each subsystem's diagnostic, run without starting the
+//! agent, for verifying a fresh deployment.
+
+use crate::commands;
+use crate::strategy_module;
+use anyhow::{Context, Result};
+use autonomy_core::server_config::AuthMethod;
+use autonomy_core::ServerConfig;
+use common::{AppEvent, EventBus};
+use execution_engine::clock_sync::CLOCK_SYNC_CONFIG_PATH;
+use execution_engine::{ClockSync, ClockSyncConfig};
+use std::collections::BTreeSet;
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Events pushed through the bus by the throughput test
+const BUS_TEST_EVENTS: usize = 10_000;
+
+/// Slowest acceptable bus, in events per second. Only enforced in release
+/// builds; unoptimised test builds are far slower.
+const MIN_BUS_THROUGHPUT: f64 = 20_000.0;
+
+/// Directories the agent writes at runtime
+const WRITABLE_DIRS: [&str; 3] = ["config", "data", "logs"];
+
+/// Outcome of one diagnostic
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+    pub duration: Duration,
+}
+
+/// Run every diagnostic, in order, whether or not earlier ones failed.
+pub async fn run(servers_config: &Path) -> Vec<Diagnostic> {
+    vec![
+        diagnose("event_bus", async { event_bus() }).await,
+        diagnose("config", async { config(servers_config) }).await,
+        diagnose("exchange", exchange()).await,
+        diagnose("ssh_keys", async { ssh_keys(servers_config) }).await,
+        diagnose("strategy_library", async { strategy_library() }).await,
+        diagnose("disk", async { disk() }).await,
+    ]
+}
+
+async fn diagnose(name: &'static str, check: impl Future<Output = Result<String>>) -> Diagnostic {
+    let started = Instant::now();
+    let outcome = check.await;
+    let (passed, detail) = match outcome {
+        Ok(detail) => (true, detail),
+        Err(e) => (false, format!("{:#}", e)),
+    };
+    Diagnostic {
+        name,
+        passed,
+        detail,
+        duration: started.elapsed(),
+    }
+}
+
+/// The pass/fail table printed by `kernel self-test`
+pub fn render(diagnostics: &[Diagnostic]) -> String {
+    let width = diagnostics.iter().map(|d| d.name.len()).max().unwrap_or(0);
+    let mut table = String::new();
+    for d in diagnostics {
+        table.push_str(&format!(
+            "{} {:<width$}  {:>6}ms  {}\n",
+            if d.passed { "✅ PASS" } else { "❌ FAIL" },
+            d.name,
+            d.duration.as_millis(),
+            d.detail,
+            width = width
+        ));
+    }
+    let failed = diagnostics.iter().filter(|d| !d.passed).count();
+    table.push_str(&format!(
+        "{} of {} checks passed\n",
+        diagnostics.len() - failed,
+        diagnostics.len()
+    ));
+    table
+}
+
+/// Every event sent must arrive, at no less than `MIN_BUS_THROUGHPUT`
+fn event_bus() -> Result<String> {
+    let bus = EventBus::new(1024);
+    let mut rx = bus.subscribe();
+    let started = Instant::now();
+    let mut received = 0;
+    for i in 0..BUS_TEST_EVENTS {
+        bus.send(AppEvent::FinancialUpdate(i as f64))
+            .context("Failed to send")?;
+        while rx.try_recv().is_ok() {
+            received += 1;
+        }
+    }
+    let throughput = BUS_TEST_EVENTS as f64 / started.elapsed().as_secs_f64();
+    if received != BUS_TEST_EVENTS {
+        anyhow::bail!("{} of {} events received", received, BUS_TEST_EVENTS);
+    }
+    if cfg!(not(debug_assertions)) && throughput < MIN_BUS_THROUGHPUT {
+        anyhow::bail!(
+            "{:.0} events/s, below {:.0}",
+            throughput,
+            MIN_BUS_THROUGHPUT
+        );
+    }
+    Ok(format!("{:.0} events/s", throughput))
+}
+
+fn config(servers_config: &Path) -> Result<String> {
+    let problems = commands::config_problems(servers_config);
+    if !problems.is_empty() {
+        anyhow::bail!("{}", problems.join("; "));
+    }
+    Ok("server and strategy configuration valid".to_string())
+}
+
+/// Read the exchange's server time from its public endpoint
+async fn exchange() -> Result<String> {
+    let config = ClockSyncConfig::load(CLOCK_SYNC_CONFIG_PATH)?;
+    let url = config.exchange_time_url.clone();
+    let offset = ClockSync::new(config).exchange_offset().await?;
+    Ok(format!("{} reachable, clock offset {}ms", url, offset))
+}
+
+/// Every key the configured servers and their bastions authenticate with
+fn ssh_keys(servers_config: &Path) -> Result<String> {
+    let config = ServerConfig::from_file(servers_config)?;
+    let mut keys = BTreeSet::new();
+    for server in config.get_enabled_servers() {
+        if server.auth_method != AuthMethod::Password {
+            keys.insert(server.get_expanded_ssh_key_path());
+        }
+        if let Some(jump) = server
+            .proxy_jump
+            .as_ref()
+            .filter(|jump| jump.auth_method != AuthMethod::Password)
+        {
+            keys.insert(ServerConfig::expand_ssh_key_path(
+                jump.ssh_key_path.as_deref().unwrap_or("~/.ssh/id_rsa"),
+            ));
+        }
+    }
+    if keys.is_empty() {
+        return Ok("no server authenticates with a key".to_string());
+    }
+    for key in &keys {
+        let contents =
+            fs::read_to_string(key).with_context(|| format!("Failed to read {:?}", key))?;
+        if !contents.contains("PRIVATE KEY") {
+            anyhow::bail!("{:?} is not a private key", key);
+        }
+    }
+    Ok(format!("{} key(s) readable", keys.len()))
+}
+
+fn strategy_library() -> Result<String> {
+    let path = strategy_module::initial_library_path();
+    if strategy_module::is_wasm_module(&path) {
+        return Ok(format!("{:?} is a WASM strategy, not loaded", path));
+    }
+    let exports = strategy_module::probe_library(&path)?;
+    Ok(format!(
+        "{:?} loaded and unloaded, exports {}",
+        path,
+        exports.join(", ")
+    ))
+}
+
+fn disk() -> Result<String> {
+    for dir in WRITABLE_DIRS {
+        let probe = PathBuf::from(dir).join(".self-test");
+        fs::create_dir_all(dir)
+            .and_then(|_| fs::write(&probe, b"self-test"))
+            .and_then(|_| fs::remove_file(&probe))
+            .with_context(|| format!("{} is not writable", dir))?;
+    }
+    Ok(format!("{} writable", WRITABLE_DIRS.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_failures_are_reported_not_propagated() {
+        let passed = diagnose("event_bus", async { event_bus() }).await;
+        assert!(passed.passed, "{}", passed.detail);
+        let failed = diagnose("config", async {
+            config(Path::new("/nonexistent/target_servers.json"))
+        })
+        .await;
+        assert!(!failed.passed);
+
+        let table = render(&[passed, failed]);
+        assert!(table.contains("✅ PASS event_bus"));
+        assert!(table.contains("❌ FAIL config"));
+        assert!(table.ends_with("1 of 2 checks passed\n"));
+    }
+}
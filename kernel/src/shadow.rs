@@ -0,0 +1,247 @@
+//! Shadow trials: a candidate strategy runs next to the live one on the same market
+//! events while both keep a paper book. The candidate goes live only if its book
+//! beats the live strategy's over the trial window.
+
+use crate::strategy_module::StrategyCandidate;
+use common::{AppEvent, AureliaResult, ShadowArm, ShadowTrialReport, StrategyDecision};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+pub const SHADOW_CONFIG_PATH: &str = "config/shadow.json";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ShadowConfig {
+    /// How long a candidate is shadow-run before it is judged
+    pub window_seconds: u64,
+    /// Paper PnL the candidate must add over the live strategy to be promoted
+    pub min_improvement: f64,
+    /// Decisions the candidate must make for its PnL to count
+    pub min_decisions: u32,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            window_seconds: 1800,
+            min_improvement: 0.0,
+            min_decisions: 1,
+        }
+    }
+}
+
+impl ShadowConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    /// +1 long, -1 short
+    quantity: f64,
+    entry: f64,
+}
+
+/// Holds at most one unit per symbol: a buy opens a long or closes a short, a sell
+/// the reverse.
+#[derive(Debug, Default)]
+struct PaperBook {
+    positions: HashMap<String, Position>,
+    prices: HashMap<String, f64>,
+    realized: f64,
+    decisions: u32,
+}
+
+impl PaperBook {
+    fn mark(&mut self, symbol: &str, price: f64) {
+        self.prices.insert(symbol.to_string(), price);
+    }
+
+    fn record(&mut self, decision: &StrategyDecision) {
+        let (symbol, price, side) = match decision {
+            StrategyDecision::Buy(symbol, price) => (symbol, *price, 1.0),
+            StrategyDecision::Sell(symbol, price) => (symbol, *price, -1.0),
+            StrategyDecision::Hold(_) => return,
+        };
+        self.decisions += 1;
+
+        match self.positions.get(symbol).copied() {
+            Some(position) if position.quantity == side => {}
+            Some(position) => {
+                self.realized += position.quantity * (price - position.entry);
+                self.positions.remove(symbol);
+            }
+            None => {
+                self.positions.insert(
+                    symbol.clone(),
+                    Position {
+                        quantity: side,
+                        entry: price,
+                    },
+                );
+            }
+        }
+    }
+
+    fn pnl(&self) -> f64 {
+        let unrealized: f64 = self
+            .positions
+            .iter()
+            .map(|(symbol, position)| {
+                let price = self.prices.get(symbol).copied().unwrap_or(position.entry);
+                position.quantity * (price - position.entry)
+            })
+            .sum();
+        self.realized + unrealized
+    }
+
+    fn summary(&self) -> ShadowArm {
+        ShadowArm {
+            decisions: self.decisions,
+            paper_pnl: self.pnl(),
+        }
+    }
+}
+
+/// Why the candidate should, or should not, replace the live strategy.
+fn verdict(
+    active: &ShadowArm,
+    challenger: &ShadowArm,
+    config: &ShadowConfig,
+) -> Result<String, String> {
+    if challenger.decisions < config.min_decisions {
+        return Err(format!(
+            "candidate made {} decisions, {} required",
+            challenger.decisions, config.min_decisions
+        ));
+    }
+    let improvement = challenger.paper_pnl - active.paper_pnl;
+    if improvement <= config.min_improvement {
+        return Err(format!(
+            "candidate paper PnL {:.2} did not beat live {:.2} by more than {:.2}",
+            challenger.paper_pnl, active.paper_pnl, config.min_improvement
+        ));
+    }
+    Ok(format!("candidate outperformed live by {:.2}", improvement))
+}
+
+pub struct ShadowTrial {
+    candidate: StrategyCandidate,
+    config: ShadowConfig,
+    started: Instant,
+    active: PaperBook,
+    challenger: PaperBook,
+    failure: Option<String>,
+}
+
+impl ShadowTrial {
+    pub fn start(candidate: StrategyCandidate, config: ShadowConfig) -> Self {
+        Self {
+            candidate,
+            config,
+            started: Instant::now(),
+            active: PaperBook::default(),
+            challenger: PaperBook::default(),
+            failure: None,
+        }
+    }
+
+    pub fn candidate(&self) -> &Path {
+        self.candidate.source()
+    }
+
+    /// Account for an event seen on the live bus.
+    pub fn observe(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::MarketData(data) => {
+                self.active.mark(&data.symbol, data.price);
+                self.challenger.mark(&data.symbol, data.price);
+                self.candidate.deliver(event);
+            }
+            // The candidate builds its indicators from the same inputs as the live module
+            AppEvent::Candle(_) | AppEvent::SentimentUpdate(_) | AppEvent::MarketConditions(_) => {
+                self.candidate.deliver(event)
+            }
+            AppEvent::StrategyDecision(decision, _) => self.active.record(decision),
+            _ => {}
+        }
+    }
+
+    /// Collect the candidate's decisions and check that it is still running.
+    pub fn poll(&mut self) {
+        for event in self.candidate.drain() {
+            if let AppEvent::StrategyDecision(decision, _) = event {
+                self.challenger.record(&decision);
+            }
+        }
+        if self.failure.is_none() {
+            if let Err(failure) = self.candidate.check() {
+                self.failure = Some(failure.to_string());
+            }
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.failure.is_some()
+            || self.started.elapsed() >= Duration::from_secs(self.config.window_seconds)
+    }
+
+    /// Judge the trial. The candidate is handed back only if it should be promoted;
+    /// otherwise it is stopped here.
+    pub fn finish(self) -> (ShadowTrialReport, Option<StrategyCandidate>) {
+        let active = self.active.summary();
+        let challenger = self.challenger.summary();
+        let outcome = match self.failure {
+            Some(failure) => Err(failure),
+            None => verdict(&active, &challenger, &self.config),
+        };
+
+        let report = ShadowTrialReport {
+            candidate: self.candidate.source().display().to_string(),
+            duration_seconds: self.started.elapsed().as_secs(),
+            active,
+            challenger,
+            promoted: outcome.is_ok(),
+            reason: outcome.clone().unwrap_or_else(|reason| reason),
+        };
+        let candidate = outcome.is_ok().then_some(self.candidate);
+        (report, candidate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paper_book_and_verdict() {
+        let mut book = PaperBook::default();
+        book.record(&StrategyDecision::Buy("BTCUSDT".to_string(), 100.0));
+        // A second buy does not add to the position
+        book.record(&StrategyDecision::Buy("BTCUSDT".to_string(), 105.0));
+        book.mark("BTCUSDT", 110.0);
+        assert_eq!(book.pnl(), 10.0);
+
+        book.record(&StrategyDecision::Sell("BTCUSDT".to_string(), 120.0));
+        book.record(&StrategyDecision::Sell("ETHUSDT".to_string(), 50.0));
+        book.mark("ETHUSDT", 55.0);
+        book.record(&StrategyDecision::Hold("BTCUSDT".to_string()));
+        let summary = book.summary();
+        assert_eq!(summary.decisions, 4);
+        assert_eq!(summary.paper_pnl, 15.0);
+
+        let config = ShadowConfig::default();
+        let idle = ShadowArm::default();
+        assert!(verdict(&idle, &summary, &config).is_ok());
+        assert!(verdict(&summary, &idle, &config).is_err());
+        assert!(verdict(&summary, &summary, &config).is_err());
+    }
+}
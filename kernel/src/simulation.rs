@@ -0,0 +1,223 @@
+//! In-process multi-agent simulation.
+//!
+//! Runs a small fleet on one machine: every agent has its own event bus fed by a
+//! shared mock exchange, and agents "deploy" replicas onto free slots of an
+//! in-memory network instead of SSH servers. Replication goes through the same
+//! [`ReplicationStrategy`] guard rails as a real deployment, so replica counts,
+//! fleet size and generation limits can be exercised without any remote hosts.
+//! The root agent stays the primary throughout; the agent has no leader election
+//! to simulate.
+
+use autonomy_core::ReplicationStrategy;
+use common::identity::AgentIdentity;
+use common::{AppEvent, EventBus, EventMeta, EventReceiver, MarketData, Topic};
+use serde::Serialize;
+
+const SYMBOL: &str = "BTCUSDT";
+
+#[derive(Debug, Clone)]
+pub struct SimulationConfig {
+    /// Agents running before the first round, including the primary
+    pub agents: usize,
+    /// Free servers replicas can be deployed onto
+    pub servers: usize,
+    pub rounds: u64,
+    /// Strategy of the primary; replicas get [`ReplicationStrategy::for_replica`]
+    pub strategy: ReplicationStrategy,
+}
+
+/// Deterministic price feed standing in for the exchange
+struct MockExchange {
+    base_price: f64,
+}
+
+impl MockExchange {
+    fn tick(&self, round: u64) -> MarketData {
+        let drift = (round as f64 / 5.0).sin() * 0.01;
+        MarketData {
+            symbol: SYMBOL.to_string(),
+            price: self.base_price * (1.0 + drift),
+            quantity: 0.01,
+            timestamp: round * 1000,
+            meta: EventMeta::default(),
+        }
+    }
+}
+
+struct SimAgent {
+    identity: AgentIdentity,
+    strategy: ReplicationStrategy,
+    bus: EventBus,
+    market_rx: EventReceiver,
+    ticks: u64,
+    last_price: Option<f64>,
+}
+
+impl SimAgent {
+    fn new(identity: AgentIdentity, strategy: ReplicationStrategy) -> Self {
+        let bus = EventBus::new(64);
+        let market_rx = bus.subscribe_to(&[Topic::Market]);
+        Self {
+            identity,
+            strategy,
+            bus,
+            market_rx,
+            ticks: 0,
+            last_price: None,
+        }
+    }
+
+    fn deliver(&mut self, data: MarketData) {
+        if self.bus.send(AppEvent::MarketData(data)).is_err() {
+            return;
+        }
+        while let Ok(event) = self.market_rx.try_recv() {
+            if let AppEvent::MarketData(data) = event {
+                self.ticks += 1;
+                self.last_price = Some(data.price);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RoundSummary {
+    pub round: u64,
+    pub fleet_size: usize,
+    pub events: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentSummary {
+    pub agent_id: String,
+    pub parent_id: Option<String>,
+    pub generation: u32,
+    pub ticks: u64,
+    pub last_price: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationReport {
+    pub rounds: Vec<RoundSummary>,
+    pub agents: Vec<AgentSummary>,
+    pub max_fleet_size: usize,
+    pub free_servers: usize,
+}
+
+pub fn run(config: &SimulationConfig) -> SimulationReport {
+    let exchange = MockExchange {
+        base_price: 70_000.0,
+    };
+    let root = AgentIdentity::new_root();
+    let mut agents = vec![SimAgent::new(root.clone(), config.strategy.clone())];
+    for _ in 1..config.agents {
+        agents.push(SimAgent::new(
+            root.spawn_child(),
+            config.strategy.for_replica(),
+        ));
+    }
+
+    let mut free_servers = config.servers;
+    let mut max_fleet_size = 0;
+    let mut rounds = Vec::new();
+
+    for round in 1..=config.rounds {
+        let mut events = Vec::new();
+
+        let tick = exchange.tick(round);
+        for agent in agents.iter_mut() {
+            agent.deliver(tick.clone());
+        }
+
+        let mut fleet_size = agents.len();
+        let primary = &agents[0];
+        let needed = primary.strategy.min_replicas.saturating_sub(fleet_size - 1);
+        if needed > 0 {
+            match primary.strategy.allowance(&primary.identity, fleet_size) {
+                Ok(allowance) => {
+                    let count = needed.min(allowance).min(free_servers);
+                    if count < needed {
+                        events.push(format!(
+                            "{} replicas needed but only {} can be deployed",
+                            needed, count
+                        ));
+                    }
+                    let strategy = primary.strategy.for_replica();
+                    let children: Vec<_> = (0..count)
+                        .map(|_| primary.identity.spawn_child())
+                        .map(|identity| SimAgent::new(identity, strategy.clone()))
+                        .collect();
+                    for child in &children {
+                        events.push(format!(
+                            "deployed replica {} (generation {})",
+                            child.identity.agent_id, child.identity.generation
+                        ));
+                    }
+                    free_servers -= count;
+                    fleet_size += count;
+                    agents.extend(children);
+                }
+                Err(e) => events.push(format!("replication blocked: {}", e)),
+            }
+        }
+
+        for event in &events {
+            tracing::info!(round, "{}", event);
+        }
+        max_fleet_size = max_fleet_size.max(fleet_size);
+        rounds.push(RoundSummary {
+            round,
+            fleet_size,
+            events,
+        });
+    }
+
+    SimulationReport {
+        rounds,
+        agents: agents
+            .iter()
+            .map(|agent| AgentSummary {
+                agent_id: agent.identity.agent_id.clone(),
+                parent_id: agent.identity.parent_id.clone(),
+                generation: agent.identity.generation,
+                ticks: agent.ticks,
+                last_price: agent.last_price,
+            })
+            .collect(),
+        max_fleet_size,
+        free_servers,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replication_fills_the_fleet_within_limits() {
+        let config = SimulationConfig {
+            agents: 1,
+            servers: 5,
+            rounds: 10,
+            strategy: ReplicationStrategy {
+                min_replicas: 3,
+                max_fleet_size: 3,
+                max_generation: 2,
+                ..ReplicationStrategy::default()
+            },
+        };
+        let report = run(&config);
+
+        // The fleet size cap holds back the third replica
+        assert_eq!(report.max_fleet_size, 3);
+        assert_eq!(report.rounds.last().unwrap().fleet_size, 3);
+        assert!(report.rounds[0]
+            .events
+            .iter()
+            .any(|event| event.contains("3 replicas needed but only 2")));
+        let generations: Vec<_> = report.agents.iter().map(|agent| agent.generation).collect();
+        assert_eq!(generations, vec![0, 1, 1]);
+        assert_eq!(report.free_servers, 3);
+        assert_eq!(report.agents[0].ticks, 10);
+    }
+}
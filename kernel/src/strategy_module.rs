@@ -0,0 +1,657 @@
+use common::event_schema::{self, STRATEGY_ABI_VERSION};
+use common::{AppEvent, AureliaError, AureliaResult, StrategyParamUpdate};
+use libloading::{Library, Symbol};
+use std::ffi::CString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use strategy_engine::{params, OUTPUT_FILE};
+use tokio::task::{self, JoinHandle};
+
+#[cfg(feature = "wasm")]
+use crate::wasm_strategy::WasmStrategy;
+#[cfg(feature = "wasm")]
+use common::{EventBus, EventReceiver, Topic};
+#[cfg(feature = "wasm")]
+use tokio::sync::broadcast::error::TryRecvError;
+
+type ModuleRunFn = unsafe extern "C" fn();
+type ModuleAbiVersionFn = unsafe extern "C" fn() -> u32;
+type ModuleHeartbeatFn = unsafe extern "C" fn() -> u64;
+type ModuleSetParamFn = unsafe extern "C" fn(*const std::os::raw::c_char, f64) -> i32;
+type ModuleSetOutputFn = unsafe extern "C" fn(*const std::os::raw::c_char) -> bool;
+type ModuleStopFn = unsafe extern "C" fn();
+type ModuleEventFn = unsafe extern "C" fn(*const std::os::raw::c_char);
+
+/// Entry point every strategy library must export.
+const RUN_SYMBOL: &[u8] = b"run_strategy_engine";
+/// The module's `STRATEGY_ABI_VERSION`. Modules without it predate versioning
+/// and are sent events without the schema envelope.
+const ABI_VERSION_SYMBOL: &[u8] = b"strategy_abi_version";
+/// Optional heartbeat counter a strategy library may export. It must increase at least
+/// once per `HEARTBEAT_TIMEOUT` while the module is making progress.
+const HEARTBEAT_SYMBOL: &[u8] = b"strategy_heartbeat";
+/// Optional parameter setter; see `strategy_engine::params` for the status codes.
+const SET_PARAM_SYMBOL: &[u8] = b"strategy_set_param";
+/// Optional: redirect the events the module writes for the kernel. Required to
+/// shadow-run a native module.
+const SET_OUTPUT_SYMBOL: &[u8] = b"strategy_set_output";
+/// Optional: ask the module to return from its entry point.
+const STOP_SYMBOL: &[u8] = b"strategy_stop";
+/// Optional: receive an `AppEvent` as JSON.
+const EVENT_SYMBOL: &[u8] = b"process_event_from_kernel";
+
+/// How long a strategy module may go without a heartbeat before it is reloaded.
+pub const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Private copies of shadow candidates and the events they write.
+pub const SHADOW_DIR: &str = "data/shadow";
+
+/// Events a WASM strategy receives.
+#[cfg(feature = "wasm")]
+const WASM_STRATEGY_TOPICS: [Topic; 3] = [Topic::Market, Topic::Financial, Topic::Reasoning];
+
+/// Whether `path` names a WASM strategy rather than a native library.
+pub fn is_wasm_module(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "wasm")
+}
+
+/// The strategy loaded at startup: `AURELIA_STRATEGY_MODULE`, which may point at
+/// a native library or, with the `wasm` feature, a `.wasm` strategy, else the
+/// debug build of `strategy_engine`.
+pub fn initial_library_path() -> PathBuf {
+    std::env::var_os("AURELIA_STRATEGY_MODULE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            PathBuf::from(if cfg!(target_os = "linux") {
+                "target/debug/libstrategy_engine.so"
+            } else if cfg!(target_os = "macos") {
+                "target/debug/libstrategy_engine.dylib"
+            } else {
+                "target/debug/strategy_engine.dll"
+            })
+        })
+}
+
+/// Load a native strategy library without running it, check that it exports
+/// `run_strategy_engine` and this kernel's strategy ABI version, and unload it
+/// again. Returns the symbols it exports.
+pub fn probe_library(lib_path: &Path) -> AureliaResult<Vec<&'static str>> {
+    // Fails without the entry point
+    let library = DynamicModule::open(lib_path)?;
+    // Loading tolerates modules that predate versioning; a fresh deployment must not ship one
+    match abi_version(&library) {
+        Some(STRATEGY_ABI_VERSION) => {}
+        Some(version) => {
+            return Err(AureliaError::Ipc(format!(
+                "{:?} speaks strategy ABI version {}, the kernel speaks {}",
+                lib_path, version, STRATEGY_ABI_VERSION
+            )))
+        }
+        None => {
+            return Err(AureliaError::Ipc(format!(
+                "{:?} does not export strategy_abi_version",
+                lib_path
+            )))
+        }
+    }
+    let exports = [
+        RUN_SYMBOL,
+        ABI_VERSION_SYMBOL,
+        HEARTBEAT_SYMBOL,
+        SET_PARAM_SYMBOL,
+        SET_OUTPUT_SYMBOL,
+        STOP_SYMBOL,
+        EVENT_SYMBOL,
+    ]
+    .into_iter()
+    .filter(|symbol| unsafe { library.get::<*const ()>(symbol) }.is_ok())
+    .filter_map(|symbol| std::str::from_utf8(symbol).ok())
+    .collect();
+    drop(library);
+    Ok(exports)
+}
+
+/// Why the watchdog gave up on a module.
+#[derive(Debug)]
+pub enum ModuleFailure {
+    NotRunning,
+    /// The module's thread returned, usually because it panicked.
+    Stopped,
+    /// No heartbeat for the given time.
+    Hung(Duration),
+}
+
+impl fmt::Display for ModuleFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModuleFailure::NotRunning => write!(f, "strategy module is not running"),
+            ModuleFailure::Stopped => write!(f, "strategy module stopped"),
+            ModuleFailure::Hung(d) => write!(f, "strategy module missed heartbeats for {:?}", d),
+        }
+    }
+}
+
+struct DynamicModule {
+    path: PathBuf,
+    library: Arc<Library>,
+    abi_version: Option<u32>,
+    task_handle: JoinHandle<()>,
+    last_heartbeat: Option<u64>,
+    last_progress: Instant,
+    has_beaten: bool,
+}
+
+impl DynamicModule {
+    /// Load a module that writes its events to `output` instead of the live file.
+    fn with_output(lib_path: PathBuf, output: &Path) -> AureliaResult<Self> {
+        let library = Self::open(&lib_path)?;
+        redirect_output(&library, &lib_path, output)?;
+        Ok(Self::start(lib_path, library))
+    }
+
+    fn open(lib_path: &Path) -> AureliaResult<Arc<Library>> {
+        let library = Arc::new(unsafe { Library::new(lib_path) }.map_err(|e| {
+            AureliaError::Ipc(format!("Failed to load library {:?}: {}", lib_path, e))
+        })?);
+        // Check the entry point before spawning so a bad library is rejected here
+        unsafe { library.get::<ModuleRunFn>(RUN_SYMBOL) }.map_err(|e| {
+            AureliaError::Ipc(format!("{:?} has no strategy entry point: {}", lib_path, e))
+        })?;
+        match abi_version(&library) {
+            Some(version) if version != STRATEGY_ABI_VERSION => {
+                return Err(AureliaError::Ipc(format!(
+                    "{:?} speaks strategy ABI version {}, the kernel speaks {}",
+                    lib_path, version, STRATEGY_ABI_VERSION
+                )))
+            }
+            Some(_) => {}
+            None => tracing::warn!(
+                "{:?} does not report its strategy ABI version, sending it unversioned events",
+                lib_path
+            ),
+        }
+        Ok(library)
+    }
+
+    fn start(lib_path: PathBuf, library: Arc<Library>) -> Self {
+        let lib = library.clone();
+        let task_handle = task::spawn_blocking(move || unsafe {
+            let run_func: Symbol<ModuleRunFn> = lib
+                .get(RUN_SYMBOL)
+                .expect("Symbol loading failed inside thread");
+            run_func();
+        });
+
+        let mut module = Self {
+            path: lib_path,
+            abi_version: abi_version(&library),
+            library,
+            task_handle,
+            last_heartbeat: None,
+            last_progress: Instant::now(),
+            has_beaten: false,
+        };
+        module.last_heartbeat = module.heartbeat();
+        module
+    }
+
+    fn heartbeat(&self) -> Option<u64> {
+        unsafe {
+            self.library
+                .get::<ModuleHeartbeatFn>(HEARTBEAT_SYMBOL)
+                .ok()
+                .map(|beat| beat())
+        }
+    }
+
+    fn set_param(&self, update: &StrategyParamUpdate) -> AureliaResult<()> {
+        let set_param =
+            unsafe { self.library.get::<ModuleSetParamFn>(SET_PARAM_SYMBOL) }.map_err(|_| {
+                AureliaError::Config(format!("{:?} has no tunable parameters", self.path))
+            })?;
+        let name = CString::new(update.name.as_str())
+            .map_err(|e| AureliaError::Config(format!("invalid parameter name: {}", e)))?;
+
+        let status = unsafe { set_param(name.as_ptr(), update.value) };
+        match status {
+            params::PARAM_OK => Ok(()),
+            params::PARAM_UNKNOWN => Err(AureliaError::Config(format!(
+                "unknown strategy parameter '{}'",
+                update.name
+            ))),
+            params::PARAM_OUT_OF_RANGE => Err(AureliaError::Config(format!(
+                "{} is out of range for strategy parameter '{}'",
+                update.value, update.name
+            ))),
+            other => Err(AureliaError::Config(format!(
+                "strategy module rejected '{}' with status {}",
+                update.name, other
+            ))),
+        }
+    }
+
+    fn check(&mut self, timeout: Duration) -> Result<(), ModuleFailure> {
+        if self.task_handle.is_finished() {
+            return Err(ModuleFailure::Stopped);
+        }
+
+        match self.heartbeat() {
+            Some(beat) if Some(beat) != self.last_heartbeat => {
+                self.last_heartbeat = Some(beat);
+                self.last_progress = Instant::now();
+                self.has_beaten = true;
+            }
+            Some(_) => {}
+            // Libraries without the heartbeat contract can only be caught stopping
+            None => self.last_progress = Instant::now(),
+        }
+
+        let silent = self.last_progress.elapsed();
+        if silent > timeout {
+            return Err(ModuleFailure::Hung(silent));
+        }
+        Ok(())
+    }
+
+    fn deliver(&self, event: &AppEvent) -> AureliaResult<()> {
+        let Ok(process) = (unsafe { self.library.get::<ModuleEventFn>(EVENT_SYMBOL) }) else {
+            return Ok(());
+        };
+        let json = match self.abi_version {
+            Some(_) => event_schema::encode(event)?,
+            None => serde_json::to_string(event)?,
+        };
+        let json = CString::new(json)
+            .map_err(|e| AureliaError::Ipc(format!("event is not a C string: {}", e)))?;
+        unsafe { process(json.as_ptr()) };
+        Ok(())
+    }
+
+    fn shutdown(&self) {
+        if let Ok(stop) = unsafe { self.library.get::<ModuleStopFn>(STOP_SYMBOL) } {
+            unsafe { stop() };
+        }
+        // A module stuck in native code cannot be interrupted; its thread is abandoned
+        self.task_handle.abort();
+    }
+}
+
+fn abi_version(library: &Library) -> Option<u32> {
+    unsafe { library.get::<ModuleAbiVersionFn>(ABI_VERSION_SYMBOL) }
+        .ok()
+        .map(|version| unsafe { version() })
+}
+
+fn redirect_output(library: &Library, lib_path: &Path, output: &Path) -> AureliaResult<()> {
+    let set_output = unsafe { library.get::<ModuleSetOutputFn>(SET_OUTPUT_SYMBOL) }
+        .map_err(|_| AureliaError::Ipc(format!("{:?} cannot redirect its output", lib_path)))?;
+    let output = CString::new(output.to_string_lossy().as_bytes())
+        .map_err(|e| AureliaError::Ipc(format!("invalid output path: {}", e)))?;
+    if unsafe { set_output(output.as_ptr()) } {
+        Ok(())
+    } else {
+        Err(AureliaError::Ipc(format!(
+            "{:?} rejected output path {:?}",
+            lib_path, output
+        )))
+    }
+}
+
+#[cfg(feature = "wasm")]
+struct WasmHost {
+    bus: EventBus,
+    strategy: Option<WasmStrategy>,
+}
+
+/// Runs the strategy module and restarts it from the last library that proved
+/// healthy when it stops or its heartbeats stop.
+///
+/// Libraries ending in `.wasm` are run in the WASM host when the kernel is built with
+/// the `wasm` feature; everything else is loaded as a native library.
+pub struct StrategySupervisor {
+    module: Option<DynamicModule>,
+    #[cfg(feature = "wasm")]
+    wasm: Option<WasmHost>,
+    last_known_good: Option<PathBuf>,
+    timeout: Duration,
+    last_recovery: Option<Instant>,
+}
+
+impl StrategySupervisor {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            module: None,
+            #[cfg(feature = "wasm")]
+            wasm: None,
+            last_known_good: None,
+            timeout,
+            last_recovery: None,
+        }
+    }
+
+    /// Allow WASM strategies, which exchange events with the kernel over `bus`.
+    #[cfg(feature = "wasm")]
+    pub fn with_wasm(mut self, bus: EventBus) -> Self {
+        self.wasm = Some(WasmHost {
+            bus,
+            strategy: None,
+        });
+        self
+    }
+
+    /// Replace the running module with the library at `path`.
+    pub fn load(&mut self, path: &Path) -> AureliaResult<()> {
+        if is_wasm_module(path) {
+            return self.load_wasm(path);
+        }
+
+        // Open first so an incompatible library leaves the running one in place
+        let library = DynamicModule::open(path)?;
+        if let Some(old) = self.module.take() {
+            old.shutdown();
+        }
+        #[cfg(feature = "wasm")]
+        if let Some(host) = self.wasm.as_mut() {
+            host.strategy = None;
+        }
+        self.module = Some(DynamicModule::start(path.to_path_buf(), library));
+        Ok(())
+    }
+
+    #[cfg(feature = "wasm")]
+    fn load_wasm(&mut self, path: &Path) -> AureliaResult<()> {
+        let host = self.wasm.as_mut().ok_or_else(|| {
+            AureliaError::Ipc("WASM strategies are not enabled in this kernel".to_string())
+        })?;
+        // Instantiate first so a broken module leaves the running one in place
+        let strategy = WasmStrategy::load(
+            path,
+            host.bus.clone(),
+            host.bus
+                .subscribe_as("wasm_strategy", &WASM_STRATEGY_TOPICS),
+        )?;
+        host.strategy = Some(strategy);
+        if let Some(old) = self.module.take() {
+            old.shutdown();
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    fn load_wasm(&mut self, path: &Path) -> AureliaResult<()> {
+        Err(AureliaError::Ipc(format!(
+            "cannot load {:?}: kernel was built without the `wasm` feature",
+            path
+        )))
+    }
+
+    /// Pass an event to the running native module. WASM strategies subscribe to
+    /// their events themselves.
+    pub fn deliver(&self, event: &AppEvent) {
+        if let Some(module) = &self.module {
+            if let Err(e) = module.deliver(event) {
+                tracing::warn!("Failed to deliver event to strategy module: {}", e);
+            }
+        }
+    }
+
+    /// Start the library at `path` as a shadow candidate. It runs next to the live
+    /// module, but nothing it emits is published.
+    pub fn spawn_candidate(&self, path: &Path) -> AureliaResult<StrategyCandidate> {
+        if is_wasm_module(path) {
+            return self.spawn_wasm_candidate(path);
+        }
+
+        std::fs::create_dir_all(SHADOW_DIR)?;
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let extension = path
+            .extension()
+            .map(|ext| format!(".{}", ext.to_string_lossy()))
+            .unwrap_or_default();
+        // A private copy, so the loader cannot hand back the live module's instance
+        let copy = Path::new(SHADOW_DIR).join(format!("candidate-{}{}", stamp, extension));
+        let output = Path::new(SHADOW_DIR).join(format!("candidate-{}.log", stamp));
+        std::fs::copy(path, &copy)?;
+
+        match DynamicModule::with_output(copy.clone(), &output) {
+            Ok(module) => Ok(StrategyCandidate {
+                source: path.to_path_buf(),
+                kind: Some(CandidateKind::Native { module, output }),
+            }),
+            Err(e) => {
+                let _ = std::fs::remove_file(&copy);
+                Err(e)
+            }
+        }
+    }
+
+    #[cfg(feature = "wasm")]
+    fn spawn_wasm_candidate(&self, path: &Path) -> AureliaResult<StrategyCandidate> {
+        let host = self.wasm.as_ref().ok_or_else(|| {
+            AureliaError::Ipc("WASM strategies are not enabled in this kernel".to_string())
+        })?;
+        // Whatever the candidate emits stays on a bus of its own
+        let private = EventBus::new(1024);
+        let decisions = private.subscribe_to(&[Topic::Strategy]);
+        let strategy = WasmStrategy::load(
+            path,
+            private,
+            host.bus
+                .subscribe_as("wasm_candidate", &WASM_STRATEGY_TOPICS),
+        )?;
+        Ok(StrategyCandidate {
+            source: path.to_path_buf(),
+            kind: Some(CandidateKind::Wasm {
+                strategy,
+                decisions,
+            }),
+        })
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    fn spawn_wasm_candidate(&self, path: &Path) -> AureliaResult<StrategyCandidate> {
+        Err(AureliaError::Ipc(format!(
+            "cannot shadow {:?}: kernel was built without the `wasm` feature",
+            path
+        )))
+    }
+
+    /// Make a shadow candidate the live module. Returns the path it now runs from.
+    pub fn promote(&mut self, mut candidate: StrategyCandidate) -> AureliaResult<PathBuf> {
+        match candidate.kind.take() {
+            Some(CandidateKind::Native { module, output }) => {
+                // Keep the running instance and point its events at the live file
+                redirect_output(&module.library, &module.path, Path::new(OUTPUT_FILE))?;
+                let _ = std::fs::remove_file(output);
+                if let Some(old) = self.module.take() {
+                    old.shutdown();
+                }
+                #[cfg(feature = "wasm")]
+                if let Some(host) = self.wasm.as_mut() {
+                    host.strategy = None;
+                }
+                let path = module.path.clone();
+                self.module = Some(module);
+                Ok(path)
+            }
+            // Its emits are wired to the private bus, so start it again on the live one
+            #[cfg(feature = "wasm")]
+            Some(CandidateKind::Wasm { .. }) => {
+                self.load(&candidate.source)?;
+                Ok(candidate.source.clone())
+            }
+            None => Err(AureliaError::Ipc(
+                "strategy candidate was already discarded".to_string(),
+            )),
+        }
+    }
+
+    /// Change a tunable parameter of the running native module. The module validates
+    /// and persists the value itself.
+    pub fn set_param(&self, update: &StrategyParamUpdate) -> AureliaResult<()> {
+        #[cfg(feature = "wasm")]
+        if self
+            .wasm
+            .as_ref()
+            .is_some_and(|host| host.strategy.is_some())
+        {
+            return Err(AureliaError::Config(
+                "WASM strategies do not take parameter updates".to_string(),
+            ));
+        }
+        match &self.module {
+            Some(module) => module.set_param(update),
+            None => Err(AureliaError::Ipc(
+                "no strategy module is running".to_string(),
+            )),
+        }
+    }
+
+    /// Check the running module. Returns the failure if there was one, together
+    /// with the outcome of reloading the last known good library.
+    pub fn supervise(&mut self) -> Option<(ModuleFailure, AureliaResult<PathBuf>)> {
+        // Whether the WASM strategy, if one is loaded, is still running
+        #[cfg(feature = "wasm")]
+        let wasm = self
+            .wasm
+            .as_ref()
+            .and_then(|host| host.strategy.as_ref())
+            .map(|strategy| (strategy.is_running(), strategy.path().to_path_buf()));
+        #[cfg(not(feature = "wasm"))]
+        let wasm: Option<(bool, PathBuf)> = None;
+
+        let failure = match (self.module.as_mut(), wasm) {
+            // Fuel limits bound every call into a WASM module, so it cannot hang
+            (_, Some((true, path))) => {
+                self.last_known_good = Some(path);
+                return None;
+            }
+            (_, Some(_)) => ModuleFailure::Stopped,
+            (Some(module), None) => match module.check(self.timeout) {
+                Ok(()) => {
+                    if module.has_beaten || module.last_heartbeat.is_none() {
+                        self.last_known_good = Some(module.path.clone());
+                    }
+                    return None;
+                }
+                Err(failure) => failure,
+            },
+            (None, None) => ModuleFailure::NotRunning,
+        };
+
+        // Give each reload a full timeout before trying again
+        if matches!(self.last_recovery, Some(at) if at.elapsed() < self.timeout) {
+            return None;
+        }
+        self.last_recovery = Some(Instant::now());
+
+        let recovery = match self.last_known_good.clone() {
+            Some(path) => self.load(&path).map(|()| path),
+            None => Err(AureliaError::Ipc(
+                "no known good strategy library to fall back to".to_string(),
+            )),
+        };
+        Some((failure, recovery))
+    }
+}
+
+enum CandidateKind {
+    Native {
+        module: DynamicModule,
+        output: PathBuf,
+    },
+    #[cfg(feature = "wasm")]
+    Wasm {
+        strategy: WasmStrategy,
+        decisions: EventReceiver,
+    },
+}
+
+/// A strategy being shadow-run. It is stopped and its files removed when dropped,
+/// unless it was promoted.
+pub struct StrategyCandidate {
+    source: PathBuf,
+    kind: Option<CandidateKind>,
+}
+
+impl StrategyCandidate {
+    /// The library the candidate was started from.
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    pub fn check(&mut self) -> Result<(), ModuleFailure> {
+        match &mut self.kind {
+            Some(CandidateKind::Native { module, .. }) => module.check(HEARTBEAT_TIMEOUT),
+            #[cfg(feature = "wasm")]
+            Some(CandidateKind::Wasm { strategy, .. }) if strategy.is_running() => Ok(()),
+            #[cfg(feature = "wasm")]
+            Some(CandidateKind::Wasm { .. }) => Err(ModuleFailure::Stopped),
+            None => Err(ModuleFailure::NotRunning),
+        }
+    }
+
+    pub fn deliver(&self, event: &AppEvent) {
+        if let Some(CandidateKind::Native { module, .. }) = &self.kind {
+            if let Err(e) = module.deliver(event) {
+                tracing::warn!("Failed to deliver event to strategy candidate: {}", e);
+            }
+        }
+    }
+
+    /// Events the candidate produced since the last call.
+    pub fn drain(&mut self) -> Vec<AppEvent> {
+        match &mut self.kind {
+            Some(CandidateKind::Native { output, .. }) => {
+                // Move the file aside first; the module recreates it on its next write
+                let reading = output.with_extension("log.reading");
+                if std::fs::rename(&*output, &reading).is_err() {
+                    return Vec::new();
+                }
+                let events = std::fs::read_to_string(&reading)
+                    .unwrap_or_default()
+                    .lines()
+                    .filter_map(|line| {
+                        event_schema::decode(line)
+                            .map_err(|e| {
+                                tracing::warn!("Dropped event from strategy candidate: {}", e)
+                            })
+                            .ok()
+                    })
+                    .collect();
+                let _ = std::fs::remove_file(&reading);
+                events
+            }
+            #[cfg(feature = "wasm")]
+            Some(CandidateKind::Wasm { decisions, .. }) => {
+                let mut events = Vec::new();
+                loop {
+                    match decisions.try_recv() {
+                        Ok(event) => events.push(event),
+                        Err(TryRecvError::Lagged(n)) => {
+                            tracing::warn!("Strategy candidate lagged by {} events", n)
+                        }
+                        Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+                    }
+                }
+                events
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+impl Drop for StrategyCandidate {
+    fn drop(&mut self) {
+        if let Some(CandidateKind::Native { module, output }) = self.kind.take() {
+            module.shutdown();
+            let _ = std::fs::remove_file(output);
+            let _ = std::fs::remove_file(&module.path);
+        }
+    }
+}
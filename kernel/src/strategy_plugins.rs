@@ -0,0 +1,683 @@
+//! Strategy plugins developed outside this repository.
+//!
+//! A plugin is a native strategy library or a `.wasm` strategy described by a
+//! [`PluginManifest`], as written by `kernel sign-plugin`. Plugins are picked
+//! up from a local directory, where each manifest sits next to its file, and
+//! from a registry: a URL serving a JSON list of manifests that say where to
+//! download each file from.
+//!
+//! A plugin is installed only when its version is newer than the installed one,
+//! its SHA-256 matches and its Ed25519 signature, which covers the plugin's
+//! name and version as well as its contents, was made with a trusted key (see
+//! [`common::signing`]). Every version is installed into a directory of its own,
+//! so the library the kernel runs is never overwritten, and recorded in
+//! [`INSTALLED_PLUGINS_FILE`]. The kernel is then asked to shadow-run the new
+//! version before promoting it, or to hot-swap it in straight away.
+//!
+//! The kernel records in [`PLUGIN_LOADS_FILE`] which plugin it runs and which
+//! failed to load or lost their trial. A version it has neither run nor failed
+//! is offered again on the next check, and the plugin it runs is loaded again
+//! when it restarts.
+
+use chrono::{DateTime, Utc};
+use common::audit::{self, AuditCategory};
+use common::event_schema::STRATEGY_ABI_VERSION;
+use common::signing;
+use common::{AppEvent, AureliaError, AureliaResult, EventBus};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const PLUGINS_CONFIG_PATH: &str = "config/strategy_plugins.json";
+/// Versions installed so far and files that failed verification, in `install_dir`
+pub const INSTALLED_PLUGINS_FILE: &str = "installed.json";
+/// What became of the plugins handed to the kernel, in `install_dir`. Only the
+/// kernel writes it, so it never races the loader over [`INSTALLED_PLUGINS_FILE`].
+pub const PLUGIN_LOADS_FILE: &str = "loads.json";
+
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginsConfig {
+    pub enabled: bool,
+    /// Local directory of plugin manifests and their files
+    pub dir: PathBuf,
+    /// URL of a registry's list of plugin manifests
+    pub registry_url: Option<String>,
+    /// Where verified plugins are installed
+    pub install_dir: PathBuf,
+    pub poll_interval_seconds: u64,
+    /// Plugin run as the strategy. Other plugins are installed but not loaded;
+    /// without it every newly installed version is handed to the kernel.
+    pub strategy: Option<String>,
+    /// Shadow-run a new version before it replaces the running strategy
+    pub shadow_trial: bool,
+    /// Hex Ed25519 key plugins must be signed with; defaults to the key in
+    /// `config/release_key.pub`
+    pub public_key: Option<String>,
+    /// Refuse plugins without a valid signature
+    pub require_signature: bool,
+}
+
+impl Default for PluginsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: PathBuf::from("plugins"),
+            registry_url: None,
+            install_dir: PathBuf::from("data/plugins"),
+            poll_interval_seconds: 300,
+            strategy: None,
+            shadow_trial: true,
+            public_key: None,
+            require_signature: true,
+        }
+    }
+}
+
+impl PluginsConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+/// Published with every plugin version.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginManifest {
+    pub name: String,
+    pub version: String,
+    /// File name of the library or `.wasm` module, which decides how it is loaded
+    pub file: String,
+    /// Where a registry's plugin is downloaded from; local plugins sit next to
+    /// their manifest
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Hex SHA-256 of the file
+    pub sha256: String,
+    /// Hex Ed25519 signature of the file under [`PluginManifest::signed_name`]
+    #[serde(default)]
+    pub signature: Option<String>,
+    /// Strategy ABI version the plugin was built against; a plugin for another
+    /// version is not downloaded
+    #[serde(default)]
+    pub abi_version: Option<u32>,
+}
+
+impl PluginManifest {
+    /// Name the file is signed under, so a signature does not carry over to
+    /// another plugin or version
+    pub fn signed_name(name: &str, version: &str) -> String {
+        format!("plugin/{}@{}", name, version)
+    }
+
+    fn label(&self) -> String {
+        format!("{} {}", self.name, self.version)
+    }
+
+    /// Reject manifests whose names could escape the install directory
+    fn check_names(&self) -> AureliaResult<()> {
+        for part in [&self.name, &self.version, &self.file] {
+            let plain = Path::new(part)
+                .file_name()
+                .is_some_and(|name| name == part.as_str());
+            if !plain || part.starts_with('.') {
+                return Err(AureliaError::Deployment(format!(
+                    "plugin {} has an invalid name, version or file '{}'",
+                    self.label(),
+                    part
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A plugin version whose signature did not verify. Keyed on what was signed, so
+/// a release signed again under the same version is still installed.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RejectedPlugin {
+    pub name: String,
+    pub version: String,
+    pub signature: Option<String>,
+}
+
+impl RejectedPlugin {
+    fn of(manifest: &PluginManifest) -> Self {
+        Self {
+            name: manifest.name.clone(),
+            version: manifest.version.clone(),
+            signature: manifest.signature.clone(),
+        }
+    }
+}
+
+/// A plugin version that was installed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstalledPlugin {
+    pub version: String,
+    pub sha256: String,
+    pub path: PathBuf,
+    pub installed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InstalledPlugins {
+    /// Latest installed version of each plugin, by name
+    pub plugins: BTreeMap<String, InstalledPlugin>,
+    /// Releases that failed verification, never fetched again
+    pub rejected: BTreeSet<RejectedPlugin>,
+}
+
+impl InstalledPlugins {
+    pub fn load(path: &Path) -> AureliaResult<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn save(&self, path: &Path) -> AureliaResult<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginLoads {
+    /// Plugin the kernel runs, loaded again when it restarts
+    pub active: Option<PathBuf>,
+    /// Plugins the kernel has run
+    pub loaded: BTreeSet<PathBuf>,
+    /// Plugins that failed to load or lost their shadow trial, with the reason
+    pub failed: BTreeMap<PathBuf, String>,
+}
+
+impl PluginLoads {
+    pub fn load(install_dir: &Path) -> AureliaResult<Self> {
+        let path = install_dir.join(PLUGIN_LOADS_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn save(&self, install_dir: &Path) -> AureliaResult<()> {
+        std::fs::create_dir_all(install_dir)?;
+        let path = install_dir.join(PLUGIN_LOADS_FILE);
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// The plugin in `install_dir` the kernel ran last, if it is still there
+    pub fn active(install_dir: &Path) -> Option<PathBuf> {
+        match Self::load(install_dir) {
+            Ok(loads) => loads.active.filter(|path| path.exists()),
+            Err(e) => {
+                tracing::error!("Failed to read strategy plugin loads: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Record that the kernel tried to run the strategy at `path`. A strategy
+    /// from outside `install_dir` that loaded replaces the active plugin.
+    pub fn record(install_dir: &Path, path: &Path, outcome: Result<(), String>) {
+        let plugin = path.starts_with(install_dir);
+        if !plugin && outcome.is_err() {
+            return;
+        }
+        let result = Self::load(install_dir).and_then(|mut loads| {
+            match outcome {
+                Ok(()) if plugin => {
+                    loads.active = Some(path.to_path_buf());
+                    loads.loaded.insert(path.to_path_buf());
+                }
+                Ok(()) => loads.active = None,
+                Err(reason) => {
+                    loads.failed.insert(path.to_path_buf(), reason);
+                }
+            }
+            loads.save(install_dir)
+        });
+        if let Err(e) = result {
+            tracing::error!("Failed to record strategy plugin load of {:?}: {}", path, e);
+        }
+    }
+}
+
+/// Compare dotted versions part by part, numerically where both parts are numbers.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |v: &str| {
+        v.trim_start_matches('v')
+            .split(['.', '-', '+'])
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+    let (a, b) = (parts(a), parts(b));
+    for (x, y) in a.iter().zip(&b) {
+        let order = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            _ => x.cmp(y),
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+pub struct PluginLoader {
+    config: PluginsConfig,
+    client: reqwest::Client,
+}
+
+impl PluginLoader {
+    pub fn new(config: PluginsConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::builder()
+                .timeout(DOWNLOAD_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    fn installed_path(&self) -> PathBuf {
+        self.config.install_dir.join(INSTALLED_PLUGINS_FILE)
+    }
+
+    /// Check for new plugin versions for as long as the kernel runs
+    pub async fn run(self, bus: EventBus) {
+        tracing::info!(
+            dir = ?self.config.dir,
+            registry = ?self.config.registry_url,
+            "Checking for strategy plugins every {}s",
+            self.config.poll_interval_seconds
+        );
+        let mut interval = tokio::time::interval(Duration::from_secs(
+            self.config.poll_interval_seconds.max(1),
+        ));
+        loop {
+            interval.tick().await;
+            for path in self.check().await {
+                let event = if self.config.shadow_trial {
+                    AppEvent::CandidateModuleReady(path)
+                } else {
+                    AppEvent::ModuleReadyForHotSwap(path)
+                };
+                if bus.send_control(event).await.is_err() {
+                    tracing::error!("Kernel is not accepting strategy plugins");
+                }
+            }
+        }
+    }
+
+    /// Install the new versions in the local directory and the registry.
+    /// Returns the paths of those that should run as the strategy and the
+    /// kernel has neither run nor failed to run.
+    pub async fn check(&self) -> Vec<String> {
+        let mut manifests = match self.local_manifests() {
+            Ok(manifests) => manifests,
+            Err(e) => {
+                tracing::warn!("Failed to read plugins in {:?}: {}", self.config.dir, e);
+                Vec::new()
+            }
+        };
+        if let Some(url) = &self.config.registry_url {
+            match self.registry_manifests(url).await {
+                Ok(remote) => manifests.extend(remote),
+                Err(e) => tracing::warn!("Failed to read plugin registry {}: {}", url, e),
+            }
+        }
+
+        // Only the latest version of each plugin is considered
+        manifests.sort_by(|(a, _), (b, _)| {
+            a.name
+                .cmp(&b.name)
+                .then(compare_versions(&b.version, &a.version))
+        });
+        manifests.dedup_by(|(a, _), (b, _)| a.name == b.name);
+
+        for (manifest, local) in manifests {
+            if !self.is_new(&manifest) {
+                continue;
+            }
+            let contents = match local {
+                Some(path) => std::fs::read(&path).map_err(AureliaError::from),
+                None => self.download(&manifest).await,
+            };
+            if let Err(e) = contents.and_then(|contents| self.install(&manifest, &contents)) {
+                tracing::error!("Strategy plugin {} not installed: {}", manifest.label(), e);
+            }
+        }
+        self.offers()
+    }
+
+    /// Installed plugins that should run as the strategy and were not settled
+    /// yet, such as a candidate dropped while another one was on trial
+    fn offers(&self) -> Vec<String> {
+        let (installed, loads) = match InstalledPlugins::load(&self.installed_path())
+            .and_then(|installed| Ok((installed, PluginLoads::load(&self.config.install_dir)?)))
+        {
+            Ok(state) => state,
+            Err(e) => {
+                tracing::error!("Failed to read installed plugins: {}", e);
+                return Vec::new();
+            }
+        };
+        installed
+            .plugins
+            .into_iter()
+            .filter(|(name, _)| self.config.strategy.as_ref().is_none_or(|s| s == name))
+            .map(|(_, plugin)| plugin.path)
+            .filter(|path| !loads.loaded.contains(path) && !loads.failed.contains_key(path))
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    /// Manifests in the local directory, with the file each one describes
+    fn local_manifests(&self) -> AureliaResult<Vec<(PluginManifest, Option<PathBuf>)>> {
+        let dir = &self.config.dir;
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut manifests = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            match serde_json::from_str::<PluginManifest>(&std::fs::read_to_string(&path)?) {
+                Ok(manifest) => {
+                    let file = dir.join(&manifest.file);
+                    manifests.push((manifest, Some(file)));
+                }
+                Err(e) => tracing::warn!("Invalid plugin manifest {:?}: {}", path, e),
+            }
+        }
+        Ok(manifests)
+    }
+
+    async fn registry_manifests(
+        &self,
+        url: &str,
+    ) -> AureliaResult<Vec<(PluginManifest, Option<PathBuf>)>> {
+        let manifests: Vec<PluginManifest> = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AureliaError::Deployment(format!("registry request failed: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| AureliaError::Deployment(format!("invalid registry: {}", e)))?;
+        Ok(manifests
+            .into_iter()
+            .map(|manifest| (manifest, None))
+            .collect())
+    }
+
+    async fn download(&self, manifest: &PluginManifest) -> AureliaResult<Vec<u8>> {
+        let url = manifest.url.as_deref().ok_or_else(|| {
+            AureliaError::Deployment(format!("registry plugin {} has no url", manifest.label()))
+        })?;
+        tracing::info!(
+            "Downloading strategy plugin {} from {}",
+            manifest.label(),
+            url
+        );
+        let bytes = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AureliaError::Deployment(format!("download failed: {}", e)))?
+            .bytes()
+            .await
+            .map_err(|e| AureliaError::Deployment(format!("download failed: {}", e)))?;
+        Ok(bytes.to_vec())
+    }
+
+    /// Whether `manifest` is worth fetching: newer than the installed version,
+    /// not rejected before and built for the kernel's strategy ABI
+    fn is_new(&self, manifest: &PluginManifest) -> bool {
+        let installed = match InstalledPlugins::load(&self.installed_path()) {
+            Ok(installed) => installed,
+            Err(e) => {
+                tracing::error!("Failed to read installed plugins: {}", e);
+                return false;
+            }
+        };
+        if installed.rejected.contains(&RejectedPlugin::of(manifest)) {
+            return false;
+        }
+        if let Some(current) = installed.plugins.get(&manifest.name) {
+            if compare_versions(&manifest.version, &current.version) != Ordering::Greater {
+                return false;
+            }
+        }
+        match manifest.abi_version {
+            Some(version) if version != STRATEGY_ABI_VERSION => {
+                tracing::debug!(
+                    "Strategy plugin {} is built for ABI version {}, the kernel speaks {}",
+                    manifest.label(),
+                    version,
+                    STRATEGY_ABI_VERSION
+                );
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Verify `contents` against the manifest and install them. Releases whose
+    /// signature does not match the trusted key are remembered and not fetched again.
+    pub fn install(&self, manifest: &PluginManifest, contents: &[u8]) -> AureliaResult<PathBuf> {
+        manifest.check_names()?;
+        let digest = format!("{:x}", Sha256::digest(contents));
+        if digest != manifest.sha256.to_lowercase() {
+            return Err(AureliaError::Deployment(format!(
+                "plugin {} has SHA-256 {}, the manifest says {}",
+                manifest.label(),
+                digest,
+                manifest.sha256
+            )));
+        }
+        let path = self.installed_path();
+        let mut installed = InstalledPlugins::load(&path)?;
+        if let Some((key, signature)) = self.signature_check(manifest)? {
+            let name = PluginManifest::signed_name(&manifest.name, &manifest.version);
+            if let Err(e) = signing::verify(&key, &name, contents, signature) {
+                installed.rejected.insert(RejectedPlugin::of(manifest));
+                installed.save(&path)?;
+                audit::record(
+                    AuditCategory::ConfigChange,
+                    "reject_strategy_plugin",
+                    manifest,
+                );
+                return Err(e);
+            }
+        }
+
+        let dir = self
+            .config
+            .install_dir
+            .join(&manifest.name)
+            .join(&manifest.version);
+        std::fs::create_dir_all(&dir)?;
+        let file = dir.join(&manifest.file);
+        std::fs::write(&file, contents)?;
+        installed.plugins.insert(
+            manifest.name.clone(),
+            InstalledPlugin {
+                version: manifest.version.clone(),
+                sha256: digest,
+                path: file.clone(),
+                installed_at: Utc::now(),
+            },
+        );
+        installed.save(&path)?;
+        tracing::info!(
+            "Installed strategy plugin {} at {:?}",
+            manifest.label(),
+            file
+        );
+        audit::record(
+            AuditCategory::ConfigChange,
+            "install_strategy_plugin",
+            manifest,
+        );
+        Ok(file)
+    }
+
+    /// The trusted key and the manifest's signature to check a plugin with, or
+    /// `None` if it may be installed unsigned. Errors here say nothing about the file.
+    fn signature_check<'a>(
+        &self,
+        manifest: &'a PluginManifest,
+    ) -> AureliaResult<Option<(String, &'a str)>> {
+        let trusted = match &self.config.public_key {
+            Some(key) => Some(key.clone()),
+            None => signing::trusted_key(".")?,
+        };
+        match (&manifest.signature, trusted) {
+            (Some(signature), Some(key)) => {
+                signing::check_public_key(&key)?;
+                Ok(Some((key, signature)))
+            }
+            _ if self.config.require_signature => Err(AureliaError::Deployment(format!(
+                "plugin {} cannot be verified: it needs a signature and a trusted key",
+                manifest.label()
+            ))),
+            _ => {
+                tracing::warn!(
+                    "Installing strategy plugin {} without a signature check",
+                    manifest.label()
+                );
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{ReleaseSigner, SecretStore};
+
+    fn manifest(signer: &ReleaseSigner, version: &str, contents: &[u8]) -> PluginManifest {
+        PluginManifest {
+            name: "momentum".to_string(),
+            version: version.to_string(),
+            file: "momentum.wasm".to_string(),
+            url: None,
+            sha256: format!("{:x}", Sha256::digest(contents)),
+            signature: Some(
+                signer.sign(&PluginManifest::signed_name("momentum", version), contents),
+            ),
+            abi_version: Some(STRATEGY_ABI_VERSION),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plugins_are_verified_and_installed_once() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let signer = ReleaseSigner::load_or_create(&SecretStore::new(dir.join("secrets"))).unwrap();
+        let config = PluginsConfig {
+            enabled: true,
+            dir: dir.join("plugins"),
+            install_dir: dir.join("installed"),
+            public_key: Some(signer.public_key()),
+            ..PluginsConfig::default()
+        };
+        let loader = PluginLoader::new(config.clone());
+        std::fs::create_dir_all(&config.dir).unwrap();
+        let publish = |manifest: &PluginManifest, contents: &[u8]| {
+            std::fs::write(config.dir.join(&manifest.file), contents).unwrap();
+            std::fs::write(
+                config.dir.join("momentum.json"),
+                serde_json::to_string(manifest).unwrap(),
+            )
+            .unwrap();
+        };
+
+        publish(&manifest(&signer, "1.9.0", b"v1.9"), b"v1.9");
+        let ready = loader.check().await;
+        assert_eq!(ready.len(), 1);
+        assert_eq!(std::fs::read(&ready[0]).unwrap(), b"v1.9");
+        // Offered again until the kernel has run it
+        assert_eq!(loader.check().await, ready);
+        PluginLoads::record(&config.install_dir, Path::new(&ready[0]), Ok(()));
+        assert!(loader.check().await.is_empty(), "already running");
+        assert_eq!(
+            PluginLoads::active(&config.install_dir).unwrap(),
+            Path::new(&ready[0])
+        );
+
+        // 1.10 is newer than 1.9, but a signature does not carry over to another version
+        let mut relabelled = manifest(&signer, "1.10.0", b"v1.10");
+        relabelled.signature = manifest(&signer, "1.9.0", b"v1.10").signature;
+        publish(&relabelled, b"v1.10");
+        assert!(loader.check().await.is_empty());
+        let installed = InstalledPlugins::load(&loader.installed_path()).unwrap();
+        assert_eq!(installed.plugins["momentum"].version, "1.9.0");
+        assert!(installed
+            .rejected
+            .contains(&RejectedPlugin::of(&relabelled)));
+
+        publish(&manifest(&signer, "1.10.0", b"v1.10 fixed"), b"v1.10 fixed");
+        let ready = loader.check().await;
+        assert_eq!(ready.len(), 1);
+        assert!(ready[0].ends_with("momentum/1.10.0/momentum.wasm"));
+        PluginLoads::record(
+            &config.install_dir,
+            Path::new(&ready[0]),
+            Err("lost its shadow trial".to_string()),
+        );
+        assert!(
+            loader.check().await.is_empty(),
+            "failed versions are not offered"
+        );
+        assert!(PluginLoads::active(&config.install_dir)
+            .unwrap()
+            .ends_with("momentum/1.9.0/momentum.wasm"));
+        // The version the kernel runs is left in place
+        assert_eq!(
+            std::fs::read(dir.join("installed/momentum/1.9.0/momentum.wasm")).unwrap(),
+            b"v1.9"
+        );
+
+        // A corrupted download is fetched again, it does not condemn the release
+        let truncated = manifest(&signer, "2.0.0", b"v2");
+        assert!(loader.install(&truncated, b"v").is_err());
+        let installed = InstalledPlugins::load(&loader.installed_path()).unwrap();
+        assert!(!installed.rejected.contains(&RejectedPlugin::of(&truncated)));
+
+        let mut escaping = manifest(&signer, "2.0.0", b"v2");
+        escaping.file = "../../kernel".to_string();
+        assert!(loader.install(&escaping, b"v2").is_err());
+    }
+}
@@ -0,0 +1,66 @@
+//! Minimal `sd_notify` client.
+//!
+//! Only active when the kernel runs as a `Type=notify` systemd service, i.e. when
+//! `NOTIFY_SOCKET` is set; otherwise every call is a no-op.
+
+use std::io;
+use std::time::Duration;
+
+pub struct Notifier {
+    #[cfg(unix)]
+    socket: std::os::unix::net::UnixDatagram,
+    #[cfg(unix)]
+    path: String,
+}
+
+impl Notifier {
+    /// Connect to the socket systemd passed in `NOTIFY_SOCKET`.
+    pub fn from_env() -> Option<Self> {
+        #[cfg(unix)]
+        {
+            let path = std::env::var("NOTIFY_SOCKET").ok()?;
+            let socket = std::os::unix::net::UnixDatagram::unbound().ok()?;
+            Some(Self { socket, path })
+        }
+        #[cfg(not(unix))]
+        {
+            None
+        }
+    }
+
+    /// Send a notification such as `READY=1` or `WATCHDOG=1`.
+    pub fn notify(&self, state: &str) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            // A leading '@' denotes a socket in the abstract namespace
+            #[cfg(target_os = "linux")]
+            if let Some(name) = self.path.strip_prefix('@') {
+                use std::os::linux::net::SocketAddrExt;
+                let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+                return self
+                    .socket
+                    .send_to_addr(state.as_bytes(), &addr)
+                    .map(|_| ());
+            }
+            self.socket
+                .send_to(state.as_bytes(), &self.path)
+                .map(|_| ())
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = state;
+            Ok(())
+        }
+    }
+}
+
+/// How often to send `WATCHDOG=1`: half of systemd's `WatchdogSec`, if enabled.
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok()? != std::process::id() {
+            return None;
+        }
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    Some(Duration::from_micros(usec / 2))
+}
@@ -0,0 +1,187 @@
+//! Log output and trace export.
+//!
+//! Logs go to stdout and the log file from `config/logging.json`, as text or
+//! JSON, panics included. With the `otel` feature and `config/tracing.json` enabled, spans are
+//! also exported over OTLP/gRPC, so the decision cycles, order round-trips and deployment phases of every agent in
+//! the fleet can be followed in Jaeger or Tempo. Each agent's spans sit under
+//! its `agent` span, which carries the agent ID.
+
+use crate::cli::LogFormat;
+use crate::log_rotation::{LoggingConfig, RotatingFile};
+use common::AureliaResult;
+use serde::{Deserialize, Serialize};
+use std::io::IsTerminal;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+pub const TRACING_CONFIG_PATH: &str = "config/tracing.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TracingConfig {
+    pub enabled: bool,
+    /// OTLP/gRPC endpoint of the collector
+    pub otlp_endpoint: String,
+    /// `service.name` of the exported spans
+    pub service_name: String,
+    /// Share of traces to keep, from 0.0 to 1.0
+    pub sample_ratio: f64,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            otlp_endpoint: "http://localhost:4317".to_string(),
+            service_name: "aurelia".to_string(),
+            sample_ratio: 1.0,
+        }
+    }
+}
+
+impl TracingConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+/// Install the global subscriber. Problems with the log file and the trace
+/// exporter are logged once the subscriber is up, and never stop the kernel.
+pub fn init(format: LogFormat, logging: &LoggingConfig, config: &TracingConfig) {
+    let file = logging
+        .file
+        .as_ref()
+        .map(|path| RotatingFile::open(path, logging).map_err(|e| (path, e)));
+    // A service's stdout is appended to the same file by systemd or docker, so
+    // stdout only gets a copy when someone is watching
+    let stdout = !matches!(file, Some(Ok(_))) || std::io::stdout().is_terminal();
+    let (file_output, file_error) = match file {
+        Some(Ok(file)) => (Some(output_layer(format, Mutex::new(file), false)), None),
+        Some(Err((path, e))) => (None, Some(format!("{}: {}", path.display(), e))),
+        None => (None, None),
+    };
+    let registry = tracing_subscriber::registry()
+        .with(stdout.then(|| output_layer(format, std::io::stdout, true)))
+        .with(file_output)
+        .with(LevelFilter::INFO);
+
+    #[cfg(feature = "otel")]
+    {
+        let (layer, error) = match config.enabled.then(|| otlp_layer(config)) {
+            Some(Ok(layer)) => (Some(layer), None),
+            Some(Err(e)) => (None, Some(e)),
+            None => (None, None),
+        };
+        registry.with(layer).init();
+        match error {
+            Some(e) => tracing::error!("Failed to start the OTLP trace exporter: {}", e),
+            None if config.enabled => {
+                tracing::info!("Exporting traces to {}", config.otlp_endpoint)
+            }
+            None => {}
+        }
+    }
+    #[cfg(not(feature = "otel"))]
+    {
+        registry.init();
+        if config.enabled {
+            tracing::warn!("Trace export is enabled but the kernel was built without `otel`");
+        }
+    }
+    if let Some(e) = file_error {
+        tracing::error!("Failed to open the log file, logging to stdout: {}", e);
+    }
+
+    // Otherwise a panic only reaches stderr, which a service appends to a file
+    // the kernel may since have rotated away
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        tracing::error!("{}", info);
+        default_hook(info);
+    }));
+}
+
+fn output_layer<S, W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match format {
+        LogFormat::Text => layer.boxed(),
+        // One JSON object per line, with span fields such as `correlation_id` included
+        LogFormat::Json => layer.json().boxed(),
+    }
+}
+
+/// Flush spans still waiting to be exported.
+pub fn shutdown() {
+    #[cfg(feature = "otel")]
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+#[cfg(feature = "otel")]
+fn otlp_layer<S>(config: &TracingConfig) -> anyhow::Result<impl Layer<S>>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::trace::{self, Sampler};
+    use opentelemetry_sdk::Resource;
+
+    let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+        config.sample_ratio.clamp(0.0, 1.0),
+    )));
+    let provider = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(&config.otlp_endpoint),
+        )
+        .with_trace_config(
+            trace::Config::default()
+                .with_sampler(sampler)
+                .with_resource(Resource::new([
+                    KeyValue::new("service.name", config.service_name.clone()),
+                    KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+                ])),
+        )
+        .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+    let tracer = provider.tracer("aurelia");
+    opentelemetry::global::set_tracer_provider(provider);
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracing_is_off_unless_configured() {
+        let config = TracingConfig::load("config/no-such-tracing.json").unwrap();
+        assert!(!config.enabled);
+
+        let config: TracingConfig =
+            serde_json::from_str(r#"{"enabled": true, "otlp_endpoint": "http://tempo:4317"}"#)
+                .unwrap();
+        assert!(config.enabled);
+        assert_eq!(config.otlp_endpoint, "http://tempo:4317");
+        assert_eq!(config.service_name, "aurelia");
+        assert_eq!(config.sample_ratio, 1.0);
+    }
+}
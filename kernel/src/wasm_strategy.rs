@@ -0,0 +1,249 @@
+//! Strategy modules compiled to `wasm32-wasi`, hosted in wasmtime.
+//!
+//! Unlike native libraries, a WASM module lives entirely inside its `Store`: unloading
+//! it drops the store and frees everything, and a runaway module is stopped by its
+//! fuel budget instead of taking a kernel thread with it.
+//!
+//! Guest contract:
+//! - export `memory`, `alloc(len: i32) -> i32` and `on_event(ptr: i32, len: i32)`.
+//!   The host writes each event as `AppEvent` JSON into a buffer obtained from
+//!   `alloc`; the guest owns that buffer afterwards.
+//! - optionally export `init()`, called once after loading, and `on_tick()`, called
+//!   every [`TICK_INTERVAL`].
+//! - import `aurelia::emit(ptr: i32, len: i32)` to publish an `AppEvent` as JSON and
+//!   `aurelia::log(ptr: i32, len: i32)` to log a UTF-8 message.
+
+use common::{
+    AppEvent, AureliaError, AureliaResult, CancellationToken, EventBus, EventReceiver, LagHandler,
+};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::{self, JoinHandle};
+use wasmtime::{
+    Caller, Config, Engine, Extern, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+    TypedFunc,
+};
+use wasmtime_wasi::preview1::{self, WasiP1Ctx};
+use wasmtime_wasi::WasiCtxBuilder;
+
+/// Instructions a single call into the guest may execute.
+const FUEL_PER_CALL: u64 = 50_000_000;
+/// Linear memory a guest may grow to.
+const MEMORY_LIMIT_BYTES: usize = 64 * 1024 * 1024;
+/// Largest message a guest may hand to a host function.
+const MAX_MESSAGE_BYTES: usize = 1024 * 1024;
+/// How often `on_tick` is called.
+pub const TICK_INTERVAL: Duration = Duration::from_secs(10);
+
+fn wasm_error(e: impl std::fmt::Display) -> AureliaError {
+    AureliaError::Ipc(format!("WASM strategy: {:#}", e))
+}
+
+struct HostState {
+    wasi: WasiP1Ctx,
+    limits: StoreLimits,
+    bus: EventBus,
+}
+
+struct Guest {
+    store: Store<HostState>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    on_event: TypedFunc<(i32, i32), ()>,
+    on_tick: Option<TypedFunc<(), ()>>,
+}
+
+impl Guest {
+    fn instantiate(path: &Path, bus: EventBus) -> AureliaResult<Self> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(wasm_error)?;
+        let module = Module::from_file(&engine, path).map_err(wasm_error)?;
+
+        let mut linker: Linker<HostState> = Linker::new(&engine);
+        preview1::add_to_linker_sync(&mut linker, |state| &mut state.wasi).map_err(wasm_error)?;
+        linker
+            .func_wrap(
+                "aurelia",
+                "emit",
+                |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> wasmtime::Result<()> {
+                    let bytes = read_guest(&mut caller, ptr, len)?;
+                    let event: AppEvent = serde_json::from_slice(&bytes)?;
+                    // Strategies decide; they do not get to steer the kernel
+                    if event.is_control_plane() {
+                        tracing::warn!(?event, "WASM strategy tried to emit a control-plane event");
+                        return Ok(());
+                    }
+                    if caller.data().bus.send(event).is_err() {
+                        tracing::debug!("No subscribers for WASM strategy event");
+                    }
+                    Ok(())
+                },
+            )
+            .map_err(wasm_error)?;
+        linker
+            .func_wrap(
+                "aurelia",
+                "log",
+                |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> wasmtime::Result<()> {
+                    let bytes = read_guest(&mut caller, ptr, len)?;
+                    tracing::info!("[WASM Strategy] {}", String::from_utf8_lossy(&bytes));
+                    Ok(())
+                },
+            )
+            .map_err(wasm_error)?;
+
+        let state = HostState {
+            wasi: WasiCtxBuilder::new().inherit_stderr().build_p1(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MEMORY_LIMIT_BYTES)
+                .build(),
+            bus,
+        };
+        let mut store = Store::new(&engine, state);
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(FUEL_PER_CALL).map_err(wasm_error)?;
+
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .map_err(wasm_error)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| wasm_error("module does not export its memory"))?;
+        let alloc = instance
+            .get_typed_func(&mut store, "alloc")
+            .map_err(wasm_error)?;
+        let on_event = instance
+            .get_typed_func(&mut store, "on_event")
+            .map_err(wasm_error)?;
+        let on_tick = instance.get_typed_func(&mut store, "on_tick").ok();
+        let init = instance.get_typed_func::<(), ()>(&mut store, "init").ok();
+
+        let mut guest = Self {
+            store,
+            memory,
+            alloc,
+            on_event,
+            on_tick,
+        };
+        if let Some(init) = init {
+            guest.refuel()?;
+            init.call(&mut guest.store, ()).map_err(wasm_error)?;
+        }
+        Ok(guest)
+    }
+
+    fn refuel(&mut self) -> AureliaResult<()> {
+        self.store.set_fuel(FUEL_PER_CALL).map_err(wasm_error)
+    }
+
+    fn deliver(&mut self, event: &AppEvent) -> AureliaResult<()> {
+        let json = serde_json::to_vec(event)?;
+        let len = i32::try_from(json.len()).map_err(wasm_error)?;
+
+        self.refuel()?;
+        let ptr = self.alloc.call(&mut self.store, len).map_err(wasm_error)?;
+        self.memory
+            .write(&mut self.store, ptr as usize, &json)
+            .map_err(wasm_error)?;
+        self.on_event
+            .call(&mut self.store, (ptr, len))
+            .map_err(wasm_error)
+    }
+
+    fn tick(&mut self) -> AureliaResult<()> {
+        let Some(on_tick) = self.on_tick.clone() else {
+            return Ok(());
+        };
+        self.refuel()?;
+        on_tick.call(&mut self.store, ()).map_err(wasm_error)
+    }
+
+    /// Feed events and ticks to the guest until `cancel` fires or the bus closes.
+    fn run(mut self, runtime: Handle, mut events: EventReceiver, cancel: CancellationToken) {
+        let mut next_tick = Instant::now() + TICK_INTERVAL;
+        // Not published: a shadow candidate's bus only carries what it emits
+        let lag = LagHandler::new("wasm_strategy");
+        loop {
+            let wait = next_tick.saturating_duration_since(Instant::now());
+            let received = runtime.block_on(async {
+                tokio::select! {
+                    _ = cancel.cancelled() => None,
+                    received = tokio::time::timeout(wait, events.recv()) => Some(received),
+                }
+            });
+
+            let result = match received {
+                None => break,
+                Some(Ok(Ok(event))) => self.deliver(&event),
+                Some(Ok(Err(RecvError::Lagged(n)))) => {
+                    lag.lagged(n);
+                    continue;
+                }
+                Some(Ok(Err(RecvError::Closed))) => break,
+                Some(Err(_)) => {
+                    next_tick = Instant::now() + TICK_INTERVAL;
+                    self.tick()
+                }
+            };
+            // A trap aborts only the call; the guest keeps receiving events
+            if let Err(e) = result {
+                tracing::error!("{}", e);
+            }
+        }
+        tracing::info!("[WASM Strategy] Unloaded.");
+    }
+}
+
+fn read_guest(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> wasmtime::Result<Vec<u8>> {
+    let len = usize::try_from(len)?;
+    if len > MAX_MESSAGE_BYTES {
+        wasmtime::bail!("message of {} bytes exceeds {}", len, MAX_MESSAGE_BYTES);
+    }
+    let memory = match caller.get_export("memory") {
+        Some(Extern::Memory(memory)) => memory,
+        _ => wasmtime::bail!("module does not export its memory"),
+    };
+    let mut buf = vec![0; len];
+    memory.read(&*caller, usize::try_from(ptr)?, &mut buf)?;
+    Ok(buf)
+}
+
+/// A loaded WASM strategy. Dropping it unloads the module.
+pub struct WasmStrategy {
+    path: PathBuf,
+    cancel: CancellationToken,
+    task_handle: JoinHandle<()>,
+}
+
+impl WasmStrategy {
+    /// Compile and instantiate the module at `path`, then start feeding it `events`.
+    pub fn load(path: &Path, bus: EventBus, events: EventReceiver) -> AureliaResult<Self> {
+        let guest = Guest::instantiate(path, bus)?;
+        let cancel = CancellationToken::new();
+        let token = cancel.clone();
+        let runtime = Handle::current();
+        let task_handle = task::spawn_blocking(move || guest.run(runtime, events, token));
+        Ok(Self {
+            path: path.to_path_buf(),
+            cancel,
+            task_handle,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.task_handle.is_finished()
+    }
+}
+
+impl Drop for WasmStrategy {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
@@ -0,0 +1,322 @@
+//! Where metamorphosis compiles its mutations.
+//!
+//! By default the strategy engine is rebuilt on this machine, which on a small
+//! VPS takes CPU and I/O away from the trading loop. With a builder server
+//! configured, the source tree is synced to it over SSH, built there, and the
+//! library is fetched back and checked against the SHA-256 the builder reports.
+//! The builder must produce libraries for the same platform as this agent.
+
+use autonomy_core::server_config::ServerConfig;
+use autonomy_core::{DeploymentCommander, SshDeployer};
+use common::{AureliaError, AureliaResult, ProcessPriority, SshTimeouts};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::info;
+
+pub const BUILD_CONFIG_PATH: &str = "config/metamorphosis_build.json";
+
+/// Workspace files sent to the builder; everything else stays on this machine
+const WORKSPACE_FILES: [&str; 2] = ["Cargo.toml", "Cargo.lock"];
+/// What is sent of each crate of the workspace, when present
+const CRATE_FILES: [&str; 8] = [
+    "Cargo.toml",
+    "build.rs",
+    "src",
+    "proto",
+    "static",
+    "examples",
+    "tests",
+    "benches",
+];
+/// Left out even inside the files above, in case secrets were put there
+const EXCLUDED_FROM_SYNC: [&str; 6] = [".env", ".env.*", "*.pem", "*.key", "secrets", "target"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BuildConfig {
+    /// Whether the agent mutates and rebuilds its strategy at all; small
+    /// replicas turn it off rather than compile on the server
+    pub enabled: bool,
+    /// `id` of the server in `servers_config` that builds mutations; `None` builds locally
+    pub builder_server_id: Option<String>,
+    pub servers_config: String,
+    /// Working directory on the builder, relative to the SSH user's home
+    pub remote_dir: String,
+    /// How long a remote build may take
+    pub timeout_seconds: u64,
+}
+
+impl Default for BuildConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            builder_server_id: None,
+            servers_config: "config/target_servers.json".to_string(),
+            remote_dir: "aurelia-build".to_string(),
+            timeout_seconds: 1800,
+        }
+    }
+}
+
+impl BuildConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CompileFarm {
+    config: BuildConfig,
+    /// Priority of the local build and of packing the source for the builder
+    priority: ProcessPriority,
+}
+
+impl CompileFarm {
+    pub fn new(config: BuildConfig) -> Self {
+        Self {
+            config,
+            priority: ProcessPriority::default(),
+        }
+    }
+
+    pub fn with_config(mut self, config: BuildConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn with_priority(mut self, priority: ProcessPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Build `package` in release mode and leave its library at `artifact`,
+    /// relative to the workspace root.
+    pub async fn build(&self, package: &str, artifact: &str) -> AureliaResult<()> {
+        let (config, priority) = (self.config.clone(), self.priority.clone());
+        let (package, artifact) = (package.to_string(), artifact.to_string());
+        tokio::task::spawn_blocking(move || match &config.builder_server_id {
+            Some(server_id) => build_remotely(&config, &priority, server_id, &package, &artifact)
+                .map_err(|e| AureliaError::Deployment(format!("{:#}", e))),
+            None => build_locally(&priority, &package),
+        })
+        .await
+        .map_err(|e| AureliaError::Deployment(e.to_string()))?
+    }
+}
+
+fn build_locally(priority: &ProcessPriority, package: &str) -> AureliaResult<()> {
+    let output = priority
+        .command("cargo")
+        .args(["build", "-p", package, "--release"])
+        .output()?;
+    if !output.status.success() {
+        return Err(AureliaError::Deployment(format!(
+            "cargo build failed:\n{}\n{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    Ok(())
+}
+
+fn build_remotely(
+    config: &BuildConfig,
+    priority: &ProcessPriority,
+    server_id: &str,
+    package: &str,
+    artifact: &str,
+) -> anyhow::Result<()> {
+    let servers = ServerConfig::from_file(&config.servers_config)?;
+    let server = servers
+        .get_server_by_id(server_id)
+        .ok_or_else(|| anyhow::anyhow!("Builder server {} is not configured", server_id))?;
+    let timeout = Duration::from_secs(config.timeout_seconds);
+    let deployer = SshDeployer::new()
+        .with_strict_host_key_checking(servers.ssh_config.strict_host_key_checking)
+        .with_timeouts(SshTimeouts {
+            connect: servers.default_settings.ssh_timeouts().connect,
+            // A busy compiler can stay silent for a long time
+            read: timeout,
+            command: timeout,
+        });
+    let mut deployer = DeploymentCommander::connect(deployer, server)?;
+    info!(
+        "[Metamorphosis Engine] Building {} on {}",
+        package, server.name
+    );
+
+    let archive = source_archive(Path::new("."), priority)?;
+    let dir = &config.remote_dir;
+    let result = (|| {
+        deployer.execute_checked(&format!("mkdir -p {:?}", dir))?;
+        deployer.upload_file(&archive, &format!("{}/source.tar.gz", dir))?;
+        // A fresh source tree drops files deleted here; the target directory is kept
+        // outside it so builds stay incremental
+        deployer.execute_checked(&format!(
+            "cd {dir:?} && rm -rf src && mkdir src && tar -xzf source.tar.gz -C src && rm source.tar.gz"
+        ))?;
+        deployer.execute_checked(&format!(
+            "cd {dir:?}/src && CARGO_TARGET_DIR=../target cargo build -p {package} --release"
+        ))?;
+
+        let remote_artifact = format!("{}/{}", dir, artifact);
+        let expected = parse_checksum(
+            &deployer.execute_checked(&format!("sha256sum {:?}", remote_artifact))?,
+        )?;
+        let library = deployer.download_file(&remote_artifact)?;
+        let actual = format!("{:x}", Sha256::digest(&library));
+        if actual != expected {
+            return Err(anyhow::anyhow!(
+                "Fetched {} has SHA-256 {}, the builder reported {}",
+                artifact,
+                actual,
+                expected
+            ));
+        }
+
+        let path = Path::new(artifact);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("fetched");
+        std::fs::write(&tmp, &library)?;
+        std::fs::rename(&tmp, path)?;
+        info!(
+            "[Metamorphosis Engine] Fetched {} ({} bytes, sha256 {})",
+            artifact,
+            library.len(),
+            actual
+        );
+        Ok(())
+    })();
+    deployer.disconnect();
+    std::fs::remove_file(&archive).ok();
+    result
+}
+
+/// Pack the sources and manifests of the workspace at `root`, and nothing else:
+/// the builder is another host and must not receive keys or local config.
+fn source_archive(root: &Path, priority: &ProcessPriority) -> anyhow::Result<PathBuf> {
+    let mut included: Vec<PathBuf> = WORKSPACE_FILES
+        .iter()
+        .map(PathBuf::from)
+        .filter(|path| root.join(path).exists())
+        .collect();
+    let mut crates: Vec<_> = std::fs::read_dir(root)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| PathBuf::from(entry.file_name()))
+        .filter(|dir| root.join(dir).join("Cargo.toml").is_file())
+        .collect();
+    crates.sort();
+    for dir in crates {
+        included.extend(
+            CRATE_FILES
+                .iter()
+                .map(|file| dir.join(file))
+                .filter(|path| root.join(path).exists()),
+        );
+    }
+    if included.is_empty() {
+        return Err(anyhow::anyhow!("No Cargo workspace in {:?}", root));
+    }
+
+    let archive =
+        std::env::temp_dir().join(format!("aurelia-source-{}.tar.gz", std::process::id()));
+    let mut tar = priority.command("tar");
+    tar.arg("-czf").arg(&archive);
+    for excluded in EXCLUDED_FROM_SYNC {
+        tar.arg(format!("--exclude={}", excluded));
+    }
+    let output = tar.arg("-C").arg(root).arg("--").args(&included).output()?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Failed to archive the source tree: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(archive)
+}
+
+fn parse_checksum(sha256sum_output: &str) -> anyhow::Result<String> {
+    sha256sum_output
+        .split_whitespace()
+        .next()
+        .filter(|hash| hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(str::to_lowercase)
+        .ok_or_else(|| anyhow::anyhow!("Unexpected sha256sum output: {}", sha256sum_output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_checksum_is_parsed() {
+        let hash = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+        let output = format!(
+            "{}  aurelia-build/target/release/libstrategy_engine.so\n",
+            hash
+        );
+        assert_eq!(parse_checksum(&output).unwrap(), hash);
+        assert!(parse_checksum("sha256sum: no such file").is_err());
+        assert!(parse_checksum("").is_err());
+
+        let config: BuildConfig =
+            serde_json::from_str(r#"{"builder_server_id": "builder"}"#).unwrap();
+        assert_eq!(config.builder_server_id.as_deref(), Some("builder"));
+        assert_eq!(config.remote_dir, "aurelia-build");
+    }
+
+    #[test]
+    fn test_secrets_in_the_tree_are_not_archived() {
+        let root = std::env::temp_dir().join(format!("aurelia-farm-{}", std::process::id()));
+        let files = [
+            "Cargo.toml",
+            ".env",
+            ".env.production",
+            "config/secrets/fleet_config_key",
+            "data/trades.jsonl",
+            "strategy_engine/Cargo.toml",
+            "strategy_engine/src/lib.rs",
+            "strategy_engine/src/.env",
+            "strategy_engine/src/server.pem",
+        ];
+        for file in files {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "x").unwrap();
+        }
+
+        let archive = source_archive(&root, &ProcessPriority::default()).unwrap();
+        let listing = std::process::Command::new("tar")
+            .arg("-tzf")
+            .arg(&archive)
+            .output()
+            .unwrap();
+        let mut archived: Vec<_> = String::from_utf8(listing.stdout)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.ends_with('/'))
+            .map(str::to_string)
+            .collect();
+        archived.sort();
+        assert_eq!(
+            archived,
+            vec![
+                "Cargo.toml",
+                "strategy_engine/Cargo.toml",
+                "strategy_engine/src/lib.rs"
+            ]
+        );
+
+        std::fs::remove_file(archive).ok();
+        std::fs::remove_dir_all(root).ok();
+    }
+}
@@ -1,11 +1,21 @@
-use common::{AppEvent, EventSender};
+pub mod compile_farm;
+pub mod policy;
+
+pub use compile_farm::{BuildConfig, CompileFarm};
+pub use policy::MutationPolicy;
+
+use common::{
+    AppEvent, EventSender, MutationRejected, ProcessPriority, StrategyParamUpdate, Topic,
+};
 use std::fs;
-use std::process::Command;
 use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::time;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 const STRATEGY_ENGINE_SOURCE_PATH: &str = "strategy_engine/src/lib.rs";
+/// Tuned live instead of by rewriting the source; see `strategy_engine::params`.
+const INTERVAL_PARAM: &str = "interval_seconds";
 #[cfg(target_os = "linux")]
 const STRATEGY_ENGINE_LIB_PATH: &str = "target/release/libstrategy_engine.so";
 #[cfg(target_os = "macos")]
@@ -15,20 +25,57 @@ const STRATEGY_ENGINE_LIB_PATH: &str = "target/release/strategy_engine.dll";
 
 pub struct MetamorphosisEngine {
     tx: EventSender,
+    policy: MutationPolicy,
+    compile_farm: CompileFarm,
 }
 
 impl MetamorphosisEngine {
     pub fn new(tx: EventSender) -> Self {
-        Self { tx }
+        Self {
+            tx,
+            policy: MutationPolicy::default(),
+            compile_farm: CompileFarm::default(),
+        }
+    }
+
+    /// Check mutations against `policy` instead of the default one
+    pub fn with_policy(mut self, policy: MutationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Build mutations as `config` says, on a builder server if one is set
+    pub fn with_build_config(mut self, config: BuildConfig) -> Self {
+        self.compile_farm = self.compile_farm.with_config(config);
+        self
+    }
+
+    /// Run local builds with `priority` so they do not slow down trading
+    pub fn with_priority(mut self, priority: ProcessPriority) -> Self {
+        self.compile_farm = self.compile_farm.with_priority(priority);
+        self
     }
 
     pub async fn run(&mut self) {
         info!("[Metamorphosis Engine] Starting self-evolution loop...");
         // For this demo, we'll only try to evolve once, 30 seconds after startup.
         time::sleep(Duration::from_secs(30)).await;
+        self.tune(INTERVAL_PARAM, 30.0).await;
         self.evolve().await;
     }
 
+    /// Adjust a strategy parameter in the running module; no recompilation needed.
+    async fn tune(&self, name: &str, value: f64) {
+        info!("[Metamorphosis Engine] Tuning {} to {}", name, value);
+        let event = AppEvent::StrategyParamUpdate(StrategyParamUpdate {
+            name: name.to_string(),
+            value,
+        });
+        if let Err(e) = self.tx.send_control(event).await {
+            error!("Failed to send StrategyParamUpdate event: {}", e);
+        }
+    }
+
     async fn evolve(&self) {
         info!("[Metamorphosis Engine] Waking up to consider evolution...");
 
@@ -48,35 +95,78 @@ impl MetamorphosisEngine {
             return;
         }
 
-        // 3. Write the new code back
+        // 3. Refuse mutations the policy does not allow, before anything is compiled
+        let violations = self
+            .policy
+            .check(STRATEGY_ENGINE_SOURCE_PATH, &source_code, &new_code);
+        if !violations.is_empty() {
+            warn!(
+                "[Metamorphosis Engine] Mutation rejected by policy: {}",
+                violations.join("; ")
+            );
+            let event = AppEvent::MutationRejected(MutationRejected {
+                path: STRATEGY_ENGINE_SOURCE_PATH.to_string(),
+                violations,
+                timestamp: chrono::Utc::now(),
+            });
+            if self.tx.send(event).is_err() {
+                error!("No subscribers for MutationRejected event");
+            }
+            return;
+        }
+
+        // 4. Write the new code back
         if let Err(e) = fs::write(STRATEGY_ENGINE_SOURCE_PATH, new_code) {
             error!("Failed to write new strategy engine source: {}", e);
             return;
         }
         info!("[Metamorphosis Engine] Source code modified. Recompiling...");
 
-        // 4. Recompile the crate
-        let output = Command::new("cargo")
-            .args(["build", "-p", "strategy_engine", "--release"])
-            .output()
-            .expect("Failed to execute cargo build");
-
-        if !output.status.success() {
-            error!(
-                "Failed to recompile strategy engine: {}\n{}",
-                String::from_utf8_lossy(&output.stdout),
-                String::from_utf8_lossy(&output.stderr)
-            );
+        // 5. Recompile the crate, here or on the builder server
+        if let Err(e) = self
+            .compile_farm
+            .build("strategy_engine", STRATEGY_ENGINE_LIB_PATH)
+            .await
+        {
+            error!("Failed to recompile strategy engine: {}", e);
             // Optional: revert the source code change here
             return;
         }
 
-        info!("Recompilation successful. Notifying kernel for hot-swap.");
+        info!("Recompilation successful. Submitting candidate for a shadow trial.");
 
-        // 5. Notify the kernel
-        let event = AppEvent::ModuleReadyForHotSwap(STRATEGY_ENGINE_LIB_PATH.to_string());
-        if let Err(e) = self.tx.send(event) {
-            error!("Failed to send ModuleReadyForHotSwap event: {}", e);
+        // 6. Let the kernel shadow-run the candidate; it goes live only if it does better
+        let mut reports = self
+            .tx
+            .subscribe_as("metamorphosis_engine", &[Topic::System]);
+        let event = AppEvent::CandidateModuleReady(STRATEGY_ENGINE_LIB_PATH.to_string());
+        if let Err(e) = self.tx.send_control(event).await {
+            error!("Failed to send CandidateModuleReady event: {}", e);
+            return;
+        }
+
+        // 7. Wait for the verdict
+        loop {
+            match reports.recv().await {
+                Ok(AppEvent::ShadowTrialCompleted(report))
+                    if report.candidate == STRATEGY_ENGINE_LIB_PATH =>
+                {
+                    if report.promoted {
+                        info!(
+                            "[Metamorphosis Engine] Evolution promoted: {}",
+                            report.reason
+                        );
+                    } else {
+                        info!(
+                            "[Metamorphosis Engine] Evolution rejected: {}",
+                            report.reason
+                        );
+                    }
+                    return;
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return,
+            }
         }
     }
 }
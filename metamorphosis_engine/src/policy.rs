@@ -0,0 +1,483 @@
+//! Limits on what metamorphosis may change in its own source.
+//!
+//! Every mutation is checked before it is written to disk and compiled. It must
+//! touch an allowed file that is not denied, stay within `max_diff_lines`, and
+//! must not add uses of banned APIs. Banned patterns are matched against the
+//! parsed source rather than its text: every path with `use` aliases resolved,
+//! the paths inside macro bodies, macro names, attributes, and the `unsafe` and
+//! `extern` keywords. `use std::process as p;` followed by `p::Command` is thus
+//! still `std::process::Command`, while comments and string literals never
+//! match. Uses are counted in the whole file, so code that already used an API
+//! before the mutation does not block it.
+
+use common::AureliaResult;
+use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use syn::visit::{self, Visit};
+
+pub const MUTATION_POLICY_PATH: &str = "config/mutation_policy.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MutationPolicy {
+    /// Files metamorphosis may change; entries ending in `/` allow a whole directory
+    pub allowed_paths: Vec<String>,
+    /// Files it may never change, as paths, directories ending in `/`, or bare
+    /// file names matched in any directory
+    pub denied_paths: Vec<String>,
+    /// Code the mutated source may not gain
+    pub banned_patterns: Vec<String>,
+    /// Most lines a mutation may add and remove in total
+    pub max_diff_lines: usize,
+}
+
+impl Default for MutationPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_paths: vec!["strategy_engine/src/".to_string()],
+            denied_paths: vec!["Cargo.toml".to_string(), "build.rs".to_string()],
+            banned_patterns: [
+                "std::process",
+                "Command::new",
+                "std::net",
+                "tokio::net",
+                "TcpListener",
+                "UdpSocket",
+                "std::fs",
+                "std::env",
+                "unsafe",
+                "extern",
+                "#[link",
+                "#[no_mangle]",
+                "asm!",
+                "include!",
+                "include_str!",
+                "include_bytes!",
+            ]
+            .into_iter()
+            .map(str::to_string)
+            .collect(),
+            max_diff_lines: 50,
+        }
+    }
+}
+
+impl MutationPolicy {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Every way in which changing `path` from `original` to `mutated` breaks the
+    /// policy; empty if the mutation may be compiled.
+    pub fn check(&self, path: &str, original: &str, mutated: &str) -> Vec<String> {
+        let mut violations = Vec::new();
+        let path = path.replace('\\', "/");
+        if !self.allowed_paths.iter().any(|p| matches_path(p, &path)) {
+            violations.push(format!("{} is not in the allowed paths", path));
+        }
+        if let Some(denied) = self.denied_paths.iter().find(|p| matches_path(p, &path)) {
+            violations.push(format!("{} is denied by {}", path, denied));
+        }
+
+        let changed = diff_lines(original, mutated);
+        if changed > self.max_diff_lines {
+            violations.push(format!(
+                "{} lines changed, at most {} allowed",
+                changed, self.max_diff_lines
+            ));
+        }
+
+        let mutated = match syn::parse_file(mutated) {
+            Ok(file) => CodeUses::of(&file),
+            Err(e) => {
+                violations.push(format!("{} does not parse as Rust: {}", path, e));
+                return violations;
+            }
+        };
+        // Source that never parsed has no uses to grandfather in
+        let original = syn::parse_file(original)
+            .map(|file| CodeUses::of(&file))
+            .unwrap_or_default();
+        for pattern in &self.banned_patterns {
+            let compact_pattern = compact(pattern);
+            if compact_pattern.is_empty() {
+                continue;
+            }
+            if mutated.count(&compact_pattern) > original.count(&compact_pattern) {
+                violations.push(format!("adds banned code `{}`", pattern));
+            }
+        }
+        violations
+    }
+}
+
+/// Everything in a source file a banned pattern can match, one entry per use.
+#[derive(Default)]
+struct CodeUses {
+    /// Names brought in by `use` and `extern crate` → the paths they stand for
+    aliases: HashMap<String, String>,
+    uses: Vec<String>,
+}
+
+impl CodeUses {
+    fn of(file: &syn::File) -> Self {
+        let mut uses = Self::default();
+        // The first pass learns the aliases, which may be declared after their use
+        uses.visit_file(file);
+        uses.uses.clear();
+        uses.visit_file(file);
+        uses
+    }
+
+    /// Uses containing `pattern`, which has its whitespace removed
+    fn count(&self, pattern: &str) -> usize {
+        self.uses.iter().filter(|u| u.contains(pattern)).count()
+    }
+
+    fn record(&mut self, code: String) {
+        self.uses.push(compact(&code));
+    }
+
+    /// Record a path given by its segments, with an aliased first segment expanded
+    fn record_path(&mut self, segments: &[String]) {
+        let Some((first, rest)) = segments.split_first() else {
+            return;
+        };
+        let mut path = self.aliases.get(first).unwrap_or(first).clone();
+        for segment in rest {
+            path.push_str("::");
+            path.push_str(segment);
+        }
+        self.record(path);
+    }
+
+    fn use_tree(&mut self, prefix: &str, tree: &syn::UseTree) {
+        let join = |name: &str| match prefix {
+            "" => name.to_string(),
+            _ if name == "self" => prefix.to_string(),
+            _ => format!("{}::{}", prefix, name),
+        };
+        match tree {
+            syn::UseTree::Path(path) => self.use_tree(&join(&path.ident.to_string()), &path.tree),
+            syn::UseTree::Name(name) => {
+                let full = join(&name.ident.to_string());
+                let alias = full.rsplit("::").next().unwrap_or(&full).to_string();
+                self.aliases.insert(alias, full.clone());
+                self.record(full);
+            }
+            syn::UseTree::Rename(rename) => {
+                let full = join(&rename.ident.to_string());
+                self.aliases.insert(rename.rename.to_string(), full.clone());
+                self.record(full);
+            }
+            syn::UseTree::Glob(_) => self.record(join("*")),
+            syn::UseTree::Group(group) => {
+                for tree in &group.items {
+                    self.use_tree(prefix, tree);
+                }
+            }
+        }
+    }
+
+    /// Paths, macro names, attributes and keywords in tokens syn leaves unparsed,
+    /// such as macro arguments and `macro_rules!` bodies
+    fn tokens(&mut self, tokens: TokenStream) {
+        let mut segments: Vec<String> = Vec::new();
+        // Whether the last token was `::`, so the next identifier extends the path
+        let mut joined = false;
+        let mut trees = tokens.into_iter().peekable();
+        while let Some(tree) = trees.next() {
+            match tree {
+                TokenTree::Ident(ident) => {
+                    let ident = ident.to_string();
+                    if !joined {
+                        self.record_path(&segments);
+                        segments.clear();
+                    }
+                    if ident == "unsafe" || ident == "extern" {
+                        self.record(ident.clone());
+                    }
+                    segments.push(ident);
+                    joined = false;
+                }
+                TokenTree::Punct(punct)
+                    if punct.as_char() == ':' && punct.spacing() == Spacing::Joint =>
+                {
+                    trees.next();
+                    joined = true;
+                }
+                TokenTree::Punct(punct) => {
+                    if punct.as_char() == '!' && !joined && !segments.is_empty() {
+                        self.record(format!("{}!", segments.join("::")));
+                    }
+                    if punct.as_char() == '#' {
+                        if let Some(TokenTree::Group(group)) = trees.peek() {
+                            if group.delimiter() == Delimiter::Bracket {
+                                self.record(format!("#[{}]", group.stream()));
+                            }
+                        }
+                    }
+                    self.record_path(&segments);
+                    segments.clear();
+                    joined = false;
+                }
+                TokenTree::Group(group) => {
+                    self.record_path(&segments);
+                    segments.clear();
+                    joined = false;
+                    self.tokens(group.stream());
+                }
+                TokenTree::Literal(_) => {
+                    self.record_path(&segments);
+                    segments.clear();
+                    joined = false;
+                }
+            }
+        }
+        self.record_path(&segments);
+    }
+}
+
+impl<'ast> Visit<'ast> for CodeUses {
+    fn visit_item_use(&mut self, item: &'ast syn::ItemUse) {
+        self.use_tree("", &item.tree);
+        visit::visit_item_use(self, item);
+    }
+
+    fn visit_item_extern_crate(&mut self, item: &'ast syn::ItemExternCrate) {
+        if let Some((_, rename)) = &item.rename {
+            self.aliases
+                .insert(rename.to_string(), item.ident.to_string());
+        }
+        self.record(format!("extern crate {}", item.ident));
+        visit::visit_item_extern_crate(self, item);
+    }
+
+    fn visit_path(&mut self, path: &'ast syn::Path) {
+        let segments: Vec<String> = path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect();
+        self.record_path(&segments);
+        visit::visit_path(self, path);
+    }
+
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        let segments: Vec<String> = mac
+            .path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect();
+        self.record(format!("{}!", segments.join("::")));
+        self.tokens(mac.tokens.clone());
+        visit::visit_macro(self, mac);
+    }
+
+    fn visit_attribute(&mut self, attr: &'ast syn::Attribute) {
+        let path = attr.path();
+        let segments: Vec<String> = path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect();
+        let path = segments.join("::");
+        match &attr.meta {
+            syn::Meta::Path(_) => self.record(format!("#[{}]", path)),
+            syn::Meta::List(list) => self.record(format!("#[{}({})]", path, list.tokens)),
+            syn::Meta::NameValue(_) => self.record(format!("#[{}=", path)),
+        }
+        if let syn::Meta::List(list) = &attr.meta {
+            self.tokens(list.tokens.clone());
+        }
+        visit::visit_attribute(self, attr);
+    }
+
+    fn visit_abi(&mut self, abi: &'ast syn::Abi) {
+        self.record("extern".to_string());
+        visit::visit_abi(self, abi);
+    }
+
+    fn visit_signature(&mut self, sig: &'ast syn::Signature) {
+        if sig.unsafety.is_some() {
+            self.record("unsafe".to_string());
+        }
+        visit::visit_signature(self, sig);
+    }
+
+    fn visit_expr_unsafe(&mut self, expr: &'ast syn::ExprUnsafe) {
+        self.record("unsafe".to_string());
+        visit::visit_expr_unsafe(self, expr);
+    }
+
+    fn visit_item_impl(&mut self, item: &'ast syn::ItemImpl) {
+        if item.unsafety.is_some() {
+            self.record("unsafe".to_string());
+        }
+        visit::visit_item_impl(self, item);
+    }
+
+    fn visit_item_trait(&mut self, item: &'ast syn::ItemTrait) {
+        if item.unsafety.is_some() {
+            self.record("unsafe".to_string());
+        }
+        visit::visit_item_trait(self, item);
+    }
+
+    fn visit_item_foreign_mod(&mut self, item: &'ast syn::ItemForeignMod) {
+        if item.unsafety.is_some() {
+            self.record("unsafe".to_string());
+        }
+        visit::visit_item_foreign_mod(self, item);
+    }
+
+    fn visit_type_bare_fn(&mut self, ty: &'ast syn::TypeBareFn) {
+        if ty.unsafety.is_some() {
+            self.record("unsafe".to_string());
+        }
+        visit::visit_type_bare_fn(self, ty);
+    }
+}
+
+fn matches_path(pattern: &str, path: &str) -> bool {
+    if pattern.ends_with('/') {
+        return path.starts_with(pattern);
+    }
+    path == pattern || (!pattern.contains('/') && path.rsplit('/').next() == Some(pattern))
+}
+
+fn compact(source: &str) -> String {
+    source.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// Lines removed plus lines added, ignoring order and indentation.
+fn diff_lines(original: &str, mutated: &str) -> usize {
+    let mut counts: HashMap<&str, i64> = HashMap::new();
+    for line in original.lines() {
+        *counts.entry(line.trim()).or_default() += 1;
+    }
+    for line in mutated.lines() {
+        *counts.entry(line.trim()).or_default() -= 1;
+    }
+    counts
+        .values()
+        .map(|count| count.unsigned_abs() as usize)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "pub unsafe extern \"C\" fn hook() {}\n\
+                          fn interval() -> Duration {\n    Duration::from_secs(60)\n}\n";
+
+    #[test]
+    fn test_policy_rejects_banned_code_and_foreign_files() {
+        let policy = MutationPolicy::default();
+        let path = "strategy_engine/src/lib.rs";
+
+        // Existing FFI hooks do not block a harmless change
+        let tuned = SOURCE.replace("from_secs(60)", "from_secs(30)");
+        assert!(policy.check(path, SOURCE, &tuned).is_empty());
+        assert_eq!(diff_lines(SOURCE, &tuned), 2);
+
+        let spawning = tuned.replace(
+            "Duration::from_secs(30)",
+            "std::process::\n        Command::new(\"sh\");\n    Duration::from_secs(30)",
+        );
+        let violations = policy.check(path, SOURCE, &spawning);
+        assert!(violations.iter().any(|v| v.contains("std::process")));
+        assert!(violations.iter().any(|v| v.contains("Command::new")));
+
+        let listening = format!(
+            "{}\nfn open() {{ std::net::TcpListener::bind(\"0.0.0.0:1\"); }}\n",
+            SOURCE
+        );
+        assert_eq!(policy.check(path, SOURCE, &listening).len(), 2);
+
+        assert_eq!(policy.check("kernel/src/main.rs", SOURCE, &tuned).len(), 1);
+        assert_eq!(
+            policy
+                .check("strategy_engine/src/build.rs", SOURCE, &tuned)
+                .len(),
+            1
+        );
+
+        let rewritten: String = (0..60)
+            .map(|i| format!("const C{}: u32 = {};\n", i, i))
+            .collect();
+        let violations = policy.check(path, SOURCE, &rewritten);
+        assert!(violations.iter().any(|v| v.contains("lines changed")));
+    }
+
+    #[test]
+    fn test_policy_sees_through_aliases_and_macros() {
+        let policy = MutationPolicy::default();
+        let path = "strategy_engine/src/lib.rs";
+        let banned = |mutated: &str| {
+            policy
+                .check(path, SOURCE, &format!("{}{}", SOURCE, mutated))
+                .into_iter()
+                .filter(|v| v.contains("banned"))
+                .collect::<Vec<_>>()
+        };
+
+        let aliased = banned(
+            "use std::process as p;
+fn run() { p::Command::new(\"sh\"); }
+",
+        );
+        assert!(aliased.iter().any(|v| v.contains("std::process")));
+        assert!(aliased.iter().any(|v| v.contains("Command::new")));
+
+        let imported = banned(
+            "use std::{fs::{self}, env};
+fn read() { fs::read(env::args().next().unwrap()); }
+",
+        );
+        assert!(imported.iter().any(|v| v.contains("std::fs")));
+        assert!(imported.iter().any(|v| v.contains("std::env")));
+
+        let hidden = banned(
+            "macro_rules! quit { () => { ::std::process::exit(0) }; }
+",
+        );
+        assert!(hidden.iter().any(|v| v.contains("std::process")));
+        assert!(!banned(
+            "fn home() { println!(\"{:?}\", std::env::var(\"HOME\")); }
+"
+        )
+        .is_empty());
+        assert!(!banned(
+            "fn f() { let _ = include_str!(\"/etc/passwd\"); }
+"
+        )
+        .is_empty());
+        assert!(!banned(
+            "#[no_mangle]
+pub fn exported() {}
+"
+        )
+        .is_empty());
+
+        // Mentions in comments and strings are not code
+        assert!(banned(
+            "// no std::fs here
+const NOTE: &str = \"unsafe std::net\";
+"
+        )
+        .is_empty());
+
+        let violations = policy.check(path, SOURCE, "fn broken( {");
+        assert!(violations.iter().any(|v| v.contains("does not parse")));
+    }
+}
@@ -0,0 +1,278 @@
+//! Local admin socket.
+//!
+//! Operators on the box manage the agent through a Unix domain socket (or a
+//! TCP port bound to localhost where there are none) even when the HTTP API is
+//! disabled. Each request is one line of JSON naming a command, e.g.
+//! `{"command": "pause", "reason": "maintenance"}`, and is answered with one
+//! line: `{"ok": true, "result": ...}` or `{"ok": false, "error": "..."}`.
+//!
+//! - `status` - identity, version, uptime, liveness and readiness
+//! - `pause` - suspend strategy decisions without closing positions
+//! - `resume` - resume trading after a pause or an emergency flatten
+//! - `dump_state` - the persistent agent state
+//! - `reload_config` - ask the kernel to reload its configuration
+//!
+//! Commands that change the agent are written to the audit log.
+
+use crate::http_server::{AGENT_VERSION, LIVENESS_TIMEOUT};
+use chrono::{DateTime, Utc};
+use common::audit::{self, AuditCategory};
+use common::{AgentIdentity, AppEvent, EventBus, HealthState, StateStore};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tracing::{info, warn};
+
+pub const ADMIN_SOCKET_PATH: &str = "data/admin.sock";
+/// Port on 127.0.0.1 where Unix domain sockets are not available
+pub const ADMIN_TCP_PORT: u16 = 7070;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminAddress {
+    Unix(PathBuf),
+    /// Always bound to localhost
+    Tcp(u16),
+}
+
+impl Default for AdminAddress {
+    fn default() -> Self {
+        if cfg!(unix) {
+            Self::Unix(PathBuf::from(ADMIN_SOCKET_PATH))
+        } else {
+            Self::Tcp(ADMIN_TCP_PORT)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum AdminCommand {
+    Status,
+    Pause {
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    Resume,
+    DumpState,
+    ReloadConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdminResponse {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AdminResponse {
+    fn ok(result: serde_json::Value) -> Self {
+        Self {
+            ok: true,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn error(error: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            result: None,
+            error: Some(error.into()),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AdminSocket {
+    address: AdminAddress,
+    identity: AgentIdentity,
+    health: HealthState,
+    events: Option<EventBus>,
+    state: Option<StateStore>,
+    started_at: DateTime<Utc>,
+}
+
+impl AdminSocket {
+    pub fn new(address: AdminAddress) -> Self {
+        Self {
+            address,
+            identity: AgentIdentity::new_root(),
+            health: HealthState::new(),
+            events: None,
+            state: None,
+            started_at: Utc::now(),
+        }
+    }
+
+    pub fn with_identity(mut self, identity: AgentIdentity) -> Self {
+        self.identity = identity;
+        self
+    }
+
+    pub fn with_health(mut self, health: HealthState) -> Self {
+        self.health = health;
+        self
+    }
+
+    /// Forward `pause`, `resume` and `reload_config` to the kernel over `bus`
+    pub fn with_event_bus(mut self, bus: EventBus) -> Self {
+        self.events = Some(bus);
+        self
+    }
+
+    /// Serve `dump_state` from `state`
+    pub fn with_state_store(mut self, state: StateStore) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    pub async fn run(self) -> anyhow::Result<()> {
+        match &self.address {
+            #[cfg(unix)]
+            AdminAddress::Unix(path) => {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                // A socket file left behind by a previous run would fail the bind
+                let _ = std::fs::remove_file(path);
+                let listener = tokio::net::UnixListener::bind(path)?;
+                info!("Admin socket listening on {}", path.display());
+                loop {
+                    let (stream, _) = listener.accept().await?;
+                    tokio::spawn(self.clone().serve(stream));
+                }
+            }
+            #[cfg(not(unix))]
+            AdminAddress::Unix(path) => {
+                anyhow::bail!(
+                    "Unix domain sockets are not available for {}",
+                    path.display()
+                )
+            }
+            AdminAddress::Tcp(port) => {
+                let listener = tokio::net::TcpListener::bind(("127.0.0.1", *port)).await?;
+                info!("Admin socket listening on 127.0.0.1:{}", port);
+                loop {
+                    let (stream, _) = listener.accept().await?;
+                    tokio::spawn(self.clone().serve(stream));
+                }
+            }
+        }
+    }
+
+    /// Answer each line of `stream` until the client disconnects.
+    async fn serve<S: AsyncRead + AsyncWrite + Unpin>(self, stream: S) {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<AdminCommand>(&line) {
+                Ok(command) => self.handle(command).await,
+                Err(e) => AdminResponse::error(format!("invalid command: {}", e)),
+            };
+            let mut reply = serde_json::to_string(&response).unwrap_or_default();
+            reply.push('\n');
+            if let Err(e) = writer.write_all(reply.as_bytes()).await {
+                warn!("Admin socket client went away: {}", e);
+                return;
+            }
+        }
+    }
+
+    pub async fn handle(&self, command: AdminCommand) -> AdminResponse {
+        match command {
+            AdminCommand::Status => AdminResponse::ok(serde_json::json!({
+                "identity": self.identity,
+                "version": AGENT_VERSION,
+                "started_at": self.started_at,
+                "uptime_seconds": (Utc::now() - self.started_at).num_seconds(),
+                "live": self.health.is_live(LIVENESS_TIMEOUT),
+                "ready": self.health.is_ready(),
+                "components": self.health.components(),
+            })),
+            AdminCommand::DumpState => match &self.state {
+                Some(state) => match serde_json::to_value(state.snapshot()) {
+                    Ok(snapshot) => AdminResponse::ok(snapshot),
+                    Err(e) => AdminResponse::error(e.to_string()),
+                },
+                None => AdminResponse::error("agent state is not attached"),
+            },
+            AdminCommand::Pause { reason } => {
+                let reason = reason.unwrap_or_else(|| "paused from the admin socket".to_string());
+                self.send(AppEvent::PauseTrading(reason)).await
+            }
+            AdminCommand::Resume => self.send(AppEvent::ResumeTrading).await,
+            AdminCommand::ReloadConfig => self.send(AppEvent::ReloadConfig).await,
+        }
+    }
+
+    async fn send(&self, event: AppEvent) -> AdminResponse {
+        let Some(bus) = &self.events else {
+            return AdminResponse::error("event bus is not attached");
+        };
+        let category = match event {
+            AppEvent::ReloadConfig => AuditCategory::ConfigChange,
+            _ => AuditCategory::Order,
+        };
+        audit::record(category, format!("admin_{}", event.kind()), &event);
+        match bus.send_control(event.clone()).await {
+            Ok(_) => AdminResponse::ok(serde_json::to_value(&event).unwrap_or_default()),
+            Err(_) => AdminResponse::error("kernel is not accepting control events"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_commands_are_answered_line_by_line() {
+        let bus = EventBus::new(8);
+        let mut control = bus.subscribe_to(&[common::Topic::Control]);
+        let admin = AdminSocket::new(AdminAddress::Tcp(0))
+            .with_event_bus(bus)
+            .with_state_store(StateStore::in_memory());
+
+        let (client, server) = tokio::io::duplex(4096);
+        tokio::spawn(admin.serve(server));
+        let (reader, mut writer) = tokio::io::split(client);
+        writer
+            .write_all(
+                concat!(
+                    "{\"command\": \"status\"}\n",
+                    "{\"command\": \"dump_state\"}\n",
+                    "{\"command\": \"pause\", \"reason\": \"maintenance\"}\n",
+                    "{\"command\": \"format_disk\"}\n",
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+        let mut replies = BufReader::new(reader).lines();
+        let mut responses = Vec::new();
+        for _ in 0..4 {
+            let line = replies.next_line().await.unwrap().unwrap();
+            responses.push(serde_json::from_str::<AdminResponse>(&line).unwrap());
+        }
+
+        let status = responses[0].result.as_ref().unwrap();
+        assert_eq!(status["version"], AGENT_VERSION);
+        assert!(responses[1].result.as_ref().unwrap().get("funds").is_some());
+        assert!(responses[2].ok);
+        assert!(matches!(
+            control.try_recv(),
+            Ok(AppEvent::PauseTrading(reason)) if reason == "maintenance"
+        ));
+        assert!(!responses[3].ok);
+        assert!(responses[3]
+            .error
+            .as_ref()
+            .unwrap()
+            .starts_with("invalid command"));
+    }
+}
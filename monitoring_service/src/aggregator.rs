@@ -1,6 +1,11 @@
-use std::collections::HashMap;
-use chrono::{DateTime, Utc, Duration};
+use crate::cluster_registry::is_live;
+use crate::http_server::AgentStatus;
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Points kept per agent; at the 5 second collection interval this is about 80 minutes
+const MAX_SERIES_POINTS: usize = 1000;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AggregatedMetrics {
@@ -13,21 +18,30 @@ pub struct AggregatedMetrics {
     pub avg_memory_usage: f32,
     pub max_memory_usage: f32,
     pub min_memory_usage: f32,
-    pub total_tasks_completed: u32,
-    pub total_tasks_failed: u32,
-    pub task_success_rate: f32,
-    pub total_network_rx_gb: f64,
-    pub total_network_tx_gb: f64,
+    /// Agents that sent a heartbeat within the period
     pub availability_percentage: f32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Trading summed over every agent that reported its trading status
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FleetTrading {
+    pub reporting_agents: usize,
+    /// Agents currently receiving market data
+    pub active_agents: usize,
+    pub total_trades: u32,
+    pub successful_trades: u32,
+    pub failed_trades: u32,
+    pub pnl: f64,
+    /// Net asset value by accounting currency
+    pub nav: BTreeMap<String, f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TimeSeriesData {
     pub timestamps: Vec<DateTime<Utc>>,
     pub cpu_usage: Vec<f32>,
     pub memory_usage: Vec<f32>,
-    pub task_throughput: Vec<u32>,
-    pub network_throughput: Vec<f64>,
+    pub disk_usage: Vec<f32>,
 }
 
 pub struct MetricsAggregator {
@@ -45,13 +59,9 @@ impl MetricsAggregator {
         }
     }
 
-    pub fn aggregate(
-        &mut self,
-        agents: &[crate::AgentStatus],
-        period_minutes: u32,
-    ) -> AggregatedMetrics {
+    pub fn aggregate(&mut self, agents: &[AgentStatus], period_minutes: u32) -> AggregatedMetrics {
         let timestamp = Utc::now();
-        
+
         if agents.is_empty() {
             return AggregatedMetrics {
                 timestamp,
@@ -63,11 +73,6 @@ impl MetricsAggregator {
                 avg_memory_usage: 0.0,
                 max_memory_usage: 0.0,
                 min_memory_usage: 0.0,
-                total_tasks_completed: 0,
-                total_tasks_failed: 0,
-                task_success_rate: 0.0,
-                total_network_rx_gb: 0.0,
-                total_network_tx_gb: 0.0,
                 availability_percentage: 0.0,
             };
         }
@@ -84,28 +89,9 @@ impl MetricsAggregator {
         let max_memory = memory_values.iter().cloned().fold(0.0, f32::max);
         let min_memory = memory_values.iter().cloned().fold(100.0, f32::min);
 
-        // Calculate task metrics
-        let total_completed: u32 = agents.iter().map(|a| a.tasks_completed).sum();
-        let total_failed: u32 = agents.iter().map(|a| a.tasks_failed).sum();
-        let task_success_rate = if total_completed + total_failed > 0 {
-            (total_completed as f32 / (total_completed + total_failed) as f32) * 100.0
-        } else {
-            0.0
-        };
-
-        // Calculate network metrics (convert to GB)
-        let total_rx_gb: f64 = agents.iter()
-            .map(|a| a.network_rx_bytes as f64 / 1_073_741_824.0)
-            .sum();
-        let total_tx_gb: f64 = agents.iter()
-            .map(|a| a.network_tx_bytes as f64 / 1_073_741_824.0)
-            .sum();
-
         // Calculate availability
         let cutoff = timestamp - Duration::minutes(period_minutes as i64);
-        let available_agents = agents.iter()
-            .filter(|a| a.last_heartbeat > cutoff)
-            .count();
+        let available_agents = agents.iter().filter(|a| a.last_heartbeat > cutoff).count();
         let availability = (available_agents as f32 / agents.len() as f32) * 100.0;
 
         let metrics = AggregatedMetrics {
@@ -118,11 +104,6 @@ impl MetricsAggregator {
             avg_memory_usage: avg_memory,
             max_memory_usage: max_memory,
             min_memory_usage: min_memory,
-            total_tasks_completed: total_completed,
-            total_tasks_failed: total_failed,
-            task_success_rate,
-            total_network_rx_gb: total_rx_gb,
-            total_network_tx_gb: total_tx_gb,
             availability_percentage: availability,
         };
 
@@ -132,38 +113,26 @@ impl MetricsAggregator {
 
         // Update time series for each agent
         for agent in agents {
-            self.update_time_series(&agent.agent_id, &agent);
+            self.update_time_series(agent, timestamp);
         }
 
         metrics
     }
 
-    fn update_time_series(&mut self, agent_id: &str, agent: &crate::AgentStatus) {
-        let entry = self.time_series.entry(agent_id.to_string()).or_insert_with(|| {
-            TimeSeriesData {
-                timestamps: Vec::new(),
-                cpu_usage: Vec::new(),
-                memory_usage: Vec::new(),
-                task_throughput: Vec::new(),
-                network_throughput: Vec::new(),
-            }
-        });
-
-        entry.timestamps.push(Utc::now());
+    fn update_time_series(&mut self, agent: &AgentStatus, timestamp: DateTime<Utc>) {
+        let entry = self.time_series.entry(agent.agent_id.clone()).or_default();
+
+        entry.timestamps.push(timestamp);
         entry.cpu_usage.push(agent.cpu_usage);
         entry.memory_usage.push(agent.memory_usage);
-        entry.task_throughput.push(agent.tasks_completed);
-        
-        let network_throughput = (agent.network_rx_bytes + agent.network_tx_bytes) as f64 / 1_048_576.0; // MB/s
-        entry.network_throughput.push(network_throughput);
-
-        // Keep only last 1000 points
-        if entry.timestamps.len() > 1000 {
-            entry.timestamps.drain(0..100);
-            entry.cpu_usage.drain(0..100);
-            entry.memory_usage.drain(0..100);
-            entry.task_throughput.drain(0..100);
-            entry.network_throughput.drain(0..100);
+        entry.disk_usage.push(agent.disk_usage);
+
+        if entry.timestamps.len() > MAX_SERIES_POINTS {
+            let excess = entry.timestamps.len() - MAX_SERIES_POINTS;
+            entry.timestamps.drain(0..excess);
+            entry.cpu_usage.drain(0..excess);
+            entry.memory_usage.drain(0..excess);
+            entry.disk_usage.drain(0..excess);
         }
     }
 
@@ -174,7 +143,8 @@ impl MetricsAggregator {
 
     pub fn get_history(&self, hours: u32) -> Vec<AggregatedMetrics> {
         let cutoff = Utc::now() - Duration::hours(hours as i64);
-        self.history.iter()
+        self.history
+            .iter()
             .filter(|m| m.timestamp > cutoff)
             .cloned()
             .collect()
@@ -186,24 +156,26 @@ impl MetricsAggregator {
 
     pub fn get_summary_stats(&self, hours: u32) -> SummaryStatistics {
         let history = self.get_history(hours);
-        
+
         if history.is_empty() {
             return SummaryStatistics::default();
         }
 
-        let avg_cpu: f32 = history.iter().map(|m| m.avg_cpu_usage).sum::<f32>() / history.len() as f32;
-        let avg_memory: f32 = history.iter().map(|m| m.avg_memory_usage).sum::<f32>() / history.len() as f32;
-        let total_tasks: u32 = history.iter().map(|m| m.total_tasks_completed).sum();
-        let avg_availability: f32 = history.iter().map(|m| m.availability_percentage).sum::<f32>() / history.len() as f32;
-        
+        let count = history.len() as f32;
         SummaryStatistics {
             period_hours: hours,
-            avg_cpu_usage: avg_cpu,
-            avg_memory_usage: avg_memory,
-            total_tasks_completed: total_tasks,
-            avg_availability: avg_availability,
+            avg_cpu_usage: history.iter().map(|m| m.avg_cpu_usage).sum::<f32>() / count,
+            avg_memory_usage: history.iter().map(|m| m.avg_memory_usage).sum::<f32>() / count,
+            avg_availability: history
+                .iter()
+                .map(|m| m.availability_percentage)
+                .sum::<f32>()
+                / count,
             peak_cpu_usage: history.iter().map(|m| m.max_cpu_usage).fold(0.0, f32::max),
-            peak_memory_usage: history.iter().map(|m| m.max_memory_usage).fold(0.0, f32::max),
+            peak_memory_usage: history
+                .iter()
+                .map(|m| m.max_memory_usage)
+                .fold(0.0, f32::max),
         }
     }
 }
@@ -213,8 +185,116 @@ pub struct SummaryStatistics {
     pub period_hours: u32,
     pub avg_cpu_usage: f32,
     pub avg_memory_usage: f32,
-    pub total_tasks_completed: u32,
     pub avg_availability: f32,
     pub peak_cpu_usage: f32,
     pub peak_memory_usage: f32,
-}
\ No newline at end of file
+}
+
+/// Sum the trading status the live agents reported
+pub fn fleet_trading<'a>(agents: impl IntoIterator<Item = &'a AgentStatus>) -> FleetTrading {
+    let mut fleet = FleetTrading::default();
+    for trading in agents
+        .into_iter()
+        .filter(|a| is_live(a))
+        .filter_map(|a| a.trading.as_ref())
+    {
+        fleet.reporting_agents += 1;
+        fleet.active_agents += usize::from(trading.active);
+        fleet.total_trades += trading.total_trades;
+        fleet.successful_trades += trading.successful_trades;
+        fleet.failed_trades += trading.failed_trades;
+        fleet.pnl += trading.pnl;
+        if let Some(nav) = &trading.nav {
+            *fleet.nav.entry(nav.currency.clone()).or_default() += nav.total;
+        }
+    }
+    fleet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_server::TradingStatus;
+    use common::NetAssetValue;
+
+    fn agent(id: &str, cpu: f32, heartbeat_minutes_ago: i64) -> AgentStatus {
+        AgentStatus {
+            agent_id: id.to_string(),
+            hostname: id.to_string(),
+            ip_address: "127.0.0.1".to_string(),
+            status: "Running".to_string(),
+            cpu_usage: cpu,
+            memory_usage: 50.0,
+            disk_usage: 10.0,
+            uptime_seconds: 0,
+            last_heartbeat: Utc::now() - Duration::minutes(heartbeat_minutes_ago),
+            version: "0.1.0".to_string(),
+            parent_id: None,
+            generation: 0,
+            deployed_at: None,
+            trading: None,
+        }
+    }
+
+    #[test]
+    fn test_fleet_trading_sums_reporting_agents() {
+        let trading = |trades: u32, pnl: f64, nav: Option<f64>| TradingStatus {
+            active: true,
+            total_trades: trades,
+            successful_trades: trades,
+            pnl,
+            nav: nav.map(|total| NetAssetValue {
+                currency: "USDT".to_string(),
+                total,
+                assets: Vec::new(),
+                unpriced: Vec::new(),
+                fiat: None,
+                timestamp: Utc::now(),
+            }),
+            ..TradingStatus::default()
+        };
+        let primary = AgentStatus {
+            trading: Some(trading(4, 12.5, Some(1000.0))),
+            ..agent("primary", 10.0, 0)
+        };
+        let replica = AgentStatus {
+            trading: Some(trading(2, -2.5, Some(500.0))),
+            ..agent("replica-1", 10.0, 0)
+        };
+        // Replicas from before trading was reported are left out
+        let silent = agent("replica-2", 10.0, 0);
+        // And so are replicas that stopped reporting
+        let stale = AgentStatus {
+            trading: Some(trading(9, 100.0, Some(9000.0))),
+            ..agent("replica-3", 10.0, 5)
+        };
+
+        let fleet = fleet_trading([&primary, &replica, &silent, &stale]);
+        assert_eq!(fleet.reporting_agents, 2);
+        assert_eq!(fleet.active_agents, 2);
+        assert_eq!(fleet.total_trades, 6);
+        assert_eq!(fleet.pnl, 10.0);
+        assert_eq!(fleet.nav.get("USDT"), Some(&1500.0));
+    }
+
+    #[test]
+    fn test_aggregate_builds_history_and_series() {
+        let mut aggregator = MetricsAggregator::new(1);
+        let agents = vec![agent("a", 20.0, 0), agent("b", 60.0, 10)];
+
+        let metrics = aggregator.aggregate(&agents, 1);
+        assert_eq!(metrics.agent_count, 2);
+        assert_eq!(metrics.avg_cpu_usage, 40.0);
+        assert_eq!(metrics.max_cpu_usage, 60.0);
+        assert_eq!(metrics.availability_percentage, 50.0);
+
+        aggregator.aggregate(&agents, 1);
+        assert_eq!(aggregator.get_history(1).len(), 2);
+        assert_eq!(
+            aggregator.get_time_series("b").unwrap().cpu_usage,
+            vec![60.0, 60.0]
+        );
+        assert!(aggregator.get_time_series("c").is_none());
+        assert_eq!(aggregator.get_summary_stats(1).peak_cpu_usage, 60.0);
+    }
+}
@@ -0,0 +1,73 @@
+use crate::http_server::{AgentStatus, MonitoringHttpService};
+use anyhow::Result;
+use autonomy_core::ClusterRegistry;
+use chrono::{Duration, Utc};
+use common::FleetFunds;
+
+/// Agents that have not reported for this many seconds no longer count towards the fleet
+const FLEET_HEARTBEAT_TIMEOUT_SECS: i64 = 60;
+
+/// Whether `agent` reported recently enough to count towards the fleet
+pub(crate) fn is_live(agent: &AgentStatus) -> bool {
+    agent.last_heartbeat > Utc::now() - Duration::seconds(FLEET_HEARTBEAT_TIMEOUT_SECS)
+}
+
+fn live_agents(agents: &[AgentStatus]) -> usize {
+    agents.iter().filter(|a| is_live(a)).count()
+}
+
+/// Summed net asset value of the live agents other than `own_id` that
+/// reported one, `None` if none did
+pub(crate) fn fleet_funds(agents: &[AgentStatus], own_id: &str) -> Option<FleetFunds> {
+    let navs: Vec<f64> = agents
+        .iter()
+        .filter(|a| a.agent_id != own_id && is_live(a))
+        .filter_map(|a| Some(a.trading.as_ref()?.nav.as_ref()?.total))
+        .collect();
+    (!navs.is_empty()).then(|| FleetFunds {
+        replicas: navs.len(),
+        funds: navs.iter().sum(),
+        timestamp: Utc::now(),
+    })
+}
+
+/// On the primary, the fleet is every agent reporting to its monitoring service
+#[async_trait::async_trait]
+impl ClusterRegistry for MonitoringHttpService {
+    async fn fleet_size(&self) -> Result<usize> {
+        let agents: Vec<AgentStatus> = self.agents.read().await.values().cloned().collect();
+        // The local agent is registered by the metrics loop, which may not have run yet
+        Ok(live_agents(&agents).max(1))
+    }
+}
+
+/// Replicas ask the primary's monitoring API for the fleet size
+pub struct HttpClusterRegistry {
+    primary_url: String,
+    client: reqwest::Client,
+}
+
+impl HttpClusterRegistry {
+    pub fn new(primary_url: impl Into<String>) -> Self {
+        Self {
+            primary_url: primary_url.into(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ClusterRegistry for HttpClusterRegistry {
+    async fn fleet_size(&self) -> Result<usize> {
+        let url = format!("{}/api/agents", self.primary_url.trim_end_matches('/'));
+        let agents: Vec<AgentStatus> = self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(live_agents(&agents))
+    }
+}
@@ -0,0 +1,425 @@
+//! Pushing config from the primary to its replicas.
+//!
+//! Replicas accept config files on `POST /api/config`, write them to their config
+//! directory and ask the kernel to reload. The primary rolls a change out in two
+//! stages: canary replicas first, then the rest once the canaries stayed ready for
+//! the bake period. A replica that was ready before the push and is not ready
+//! after it halts the rollout.
+
+use crate::http_server::MonitoringHttpService;
+use anyhow::Result;
+use autonomy_core::{HealthThresholds, ReplicationStrategy};
+use chrono::{DateTime, Utc};
+use common::host_port;
+use common::strategy_config::StrategyConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// Directory config files are pushed into, relative to the deployment directory
+pub const CONFIG_DIR: &str = "config";
+
+/// Config files that may be pushed or patched at runtime: the ones the kernel
+/// reloads or validates. Identity, secrets and approvals only change on redeploy.
+pub const PUSHABLE_CONFIG_FILES: &[&str] = &[
+    "autonomy.json",
+    "health.json",
+    "replication.json",
+    "strategy.json",
+    "strategy_params.json",
+];
+
+/// Files and version sent from the primary to a replica
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigPush {
+    pub version: String,
+    /// File name in the config directory → contents
+    pub files: BTreeMap<String, String>,
+}
+
+/// The config version a replica last applied
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppliedConfig {
+    pub version: String,
+    pub files: Vec<String>,
+    pub applied_at: DateTime<Utc>,
+}
+
+/// Write pushed files into `dir`, refusing any not in [`PUSHABLE_CONFIG_FILES`]
+pub fn write_config_files(dir: &Path, files: &BTreeMap<String, String>) -> Result<()> {
+    for (name, contents) in files {
+        if !PUSHABLE_CONFIG_FILES.contains(&name.as_str()) {
+            return Err(anyhow::anyhow!("Refusing to write config file {:?}", name));
+        }
+        serde_json::from_str::<serde_json::Value>(contents)
+            .map_err(|e| anyhow::anyhow!("{} is not valid JSON: {}", name, e))?;
+        // Refuse config the agent would not start with or reload
+        let validated = match name.as_str() {
+            "strategy.json" => StrategyConfig::from_json(contents)
+                .map(|_| ())
+                .map_err(Into::into),
+            "replication.json" => serde_json::from_str::<ReplicationStrategy>(contents)
+                .map_err(Into::into)
+                .and_then(|strategy| strategy.validate()),
+            "health.json" => serde_json::from_str::<HealthThresholds>(contents)
+                .map_err(Into::into)
+                .and_then(|thresholds| thresholds.validate()),
+            _ => Ok(()),
+        };
+        validated.map_err(|e: anyhow::Error| anyhow::anyhow!("{} is invalid: {}", name, e))?;
+    }
+
+    std::fs::create_dir_all(dir)?;
+    for (name, contents) in files {
+        // Write then rename so the kernel never reloads a half-written file
+        let path = dir.join(name);
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, contents)?;
+        std::fs::rename(&tmp, &path)?;
+    }
+    Ok(())
+}
+
+/// Overlay the fields in `patch` onto `current`, merging nested objects.
+/// Fields `current` does not have are refused so typos are not silently dropped.
+pub fn apply_patch(
+    current: &serde_json::Value,
+    patch: &serde_json::Value,
+) -> Result<serde_json::Value> {
+    let (Some(current), Some(patch)) = (current.as_object(), patch.as_object()) else {
+        return Err(anyhow::anyhow!("The patch must be a JSON object"));
+    };
+    let mut merged = current.clone();
+    for (field, value) in patch {
+        let Some(existing) = current.get(field) else {
+            return Err(anyhow::anyhow!("Unknown field {:?}", field));
+        };
+        let value = if existing.is_object() && value.is_object() {
+            apply_patch(existing, value).map_err(|e| anyhow::anyhow!("{}: {}", field, e))?
+        } else {
+            value.clone()
+        };
+        merged.insert(field.clone(), value);
+    }
+    Ok(serde_json::Value::Object(merged))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RolloutRequest {
+    /// Defaults to the current time
+    pub version: Option<String>,
+    /// Files from the primary's config directory to push; replicas re-apply
+    /// `strategy_params.json` to the running strategy on reload
+    pub files: Vec<String>,
+    pub canary_count: usize,
+    /// How long canaries must stay ready before the rest of the fleet is updated
+    pub bake_seconds: u64,
+    pub api_port: u16,
+    pub request_timeout_seconds: u64,
+}
+
+impl Default for RolloutRequest {
+    fn default() -> Self {
+        Self {
+            version: None,
+            files: vec!["strategy_params.json".to_string()],
+            canary_count: 1,
+            bake_seconds: 60,
+            api_port: 8080,
+            request_timeout_seconds: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RolloutStage {
+    Canary,
+    Fleet,
+    Completed,
+    Halted { reason: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicaConfigState {
+    pub ip_address: String,
+    pub applied_version: Option<String>,
+    pub ready_before: bool,
+    pub ready_after: Option<bool>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RolloutStatus {
+    pub version: String,
+    pub stage: RolloutStage,
+    pub started_at: DateTime<Utc>,
+    pub replicas: BTreeMap<String, ReplicaConfigState>,
+}
+
+impl RolloutStatus {
+    pub fn in_progress(&self) -> bool {
+        matches!(self.stage, RolloutStage::Canary | RolloutStage::Fleet)
+    }
+}
+
+/// Runs one rollout from the primary, publishing progress to `service.rollout`
+pub struct ConfigRollout {
+    service: MonitoringHttpService,
+    request: RolloutRequest,
+    push: ConfigPush,
+    client: reqwest::Client,
+}
+
+impl ConfigRollout {
+    /// Read the files to push from the local config directory
+    pub fn new(service: MonitoringHttpService, request: RolloutRequest) -> Result<Self> {
+        let mut files = BTreeMap::new();
+        for name in &request.files {
+            let contents = std::fs::read_to_string(Path::new(CONFIG_DIR).join(name))
+                .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", name, e))?;
+            files.insert(name.clone(), contents);
+        }
+        let version = request
+            .version
+            .clone()
+            .unwrap_or_else(|| Utc::now().format("%Y%m%d%H%M%S").to_string());
+
+        Ok(Self {
+            service,
+            request,
+            push: ConfigPush { version, files },
+            client: reqwest::Client::new(),
+        })
+    }
+
+    pub fn version(&self) -> &str {
+        &self.push.version
+    }
+
+    /// Status published before any replica has been contacted
+    pub fn initial_status(&self) -> RolloutStatus {
+        RolloutStatus {
+            version: self.push.version.clone(),
+            stage: RolloutStage::Canary,
+            started_at: Utc::now(),
+            replicas: BTreeMap::new(),
+        }
+    }
+
+    pub async fn run(self) {
+        let replicas: Vec<(String, String)> = self
+            .service
+            .agents
+            .read()
+            .await
+            .values()
+            .filter(|agent| agent.agent_id != self.service.identity.agent_id)
+            .map(|agent| (agent.agent_id.clone(), agent.ip_address.clone()))
+            .collect();
+
+        let mut status = self.initial_status();
+        for (agent_id, ip) in &replicas {
+            let ready_before = self.is_ready(agent_id, ip).await;
+            status.replicas.insert(
+                agent_id.clone(),
+                ReplicaConfigState {
+                    ip_address: ip.clone(),
+                    applied_version: None,
+                    ready_before,
+                    ready_after: None,
+                    error: None,
+                },
+            );
+        }
+        self.publish(&status).await;
+
+        let canary_count = self.request.canary_count.min(replicas.len());
+        let (canaries, rest) = replicas.split_at(canary_count);
+        tracing::info!(
+            version = %self.push.version,
+            canaries = canaries.len(),
+            rest = rest.len(),
+            "Starting config rollout"
+        );
+
+        if let Err(reason) = self.stage(canaries, &mut status).await {
+            return self.halt(status, reason).await;
+        }
+
+        status.stage = RolloutStage::Fleet;
+        self.publish(&status).await;
+        if let Err(reason) = self.stage(rest, &mut status).await {
+            return self.halt(status, reason).await;
+        }
+
+        tracing::info!(version = %self.push.version, "Config rollout completed");
+        status.stage = RolloutStage::Completed;
+        self.publish(&status).await;
+    }
+
+    /// Push to `replicas`, wait out the bake period and check for regressions
+    async fn stage(
+        &self,
+        replicas: &[(String, String)],
+        status: &mut RolloutStatus,
+    ) -> std::result::Result<(), String> {
+        if replicas.is_empty() {
+            return Ok(());
+        }
+
+        for (agent_id, ip) in replicas {
+            let state = status.replicas.get_mut(agent_id).expect("replica tracked");
+            match self.push_to(agent_id, ip).await {
+                Ok(applied) => state.applied_version = Some(applied.version),
+                Err(e) => {
+                    state.error = Some(e.to_string());
+                    self.publish(status).await;
+                    return Err(format!("push to {} failed: {}", agent_id, e));
+                }
+            }
+        }
+        self.publish(status).await;
+
+        tokio::time::sleep(Duration::from_secs(self.request.bake_seconds)).await;
+
+        let mut regressed = Vec::new();
+        for (agent_id, ip) in replicas {
+            let ready = self.is_ready(agent_id, ip).await;
+            let state = status.replicas.get_mut(agent_id).expect("replica tracked");
+            state.ready_after = Some(ready);
+            if state.ready_before && !ready {
+                regressed.push(agent_id.clone());
+            }
+        }
+        self.publish(status).await;
+
+        if regressed.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("health regressed on {}", regressed.join(", ")))
+        }
+    }
+
+    async fn halt(&self, mut status: RolloutStatus, reason: String) {
+        tracing::error!(version = %self.push.version, "Config rollout halted: {}", reason);
+        status.stage = RolloutStage::Halted { reason };
+        self.publish(&status).await;
+    }
+
+    async fn publish(&self, status: &RolloutStatus) {
+        *self.service.rollout.write().await = Some(status.clone());
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.request.request_timeout_seconds)
+    }
+
+    /// Where to reach a replica's API: through its bastion if the deployment
+    /// commander knows it as a server behind one, directly otherwise
+    async fn api_address(&self, agent_id: &str, ip: &str) -> Result<String> {
+        match &self.service.deployment_commander {
+            Some(commander) => {
+                commander
+                    .tcp_address(agent_id, ip, self.request.api_port)
+                    .await
+            }
+            None => Ok(host_port(ip, self.request.api_port)),
+        }
+    }
+
+    /// Replicas only accept pushes carrying the approval token, which they share
+    /// with the primary through the sealed config
+    async fn push_to(&self, agent_id: &str, ip: &str) -> Result<AppliedConfig> {
+        let address = self.api_address(agent_id, ip).await?;
+        let mut request = self
+            .client
+            .post(format!("http://{}/api/config", address))
+            .timeout(self.timeout())
+            .json(&self.push);
+        if let Some(token) = self
+            .service
+            .approvals
+            .as_ref()
+            .and_then(|gate| gate.token())
+        {
+            request = request.bearer_auth(token);
+        }
+        Ok(request.send().await?.error_for_status()?.json().await?)
+    }
+
+    async fn is_ready(&self, agent_id: &str, ip: &str) -> bool {
+        let address = match self.api_address(agent_id, ip).await {
+            Ok(address) => address,
+            Err(e) => {
+                tracing::warn!(agent_id, "Cannot reach replica API: {}", e);
+                return false;
+            }
+        };
+        self.client
+            .get(format!("http://{}/ready", address))
+            .timeout(self.timeout())
+            .send()
+            .await
+            .is_ok_and(|response| response.status().is_success())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_config_files_rejects_unsafe_names() {
+        let dir = std::env::temp_dir().join(format!("config-push-{}", std::process::id()));
+        let files =
+            |name: &str, contents: &str| BTreeMap::from([(name.to_string(), contents.to_string())]);
+
+        write_config_files(
+            dir.as_path(),
+            &files("strategy.json", "{\"threshold\": 0.05}"),
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.as_path().join("strategy.json")).unwrap(),
+            "{\"threshold\": 0.05}"
+        );
+
+        assert!(write_config_files(dir.as_path(), &files("../identity.json", "{}")).is_err());
+        assert!(write_config_files(dir.as_path(), &files("start.sh", "{}")).is_err());
+        assert!(write_config_files(dir.as_path(), &files("approvals.json", "{}")).is_err());
+        assert!(write_config_files(dir.as_path(), &files("identity.json", "{}")).is_err());
+        assert!(write_config_files(dir.as_path(), &files("strategy_params.json", "no")).is_err());
+        assert!(write_config_files(dir.as_path(), &files("strategy.json", "{\"a\": 1}")).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_patches_are_merged_and_validated() {
+        let current = serde_json::to_value(ReplicationStrategy::default()).unwrap();
+        let patched = apply_patch(
+            &current,
+            &serde_json::json!({"min_replicas": 3, "retry": {"max_delay_seconds": 60}}),
+        )
+        .unwrap();
+        assert_eq!(patched["min_replicas"], 3);
+        assert_eq!(patched["retry"]["max_delay_seconds"], 60);
+        assert_eq!(patched["max_replicas"], current["max_replicas"]);
+        assert_eq!(
+            patched["retry"]["base_delay_seconds"],
+            current["retry"]["base_delay_seconds"]
+        );
+
+        assert!(apply_patch(&current, &serde_json::json!({"min_replica": 3})).is_err());
+        assert!(apply_patch(&current, &serde_json::json!([1])).is_err());
+
+        // A patch that breaks an invariant is refused before it is written
+        let dir = std::env::temp_dir().join(format!("config-patch-{}", std::process::id()));
+        let too_many = apply_patch(&current, &serde_json::json!({"min_replicas": 9})).unwrap();
+        let files = BTreeMap::from([("replication.json".to_string(), too_many.to_string())]);
+        assert!(write_config_files(dir.as_path(), &files).is_err());
+        assert!(!dir.join("replication.json").exists());
+    }
+}
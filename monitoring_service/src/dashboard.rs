@@ -0,0 +1,56 @@
+//! The dashboard single-page app, compiled into the binary from `static/`.
+//!
+//! The page only talks to the JSON endpoints of the monitoring API, so a replica
+//! serves a dashboard of its own view and the primary one of the whole fleet.
+
+use actix_web::{web, HttpResponse, Result};
+use include_dir::{include_dir, Dir};
+
+static ASSETS: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/static");
+
+fn content_type(path: &str) -> &'static str {
+    match path.rsplit('.').next() {
+        Some("html") => "text/html; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("json") => "application/json",
+        _ => "application/octet-stream",
+    }
+}
+
+fn serve(path: &str) -> HttpResponse {
+    match ASSETS.get_file(path) {
+        Some(file) => HttpResponse::Ok()
+            .content_type(content_type(path))
+            .body(file.contents()),
+        None => HttpResponse::NotFound().json(serde_json::json!({
+            "error": format!("No dashboard asset '{}'", path),
+        })),
+    }
+}
+
+pub async fn index() -> Result<HttpResponse> {
+    Ok(serve("index.html"))
+}
+
+pub async fn asset(path: web::Path<String>) -> Result<HttpResponse> {
+    Ok(serve(&path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assets_are_embedded() {
+        for path in ["index.html", "dashboard.js", "dashboard.css"] {
+            assert!(ASSETS.get_file(path).is_some(), "{} is missing", path);
+        }
+        assert_eq!(
+            content_type("dashboard.js"),
+            "text/javascript; charset=utf-8"
+        );
+        assert!(ASSETS.get_file("../Cargo.toml").is_none());
+    }
+}
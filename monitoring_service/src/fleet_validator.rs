@@ -0,0 +1,269 @@
+use crate::http_server::{AgentStatus, MonitoringHttpService, AGENT_VERSION};
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use common::{host_port, AppEvent, EventBus, FleetCheck, FleetValidationReport, ReplicaValidation};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+pub const FLEET_VALIDATION_CONFIG_PATH: &str = "config/fleet_validation.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FleetValidationConfig {
+    pub enabled: bool,
+    pub interval_seconds: u64,
+    /// Port of the replicas' monitoring API
+    pub api_port: u16,
+    pub request_timeout_seconds: u64,
+    /// A replica whose last log batch is older than this is considered down
+    pub heartbeat_timeout_seconds: i64,
+    /// A replica must have shipped a log line within this window
+    pub log_window_seconds: i64,
+}
+
+impl Default for FleetValidationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_seconds: 300,
+            api_port: 8080,
+            request_timeout_seconds: 5,
+            heartbeat_timeout_seconds: 120,
+            log_window_seconds: 600,
+        }
+    }
+}
+
+impl FleetValidationConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+fn check(name: &str, passed: bool, detail: impl Into<Option<String>>) -> FleetCheck {
+    FleetCheck {
+        name: name.to_string(),
+        passed,
+        detail: detail.into(),
+    }
+}
+
+/// Reduced validation pass the primary runs against every replica reporting to it.
+///
+/// Liveness and log activity come from the log batches replicas ship to the primary;
+/// API health and version are checked over HTTP on the replica itself.
+pub struct FleetValidator {
+    service: MonitoringHttpService,
+    events: EventBus,
+    config: FleetValidationConfig,
+    client: reqwest::Client,
+}
+
+impl FleetValidator {
+    pub fn new(
+        service: MonitoringHttpService,
+        events: EventBus,
+        config: FleetValidationConfig,
+    ) -> Self {
+        Self {
+            service,
+            events,
+            config,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub async fn run(self) {
+        if !self.config.enabled {
+            tracing::info!("Fleet validation disabled");
+            return;
+        }
+
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_seconds));
+        loop {
+            interval.tick().await;
+            let report = self.validate_fleet().await;
+            if report.failed > 0 {
+                tracing::warn!(
+                    passed = report.passed,
+                    failed = report.failed,
+                    "Fleet validation found unhealthy replicas"
+                );
+            } else {
+                tracing::info!(passed = report.passed, "Fleet validation passed");
+            }
+
+            *self.service.fleet_validation.write().await = Some(report.clone());
+            if self.events.send(AppEvent::FleetValidation(report)).is_err() {
+                tracing::debug!("No subscribers for fleet validation results");
+            }
+        }
+    }
+
+    pub async fn validate_fleet(&self) -> FleetValidationReport {
+        let replicas: Vec<AgentStatus> = self
+            .service
+            .agents
+            .read()
+            .await
+            .values()
+            .filter(|agent| agent.agent_id != self.service.identity.agent_id)
+            .cloned()
+            .collect();
+
+        let mut results = Vec::new();
+        for replica in &replicas {
+            results.push(self.validate_replica(replica, Utc::now()).await);
+        }
+
+        let passed = results.iter().filter(|r| r.passed).count();
+        FleetValidationReport {
+            timestamp: Utc::now(),
+            passed,
+            failed: results.len() - passed,
+            replicas: results,
+        }
+    }
+
+    async fn validate_replica(
+        &self,
+        replica: &AgentStatus,
+        now: DateTime<Utc>,
+    ) -> ReplicaValidation {
+        let heartbeat_age = now - replica.last_heartbeat;
+        let process_up = check(
+            "process_up",
+            heartbeat_age <= ChronoDuration::seconds(self.config.heartbeat_timeout_seconds),
+            format!("last heartbeat {}s ago", heartbeat_age.num_seconds()),
+        );
+
+        let last_log = self
+            .service
+            .logs
+            .read()
+            .await
+            .last_timestamp(&replica.agent_id);
+        let log_activity = match last_log {
+            Some(at) => check(
+                "log_activity",
+                now - at <= ChronoDuration::seconds(self.config.log_window_seconds),
+                format!("last log line at {}", at.to_rfc3339()),
+            ),
+            None => check("log_activity", false, "no log lines received".to_string()),
+        };
+
+        let base_url = format!(
+            "http://{}",
+            host_port(&replica.ip_address, self.config.api_port)
+        );
+        let api_healthy = match self.get(&format!("{}/health", base_url)).await {
+            Ok(_) => check("monitoring_api", true, None),
+            Err(e) => check("monitoring_api", false, e.to_string()),
+        };
+        let version_match = match self.get(&base_url).await {
+            Ok(root) => {
+                let version = root["version"].as_str().unwrap_or_default();
+                check(
+                    "version_match",
+                    version == AGENT_VERSION,
+                    format!("replica {}, primary {}", version, AGENT_VERSION),
+                )
+            }
+            Err(e) => check("version_match", false, e.to_string()),
+        };
+
+        let checks = vec![process_up, api_healthy, log_activity, version_match];
+        ReplicaValidation {
+            agent_id: replica.agent_id.clone(),
+            ip_address: replica.ip_address.clone(),
+            passed: checks.iter().all(|c| c.passed),
+            checks,
+        }
+    }
+
+    async fn get(&self, url: &str) -> Result<serde_json::Value> {
+        Ok(self
+            .client
+            .get(url)
+            .timeout(Duration::from_secs(self.config.request_timeout_seconds))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_store::LogLine;
+
+    fn replica(last_heartbeat: DateTime<Utc>) -> AgentStatus {
+        AgentStatus {
+            agent_id: "replica-1".to_string(),
+            hostname: String::new(),
+            ip_address: "127.0.0.1".to_string(),
+            status: "Running".to_string(),
+            cpu_usage: 0.0,
+            memory_usage: 0.0,
+            disk_usage: 0.0,
+            uptime_seconds: 0,
+            last_heartbeat,
+            version: String::new(),
+            parent_id: None,
+            generation: 1,
+            deployed_at: None,
+            trading: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replica_checks_use_heartbeats_and_logs() {
+        let service = MonitoringHttpService::new(0);
+        let now = Utc::now();
+        service.logs.write().await.append(
+            "replica-1",
+            vec![LogLine {
+                timestamp: now - ChronoDuration::seconds(30),
+                line: "[INFO] tick".to_string(),
+            }],
+        );
+        // Nothing listens on port 1, so the HTTP checks fail fast
+        let validator = FleetValidator::new(
+            service,
+            EventBus::new(16),
+            FleetValidationConfig {
+                api_port: 1,
+                ..Default::default()
+            },
+        );
+
+        let result = validator.validate_replica(&replica(now), now).await;
+        let outcomes: Vec<_> = result
+            .checks
+            .iter()
+            .map(|c| (c.name.as_str(), c.passed))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                ("process_up", true),
+                ("monitoring_api", false),
+                ("log_activity", true),
+                ("version_match", false),
+            ]
+        );
+        assert!(!result.passed);
+
+        let stale = now - ChronoDuration::seconds(600);
+        let result = validator.validate_replica(&replica(stale), now).await;
+        assert!(!result.checks[0].passed);
+    }
+}
@@ -0,0 +1,270 @@
+//! gRPC API served next to the REST API, for typed clients outside Rust and for
+//! agents talking to each other. It exposes the same state and controls as the
+//! REST handlers, read from the same [`MonitoringHttpService`]; the messages are
+//! defined in `common/proto/aurelia.proto`.
+
+use crate::http_server::{MonitoringHttpService, AGENT_VERSION, LIVENESS_TIMEOUT};
+use autonomy_core::approvals::ApprovalError;
+use common::audit::{self, AuditCategory};
+use common::proto::agent_server::{Agent, AgentServer};
+use common::proto::{
+    AgentStatus, ApprovalResponse, Component, DecideApprovalRequest, Event, GetStatusRequest,
+    SetStrategyParamRequest, SetStrategyParamResponse, SubscribeRequest,
+};
+use common::{AppEvent, EventReceiver, StrategyParamUpdate, Topic};
+use futures_util::Stream;
+use std::pin::Pin;
+use tokio::sync::broadcast::error::RecvError;
+use tonic::{Request, Response, Status};
+
+pub struct GrpcService {
+    http: MonitoringHttpService,
+}
+
+impl GrpcService {
+    pub fn new(http: MonitoringHttpService) -> Self {
+        Self { http }
+    }
+
+    /// Serve the gRPC API on `port` until the server fails.
+    pub async fn serve(self, port: u16) -> anyhow::Result<()> {
+        let addr = ([0, 0, 0, 0], port).into();
+        tracing::info!("Starting gRPC API on port {}", port);
+        tonic::transport::Server::builder()
+            .add_service(AgentServer::new(self))
+            .serve(addr)
+            .await?;
+        Ok(())
+    }
+}
+
+/// The token of an `authorization: Bearer <token>` metadata entry
+fn bearer_token<T>(request: &Request<T>) -> Option<&str> {
+    request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+fn event_message(event: &AppEvent) -> Result<Event, Status> {
+    Ok(Event {
+        kind: event.kind().to_string(),
+        topic: event.topic().label().to_string(),
+        payload_json: serde_json::to_string(event).map_err(|e| Status::internal(e.to_string()))?,
+        timestamp_ms: common::clock::local_now_millis(),
+    })
+}
+
+/// Events from `rx` until the bus closes. A client too slow to keep up skips
+/// the events it missed, like the engines do.
+fn event_stream(rx: EventReceiver) -> impl Stream<Item = Result<Event, Status>> {
+    futures_util::stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => return Some((event_message(&event), rx)),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+#[tonic::async_trait]
+impl Agent for GrpcService {
+    async fn get_status(
+        &self,
+        _request: Request<GetStatusRequest>,
+    ) -> Result<Response<AgentStatus>, Status> {
+        let identity = &self.http.identity;
+        let metrics = self.http.system_metrics.read().await.clone();
+        let trading = self.http.trading_status.read().await.clone();
+        Ok(Response::new(AgentStatus {
+            agent_id: identity.agent_id.clone(),
+            parent_id: identity.parent_id.clone(),
+            generation: identity.generation,
+            version: AGENT_VERSION.to_string(),
+            live: self.http.health.is_live(LIVENESS_TIMEOUT),
+            ready: self.http.health.is_ready(),
+            components: self
+                .http
+                .health
+                .components()
+                .into_iter()
+                .map(|c| Component {
+                    name: c.name,
+                    ready: c.ready,
+                    detail: c.detail,
+                })
+                .collect(),
+            cpu_usage: metrics.cpu_usage,
+            memory_usage_mb: metrics.memory_usage_mb,
+            trading_active: trading.active,
+            total_trades: trading.total_trades,
+            pnl: trading.pnl,
+        }))
+    }
+
+    type SubscribeStream = Pin<Box<dyn Stream<Item = Result<Event, Status>> + Send>>;
+
+    async fn subscribe(
+        &self,
+        request: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let Some(bus) = &self.http.events else {
+            return Err(Status::unavailable("Event bus is not attached"));
+        };
+        let labels = request.into_inner().topics;
+        let topics = if labels.is_empty() {
+            Topic::ALL.to_vec()
+        } else {
+            labels
+                .iter()
+                .map(|label| {
+                    Topic::from_label(label)
+                        .ok_or_else(|| Status::invalid_argument(format!("unknown topic {}", label)))
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let rx = bus.subscribe_as("grpc", &topics);
+        Ok(Response::new(Box::pin(event_stream(rx))))
+    }
+
+    async fn set_strategy_param(
+        &self,
+        request: Request<SetStrategyParamRequest>,
+    ) -> Result<Response<SetStrategyParamResponse>, Status> {
+        let (Some(gate), Some(bus)) = (&self.http.approvals, &self.http.events) else {
+            return Err(Status::unavailable(
+                "Strategy parameter updates need the approval token and the event bus",
+            ));
+        };
+        if !gate.is_authorized(bearer_token(&request)) {
+            return Err(Status::unauthenticated(
+                "A valid approval token is required",
+            ));
+        }
+        let request = request.into_inner();
+        let update = StrategyParamUpdate {
+            name: request.name,
+            value: request.value,
+        };
+        audit::record(AuditCategory::ConfigChange, "strategy_param", &update);
+        bus.send_control(AppEvent::StrategyParamUpdate(update))
+            .await
+            .map_err(|_| Status::unavailable("Kernel is not accepting control events"))?;
+        Ok(Response::new(SetStrategyParamResponse {}))
+    }
+
+    async fn decide_approval(
+        &self,
+        request: Request<DecideApprovalRequest>,
+    ) -> Result<Response<ApprovalResponse>, Status> {
+        let Some(gate) = &self.http.approvals else {
+            return Err(Status::unavailable("Approvals are not enabled"));
+        };
+        if !gate.is_authorized(bearer_token(&request)) {
+            return Err(Status::unauthenticated(
+                "A valid approval token is required",
+            ));
+        }
+
+        let request = request.into_inner();
+        let operator = if request.operator.is_empty() {
+            "operator".to_string()
+        } else {
+            request.operator
+        };
+        let result = if request.approve {
+            gate.approve(&request.id, &operator)
+        } else {
+            gate.reject(&request.id, &operator)
+        };
+        match result {
+            Ok(decided) => Ok(Response::new(ApprovalResponse {
+                id: decided.id,
+                status: serde_json::to_value(decided.status)
+                    .ok()
+                    .and_then(|status| status.as_str().map(str::to_string))
+                    .unwrap_or_default(),
+                summary: decided.summary,
+            })),
+            Err(e @ ApprovalError::NotFound) => Err(Status::not_found(e.to_string())),
+            Err(e @ ApprovalError::AlreadyDecided(_)) => {
+                Err(Status::failed_precondition(e.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use autonomy_core::ApprovalGate;
+    use common::EventBus;
+    use futures_util::StreamExt;
+
+    #[tokio::test]
+    async fn test_subscribers_receive_events_of_their_topics() {
+        let bus = EventBus::new(8);
+        let mut http = MonitoringHttpService::new(0);
+        http.events = Some(bus.clone());
+        let service = GrpcService::new(http);
+
+        let request = Request::new(SubscribeRequest {
+            topics: vec!["financial".to_string()],
+        });
+        let mut stream = service.subscribe(request).await.unwrap().into_inner();
+        bus.send(AppEvent::ReloadConfig).ok();
+        bus.send(AppEvent::FinancialUpdate(42.0)).unwrap();
+
+        let event = stream.next().await.unwrap().unwrap();
+        assert_eq!(event.kind, AppEvent::FinancialUpdate(42.0).kind());
+        assert_eq!(event.topic, "financial");
+        let payload: AppEvent = serde_json::from_str(&event.payload_json).unwrap();
+        assert!(matches!(payload, AppEvent::FinancialUpdate(funds) if funds == 42.0));
+
+        let unknown = Request::new(SubscribeRequest {
+            topics: vec!["weather".to_string()],
+        });
+        assert_eq!(
+            service.subscribe(unknown).await.err().unwrap().code(),
+            tonic::Code::InvalidArgument
+        );
+    }
+
+    #[tokio::test]
+    async fn test_strategy_params_need_the_approval_token() {
+        let (bus, mut control) = EventBus::with_control_channel(8, 8);
+        let mut http = MonitoringHttpService::new(0);
+        http.events = Some(bus.clone());
+        http.approvals = Some(ApprovalGate::default().with_token("secret".to_string()));
+        let service = GrpcService::new(http);
+        let update = |token: Option<&str>| {
+            let mut request = Request::new(SetStrategyParamRequest {
+                name: "interval_seconds".to_string(),
+                value: 30.0,
+            });
+            if let Some(token) = token {
+                let value = format!("Bearer {}", token).parse().unwrap();
+                request.metadata_mut().insert("authorization", value);
+            }
+            request
+        };
+
+        for token in [None, Some("wrong")] {
+            let status = service.set_strategy_param(update(token)).await.unwrap_err();
+            assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        }
+        assert!(control.try_recv().is_err());
+
+        service
+            .set_strategy_param(update(Some("secret")))
+            .await
+            .unwrap();
+        assert!(matches!(
+            control.try_recv(),
+            Ok(AppEvent::StrategyParamUpdate(update)) if update.name == "interval_seconds"
+        ));
+    }
+}
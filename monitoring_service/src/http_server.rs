@@ -1,11 +1,38 @@
+use crate::aggregator::{fleet_trading, FleetTrading, MetricsAggregator};
+use crate::cluster_registry::fleet_funds;
+use crate::config_rollout::{
+    apply_patch, write_config_files, AppliedConfig, ConfigPush, ConfigRollout, RolloutRequest,
+    RolloutStatus, CONFIG_DIR,
+};
+use crate::dashboard;
+use crate::log_store::{LogBatch, LogStore};
+use crate::pagination::PageQuery;
+use crate::prometheus;
 use actix_cors::Cors;
-use actix_web::{middleware, web, App, HttpResponse, HttpServer, Result};
+use actix_web::{middleware, web, App, HttpRequest, HttpResponse, HttpServer, Result};
+use autonomy_core::approvals::ApprovalError;
+use autonomy_core::health_monitor::HEALTH_CONFIG_PATH;
+use autonomy_core::self_replicator::REPLICATION_CONFIG_PATH;
+use autonomy_core::{
+    ApprovalGate, DecisionJournal, DeploymentCommander, FleetCommand, HealthThresholds,
+    ReplicationStrategy, SelfReplicator,
+};
 use chrono::{DateTime, Utc};
+use common::audit::{self, AuditCategory};
+use common::trade_ledger::ReportPeriod;
+use common::{
+    AgentIdentity, AppEvent, CostModel, CredentialReport, EventBus, FleetKey,
+    FleetValidationReport, HealthState, HealthSummary, MigrationStatus, NetAssetValue,
+    PerformanceReport, RateLimiter, RecoveryStats, SchedulerStatus, StrategyParamUpdate,
+    TradeLedger,
+};
+use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
 use std::sync::Arc;
+use survival_protocol::Budget;
 use sysinfo::System;
-use tokio::sync::RwLock;
+use tokio::sync::{watch, RwLock};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentStatus {
@@ -19,6 +46,15 @@ pub struct AgentStatus {
     pub uptime_seconds: u64,
     pub last_heartbeat: DateTime<Utc>,
     pub version: String,
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    #[serde(default)]
+    pub generation: u32,
+    #[serde(default)]
+    pub deployed_at: Option<DateTime<Utc>>,
+    /// Trading activity the agent last reported
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trading: Option<TradingStatus>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,9 +66,40 @@ pub struct ClusterStatus {
     pub total_cpu_usage: f32,
     pub total_memory_usage: f32,
     pub cluster_health: String,
+    pub trading: FleetTrading,
+    /// This agent's survival budget, when it runs the survival protocol
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runway: Option<RunwayStatus>,
     pub agents: Vec<AgentStatus>,
 }
 
+/// Runway from the survival protocol's latest budget
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RunwayStatus {
+    pub funds: f64,
+    pub hourly_cost: f64,
+    pub runway_hours: f64,
+    /// Live replicas whose funds are pooled into the fleet runway
+    pub replicas: usize,
+    pub replica_funds: f64,
+    /// Only on a primary that has heard from its replicas
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fleet_runway_hours: Option<f64>,
+}
+
+impl From<Budget> for RunwayStatus {
+    fn from(budget: Budget) -> Self {
+        Self {
+            funds: budget.funds,
+            hourly_cost: budget.hourly_cost,
+            runway_hours: budget.runway_hours(),
+            replicas: budget.replicas,
+            replica_funds: budget.replica_funds,
+            fleet_runway_hours: (budget.replicas > 0).then(|| budget.fleet_runway_hours()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemMetrics {
     pub cpu_usage: f32,
@@ -42,7 +109,7 @@ pub struct SystemMetrics {
     pub timestamp: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TradingStatus {
     pub active: bool,
     pub last_price: HashMap<String, f64>,
@@ -50,6 +117,36 @@ pub struct TradingStatus {
     pub successful_trades: u32,
     pub failed_trades: u32,
     pub pnl: f64,
+    /// Latest valuation of the portfolio
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nav: Option<NetAssetValue>,
+}
+
+/// Latest snapshots the autonomous agent published of its subsystems.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SubsystemStatus {
+    pub scheduler: Option<SchedulerStatus>,
+    pub recovery: Option<RecoveryStats>,
+    pub health: Option<HealthSummary>,
+    /// External dependencies whose circuit breaker is open
+    #[serde(default)]
+    pub open_circuits: BTreeSet<String>,
+    /// Events each bus subscriber lost by falling behind, since startup
+    #[serde(default)]
+    pub lost_events: BTreeMap<String, u64>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// How quickly market data turns into decisions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PipelineMetrics {
+    /// Events waiting in the slowest event bus subscription
+    pub event_backlog: usize,
+    /// Decisions whose triggering market tick was seen
+    pub decisions: u64,
+    /// Summed tick-to-decision latency, measured from the tick's exchange timestamp
+    pub decision_latency_total_ms: f64,
+    pub decision_latency_max_ms: f64,
 }
 
 #[derive(Clone)]
@@ -57,9 +154,100 @@ pub struct MonitoringHttpService {
     pub agents: Arc<RwLock<HashMap<String, AgentStatus>>>,
     pub system_metrics: Arc<RwLock<SystemMetrics>>,
     pub trading_status: Arc<RwLock<TradingStatus>>,
+    pub pipeline: Arc<RwLock<PipelineMetrics>>,
+    /// Cluster-wide history and per-agent series, sampled by the metrics loop
+    pub aggregator: Arc<RwLock<MetricsAggregator>>,
+    /// Latest result of the primary's fleet validation pass
+    pub fleet_validation: Arc<RwLock<Option<FleetValidationReport>>>,
+    /// Latest strategy scoreboard from the allocator
+    pub strategy_performance: Arc<RwLock<Option<PerformanceReport>>>,
+    /// Latest scheduler, recovery and health snapshots from the bus
+    pub subsystems: Arc<RwLock<SubsystemStatus>>,
+    /// Config version this agent last applied from a push
+    pub applied_config: Arc<RwLock<Option<AppliedConfig>>>,
+    /// Latest config rollout started from this agent
+    pub rollout: Arc<RwLock<Option<RolloutStatus>>>,
+    pub deployment_commander: Option<Arc<DeploymentCommander>>,
+    /// Attached once the autonomous agent is running, see `attach_replicator`
+    pub replicator: Arc<RwLock<Option<Arc<SelfReplicator>>>>,
+    /// Latest progress of recent agent migrations, oldest first
+    pub migrations: Arc<RwLock<VecDeque<MigrationStatus>>>,
+    pub decisions: Option<DecisionJournal>,
+    /// Actions waiting for an operator, see `/api/approvals`
+    pub approvals: Option<ApprovalGate>,
+    /// Replicas shipping logs must present its [`FleetKey::auth_token`]
+    pub fleet_key: Option<FleetKey>,
+    pub events: Option<EventBus>,
+    pub rate_limiter: Option<RateLimiter>,
+    pub trades: Option<TradeLedger>,
+    /// Included in trade reports that contain simulated fills
+    pub cost_model: CostModel,
+    /// Exchange credential check made at startup
+    pub credentials: Option<CredentialReport>,
+    /// Latest budget from the survival protocol, reported with the cluster status
+    pub budget: Option<watch::Receiver<Budget>>,
+    pub logs: Arc<RwLock<LogStore>>,
+    pub health: HealthState,
+    pub identity: AgentIdentity,
     pub port: u16,
 }
 
+/// Version reported on `/` and in `AgentStatus`; replicas must match the primary
+pub const AGENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Migrations kept for `/api/migrations`
+const MIGRATION_HISTORY: usize = 20;
+
+/// How long the main loop may go without a heartbeat before `/live` fails
+pub(crate) const LIVENESS_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FlattenRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    /// Only return entries from this sequence number on
+    pub since: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DecisionsQuery {
+    /// Only return decisions made after this time (RFC 3339)
+    pub since: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TradeReportQuery {
+    /// Only report fills at or after this time (RFC 3339)
+    pub from: Option<DateTime<Utc>>,
+    /// Only report fills before this time (RFC 3339)
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub period: ReportPeriod,
+    /// `json` (default) or `csv`
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MetricsHistoryQuery {
+    /// How far back to report (default 1)
+    pub hours: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReplicationQuery {
+    /// Most recent replication results to return (default 50)
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogsQuery {
+    /// Only return lines with a sequence number greater than this
+    pub since: Option<u64>,
+}
+
 impl MonitoringHttpService {
     pub fn new(port: u16) -> Self {
         Self {
@@ -71,18 +259,38 @@ impl MonitoringHttpService {
                 memory_percentage: 0.0,
                 timestamp: Utc::now(),
             })),
-            trading_status: Arc::new(RwLock::new(TradingStatus {
-                active: false,
-                last_price: HashMap::new(),
-                total_trades: 0,
-                successful_trades: 0,
-                failed_trades: 0,
-                pnl: 0.0,
-            })),
+            trading_status: Arc::new(RwLock::new(TradingStatus::default())),
+            pipeline: Arc::new(RwLock::new(PipelineMetrics::default())),
+            aggregator: Arc::new(RwLock::new(MetricsAggregator::new(1))),
+            fleet_validation: Arc::new(RwLock::new(None)),
+            strategy_performance: Arc::new(RwLock::new(None)),
+            subsystems: Arc::new(RwLock::new(SubsystemStatus::default())),
+            applied_config: Arc::new(RwLock::new(None)),
+            rollout: Arc::new(RwLock::new(None)),
+            deployment_commander: None,
+            replicator: Arc::new(RwLock::new(None)),
+            migrations: Arc::new(RwLock::new(VecDeque::new())),
+            decisions: None,
+            approvals: None,
+            fleet_key: None,
+            events: None,
+            rate_limiter: None,
+            trades: None,
+            cost_model: CostModel::default(),
+            credentials: None,
+            budget: None,
+            logs: Arc::new(RwLock::new(LogStore::default())),
+            health: HealthState::new(),
+            identity: AgentIdentity::new_root(),
             port,
         }
     }
 
+    /// Serve the replicator's state on `/api/replication`
+    pub async fn attach_replicator(&self, replicator: Arc<SelfReplicator>) {
+        *self.replicator.write().await = Some(replicator);
+    }
+
     pub fn start_server(self) {
         let service_data = web::Data::new(self.clone());
 
@@ -95,12 +303,41 @@ impl MonitoringHttpService {
         println!("🚀 Rust监控API启动在: http://0.0.0.0:{}", self.port);
         println!("📊 API端点:");
         println!("   GET /");
+        println!("   GET /dashboard");
         println!("   GET /api/status");
         println!("   GET /api/agents");
         println!("   GET /api/cluster/status");
         println!("   GET /api/metrics");
+        println!("   GET /api/metrics/history?hours=&from=&to=&page=&limit=");
+        println!("   GET /api/agents/{{id}}/metrics");
         println!("   GET /api/trading");
+        println!("   GET /api/trades?from=&to=&page=&limit=");
+        println!("   GET /api/credentials");
+        println!("   GET /api/pipeline");
+        println!("   GET /api/bus");
+        println!("   GET /api/fleet/validation");
+        println!("   GET /api/deployments");
+        println!("   GET /api/replication?limit=");
+        println!("   GET /api/migrations");
+        println!("   PATCH /api/replication/strategy");
+        println!("   PATCH /api/health/thresholds");
+        println!("   GET/POST /api/config");
+        println!("   GET /api/fleet/config");
+        println!("   POST /api/fleet/config/rollout");
+        println!("   GET /api/decisions?since=&from=&to=&page=&limit=");
+        println!("   POST /api/strategy/params");
+        println!("   GET /api/audit?since=");
+        println!("   GET /api/audit/verify");
+        println!("   GET /api/approvals?page=&limit=");
+        println!("   POST /api/approvals/{{id}}/approve");
+        println!("   POST /api/approvals/{{id}}/reject");
+        println!("   POST /api/fleet/exec");
+        println!("   GET /api/servers/{{server_id}}/logs/stream");
+        println!("   GET/POST /api/agents/{{id}}/logs?since=");
         println!("   GET /health");
+        println!("   GET /live");
+        println!("   GET /ready");
+        println!("   GET /metrics");
 
         let port = self.port;
 
@@ -122,12 +359,73 @@ impl MonitoringHttpService {
                         .wrap(cors)
                         .wrap(middleware::Logger::default())
                         .route("/", web::get().to(root_handler))
+                        .route("/dashboard", web::get().to(dashboard::index))
+                        .route("/dashboard/{path:.*}", web::get().to(dashboard::asset))
                         .route("/api/status", web::get().to(get_status))
                         .route("/api/agents", web::get().to(get_agents))
+                        .route("/api/agents/{id}/logs", web::get().to(get_agent_logs))
+                        .route("/api/agents/{id}/logs", web::post().to(ingest_agent_logs))
                         .route("/api/cluster/status", web::get().to(get_cluster_status))
                         .route("/api/metrics", web::get().to(get_metrics))
+                        .route("/api/metrics/history", web::get().to(get_metrics_history))
+                        .route("/api/agents/{id}/metrics", web::get().to(get_agent_metrics))
                         .route("/api/trading", web::get().to(get_trading_status))
+                        .route("/api/trading/flatten", web::post().to(emergency_flatten))
+                        .route("/api/trading/resume", web::post().to(resume_trading))
+                        .route("/api/pipeline", web::get().to(get_pipeline))
+                        .route("/api/bus", web::get().to(get_bus_metrics))
+                        .route("/api/fleet/validation", web::get().to(get_fleet_validation))
+                        .route("/api/deployments", web::get().to(get_deployments))
+                        .route("/api/replication", web::get().to(get_replication))
+                        .route("/api/migrations", web::get().to(get_migrations))
+                        .route(
+                            "/api/replication/strategy",
+                            web::patch().to(patch_replication_strategy),
+                        )
+                        .route(
+                            "/api/health/thresholds",
+                            web::patch().to(patch_health_thresholds),
+                        )
+                        .route("/api/config", web::get().to(get_applied_config))
+                        .route("/api/config", web::post().to(apply_config))
+                        .route("/api/fleet/config", web::get().to(get_config_rollout))
+                        .route(
+                            "/api/fleet/config/rollout",
+                            web::post().to(start_config_rollout),
+                        )
+                        .route("/api/decisions", web::get().to(get_decisions))
+                        .route("/api/strategy/params", web::post().to(set_strategy_param))
+                        .route(
+                            "/api/market/symbols/{symbol}",
+                            web::put().to(subscribe_symbol),
+                        )
+                        .route(
+                            "/api/market/symbols/{symbol}",
+                            web::delete().to(unsubscribe_symbol),
+                        )
+                        .route("/api/audit", web::get().to(export_audit_log))
+                        .route("/api/audit/verify", web::get().to(verify_audit_log))
+                        .route("/api/approvals", web::get().to(get_approvals))
+                        .route("/api/approvals/{id}/approve", web::post().to(approve))
+                        .route("/api/approvals/{id}/reject", web::post().to(reject))
+                        .route("/api/rate_limits", web::get().to(get_rate_limits))
+                        .route("/api/trades", web::get().to(get_trades))
+                        .route("/api/credentials", web::get().to(get_credentials))
+                        .route("/api/reports/trades", web::get().to(get_trade_report))
+                        .route(
+                            "/api/strategies/performance",
+                            web::get().to(get_strategy_performance),
+                        )
+                        .route("/api/subsystems", web::get().to(get_subsystems))
+                        .route("/api/fleet/exec", web::post().to(execute_fleet_command))
+                        .route(
+                            "/api/servers/{server_id}/logs/stream",
+                            web::get().to(stream_server_logs),
+                        )
                         .route("/health", web::get().to(health_check))
+                        .route("/live", web::get().to(liveness))
+                        .route("/ready", web::get().to(readiness))
+                        .route("/metrics", web::get().to(prometheus_metrics))
                 })
                 .bind(("0.0.0.0", port))
                 .expect("Failed to bind server")
@@ -158,6 +456,7 @@ impl MonitoringHttpService {
             };
 
             *self.system_metrics.write().await = metrics;
+            let trading = self.trading_status.read().await.clone();
 
             // 更新本地agent状态
             let mut agents = self.agents.write().await;
@@ -166,9 +465,9 @@ impl MonitoringHttpService {
                 .unwrap_or_else(|_| "localhost".to_string());
 
             agents.insert(
-                "local".to_string(),
+                self.identity.agent_id.clone(),
                 AgentStatus {
-                    agent_id: "local".to_string(),
+                    agent_id: self.identity.agent_id.clone(),
                     hostname: hostname.clone(),
                     ip_address: "127.0.0.1".to_string(),
                     status: "Running".to_string(),
@@ -177,10 +476,25 @@ impl MonitoringHttpService {
                     disk_usage: 0.0,
                     uptime_seconds: System::uptime(),
                     last_heartbeat: Utc::now(),
-                    version: "0.1.0".to_string(),
+                    version: AGENT_VERSION.to_string(),
+                    parent_id: self.identity.parent_id.clone(),
+                    generation: self.identity.generation,
+                    deployed_at: Some(self.identity.deployed_at),
+                    trading: Some(trading),
                 },
             );
 
+            let snapshot: Vec<AgentStatus> = agents.values().cloned().collect();
+            drop(agents);
+            self.aggregator.write().await.aggregate(&snapshot, 1);
+
+            // The primary's survival protocol pools the replicas' funds into the fleet runway
+            if let Some(events) = self.events.as_ref().filter(|_| self.identity.is_primary()) {
+                if let Some(funds) = fleet_funds(&snapshot, &self.identity.agent_id) {
+                    let _ = events.send(AppEvent::FleetFunds(funds));
+                }
+            }
+
             tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
         }
     }
@@ -213,21 +527,104 @@ impl MonitoringHttpService {
         let mut status = self.trading_status.write().await;
         status.pnl = pnl;
     }
+
+    pub async fn update_nav(&self, nav: NetAssetValue) {
+        self.trading_status.write().await.nav = Some(nav);
+    }
+
+    pub async fn record_strategy_performance(&self, report: PerformanceReport) {
+        *self.strategy_performance.write().await = Some(report);
+    }
+
+    /// Keep the subsystem snapshot carried by `event`, if it is one
+    pub async fn record_subsystem_status(&self, event: &AppEvent) {
+        let mut subsystems = self.subsystems.write().await;
+        match event {
+            AppEvent::SchedulerStatus(status) => subsystems.scheduler = Some(status.clone()),
+            AppEvent::RecoveryStats(stats) => subsystems.recovery = Some(stats.clone()),
+            AppEvent::HealthSummary(summary) => subsystems.health = Some(*summary.clone()),
+            AppEvent::CircuitOpen(component) => {
+                subsystems.open_circuits.insert(component.clone());
+            }
+            AppEvent::CircuitClosed(component) => {
+                subsystems.open_circuits.remove(component);
+            }
+            AppEvent::ConsumerLagged(lag) => {
+                *subsystems
+                    .lost_events
+                    .entry(lag.consumer.clone())
+                    .or_default() += lag.missed;
+            }
+            _ => return,
+        }
+        subsystems.updated_at = Some(Utc::now());
+    }
+
+    /// Keep the latest progress of a migration, replacing its earlier stages
+    pub async fn record_migration(&self, status: MigrationStatus) {
+        let mut migrations = self.migrations.write().await;
+        match migrations.iter_mut().find(|m| m.id == status.id) {
+            Some(existing) => *existing = status,
+            None => migrations.push_back(status),
+        }
+        while migrations.len() > MIGRATION_HISTORY {
+            migrations.pop_front();
+        }
+    }
+
+    pub async fn record_decision_latency(&self, latency_ms: f64) {
+        let mut pipeline = self.pipeline.write().await;
+        pipeline.decisions += 1;
+        pipeline.decision_latency_total_ms += latency_ms;
+        pipeline.decision_latency_max_ms = pipeline.decision_latency_max_ms.max(latency_ms);
+    }
 }
 
 // Handler functions
 async fn root_handler() -> Result<HttpResponse> {
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "service": "Aurelia Monitoring API",
-        "version": "0.1.0",
+        "version": AGENT_VERSION,
         "status": "running",
         "endpoints": [
+            "/dashboard",
             "/api/status",
             "/api/agents",
             "/api/cluster/status",
             "/api/metrics",
+            "/api/metrics/history",
+            "/api/agents/{id}/metrics",
             "/api/trading",
-            "/health"
+            "/api/trading/flatten",
+            "/api/trading/resume",
+            "/api/pipeline",
+            "/api/bus",
+            "/api/fleet/validation",
+            "/api/deployments",
+            "/api/replication",
+            "/api/migrations",
+            "/api/replication/strategy",
+            "/api/health/thresholds",
+            "/api/config",
+            "/api/fleet/config",
+            "/api/fleet/config/rollout",
+            "/api/decisions",
+            "/api/strategy/params",
+            "/api/audit",
+            "/api/audit/verify",
+            "/api/approvals",
+            "/api/approvals/{id}/approve",
+            "/api/approvals/{id}/reject",
+            "/api/rate_limits",
+            "/api/trades",
+            "/api/reports/trades",
+            "/api/strategies/performance",
+            "/api/servers/{server_id}/logs/stream",
+            "/api/agents/{id}/logs",
+            "/health",
+            "/live",
+            "/ready",
+            "/metrics"
         ]
     })))
 }
@@ -252,6 +649,96 @@ async fn get_agents(service: web::Data<MonitoringHttpService>) -> Result<HttpRes
     Ok(HttpResponse::Ok().json(agent_list))
 }
 
+async fn get_agent_logs(
+    service: web::Data<MonitoringHttpService>,
+    agent_id: web::Path<String>,
+    query: web::Query<LogsQuery>,
+    page: web::Query<PageQuery>,
+) -> Result<HttpResponse> {
+    let since = query.since.unwrap_or(0);
+    // `since` is the cursor here, so only the time filter and limit apply
+    let mut entries: Vec<_> = service
+        .logs
+        .read()
+        .await
+        .since(&agent_id, since)
+        .into_iter()
+        .filter(|e| page.contains(e.timestamp))
+        .collect();
+    let has_more = entries.len() > page.limit();
+    entries.truncate(page.limit());
+    let next = entries.last().map(|e| e.seq).unwrap_or(since);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "agent_id": agent_id.as_str(),
+        "entries": entries,
+        "next": next,
+        "has_more": has_more,
+    })))
+}
+
+async fn ingest_agent_logs(
+    req: HttpRequest,
+    service: web::Data<MonitoringHttpService>,
+    agent_id: web::Path<String>,
+    batch: web::Json<LogBatch>,
+) -> Result<HttpResponse> {
+    // Batches register agents in the replica tree, so only the fleet may send them
+    let Some(fleet_key) = &service.fleet_key else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Log ingestion needs a fleet key",
+        })));
+    };
+    if !fleet_key.is_auth_token(bearer_token(&req)) {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "A valid fleet token is required",
+        })));
+    }
+
+    let batch = batch.into_inner();
+    let received = batch.lines.len();
+
+    // 每个批次同时作为副本的心跳
+    if let Some(identity) = &batch.identity {
+        let mut agents = service.agents.write().await;
+        let agent = agents
+            .entry(agent_id.to_string())
+            .or_insert_with(|| AgentStatus {
+                agent_id: agent_id.to_string(),
+                hostname: String::new(),
+                ip_address: req
+                    .peer_addr()
+                    .map(|addr| addr.ip().to_string())
+                    .unwrap_or_default(),
+                status: "Running".to_string(),
+                cpu_usage: 0.0,
+                memory_usage: 0.0,
+                disk_usage: 0.0,
+                uptime_seconds: 0,
+                last_heartbeat: Utc::now(),
+                version: String::new(),
+                parent_id: None,
+                generation: 0,
+                deployed_at: None,
+                trading: None,
+            });
+        agent.last_heartbeat = Utc::now();
+        agent.parent_id = identity.parent_id.clone();
+        agent.generation = identity.generation;
+        agent.deployed_at = Some(identity.deployed_at);
+        if let Some(trading) = &batch.trading {
+            agent.trading = Some(trading.clone());
+        }
+    }
+
+    let last_seq = service.logs.write().await.append(&agent_id, batch.lines);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "received": received,
+        "last_seq": last_seq,
+    })))
+}
+
 async fn get_cluster_status(service: web::Data<MonitoringHttpService>) -> Result<HttpResponse> {
     let agents = service.agents.read().await;
     let metrics = service.system_metrics.read().await;
@@ -273,6 +760,11 @@ async fn get_cluster_status(service: web::Data<MonitoringHttpService>) -> Result
             "Degraded"
         }
         .to_string(),
+        trading: fleet_trading(agents.values()),
+        runway: service
+            .budget
+            .as_ref()
+            .map(|budget| RunwayStatus::from(*budget.borrow())),
         agents: agents.values().cloned().collect(),
     };
 
@@ -284,14 +776,902 @@ async fn get_metrics(service: web::Data<MonitoringHttpService>) -> Result<HttpRe
     Ok(HttpResponse::Ok().json(metrics.clone()))
 }
 
+async fn get_metrics_history(
+    service: web::Data<MonitoringHttpService>,
+    query: web::Query<MetricsHistoryQuery>,
+    page: web::Query<PageQuery>,
+) -> Result<HttpResponse> {
+    // `from` reaches back as far as it says, otherwise `hours` does
+    let hours = match page.from {
+        Some(from) => (Utc::now() - from).num_hours().clamp(0, u32::MAX as i64) as u32 + 1,
+        None => query.hours.unwrap_or(1),
+    };
+    let aggregator = service.aggregator.read().await;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "summary": aggregator.get_summary_stats(hours),
+        "history": page.paginate(aggregator.get_history(hours), |m| m.timestamp),
+    })))
+}
+
+async fn get_agent_metrics(
+    service: web::Data<MonitoringHttpService>,
+    agent_id: web::Path<String>,
+) -> Result<HttpResponse> {
+    match service.aggregator.read().await.get_time_series(&agent_id) {
+        Some(series) => Ok(HttpResponse::Ok().json(series)),
+        None => Ok(HttpResponse::NotFound().json(serde_json::json!({
+            "error": format!("No metrics recorded for agent {}", agent_id),
+        }))),
+    }
+}
+
 async fn get_trading_status(service: web::Data<MonitoringHttpService>) -> Result<HttpResponse> {
     let trading = service.trading_status.read().await;
     Ok(HttpResponse::Ok().json(trading.clone()))
 }
 
+async fn get_pipeline(service: web::Data<MonitoringHttpService>) -> Result<HttpResponse> {
+    let mut pipeline = service.pipeline.read().await.clone();
+    if let Some(bus) = &service.events {
+        pipeline.event_backlog = bus.backlog();
+    }
+    Ok(HttpResponse::Ok().json(pipeline))
+}
+
+async fn get_bus_metrics(service: web::Data<MonitoringHttpService>) -> Result<HttpResponse> {
+    let Some(bus) = &service.events else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Event bus is not attached",
+        })));
+    };
+
+    Ok(HttpResponse::Ok().json(bus.metrics()))
+}
+
+async fn prometheus_metrics(service: web::Data<MonitoringHttpService>) -> Result<HttpResponse> {
+    let Some(bus) = &service.events else {
+        return Ok(HttpResponse::ServiceUnavailable().body("event bus is not attached\n"));
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(prometheus::CONTENT_TYPE)
+        .body(prometheus::render(&bus.metrics())))
+}
+
+async fn get_fleet_validation(service: web::Data<MonitoringHttpService>) -> Result<HttpResponse> {
+    match service.fleet_validation.read().await.as_ref() {
+        Some(report) => Ok(HttpResponse::Ok().json(report)),
+        None => Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Fleet validation has not run yet",
+        }))),
+    }
+}
+
+async fn get_strategy_performance(
+    service: web::Data<MonitoringHttpService>,
+) -> Result<HttpResponse> {
+    match service.strategy_performance.read().await.as_ref() {
+        Some(report) => Ok(HttpResponse::Ok().json(report)),
+        None => Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Strategies have not been scored yet",
+        }))),
+    }
+}
+
+async fn get_credentials(service: web::Data<MonitoringHttpService>) -> Result<HttpResponse> {
+    match &service.credentials {
+        Some(report) => Ok(HttpResponse::Ok().json(report)),
+        None => Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Exchange credentials have not been checked",
+        }))),
+    }
+}
+
+async fn get_subsystems(service: web::Data<MonitoringHttpService>) -> Result<HttpResponse> {
+    let subsystems = service.subsystems.read().await;
+    if subsystems.updated_at.is_none() {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "No subsystem status has been published yet",
+        })));
+    }
+    Ok(HttpResponse::Ok().json(&*subsystems))
+}
+
+async fn get_deployments(service: web::Data<MonitoringHttpService>) -> Result<HttpResponse> {
+    let Some(commander) = &service.deployment_commander else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Deployment commander is not configured",
+        })));
+    };
+
+    let mut servers: Vec<_> = commander
+        .get_deployment_status()
+        .await
+        .into_values()
+        .collect();
+    servers.sort_by(|a, b| a.server_id.cmp(&b.server_id));
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "timestamp": Utc::now(),
+        "servers": servers,
+    })))
+}
+
+async fn get_replication(
+    service: web::Data<MonitoringHttpService>,
+    query: web::Query<ReplicationQuery>,
+) -> Result<HttpResponse> {
+    let Some(replicator) = service.replicator.read().await.clone() else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Self-replicator is not configured",
+        })));
+    };
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "timestamp": Utc::now(),
+        "status": replicator.get_status().await,
+        "recent_results": replicator.recent_results(query.limit.unwrap_or(50)).await,
+        "lineage": replicator.get_lineage().await,
+    })))
+}
+
+async fn get_migrations(service: web::Data<MonitoringHttpService>) -> Result<HttpResponse> {
+    let migrations = service.migrations.read().await;
+    let active = migrations.iter().filter(|m| !m.stage.is_finished()).count();
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "timestamp": Utc::now(),
+        "active": active,
+        "migrations": migrations.iter().rev().collect::<Vec<_>>(),
+    })))
+}
+
+async fn patch_replication_strategy(
+    service: web::Data<MonitoringHttpService>,
+    req: HttpRequest,
+    patch: web::Json<serde_json::Value>,
+) -> Result<HttpResponse> {
+    let current = ReplicationStrategy::load(
+        std::path::Path::new(REPLICATION_CONFIG_PATH),
+        &service.identity,
+    );
+    patch_config_file(&service, &req, REPLICATION_CONFIG_PATH, current, &patch).await
+}
+
+async fn patch_health_thresholds(
+    service: web::Data<MonitoringHttpService>,
+    req: HttpRequest,
+    patch: web::Json<serde_json::Value>,
+) -> Result<HttpResponse> {
+    let current = HealthThresholds::load(HEALTH_CONFIG_PATH);
+    patch_config_file(&service, &req, HEALTH_CONFIG_PATH, current, &patch).await
+}
+
+/// Apply `patch` to the config file at `path` and ask the kernel to reload it.
+/// Guarded by the approval token since it loosens the agent's guardrails.
+async fn patch_config_file<T: Serialize>(
+    service: &MonitoringHttpService,
+    req: &HttpRequest,
+    path: &str,
+    current: anyhow::Result<T>,
+    patch: &serde_json::Value,
+) -> Result<HttpResponse> {
+    let (Some(gate), Some(bus)) = (&service.approvals, &service.events) else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Runtime config changes need the approval token and config reload",
+        })));
+    };
+    if !gate.is_authorized(bearer_token(req)) {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "A valid approval token is required",
+        })));
+    }
+
+    let patched = match current
+        .and_then(|current| Ok(serde_json::to_value(current)?))
+        .and_then(|current| apply_patch(&current, patch))
+    {
+        Ok(patched) => patched,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                "error": e.to_string(),
+            })))
+        }
+    };
+    let path = std::path::Path::new(path);
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name().and_then(|n| n.to_str())) else {
+        return Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Invalid config path {:?}", path),
+        })));
+    };
+    let files = BTreeMap::from([(name.to_string(), serde_json::to_string_pretty(&patched)?)]);
+    if let Err(e) = write_config_files(dir, &files) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": e.to_string(),
+        })));
+    }
+    audit::record(
+        AuditCategory::ConfigChange,
+        "patch_config",
+        serde_json::json!({
+            "file": name,
+            "patch": patch,
+        }),
+    );
+    if bus.send_control(AppEvent::ReloadConfig).await.is_err() {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Kernel is not accepting control events",
+        })));
+    }
+
+    tracing::info!(file = name, "Patched config at runtime");
+    Ok(HttpResponse::Ok().json(patched))
+}
+
+async fn get_applied_config(service: web::Data<MonitoringHttpService>) -> Result<HttpResponse> {
+    match service.applied_config.read().await.as_ref() {
+        Some(applied) => Ok(HttpResponse::Ok().json(applied)),
+        None => Ok(HttpResponse::NotFound().json(serde_json::json!({
+            "error": "No config has been pushed to this agent",
+        }))),
+    }
+}
+
+/// Write config pushed by the primary and ask the kernel to reload it.
+/// Guarded by the approval token, which the primary presents when it pushes.
+async fn apply_config(
+    service: web::Data<MonitoringHttpService>,
+    req: HttpRequest,
+    push: web::Json<ConfigPush>,
+) -> Result<HttpResponse> {
+    let (Some(gate), Some(bus)) = (&service.approvals, &service.events) else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Config push needs the approval token and config reload",
+        })));
+    };
+    if !gate.is_authorized(bearer_token(&req)) {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "A valid approval token is required",
+        })));
+    }
+
+    let push = push.into_inner();
+    if let Err(e) = write_config_files(std::path::Path::new(CONFIG_DIR), &push.files) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": e.to_string(),
+        })));
+    }
+    audit::record(
+        AuditCategory::ConfigChange,
+        "apply_config",
+        serde_json::json!({
+            "version": push.version,
+            "files": push
+                .files
+                .iter()
+                .map(|(name, contents)| (name.clone(), audit::digest(contents.as_bytes())))
+                .collect::<BTreeMap<_, _>>(),
+        }),
+    );
+    if bus.send_control(AppEvent::ReloadConfig).await.is_err() {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Kernel is not accepting control events",
+        })));
+    }
+
+    let applied = AppliedConfig {
+        version: push.version,
+        files: push.files.into_keys().collect(),
+        applied_at: Utc::now(),
+    };
+    tracing::info!(version = %applied.version, "Applied pushed config");
+    *service.applied_config.write().await = Some(applied.clone());
+    Ok(HttpResponse::Ok().json(applied))
+}
+
+async fn get_config_rollout(service: web::Data<MonitoringHttpService>) -> Result<HttpResponse> {
+    match service.rollout.read().await.as_ref() {
+        Some(status) => Ok(HttpResponse::Ok().json(status)),
+        None => Ok(HttpResponse::NotFound().json(serde_json::json!({
+            "error": "No config rollout has been started",
+        }))),
+    }
+}
+
+/// Start pushing config to the fleet; progress is reported on `/api/fleet/config`.
+/// Guarded by the approval token since it reconfigures every replica.
+async fn start_config_rollout(
+    service: web::Data<MonitoringHttpService>,
+    req: HttpRequest,
+    request: Option<web::Json<RolloutRequest>>,
+) -> Result<HttpResponse> {
+    let Some(gate) = &service.approvals else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Config rollout needs the approval token",
+        })));
+    };
+    if !gate.is_authorized(bearer_token(&req)) {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "A valid approval token is required",
+        })));
+    }
+    if !service.identity.is_primary() {
+        return Ok(HttpResponse::Forbidden().json(serde_json::json!({
+            "error": "Only the primary can roll out config",
+        })));
+    }
+
+    let mut current = service.rollout.write().await;
+    if current.as_ref().is_some_and(RolloutStatus::in_progress) {
+        return Ok(HttpResponse::Conflict().json(serde_json::json!({
+            "error": "A config rollout is already in progress",
+        })));
+    }
+
+    let request = request.map(|r| r.into_inner()).unwrap_or_default();
+    let rollout = match ConfigRollout::new(service.get_ref().clone(), request) {
+        Ok(rollout) => rollout,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                "error": e.to_string(),
+            })))
+        }
+    };
+    let version = rollout.version().to_string();
+    audit::record(
+        AuditCategory::ConfigChange,
+        "config_rollout",
+        serde_json::json!({ "version": version }),
+    );
+    // Claim the slot before the lock is released so concurrent requests conflict
+    *current = Some(rollout.initial_status());
+    drop(current);
+    tokio::spawn(rollout.run());
+
+    Ok(HttpResponse::Accepted().json(serde_json::json!({
+        "version": version,
+        "status": "/api/fleet/config",
+    })))
+}
+
+async fn get_decisions(
+    service: web::Data<MonitoringHttpService>,
+    query: web::Query<DecisionsQuery>,
+    page: web::Query<PageQuery>,
+) -> Result<HttpResponse> {
+    let Some(journal) = &service.decisions else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Decision journal is not configured",
+        })));
+    };
+
+    Ok(HttpResponse::Ok().json(page.paginate(journal.since(query.since), |r| r.timestamp)))
+}
+
+/// Individual fills from the trade ledger, oldest first
+async fn get_trades(
+    service: web::Data<MonitoringHttpService>,
+    page: web::Query<PageQuery>,
+) -> Result<HttpResponse> {
+    let Some(ledger) = &service.trades else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Trade ledger is not configured",
+        })));
+    };
+
+    Ok(HttpResponse::Ok().json(page.paginate(ledger.fills(), |fill| fill.timestamp)))
+}
+
+async fn get_trade_report(
+    service: web::Data<MonitoringHttpService>,
+    query: web::Query<TradeReportQuery>,
+) -> Result<HttpResponse> {
+    let Some(ledger) = &service.trades else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Trade ledger is not configured",
+        })));
+    };
+
+    let report = ledger
+        .report(query.from, query.to, query.period)
+        .with_cost_model(service.cost_model.clone());
+    match query.format.as_deref() {
+        None | Some("json") => Ok(HttpResponse::Ok().json(report)),
+        Some("csv") => Ok(HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .body(report.to_csv())),
+        Some(other) => Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Unknown report format '{}'", other),
+        }))),
+    }
+}
+
+async fn get_rate_limits(service: web::Data<MonitoringHttpService>) -> Result<HttpResponse> {
+    let Some(limiter) = &service.rate_limiter else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Rate limiter is not configured",
+        })));
+    };
+
+    Ok(HttpResponse::Ok().json(limiter.stats()))
+}
+
+/// Retune the running strategy, guarded by the approval token since it changes
+/// how the agent trades. The strategy module validates the update, so acceptance
+/// here only means it was queued.
+async fn set_strategy_param(
+    service: web::Data<MonitoringHttpService>,
+    req: HttpRequest,
+    update: web::Json<StrategyParamUpdate>,
+) -> Result<HttpResponse> {
+    let (Some(gate), Some(bus)) = (&service.approvals, &service.events) else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Strategy parameter updates need the approval token and the event bus",
+        })));
+    };
+    if !gate.is_authorized(bearer_token(&req)) {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "A valid approval token is required",
+        })));
+    }
+
+    let update = update.into_inner();
+    audit::record(AuditCategory::ConfigChange, "strategy_param", &update);
+    match bus
+        .send_control(AppEvent::StrategyParamUpdate(update.clone()))
+        .await
+    {
+        Ok(_) => Ok(HttpResponse::Accepted().json(update)),
+        Err(_) => Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Kernel is not accepting control events",
+        }))),
+    }
+}
+
+async fn subscribe_symbol(
+    service: web::Data<MonitoringHttpService>,
+    req: HttpRequest,
+    symbol: web::Path<String>,
+) -> Result<HttpResponse> {
+    let event = AppEvent::SubscribeSymbol(symbol.into_inner());
+    change_symbol_subscription(&service, &req, event).await
+}
+
+async fn unsubscribe_symbol(
+    service: web::Data<MonitoringHttpService>,
+    req: HttpRequest,
+    symbol: web::Path<String>,
+) -> Result<HttpResponse> {
+    let event = AppEvent::UnsubscribeSymbol(symbol.into_inner());
+    change_symbol_subscription(&service, &req, event).await
+}
+
+/// Change what the agent trades, guarded by the approval token
+async fn change_symbol_subscription(
+    service: &MonitoringHttpService,
+    req: &HttpRequest,
+    event: AppEvent,
+) -> Result<HttpResponse> {
+    let (Some(gate), Some(bus)) = (&service.approvals, &service.events) else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Symbol subscriptions need the approval token and the event bus",
+        })));
+    };
+    if !gate.is_authorized(bearer_token(req)) {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "A valid approval token is required",
+        })));
+    }
+
+    audit::record(AuditCategory::ConfigChange, "symbol_subscription", &event);
+    match bus.send_control(event.clone()).await {
+        Ok(_) => Ok(HttpResponse::Accepted().json(event)),
+        Err(_) => Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Kernel is not accepting control events",
+        }))),
+    }
+}
+
+/// Pull the kill switch: cancel all orders, close all positions, suspend trading
+async fn emergency_flatten(
+    service: web::Data<MonitoringHttpService>,
+    req: HttpRequest,
+    request: Option<web::Json<FlattenRequest>>,
+) -> Result<HttpResponse> {
+    let reason = request
+        .and_then(|request| request.into_inner().reason)
+        .unwrap_or_else(|| "requested by operator".to_string());
+    send_trading_control(&service, &req, AppEvent::EmergencyFlatten(reason)).await
+}
+
+async fn resume_trading(
+    service: web::Data<MonitoringHttpService>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    send_trading_control(&service, &req, AppEvent::ResumeTrading).await
+}
+
+/// Send a flatten or resume to the kernel, guarded by the approval token since
+/// it closes positions or lifts a safety stop
+async fn send_trading_control(
+    service: &MonitoringHttpService,
+    req: &HttpRequest,
+    event: AppEvent,
+) -> Result<HttpResponse> {
+    let (Some(gate), Some(bus)) = (&service.approvals, &service.events) else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Trading controls need the approval token and the event bus",
+        })));
+    };
+    if !gate.is_authorized(bearer_token(req)) {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "A valid approval token is required",
+        })));
+    }
+
+    audit::record(AuditCategory::Order, event.kind(), &event);
+    match bus.send_control(event.clone()).await {
+        Ok(_) => Ok(HttpResponse::Accepted().json(event)),
+        Err(_) => Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Kernel is not accepting control events",
+        }))),
+    }
+}
+
+/// The response refusing a request for the audit log, unless it carries the
+/// approval token
+fn audit_access_denied(service: &MonitoringHttpService, req: &HttpRequest) -> Option<HttpResponse> {
+    let Some(gate) = &service.approvals else {
+        return Some(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Audit log access needs the approval token",
+        })));
+    };
+    if !gate.is_authorized(bearer_token(req)) {
+        return Some(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "A valid approval token is required",
+        })));
+    }
+    None
+}
+
+/// The audit log as JSON Lines, exactly as stored so the chain can be re-verified.
+/// Guarded by the approval token since it holds the trade and deployment history.
+async fn export_audit_log(
+    service: web::Data<MonitoringHttpService>,
+    req: HttpRequest,
+    query: web::Query<AuditQuery>,
+) -> Result<HttpResponse> {
+    if let Some(response) = audit_access_denied(&service, &req) {
+        return Ok(response);
+    }
+    let Some(log) = audit::global() else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Audit log is not configured",
+        })));
+    };
+
+    match log.entries(query.since.unwrap_or(0)) {
+        Ok(entries) => {
+            let mut body = String::new();
+            for entry in entries {
+                body.push_str(&serde_json::to_string(&entry)?);
+                body.push('\n');
+            }
+            Ok(HttpResponse::Ok()
+                .content_type("application/x-ndjson")
+                .body(body))
+        }
+        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": e.to_string(),
+        }))),
+    }
+}
+
+async fn verify_audit_log(
+    service: web::Data<MonitoringHttpService>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    if let Some(response) = audit_access_denied(&service, &req) {
+        return Ok(response);
+    }
+    let Some(log) = audit::global() else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Audit log is not configured",
+        })));
+    };
+
+    match log.verify() {
+        Ok(entries) => Ok(HttpResponse::Ok().json(serde_json::json!({
+            "valid": true,
+            "entries": entries,
+        }))),
+        Err(e) => Ok(HttpResponse::Ok().json(serde_json::json!({
+            "valid": false,
+            "error": e.to_string(),
+        }))),
+    }
+}
+
+async fn get_approvals(
+    service: web::Data<MonitoringHttpService>,
+    page: web::Query<PageQuery>,
+) -> Result<HttpResponse> {
+    let Some(gate) = &service.approvals else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Approvals are not enabled",
+        })));
+    };
+
+    Ok(HttpResponse::Ok().json(page.paginate(gate.requests(), |r| r.requested_at)))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ApprovalDecision {
+    /// Recorded as `decided_by`
+    pub operator: Option<String>,
+}
+
+async fn approve(
+    service: web::Data<MonitoringHttpService>,
+    req: HttpRequest,
+    id: web::Path<String>,
+    body: Option<web::Json<ApprovalDecision>>,
+) -> Result<HttpResponse> {
+    decide_approval(&service, &req, &id, body, true)
+}
+
+async fn reject(
+    service: web::Data<MonitoringHttpService>,
+    req: HttpRequest,
+    id: web::Path<String>,
+    body: Option<web::Json<ApprovalDecision>>,
+) -> Result<HttpResponse> {
+    decide_approval(&service, &req, &id, body, false)
+}
+
+fn decide_approval(
+    service: &MonitoringHttpService,
+    req: &HttpRequest,
+    id: &str,
+    body: Option<web::Json<ApprovalDecision>>,
+    approved: bool,
+) -> Result<HttpResponse> {
+    let Some(gate) = &service.approvals else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Approvals are not enabled",
+        })));
+    };
+    if !gate.is_authorized(bearer_token(req)) {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "A valid approval token is required",
+        })));
+    }
+
+    let operator = body
+        .and_then(|body| body.into_inner().operator)
+        .unwrap_or_else(|| "operator".to_string());
+    let result = if approved {
+        gate.approve(id, &operator)
+    } else {
+        gate.reject(id, &operator)
+    };
+    match result {
+        Ok(request) => Ok(HttpResponse::Ok().json(request)),
+        Err(e @ ApprovalError::NotFound) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+            "error": e.to_string(),
+        }))),
+        Err(e @ ApprovalError::AlreadyDecided(_)) => {
+            Ok(HttpResponse::Conflict().json(serde_json::json!({
+                "error": e.to_string(),
+            })))
+        }
+    }
+}
+
+fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// Run a shell command across the fleet, guarded by the approval token since
+/// it gives shell access to every server
+async fn execute_fleet_command(
+    service: web::Data<MonitoringHttpService>,
+    req: HttpRequest,
+    body: web::Json<FleetCommand>,
+) -> Result<HttpResponse> {
+    let (Some(gate), Some(commander)) = (&service.approvals, &service.deployment_commander) else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Fleet commands need the approval token and a deployment commander",
+        })));
+    };
+    if !gate.is_authorized(bearer_token(&req)) {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "A valid approval token is required",
+        })));
+    }
+
+    let request = body.into_inner();
+    if request.command.trim().is_empty() {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "command must not be empty",
+        })));
+    }
+    audit::record(AuditCategory::SshCommand, "fleet_exec", &request);
+    match commander.execute_on_all(&request).await {
+        Ok(results) => Ok(HttpResponse::Ok().json(results)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": e.to_string(),
+        }))),
+    }
+}
+
+/// Follow a server's kernel log, guarded by the approval token since it holds
+/// an SSH session to the server open
+async fn stream_server_logs(
+    service: web::Data<MonitoringHttpService>,
+    req: HttpRequest,
+    server_id: web::Path<String>,
+) -> Result<HttpResponse> {
+    let Some(gate) = &service.approvals else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Remote log streaming needs the approval token",
+        })));
+    };
+    if !gate.is_authorized(bearer_token(&req)) {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "A valid approval token is required",
+        })));
+    }
+    let Some(commander) = service.deployment_commander.clone() else {
+        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Remote log streaming is not configured",
+        })));
+    };
+
+    match commander.stream_logs(&server_id).await {
+        Ok(stream) => Ok(HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .streaming(stream.map(|line| line.map(|l| web::Bytes::from(l + "\n"))))),
+        Err(e) => Ok(HttpResponse::BadGateway().json(serde_json::json!({
+            "error": e.to_string(),
+        }))),
+    }
+}
+
 async fn health_check() -> Result<HttpResponse> {
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "status": "ok",
         "timestamp": Utc::now(),
     })))
 }
+
+async fn liveness(service: web::Data<MonitoringHttpService>) -> Result<HttpResponse> {
+    let live = service.health.is_live(LIVENESS_TIMEOUT);
+    let body = serde_json::json!({
+        "live": live,
+        "timestamp": Utc::now(),
+    });
+
+    if live {
+        Ok(HttpResponse::Ok().json(body))
+    } else {
+        Ok(HttpResponse::ServiceUnavailable().json(body))
+    }
+}
+
+async fn readiness(service: web::Data<MonitoringHttpService>) -> Result<HttpResponse> {
+    let ready = service.health.is_ready();
+    let body = serde_json::json!({
+        "ready": ready,
+        "components": service.health.components(),
+        "timestamp": Utc::now(),
+    });
+
+    if ready {
+        Ok(HttpResponse::Ok().json(body))
+    } else {
+        Ok(HttpResponse::ServiceUnavailable().json(body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use autonomy_core::ApprovalGate;
+
+    #[actix_web::test]
+    async fn test_config_rollout_needs_the_approval_token() {
+        let service = web::Data::new(MonitoringHttpService::new(0));
+        let response =
+            start_config_rollout(service, TestRequest::default().to_http_request(), None)
+                .await
+                .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let mut http = MonitoringHttpService::new(0);
+        http.approvals = Some(ApprovalGate::default().with_token("secret".to_string()));
+        let service = web::Data::new(http);
+        for token in [None, Some("wrong")] {
+            let mut req = TestRequest::default();
+            if let Some(token) = token {
+                req = req.insert_header(("Authorization", format!("Bearer {}", token)));
+            }
+            let response = start_config_rollout(service.clone(), req.to_http_request(), None)
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        assert!(service.rollout.read().await.is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_audit_log_needs_the_approval_token() {
+        let mut http = MonitoringHttpService::new(0);
+        http.approvals = Some(ApprovalGate::default().with_token("secret".to_string()));
+        let service = web::Data::new(http);
+        for token in [None, Some("wrong")] {
+            let mut req = TestRequest::default();
+            if let Some(token) = token {
+                req = req.insert_header(("Authorization", format!("Bearer {}", token)));
+            }
+            let req = req.to_http_request();
+            let query = web::Query(AuditQuery { since: None });
+            let response = export_audit_log(service.clone(), req.clone(), query)
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            let response = verify_audit_log(service.clone(), req).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_log_streaming_needs_the_approval_token() {
+        let mut http = MonitoringHttpService::new(0);
+        http.approvals = Some(ApprovalGate::default().with_token("secret".to_string()));
+        let service = web::Data::new(http);
+        let server_id = web::Path::from("server-1".to_string());
+        let response = stream_server_logs(
+            service.clone(),
+            TestRequest::default().to_http_request(),
+            server_id,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // Past the token, the missing commander is what stops it
+        let req = TestRequest::default()
+            .insert_header(("Authorization", "Bearer secret"))
+            .to_http_request();
+        let server_id = web::Path::from("server-1".to_string());
+        let response = stream_server_logs(service, req, server_id).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[actix_web::test]
+    async fn test_cluster_status_reports_the_fleet_runway() {
+        let mut http = MonitoringHttpService::new(0);
+        let (_budget_tx, budget) = watch::channel(Budget {
+            funds: 12.0,
+            hourly_cost: 0.5,
+            replicas: 2,
+            replica_funds: 60.0,
+            ..Budget::default()
+        });
+        http.budget = Some(budget);
+        let response = get_cluster_status(web::Data::new(http)).await.unwrap();
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        let status: ClusterStatus = serde_json::from_slice(&body).unwrap();
+
+        let runway = status.runway.unwrap();
+        assert_eq!(runway.runway_hours, 24.0);
+        // 72 across three agents at 0.5 each
+        assert_eq!(runway.fleet_runway_hours, Some(48.0));
+    }
+}
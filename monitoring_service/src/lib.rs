@@ -1,16 +1,58 @@
+pub mod admin_socket;
+pub mod aggregator;
+pub mod cluster_registry;
+pub mod config_rollout;
+mod dashboard;
+pub mod fleet_validator;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 pub mod http_server;
-pub mod simple_server;
+pub mod log_shipper;
+pub mod log_store;
+pub mod pagination;
+pub mod prometheus;
 
+use autonomy_core::{ApprovalGate, DecisionJournal, DeploymentCommander};
+use common::{
+    AgentIdentity, CostModel, CredentialReport, EventBus, FleetKey, HealthState, RateLimiter,
+    StateStore, TradeLedger,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use survival_protocol::Budget;
+use tokio::sync::{watch, RwLock};
+
+pub use admin_socket::{AdminAddress, AdminCommand, AdminResponse, AdminSocket};
+pub use aggregator::{FleetTrading, MetricsAggregator};
+pub use cluster_registry::HttpClusterRegistry;
+pub use config_rollout::{ConfigRollout, RolloutRequest, RolloutStatus};
+pub use fleet_validator::{FleetValidationConfig, FleetValidator, FLEET_VALIDATION_CONFIG_PATH};
 pub use http_server::{
-    AgentStatus, ClusterStatus, MonitoringHttpService, SystemMetrics, TradingStatus,
+    AgentStatus, ClusterStatus, MonitoringHttpService, PipelineMetrics, RunwayStatus,
+    SystemMetrics, TradingStatus,
 };
-pub use simple_server::SimpleAgentStatus;
-use simple_server::SimpleMonitoringService;
+pub use log_shipper::{LogShipper, LogShipperConfig};
+pub use log_store::{LogBatch, LogEntry, LogLine, LogStore};
 
-#[derive(Debug, Clone)]
+pub const MONITORING_CONFIG_PATH: &str = "config/monitoring.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MonitoringConfig {
     pub port: u16,
     pub use_http: bool,
+    /// Port of the gRPC API, served alongside the HTTP API by agents built with
+    /// the `grpc` feature
+    pub grpc_port: Option<u16>,
+    /// Where the local admin socket listens; served whether or not HTTP is enabled
+    #[serde(skip)]
+    pub admin: Option<AdminAddress>,
+    /// How long aggregated fleet metrics are kept
+    pub metrics_retention_days: u32,
+    /// URL the replicas this agent deploys reach its API at; derived from the
+    /// address this machine connects out from and `port` when unset
+    pub advertised_url: Option<String>,
 }
 
 impl Default for MonitoringConfig {
@@ -18,12 +60,46 @@ impl Default for MonitoringConfig {
         Self {
             port: 8080,
             use_http: true,
+            grpc_port: Some(50051),
+            admin: Some(AdminAddress::default()),
+            metrics_retention_days: 1,
+            advertised_url: None,
+        }
+    }
+}
+
+impl MonitoringConfig {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
         }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Where other agents reach this agent's API, `None` without HTTP or when no
+    /// address can be found.
+    pub fn advertised_url(&self) -> Option<String> {
+        if !self.use_http {
+            return None;
+        }
+        if let Some(url) = &self.advertised_url {
+            return Some(url.trim_end_matches('/').to_string());
+        }
+        // Connecting a UDP socket sends nothing, it only picks the outbound address
+        let socket = std::net::UdpSocket::bind(("0.0.0.0", 0)).ok()?;
+        socket.connect(("192.0.2.1", 9)).ok()?;
+        let ip = socket.local_addr().ok()?.ip();
+        Some(format!(
+            "http://{}",
+            common::ssh::host_port(&ip.to_string(), self.port)
+        ))
     }
 }
 
 pub struct MonitoringService {
-    inner: SimpleMonitoringService,
+    admin: Option<AdminSocket>,
     http_service: Option<MonitoringHttpService>,
     config: MonitoringConfig,
 }
@@ -31,29 +107,158 @@ pub struct MonitoringService {
 impl MonitoringService {
     pub fn new(config: MonitoringConfig) -> Self {
         let http_service = if config.use_http {
-            Some(MonitoringHttpService::new(config.port))
+            let mut http_service = MonitoringHttpService::new(config.port);
+            http_service.aggregator = Arc::new(RwLock::new(MetricsAggregator::new(
+                config.metrics_retention_days,
+            )));
+            Some(http_service)
         } else {
             None
         };
 
         Self {
-            inner: SimpleMonitoringService::new(config.port),
+            admin: config.admin.clone().map(AdminSocket::new),
             http_service,
             config,
         }
     }
 
+    /// Enable the remote log streaming endpoint using the given commander
+    pub fn with_deployment_commander(mut self, commander: Arc<DeploymentCommander>) -> Self {
+        if let Some(http_service) = self.http_service.as_mut() {
+            http_service.deployment_commander = Some(commander);
+        }
+        self
+    }
+
+    /// Serve the decision audit trail on `/api/decisions`
+    pub fn with_decision_journal(mut self, journal: DecisionJournal) -> Self {
+        if let Some(http_service) = self.http_service.as_mut() {
+            http_service.decisions = Some(journal);
+        }
+        self
+    }
+
+    /// Serve pending approvals on `/api/approvals`
+    pub fn with_approval_gate(mut self, gate: ApprovalGate) -> Self {
+        if let Some(http_service) = self.http_service.as_mut() {
+            http_service.approvals = Some(gate);
+        }
+        self
+    }
+
+    /// Back `/live`, `/ready` and the admin socket's `status` with the agent's health state
+    pub fn with_health(mut self, health: HealthState) -> Self {
+        self.admin = self.admin.map(|admin| admin.with_health(health.clone()));
+        if let Some(http_service) = self.http_service.as_mut() {
+            http_service.health = health;
+        }
+        self
+    }
+
+    /// Accept strategy parameter updates on `/api/strategy/params` and forward them
+    /// to the kernel over `bus`, and report its metrics on `/api/bus` and `/metrics`.
+    /// Admin socket commands are forwarded over the same bus.
+    pub fn with_event_bus(mut self, bus: EventBus) -> Self {
+        self.admin = self.admin.map(|admin| admin.with_event_bus(bus.clone()));
+        if let Some(http_service) = self.http_service.as_mut() {
+            http_service.events = Some(bus);
+        }
+        self
+    }
+
+    /// Report the survival protocol's runway, and the fleet's on the primary, on
+    /// `/api/cluster/status`
+    pub fn with_budget(mut self, budget: watch::Receiver<Budget>) -> Self {
+        if let Some(http_service) = self.http_service.as_mut() {
+            http_service.budget = Some(budget);
+        }
+        self
+    }
+
+    /// Report outbound request throttling on `/api/rate_limits`
+    pub fn with_rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        if let Some(http_service) = self.http_service.as_mut() {
+            http_service.rate_limiter = Some(limiter);
+        }
+        self
+    }
+
+    /// Serve trade reports on `/api/reports/trades`
+    pub fn with_trade_ledger(mut self, ledger: TradeLedger) -> Self {
+        if let Some(http_service) = self.http_service.as_mut() {
+            http_service.trades = Some(ledger);
+        }
+        self
+    }
+
+    /// The cost model simulated fills are priced with, shown in trade reports
+    pub fn with_cost_model(mut self, model: CostModel) -> Self {
+        if let Some(http_service) = self.http_service.as_mut() {
+            http_service.cost_model = model;
+        }
+        self
+    }
+
+    /// Accept log batches from replicas presenting the fleet token of `key`
+    pub fn with_fleet_key(mut self, key: FleetKey) -> Self {
+        if let Some(http_service) = self.http_service.as_mut() {
+            http_service.fleet_key = Some(key);
+        }
+        self
+    }
+
+    /// Report the exchange credential check on `/api/credentials`
+    pub fn with_credential_report(mut self, report: CredentialReport) -> Self {
+        if let Some(http_service) = self.http_service.as_mut() {
+            http_service.credentials = Some(report);
+        }
+        self
+    }
+
+    /// Report the local agent under its persistent identity
+    pub fn with_identity(mut self, identity: AgentIdentity) -> Self {
+        self.admin = self
+            .admin
+            .map(|admin| admin.with_identity(identity.clone()));
+        if let Some(http_service) = self.http_service.as_mut() {
+            http_service.identity = identity;
+        }
+        self
+    }
+
+    /// Serve `dump_state` on the admin socket
+    pub fn with_state_store(mut self, state: StateStore) -> Self {
+        self.admin = self.admin.map(|admin| admin.with_state_store(state));
+        self
+    }
+
     pub async fn start(self: std::sync::Arc<Self>) -> anyhow::Result<()> {
+        if let Some(admin) = &self.admin {
+            let admin = admin.clone();
+            tokio::spawn(async move {
+                if let Err(e) = admin.run().await {
+                    tracing::error!("Admin socket error: {}", e);
+                }
+            });
+        }
+
         if self.config.use_http {
             if let Some(http_service) = &self.http_service {
                 // 启动HTTP服务器
                 http_service.clone().start_server();
                 println!("✅ Rust监控API已启动在端口 {}", self.config.port);
+
+                #[cfg(feature = "grpc")]
+                if let Some(port) = self.config.grpc_port {
+                    let grpc = grpc::GrpcService::new(http_service.clone());
+                    tokio::spawn(async move {
+                        if let Err(e) = grpc.serve(port).await {
+                            tracing::error!("gRPC API error: {}", e);
+                        }
+                    });
+                }
             }
-        } else {
-            // 使用简单监控服务
-            let inner = std::sync::Arc::new(self.inner.clone());
-            inner.start().await?;
         }
         Ok(())
     }
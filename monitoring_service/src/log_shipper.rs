@@ -0,0 +1,199 @@
+use crate::http_server::TradingStatus;
+use crate::log_store::{LogBatch, LogLine};
+use chrono::Utc;
+use common::{AgentIdentity, FleetKey, SecretStore};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+/// Upper bound on unsent lines kept while the primary is unreachable
+const MAX_PENDING_LINES: usize = 50_000;
+
+#[derive(Debug, Clone)]
+pub struct LogShipperConfig {
+    /// Base URL of the primary's monitoring API, e.g. `http://10.0.0.1:8080`
+    pub primary_url: String,
+    pub agent_id: String,
+    /// Sent with every batch so the primary can place the agent in the replica tree
+    pub identity: AgentIdentity,
+    pub log_path: PathBuf,
+    /// [`FleetKey::auth_token`] the primary requires with every batch
+    pub fleet_token: Option<String>,
+    pub batch_size: usize,
+    pub flush_interval: Duration,
+}
+
+impl LogShipperConfig {
+    /// Build a configuration from the environment.
+    ///
+    /// Returns `None` unless `AURELIA_PRIMARY_URL` is set, i.e. on the primary itself.
+    /// `AURELIA_AGENT_ID` defaults to the agent's persistent ID and `AURELIA_LOG_PATH`
+    /// to `logs/aurelia.log`. The fleet token comes from the fleet key in the secret
+    /// store, i.e. `AURELIA_SECRET_FLEET_CONFIG_KEY`.
+    pub fn from_env(identity: AgentIdentity) -> Option<Self> {
+        let primary_url = std::env::var("AURELIA_PRIMARY_URL").ok()?;
+        let agent_id =
+            std::env::var("AURELIA_AGENT_ID").unwrap_or_else(|_| identity.agent_id.clone());
+        let log_path = std::env::var("AURELIA_LOG_PATH")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("logs/aurelia.log"));
+        let fleet_token = match FleetKey::load(&SecretStore::default()) {
+            Ok(key) => key.map(|key| key.auth_token()),
+            Err(e) => {
+                tracing::error!("Invalid fleet key: {}", e);
+                None
+            }
+        };
+
+        Some(Self {
+            primary_url,
+            agent_id,
+            identity,
+            log_path,
+            fleet_token,
+            batch_size: 500,
+            flush_interval: Duration::from_secs(5),
+        })
+    }
+}
+
+/// Tails the local log file and forwards new lines to the primary's monitoring API
+pub struct LogShipper {
+    config: LogShipperConfig,
+    client: reqwest::Client,
+    offset: u64,
+    partial: String,
+    trading: Option<Arc<RwLock<TradingStatus>>>,
+}
+
+impl LogShipper {
+    pub fn new(config: LogShipperConfig) -> Self {
+        // Only ship what is written from now on
+        let offset = std::fs::metadata(&config.log_path)
+            .map(|m| m.len())
+            .unwrap_or(0);
+
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            offset,
+            partial: String::new(),
+            trading: None,
+        }
+    }
+
+    /// Report this agent's trading status and NAV with every batch
+    pub fn with_trading_status(mut self, trading: Arc<RwLock<TradingStatus>>) -> Self {
+        self.trading = Some(trading);
+        self
+    }
+
+    pub async fn run(mut self) {
+        if self.config.fleet_token.is_none() {
+            tracing::warn!("No fleet key, the primary will refuse the shipped logs");
+        }
+        tracing::info!(
+            "Shipping {:?} to {} as agent {}",
+            self.config.log_path,
+            self.config.primary_url,
+            self.config.agent_id
+        );
+
+        let mut pending: Vec<LogLine> = Vec::new();
+        let mut interval = tokio::time::interval(self.config.flush_interval);
+
+        loop {
+            interval.tick().await;
+
+            match self.read_new_lines() {
+                Ok(lines) => pending.extend(lines),
+                Err(e) => tracing::debug!("Failed to read {:?}: {}", self.config.log_path, e),
+            }
+
+            // An empty batch still serves as a heartbeat
+            if pending.is_empty() {
+                if let Err(e) = self.ship(&[]).await {
+                    tracing::debug!("Failed to send heartbeat to primary: {}", e);
+                }
+            }
+
+            while !pending.is_empty() {
+                let count = pending.len().min(self.config.batch_size);
+                match self.ship(&pending[..count]).await {
+                    Ok(()) => {
+                        pending.drain(..count);
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to ship logs to primary: {}", e);
+                        break;
+                    }
+                }
+            }
+
+            if pending.len() > MAX_PENDING_LINES {
+                let excess = pending.len() - MAX_PENDING_LINES;
+                pending.drain(..excess);
+                tracing::warn!("Dropped {} unsent log lines", excess);
+            }
+        }
+    }
+
+    /// Read complete lines appended since the last call
+    fn read_new_lines(&mut self) -> std::io::Result<Vec<LogLine>> {
+        let mut file = File::open(&self.config.log_path)?;
+        let len = file.metadata()?.len();
+
+        // The file was truncated or rotated, start over
+        if len < self.offset {
+            self.offset = 0;
+            self.partial.clear();
+        }
+
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+        self.offset += buf.len() as u64;
+        self.partial.push_str(&String::from_utf8_lossy(&buf));
+
+        let Some(last_newline) = self.partial.rfind('\n') else {
+            return Ok(Vec::new());
+        };
+        let rest = self.partial.split_off(last_newline + 1);
+        let complete = std::mem::replace(&mut self.partial, rest);
+
+        let now = Utc::now();
+        Ok(complete
+            .lines()
+            .map(|line| LogLine {
+                timestamp: now,
+                line: line.to_string(),
+            })
+            .collect())
+    }
+
+    async fn ship(&self, lines: &[LogLine]) -> anyhow::Result<()> {
+        let url = format!(
+            "{}/api/agents/{}/logs",
+            self.config.primary_url.trim_end_matches('/'),
+            self.config.agent_id
+        );
+
+        let trading = match &self.trading {
+            Some(trading) => Some(trading.read().await.clone()),
+            None => None,
+        };
+        let mut request = self.client.post(url).json(&LogBatch {
+            lines: lines.to_vec(),
+            identity: Some(self.config.identity.clone()),
+            trading,
+        });
+        if let Some(token) = &self.config.fleet_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use crate::http_server::TradingStatus;
+use chrono::{DateTime, Utc};
+use common::AgentIdentity;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// Number of log lines kept per agent before the oldest are evicted
+pub const DEFAULT_LOG_CAPACITY: usize = 10_000;
+
+/// A log line as read by the shipping agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogLine {
+    pub timestamp: DateTime<Utc>,
+    pub line: String,
+}
+
+/// Payload POSTed by a replica to `/api/agents/{id}/logs`
+///
+/// Replicas send a batch on every flush, even an empty one, so it doubles as a
+/// heartbeat carrying the sender's identity and trading status.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogBatch {
+    pub lines: Vec<LogLine>,
+    #[serde(default)]
+    pub identity: Option<AgentIdentity>,
+    #[serde(default)]
+    pub trading: Option<TradingStatus>,
+}
+
+/// A stored log line; `seq` is the cursor used by `?since=`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    pub line: String,
+}
+
+#[derive(Debug, Default)]
+struct AgentLogs {
+    next_seq: u64,
+    entries: VecDeque<LogEntry>,
+}
+
+/// In-memory ring buffer of shipped logs, one per agent
+#[derive(Debug)]
+pub struct LogStore {
+    capacity: usize,
+    agents: HashMap<String, AgentLogs>,
+}
+
+impl Default for LogStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOG_CAPACITY)
+    }
+}
+
+impl LogStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            agents: HashMap::new(),
+        }
+    }
+
+    /// Append lines for an agent, returning the sequence number of the last stored line
+    pub fn append(&mut self, agent_id: &str, lines: Vec<LogLine>) -> u64 {
+        let logs = self.agents.entry(agent_id.to_string()).or_default();
+
+        for line in lines {
+            logs.next_seq += 1;
+            logs.entries.push_back(LogEntry {
+                seq: logs.next_seq,
+                timestamp: line.timestamp,
+                line: line.line,
+            });
+        }
+
+        while logs.entries.len() > self.capacity {
+            logs.entries.pop_front();
+        }
+
+        logs.next_seq
+    }
+
+    /// Timestamp of the newest line an agent has shipped
+    pub fn last_timestamp(&self, agent_id: &str) -> Option<DateTime<Utc>> {
+        self.agents
+            .get(agent_id)?
+            .entries
+            .back()
+            .map(|e| e.timestamp)
+    }
+
+    /// Lines of an agent with a sequence number greater than `since`
+    pub fn since(&self, agent_id: &str, since: u64) -> Vec<LogEntry> {
+        self.agents
+            .get(agent_id)
+            .map(|logs| {
+                logs.entries
+                    .iter()
+                    .filter(|e| e.seq > since)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(texts: &[&str]) -> Vec<LogLine> {
+        texts
+            .iter()
+            .map(|t| LogLine {
+                timestamp: Utc::now(),
+                line: t.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ring_buffer_and_since_cursor() {
+        let mut store = LogStore::new(3);
+
+        assert_eq!(store.append("replica-1", lines(&["a", "b"])), 2);
+        assert_eq!(store.append("replica-1", lines(&["c", "d"])), 4);
+
+        // "a" was evicted, sequence numbers keep counting
+        let all: Vec<_> = store.since("replica-1", 0);
+        assert_eq!(
+            all.iter().map(|e| e.line.as_str()).collect::<Vec<_>>(),
+            vec!["b", "c", "d"]
+        );
+
+        let newer = store.since("replica-1", 3);
+        assert_eq!(newer.len(), 1);
+        assert_eq!(newer[0].seq, 4);
+
+        assert!(store.since("unknown", 0).is_empty());
+    }
+}
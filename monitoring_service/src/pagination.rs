@@ -0,0 +1,139 @@
+//! Paging and time filtering shared by the list endpoints.
+//!
+//! List endpoints take `?from=&to=` (RFC 3339, `from` inclusive, `to`
+//! exclusive), `?page=&limit=` (1-based page, [`DEFAULT_LIMIT`] items by
+//! default and never more than [`MAX_LIMIT`]) and `?order=asc|desc`. Items are
+//! kept in the order their source yields them, oldest first for histories, so
+//! a page holds the same items however often it is requested.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_LIMIT: usize = 100;
+pub const MAX_LIMIT: usize = 1000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Order {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PageQuery {
+    /// Only items at or after this time (RFC 3339)
+    pub from: Option<DateTime<Utc>>,
+    /// Only items before this time (RFC 3339)
+    pub to: Option<DateTime<Utc>>,
+    /// Page to return, starting at 1
+    pub page: Option<usize>,
+    /// Items per page
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub order: Order,
+}
+
+/// One page of a list endpoint's items.
+#[derive(Debug, Clone, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub page: usize,
+    pub limit: usize,
+    /// Items matching the time filter, on all pages
+    pub total: usize,
+    pub has_more: bool,
+}
+
+impl PageQuery {
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+
+    pub fn page(&self) -> usize {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    /// Whether `at` falls within `from` and `to`.
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        self.from.iter().all(|from| at >= *from) && self.to.iter().all(|to| at < *to)
+    }
+
+    /// The requested page of `items`, after filtering them by the time `timestamp`
+    /// gives each.
+    pub fn paginate<T>(
+        &self,
+        items: impl IntoIterator<Item = T>,
+        timestamp: impl Fn(&T) -> DateTime<Utc>,
+    ) -> Page<T> {
+        let mut items: Vec<T> = items
+            .into_iter()
+            .filter(|item| self.contains(timestamp(item)))
+            .collect();
+        if self.order == Order::Desc {
+            items.reverse();
+        }
+        let (page, limit, total) = (self.page(), self.limit(), items.len());
+        let start = (page - 1).saturating_mul(limit).min(total);
+        let end = start.saturating_add(limit).min(total);
+        Page {
+            items: items.drain(start..end).collect(),
+            page,
+            limit,
+            total,
+            has_more: end < total,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn test_pages_are_filtered_by_time_and_stable() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let items: Vec<_> = (0..10).map(|i| (i, start + Duration::hours(i))).collect();
+        let query = PageQuery {
+            from: Some(start + Duration::hours(2)),
+            to: Some(start + Duration::hours(9)),
+            page: Some(2),
+            limit: Some(3),
+            ..Default::default()
+        };
+
+        let page = query.paginate(items.clone(), |(_, at)| *at);
+        assert_eq!(page.total, 7);
+        assert_eq!(
+            page.items.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+            [5, 6, 7]
+        );
+        assert!(page.has_more);
+
+        let last = PageQuery {
+            page: Some(3),
+            ..query.clone()
+        }
+        .paginate(items.clone(), |(_, at)| *at);
+        assert_eq!(last.items.len(), 1);
+        assert!(!last.has_more);
+
+        let newest = PageQuery {
+            order: Order::Desc,
+            page: None,
+            ..query
+        }
+        .paginate(items.clone(), |(_, at)| *at);
+        assert_eq!(newest.items[0].0, 8);
+
+        let beyond = PageQuery {
+            page: Some(100),
+            limit: Some(100_000),
+            ..Default::default()
+        }
+        .paginate(items, |(_, at)| *at);
+        assert!(beyond.items.is_empty());
+        assert_eq!(beyond.limit, MAX_LIMIT);
+    }
+}
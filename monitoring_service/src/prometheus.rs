@@ -0,0 +1,181 @@
+//! Event bus metrics in the Prometheus text exposition format, served on `/metrics`.
+
+use common::BusMetricsSnapshot;
+use std::fmt::Write;
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Label values may not contain raw quotes, backslashes or newlines
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+pub fn render(metrics: &BusMetricsSnapshot) -> String {
+    let mut out = String::new();
+
+    header(
+        &mut out,
+        "aurelia_bus_events_published_total",
+        "counter",
+        "Events published on the event bus",
+    );
+    for (kind, counters) in &metrics.events {
+        let _ = writeln!(
+            out,
+            "aurelia_bus_events_published_total{{event=\"{}\"}} {}",
+            kind, counters.published
+        );
+    }
+    header(
+        &mut out,
+        "aurelia_bus_events_delivered_total",
+        "counter",
+        "Copies of events handed to subscriptions",
+    );
+    for (kind, counters) in &metrics.events {
+        let _ = writeln!(
+            out,
+            "aurelia_bus_events_delivered_total{{event=\"{}\"}} {}",
+            kind, counters.delivered
+        );
+    }
+    header(
+        &mut out,
+        "aurelia_bus_events_unrouted_total",
+        "counter",
+        "Events no subscription was interested in",
+    );
+    for (kind, counters) in &metrics.events {
+        let _ = writeln!(
+            out,
+            "aurelia_bus_events_unrouted_total{{event=\"{}\"}} {}",
+            kind, counters.unrouted
+        );
+    }
+
+    header(
+        &mut out,
+        "aurelia_bus_subscriber_backlog",
+        "gauge",
+        "Events waiting to be received by a subscriber",
+    );
+    for s in &metrics.subscribers {
+        let _ = writeln!(
+            out,
+            "aurelia_bus_subscriber_backlog{{subscriber=\"{}\"}} {}",
+            escape(&s.name),
+            s.backlog
+        );
+    }
+    header(
+        &mut out,
+        "aurelia_bus_subscriber_capacity",
+        "gauge",
+        "Backlog at which a subscriber starts losing events",
+    );
+    for s in &metrics.subscribers {
+        let _ = writeln!(
+            out,
+            "aurelia_bus_subscriber_capacity{{subscriber=\"{}\"}} {}",
+            escape(&s.name),
+            s.capacity
+        );
+    }
+    header(
+        &mut out,
+        "aurelia_bus_subscriber_received_total",
+        "counter",
+        "Events received by a subscriber",
+    );
+    for s in &metrics.subscribers {
+        let _ = writeln!(
+            out,
+            "aurelia_bus_subscriber_received_total{{subscriber=\"{}\"}} {}",
+            escape(&s.name),
+            s.received
+        );
+    }
+    header(
+        &mut out,
+        "aurelia_bus_subscriber_lagged_total",
+        "counter",
+        "Events a subscriber lost because its backlog overflowed",
+    );
+    for s in &metrics.subscribers {
+        let _ = writeln!(
+            out,
+            "aurelia_bus_subscriber_lagged_total{{subscriber=\"{}\"}} {}",
+            escape(&s.name),
+            s.lagged
+        );
+    }
+
+    header(
+        &mut out,
+        "aurelia_bus_receive_latency_seconds",
+        "histogram",
+        "Time from publishing an event to a subscriber receiving it",
+    );
+    for s in &metrics.subscribers {
+        let name = escape(&s.name);
+        for (le, count) in &s.latency.buckets {
+            let _ = writeln!(
+                out,
+                "aurelia_bus_receive_latency_seconds_bucket{{subscriber=\"{}\",le=\"{}\"}} {}",
+                name, le, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "aurelia_bus_receive_latency_seconds_bucket{{subscriber=\"{}\",le=\"+Inf\"}} {}",
+            name, s.latency.count
+        );
+        let _ = writeln!(
+            out,
+            "aurelia_bus_receive_latency_seconds_sum{{subscriber=\"{}\"}} {}",
+            name, s.latency.sum_seconds
+        );
+        let _ = writeln!(
+            out,
+            "aurelia_bus_receive_latency_seconds_count{{subscriber=\"{}\"}} {}",
+            name, s.latency.count
+        );
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{AppEvent, EventBus, SystemState, Topic};
+
+    #[test]
+    fn test_render_bus_metrics() {
+        let bus = EventBus::new(8);
+        let mut rx = bus.subscribe_as("survival_protocol", &[Topic::System]);
+        bus.send(AppEvent::SystemStateChange(SystemState::Conservation))
+            .unwrap();
+        rx.try_recv().unwrap();
+
+        let text = render(&bus.metrics());
+        assert!(
+            text.contains("aurelia_bus_events_published_total{event=\"system_state_change\"} 1\n")
+        );
+        assert!(
+            text.contains("aurelia_bus_subscriber_backlog{subscriber=\"survival_protocol\"} 0\n")
+        );
+        assert!(text.contains(
+            "aurelia_bus_receive_latency_seconds_bucket{subscriber=\"survival_protocol\",le=\"+Inf\"} 1\n"
+        ));
+        assert!(text.contains("# TYPE aurelia_bus_receive_latency_seconds histogram\n"));
+    }
+}
@@ -0,0 +1,220 @@
+// Polls the monitoring API and renders the dashboard. Every section tolerates its
+// endpoint being unavailable (503 when a feature is not configured on this agent).
+
+const REFRESH_MS = 5000;
+const DECISION_LIMIT = 20;
+
+let selectedAgent = null;
+
+async function getJson(path) {
+    try {
+        const response = await fetch(path);
+        const body = await response.json().catch(() => null);
+        return { ok: response.ok, status: response.status, body };
+    } catch (error) {
+        return { ok: false, status: 0, body: null };
+    }
+}
+
+function escapeHtml(value) {
+    return String(value ?? '').replace(/[&<>"']/g, c => ({
+        '&': '&amp;', '<': '&lt;', '>': '&gt;', '"': '&quot;', "'": '&#39;',
+    })[c]);
+}
+
+function setText(id, text) {
+    document.getElementById(id).textContent = text;
+}
+
+function statusClass(status) {
+    switch (status) {
+        case 'Running': return 'ok';
+        case 'Degraded': return 'warning';
+        case 'Offline': return 'critical';
+        default: return '';
+    }
+}
+
+// Draws percentage series (0-100) as lines sharing the x axis
+function drawChart(canvas, series) {
+    const ctx = canvas.getContext('2d');
+    const width = canvas.width = canvas.clientWidth * window.devicePixelRatio;
+    const height = canvas.height = canvas.clientHeight * window.devicePixelRatio;
+    ctx.clearRect(0, 0, width, height);
+
+    ctx.strokeStyle = '#edf2f7';
+    ctx.lineWidth = 1;
+    for (const level of [25, 50, 75]) {
+        const y = height - (level / 100) * height;
+        ctx.beginPath();
+        ctx.moveTo(0, y);
+        ctx.lineTo(width, y);
+        ctx.stroke();
+    }
+
+    for (const { values, color } of series) {
+        if (values.length < 2) continue;
+        ctx.strokeStyle = color;
+        ctx.lineWidth = 2 * window.devicePixelRatio;
+        ctx.beginPath();
+        values.forEach((value, i) => {
+            const x = (i / (values.length - 1)) * width;
+            const y = height - (Math.min(Math.max(value, 0), 100) / 100) * height;
+            if (i === 0) ctx.moveTo(x, y); else ctx.lineTo(x, y);
+        });
+        ctx.stroke();
+    }
+}
+
+function renderCluster(cluster, history) {
+    if (!cluster) return;
+    const dot = document.getElementById('clusterDot');
+    dot.className = 'dot ' + (cluster.cluster_health === 'Healthy' ? 'ok' : 'warning');
+    setText('clusterHealth', `Cluster ${cluster.cluster_health}`);
+    setText('agentCount', `${cluster.total_agents} agents`);
+    setText('healthyAgents', cluster.healthy_agents);
+    setText('degradedAgents', cluster.degraded_agents);
+    setText('offlineAgents', cluster.offline_agents);
+
+    const agents = [...cluster.agents].sort((a, b) => a.generation - b.generation
+        || a.agent_id.localeCompare(b.agent_id));
+    document.getElementById('agents').innerHTML = agents.map(agent => `
+        <tr>
+            <td>${escapeHtml(agent.agent_id)}</td>
+            <td>${escapeHtml(agent.hostname)} <span class="muted">${escapeHtml(agent.ip_address)}</span></td>
+            <td><span class="dot ${statusClass(agent.status)}"></span> ${escapeHtml(agent.status)}</td>
+            <td>${agent.generation}</td>
+            <td>${escapeHtml(agent.version)}</td>
+            <td>${agent.cpu_usage.toFixed(1)}%</td>
+            <td>${agent.memory_usage.toFixed(1)}%</td>
+            <td>${new Date(agent.last_heartbeat).toLocaleTimeString()}</td>
+        </tr>`).join('');
+
+    const select = document.getElementById('agentSelect');
+    const ids = agents.map(agent => agent.agent_id);
+    if (!ids.includes(selectedAgent)) selectedAgent = ids[0] ?? null;
+    if ([...select.options].map(o => o.value).join() !== ids.join()) {
+        select.innerHTML = ids.map(id => `<option value="${escapeHtml(id)}">${escapeHtml(id)}</option>`).join('');
+    }
+    select.value = selectedAgent ?? '';
+
+    if (history) {
+        setText('availability', `${history.summary.avg_availability.toFixed(1)}%`);
+        drawChart(document.getElementById('clusterChart'), [
+            { values: history.history.items.map(m => m.avg_cpu_usage), color: '#667eea' },
+            { values: history.history.items.map(m => m.avg_memory_usage), color: '#ed8936' },
+        ]);
+    }
+}
+
+async function renderAgentSeries() {
+    if (!selectedAgent) return;
+    const series = await getJson(`/api/agents/${encodeURIComponent(selectedAgent)}/metrics`);
+    drawChart(document.getElementById('agentChart'), series.ok ? [
+        { values: series.body.cpu_usage, color: '#667eea' },
+        { values: series.body.memory_usage, color: '#ed8936' },
+    ] : []);
+}
+
+function renderTrading(trading) {
+    if (!trading) return;
+    setText('tradingActive', trading.active ? 'yes' : 'no');
+    setText('totalTrades', trading.total_trades);
+    const settled = trading.successful_trades + trading.failed_trades;
+    setText('successRate', settled ? `${(100 * trading.successful_trades / settled).toFixed(1)}%` : '–');
+    setText('pnl', trading.pnl.toFixed(2));
+    document.getElementById('prices').innerHTML = Object.entries(trading.last_price)
+        .sort(([a], [b]) => a.localeCompare(b))
+        .map(([symbol, price]) => `<tr><td>${escapeHtml(symbol)}</td><td>${price}</td></tr>`)
+        .join('');
+}
+
+function describeDecision(decision) {
+    const kind = typeof decision === 'string' ? decision : Object.keys(decision)[0];
+    const detail = typeof decision === 'string' ? {} : decision[kind];
+    const reason = detail.reason ? ` – ${detail.reason}` : '';
+    return `${kind}${reason}`;
+}
+
+function renderDecisions(decisions) {
+    const list = document.getElementById('decisions');
+    if (!decisions) {
+        list.innerHTML = '<li class="muted">Decision journal is not configured</li>';
+        return;
+    }
+    const outcomeClass = { Success: 'ok', Failure: 'critical', Neutral: '' };
+    list.innerHTML = decisions.items.map(record => {
+        const outcome = record.outcome ? record.outcome.outcome : 'Pending';
+        return `<li class="${outcomeClass[outcome] ?? ''}">
+            <span class="muted">${new Date(record.timestamp).toLocaleString()}</span>
+            ${escapeHtml(describeDecision(record.decision))}
+            <span class="muted">${escapeHtml(outcome)}</span>
+        </li>`;
+    }).join('') || '<li class="muted">No decisions yet</li>';
+}
+
+// Alerts are derived from the health and fleet endpoints rather than stored anywhere
+function renderAlerts({ ready, cluster, validation, replication }) {
+    const alerts = [];
+    if (ready && ready.body) {
+        for (const component of ready.body.components.filter(c => !c.ready)) {
+            alerts.push(['critical', `Component ${component.name} is not ready${component.detail ? ': ' + component.detail : ''}`]);
+        }
+    }
+    if (cluster) {
+        for (const agent of cluster.agents.filter(a => a.status !== 'Running')) {
+            alerts.push([statusClass(agent.status) || 'warning', `Agent ${agent.agent_id} is ${agent.status}`]);
+        }
+    }
+    if (validation) {
+        for (const replica of validation.replicas.filter(r => !r.passed)) {
+            const failed = replica.checks.filter(c => !c.passed).map(c => c.name).join(', ');
+            alerts.push(['warning', `Replica ${replica.agent_id} failed validation: ${failed}`]);
+        }
+    }
+    if (replication && replication.status.recent_failures > 0) {
+        alerts.push(['warning', `${replication.status.recent_failures} replication failures in the last hour`]);
+    }
+
+    document.getElementById('alerts').innerHTML = alerts
+        .map(([level, message]) => `<li class="${level}">${escapeHtml(message)}</li>`)
+        .join('') || '<li class="ok">No active alerts</li>';
+}
+
+async function refresh() {
+    const [status, cluster, history, trading, decisions, ready, validation, replication] =
+        await Promise.all([
+            getJson('/'),
+            getJson('/api/cluster/status'),
+            getJson('/api/metrics/history?hours=1&limit=1000'),
+            getJson('/api/trading'),
+            getJson(`/api/decisions?order=desc&limit=${DECISION_LIMIT}`),
+            getJson('/ready'),
+            getJson('/api/fleet/validation'),
+            getJson('/api/replication?limit=10'),
+        ]);
+
+    document.getElementById('apiDot').className = 'dot ' + (status.ok ? 'ok' : 'critical');
+    setText('lastUpdate', status.ok ? `updated ${new Date().toLocaleTimeString()}` : 'API unreachable');
+    if (status.ok) setText('version', `v${status.body.version}`);
+
+    const body = response => (response.ok ? response.body : null);
+    renderCluster(body(cluster), body(history));
+    renderTrading(body(trading));
+    renderDecisions(body(decisions));
+    renderAlerts({
+        ready,
+        cluster: body(cluster),
+        validation: body(validation),
+        replication: body(replication),
+    });
+    await renderAgentSeries();
+}
+
+document.getElementById('agentSelect').addEventListener('change', event => {
+    selectedAgent = event.target.value;
+    renderAgentSeries();
+});
+
+refresh();
+setInterval(refresh, REFRESH_MS);
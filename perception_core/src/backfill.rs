@@ -0,0 +1,204 @@
+//! Candles missed while the agent was down, fetched from the exchange's REST API.
+//!
+//! Only holes are filled: candles already in the [`MarketStore`] are kept, and
+//! nothing at or after the candle the agent came back up in is touched, since the
+//! live recorder is building it from trades.
+
+use crate::market_store::MarketStore;
+use common::{AureliaError, AureliaResult, Candle, EndpointClass, RateLimiter};
+
+const SPOT_REST_API: &str = "https://api.binance.com";
+
+/// Most candles Binance returns per request
+const KLINES_PER_REQUEST: u64 = 1000;
+
+/// Request weight of `/api/v3/klines` at up to 1000 candles
+const KLINES_WEIGHT: f64 = 2.0;
+
+/// Binance kline intervals by their length in seconds
+const INTERVALS: [(u64, &str); 13] = [
+    (1, "1s"),
+    (60, "1m"),
+    (180, "3m"),
+    (300, "5m"),
+    (900, "15m"),
+    (1800, "30m"),
+    (3600, "1h"),
+    (7200, "2h"),
+    (14400, "4h"),
+    (21600, "6h"),
+    (28800, "8h"),
+    (43200, "12h"),
+    (86400, "1d"),
+];
+
+/// A kline row, `[open_time, open, high, low, close, volume, close_time,
+/// quote_volume, trades, ...]` with the prices and volumes as strings
+fn parse_kline(symbol: &str, row: &[serde_json::Value]) -> Option<Candle> {
+    let number = |i: usize| row.get(i)?.as_str()?.parse().ok();
+    Some(Candle {
+        symbol: symbol.to_string(),
+        open_time: row.first()?.as_u64()?,
+        open: number(1)?,
+        high: number(2)?,
+        low: number(3)?,
+        close: number(4)?,
+        volume: number(5)?,
+        trades: row.get(8)?.as_u64()?,
+    })
+}
+
+/// Open times, in milliseconds, of the candles missing before `until_ms`: from
+/// the one after `last_ms`, or `max_lookback_ms` back without one, up to but not
+/// including the candle `until_ms` falls in.
+pub fn gap(
+    last_ms: Option<u64>,
+    until_ms: u64,
+    interval_ms: u64,
+    max_lookback_ms: u64,
+) -> Option<(u64, u64)> {
+    let end = until_ms - until_ms % interval_ms;
+    let earliest = end.saturating_sub(max_lookback_ms);
+    let start = last_ms.map_or(earliest, |last| (last + interval_ms).max(earliest));
+    (start < end).then_some((start, end))
+}
+
+pub struct CandleBackfill {
+    client: reqwest::Client,
+    limiter: RateLimiter,
+    interval_seconds: u64,
+    interval: &'static str,
+}
+
+impl CandleBackfill {
+    /// Fails unless Binance has klines of `interval_seconds`
+    pub fn new(interval_seconds: u64, limiter: RateLimiter) -> AureliaResult<Self> {
+        let interval = INTERVALS
+            .iter()
+            .find(|(seconds, _)| *seconds == interval_seconds)
+            .map(|(_, name)| *name)
+            .ok_or_else(|| {
+                AureliaError::Config(format!(
+                    "no exchange candles of {}s to backfill from",
+                    interval_seconds
+                ))
+            })?;
+        Ok(Self {
+            client: reqwest::Client::new(),
+            limiter,
+            interval_seconds,
+            interval,
+        })
+    }
+
+    /// Fill the gap in `symbol`'s candles before `until_ms`, looking back at most
+    /// `max_lookback_ms`. Returns the number of candles added.
+    pub async fn run(
+        &self,
+        store: &MarketStore,
+        symbol: &str,
+        until_ms: u64,
+        max_lookback_ms: u64,
+    ) -> AureliaResult<usize> {
+        let interval_ms = self.interval_seconds * 1000;
+        let last = store
+            .latest_candle_before(symbol, until_ms - until_ms % interval_ms)?
+            .map(|candle| candle.open_time);
+        let Some((mut start, end)) = gap(last, until_ms, interval_ms, max_lookback_ms) else {
+            return Ok(0);
+        };
+        let mut added = 0;
+        while start < end {
+            let candles = self.klines(symbol, start, end - 1).await?;
+            let Some(last) = candles.last().map(|candle| candle.open_time) else {
+                break;
+            };
+            added += store.insert_candles(&candles)?;
+            start = last + interval_ms;
+            if (candles.len() as u64) < KLINES_PER_REQUEST {
+                break;
+            }
+        }
+        Ok(added)
+    }
+
+    /// Candles opening in `[from_ms, to_ms]`
+    async fn klines(&self, symbol: &str, from_ms: u64, to_ms: u64) -> AureliaResult<Vec<Candle>> {
+        self.limiter
+            .acquire(EndpointClass::ExchangeMarketData, KLINES_WEIGHT)
+            .await;
+        let rows = async {
+            self.client
+                .get(format!("{}/api/v3/klines", SPOT_REST_API))
+                .query(&[
+                    ("symbol", symbol.to_string()),
+                    ("interval", self.interval.to_string()),
+                    ("startTime", from_ms.to_string()),
+                    ("endTime", to_ms.to_string()),
+                    ("limit", KLINES_PER_REQUEST.to_string()),
+                ])
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<Vec<Vec<serde_json::Value>>>()
+                .await
+        }
+        .await
+        .map_err(|e| AureliaError::Exchange(format!("klines of {}: {}", symbol, e)))?;
+        rows.iter()
+            .map(|row| {
+                parse_kline(symbol, row).ok_or_else(|| {
+                    AureliaError::Exchange(format!("malformed kline of {}: {:?}", symbol, row))
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gap_and_klines() {
+        const MINUTE: u64 = 60_000;
+        // Down from 10:05 to 10:30:20, the 10:30 candle is being recorded live
+        let until = 630 * MINUTE + 20_000;
+        assert_eq!(
+            gap(Some(605 * MINUTE), until, MINUTE, 24 * 60 * MINUTE),
+            Some((606 * MINUTE, 630 * MINUTE))
+        );
+        // Never recorded anything: only the lookback window
+        assert_eq!(
+            gap(None, until, MINUTE, 10 * MINUTE),
+            Some((620 * MINUTE, 630 * MINUTE))
+        );
+        assert_eq!(gap(Some(629 * MINUTE), until, MINUTE, 10 * MINUTE), None);
+
+        let store = MarketStore::in_memory(60).unwrap();
+        let row: Vec<serde_json::Value> = serde_json::from_str(
+            r#"[60000,"100.0","101.5","99.0","100.5","12.5",119999,"1250.0",42,"6.0","600.0","0"]"#,
+        )
+        .unwrap();
+        let candles = vec![parse_kline("BTCUSDT", &row).unwrap()];
+        assert!(parse_kline("BTCUSDT", &row[..5]).is_none());
+        assert_eq!(candles[0].high, 101.5);
+        assert_eq!(candles[0].trades, 42);
+        assert_eq!(store.insert_candles(&candles).unwrap(), 1);
+        // Existing candles are never overwritten
+        assert_eq!(store.insert_candles(&candles).unwrap(), 0);
+        assert_eq!(
+            store
+                .latest_candle_before("BTCUSDT", 120_000)
+                .unwrap()
+                .unwrap()
+                .open_time,
+            60_000
+        );
+        assert!(store
+            .latest_candle_before("BTCUSDT", 60_000)
+            .unwrap()
+            .is_none());
+        assert!(CandleBackfill::new(42, RateLimiter::default()).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+//! Candles built from the raw trade stream.
+//!
+//! A candle is published as `AppEvent::Candle` once the first trade of a later
+//! interval arrives, so an interval without trades produces no candle.
+
+use common::{Candle, MarketData};
+use std::collections::HashMap;
+
+/// Length of the published candles
+pub const CANDLE_INTERVAL_SECONDS: u64 = 60;
+
+pub struct CandleBuilder {
+    interval_ms: u64,
+    open: HashMap<String, Candle>,
+}
+
+impl CandleBuilder {
+    pub fn new(interval_seconds: u64) -> Self {
+        Self {
+            interval_ms: interval_seconds.max(1) * 1000,
+            open: HashMap::new(),
+        }
+    }
+
+    /// Add a trade, returning the symbol's previous candle if the trade closed it
+    pub fn update(&mut self, tick: &MarketData) -> Option<Candle> {
+        let open_time = tick.timestamp - tick.timestamp % self.interval_ms;
+        if let Some(candle) = self.open.get_mut(&tick.symbol) {
+            if candle.open_time == open_time {
+                candle.high = candle.high.max(tick.price);
+                candle.low = candle.low.min(tick.price);
+                candle.close = tick.price;
+                candle.volume += tick.quantity;
+                candle.trades += 1;
+                return None;
+            }
+            // Late trades of an interval that already closed are dropped
+            if candle.open_time > open_time {
+                return None;
+            }
+        }
+        self.open.insert(
+            tick.symbol.clone(),
+            Candle {
+                symbol: tick.symbol.clone(),
+                open_time,
+                open: tick.price,
+                high: tick.price,
+                low: tick.price,
+                close: tick.price,
+                volume: tick.quantity,
+                trades: 1,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick(timestamp: u64, price: f64) -> MarketData {
+        MarketData {
+            symbol: "BTCUSDT".to_string(),
+            price,
+            quantity: 0.5,
+            timestamp,
+            meta: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_candle_closes_on_next_interval() {
+        let mut builder = CandleBuilder::new(60);
+        assert!(builder.update(&tick(1_000, 100.0)).is_none());
+        assert!(builder.update(&tick(20_000, 104.0)).is_none());
+        assert!(builder.update(&tick(59_999, 98.0)).is_none());
+
+        let candle = builder.update(&tick(61_000, 101.0)).unwrap();
+        assert_eq!(candle.open_time, 0);
+        assert_eq!(
+            (candle.open, candle.high, candle.low, candle.close),
+            (100.0, 104.0, 98.0, 98.0)
+        );
+        assert_eq!((candle.volume, candle.trades), (1.5, 3));
+
+        // A straggler from the closed minute does not reopen it
+        assert!(builder.update(&tick(59_000, 90.0)).is_none());
+    }
+}
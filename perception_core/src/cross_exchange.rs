@@ -0,0 +1,497 @@
+//! Prices of the same symbol on several exchanges and the spread between them.
+//!
+//! Each configured venue streams its best bid and ask over its public
+//! WebSocket API. [`SpreadAggregator`] keeps the latest quote per venue,
+//! estimates every venue's feed latency from the gap between the exchange's
+//! timestamp and the local receive time, and compares only quotes that are
+//! fresh and were taken close enough together once that latency is allowed for.
+//! The best buy-low/sell-high pair per symbol is published as
+//! `AppEvent::CrossExchangeSpread`, at most once per `emit_interval_ms` unless
+//! an opportunity opens up in between.
+
+use chrono::Utc;
+use common::{AppEvent, AureliaResult, CrossExchangeSpread, EventSender};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+
+pub const CROSS_EXCHANGE_CONFIG_PATH: &str = "config/cross_exchange.json";
+
+const BINANCE_WS_API: &str = "wss://stream.binance.com:9443/stream";
+const OKX_WS_API: &str = "wss://ws.okx.com:8443/ws/v5/public";
+
+/// Longest wait between reconnection attempts.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// Weight of the newest sample in a venue's latency estimate
+const LATENCY_SMOOTHING: f64 = 0.1;
+
+/// Quote currencies a symbol such as `BTCUSDT` may end in
+const QUOTE_ASSETS: [&str; 5] = ["USDT", "USDC", "FDUSD", "BTC", "ETH"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Venue {
+    Binance,
+    Okx,
+}
+
+impl fmt::Display for Venue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Venue::Binance => write!(f, "binance"),
+            Venue::Okx => write!(f, "okx"),
+        }
+    }
+}
+
+impl Venue {
+    fn url(self, symbols: &[String]) -> String {
+        match self {
+            Venue::Binance => {
+                let streams: Vec<String> = symbols
+                    .iter()
+                    .map(|s| format!("{}@ticker", s.to_lowercase()))
+                    .collect();
+                format!("{}?streams={}", BINANCE_WS_API, streams.join("/"))
+            }
+            Venue::Okx => OKX_WS_API.to_string(),
+        }
+    }
+
+    /// Frame to send once connected, for venues that subscribe in-band
+    fn subscribe(self, symbols: &[String]) -> Option<String> {
+        match self {
+            Venue::Binance => None,
+            Venue::Okx => {
+                let args: Vec<_> = symbols
+                    .iter()
+                    .filter_map(|s| okx_instrument(s))
+                    .map(|inst_id| serde_json::json!({"channel": "tickers", "instId": inst_id}))
+                    .collect();
+                Some(serde_json::json!({"op": "subscribe", "args": args}).to_string())
+            }
+        }
+    }
+
+    fn parse(self, text: &str, received_at: u64) -> Option<VenueQuote> {
+        let (symbol, bid, ask, exchange_time) = match self {
+            Venue::Binance => {
+                let ticker = serde_json::from_str::<BinanceCombined>(text).ok()?.data;
+                (ticker.symbol, ticker.bid, ticker.ask, ticker.event_time)
+            }
+            Venue::Okx => {
+                let ticker = serde_json::from_str::<OkxMessage>(text)
+                    .ok()?
+                    .data
+                    .into_iter()
+                    .next()?;
+                let time = ticker.ts.parse().ok()?;
+                (
+                    ticker.inst_id.replace('-', ""),
+                    ticker.bid,
+                    ticker.ask,
+                    time,
+                )
+            }
+        };
+        let (bid, ask) = (bid.parse().ok()?, ask.parse().ok()?);
+        (bid > 0.0 && ask > 0.0).then_some(VenueQuote {
+            venue: self,
+            symbol,
+            bid,
+            ask,
+            exchange_time,
+            received_at,
+        })
+    }
+}
+
+/// `BTCUSDT` as OKX names it, `BTC-USDT`
+fn okx_instrument(symbol: &str) -> Option<String> {
+    let quote = QUOTE_ASSETS
+        .iter()
+        .find(|quote| symbol.len() > quote.len() && symbol.ends_with(*quote))?;
+    let base = &symbol[..symbol.len() - quote.len()];
+    Some(format!("{}-{}", base, quote))
+}
+
+/// `<symbol>@ticker` on the combined stream
+#[derive(Debug, Deserialize)]
+struct BinanceCombined {
+    data: BinanceTicker,
+}
+
+#[derive(Debug, Deserialize)]
+struct BinanceTicker {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "b")]
+    bid: String,
+    #[serde(rename = "a")]
+    ask: String,
+    #[serde(rename = "E")]
+    event_time: u64,
+}
+
+/// A push on OKX's `tickers` channel
+#[derive(Debug, Deserialize)]
+struct OkxMessage {
+    data: Vec<OkxTicker>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OkxTicker {
+    inst_id: String,
+    #[serde(rename = "bidPx")]
+    bid: String,
+    #[serde(rename = "askPx")]
+    ask: String,
+    ts: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CrossExchangeConfig {
+    pub enabled: bool,
+    /// Symbols in Binance notation, e.g. `BTCUSDT`
+    pub symbols: Vec<String>,
+    pub venues: Vec<Venue>,
+    /// Taker fee per venue in basis points; venues not listed pay `default_fee_bps`
+    pub fee_bps: HashMap<Venue, f64>,
+    pub default_fee_bps: f64,
+    /// Net spread at which a spread counts as an opportunity
+    pub min_net_spread_bps: f64,
+    /// Quotes older than this, latency included, are not compared
+    pub max_quote_age_ms: u64,
+    /// Two quotes taken further apart than this are not compared
+    pub max_skew_ms: u64,
+    /// Least time between two events for the same symbol
+    pub emit_interval_ms: u64,
+}
+
+impl Default for CrossExchangeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            symbols: vec!["BTCUSDT".to_string()],
+            venues: vec![Venue::Binance, Venue::Okx],
+            fee_bps: HashMap::new(),
+            default_fee_bps: 10.0,
+            min_net_spread_bps: 0.0,
+            max_quote_age_ms: 3000,
+            max_skew_ms: 1000,
+            emit_interval_ms: 1000,
+        }
+    }
+}
+
+impl CrossExchangeConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn fee(&self, venue: Venue) -> f64 {
+        self.fee_bps
+            .get(&venue)
+            .copied()
+            .unwrap_or(self.default_fee_bps)
+    }
+}
+
+/// Best bid and ask of a symbol on one venue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VenueQuote {
+    pub venue: Venue,
+    pub symbol: String,
+    pub bid: f64,
+    pub ask: f64,
+    /// When the exchange produced the quote, by its clock, Unix milliseconds
+    pub exchange_time: u64,
+    /// When the quote arrived here, Unix milliseconds
+    pub received_at: u64,
+}
+
+/// Latest quotes per symbol and venue, and the spreads between them.
+pub struct SpreadAggregator {
+    config: CrossExchangeConfig,
+    quotes: HashMap<String, HashMap<Venue, VenueQuote>>,
+    /// Smoothed receive time minus exchange time, which also absorbs the
+    /// difference between the exchange's clock and ours
+    latency_ms: HashMap<Venue, f64>,
+    last_emitted: HashMap<String, (u64, bool)>,
+}
+
+impl SpreadAggregator {
+    pub fn new(config: CrossExchangeConfig) -> Self {
+        Self {
+            config,
+            quotes: HashMap::new(),
+            latency_ms: HashMap::new(),
+            last_emitted: HashMap::new(),
+        }
+    }
+
+    pub fn latency_ms(&self, venue: Venue) -> f64 {
+        self.latency_ms.get(&venue).copied().unwrap_or(0.0)
+    }
+
+    /// Record `quote` and return the spread for its symbol if one is due.
+    pub fn update(&mut self, quote: VenueQuote) -> Option<CrossExchangeSpread> {
+        let sample = quote.received_at as f64 - quote.exchange_time as f64;
+        self.latency_ms
+            .entry(quote.venue)
+            .and_modify(|latency| *latency += LATENCY_SMOOTHING * (sample - *latency))
+            .or_insert(sample);
+        let now = quote.received_at;
+        let symbol = quote.symbol.clone();
+        self.quotes
+            .entry(symbol.clone())
+            .or_default()
+            .insert(quote.venue, quote);
+
+        let spread = self.best_spread(&symbol, now)?;
+        let due = match self.last_emitted.get(&symbol) {
+            Some(&(at, was_opportunity)) => {
+                now.saturating_sub(at) >= self.config.emit_interval_ms
+                    || (spread.opportunity && !was_opportunity)
+            }
+            None => true,
+        };
+        if !due {
+            return None;
+        }
+        self.last_emitted.insert(symbol, (now, spread.opportunity));
+        Some(spread)
+    }
+
+    /// When the venue produced `quote`, on our clock
+    fn taken_at(&self, quote: &VenueQuote) -> f64 {
+        quote.received_at as f64 - self.latency_ms(quote.venue)
+    }
+
+    fn best_spread(&self, symbol: &str, now: u64) -> Option<CrossExchangeSpread> {
+        let fresh: Vec<&VenueQuote> = self
+            .quotes
+            .get(symbol)?
+            .values()
+            .filter(|q| now as f64 - self.taken_at(q) <= self.config.max_quote_age_ms as f64)
+            .collect();
+        let mut best: Option<CrossExchangeSpread> = None;
+        for buy in &fresh {
+            for sell in &fresh {
+                if buy.venue == sell.venue {
+                    continue;
+                }
+                let skew_ms = (self.taken_at(buy) - self.taken_at(sell)).abs();
+                if skew_ms > self.config.max_skew_ms as f64 {
+                    continue;
+                }
+                let spread_bps = (sell.bid - buy.ask) / buy.ask * 10_000.0;
+                let net_spread_bps =
+                    spread_bps - self.config.fee(buy.venue) - self.config.fee(sell.venue);
+                if best
+                    .as_ref()
+                    .is_some_and(|b| b.net_spread_bps >= net_spread_bps)
+                {
+                    continue;
+                }
+                best = Some(CrossExchangeSpread {
+                    symbol: symbol.to_string(),
+                    buy_venue: buy.venue.to_string(),
+                    sell_venue: sell.venue.to_string(),
+                    buy_price: buy.ask,
+                    sell_price: sell.bid,
+                    spread_bps,
+                    net_spread_bps,
+                    opportunity: net_spread_bps >= self.config.min_net_spread_bps,
+                    skew_ms,
+                    buy_latency_ms: self.latency_ms(buy.venue),
+                    sell_latency_ms: self.latency_ms(sell.venue),
+                    timestamp: now,
+                });
+            }
+        }
+        best
+    }
+}
+
+/// Streams the configured venues and publishes the spreads between them.
+pub struct CrossExchangeCollector {
+    config: CrossExchangeConfig,
+}
+
+impl CrossExchangeCollector {
+    pub fn new(config: CrossExchangeConfig) -> Self {
+        Self { config }
+    }
+
+    pub async fn run(self, tx: EventSender) {
+        tracing::info!(
+            "[Perception Core] Comparing {:?} across {:?}",
+            self.config.symbols,
+            self.config.venues
+        );
+        let (quote_tx, mut quotes) = mpsc::channel(1024);
+        for venue in &self.config.venues {
+            tokio::spawn(stream(
+                *venue,
+                self.config.symbols.clone(),
+                quote_tx.clone(),
+            ));
+        }
+        drop(quote_tx);
+
+        let mut aggregator = SpreadAggregator::new(self.config);
+        while let Some(quote) = quotes.recv().await {
+            if let Some(spread) = aggregator.update(quote) {
+                if spread.opportunity {
+                    tracing::info!(
+                        "[Perception Core] {} buy on {} at {}, sell on {} at {}: {:.1} bps net",
+                        spread.symbol,
+                        spread.buy_venue,
+                        spread.buy_price,
+                        spread.sell_venue,
+                        spread.sell_price,
+                        spread.net_spread_bps
+                    );
+                }
+                if tx
+                    .send(AppEvent::CrossExchangeSpread(Box::new(spread)))
+                    .is_err()
+                {
+                    tracing::debug!("No subscribers for cross-exchange spreads");
+                }
+            }
+        }
+    }
+}
+
+/// Keep one venue's stream connected, reconnecting with backoff.
+async fn stream(venue: Venue, symbols: Vec<String>, quotes: mpsc::Sender<VenueQuote>) {
+    let url = venue.url(&symbols);
+    let mut delay = Duration::from_secs(1);
+    loop {
+        match connect_async(url.as_str()).await {
+            Ok((ws, _)) => {
+                tracing::info!("[Perception Core] {} ticker stream connected", venue);
+                delay = Duration::from_secs(1);
+                let (mut write, mut read) = ws.split();
+                let subscribed = match venue.subscribe(&symbols) {
+                    Some(frame) => write.send(Message::Text(frame)).await,
+                    None => Ok(()),
+                };
+                if let Err(e) = subscribed {
+                    tracing::warn!("[Perception Core] {} subscription failed: {}", venue, e);
+                }
+                while let Some(message) = read.next().await {
+                    let Ok(Message::Text(text)) = message else {
+                        continue;
+                    };
+                    let received_at = Utc::now().timestamp_millis() as u64;
+                    if let Some(quote) = venue.parse(&text, received_at) {
+                        if quotes.send(quote).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                tracing::warn!(
+                    "[Perception Core] {} ticker stream ended, reconnecting",
+                    venue
+                );
+            }
+            Err(e) => {
+                tracing::warn!("[Perception Core] {} stream connect failed: {}", venue, e);
+            }
+        }
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(venue: Venue, bid: f64, ask: f64, exchange_time: u64, received_at: u64) -> VenueQuote {
+        VenueQuote {
+            venue,
+            symbol: "BTCUSDT".to_string(),
+            bid,
+            ask,
+            exchange_time,
+            received_at,
+        }
+    }
+
+    #[test]
+    fn test_venue_payloads() {
+        let binance = r#"{"stream":"btcusdt@ticker","data":{"e":"24hrTicker","E":1700000002000,
+            "s":"BTCUSDT","c":"65000.0","b":"64999.5","B":"1.2","a":"65000.5","A":"0.8"}}"#;
+        let parsed = Venue::Binance.parse(binance, 1700000002050).unwrap();
+        assert_eq!((parsed.bid, parsed.ask), (64999.5, 65000.5));
+        assert_eq!(parsed.exchange_time, 1700000002000);
+
+        let okx = r#"{"arg":{"channel":"tickers","instId":"BTC-USDT"},"data":[{"instType":"SPOT",
+            "instId":"BTC-USDT","last":"65010","bidPx":"65009.9","bidSz":"0.3",
+            "askPx":"65010.1","askSz":"0.5","ts":"1700000002010"}]}"#;
+        let parsed = Venue::Okx.parse(okx, 1700000002090).unwrap();
+        assert_eq!(parsed.symbol, "BTCUSDT");
+        assert_eq!(parsed.ask, 65010.1);
+        // Subscription acknowledgements carry no quote
+        assert!(Venue::Okx
+            .parse(r#"{"event":"subscribe","arg":{}}"#, 0)
+            .is_none());
+
+        assert_eq!(okx_instrument("ETHBTC").as_deref(), Some("ETH-BTC"));
+        let frame = Venue::Okx.subscribe(&["BTCUSDT".to_string()]).unwrap();
+        assert!(frame.contains(r#""instId":"BTC-USDT""#));
+    }
+
+    #[test]
+    fn test_spreads_allow_for_latency_and_skip_stale_quotes() {
+        let config = CrossExchangeConfig {
+            fee_bps: HashMap::from([(Venue::Binance, 1.0), (Venue::Okx, 2.0)]),
+            min_net_spread_bps: 5.0,
+            ..CrossExchangeConfig::default()
+        };
+        let mut aggregator = SpreadAggregator::new(config);
+
+        // A single venue has nothing to compare with
+        assert!(aggregator
+            .update(quote(Venue::Binance, 99.9, 100.0, 1_000, 1_020))
+            .is_none());
+        // OKX's quote arrives 400ms after Binance's, but its feed lags 400ms
+        // more, so both were taken at the same moment
+        let spread = aggregator
+            .update(quote(Venue::Okx, 100.2, 100.3, 1_000, 1_420))
+            .unwrap();
+        assert_eq!(spread.buy_venue, "binance");
+        assert_eq!(spread.sell_venue, "okx");
+        assert!((spread.spread_bps - 20.0).abs() < 1e-6);
+        assert!((spread.net_spread_bps - 17.0).abs() < 1e-6);
+        assert!(spread.opportunity);
+        assert!(spread.skew_ms < 1e-6);
+        assert_eq!(spread.sell_latency_ms, 420.0);
+
+        // Within the emit interval nothing new is published
+        assert!(aggregator
+            .update(quote(Venue::Okx, 100.2, 100.3, 1_500, 1_900))
+            .is_none());
+        // Once Binance's quote is too old it is no longer compared
+        assert!(aggregator
+            .update(quote(Venue::Okx, 100.2, 100.3, 4_600, 5_000))
+            .is_none());
+    }
+}
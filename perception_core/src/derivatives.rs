@@ -0,0 +1,459 @@
+//! Derivatives and 24h ticker data from Binance: funding rates and open interest
+//! from USDⓈ-M futures, rolling statistics from spot.
+//!
+//! Funding and ticker statistics stream over WebSocket when enabled; REST polling
+//! covers them while a stream is down. Open interest has no stream and is always
+//! polled.
+
+use common::{
+    AppEvent, AureliaError, AureliaResult, EndpointClass, EventSender, FundingRate, OpenInterest,
+    RateLimiter, TickerStats,
+};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+
+pub const DERIVATIVES_CONFIG_PATH: &str = "config/derivatives.json";
+
+const FUTURES_REST_API: &str = "https://fapi.binance.com";
+const SPOT_REST_API: &str = "https://api.binance.com";
+const FUTURES_WS_API: &str = "wss://fstream.binance.com/stream";
+const SPOT_WS_API: &str = "wss://stream.binance.com:9443/stream";
+
+/// Longest wait between reconnection attempts.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DerivativesConfig {
+    pub enabled: bool,
+    pub symbols: Vec<String>,
+    pub poll_interval_seconds: u64,
+    pub use_websocket: bool,
+}
+
+impl Default for DerivativesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            symbols: vec!["BTCUSDT".to_string()],
+            poll_interval_seconds: 60,
+            use_websocket: true,
+        }
+    }
+}
+
+impl DerivativesConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+/// Binance sends decimals as strings. One that is not a finite number fails the
+/// whole record, which is then skipped rather than read as zero.
+fn number<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    match value.parse::<f64>() {
+        Ok(number) if number.is_finite() => Ok(number),
+        _ => Err(serde::de::Error::custom(format!(
+            "invalid number {:?}",
+            value
+        ))),
+    }
+}
+
+/// `GET /fapi/v1/premiumIndex`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PremiumIndex {
+    symbol: String,
+    #[serde(deserialize_with = "number")]
+    mark_price: f64,
+    #[serde(deserialize_with = "number")]
+    index_price: f64,
+    #[serde(deserialize_with = "number")]
+    last_funding_rate: f64,
+    next_funding_time: u64,
+    time: u64,
+}
+
+impl From<PremiumIndex> for FundingRate {
+    fn from(index: PremiumIndex) -> Self {
+        Self {
+            symbol: index.symbol,
+            funding_rate: index.last_funding_rate,
+            mark_price: index.mark_price,
+            index_price: index.index_price,
+            next_funding_time: index.next_funding_time,
+            timestamp: index.time,
+        }
+    }
+}
+
+/// `GET /fapi/v1/openInterest`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OpenInterestResponse {
+    symbol: String,
+    #[serde(deserialize_with = "number")]
+    open_interest: f64,
+    time: u64,
+}
+
+impl From<OpenInterestResponse> for OpenInterest {
+    fn from(response: OpenInterestResponse) -> Self {
+        Self {
+            symbol: response.symbol,
+            open_interest: response.open_interest,
+            timestamp: response.time,
+        }
+    }
+}
+
+/// `GET /api/v3/ticker/24hr`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Ticker24h {
+    symbol: String,
+    #[serde(deserialize_with = "number")]
+    last_price: f64,
+    #[serde(deserialize_with = "number")]
+    price_change_percent: f64,
+    #[serde(deserialize_with = "number")]
+    high_price: f64,
+    #[serde(deserialize_with = "number")]
+    low_price: f64,
+    #[serde(deserialize_with = "number")]
+    volume: f64,
+    #[serde(deserialize_with = "number")]
+    quote_volume: f64,
+    close_time: u64,
+}
+
+impl From<Ticker24h> for TickerStats {
+    fn from(ticker: Ticker24h) -> Self {
+        Self {
+            symbol: ticker.symbol,
+            last_price: ticker.last_price,
+            price_change_percent: ticker.price_change_percent,
+            high_price: ticker.high_price,
+            low_price: ticker.low_price,
+            volume: ticker.volume,
+            quote_volume: ticker.quote_volume,
+            timestamp: ticker.close_time,
+        }
+    }
+}
+
+/// Payload of a combined stream message: `{"stream": ..., "data": {...}}`.
+#[derive(Debug, Deserialize)]
+struct Combined<T> {
+    data: T,
+}
+
+/// `<symbol>@markPrice@1s`
+#[derive(Debug, Deserialize)]
+struct MarkPriceUpdate {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "p", deserialize_with = "number")]
+    mark_price: f64,
+    #[serde(rename = "i", deserialize_with = "number")]
+    index_price: f64,
+    #[serde(rename = "r", deserialize_with = "number")]
+    funding_rate: f64,
+    #[serde(rename = "T")]
+    next_funding_time: u64,
+    #[serde(rename = "E")]
+    event_time: u64,
+}
+
+impl From<MarkPriceUpdate> for FundingRate {
+    fn from(update: MarkPriceUpdate) -> Self {
+        Self {
+            symbol: update.symbol,
+            funding_rate: update.funding_rate,
+            mark_price: update.mark_price,
+            index_price: update.index_price,
+            next_funding_time: update.next_funding_time,
+            timestamp: update.event_time,
+        }
+    }
+}
+
+/// `<symbol>@ticker`
+#[derive(Debug, Deserialize)]
+struct TickerUpdate {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "c", deserialize_with = "number")]
+    last_price: f64,
+    #[serde(rename = "P", deserialize_with = "number")]
+    price_change_percent: f64,
+    #[serde(rename = "h", deserialize_with = "number")]
+    high_price: f64,
+    #[serde(rename = "l", deserialize_with = "number")]
+    low_price: f64,
+    #[serde(rename = "v", deserialize_with = "number")]
+    volume: f64,
+    #[serde(rename = "q", deserialize_with = "number")]
+    quote_volume: f64,
+    #[serde(rename = "E")]
+    event_time: u64,
+}
+
+impl From<TickerUpdate> for TickerStats {
+    fn from(update: TickerUpdate) -> Self {
+        Self {
+            symbol: update.symbol,
+            last_price: update.last_price,
+            price_change_percent: update.price_change_percent,
+            high_price: update.high_price,
+            low_price: update.low_price,
+            volume: update.volume,
+            quote_volume: update.quote_volume,
+            timestamp: update.event_time,
+        }
+    }
+}
+
+/// Which streams a WebSocket connection carries.
+#[derive(Debug, Clone, Copy)]
+enum StreamKind {
+    Funding,
+    Ticker,
+}
+
+impl StreamKind {
+    fn url(self, symbols: &[String]) -> String {
+        let (base, suffix) = match self {
+            StreamKind::Funding => (FUTURES_WS_API, "@markPrice@1s"),
+            StreamKind::Ticker => (SPOT_WS_API, "@ticker"),
+        };
+        let streams: Vec<String> = symbols
+            .iter()
+            .map(|s| format!("{}{}", s.to_lowercase(), suffix))
+            .collect();
+        format!("{}?streams={}", base, streams.join("/"))
+    }
+
+    fn parse(self, text: &str) -> Option<AppEvent> {
+        let event = match self {
+            StreamKind::Funding => serde_json::from_str::<Combined<MarkPriceUpdate>>(text)
+                .map(|m| AppEvent::FundingRate(m.data.into())),
+            StreamKind::Ticker => serde_json::from_str::<Combined<TickerUpdate>>(text)
+                .map(|m| AppEvent::TickerStats(m.data.into())),
+        };
+        event
+            .map_err(|e| tracing::warn!("[Perception Core] Skipping {:?} update: {}", self, e))
+            .ok()
+    }
+}
+
+/// Collects funding, open interest and ticker statistics for the configured symbols.
+pub struct DerivativesCollector {
+    config: DerivativesConfig,
+    client: reqwest::Client,
+    limiter: RateLimiter,
+    funding_streaming: Arc<AtomicBool>,
+    ticker_streaming: Arc<AtomicBool>,
+}
+
+impl DerivativesCollector {
+    pub fn new(config: DerivativesConfig) -> AureliaResult<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .map_err(|e| AureliaError::Config(format!("derivatives HTTP client: {}", e)))?;
+        Ok(Self {
+            config,
+            client,
+            limiter: RateLimiter::default(),
+            funding_streaming: Arc::new(AtomicBool::new(false)),
+            ticker_streaming: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Draw REST requests from a budget shared with the rest of the agent
+    pub fn with_rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    pub async fn run(self, tx: EventSender) {
+        tracing::info!(
+            "[Perception Core] Collecting derivatives data for {:?}",
+            self.config.symbols
+        );
+        if self.config.use_websocket {
+            for (kind, streaming) in [
+                (StreamKind::Funding, self.funding_streaming.clone()),
+                (StreamKind::Ticker, self.ticker_streaming.clone()),
+            ] {
+                let url = kind.url(&self.config.symbols);
+                tokio::spawn(stream(kind, url, streaming, tx.clone()));
+            }
+        }
+
+        let mut interval = tokio::time::interval(Duration::from_secs(
+            self.config.poll_interval_seconds.max(1),
+        ));
+        loop {
+            interval.tick().await;
+            for symbol in &self.config.symbols {
+                for event in self.poll(symbol).await {
+                    if let Err(e) = tx.send(event) {
+                        tracing::error!("[Perception Core] Failed to send derivatives data: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn poll(&self, symbol: &str) -> Vec<AppEvent> {
+        let mut events = Vec::new();
+        let open_interest = self
+            .get::<OpenInterestResponse>(FUTURES_REST_API, "/fapi/v1/openInterest", symbol, 1.0)
+            .await;
+        events.extend(open_interest.map(|oi| AppEvent::OpenInterest(oi.into())));
+        if !self.funding_streaming.load(Ordering::Relaxed) {
+            let funding = self
+                .get::<PremiumIndex>(FUTURES_REST_API, "/fapi/v1/premiumIndex", symbol, 1.0)
+                .await;
+            events.extend(funding.map(|f| AppEvent::FundingRate(f.into())));
+        }
+        if !self.ticker_streaming.load(Ordering::Relaxed) {
+            let ticker = self
+                .get::<Ticker24h>(SPOT_REST_API, "/api/v3/ticker/24hr", symbol, 2.0)
+                .await;
+            events.extend(ticker.map(|t| AppEvent::TickerStats(t.into())));
+        }
+        events
+    }
+
+    /// `weight` is the request weight Binance charges for the endpoint.
+    async fn get<T: serde::de::DeserializeOwned>(
+        &self,
+        base: &str,
+        path: &str,
+        symbol: &str,
+        weight: f64,
+    ) -> Option<T> {
+        self.limiter
+            .acquire(EndpointClass::ExchangeMarketData, weight)
+            .await;
+        let result = async {
+            self.client
+                .get(format!("{}{}", base, path))
+                .query(&[("symbol", symbol)])
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<T>()
+                .await
+        }
+        .await;
+        result
+            .map_err(|e| tracing::warn!("[Perception Core] {} {} failed: {}", path, symbol, e))
+            .ok()
+    }
+}
+
+/// Keep a stream connected, reconnecting with backoff. `streaming` tells the REST
+/// poller whether it needs to fill in.
+async fn stream(kind: StreamKind, url: String, streaming: Arc<AtomicBool>, tx: EventSender) {
+    let mut delay = Duration::from_secs(1);
+    loop {
+        match connect_async(url.as_str()).await {
+            Ok((ws, _)) => {
+                tracing::info!("[Perception Core] {:?} stream connected", kind);
+                streaming.store(true, Ordering::Relaxed);
+                delay = Duration::from_secs(1);
+                let (_write, mut read) = ws.split();
+                while let Some(message) = read.next().await {
+                    let Ok(Message::Text(text)) = message else {
+                        continue;
+                    };
+                    if let Some(event) = kind.parse(&text) {
+                        if tx.send(event).is_err() {
+                            tracing::debug!("No subscribers for {:?} stream", kind);
+                        }
+                    }
+                }
+                streaming.store(false, Ordering::Relaxed);
+                tracing::warn!("[Perception Core] {:?} stream ended, reconnecting", kind);
+            }
+            Err(e) => {
+                tracing::warn!("[Perception Core] {:?} stream connect failed: {}", kind, e);
+            }
+        }
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rest_and_stream_payloads() {
+        let index: PremiumIndex = serde_json::from_str(
+            r#"{"symbol":"BTCUSDT","markPrice":"65000.10","indexPrice":"64990.00",
+                "estimatedSettlePrice":"64995.0","lastFundingRate":"0.00010000",
+                "interestRate":"0.0001","nextFundingTime":1700006400000,"time":1700000000000}"#,
+        )
+        .unwrap();
+        let funding = FundingRate::from(index);
+        assert_eq!(funding.funding_rate, 0.0001);
+        assert_eq!(funding.mark_price, 65000.1);
+
+        let oi: OpenInterestResponse = serde_json::from_str(
+            r#"{"openInterest":"81234.567","symbol":"BTCUSDT","time":1700000000000}"#,
+        )
+        .unwrap();
+        assert_eq!(OpenInterest::from(oi).open_interest, 81234.567);
+
+        let stream = r#"{"stream":"btcusdt@markPrice@1s","data":{"e":"markPriceUpdate",
+            "E":1700000001000,"s":"BTCUSDT","p":"65001.0","i":"64991.0","P":"64996.0",
+            "r":"-0.00002000","T":1700006400000}}"#;
+        let Some(AppEvent::FundingRate(update)) = StreamKind::Funding.parse(stream) else {
+            panic!("mark price update not parsed");
+        };
+        assert_eq!(update.funding_rate, -0.00002);
+        assert_eq!(update.timestamp, 1700000001000);
+
+        let ticker = r#"{"stream":"btcusdt@ticker","data":{"e":"24hrTicker","E":1700000002000,
+            "s":"BTCUSDT","p":"500.0","P":"0.775","w":"64800.0","c":"65000.0","Q":"0.01",
+            "o":"64500.0","h":"65500.0","l":"64000.0","v":"12345.6","q":"800000000.0",
+            "O":1699913602000,"C":1700000002000,"F":1,"L":2,"n":2}}"#;
+        let Some(AppEvent::TickerStats(stats)) = StreamKind::Ticker.parse(ticker) else {
+            panic!("ticker update not parsed");
+        };
+        assert_eq!(stats.price_change_percent, 0.775);
+        assert_eq!(stats.high_price, 65500.0);
+
+        // A malformed decimal drops the record instead of reading as zero
+        assert!(StreamKind::Funding
+            .parse(&stream.replace(r#""p":"65001.0""#, r#""p":"n/a""#))
+            .is_none());
+        assert!(serde_json::from_str::<OpenInterestResponse>(
+            r#"{"openInterest":"","symbol":"BTCUSDT","time":1700000000000}"#,
+        )
+        .is_err());
+
+        assert_eq!(
+            StreamKind::Ticker.url(&["BTCUSDT".to_string(), "ETHUSDT".to_string()]),
+            "wss://stream.binance.com:9443/stream?streams=btcusdt@ticker/ethusdt@ticker"
+        );
+    }
+}
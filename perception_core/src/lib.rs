@@ -1,9 +1,35 @@
-use common::{AppEvent, EventSender, MarketData};
-use futures_util::{pin_mut, stream::StreamExt};
+use candles::CANDLE_INTERVAL_SECONDS;
+use common::health::component::PERCEPTION;
+use common::{
+    AppEvent, AureliaError, AureliaResult, EventMeta, EventReceiver, EventSender, HealthState,
+    MarketData,
+};
+use futures_util::{pin_mut, stream::StreamExt, SinkExt};
 use rustls::crypto::CryptoProvider;
 use serde::Deserialize;
+use std::time::{Duration, Instant};
 use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
 
+pub mod backfill;
+pub mod candles;
+pub mod cross_exchange;
+pub mod derivatives;
+pub mod market_store;
+pub mod news;
+pub mod regime;
+pub mod sampling;
+pub mod universe;
+
+pub use backfill::CandleBackfill;
+pub use candles::CandleBuilder;
+pub use cross_exchange::{CrossExchangeCollector, CrossExchangeConfig, SpreadAggregator};
+pub use derivatives::{DerivativesCollector, DerivativesConfig};
+pub use market_store::{MarketRecorder, MarketStore, MarketStoreConfig};
+pub use news::{NewsConfig, NewsPoller};
+pub use regime::RegimeDetector;
+pub use sampling::{MarketSampler, SamplingConfig};
+pub use universe::{SymbolUniverse, UniverseConfig};
+
 #[derive(Debug, Deserialize)]
 pub struct BinanceTrade {
     #[serde(rename = "s")]
@@ -16,36 +42,139 @@ pub struct BinanceTrade {
     pub timestamp: u64,
 }
 
-const BINANCE_WS_API: &str = "wss://stream.binance.com:9443/ws/btcusdt@trade";
+/// A trade as sent on the combined stream, or on a raw stream by a mock exchange
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TradeMessage {
+    Combined { data: BinanceTrade },
+    Raw(BinanceTrade),
+}
+
+impl TradeMessage {
+    fn into_trade(self) -> BinanceTrade {
+        match self {
+            TradeMessage::Combined { data } => data,
+            TradeMessage::Raw(trade) => trade,
+        }
+    }
+}
+
+const BINANCE_WS_API: &str = "wss://stream.binance.com:9443/stream";
+
+/// Overrides the trade stream, e.g. to point the agent at a mock exchange in soak tests
+pub const MARKET_WS_URL_ENV: &str = "AURELIA_MARKET_WS_URL";
+
+/// How often trades held back by the sampler are checked for release
+const SAMPLER_FLUSH_INTERVAL: Duration = Duration::from_millis(50);
+
+fn market_ws_url(universe: &SymbolUniverse) -> String {
+    std::env::var(MARKET_WS_URL_ENV).unwrap_or_else(|_| universe.stream_url(BINANCE_WS_API))
+}
+
+fn send_market_data(tx: &EventSender, market_data: MarketData) {
+    if let Err(e) = tx.send(AppEvent::MarketData(market_data)) {
+        eprintln!("[Perception Core] Failed to send market data: {}", e);
+    }
+}
 
-pub async fn run(tx: EventSender) {
+/// Publish every trade of the symbols in `universe` as a `MarketTick` and a
+/// sampled subset as `MarketData`, see [`sampling`], and a `Candle` at the end
+/// of every interval. Symbol subscriptions arriving on `control` change the
+/// universe on the open stream, see [`universe`].
+pub async fn run(
+    tx: EventSender,
+    mut control: EventReceiver,
+    health: HealthState,
+    sampling: SamplingConfig,
+    mut universe: SymbolUniverse,
+) -> AureliaResult<()> {
     let _ = CryptoProvider::install_default(rustls::crypto::ring::default_provider());
 
-    println!("[Perception Core] Connecting to Binance WebSocket...");
+    let url = market_ws_url(&universe);
+    println!(
+        "[Perception Core] Connecting to market WebSocket {}...",
+        url
+    );
 
-    let (ws_stream, _) = connect_async(BINANCE_WS_API)
-        .await
-        .expect("Failed to connect to WebSocket");
+    let (ws_stream, _) = connect_async(url.as_str()).await.map_err(|e| {
+        let error = format!("Failed to connect to WebSocket: {}", e);
+        health.set(PERCEPTION, false, Some(error.clone()));
+        AureliaError::Exchange(error)
+    })?;
+    health.set(PERCEPTION, true, None);
     tracing::info!(
         "[Perception Core] Connection to Binance WebSocket successful. Awaiting market data..."
     );
 
-    let (_write, read) = ws_stream.split();
+    let (mut write, read) = ws_stream.split();
     pin_mut!(read);
 
-    while let Some(message) = read.next().await {
+    let mut sampler = MarketSampler::new(sampling);
+    let mut candles = CandleBuilder::new(CANDLE_INTERVAL_SECONDS);
+    let mut regimes = RegimeDetector::new();
+    let mut flush = tokio::time::interval(SAMPLER_FLUSH_INTERVAL);
+
+    loop {
+        let message = tokio::select! {
+            message = read.next() => match message {
+                Some(message) => message,
+                None => break,
+            },
+            _ = flush.tick() => {
+                for market_data in sampler.flush(Instant::now()) {
+                    send_market_data(&tx, market_data);
+                }
+                continue;
+            }
+            Ok(event) = control.recv() => {
+                if let Some(frame) = universe.apply(&event) {
+                    tracing::info!(
+                        symbols = ?universe.symbols().collect::<Vec<_>>(),
+                        "[Perception Core] {:?}", event
+                    );
+                    if let Err(e) = write.send(Message::Text(frame)).await {
+                        tracing::error!("[Perception Core] Failed to change subscription: {}", e);
+                    }
+                }
+                continue;
+            }
+        };
         if let Ok(Message::Text(text)) = message {
-            if let Ok(trade) = serde_json::from_str::<BinanceTrade>(&text) {
+            if let Ok(trade) =
+                serde_json::from_str::<TradeMessage>(&text).map(TradeMessage::into_trade)
+            {
                 let market_data = MarketData {
                     symbol: trade.symbol,
                     price: trade.price.parse().unwrap_or(0.0),
                     quantity: trade.quantity.parse().unwrap_or(0.0),
                     timestamp: trade.timestamp,
+                    meta: EventMeta::default(),
                 };
-                if let Err(e) = tx.send(AppEvent::MarketData(market_data)) {
-                    eprintln!("[Perception Core] Failed to send market data: {}", e);
+                tracing::trace!(
+                    correlation_id = %market_data.meta.correlation_id,
+                    symbol = %market_data.symbol,
+                    price = market_data.price,
+                    "[Perception Core] Market data received"
+                );
+                if let Some(candle) = candles.update(&market_data) {
+                    let conditions = regimes.update(&candle);
+                    if let Err(e) = tx.send(AppEvent::Candle(candle)) {
+                        eprintln!("[Perception Core] Failed to send candle: {}", e);
+                    }
+                    if let Some(conditions) = conditions {
+                        let _ = tx.send(AppEvent::MarketConditions(conditions));
+                    }
+                }
+                // Nobody subscribing to raw ticks is not an error
+                let _ = tx.send(AppEvent::MarketTick(market_data.clone()));
+                if let Some(market_data) = sampler.offer(market_data, Instant::now()) {
+                    send_market_data(&tx, market_data);
                 }
             }
         }
     }
+
+    let error = "Binance WebSocket stream ended".to_string();
+    health.set(PERCEPTION, false, Some(error.clone()));
+    Err(AureliaError::Exchange(error))
 }
@@ -0,0 +1,589 @@
+//! On-disk history of the market data the agent has seen.
+//!
+//! Every trade from the market stream is kept in a SQLite database together with
+//! candles built from it. Raw trades are compacted away first, candles later, so
+//! the backtester and indicator warm-up can look back without the database
+//! growing without bound. The freed space is given back in small steps, so
+//! recording never waits for a full `VACUUM`. In Conservation mode raw trades are not stored at all
+//! and both retention windows shrink.
+
+use common::{
+    AppEvent, AureliaError, AureliaResult, Candle, EventReceiver, EventSender, LagHandler,
+    MarketData, SystemState,
+};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+pub const MARKET_STORE_CONFIG_PATH: &str = "config/market_store.json";
+
+const HOUR_MS: u64 = 3_600_000;
+/// Pages given back to the file system per step of a compaction
+const VACUUM_STEP_PAGES: u32 = 256;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MarketStoreConfig {
+    pub enabled: bool,
+    pub path: PathBuf,
+    pub candle_interval_seconds: u64,
+    /// Raw trades older than this are deleted; their candles are kept
+    pub trade_retention_hours: u64,
+    pub candle_retention_hours: u64,
+    pub conservation_trade_retention_hours: u64,
+    pub conservation_candle_retention_hours: u64,
+    /// Trades are written in batches at this interval
+    pub flush_interval_seconds: u64,
+    pub compaction_interval_seconds: u64,
+}
+
+impl Default for MarketStoreConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: PathBuf::from("data/market.db"),
+            candle_interval_seconds: 60,
+            trade_retention_hours: 24,
+            candle_retention_hours: 24 * 30,
+            conservation_trade_retention_hours: 1,
+            conservation_candle_retention_hours: 24 * 7,
+            flush_interval_seconds: 1,
+            compaction_interval_seconds: 3600,
+        }
+    }
+}
+
+impl MarketStoreConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn retention_ms(&self, state: SystemState) -> (u64, u64) {
+        let (trades, candles) = match state {
+            SystemState::Normal | SystemState::Safe => {
+                (self.trade_retention_hours, self.candle_retention_hours)
+            }
+            SystemState::Conservation => (
+                self.conservation_trade_retention_hours,
+                self.conservation_candle_retention_hours,
+            ),
+        };
+        (trades * HOUR_MS, candles * HOUR_MS)
+    }
+}
+
+fn storage_error(e: rusqlite::Error) -> AureliaError {
+    AureliaError::Storage(e.to_string())
+}
+
+pub struct MarketStore {
+    conn: Mutex<Connection>,
+    candle_interval_ms: u64,
+}
+
+impl MarketStore {
+    pub fn open(path: impl AsRef<Path>, candle_interval_seconds: u64) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let conn = Connection::open(path).map_err(storage_error)?;
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .map_err(storage_error)?;
+        Self::with_connection(conn, candle_interval_seconds)
+    }
+
+    pub fn in_memory(candle_interval_seconds: u64) -> AureliaResult<Self> {
+        Self::with_connection(
+            Connection::open_in_memory().map_err(storage_error)?,
+            candle_interval_seconds,
+        )
+    }
+
+    fn with_connection(conn: Connection, candle_interval_seconds: u64) -> AureliaResult<Self> {
+        // Freed pages are only returned step by step with incremental vacuum; a
+        // database created without it is converted once
+        let auto_vacuum: i64 = conn
+            .query_row("PRAGMA auto_vacuum", [], |row| row.get(0))
+            .map_err(storage_error)?;
+        if auto_vacuum != 2 {
+            conn.execute_batch("PRAGMA auto_vacuum = INCREMENTAL; VACUUM;")
+                .map_err(storage_error)?;
+        }
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS trades (
+                symbol TEXT NOT NULL,
+                timestamp INTEGER NOT NULL,
+                price REAL NOT NULL,
+                quantity REAL NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS trades_by_time ON trades (symbol, timestamp);
+            CREATE TABLE IF NOT EXISTS candles (
+                symbol TEXT NOT NULL,
+                open_time INTEGER NOT NULL,
+                open REAL NOT NULL,
+                high REAL NOT NULL,
+                low REAL NOT NULL,
+                close REAL NOT NULL,
+                volume REAL NOT NULL,
+                trades INTEGER NOT NULL,
+                PRIMARY KEY (symbol, open_time)
+            );",
+        )
+        .map_err(storage_error)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            candle_interval_ms: candle_interval_seconds.max(1) * 1000,
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().expect("market store lock poisoned")
+    }
+
+    /// Store a batch of trades in one transaction, updating their candles.
+    /// With `keep_raw` false only the candles are updated.
+    pub fn record(&self, ticks: &[MarketData], keep_raw: bool) -> AureliaResult<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(storage_error)?;
+        {
+            let mut insert_trade = tx
+                .prepare_cached(
+                    "INSERT INTO trades (symbol, timestamp, price, quantity) VALUES (?1, ?2, ?3, ?4)",
+                )
+                .map_err(storage_error)?;
+            let mut upsert_candle = tx
+                .prepare_cached(
+                    "INSERT INTO candles (symbol, open_time, open, high, low, close, volume, trades)
+                     VALUES (?1, ?2, ?3, ?3, ?3, ?3, ?4, 1)
+                     ON CONFLICT (symbol, open_time) DO UPDATE SET
+                        high = max(high, excluded.high),
+                        low = min(low, excluded.low),
+                        close = excluded.close,
+                        volume = volume + excluded.volume,
+                        trades = trades + 1",
+                )
+                .map_err(storage_error)?;
+
+            for tick in ticks {
+                if keep_raw {
+                    insert_trade
+                        .execute(params![
+                            tick.symbol,
+                            tick.timestamp as i64,
+                            tick.price,
+                            tick.quantity
+                        ])
+                        .map_err(storage_error)?;
+                }
+                let open_time = tick.timestamp - tick.timestamp % self.candle_interval_ms;
+                upsert_candle
+                    .execute(params![
+                        tick.symbol,
+                        open_time as i64,
+                        tick.price,
+                        tick.quantity
+                    ])
+                    .map_err(storage_error)?;
+            }
+        }
+        tx.commit().map_err(storage_error)
+    }
+
+    /// Store complete candles, such as ones fetched from the exchange, keeping
+    /// any already stored for the same time. Returns the number added.
+    pub fn insert_candles(&self, candles: &[Candle]) -> AureliaResult<usize> {
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(storage_error)?;
+        let mut added = 0;
+        {
+            let mut insert = tx
+                .prepare_cached(
+                    "INSERT INTO candles (symbol, open_time, open, high, low, close, volume, trades)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                     ON CONFLICT (symbol, open_time) DO NOTHING",
+                )
+                .map_err(storage_error)?;
+            for candle in candles {
+                added += insert
+                    .execute(params![
+                        candle.symbol,
+                        candle.open_time as i64,
+                        candle.open,
+                        candle.high,
+                        candle.low,
+                        candle.close,
+                        candle.volume,
+                        candle.trades as i64
+                    ])
+                    .map_err(storage_error)?;
+            }
+        }
+        tx.commit().map_err(storage_error)?;
+        Ok(added)
+    }
+
+    pub fn candle_interval_seconds(&self) -> u64 {
+        self.candle_interval_ms / 1000
+    }
+
+    /// Raw trades of `symbol` in `[from_ms, to_ms)`, oldest first
+    pub fn trades(&self, symbol: &str, from_ms: u64, to_ms: u64) -> AureliaResult<Vec<MarketData>> {
+        let conn = self.conn();
+        let mut query = conn
+            .prepare_cached(
+                "SELECT timestamp, price, quantity FROM trades
+                 WHERE symbol = ?1 AND timestamp >= ?2 AND timestamp < ?3
+                 ORDER BY timestamp, rowid",
+            )
+            .map_err(storage_error)?;
+        let rows = query
+            .query_map(params![symbol, from_ms as i64, to_ms as i64], |row| {
+                Ok(MarketData {
+                    symbol: symbol.to_string(),
+                    timestamp: row.get::<_, i64>(0)? as u64,
+                    price: row.get(1)?,
+                    quantity: row.get(2)?,
+                    meta: Default::default(),
+                })
+            })
+            .map_err(storage_error)?;
+        rows.collect::<Result<_, _>>().map_err(storage_error)
+    }
+
+    /// Market data of `symbol` in `[from_ms, to_ms)` for the backtester, oldest
+    /// first: the raw trades that are still kept, preceded by four ticks for each
+    /// candle that closed before the first of them.
+    pub fn replay(&self, symbol: &str, from_ms: u64, to_ms: u64) -> AureliaResult<Vec<MarketData>> {
+        let trades = self.trades(symbol, from_ms, to_ms)?;
+        let candles_to = trades.first().map_or(to_ms, |trade| {
+            trade.timestamp - trade.timestamp % self.candle_interval_ms
+        });
+        let mut ticks: Vec<_> = self
+            .candles(symbol, from_ms, candles_to)?
+            .iter()
+            .flat_map(|candle| candle_ticks(candle, self.candle_interval_ms))
+            .collect();
+        ticks.extend(trades);
+        Ok(ticks)
+    }
+
+    /// Candles of `symbol` opening in `[from_ms, to_ms)`, oldest first
+    pub fn candles(&self, symbol: &str, from_ms: u64, to_ms: u64) -> AureliaResult<Vec<Candle>> {
+        self.query_candles(
+            "SELECT open_time, open, high, low, close, volume, trades FROM candles
+             WHERE symbol = ?1 AND open_time >= ?2 AND open_time < ?3
+             ORDER BY open_time",
+            params![symbol, from_ms as i64, to_ms as i64],
+            symbol,
+        )
+    }
+
+    /// The last `limit` candles of `symbol`, oldest first, e.g. to warm up indicators
+    pub fn recent_candles(&self, symbol: &str, limit: usize) -> AureliaResult<Vec<Candle>> {
+        let mut candles = self.query_candles(
+            "SELECT open_time, open, high, low, close, volume, trades FROM candles
+             WHERE symbol = ?1 ORDER BY open_time DESC LIMIT ?2",
+            params![symbol, limit as i64],
+            symbol,
+        )?;
+        candles.reverse();
+        Ok(candles)
+    }
+
+    /// The last candle of `symbol` opening before `before_ms`
+    pub fn latest_candle_before(
+        &self,
+        symbol: &str,
+        before_ms: u64,
+    ) -> AureliaResult<Option<Candle>> {
+        Ok(self
+            .query_candles(
+                "SELECT open_time, open, high, low, close, volume, trades FROM candles
+                 WHERE symbol = ?1 AND open_time < ?2 ORDER BY open_time DESC LIMIT 1",
+                params![symbol, before_ms as i64],
+                symbol,
+            )?
+            .pop())
+    }
+
+    fn query_candles(
+        &self,
+        sql: &str,
+        params: impl rusqlite::Params,
+        symbol: &str,
+    ) -> AureliaResult<Vec<Candle>> {
+        let conn = self.conn();
+        let mut query = conn.prepare_cached(sql).map_err(storage_error)?;
+        let rows = query
+            .query_map(params, |row| {
+                Ok(Candle {
+                    symbol: symbol.to_string(),
+                    open_time: row.get::<_, i64>(0)? as u64,
+                    open: row.get(1)?,
+                    high: row.get(2)?,
+                    low: row.get(3)?,
+                    close: row.get(4)?,
+                    volume: row.get(5)?,
+                    trades: row.get::<_, i64>(6)? as u64,
+                })
+            })
+            .map_err(storage_error)?;
+        rows.collect::<Result<_, _>>().map_err(storage_error)
+    }
+
+    /// Delete trades before `trades_before_ms` and candles before
+    /// `candles_before_ms`, then give the space back to the file system.
+    /// Returns the number of deleted rows.
+    pub fn compact(&self, trades_before_ms: u64, candles_before_ms: u64) -> AureliaResult<usize> {
+        let deleted = {
+            let conn = self.conn();
+            let trades = conn
+                .execute(
+                    "DELETE FROM trades WHERE timestamp < ?1",
+                    params![trades_before_ms as i64],
+                )
+                .map_err(storage_error)?;
+            let candles = conn
+                .execute(
+                    "DELETE FROM candles WHERE open_time < ?1",
+                    params![candles_before_ms as i64],
+                )
+                .map_err(storage_error)?;
+            trades + candles
+        };
+        if deleted > 0 {
+            // The lock is released between steps, so the recorder waits for one
+            // step at most
+            while self.vacuum_step()? {}
+            self.conn()
+                .execute_batch("PRAGMA wal_checkpoint(TRUNCATE);")
+                .map_err(storage_error)?;
+        }
+        Ok(deleted)
+    }
+
+    /// Give up to [`VACUUM_STEP_PAGES`] free pages back; false once none are left.
+    fn vacuum_step(&self) -> AureliaResult<bool> {
+        let conn = self.conn();
+        let free: i64 = conn
+            .query_row("PRAGMA freelist_count", [], |row| row.get(0))
+            .map_err(storage_error)?;
+        if free == 0 {
+            return Ok(false);
+        }
+        // Each step of the statement frees one page, so it is run to the end
+        let mut vacuum = conn
+            .prepare(&format!("PRAGMA incremental_vacuum({})", VACUUM_STEP_PAGES))
+            .map_err(storage_error)?;
+        let mut rows = vacuum.query([]).map_err(storage_error)?;
+        while rows.next().map_err(storage_error)?.is_some() {}
+        Ok(true)
+    }
+}
+
+/// Ticks at the open, the extreme away from the close, the extreme towards it and
+/// the close of `candle`, spread over its interval with a quarter of its volume each.
+fn candle_ticks(candle: &Candle, interval_ms: u64) -> impl Iterator<Item = MarketData> + '_ {
+    let (first, second) = if candle.close >= candle.open {
+        (candle.low, candle.high)
+    } else {
+        (candle.high, candle.low)
+    };
+    [candle.open, first, second, candle.close]
+        .into_iter()
+        .enumerate()
+        .map(move |(i, price)| MarketData {
+            symbol: candle.symbol.clone(),
+            price,
+            quantity: candle.volume / 4.0,
+            timestamp: candle.open_time + i as u64 * interval_ms / 4,
+            meta: Default::default(),
+        })
+}
+
+/// Feeds market data from the event bus into a [`MarketStore`]
+pub struct MarketRecorder {
+    store: Arc<MarketStore>,
+    config: MarketStoreConfig,
+    state: SystemState,
+    lag: LagHandler,
+}
+
+impl MarketRecorder {
+    pub fn new(store: Arc<MarketStore>, config: MarketStoreConfig) -> Self {
+        Self {
+            store,
+            config,
+            state: SystemState::Normal,
+            lag: LagHandler::new("market_store"),
+        }
+    }
+
+    /// Publish the gaps in the recorded history as `ConsumerLagged`
+    pub fn with_event_bus(mut self, tx: EventSender) -> Self {
+        self.lag = self.lag.with_event_bus(tx);
+        self
+    }
+
+    /// `rx` must carry the MarketTicks and System topics
+    pub async fn run(mut self, mut rx: EventReceiver) {
+        let mut flush = tokio::time::interval(Duration::from_secs(
+            self.config.flush_interval_seconds.max(1),
+        ));
+        let mut compaction = tokio::time::interval(Duration::from_secs(
+            self.config.compaction_interval_seconds.max(60),
+        ));
+        let mut pending = Vec::new();
+
+        loop {
+            tokio::select! {
+                event = rx.recv() => match event {
+                    Ok(AppEvent::MarketTick(tick)) => pending.push(tick),
+                    Ok(AppEvent::SystemStateChange(state)) => {
+                        if state != self.state {
+                            tracing::info!(?state, "[Market Store] Adjusting retention");
+                            self.state = state;
+                            self.compact().await;
+                        }
+                    }
+                    Ok(_) => {}
+                    // The history has a gap
+                    Err(RecvError::Lagged(n)) => {
+                        self.lag.lagged(n);
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                _ = flush.tick() => self.flush(&mut pending).await,
+                _ = compaction.tick() => self.compact().await,
+            }
+        }
+        self.flush(&mut pending).await;
+    }
+
+    async fn flush(&self, pending: &mut Vec<MarketData>) {
+        if pending.is_empty() {
+            return;
+        }
+        let ticks = std::mem::take(pending);
+        let store = self.store.clone();
+        let keep_raw = self.state != SystemState::Conservation;
+        let result = tokio::task::spawn_blocking(move || store.record(&ticks, keep_raw))
+            .await
+            .unwrap_or_else(|e| Err(AureliaError::Storage(e.to_string())));
+        if let Err(e) = result {
+            tracing::error!("[Market Store] Failed to record market data: {}", e);
+        }
+    }
+
+    async fn compact(&self) {
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        let (trades, candles) = self.config.retention_ms(self.state.clone());
+        let store = self.store.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            store.compact(now.saturating_sub(trades), now.saturating_sub(candles))
+        })
+        .await
+        .unwrap_or_else(|e| Err(AureliaError::Storage(e.to_string())));
+        match result {
+            Ok(0) => {}
+            Ok(deleted) => tracing::info!("[Market Store] Compacted {} rows", deleted),
+            Err(e) => tracing::error!("[Market Store] Compaction failed: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick(timestamp: u64, price: f64) -> MarketData {
+        MarketData {
+            symbol: "BTCUSDT".to_string(),
+            price,
+            quantity: 0.5,
+            timestamp,
+            meta: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_trades_build_candles_and_compact() {
+        let store = MarketStore::in_memory(60).unwrap();
+        store
+            .record(
+                &[tick(0, 100.0), tick(10_000, 105.0), tick(20_000, 99.0)],
+                true,
+            )
+            .unwrap();
+        store.record(&[tick(60_000, 101.0)], false).unwrap();
+
+        assert_eq!(store.trades("BTCUSDT", 0, u64::MAX / 2).unwrap().len(), 3);
+        let candles = store.candles("BTCUSDT", 0, 120_000).unwrap();
+        assert_eq!(candles.len(), 2);
+        assert_eq!(
+            (
+                candles[0].open,
+                candles[0].high,
+                candles[0].low,
+                candles[0].close
+            ),
+            (100.0, 105.0, 99.0, 99.0)
+        );
+        assert_eq!(candles[0].trades, 3);
+        assert_eq!(candles[0].volume, 1.5);
+        assert_eq!(
+            store.recent_candles("BTCUSDT", 1).unwrap()[0].open_time,
+            60_000
+        );
+
+        assert_eq!(store.compact(15_000, 60_000).unwrap(), 3);
+        assert_eq!(store.trades("BTCUSDT", 0, u64::MAX / 2).unwrap().len(), 1);
+        assert_eq!(store.candles("BTCUSDT", 0, 120_000).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_replay_falls_back_to_candles_where_trades_were_compacted() {
+        let store = MarketStore::in_memory(60).unwrap();
+        store
+            .record(
+                &[
+                    tick(0, 100.0),
+                    tick(10_000, 95.0),
+                    tick(20_000, 108.0),
+                    tick(30_000, 104.0),
+                ],
+                true,
+            )
+            .unwrap();
+        store
+            .record(&[tick(70_000, 103.0), tick(80_000, 102.0)], true)
+            .unwrap();
+        store.compact(60_000, 0).unwrap();
+
+        let ticks = store.replay("BTCUSDT", 0, 120_000).unwrap();
+        let replayed: Vec<_> = ticks.iter().map(|t| (t.timestamp, t.price)).collect();
+        assert_eq!(
+            replayed,
+            vec![
+                (0, 100.0),
+                (15_000, 95.0),
+                (30_000, 108.0),
+                (45_000, 104.0),
+                (70_000, 103.0),
+                (80_000, 102.0),
+            ]
+        );
+        assert_eq!(ticks[0].quantity, 0.5);
+        // A window inside the kept trades replays them alone
+        assert_eq!(store.replay("BTCUSDT", 75_000, 120_000).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,323 @@
+use chrono::{DateTime, Utc};
+use common::{AppEvent, AureliaError, AureliaResult, EventSender, NewsItem};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::path::Path;
+use std::time::Duration;
+
+pub const NEWS_CONFIG_PATH: &str = "config/news_sources.json";
+
+/// Articles remembered for deduplication.
+const SEEN_CAPACITY: usize = 5000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NewsConfig {
+    pub poll_interval_seconds: u64,
+    /// Articles published longer ago than this are skipped, so a restart does not
+    /// replay every feed
+    pub max_age_hours: u64,
+    pub request_timeout_seconds: u64,
+    pub sources: Vec<NewsSource>,
+}
+
+impl Default for NewsConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_seconds: 300,
+            max_age_hours: 24,
+            request_timeout_seconds: 20,
+            sources: Vec::new(),
+        }
+    }
+}
+
+impl NewsConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewsSource {
+    pub name: String,
+    pub url: String,
+    #[serde(flatten)]
+    pub format: FeedFormat,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FeedFormat {
+    /// RSS, Atom or JSON Feed
+    Feed,
+    /// A JSON news API. Each field is a JSON pointer: `items` into the response,
+    /// the others into each item.
+    Json {
+        items: String,
+        title: String,
+        link: String,
+        #[serde(default)]
+        published: Option<String>,
+    },
+}
+
+/// Remembers recent articles by URL and by title, so the same story syndicated
+/// under another link or with tracking parameters is reported once.
+#[derive(Debug, Default)]
+struct SeenArticles {
+    keys: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl SeenArticles {
+    /// Returns `true` the first time an article is seen.
+    fn insert(&mut self, item: &NewsItem) -> bool {
+        let keys = [
+            format!("url:{}", normalize_url(&item.url)),
+            format!("title:{}", normalize_title(&item.title)),
+        ];
+        if keys.iter().any(|key| self.keys.contains(key)) {
+            return false;
+        }
+        for key in keys {
+            self.keys.insert(key.clone());
+            self.order.push_back(key);
+        }
+        while self.order.len() > SEEN_CAPACITY {
+            if let Some(old) = self.order.pop_front() {
+                self.keys.remove(&old);
+            }
+        }
+        true
+    }
+}
+
+fn normalize_url(url: &str) -> String {
+    let without_fragment = url.split('#').next().unwrap_or(url);
+    let (base, query) = match without_fragment.split_once('?') {
+        Some((base, query)) => (base, Some(query)),
+        None => (without_fragment, None),
+    };
+    let mut normalized = base
+        .trim_end_matches('/')
+        .replacen("http://", "https://", 1)
+        .to_lowercase();
+    let params: Vec<&str> = query
+        .into_iter()
+        .flat_map(|q| q.split('&'))
+        .filter(|p| !p.is_empty() && !p.starts_with("utm_"))
+        .collect();
+    if !params.is_empty() {
+        normalized.push('?');
+        normalized.push_str(&params.join("&"));
+    }
+    normalized
+}
+
+fn normalize_title(title: &str) -> String {
+    title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn millis(at: DateTime<Utc>) -> u64 {
+    at.timestamp_millis().max(0) as u64
+}
+
+fn parse_feed(source: &str, body: &[u8], now: DateTime<Utc>) -> AureliaResult<Vec<NewsItem>> {
+    let feed = feed_rs::parser::parse(body)
+        .map_err(|e| AureliaError::Exchange(format!("{}: invalid feed: {}", source, e)))?;
+    Ok(feed
+        .entries
+        .into_iter()
+        .filter_map(|entry| {
+            let title = entry.title?.content.trim().to_string();
+            let url = entry.links.first()?.href.clone();
+            let published = entry.published.or(entry.updated).unwrap_or(now);
+            Some(NewsItem {
+                title,
+                url,
+                source: source.to_string(),
+                timestamp: millis(published),
+            })
+        })
+        .collect())
+}
+
+fn parse_json(
+    source: &str,
+    body: &[u8],
+    format: &FeedFormat,
+    now: DateTime<Utc>,
+) -> AureliaResult<Vec<NewsItem>> {
+    let FeedFormat::Json {
+        items,
+        title,
+        link,
+        published,
+    } = format
+    else {
+        return Ok(Vec::new());
+    };
+    let response: serde_json::Value = serde_json::from_slice(body)?;
+    let entries = response
+        .pointer(items)
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| AureliaError::Exchange(format!("{}: no item array at {}", source, items)))?;
+
+    Ok(entries
+        .iter()
+        .filter_map(|entry| {
+            let text = |pointer: &str| entry.pointer(pointer)?.as_str().map(str::trim);
+            let published = published
+                .as_deref()
+                .and_then(text)
+                .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+                .map(|ts| ts.with_timezone(&Utc))
+                .unwrap_or(now);
+            Some(NewsItem {
+                title: text(title)?.to_string(),
+                url: text(link)?.to_string(),
+                source: source.to_string(),
+                timestamp: millis(published),
+            })
+        })
+        .collect())
+}
+
+/// Polls RSS/Atom feeds and JSON news APIs, publishing each new article once as
+/// `AppEvent::NewsItem`.
+pub struct NewsPoller {
+    config: NewsConfig,
+    client: reqwest::Client,
+    seen: SeenArticles,
+}
+
+impl NewsPoller {
+    pub fn new(config: NewsConfig) -> AureliaResult<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.request_timeout_seconds))
+            .user_agent("aurelia-news/0.1")
+            .build()
+            .map_err(|e| AureliaError::Config(format!("news HTTP client: {}", e)))?;
+        Ok(Self {
+            config,
+            client,
+            seen: SeenArticles::default(),
+        })
+    }
+
+    async fn fetch(&self, source: &NewsSource) -> AureliaResult<Vec<NewsItem>> {
+        let response = self
+            .client
+            .get(&source.url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AureliaError::Exchange(format!("{}: {}", source.name, e)))?;
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| AureliaError::Exchange(format!("{}: {}", source.name, e)))?;
+
+        let now = Utc::now();
+        match &source.format {
+            FeedFormat::Feed => parse_feed(&source.name, &body, now),
+            format @ FeedFormat::Json { .. } => parse_json(&source.name, &body, format, now),
+        }
+    }
+
+    /// Keep the articles that are recent enough and not seen before.
+    fn fresh(&mut self, items: Vec<NewsItem>, now: DateTime<Utc>) -> Vec<NewsItem> {
+        let max_age = chrono::Duration::hours(self.config.max_age_hours as i64);
+        let cutoff = millis(now - max_age);
+        items
+            .into_iter()
+            .filter(|item| item.timestamp >= cutoff)
+            .filter(|item| self.seen.insert(item))
+            .collect()
+    }
+
+    pub async fn run(mut self, tx: EventSender) {
+        tracing::info!(
+            "[Perception Core] Polling {} news sources every {}s",
+            self.config.sources.len(),
+            self.config.poll_interval_seconds
+        );
+        let mut interval = tokio::time::interval(Duration::from_secs(
+            self.config.poll_interval_seconds.max(1),
+        ));
+        loop {
+            interval.tick().await;
+            for source in self.config.sources.clone() {
+                let items = match self.fetch(&source).await {
+                    Ok(items) => self.fresh(items, Utc::now()),
+                    Err(e) => {
+                        tracing::warn!("[Perception Core] News poll failed: {}", e);
+                        continue;
+                    }
+                };
+                for item in items {
+                    tracing::debug!(source = %item.source, "[Perception Core] News: {}", item.title);
+                    if let Err(e) = tx.send(AppEvent::NewsItem(item)) {
+                        tracing::error!("[Perception Core] Failed to send news item: {}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RSS: &str = r#"<?xml version="1.0"?>
+<rss version="2.0"><channel><title>Crypto</title>
+  <item><title>Bitcoin rallies</title><link>https://news.example.com/btc?utm_source=rss</link>
+    <pubDate>Mon, 01 Jan 2024 10:00:00 GMT</pubDate></item>
+  <item><title>Old news</title><link>https://news.example.com/old</link>
+    <pubDate>Sun, 01 Jan 2023 10:00:00 GMT</pubDate></item>
+</channel></rss>"#;
+
+    #[test]
+    fn test_feeds_are_parsed_and_deduplicated() {
+        let now: DateTime<Utc> = "2024-01-01T12:00:00Z".parse().unwrap();
+        let mut poller = NewsPoller::new(NewsConfig::default()).unwrap();
+
+        let items = parse_feed("example", RSS.as_bytes(), now).unwrap();
+        assert_eq!(items.len(), 2);
+        let fresh = poller.fresh(items, now);
+        assert_eq!(fresh.len(), 1);
+        assert_eq!(fresh[0].title, "Bitcoin rallies");
+        assert_eq!(fresh[0].source, "example");
+
+        let api = FeedFormat::Json {
+            items: "/results".to_string(),
+            title: "/title".to_string(),
+            link: "/url".to_string(),
+            published: Some("/published_at".to_string()),
+        };
+        let body = br#"{"results": [
+            {"title": "Bitcoin rallies!", "url": "https://other.example.com/a", "published_at": "2024-01-01T11:00:00Z"},
+            {"title": "ETH upgrade ships", "url": "http://news.example.com/btc/#top"},
+            {"title": "Solana outage", "url": "https://news.example.com/sol"}
+        ]}"#;
+        let items = parse_json("api", body, &api, now).unwrap();
+        assert_eq!(items.len(), 3);
+        // Same title under another link, then the RSS link without its tracking query
+        let fresh = poller.fresh(items, now);
+        assert_eq!(fresh.len(), 1);
+        assert_eq!(fresh[0].title, "Solana outage");
+        assert_eq!(fresh[0].timestamp, millis(now));
+    }
+}
@@ -0,0 +1,249 @@
+//! Market regimes estimated from closed candles.
+//!
+//! Every candle updates the symbol's look-back window and yields its
+//! [`MarketConditions`]: how wide recent price swings are compared with the rest
+//! of the window, how directional the recent path was, and how recent volume
+//! compares with its average. A symbol only changes regime once the new
+//! classification has held for [`REGIME_CONFIRMATION`] candles, so one odd
+//! candle does not flip strategies in and out of a regime.
+
+use common::{Candle, MarketConditions, MarketRegime};
+use std::collections::{HashMap, VecDeque};
+
+/// Candles kept per symbol
+pub const REGIME_WINDOW: usize = 120;
+
+/// Candles needed before a regime is reported
+pub const MIN_CANDLES: usize = 30;
+
+/// Candles the current swings, trend and volume are measured over
+const RECENT_CANDLES: usize = 10;
+
+/// Consecutive candles a new classification must hold before it is adopted
+pub const REGIME_CONFIRMATION: u32 = 2;
+
+/// Volatility percentile from which a market counts as volatile
+const VOLATILE_PERCENTILE: f64 = 0.9;
+
+/// Trend strength from which a market counts as trending
+const TRENDING_STRENGTH: f64 = 0.4;
+
+/// Liquidity below which a market counts as illiquid
+const ILLIQUID_LIQUIDITY: f64 = 0.25;
+
+#[derive(Debug, Default)]
+struct SymbolRegime {
+    candles: VecDeque<Candle>,
+    regime: MarketRegime,
+    /// A classification different from `regime` and how many candles it held
+    pending: Option<(MarketRegime, u32)>,
+}
+
+#[derive(Debug, Default)]
+pub struct RegimeDetector {
+    symbols: HashMap<String, SymbolRegime>,
+}
+
+impl RegimeDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a closed candle, returning the symbol's conditions once the window
+    /// holds [`MIN_CANDLES`] candles.
+    pub fn update(&mut self, candle: &Candle) -> Option<MarketConditions> {
+        let state = self.symbols.entry(candle.symbol.clone()).or_default();
+        state.candles.push_back(candle.clone());
+        while state.candles.len() > REGIME_WINDOW {
+            state.candles.pop_front();
+        }
+        if state.candles.len() < MIN_CANDLES {
+            return None;
+        }
+
+        let volatility = volatility_percentile(&state.candles);
+        let trend_strength = trend_strength(&state.candles);
+        let liquidity = liquidity(&state.candles);
+        let regime = state.confirm(classify(volatility, trend_strength, liquidity));
+        let opportunity_score = trend_strength.abs() * liquidity;
+
+        Some(MarketConditions {
+            symbol: Some(candle.symbol.clone()),
+            regime,
+            volatility,
+            trend_strength,
+            liquidity,
+            opportunity_score,
+            risk_level: ((volatility + 1.0 - liquidity) / 2.0).clamp(0.0, 1.0),
+            sentiment: 0.0,
+            timestamp: candle.open_time,
+        })
+    }
+
+    /// The current regime of `symbol`, `Unknown` before enough candles arrived.
+    pub fn regime(&self, symbol: &str) -> MarketRegime {
+        self.symbols
+            .get(symbol)
+            .map(|state| state.regime)
+            .unwrap_or_default()
+    }
+}
+
+impl SymbolRegime {
+    fn confirm(&mut self, classified: MarketRegime) -> MarketRegime {
+        if classified == self.regime {
+            self.pending = None;
+        } else if self.regime == MarketRegime::Unknown {
+            self.regime = classified;
+        } else {
+            let held = match self.pending {
+                Some((pending, held)) if pending == classified => held + 1,
+                _ => 1,
+            };
+            if held >= REGIME_CONFIRMATION {
+                self.regime = classified;
+                self.pending = None;
+            } else {
+                self.pending = Some((classified, held));
+            }
+        }
+        self.regime
+    }
+}
+
+fn classify(volatility: f64, trend_strength: f64, liquidity: f64) -> MarketRegime {
+    if liquidity < ILLIQUID_LIQUIDITY {
+        MarketRegime::Illiquid
+    } else if volatility >= VOLATILE_PERCENTILE {
+        MarketRegime::Volatile
+    } else if trend_strength >= TRENDING_STRENGTH {
+        MarketRegime::TrendingUp
+    } else if trend_strength <= -TRENDING_STRENGTH {
+        MarketRegime::TrendingDown
+    } else {
+        MarketRegime::Ranging
+    }
+}
+
+/// Share of the window's rolling average ranges that are narrower than the
+/// current one.
+fn volatility_percentile(candles: &VecDeque<Candle>) -> f64 {
+    let ranges: Vec<f64> = candles
+        .iter()
+        .map(|c| {
+            if c.close > 0.0 {
+                (c.high - c.low) / c.close
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    let averages: Vec<f64> = ranges
+        .windows(RECENT_CANDLES)
+        .map(|window| window.iter().sum::<f64>() / window.len() as f64)
+        .collect();
+    let Some(current) = averages.last() else {
+        return 0.0;
+    };
+    let narrower = averages.iter().filter(|average| *average < current).count();
+    narrower as f64 / averages.len() as f64
+}
+
+/// Net change of the recent closes divided by the sum of their absolute changes.
+fn trend_strength(candles: &VecDeque<Candle>) -> f64 {
+    let closes: Vec<f64> = candles
+        .iter()
+        .skip(candles.len().saturating_sub(RECENT_CANDLES + 1))
+        .map(|c| c.close)
+        .collect();
+    let path: f64 = closes.windows(2).map(|w| (w[1] - w[0]).abs()).sum();
+    if path == 0.0 {
+        return 0.0;
+    }
+    (closes[closes.len() - 1] - closes[0]) / path
+}
+
+/// Average recent volume relative to the window's average, capped at 1.
+fn liquidity(candles: &VecDeque<Candle>) -> f64 {
+    let average = candles.iter().map(|c| c.volume).sum::<f64>() / candles.len() as f64;
+    if average <= 0.0 {
+        return 0.0;
+    }
+    let recent = candles
+        .iter()
+        .rev()
+        .take(RECENT_CANDLES)
+        .map(|c| c.volume)
+        .sum::<f64>()
+        / RECENT_CANDLES.min(candles.len()) as f64;
+    (recent / average).min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candle(minute: u64, close: f64, range: f64, volume: f64) -> Candle {
+        Candle {
+            symbol: "BTCUSDT".to_string(),
+            open_time: minute * 60_000,
+            open: close,
+            high: close + range / 2.0,
+            low: close - range / 2.0,
+            close,
+            volume,
+            trades: 10,
+        }
+    }
+
+    #[test]
+    fn test_classifies_trend_range_and_volatility() {
+        let mut detector = RegimeDetector::new();
+        let mut minute = 0;
+        let mut last = None;
+
+        // Steady climb with ordinary swings and volume
+        for i in 0..MIN_CANDLES {
+            last = detector.update(&candle(minute, 100.0 + i as f64, 1.0, 5.0));
+            minute += 1;
+        }
+        let conditions = last.take().unwrap();
+        assert_eq!(conditions.regime, MarketRegime::TrendingUp);
+        assert!((conditions.trend_strength - 1.0).abs() < 1e-9);
+        assert!((conditions.liquidity - 1.0).abs() < 1e-9);
+
+        // Back and forth around one level
+        for i in 0..RECENT_CANDLES {
+            let close = if i % 2 == 0 { 130.0 } else { 131.0 };
+            last = detector.update(&candle(minute, close, 1.0, 5.0));
+            minute += 1;
+        }
+        assert_eq!(last.take().unwrap().regime, MarketRegime::Ranging);
+
+        // Swings far wider than anything seen so far
+        for i in 0..RECENT_CANDLES {
+            let close = if i % 2 == 0 { 130.0 } else { 131.0 };
+            last = detector.update(&candle(minute, close, 20.0, 5.0));
+            minute += 1;
+        }
+        let conditions = last.take().unwrap();
+        assert_eq!(conditions.regime, MarketRegime::Volatile);
+        assert!(conditions.volatility >= VOLATILE_PERCENTILE);
+        assert_eq!(detector.regime("BTCUSDT"), MarketRegime::Volatile);
+    }
+
+    #[test]
+    fn test_regime_change_needs_confirmation() {
+        let mut state = SymbolRegime::default();
+        assert_eq!(state.confirm(MarketRegime::Ranging), MarketRegime::Ranging);
+
+        // One volatile candle between ranging ones does not flip the regime
+        assert_eq!(state.confirm(MarketRegime::Volatile), MarketRegime::Ranging);
+        assert_eq!(state.confirm(MarketRegime::Ranging), MarketRegime::Ranging);
+        assert_eq!(state.confirm(MarketRegime::Volatile), MarketRegime::Ranging);
+        assert_eq!(
+            state.confirm(MarketRegime::Volatile),
+            MarketRegime::Volatile
+        );
+    }
+}
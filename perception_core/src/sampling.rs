@@ -0,0 +1,177 @@
+//! Conflation of the trade stream before it reaches the engines.
+//!
+//! In volatile markets the exchange can send far more trades than the engines
+//! subscribed to `Topic::Market` keep up with. The sampler lets at most
+//! `max_events_per_second` trades per symbol through; while a symbol is over
+//! budget only its latest trade is held back and sent once the next second
+//! starts. Every trade is still published unsampled as `AppEvent::MarketTick`
+//! for consumers that need raw ticks.
+
+use common::{AureliaResult, MarketData};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+pub const SAMPLING_CONFIG_PATH: &str = "config/market_sampling.json";
+
+const WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SamplingConfig {
+    pub enabled: bool,
+    /// Per symbol
+    pub max_events_per_second: u32,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_events_per_second: 10,
+        }
+    }
+}
+
+impl SamplingConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+struct SymbolWindow {
+    started: Instant,
+    emitted: u32,
+    /// Latest trade held back while the symbol is over budget
+    pending: Option<MarketData>,
+}
+
+pub struct MarketSampler {
+    config: SamplingConfig,
+    symbols: HashMap<String, SymbolWindow>,
+    conflated: u64,
+}
+
+impl MarketSampler {
+    pub fn new(config: SamplingConfig) -> Self {
+        Self {
+            config,
+            symbols: HashMap::new(),
+            conflated: 0,
+        }
+    }
+
+    /// Trades dropped in favour of a later trade of the same symbol
+    pub fn conflated(&self) -> u64 {
+        self.conflated
+    }
+
+    /// Returns the trade if it may be sent now, otherwise keeps it as the
+    /// symbol's pending trade
+    pub fn offer(&mut self, tick: MarketData, now: Instant) -> Option<MarketData> {
+        if !self.config.enabled {
+            return Some(tick);
+        }
+        let limit = self.config.max_events_per_second.max(1);
+        let Some(window) = self.symbols.get_mut(&tick.symbol) else {
+            self.symbols.insert(
+                tick.symbol.clone(),
+                SymbolWindow {
+                    started: now,
+                    emitted: 1,
+                    pending: None,
+                },
+            );
+            return Some(tick);
+        };
+
+        if now.duration_since(window.started) >= WINDOW {
+            window.started = now;
+            window.emitted = 0;
+        }
+        if window.emitted < limit {
+            window.emitted += 1;
+            // A newer trade supersedes the one held back
+            if window.pending.take().is_some() {
+                self.conflated += 1;
+            }
+            Some(tick)
+        } else {
+            if window.pending.replace(tick).is_some() {
+                self.conflated += 1;
+            }
+            None
+        }
+    }
+
+    /// Pending trades of symbols whose budget has renewed
+    pub fn flush(&mut self, now: Instant) -> Vec<MarketData> {
+        let mut ready = Vec::new();
+        for window in self.symbols.values_mut() {
+            if window.pending.is_some() && now.duration_since(window.started) >= WINDOW {
+                window.started = now;
+                window.emitted = 1;
+                ready.extend(window.pending.take());
+            }
+        }
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick(price: f64) -> MarketData {
+        MarketData {
+            symbol: "BTCUSDT".to_string(),
+            price,
+            quantity: 0.1,
+            timestamp: 0,
+            meta: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_over_budget_trades_conflate_to_latest() {
+        let mut sampler = MarketSampler::new(SamplingConfig {
+            enabled: true,
+            max_events_per_second: 2,
+        });
+        let start = Instant::now();
+
+        assert!(sampler.offer(tick(1.0), start).is_some());
+        assert!(sampler.offer(tick(2.0), start).is_some());
+        assert!(sampler.offer(tick(3.0), start).is_none());
+        assert!(sampler.offer(tick(4.0), start).is_none());
+        assert!(sampler.flush(start + Duration::from_millis(500)).is_empty());
+
+        let flushed = sampler.flush(start + WINDOW);
+        assert_eq!(flushed.len(), 1);
+        assert_eq!(flushed[0].price, 4.0);
+        assert_eq!(sampler.conflated(), 1);
+
+        // The flushed trade counts against the new window
+        let later = start + WINDOW;
+        assert!(sampler.offer(tick(5.0), later).is_some());
+        assert!(sampler.offer(tick(6.0), later).is_none());
+    }
+
+    #[test]
+    fn test_disabled_sampler_passes_everything() {
+        let mut sampler = MarketSampler::new(SamplingConfig {
+            enabled: false,
+            max_events_per_second: 1,
+        });
+        let now = Instant::now();
+        for i in 0..5 {
+            assert!(sampler.offer(tick(i as f64), now).is_some());
+        }
+    }
+}
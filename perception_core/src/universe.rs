@@ -0,0 +1,144 @@
+//! The symbols whose trades are streamed.
+//!
+//! The universe starts out as the symbols in `config/symbols.json`. While the
+//! agent runs, `AppEvent::SubscribeSymbol` and `AppEvent::UnsubscribeSymbol` on
+//! the control topic add and remove symbols by sending `SUBSCRIBE` and
+//! `UNSUBSCRIBE` frames on the open combined stream, so the strategy or an
+//! operator can change what is tracked without reconnecting.
+
+use common::{AppEvent, AureliaResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+pub const SYMBOL_UNIVERSE_PATH: &str = "config/symbols.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UniverseConfig {
+    /// Exchange symbols such as `BTCUSDT`
+    pub symbols: Vec<String>,
+}
+
+impl Default for UniverseConfig {
+    fn default() -> Self {
+        Self {
+            symbols: vec!["BTCUSDT".to_string()],
+        }
+    }
+}
+
+impl UniverseConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SubscriptionFrame<'a> {
+    method: &'a str,
+    params: Vec<String>,
+    id: u64,
+}
+
+/// The symbols currently subscribed to, and the frames that change them.
+#[derive(Debug, Clone)]
+pub struct SymbolUniverse {
+    symbols: BTreeSet<String>,
+    next_request_id: u64,
+}
+
+impl SymbolUniverse {
+    pub fn new(config: &UniverseConfig) -> Self {
+        Self {
+            symbols: config.symbols.iter().map(|s| s.to_uppercase()).collect(),
+            next_request_id: 1,
+        }
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = &str> {
+        self.symbols.iter().map(String::as_str)
+    }
+
+    /// Combined stream URL under `base` carrying the trades of every symbol
+    pub fn stream_url(&self, base: &str) -> String {
+        if self.symbols.is_empty() {
+            return base.to_string();
+        }
+        let streams: Vec<String> = self.symbols().map(trade_stream).collect();
+        format!("{}?streams={}", base, streams.join("/"))
+    }
+
+    /// Apply a subscribe or unsubscribe event. Returns the frame to send on the
+    /// stream, or `None` for other events and for changes that change nothing.
+    pub fn apply(&mut self, event: &AppEvent) -> Option<String> {
+        let (method, symbol) = match event {
+            AppEvent::SubscribeSymbol(symbol) => ("SUBSCRIBE", symbol.to_uppercase()),
+            AppEvent::UnsubscribeSymbol(symbol) => ("UNSUBSCRIBE", symbol.to_uppercase()),
+            _ => return None,
+        };
+        let changed = if method == "SUBSCRIBE" {
+            self.symbols.insert(symbol.clone())
+        } else {
+            self.symbols.remove(&symbol)
+        };
+        if !changed {
+            return None;
+        }
+        let frame = SubscriptionFrame {
+            method,
+            params: vec![trade_stream(&symbol)],
+            id: self.next_request_id,
+        };
+        self.next_request_id += 1;
+        serde_json::to_string(&frame).ok()
+    }
+}
+
+/// `<symbol>@trade`
+fn trade_stream(symbol: &str) -> String {
+    format!("{}@trade", symbol.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changes_to_the_universe_become_subscription_frames() {
+        let mut universe = SymbolUniverse::new(&UniverseConfig::default());
+        assert_eq!(
+            universe.stream_url("wss://stream.binance.com:9443/stream"),
+            "wss://stream.binance.com:9443/stream?streams=btcusdt@trade"
+        );
+
+        let frame = universe
+            .apply(&AppEvent::SubscribeSymbol("ethusdt".to_string()))
+            .unwrap();
+        assert_eq!(
+            frame,
+            r#"{"method":"SUBSCRIBE","params":["ethusdt@trade"],"id":1}"#
+        );
+        assert_eq!(
+            universe.symbols().collect::<Vec<_>>(),
+            ["BTCUSDT", "ETHUSDT"]
+        );
+        assert!(universe
+            .apply(&AppEvent::SubscribeSymbol("ETHUSDT".to_string()))
+            .is_none());
+
+        let frame = universe
+            .apply(&AppEvent::UnsubscribeSymbol("BTCUSDT".to_string()))
+            .unwrap();
+        assert!(frame.contains(r#""method":"UNSUBSCRIBE""#) && frame.contains(r#""id":2"#));
+        assert!(universe
+            .apply(&AppEvent::UnsubscribeSymbol("BTCUSDT".to_string()))
+            .is_none());
+        assert!(universe.apply(&AppEvent::ReloadConfig).is_none());
+    }
+}
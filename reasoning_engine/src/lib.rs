@@ -1,25 +1,61 @@
-use common::{AppEvent, EventReceiver, EventSender};
+use common::{
+    AppEvent, AureliaResult, CircuitBreaker, CorrelationId, EndpointClass, EventReceiver,
+    EventSender, LagHandler, RateLimiter,
+};
+use std::collections::HashMap;
 use tokio::sync::broadcast::error::RecvError;
 use tracing::{error, info, warn};
 
+pub mod sentiment;
+
+pub use sentiment::SentimentAggregator;
+
+/// Results kept per cache for serving while a circuit is open; the cache is
+/// emptied once it holds this many.
+const RESULT_CACHE_LEN: usize = 256;
+
 pub struct ReasoningEngine {
     tx: EventSender,
     rx: EventReceiver,
+    limiter: RateLimiter,
+    web_search: CircuitBreaker,
+    llm: CircuitBreaker,
+    /// Last results per search query
+    search_results: HashMap<String, Vec<String>>,
+    /// Last analysis per URL
+    analyses: HashMap<String, String>,
 }
 
 impl ReasoningEngine {
     pub fn new(tx: EventSender, rx: EventReceiver) -> Self {
-        Self { tx, rx }
+        Self {
+            web_search: CircuitBreaker::new("web_search").with_event_bus(tx.clone()),
+            llm: CircuitBreaker::new("llm").with_event_bus(tx.clone()),
+            tx,
+            rx,
+            limiter: RateLimiter::default(),
+            search_results: HashMap::new(),
+            analyses: HashMap::new(),
+        }
+    }
+
+    /// Draw search and LLM requests from a budget shared with the rest of the agent
+    pub fn with_rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.limiter = limiter;
+        self
     }
 
     pub async fn run(&mut self) {
         info!("[Reasoning Engine] Starting...");
+        let lag = LagHandler::new("reasoning_engine").with_event_bus(self.tx.clone());
         loop {
             match self.rx.recv().await {
                 Ok(AppEvent::WebSearchQuery(query)) => self.handle_web_search(query).await,
-                Ok(AppEvent::LlmQuery(query)) => self.handle_llm_query(query).await,
+                Ok(AppEvent::LlmQuery(query, id)) => self.handle_llm_query(query, id).await,
                 Ok(_) => {}
-                Err(RecvError::Lagged(n)) => warn!("[Reasoning Engine] Lagged by {} messages", n),
+                Err(RecvError::Lagged(n)) => {
+                    lag.lagged(n);
+                }
                 Err(RecvError::Closed) => {
                     error!("[Reasoning Engine] Event channel closed.");
                     break;
@@ -28,41 +64,113 @@ impl ReasoningEngine {
         }
     }
 
-    async fn handle_web_search(&self, query: String) {
+    async fn handle_web_search(&mut self, query: String) {
         info!(
             "[Reasoning Engine] Received WebSearchQuery for: '{}'. Emitting simulated response.",
             query
         );
-        // In a real human-in-the-loop or agent-driven system, the agent would see the log above
-        // and call the google_web_search tool. For now, we simulate the agent's action.
-        let results = vec![
-            "https://www.coindesk.com/markets/2025/08/05/bitcoin-holds-steady-as-new-data-emerges/"
-                .to_string(),
-            "https://cointelegraph.com/news/analysis-bitcoin-price-prediction-2025".to_string(),
-        ];
+        let limiter = &self.limiter;
+        let outcome = self
+            .web_search
+            .call(async {
+                limiter.acquire(EndpointClass::WebSearch, 1.0).await;
+                web_search(&query).await
+            })
+            .await;
+        let results = match outcome {
+            Ok(results) => {
+                cache(&mut self.search_results, query, results.clone());
+                results
+            }
+            Err(e) => match self.search_results.get(&query) {
+                Some(cached) => {
+                    warn!(
+                        "[Reasoning Engine] Web search unavailable, serving cached results: {}",
+                        e
+                    );
+                    cached.clone()
+                }
+                None => {
+                    warn!(
+                        "[Reasoning Engine] Web search unavailable, skipping '{}': {}",
+                        query, e
+                    );
+                    return;
+                }
+            },
+        };
         let response = AppEvent::WebSearchResponse(results);
         if let Err(e) = self.tx.send(response) {
             error!("[Reasoning Engine] Failed to send WebSearchResponse: {}", e);
         }
     }
 
-    async fn handle_llm_query(&self, url: String) {
+    async fn handle_llm_query(&mut self, url: String, id: CorrelationId) {
         info!(
             "[Reasoning Engine] Received LlmQuery for URL: '{}'. Simulating fetch and analysis.",
             url
         );
-        // The agent would see the log above, call the web_fetch tool, and then another LLM for analysis.
-        // We simulate both actions.
-        let fetched_content_snippet = "(Simulated Fetched Content) Bitcoin (BTC) remained stable on Tuesday morning, trading around the $70,000 mark as investors digested new inflation data...";
-        info!(
-            "[Reasoning Engine] Simulated fetched content: '{}'",
-            fetched_content_snippet
-        );
-
-        let llm_response = "SIMULATED SENTIMENT: The fetched content appears to be neutral, with a focus on market stability.".to_string();
-        let response = AppEvent::LlmResponse(llm_response);
+        let limiter = &self.limiter;
+        let outcome = self
+            .llm
+            .call(async {
+                limiter.acquire(EndpointClass::Llm, 1.0).await;
+                analyze(&url).await
+            })
+            .await;
+        let llm_response = match outcome {
+            Ok(analysis) => {
+                cache(&mut self.analyses, url, analysis.clone());
+                analysis
+            }
+            Err(e) => match self.analyses.get(&url) {
+                Some(cached) => {
+                    warn!(
+                        "[Reasoning Engine] LLM unavailable, serving cached analysis: {}",
+                        e
+                    );
+                    cached.clone()
+                }
+                None => {
+                    warn!(
+                        "[Reasoning Engine] LLM unavailable, skipping analysis of '{}': {}",
+                        url, e
+                    );
+                    return;
+                }
+            },
+        };
+        let response = AppEvent::LlmResponse(llm_response, id);
         if let Err(e) = self.tx.send(response) {
             error!("[Reasoning Engine] Failed to send LlmResponse: {}", e);
         }
     }
 }
+
+fn cache<T>(cache: &mut HashMap<String, T>, key: String, value: T) {
+    if cache.len() >= RESULT_CACHE_LEN && !cache.contains_key(&key) {
+        cache.clear();
+    }
+    cache.insert(key, value);
+}
+
+async fn web_search(_query: &str) -> AureliaResult<Vec<String>> {
+    // In a real human-in-the-loop or agent-driven system, the agent would see the log above
+    // and call the google_web_search tool. For now, we simulate the agent's action.
+    Ok(vec![
+        "https://www.coindesk.com/markets/2025/08/05/bitcoin-holds-steady-as-new-data-emerges/"
+            .to_string(),
+        "https://cointelegraph.com/news/analysis-bitcoin-price-prediction-2025".to_string(),
+    ])
+}
+
+async fn analyze(_url: &str) -> AureliaResult<String> {
+    // The agent would see the log above, call the web_fetch tool, and then another LLM for analysis.
+    // We simulate both actions.
+    let fetched_content_snippet = "(Simulated Fetched Content) Bitcoin (BTC) remained stable on Tuesday morning, trading around the $70,000 mark as investors digested new inflation data...";
+    info!(
+        "[Reasoning Engine] Simulated fetched content: '{}'",
+        fetched_content_snippet
+    );
+    Ok("SIMULATED SENTIMENT: The fetched content on Bitcoin appears to be neutral, with a focus on market stability.".to_string())
+}
@@ -0,0 +1,296 @@
+use common::{AppEvent, CorrelationId, EventReceiver, EventSender, LagHandler, SentimentUpdate};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, error, info};
+
+/// Age at which an analysis counts half as much as a fresh one.
+const HALF_LIFE: Duration = Duration::from_secs(6 * 60 * 60);
+/// Analyses older than this are dropped from the series.
+const RETENTION: Duration = Duration::from_secs(48 * 60 * 60);
+/// Requested analyses that have not arrived after this long are forgotten.
+const PENDING_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Symbols and the names they go by in news text.
+const SYMBOL_ALIASES: &[(&str, &[&str])] = &[
+    ("BTCUSDT", &["bitcoin", "btc", "btcusdt"]),
+    ("ETHUSDT", &["ethereum", "eth", "ether", "ethusdt"]),
+    ("SOLUSDT", &["solana", "sol", "solusdt"]),
+];
+
+const BULLISH: &[&str] = &[
+    "bullish",
+    "positive",
+    "optimistic",
+    "rally",
+    "surge",
+    "gain",
+    "gains",
+    "upbeat",
+    "strong",
+    "buy",
+];
+const BEARISH: &[&str] = &[
+    "bearish",
+    "negative",
+    "pessimistic",
+    "selloff",
+    "plunge",
+    "drop",
+    "decline",
+    "weak",
+    "fear",
+    "sell",
+];
+const NEUTRAL: &[&str] = &["neutral", "stable", "steady", "flat", "sideways", "mixed"];
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Score an LLM analysis per symbol, from -1 (bearish) to 1 (bullish).
+///
+/// Lines of the form `SENTIMENT <SYMBOL>: <score>` are taken as given. Otherwise the
+/// text is scored by its sentiment words and attributed to every symbol it names;
+/// text that names no symbol or carries no sentiment yields nothing.
+pub fn parse_sentiment(text: &str) -> Vec<(String, f64)> {
+    let explicit: Vec<(String, f64)> = text
+        .lines()
+        .filter_map(|line| {
+            let rest = line.trim().strip_prefix("SENTIMENT ")?;
+            let (symbol, score) = rest.split_once(':')?;
+            let symbol = symbol.trim();
+            if symbol.is_empty() || symbol.contains(char::is_whitespace) {
+                return None;
+            }
+            let score: f64 = score.trim().parse().ok()?;
+            score
+                .is_finite()
+                .then(|| (symbol.to_uppercase(), score.clamp(-1.0, 1.0)))
+        })
+        .collect();
+    if !explicit.is_empty() {
+        return explicit;
+    }
+
+    let lower = text.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    let count = |list: &[&str]| words.iter().filter(|w| list.contains(w)).count() as f64;
+
+    let (bullish, bearish, neutral) = (count(BULLISH), count(BEARISH), count(NEUTRAL));
+    let total = bullish + bearish + neutral;
+    if total == 0.0 {
+        return Vec::new();
+    }
+    let score = (bullish - bearish) / total;
+
+    SYMBOL_ALIASES
+        .iter()
+        .filter(|(_, aliases)| words.iter().any(|w| aliases.contains(w)))
+        .map(|(symbol, _)| (symbol.to_string(), score))
+        .collect()
+}
+
+/// Scores for one symbol, weighted by age with an exponential decay.
+#[derive(Debug, Default)]
+struct SentimentSeries {
+    /// (Unix milliseconds, score), oldest first
+    points: Vec<(u64, f64)>,
+}
+
+impl SentimentSeries {
+    fn record(&mut self, at: u64, score: f64) {
+        self.points.push((at, score));
+        let cutoff = at.saturating_sub(RETENTION.as_millis() as u64);
+        self.points.retain(|(t, _)| *t >= cutoff);
+    }
+
+    fn value(&self, at: u64) -> Option<f64> {
+        let half_life = HALF_LIFE.as_millis() as f64;
+        let (weighted, weights) =
+            self.points
+                .iter()
+                .fold((0.0, 0.0), |(weighted, weights), (t, score)| {
+                    let age = at.saturating_sub(*t) as f64;
+                    let weight = 0.5f64.powf(age / half_life);
+                    (weighted + weight * score, weights + weight)
+                });
+        (weights > 0.0).then(|| weighted / weights)
+    }
+}
+
+/// Turns reasoning output into `AppEvent::SentimentUpdate`s: search results and news
+/// articles are sent off for analysis, and each analysis is scored and folded into a
+/// per-symbol series. Answers to queries the aggregator did not send are ignored.
+pub struct SentimentAggregator {
+    tx: EventSender,
+    rx: EventReceiver,
+    series: HashMap<String, SentimentSeries>,
+    /// Queries awaiting an analysis, with the Unix milliseconds they were sent
+    pending: HashMap<CorrelationId, u64>,
+}
+
+impl SentimentAggregator {
+    pub fn new(tx: EventSender, rx: EventReceiver) -> Self {
+        Self {
+            tx,
+            rx,
+            series: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    pub async fn run(&mut self) {
+        info!("[Sentiment Aggregator] Starting...");
+        let lag = LagHandler::new("sentiment_aggregator").with_event_bus(self.tx.clone());
+        loop {
+            match self.rx.recv().await {
+                Ok(AppEvent::WebSearchResponse(urls)) => self.request_analysis(urls),
+                Ok(AppEvent::NewsItem(item)) => {
+                    info!(
+                        "[Sentiment Aggregator] News from {}: '{}'. Requesting analysis.",
+                        item.source, item.title
+                    );
+                    self.request_analysis(vec![item.url]);
+                }
+                Ok(AppEvent::LlmResponse(analysis, id)) => {
+                    if self.pending.remove(&id).is_some() {
+                        self.handle_analysis(&analysis);
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(n)) => {
+                    lag.lagged(n);
+                }
+                Err(RecvError::Closed) => {
+                    error!("[Sentiment Aggregator] Event channel closed.");
+                    break;
+                }
+            }
+        }
+    }
+
+    fn request_analysis(&mut self, urls: Vec<String>) {
+        let now = now_millis();
+        let cutoff = now.saturating_sub(PENDING_TIMEOUT.as_millis() as u64);
+        self.pending.retain(|_, sent| *sent >= cutoff);
+        for url in urls {
+            let id = CorrelationId::new();
+            match self.tx.send(AppEvent::LlmQuery(url, id.clone())) {
+                Ok(_) => {
+                    self.pending.insert(id, now);
+                }
+                Err(e) => error!("[Sentiment Aggregator] Failed to request analysis: {}", e),
+            }
+        }
+    }
+
+    fn handle_analysis(&mut self, analysis: &str) {
+        let scores = parse_sentiment(analysis);
+        if scores.is_empty() {
+            debug!("[Sentiment Aggregator] No sentiment in response, ignoring");
+            return;
+        }
+
+        let now = now_millis();
+        for (symbol, score) in scores {
+            if let Some(update) = self.record(&symbol, score, now) {
+                info!(
+                    "[Sentiment Aggregator] {} sentiment {:.2} ({} samples)",
+                    update.symbol, update.score, update.samples
+                );
+                if let Err(e) = self.tx.send(AppEvent::SentimentUpdate(update)) {
+                    error!("[Sentiment Aggregator] Failed to publish update: {}", e);
+                }
+            }
+        }
+    }
+
+    fn record(&mut self, symbol: &str, score: f64, at: u64) -> Option<SentimentUpdate> {
+        let series = self.series.entry(symbol.to_string()).or_default();
+        series.record(at, score);
+        Some(SentimentUpdate {
+            symbol: symbol.to_string(),
+            score: series.value(at)?,
+            samples: series.points.len() as u32,
+            timestamp: at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::EventBus;
+
+    #[test]
+    fn test_parse_and_decay() {
+        assert_eq!(
+            parse_sentiment("Bitcoin looks bullish after strong ETF inflows"),
+            vec![("BTCUSDT".to_string(), 1.0)]
+        );
+        assert_eq!(
+            parse_sentiment("BTC and ETH: neutral, some fear of a decline"),
+            vec![
+                ("BTCUSDT".to_string(), -2.0 / 3.0),
+                ("ETHUSDT".to_string(), -2.0 / 3.0)
+            ]
+        );
+        assert_eq!(
+            parse_sentiment("Overview\nSENTIMENT solusdt: 1.7"),
+            vec![("SOLUSDT".to_string(), 1.0)]
+        );
+        assert!(parse_sentiment("The market is bullish").is_empty());
+        assert!(parse_sentiment("DEPLOY: bitcoin nodes are cheap").is_empty());
+
+        let bus = EventBus::new(16);
+        let mut aggregator = SentimentAggregator::new(bus.clone(), bus.subscribe());
+        let half_life = HALF_LIFE.as_millis() as u64;
+        aggregator.record("BTCUSDT", 1.0, 0);
+        let update = aggregator.record("BTCUSDT", -1.0, half_life).unwrap();
+        // The older, bullish reading counts half as much as the fresh one
+        assert!((update.score - (-1.0 / 3.0)).abs() < 1e-9);
+        assert_eq!(update.samples, 2);
+
+        // Readings past the retention window drop out
+        let much_later = half_life + RETENTION.as_millis() as u64 + 1;
+        let update = aggregator.record("BTCUSDT", 0.5, much_later).unwrap();
+        assert_eq!((update.score, update.samples), (0.5, 1));
+    }
+
+    #[tokio::test]
+    async fn test_only_answers_to_own_queries_are_scored() {
+        let bus = EventBus::new(16);
+        let mut reasoning = bus.subscribe();
+        let mut updates = bus.subscribe();
+        let mut aggregator = SentimentAggregator::new(bus.clone(), bus.subscribe());
+        tokio::spawn(async move { aggregator.run().await });
+
+        // Another component's query is answered first
+        let other = CorrelationId::new();
+        bus.send(AppEvent::LlmResponse("Bitcoin looks bullish".into(), other))
+            .unwrap();
+        bus.send(AppEvent::WebSearchResponse(vec!["https://news".into()]))
+            .unwrap();
+        let id = loop {
+            if let AppEvent::LlmQuery(_, id) = reasoning.recv().await.unwrap() {
+                break id;
+            }
+        };
+        bus.send(AppEvent::LlmResponse("Bitcoin looks bearish".into(), id))
+            .unwrap();
+
+        let update = loop {
+            if let AppEvent::SentimentUpdate(update) = updates.recv().await.unwrap() {
+                break update;
+            }
+        };
+        assert_eq!((update.score, update.samples), (-1.0, 1));
+    }
+}
@@ -13,7 +13,8 @@ pub async fn run(tx: EventSender, mut rx: EventReceiver) {
         if let Ok(AppEvent::SystemStateChange(new_state)) = rx.try_recv() {
             info!("[Resource Monitor] Received new state: {:?}", new_state);
             interval_duration = match new_state {
-                SystemState::Normal => Duration::from_secs(5),
+                // Vitals are still watched closely after a flatten
+                SystemState::Normal | SystemState::Safe => Duration::from_secs(5),
                 SystemState::Conservation => Duration::from_secs(30), // Slow down
             };
         }
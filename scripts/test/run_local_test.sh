@@ -47,11 +47,14 @@ if [ ! -f config/strategy.json ]; then
     mkdir -p config
     cat > config/strategy.json << EOF
 {
+    "version": 1,
     "strategy_type": "momentum",
     "symbol": "BTCUSDT",
     "interval": "1h",
     "lookback_periods": 20,
-    "threshold": 0.02
+    "threshold": 0.02,
+    "cpu_usage_threshold": 75.0,
+    "price_drop_threshold": 0.05
 }
 EOF
     echo "✅ Created config/strategy.json"
@@ -0,0 +1,283 @@
+//! Market state the momentum strategy builds from the events the kernel forwards.
+//!
+//! Candle closes feed a fast and a slow exponential moving average per symbol.
+//! Once the slow average has seen enough candles, a crossover of the two becomes
+//! a decision at the latest traded price; buys are held back while sentiment is
+//! below the configured floor or the market is volatile or illiquid.
+
+use common::{AppEvent, DecisionRationale, EventMeta, MarketRegime, StrategyDecision};
+use std::collections::BTreeMap;
+
+/// Gap between the averages, relative to the slow one, at which a crossover is
+/// fully confident
+const FULL_CONFIDENCE_GAP: f64 = 0.01;
+
+/// How far `signal` cleared `threshold`: 0 right at it, 1 at twice the threshold.
+pub(crate) fn confidence(signal: f64, threshold: f64) -> f64 {
+    let (signal, threshold) = (signal.abs(), threshold.abs());
+    if threshold == 0.0 {
+        return 1.0;
+    }
+    ((signal - threshold) / threshold).clamp(0.0, 1.0)
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Ema {
+    value: Option<f64>,
+}
+
+impl Ema {
+    fn update(&mut self, sample: f64, period: f64) {
+        let alpha = 2.0 / (period + 1.0);
+        self.value = Some(match self.value {
+            Some(value) => value + alpha * (sample - value),
+            None => sample,
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Trend {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Default)]
+struct SymbolState {
+    last_price: Option<f64>,
+    /// Metadata of the latest tick, carried by decisions made on it
+    last_meta: EventMeta,
+    fast: Ema,
+    slow: Ema,
+    candles: u32,
+    sentiment: Option<f64>,
+    regime: MarketRegime,
+    /// Trend of the last decision, or of warm-up if none was made yet
+    trend: Option<Trend>,
+}
+
+/// Settings read from the engine parameters on every cycle
+#[derive(Debug, Clone, Copy)]
+pub struct IndicatorParams {
+    pub fast_period: f64,
+    pub slow_period: f64,
+    pub min_sentiment: f64,
+    pub reversion_window: f64,
+    pub reversion_threshold: f64,
+    pub sentiment_entry: f64,
+    pub sentiment_exit: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct MarketView {
+    symbols: BTreeMap<String, SymbolState>,
+}
+
+impl MarketView {
+    pub const fn new() -> Self {
+        Self {
+            symbols: BTreeMap::new(),
+        }
+    }
+
+    /// Update the indicators; events other than market data, candles, sentiment
+    /// and market conditions are ignored.
+    pub fn observe(&mut self, event: &AppEvent, params: &IndicatorParams) {
+        match event {
+            AppEvent::MarketData(data) => {
+                let state = self.symbols.entry(data.symbol.clone()).or_default();
+                state.last_price = Some(data.price);
+                state.last_meta = data.meta.clone();
+            }
+            AppEvent::Candle(candle) => {
+                let state = self.symbols.entry(candle.symbol.clone()).or_default();
+                state.fast.update(candle.close, params.fast_period);
+                state.slow.update(candle.close, params.slow_period);
+                state.candles += 1;
+                state.last_price.get_or_insert(candle.close);
+            }
+            AppEvent::SentimentUpdate(update) => {
+                let state = self.symbols.entry(update.symbol.clone()).or_default();
+                state.sentiment = Some(update.score);
+            }
+            AppEvent::MarketConditions(conditions) => {
+                if let Some(symbol) = &conditions.symbol {
+                    self.symbols.entry(symbol.clone()).or_default().regime = conditions.regime;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Decisions for every symbol whose trend turned since the last one.
+    pub fn decide(&mut self, params: &IndicatorParams) -> Vec<(StrategyDecision, EventMeta)> {
+        let mut decisions = Vec::new();
+        for (symbol, state) in &mut self.symbols {
+            if (state.candles as f64) < params.slow_period {
+                continue;
+            }
+            let (Some(fast), Some(slow), Some(price)) =
+                (state.fast.value, state.slow.value, state.last_price)
+            else {
+                continue;
+            };
+            let trend = if fast > slow { Trend::Up } else { Trend::Down };
+            // The first trend after warm-up is a baseline, not a crossover
+            let Some(previous) = state.trend else {
+                state.trend = Some(trend);
+                continue;
+            };
+            if trend == previous {
+                continue;
+            }
+
+            // Crossovers in choppy or thin markets are mostly noise
+            let unsettled = matches!(
+                state.regime,
+                MarketRegime::Volatile | MarketRegime::Illiquid
+            );
+            let decision = match trend {
+                Trend::Up if state.sentiment.unwrap_or(0.0) < params.min_sentiment => continue,
+                Trend::Up if unsettled => continue,
+                Trend::Up => StrategyDecision::Buy(symbol.clone(), price),
+                Trend::Down => StrategyDecision::Sell(symbol.clone(), price),
+            };
+            state.trend = Some(trend);
+            let gap = if slow > 0.0 {
+                (fast - slow) / slow
+            } else {
+                0.0
+            };
+            let rationale = DecisionRationale {
+                indicators: BTreeMap::from([
+                    ("fast_ema".to_string(), fast),
+                    ("slow_ema".to_string(), slow),
+                    ("price".to_string(), price),
+                ]),
+                sentiment: state.sentiment,
+                regime: state.regime,
+                confidence: (gap.abs() / FULL_CONFIDENCE_GAP).min(1.0),
+                reason: format!(
+                    "fast EMA crossed {} slow EMA",
+                    if trend == Trend::Up { "above" } else { "below" }
+                ),
+            };
+            decisions.push((decision, state.last_meta.clone().with_rationale(rationale)));
+        }
+        decisions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{Candle, MarketConditions, MarketData, SentimentUpdate};
+
+    const PARAMS: IndicatorParams = IndicatorParams {
+        fast_period: 2.0,
+        slow_period: 4.0,
+        min_sentiment: -0.5,
+        reversion_window: 4.0,
+        reversion_threshold: 1.5,
+        sentiment_entry: 0.5,
+        sentiment_exit: -0.2,
+    };
+
+    fn candle(close: f64) -> AppEvent {
+        AppEvent::Candle(Candle {
+            symbol: "BTCUSDT".to_string(),
+            open_time: 0,
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 1.0,
+            trades: 1,
+        })
+    }
+
+    fn tick(price: f64) -> AppEvent {
+        AppEvent::MarketData(MarketData {
+            symbol: "BTCUSDT".to_string(),
+            price,
+            quantity: 0.1,
+            timestamp: 0,
+            meta: Default::default(),
+        })
+    }
+
+    fn sentiment(score: f64) -> AppEvent {
+        AppEvent::SentimentUpdate(SentimentUpdate {
+            symbol: "BTCUSDT".to_string(),
+            score,
+            samples: 1,
+            timestamp: 0,
+        })
+    }
+
+    #[test]
+    fn test_crossovers_become_decisions_after_warm_up() {
+        let mut view = MarketView::default();
+        for close in [100.0, 99.0, 98.0] {
+            view.observe(&candle(close), &PARAMS);
+        }
+        assert!(view.decide(&PARAMS).is_empty(), "still warming up");
+        view.observe(&candle(97.0), &PARAMS);
+        assert!(view.decide(&PARAMS).is_empty(), "downtrend is the baseline");
+
+        // Bearish news holds the buy back until sentiment recovers
+        view.observe(&sentiment(-0.8), &PARAMS);
+        for close in [105.0, 110.0] {
+            view.observe(&candle(close), &PARAMS);
+        }
+        assert!(view.decide(&PARAMS).is_empty());
+        view.observe(&sentiment(0.2), &PARAMS);
+        view.observe(&tick(110.5), &PARAMS);
+        let decisions = view.decide(&PARAMS);
+        assert!(matches!(
+            decisions.as_slice(),
+            [(StrategyDecision::Buy(symbol, price), _)] if symbol == "BTCUSDT" && *price == 110.5
+        ));
+        let rationale = decisions[0].1.rationale.as_ref().unwrap();
+        assert!(rationale.indicators["fast_ema"] > rationale.indicators["slow_ema"]);
+        assert_eq!(rationale.sentiment, Some(0.2));
+        assert_eq!(rationale.reason, "fast EMA crossed above slow EMA");
+        assert!(view.decide(&PARAMS).is_empty());
+
+        for close in [90.0, 80.0] {
+            view.observe(&candle(close), &PARAMS);
+        }
+        assert!(matches!(
+            view.decide(&PARAMS).as_slice(),
+            [(StrategyDecision::Sell(_, _), _)]
+        ));
+    }
+
+    #[test]
+    fn test_volatile_regime_holds_back_buys() {
+        let mut view = MarketView::default();
+        for close in [100.0, 99.0, 98.0, 97.0] {
+            view.observe(&candle(close), &PARAMS);
+        }
+        assert!(view.decide(&PARAMS).is_empty());
+
+        let regime = |regime| {
+            AppEvent::MarketConditions(MarketConditions {
+                symbol: Some("BTCUSDT".to_string()),
+                regime,
+                ..MarketConditions::default()
+            })
+        };
+        view.observe(&regime(MarketRegime::Volatile), &PARAMS);
+        for close in [105.0, 110.0] {
+            view.observe(&candle(close), &PARAMS);
+        }
+        assert!(view.decide(&PARAMS).is_empty());
+
+        view.observe(&regime(MarketRegime::TrendingUp), &PARAMS);
+        assert!(matches!(
+            view.decide(&PARAMS).as_slice(),
+            [(StrategyDecision::Buy(_, _), _)]
+        ));
+    }
+}
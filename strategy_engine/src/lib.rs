@@ -1,20 +1,162 @@
-use common::AppEvent;
+use common::event_schema::{self, STRATEGY_ABI_VERSION};
+use common::strategies::STRATEGIES_PATH;
+use common::strategy_config::STRATEGY_CONFIG_PATH;
+use common::{AppEvent, AureliaError, AureliaResult, StrategySet};
+use indicators::IndicatorParams;
+use params::{
+    FAST_EMA_PERIOD, INTERVAL_SECONDS, MIN_SENTIMENT, PARAM_INVALID_NAME, PARAM_OK,
+    REVERSION_THRESHOLD, REVERSION_WINDOW, SENTIMENT_ENTRY, SENTIMENT_EXIT, SLOW_EMA_PERIOD,
+};
+use std::ffi::CStr;
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock, RwLock};
 use std::time::Duration;
+use strategies::StrategyBook;
 use tokio::runtime::Runtime;
 use tokio::time;
-use tracing::{error, info};
+use tracing::{debug, error, info, warn};
 
-const OUTPUT_FILE: &str = "strategy_output.log";
+pub mod indicators;
+pub mod params;
+pub mod strategies;
+
+/// Where the engine writes events for the kernel unless told otherwise.
+pub const OUTPUT_FILE: &str = "strategy_output.log";
+
+/// Bumped on every engine cycle; the kernel reloads the module when it stops moving.
+static HEARTBEAT: AtomicU64 = AtomicU64::new(0);
+/// The heartbeat must keep moving when the analysis interval is longer than the
+/// kernel's watchdog timeout.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// Set by the kernel to stop the engine; checked on every heartbeat.
+static STOP: AtomicBool = AtomicBool::new(false);
+/// Overrides `OUTPUT_FILE`, e.g. while the module runs as a shadow candidate.
+static OUTPUT_PATH: RwLock<Option<PathBuf>> = RwLock::new(None);
+/// Strategy state, fed by `process_event_from_kernel` and read every analysis cycle.
+static STRATEGIES: OnceLock<Mutex<StrategyBook>> = OnceLock::new();
+
+fn output_path() -> PathBuf {
+    OUTPUT_PATH
+        .read()
+        .expect("output path lock poisoned")
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(OUTPUT_FILE))
+}
 
 #[no_mangle]
 pub extern "C" fn run_strategy_engine() {
-    let rt = Runtime::new().unwrap();
-    rt.block_on(async {
-        let mut engine = StrategyEngine::new();
-        engine.run().await;
+    // A panic must not unwind into the kernel; returning lets its watchdog restart us
+    STOP.store(false, Ordering::Relaxed);
+    let result = std::panic::catch_unwind(|| {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let mut engine = StrategyEngine::new();
+            engine.run().await;
+        });
+    });
+    if result.is_err() {
+        error!("[Strategy Engine DLL] Engine panicked, stopping.");
+    }
+}
+
+/// Checked by the kernel before it swaps this module in, see
+/// [`common::event_schema`].
+#[no_mangle]
+pub extern "C" fn strategy_abi_version() -> u32 {
+    STRATEGY_ABI_VERSION
+}
+
+/// Heartbeat counter polled by the kernel's watchdog.
+#[no_mangle]
+pub extern "C" fn strategy_heartbeat() -> u64 {
+    HEARTBEAT.load(Ordering::Relaxed)
+}
+
+/// Ask the engine to return from `run_strategy_engine` within one heartbeat.
+#[no_mangle]
+pub extern "C" fn strategy_stop() {
+    STOP.store(true, Ordering::Relaxed);
+}
+
+/// Write events for the kernel to `path` instead of [`OUTPUT_FILE`]. Returns `false`
+/// if the path is not valid UTF-8.
+///
+/// # Safety
+///
+/// `path` must be a valid pointer to a null-terminated C string that stays valid
+/// for the duration of the call.
+#[no_mangle]
+pub unsafe extern "C" fn strategy_set_output(path: *const std::os::raw::c_char) -> bool {
+    let Ok(path) = CStr::from_ptr(path).to_str() else {
+        return false;
+    };
+    *OUTPUT_PATH.write().expect("output path lock poisoned") = Some(PathBuf::from(path));
+    true
+}
+
+/// Set a tunable parameter (see [`params::PARAM_SPECS`]). Returns [`PARAM_OK`] or
+/// one of the other `params::PARAM_*` codes.
+///
+/// # Safety
+///
+/// `name` must be a valid pointer to a null-terminated C string that stays valid
+/// for the duration of the call.
+#[no_mangle]
+pub unsafe extern "C" fn strategy_set_param(name: *const std::os::raw::c_char, value: f64) -> i32 {
+    let Ok(name) = CStr::from_ptr(name).to_str() else {
+        return PARAM_INVALID_NAME;
+    };
+    match params::update(name, value) {
+        Ok(()) => {
+            info!("[Strategy Engine DLL] Parameter {} set to {}", name, value);
+            PARAM_OK
+        }
+        Err(e) => {
+            warn!("[Strategy Engine DLL] Rejected parameter update: {}", e);
+            e.code()
+        }
+    }
+}
+
+fn analysis_interval() -> Duration {
+    Duration::from_secs_f64(params::current(INTERVAL_SECONDS))
+}
+
+/// Indicator settings of `strategy` from the current parameter values.
+pub fn indicator_params(strategy: &str) -> IndicatorParams {
+    let current = |name| params::current_for(strategy, name);
+    IndicatorParams {
+        fast_period: current(FAST_EMA_PERIOD),
+        slow_period: current(SLOW_EMA_PERIOD),
+        min_sentiment: current(MIN_SENTIMENT),
+        reversion_window: current(REVERSION_WINDOW),
+        reversion_threshold: current(REVERSION_THRESHOLD),
+        sentiment_entry: current(SENTIMENT_ENTRY),
+        sentiment_exit: current(SENTIMENT_EXIT),
+    }
+}
+
+fn with_strategies<T>(f: impl FnOnce(&mut StrategyBook) -> T) -> T {
+    let book = STRATEGIES.get_or_init(|| {
+        let set = StrategySet::load_or_legacy(STRATEGIES_PATH, STRATEGY_CONFIG_PATH)
+            .unwrap_or_else(|e| {
+                error!(
+                    "[Strategy Engine DLL] Invalid strategies config, using the defaults: {}",
+                    e
+                );
+                StrategySet::default()
+            });
+        let book = StrategyBook::new(&set);
+        info!(
+            "[Strategy Engine DLL] Running strategies: {}",
+            book.ids().collect::<Vec<_>>().join(", ")
+        );
+        Mutex::new(book)
     });
+    f(&mut book.lock().expect("strategy book lock poisoned"))
 }
 
 pub struct StrategyEngine {}
@@ -28,43 +170,66 @@ impl Default for StrategyEngine {
 impl StrategyEngine {
     pub fn new() -> Self {
         // Clear the output file on start
-        let _ = std::fs::remove_file(OUTPUT_FILE);
+        let _ = std::fs::remove_file(output_path());
         Self {}
     }
 
     pub async fn run(&mut self) {
         info!("[Strategy Engine DLL] Starting...");
-        let mut interval = time::interval(Duration::from_secs(10));
+        let mut period = analysis_interval();
+        let mut analysis = time::interval(period);
+        let mut heartbeat = time::interval(HEARTBEAT_INTERVAL);
         loop {
-            interval.tick().await;
-            self.reason().await;
+            tokio::select! {
+                _ = heartbeat.tick() => {
+                    if STOP.load(Ordering::Relaxed) {
+                        info!("[Strategy Engine DLL] Stopped by kernel.");
+                        return;
+                    }
+                    HEARTBEAT.fetch_add(1, Ordering::Relaxed);
+                    // Pick up parameter changes without waiting out a long interval
+                    let wanted = analysis_interval();
+                    if wanted != period {
+                        info!("[Strategy Engine DLL] Analysis interval is now {:?}", wanted);
+                        period = wanted;
+                        analysis = time::interval_at(time::Instant::now() + period, period);
+                    }
+                }
+                _ = analysis.tick() => self.reason().await,
+            }
         }
     }
 
+    #[tracing::instrument(name = "decision_cycle", skip_all)]
     async fn reason(&self) {
-        info!("[Strategy Engine] Waking up to analyze market...");
-        let event = AppEvent::WebSearchQuery("bitcoin price analysis".to_string());
-        self.send_event_to_kernel(event);
+        debug!("[Strategy Engine] Waking up to analyze market...");
+        for (decision, meta) in with_strategies(|book| book.decide(indicator_params)) {
+            info!(
+                correlation_id = %meta.correlation_id,
+                strategy_id = meta.strategy_id.as_deref().unwrap_or_default(),
+                "[Strategy Engine] Decided {:?}", decision
+            );
+            let event = AppEvent::StrategyDecision(decision, meta);
+            if let Err(e) = self.send_event_to_kernel(event) {
+                error!("Failed to send event to kernel: {}", e);
+            }
+        }
     }
 
-    fn send_event_to_kernel(&self, event: AppEvent) {
-        let json = serde_json::to_string(&event).unwrap();
-        match OpenOptions::new()
+    fn send_event_to_kernel(&self, event: AppEvent) -> AureliaResult<()> {
+        let json = event_schema::encode(&event)?;
+        let mut file = OpenOptions::new()
             .append(true)
             .create(true)
-            .open(OUTPUT_FILE)
-        {
-            Ok(mut file) => {
-                if let Err(e) = writeln!(file, "{}", json) {
-                    error!("Failed to write to output file: {}", e);
-                }
-            }
-            Err(e) => error!("Failed to open output file: {}", e),
-        }
+            .open(output_path())
+            .map_err(|e| AureliaError::Ipc(format!("Failed to open output file: {}", e)))?;
+        writeln!(file, "{}", json)
+            .map_err(|e| AureliaError::Ipc(format!("Failed to write to output file: {}", e)))
     }
 }
 
-/// Process event from kernel
+/// Process event from kernel. Market data, candles and sentiment updates feed
+/// the strategies trading their symbol; the next analysis cycle decides on them.
 ///
 /// # Safety
 ///
@@ -75,14 +240,23 @@ impl StrategyEngine {
 /// - The string is valid UTF-8
 #[no_mangle]
 pub unsafe extern "C" fn process_event_from_kernel(event_json: *const std::os::raw::c_char) {
-    let c_str = std::ffi::CStr::from_ptr(event_json);
+    let c_str = CStr::from_ptr(event_json);
     if let Ok(json_str) = c_str.to_str() {
-        if let Ok(event) = serde_json::from_str::<AppEvent>(json_str) {
-            // In a real implementation, you'd send this to the engine's main task via an internal channel.
-            info!(
-                "[Strategy Engine DLL] Received event from kernel: {:?}",
-                event
-            );
+        match event_schema::decode(json_str) {
+            Ok(event) => {
+                match event.correlation_id() {
+                    Some(id) => debug!(
+                        correlation_id = %id,
+                        "[Strategy Engine DLL] Received event from kernel: {:?}", event
+                    ),
+                    None => debug!(
+                        "[Strategy Engine DLL] Received event from kernel: {:?}",
+                        event
+                    ),
+                }
+                with_strategies(|book| book.observe(&event, indicator_params));
+            }
+            Err(e) => warn!("[Strategy Engine DLL] Dropped event from kernel: {}", e),
         }
     }
 }
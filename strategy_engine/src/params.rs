@@ -0,0 +1,296 @@
+//! Named strategy parameters that can be changed while the engine runs.
+//!
+//! Values are validated against their spec and persisted to [`PARAMS_PATH`], so a
+//! tuned value survives restarts and module reloads. A value stored under
+//! `<strategy id>.<name>` overrides `<name>` for that strategy only, which lets
+//! each strategy be tuned on its own.
+
+use common::AureliaResult;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::{OnceLock, RwLock};
+use tracing::warn;
+
+pub const PARAMS_PATH: &str = "config/strategy_params.json";
+
+/// A tunable parameter and the range it may take.
+#[derive(Debug, Clone, Copy)]
+pub struct ParamSpec {
+    pub name: &'static str,
+    pub default: f64,
+    pub min: f64,
+    pub max: f64,
+    pub description: &'static str,
+}
+
+pub const INTERVAL_SECONDS: &str = "interval_seconds";
+pub const FAST_EMA_PERIOD: &str = "fast_ema_period";
+pub const SLOW_EMA_PERIOD: &str = "slow_ema_period";
+pub const MIN_SENTIMENT: &str = "min_sentiment";
+pub const REVERSION_WINDOW: &str = "reversion_window";
+pub const REVERSION_THRESHOLD: &str = "reversion_threshold";
+pub const SENTIMENT_ENTRY: &str = "sentiment_entry";
+pub const SENTIMENT_EXIT: &str = "sentiment_exit";
+
+pub const PARAM_SPECS: &[ParamSpec] = &[
+    ParamSpec {
+        name: INTERVAL_SECONDS,
+        default: 10.0,
+        min: 1.0,
+        max: 3600.0,
+        description: "Seconds between market analysis cycles",
+    },
+    ParamSpec {
+        name: FAST_EMA_PERIOD,
+        default: 12.0,
+        min: 2.0,
+        max: 200.0,
+        description: "Candles averaged by the fast moving average",
+    },
+    ParamSpec {
+        name: SLOW_EMA_PERIOD,
+        default: 26.0,
+        min: 3.0,
+        max: 500.0,
+        description: "Candles averaged by the slow moving average; also the warm-up length",
+    },
+    ParamSpec {
+        name: MIN_SENTIMENT,
+        default: -0.3,
+        min: -1.0,
+        max: 1.0,
+        description: "Lowest news sentiment at which a buy signal is acted on",
+    },
+    ParamSpec {
+        name: REVERSION_WINDOW,
+        default: 20.0,
+        min: 3.0,
+        max: 500.0,
+        description: "Candles in the rolling mean mean reversion trades around",
+    },
+    ParamSpec {
+        name: REVERSION_THRESHOLD,
+        default: 2.0,
+        min: 0.5,
+        max: 5.0,
+        description: "Standard deviations from the rolling mean that trigger a reversion trade",
+    },
+    ParamSpec {
+        name: SENTIMENT_ENTRY,
+        default: 0.5,
+        min: -1.0,
+        max: 1.0,
+        description: "News sentiment at or above which the sentiment strategy buys",
+    },
+    ParamSpec {
+        name: SENTIMENT_EXIT,
+        default: -0.2,
+        min: -1.0,
+        max: 1.0,
+        description: "News sentiment at or below which the sentiment strategy sells",
+    },
+];
+
+/// The spec of a parameter, also for names scoped to a strategy.
+pub fn spec(name: &str) -> Option<&'static ParamSpec> {
+    let base = name.rsplit_once('.').map_or(name, |(_, base)| base);
+    PARAM_SPECS.iter().find(|spec| spec.name == base)
+}
+
+/// The name under which `strategy` stores its own value of `name`.
+pub fn scoped(strategy: &str, name: &str) -> String {
+    format!("{}.{}", strategy, name)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamError {
+    Unknown(String),
+    OutOfRange {
+        name: String,
+        value: f64,
+        min: f64,
+        max: f64,
+    },
+}
+
+impl ParamError {
+    /// The status `strategy_set_param` returns for this error.
+    pub fn code(&self) -> i32 {
+        match self {
+            ParamError::Unknown(_) => PARAM_UNKNOWN,
+            ParamError::OutOfRange { .. } => PARAM_OUT_OF_RANGE,
+        }
+    }
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamError::Unknown(name) => write!(f, "unknown strategy parameter '{}'", name),
+            ParamError::OutOfRange {
+                name,
+                value,
+                min,
+                max,
+            } => write!(
+                f,
+                "strategy parameter '{}' = {} is outside {}..={}",
+                name, value, min, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParamError {}
+
+/// Status codes returned across the FFI boundary by `strategy_set_param`.
+pub const PARAM_OK: i32 = 0;
+pub const PARAM_UNKNOWN: i32 = 1;
+pub const PARAM_OUT_OF_RANGE: i32 = 2;
+pub const PARAM_INVALID_NAME: i32 = 3;
+
+/// Current parameter values. Parameters without a stored value use their default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct StrategyParams {
+    values: BTreeMap<String, f64>,
+}
+
+impl StrategyParams {
+    /// Load stored values, dropping any that no longer match a spec.
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let stored: Self = match std::fs::read_to_string(path) {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(params) => params,
+                Err(e) => {
+                    warn!("Ignoring invalid strategy parameters in {:?}: {}", path, e);
+                    return Self::default();
+                }
+            },
+            Err(_) => return Self::default(),
+        };
+
+        let mut params = Self::default();
+        for (name, value) in stored.values {
+            if let Err(e) = params.set(&name, value) {
+                warn!("Ignoring stored strategy parameter: {}", e);
+            }
+        }
+        params
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> AureliaResult<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// The value of `name`; a scoped name falls back to the value of its base name.
+    pub fn get(&self, name: &str) -> Option<f64> {
+        let spec = spec(name)?;
+        let value = self
+            .values
+            .get(name)
+            .or_else(|| self.values.get(spec.name))
+            .copied();
+        Some(value.unwrap_or(spec.default))
+    }
+
+    pub fn set(&mut self, name: &str, value: f64) -> Result<(), ParamError> {
+        let spec = spec(name).ok_or_else(|| ParamError::Unknown(name.to_string()))?;
+        // NaN fails both comparisons, so check containment rather than the bounds
+        if !(spec.min..=spec.max).contains(&value) {
+            return Err(ParamError::OutOfRange {
+                name: name.to_string(),
+                value,
+                min: spec.min,
+                max: spec.max,
+            });
+        }
+        self.values.insert(name.to_string(), value);
+        Ok(())
+    }
+}
+
+static PARAMS: OnceLock<RwLock<StrategyParams>> = OnceLock::new();
+
+fn params() -> &'static RwLock<StrategyParams> {
+    PARAMS.get_or_init(|| RwLock::new(StrategyParams::load(PARAMS_PATH)))
+}
+
+/// The current value of a parameter from [`PARAM_SPECS`].
+pub fn current(name: &str) -> f64 {
+    params()
+        .read()
+        .expect("strategy params lock poisoned")
+        .get(name)
+        .unwrap_or_else(|| panic!("'{}' is not a strategy parameter", name))
+}
+
+/// The current value of a parameter from [`PARAM_SPECS`] as seen by `strategy`.
+pub fn current_for(strategy: &str, name: &str) -> f64 {
+    current(&scoped(strategy, name))
+}
+
+/// Validate and apply a new value, then persist all values.
+pub fn update(name: &str, value: f64) -> Result<(), ParamError> {
+    let mut params = params().write().expect("strategy params lock poisoned");
+    params.set(name, value)?;
+    // The new value is live either way; losing it on restart is not worth rejecting it
+    if let Err(e) = params.save(PARAMS_PATH) {
+        warn!("Failed to persist strategy parameters: {}", e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_params_are_validated_and_persisted() {
+        let dir = std::env::temp_dir().join(format!("strategy-params-{}", std::process::id()));
+        let path = dir.join("strategy_params.json");
+
+        let mut params = StrategyParams::default();
+        assert_eq!(params.get(INTERVAL_SECONDS), Some(10.0));
+        assert_eq!(
+            params.set("missing", 1.0),
+            Err(ParamError::Unknown("missing".to_string()))
+        );
+        assert_eq!(
+            params.set(INTERVAL_SECONDS, 0.0).map_err(|e| e.code()),
+            Err(PARAM_OUT_OF_RANGE)
+        );
+        assert!(params.set(INTERVAL_SECONDS, f64::NAN).is_err());
+
+        params.set(INTERVAL_SECONDS, 30.0).unwrap();
+        params.save(&path).unwrap();
+        assert_eq!(
+            StrategyParams::load(&path).get(INTERVAL_SECONDS),
+            Some(30.0)
+        );
+
+        // Strategies see the shared value unless they have their own
+        let scoped_interval = scoped("momentum", INTERVAL_SECONDS);
+        assert_eq!(params.get(&scoped_interval), Some(30.0));
+        params.set(&scoped_interval, 5.0).unwrap();
+        assert_eq!(params.get(&scoped_interval), Some(5.0));
+        assert_eq!(params.get(INTERVAL_SECONDS), Some(30.0));
+        assert!(params.set("momentum.missing", 1.0).is_err());
+
+        // Values that are no longer valid fall back to the default
+        std::fs::write(&path, r#"{"interval_seconds": 0, "gone": 1}"#).unwrap();
+        assert_eq!(StrategyParams::load(&path), StrategyParams::default());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
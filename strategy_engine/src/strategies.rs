@@ -0,0 +1,404 @@
+//! The strategies of the configured [`StrategySet`], run side by side.
+//!
+//! Every strategy only sees the events of its own symbols and keeps its own
+//! state, so one strategy's signal never suppresses another's. Decisions carry
+//! the ID of the strategy that made them in `EventMeta::strategy_id`.
+
+use crate::indicators::{confidence, IndicatorParams, MarketView};
+use common::{
+    AppEvent, DecisionRationale, EventMeta, MarketRegime, StrategyDecision, StrategyKind,
+    StrategySet, StrategySpec,
+};
+use std::collections::{BTreeMap, VecDeque};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    fn decision(self, symbol: &str, price: f64) -> StrategyDecision {
+        match self {
+            Side::Buy => StrategyDecision::Buy(symbol.to_string(), price),
+            Side::Sell => StrategyDecision::Sell(symbol.to_string(), price),
+        }
+    }
+}
+
+/// Latest traded price of a symbol and the metadata of the tick that set it.
+#[derive(Debug, Default)]
+struct Quote {
+    price: Option<f64>,
+    meta: EventMeta,
+}
+
+impl Quote {
+    fn observe(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::MarketData(data) => {
+                self.price = Some(data.price);
+                self.meta = data.meta.clone();
+            }
+            AppEvent::Candle(candle) => {
+                self.price.get_or_insert(candle.close);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ReversionState {
+    quote: Quote,
+    closes: VecDeque<f64>,
+    regime: MarketRegime,
+    last: Option<Side>,
+}
+
+/// Buys when the price is far below the rolling mean of recent closes and sells
+/// when it is far above it. No buys are made into a falling market.
+#[derive(Debug, Default)]
+struct MeanReversion {
+    symbols: BTreeMap<String, ReversionState>,
+}
+
+impl MeanReversion {
+    fn observe(&mut self, symbol: &str, event: &AppEvent, params: &IndicatorParams) {
+        let state = self.symbols.entry(symbol.to_string()).or_default();
+        state.quote.observe(event);
+        match event {
+            AppEvent::Candle(candle) => {
+                state.closes.push_back(candle.close);
+                while state.closes.len() > params.reversion_window as usize {
+                    state.closes.pop_front();
+                }
+            }
+            AppEvent::MarketConditions(conditions) => state.regime = conditions.regime,
+            _ => {}
+        }
+    }
+
+    fn decide(&mut self, params: &IndicatorParams) -> Vec<(StrategyDecision, EventMeta)> {
+        let mut decisions = Vec::new();
+        for (symbol, state) in &mut self.symbols {
+            let window = state.closes.len();
+            if window < params.reversion_window as usize {
+                continue;
+            }
+            let Some(price) = state.quote.price else {
+                continue;
+            };
+            let mean = state.closes.iter().sum::<f64>() / window as f64;
+            let variance = state
+                .closes
+                .iter()
+                .map(|close| (close - mean).powi(2))
+                .sum::<f64>()
+                / window as f64;
+            if variance == 0.0 {
+                continue;
+            }
+            let std_dev = variance.sqrt();
+            let deviation = (price - mean) / std_dev;
+            let side = if deviation <= -params.reversion_threshold {
+                // In a downtrend a low price tends to get lower, not revert
+                if state.regime == MarketRegime::TrendingDown {
+                    continue;
+                }
+                Side::Buy
+            } else if deviation >= params.reversion_threshold {
+                Side::Sell
+            } else {
+                continue;
+            };
+            if state.last == Some(side) {
+                continue;
+            }
+            state.last = Some(side);
+            let rationale = DecisionRationale {
+                indicators: BTreeMap::from([
+                    ("mean".to_string(), mean),
+                    ("std_dev".to_string(), std_dev),
+                    ("deviation".to_string(), deviation),
+                    ("price".to_string(), price),
+                ]),
+                sentiment: None,
+                regime: state.regime,
+                confidence: confidence(deviation, params.reversion_threshold),
+                reason: format!(
+                    "price {:.2} standard deviations {} the {}-candle mean",
+                    deviation.abs(),
+                    if side == Side::Buy { "below" } else { "above" },
+                    window
+                ),
+            };
+            decisions.push((
+                side.decision(symbol, price),
+                state.quote.meta.clone().with_rationale(rationale),
+            ));
+        }
+        decisions
+    }
+}
+
+#[derive(Debug, Default)]
+struct FollowerState {
+    quote: Quote,
+    sentiment: Option<f64>,
+    last: Option<Side>,
+}
+
+/// Buys on good news and sells on bad news.
+#[derive(Debug, Default)]
+struct SentimentFollower {
+    symbols: BTreeMap<String, FollowerState>,
+}
+
+impl SentimentFollower {
+    fn observe(&mut self, symbol: &str, event: &AppEvent) {
+        let state = self.symbols.entry(symbol.to_string()).or_default();
+        state.quote.observe(event);
+        if let AppEvent::SentimentUpdate(update) = event {
+            state.sentiment = Some(update.score);
+        }
+    }
+
+    fn decide(&mut self, params: &IndicatorParams) -> Vec<(StrategyDecision, EventMeta)> {
+        let mut decisions = Vec::new();
+        for (symbol, state) in &mut self.symbols {
+            let (Some(sentiment), Some(price)) = (state.sentiment, state.quote.price) else {
+                continue;
+            };
+            let (side, threshold) = if sentiment >= params.sentiment_entry {
+                (Side::Buy, params.sentiment_entry)
+            } else if sentiment <= params.sentiment_exit {
+                (Side::Sell, params.sentiment_exit)
+            } else {
+                continue;
+            };
+            if state.last == Some(side) {
+                continue;
+            }
+            state.last = Some(side);
+            let rationale = DecisionRationale {
+                indicators: BTreeMap::from([("price".to_string(), price)]),
+                sentiment: Some(sentiment),
+                regime: MarketRegime::Unknown,
+                confidence: confidence(sentiment, threshold),
+                reason: format!(
+                    "sentiment {:.2} {} the {} threshold {:.2}",
+                    sentiment,
+                    if side == Side::Buy {
+                        "reached"
+                    } else {
+                        "fell to"
+                    },
+                    if side == Side::Buy { "entry" } else { "exit" },
+                    threshold
+                ),
+            };
+            decisions.push((
+                side.decision(symbol, price),
+                state.quote.meta.clone().with_rationale(rationale),
+            ));
+        }
+        decisions
+    }
+}
+
+#[derive(Debug)]
+enum Model {
+    Momentum(MarketView),
+    MeanReversion(MeanReversion),
+    Sentiment(SentimentFollower),
+}
+
+#[derive(Debug)]
+struct Running {
+    spec: StrategySpec,
+    model: Model,
+}
+
+fn symbol_of(event: &AppEvent) -> Option<&str> {
+    match event {
+        AppEvent::MarketData(data) => Some(data.symbol.as_str()),
+        AppEvent::Candle(candle) => Some(candle.symbol.as_str()),
+        AppEvent::SentimentUpdate(update) => Some(update.symbol.as_str()),
+        AppEvent::MarketConditions(conditions) => conditions.symbol.as_deref(),
+        _ => None,
+    }
+}
+
+/// The enabled strategies of a [`StrategySet`]. Parameters are looked up per
+/// strategy ID, so each strategy can be tuned on its own.
+#[derive(Debug)]
+pub struct StrategyBook {
+    strategies: Vec<Running>,
+}
+
+impl StrategyBook {
+    pub fn new(set: &StrategySet) -> Self {
+        let strategies = set
+            .enabled()
+            .map(|spec| Running {
+                spec: spec.clone(),
+                model: match spec.kind {
+                    StrategyKind::Momentum => Model::Momentum(MarketView::new()),
+                    StrategyKind::MeanReversion => Model::MeanReversion(MeanReversion::default()),
+                    StrategyKind::Sentiment => Model::Sentiment(SentimentFollower::default()),
+                },
+            })
+            .collect();
+        Self { strategies }
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.strategies
+            .iter()
+            .map(|running| running.spec.id.as_str())
+    }
+
+    /// Feed an event to the strategies trading its symbol.
+    pub fn observe(&mut self, event: &AppEvent, params: impl Fn(&str) -> IndicatorParams) {
+        let Some(symbol) = symbol_of(event) else {
+            return;
+        };
+        for running in &mut self.strategies {
+            if !running.spec.trades(symbol) {
+                continue;
+            }
+            let params = params(&running.spec.id);
+            match &mut running.model {
+                Model::Momentum(view) => view.observe(event, &params),
+                Model::MeanReversion(model) => model.observe(symbol, event, &params),
+                Model::Sentiment(model) => model.observe(symbol, event),
+            }
+        }
+    }
+
+    /// Decisions of every strategy, tagged with the ID of the strategy that made them.
+    pub fn decide(
+        &mut self,
+        params: impl Fn(&str) -> IndicatorParams,
+    ) -> Vec<(StrategyDecision, EventMeta)> {
+        let mut decisions = Vec::new();
+        for running in &mut self.strategies {
+            let params = params(&running.spec.id);
+            let made = match &mut running.model {
+                Model::Momentum(view) => view.decide(&params),
+                Model::MeanReversion(model) => model.decide(&params),
+                Model::Sentiment(model) => model.decide(&params),
+            };
+            decisions.extend(made.into_iter().map(|(decision, mut meta)| {
+                meta.strategy_id = Some(running.spec.id.clone());
+                (decision, meta)
+            }));
+        }
+        decisions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{Candle, MarketData, SentimentUpdate};
+
+    fn params(_strategy: &str) -> IndicatorParams {
+        IndicatorParams {
+            fast_period: 2.0,
+            slow_period: 4.0,
+            min_sentiment: -0.5,
+            reversion_window: 4.0,
+            reversion_threshold: 1.5,
+            sentiment_entry: 0.5,
+            sentiment_exit: -0.2,
+        }
+    }
+
+    fn candle(symbol: &str, close: f64) -> AppEvent {
+        AppEvent::Candle(Candle {
+            symbol: symbol.to_string(),
+            open_time: 0,
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 1.0,
+            trades: 1,
+        })
+    }
+
+    fn tick(symbol: &str, price: f64) -> AppEvent {
+        AppEvent::MarketData(MarketData {
+            symbol: symbol.to_string(),
+            price,
+            quantity: 0.1,
+            timestamp: 0,
+            meta: Default::default(),
+        })
+    }
+
+    fn sentiment(symbol: &str, score: f64) -> AppEvent {
+        AppEvent::SentimentUpdate(SentimentUpdate {
+            symbol: symbol.to_string(),
+            score,
+            samples: 1,
+            timestamp: 0,
+        })
+    }
+
+    fn tagged(decisions: &[(StrategyDecision, EventMeta)]) -> Vec<(&str, &StrategyDecision)> {
+        decisions
+            .iter()
+            .map(|(decision, meta)| (meta.strategy_id.as_deref().unwrap(), decision))
+            .collect()
+    }
+
+    #[test]
+    fn test_strategies_decide_independently() {
+        let set: StrategySet = serde_json::from_str(
+            r#"{"strategies": [
+                {"id": "fade_btc", "kind": "mean_reversion", "allocation": 0.5, "symbols": ["BTCUSDT"]},
+                {"id": "news", "kind": "sentiment", "allocation": 0.5}
+            ]}"#,
+        )
+        .unwrap();
+        let mut book = StrategyBook::new(&set);
+        assert_eq!(book.ids().collect::<Vec<_>>(), ["fade_btc", "news"]);
+
+        for close in [100.0, 102.0, 98.0, 100.0] {
+            book.observe(&candle("BTCUSDT", close), params);
+            book.observe(&candle("ETHUSDT", close), params);
+        }
+        assert!(book.decide(params).is_empty());
+
+        // A sharp drop is a reversion buy, but only where the strategy trades
+        book.observe(&tick("BTCUSDT", 95.0), params);
+        book.observe(&tick("ETHUSDT", 95.0), params);
+        book.observe(&sentiment("ETHUSDT", 0.7), params);
+        let decisions = book.decide(params);
+        assert!(matches!(
+            tagged(&decisions).as_slice(),
+            [
+                ("fade_btc", StrategyDecision::Buy(btc, _)),
+                ("news", StrategyDecision::Buy(eth, _)),
+            ] if btc == "BTCUSDT" && eth == "ETHUSDT"
+        ));
+        // Each decision explains itself in the terms of its own strategy
+        let reversion = decisions[0].1.rationale.as_ref().unwrap();
+        assert_eq!(reversion.indicators["mean"], 100.0);
+        assert!(reversion.indicators["deviation"] < -3.5);
+        assert_eq!(reversion.confidence, 1.0);
+        let news = decisions[1].1.rationale.as_ref().unwrap();
+        assert_eq!(news.sentiment, Some(0.7));
+        assert!((news.confidence - 0.4).abs() < 1e-9);
+        assert!(book.decide(params).is_empty(), "signals are not repeated");
+
+        book.observe(&sentiment("ETHUSDT", -0.4), params);
+        assert!(matches!(
+            tagged(&book.decide(params)).as_slice(),
+            [("news", StrategyDecision::Sell(_, _))]
+        ));
+    }
+}
@@ -0,0 +1,264 @@
+//! Dead man's switch for the agent as a whole.
+//!
+//! While the engines keep publishing and the kernel keeps heartbeating, the
+//! switch pings an external check URL (healthchecks.io style), so an outside
+//! service notices when the pings stop. When the agent goes quiet for longer
+//! than `silence_timeout_seconds`, or the event bus closes, it escalates once:
+//! it reports the failure to the check URL, alerts the operator, requests an
+//! emergency replication so the fleet survives, saves the agent state and exits
+//! for the service manager to restart the process.
+
+use common::{AgentIdentity, AureliaResult, EventReceiver, HealthState, StateStore};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{self, Duration, Instant};
+use tracing::{debug, error, info, warn};
+
+pub const DEAD_MANS_SWITCH_CONFIG_PATH: &str = "config/dead_mans_switch.json";
+
+/// Exit status after escalating, so the service manager restarts the agent
+pub const SILENCE_EXIT_CODE: i32 = 75;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Escalation must not hang on a replication that never finishes
+const EMERGENCY_REPLICATION_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeadMansSwitchConfig {
+    pub enabled: bool,
+    /// Pinged with GET while the agent is healthy, and at `<url>/fail` on escalation
+    pub ping_url: Option<String>,
+    pub ping_interval_seconds: u64,
+    /// How long the engines may stay quiet before the switch escalates
+    pub silence_timeout_seconds: u64,
+    /// Operator webhook that receives an [`OperatorAlert`] as JSON
+    pub alert_url: Option<String>,
+    /// Exit with [`SILENCE_EXIT_CODE`] after escalating
+    pub exit_on_silence: bool,
+}
+
+impl Default for DeadMansSwitchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ping_url: None,
+            ping_interval_seconds: 60,
+            silence_timeout_seconds: 300,
+            alert_url: None,
+            exit_on_silence: true,
+        }
+    }
+}
+
+impl DeadMansSwitchConfig {
+    pub fn load(path: impl AsRef<Path>) -> AureliaResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+/// Body posted to `alert_url` when the switch escalates.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperatorAlert {
+    pub agent_id: Option<String>,
+    pub reason: String,
+    /// Seconds since the last event from the engines
+    pub silent_seconds: u64,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+type EmergencyReplication =
+    Box<dyn Fn() -> Pin<Box<dyn Future<Output = Result<(), String>> + Send>> + Send + Sync>;
+
+pub struct DeadMansSwitch {
+    config: DeadMansSwitchConfig,
+    /// Events from the engines; any event counts as a sign of life
+    rx: EventReceiver,
+    client: reqwest::Client,
+    health: Option<HealthState>,
+    identity: Option<AgentIdentity>,
+    state: Option<StateStore>,
+    emergency_replication: Option<EmergencyReplication>,
+}
+
+impl DeadMansSwitch {
+    pub fn new(config: DeadMansSwitchConfig, rx: EventReceiver) -> Self {
+        Self {
+            config,
+            rx,
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            health: None,
+            identity: None,
+            state: None,
+            emergency_replication: None,
+        }
+    }
+
+    /// Also escalate when the kernel's main loop stops heartbeating
+    pub fn with_health(mut self, health: HealthState) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// Name the agent in operator alerts
+    pub fn with_identity(mut self, identity: AgentIdentity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Save the agent state before exiting
+    pub fn with_state_store(mut self, state: StateStore) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Run `replicate` on escalation so that a replica takes over
+    pub fn with_emergency_replication<F, Fut>(mut self, replicate: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        self.emergency_replication = Some(Box::new(move || Box::pin(replicate())));
+        self
+    }
+
+    /// Watch the agent until it goes quiet, then escalate. Only returns after
+    /// escalating if `exit_on_silence` is off.
+    pub async fn run(mut self) {
+        let timeout = Duration::from_secs(self.config.silence_timeout_seconds.max(1));
+        info!(
+            timeout_seconds = timeout.as_secs(),
+            "[Survival Protocol] Dead man's switch armed"
+        );
+        let mut check = time::interval(Duration::from_secs(
+            self.config.ping_interval_seconds.max(1),
+        ));
+        let mut last_event = Instant::now();
+        let reason = loop {
+            tokio::select! {
+                _ = check.tick() => {
+                    if last_event.elapsed() >= timeout {
+                        break format!("no engine events for {}s", last_event.elapsed().as_secs());
+                    }
+                    if self.health.as_ref().is_some_and(|health| !health.is_live(timeout)) {
+                        break "kernel main loop stopped heartbeating".to_string();
+                    }
+                    self.ping("").await;
+                }
+                event = self.rx.recv() => match event {
+                    Ok(_) | Err(RecvError::Lagged(_)) => last_event = Instant::now(),
+                    Err(RecvError::Closed) => break "event bus closed".to_string(),
+                },
+            }
+        };
+        self.escalate(&reason, last_event.elapsed()).await;
+        if self.config.exit_on_silence {
+            std::process::exit(SILENCE_EXIT_CODE);
+        }
+    }
+
+    async fn ping(&self, suffix: &str) {
+        let Some(url) = &self.config.ping_url else {
+            return;
+        };
+        let url = format!("{}{}", url.trim_end_matches('/'), suffix);
+        match self.client.get(&url).send().await {
+            Ok(response) if response.status().is_success() => {
+                debug!("[Survival Protocol] Pinged {}", url)
+            }
+            Ok(response) => warn!(
+                "[Survival Protocol] Ping to {} returned {}",
+                url,
+                response.status()
+            ),
+            Err(e) => warn!("[Survival Protocol] Ping to {} failed: {}", url, e),
+        }
+    }
+
+    async fn escalate(&self, reason: &str, silent_for: Duration) {
+        error!(
+            reason = reason,
+            "[Survival Protocol] Dead man's switch tripped, escalating"
+        );
+        self.ping("/fail").await;
+
+        if let Some(url) = &self.config.alert_url {
+            let alert = OperatorAlert {
+                agent_id: self.identity.as_ref().map(|i| i.agent_id.clone()),
+                reason: reason.to_string(),
+                silent_seconds: silent_for.as_secs(),
+                timestamp: chrono::Utc::now(),
+            };
+            match self.client.post(url).json(&alert).send().await {
+                Ok(response) if response.status().is_success() => {
+                    info!("[Survival Protocol] Operator alerted")
+                }
+                Ok(response) => error!(
+                    "[Survival Protocol] Operator alert returned {}",
+                    response.status()
+                ),
+                Err(e) => error!("[Survival Protocol] Operator alert failed: {}", e),
+            }
+        }
+
+        if let Some(replicate) = &self.emergency_replication {
+            match time::timeout(EMERGENCY_REPLICATION_TIMEOUT, replicate()).await {
+                Ok(Ok(())) => info!("[Survival Protocol] Emergency replication succeeded"),
+                Ok(Err(e)) => error!("[Survival Protocol] Emergency replication failed: {}", e),
+                Err(_) => error!("[Survival Protocol] Emergency replication timed out"),
+            }
+        }
+
+        if let Some(state) = &self.state {
+            if let Err(e) = state.save() {
+                error!("[Survival Protocol] Failed to save agent state: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{EventBus, Topic};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_closed_bus_trips_the_switch() {
+        let bus = EventBus::new(8);
+        let rx = bus.subscribe_as("dead_mans_switch", &[Topic::System]);
+        let replications = Arc::new(AtomicUsize::new(0));
+        let counter = replications.clone();
+        let switch = DeadMansSwitch::new(
+            DeadMansSwitchConfig {
+                exit_on_silence: false,
+                ..Default::default()
+            },
+            rx,
+        )
+        .with_emergency_replication(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async { Ok(()) }
+        });
+
+        let handle = tokio::spawn(switch.run());
+        drop(bus);
+        time::timeout(Duration::from_secs(5), handle)
+            .await
+            .expect("switch did not trip")
+            .unwrap();
+        assert_eq!(replications.load(Ordering::SeqCst), 1);
+    }
+}
@@ -1,57 +1,233 @@
-use common::{AppEvent, EventReceiver, EventSender, SystemState};
+pub mod dead_mans_switch;
+
+use chrono::{DateTime, Utc};
+use common::{
+    clock, AppEvent, EventReceiver, EventSender, FleetFunds, LagHandler, NetAssetValue,
+    SharedClock, StateStore, SystemState,
+};
+use std::collections::VecDeque;
+use std::time::Duration;
 use tracing::{error, info, warn};
 
-use tokio::time::{self, Duration};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::watch;
+
+pub use dead_mans_switch::{DeadMansSwitch, DeadMansSwitchConfig, DEAD_MANS_SWITCH_CONFIG_PATH};
 
 const SIMULATED_HOURLY_COST: f64 = 0.5; // e.g., $0.50 per hour
-const MINIMUM_RUNWAY_HOURS: f64 = 24.0; // Require at least 24 hours of runway
+pub const MINIMUM_RUNWAY_HOURS: f64 = 24.0; // Require at least 24 hours of runway
+/// Below this the agent flattens every position and enters the safe state.
+pub const CRITICAL_RUNWAY_HOURS: f64 = 2.0;
+const PERFORMANCE_WINDOW: Duration = Duration::from_secs(24 * 60 * 60); // Trailing window for recent PnL
+const RUNWAY_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Replica funds older than this no longer count towards the fleet's runway
+const FLEET_FUNDS_MAX_AGE: Duration = Duration::from_secs(5 * 60);
+
+/// Snapshot of the agent's finances, published on every runway check. Amounts are
+/// in the accounting currency, see [`common::valuation`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Budget {
+    pub funds: f64,
+    /// Current cost of running the agent.
+    pub hourly_cost: f64,
+    /// Change in funds over the trailing performance window.
+    pub recent_pnl: f64,
+    /// Live replicas that reported their funds, on the primary only.
+    pub replicas: usize,
+    /// Their summed funds.
+    pub replica_funds: f64,
+}
+
+impl Budget {
+    pub fn runway_hours(&self) -> f64 {
+        self.projected_runway_hours(0.0)
+    }
+
+    /// Runway if the hourly cost grew by `extra_hourly_cost`, e.g. for a new server.
+    pub fn projected_runway_hours(&self, extra_hourly_cost: f64) -> f64 {
+        self.funds / (self.hourly_cost + extra_hourly_cost)
+    }
+
+    /// Runway of the whole fleet, pooling every agent's funds to pay for every
+    /// agent at this agent's hourly cost.
+    pub fn fleet_runway_hours(&self) -> f64 {
+        (self.funds + self.replica_funds) / (self.hourly_cost * (self.replicas + 1) as f64)
+    }
+}
 
 pub struct SurvivalProtocol {
     tx: EventSender,
     rx: EventReceiver,
     current_funds: f64,
     current_state: SystemState,
+    funds_history: VecDeque<(DateTime<Utc>, f64)>,
+    budget_tx: watch::Sender<Budget>,
+    state: Option<StateStore>,
+    clock: SharedClock,
+    /// The holdings behind the current funds, when reported asset by asset
+    holdings: Option<NetAssetValue>,
+    /// Latest replica funds and when they arrived
+    fleet: Option<(DateTime<Utc>, FleetFunds)>,
+    /// Funds from a lost `FinancialUpdate` are stale until resent
+    lag: LagHandler,
 }
 
 impl SurvivalProtocol {
     pub fn new(tx: EventSender, rx: EventReceiver, initial_funds: f64) -> Self {
+        let (budget_tx, _) = watch::channel(Budget {
+            funds: initial_funds,
+            hourly_cost: SIMULATED_HOURLY_COST,
+            recent_pnl: 0.0,
+            ..Budget::default()
+        });
+        let clock = clock::system();
+        let lag = LagHandler::new("survival_protocol")
+            .with_event_bus(tx.clone())
+            .with_resync();
         Self {
             tx,
             rx,
             current_funds: initial_funds,
             current_state: SystemState::Normal,
+            funds_history: VecDeque::from([(clock.now(), initial_funds)]),
+            budget_tx,
+            state: None,
+            clock,
+            holdings: None,
+            fleet: None,
+            lag,
         }
     }
 
+    /// Measure runway checks and the performance window on `clock`
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        for (time, _) in &mut self.funds_history {
+            *time = clock.now();
+        }
+        self.clock = clock;
+        self
+    }
+
+    /// Keep the funds reported by `FinancialUpdate` in the persistent agent state
+    pub fn with_state_store(mut self, state: StateStore) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Subscribe to budget updates, e.g. for scaling decisions.
+    pub fn budget(&self) -> watch::Receiver<Budget> {
+        self.budget_tx.subscribe()
+    }
+
     pub async fn run(&mut self) {
         info!("[Survival Protocol] Starting...");
-        let mut health_check_interval = time::interval(Duration::from_secs(60));
+        let clock = self.clock.clone();
+        let mut next_check = clock.now();
 
         loop {
+            let wait = (next_check - clock.now()).to_std().unwrap_or_default();
             tokio::select! {
-                _ = health_check_interval.tick() => {
+                _ = clock.sleep(wait) => {
+                    next_check += RUNWAY_CHECK_INTERVAL;
                     self.check_runway().await;
                 }
-                Ok(event) = self.rx.recv() => {
-                    if let AppEvent::FinancialUpdate(funds) = event {
-                        self.current_funds = funds;
+                event = self.rx.recv() => match event {
+                    Ok(AppEvent::FinancialUpdate(funds)) => {
+                        self.record_funds(funds);
                         self.check_runway().await;
                     }
-                }
-                else => { break; } // Channel closed
+                    Ok(AppEvent::NetAssetValue(nav)) => self.record_holdings(*nav),
+                    Ok(AppEvent::FleetFunds(fleet)) => self.fleet = Some((clock.now(), fleet)),
+                    // A flatten requested elsewhere, e.g. on critical health
+                    Ok(AppEvent::EmergencyFlatten(_)) if self.current_state != SystemState::Safe => {
+                        self.change_system_state(SystemState::Safe).await;
+                    }
+                    Ok(AppEvent::ResumeTrading) if self.current_state == SystemState::Safe => {
+                        info!("[Survival Protocol] Trading resumed, leaving the safe state.");
+                        self.change_system_state(SystemState::Normal).await;
+                        self.check_runway().await;
+                    }
+                    Ok(_) => {}
+                    // The execution engine answers with the current funds
+                    Err(RecvError::Lagged(n)) => {
+                        self.lag.lagged(n);
+                    }
+                    Err(RecvError::Closed) => break,
+                },
             }
         }
     }
 
+    fn record_funds(&mut self, funds: f64) {
+        self.current_funds = funds;
+        if let Some(state) = &self.state {
+            state.set_funds(funds);
+        }
+        self.funds_history.push_back((self.clock.now(), funds));
+    }
+
+    fn record_holdings(&mut self, nav: NetAssetValue) {
+        if !nav.unpriced.is_empty() {
+            warn!(
+                unpriced = ?nav.unpriced,
+                "[Survival Protocol] Funds leave out assets without a price in {}",
+                nav.currency
+            );
+        }
+        if let Some(fiat) = &nav.fiat {
+            info!(
+                value = fiat.value,
+                pnl = fiat.pnl,
+                "[Survival Protocol] Net asset value in {}",
+                fiat.currency
+            );
+        }
+        self.holdings = Some(nav);
+    }
+
     async fn check_runway(&mut self) {
-        let runway_hours = self.current_funds / SIMULATED_HOURLY_COST;
+        let budget = self.current_budget();
+        let runway_hours = budget.runway_hours();
         info!(
             funds = self.current_funds,
+            currency = self.holdings.as_ref().map(|nav| nav.currency.as_str()),
             runway_hours = runway_hours,
+            recent_pnl = budget.recent_pnl,
             "[Survival Protocol] Runway check."
         );
+        if budget.replicas > 0 {
+            let fleet_runway_hours = budget.fleet_runway_hours();
+            info!(
+                replicas = budget.replicas,
+                replica_funds = budget.replica_funds,
+                fleet_runway_hours = fleet_runway_hours,
+                "[Survival Protocol] Fleet runway check."
+            );
+            if fleet_runway_hours < MINIMUM_RUNWAY_HOURS {
+                warn!("[Survival Protocol] Fleet runway is below threshold.");
+            }
+        }
+        self.budget_tx.send_replace(budget);
 
-        if runway_hours < MINIMUM_RUNWAY_HOURS && self.current_state == SystemState::Normal {
+        // Only an explicit ResumeTrading leaves the safe state
+        if self.current_state == SystemState::Safe {
+            return;
+        }
+        if runway_hours < CRITICAL_RUNWAY_HOURS {
+            error!("[Survival Protocol] Runway has collapsed! Flattening all positions.");
+            self.change_system_state(SystemState::Safe).await;
+            let reason = format!("runway collapsed to {:.1} hours", runway_hours);
+            if let Err(e) = self
+                .tx
+                .send_control(AppEvent::EmergencyFlatten(reason))
+                .await
+            {
+                error!(
+                    "[Survival Protocol] Failed to send EmergencyFlatten event: {}",
+                    e
+                );
+            }
+        } else if runway_hours < MINIMUM_RUNWAY_HOURS && self.current_state == SystemState::Normal {
             warn!("[Survival Protocol] Runway is below threshold! Entering CONSERVATION mode.");
             self.change_system_state(SystemState::Conservation).await;
         } else if runway_hours >= MINIMUM_RUNWAY_HOURS
@@ -62,9 +238,38 @@ impl SurvivalProtocol {
         }
     }
 
+    fn current_budget(&mut self) -> Budget {
+        // Keep the newest sample older than the window as the baseline
+        let now = self.clock.now();
+        while self.funds_history.len() > 1
+            && (now - self.funds_history[1].0).to_std().unwrap_or_default() >= PERFORMANCE_WINDOW
+        {
+            self.funds_history.pop_front();
+        }
+        let baseline = self
+            .funds_history
+            .front()
+            .map_or(self.current_funds, |(_, funds)| *funds);
+        let fleet = self.fleet.as_ref().filter(|(received, _)| {
+            (now - *received).to_std().unwrap_or_default() < FLEET_FUNDS_MAX_AGE
+        });
+
+        Budget {
+            funds: self.current_funds,
+            hourly_cost: SIMULATED_HOURLY_COST,
+            recent_pnl: self.current_funds - baseline,
+            replicas: fleet.map_or(0, |(_, fleet)| fleet.replicas),
+            replica_funds: fleet.map_or(0.0, |(_, fleet)| fleet.funds),
+        }
+    }
+
     async fn change_system_state(&mut self, new_state: SystemState) {
         self.current_state = new_state.clone();
-        if let Err(e) = self.tx.send(AppEvent::SystemStateChange(new_state)) {
+        if let Err(e) = self
+            .tx
+            .send_control(AppEvent::SystemStateChange(new_state))
+            .await
+        {
             error!(
                 "[Survival Protocol] Failed to send SystemStateChange event: {}",
                 e
@@ -72,3 +277,75 @@ impl SurvivalProtocol {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{Clock, EventBus, MockClock};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_recent_pnl_follows_the_clock() {
+        let bus = EventBus::new(8);
+        let clock = MockClock::default();
+        let mut sp = SurvivalProtocol::new(bus.clone(), bus.subscribe(), 100.0)
+            .with_clock(Arc::new(clock.clone()));
+
+        clock.advance(Duration::from_secs(3600));
+        sp.record_funds(120.0);
+        assert_eq!(sp.current_budget().recent_pnl, 20.0);
+
+        // A day later the first sample has left the window
+        clock.advance(PERFORMANCE_WINDOW);
+        sp.record_funds(130.0);
+        assert_eq!(sp.current_budget().recent_pnl, 10.0);
+        assert_eq!(sp.funds_history.len(), 2);
+    }
+
+    #[test]
+    fn test_fleet_runway_pools_replica_funds_until_they_go_stale() {
+        let bus = EventBus::new(8);
+        let clock = MockClock::default();
+        let mut sp = SurvivalProtocol::new(bus.clone(), bus.subscribe(), 12.0)
+            .with_clock(Arc::new(clock.clone()));
+        assert_eq!(sp.current_budget().fleet_runway_hours(), 24.0);
+
+        sp.fleet = Some((
+            clock.now(),
+            FleetFunds {
+                replicas: 2,
+                funds: 60.0,
+                timestamp: clock.now(),
+            },
+        ));
+        let budget = sp.current_budget();
+        assert_eq!(budget.runway_hours(), 24.0);
+        // 72 across three agents at 0.5 each
+        assert_eq!(budget.fleet_runway_hours(), 48.0);
+
+        clock.advance(FLEET_FUNDS_MAX_AGE);
+        assert_eq!(sp.current_budget().replicas, 0);
+    }
+
+    #[tokio::test]
+    async fn test_collapsed_runway_flattens_once() {
+        let bus = EventBus::new(8);
+        let mut events = bus.subscribe();
+        let mut sp = SurvivalProtocol::new(bus.clone(), bus.subscribe(), 0.5);
+
+        sp.check_runway().await;
+        assert!(matches!(
+            events.try_recv(),
+            Ok(AppEvent::SystemStateChange(SystemState::Safe))
+        ));
+        assert!(matches!(
+            events.try_recv(),
+            Ok(AppEvent::EmergencyFlatten(_))
+        ));
+
+        // Still broke, but already safe
+        sp.check_runway().await;
+        assert!(events.try_recv().is_err());
+        assert_eq!(sp.current_state, SystemState::Safe);
+    }
+}
@@ -1,4 +1,4 @@
-use autonomy_core::{DeploymentCommander, SshDeployer, AuthMethod};
+use autonomy_core::{DeploymentCommander, FleetCommand, SshDeployer, AuthMethod};
 use std::path::PathBuf;
 use tracing::{info, error, warn};
 
@@ -184,13 +184,13 @@ async fn main() -> anyhow::Result<()> {
 
     for (cmd, desc) in test_commands {
         println!("\n📌 {}:", desc);
-        match commander.execute_on_all(cmd).await {
+        match commander.execute_on_all(&FleetCommand::new(cmd)).await {
             Ok(results) => {
-                for (server_id, result) in results {
-                    if server_id == target_server {
-                        match result {
-                            Ok(output) => println!("{}", output.trim()),
-                            Err(e) => println!("错误: {}", e),
+                for result in results {
+                    if result.server_id == target_server {
+                        match &result.error {
+                            None => println!("{}", result.stdout.trim()),
+                            Some(e) => println!("错误: {}", e),
                         }
                     }
                 }
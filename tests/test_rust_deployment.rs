@@ -1,4 +1,4 @@
-use autonomy_core::{DeploymentCommander, SshDeployer, AuthMethod};
+use autonomy_core::{DeploymentCommander, FleetCommand, SshDeployer, AuthMethod};
 use std::path::PathBuf;
 
 #[tokio::main]
@@ -90,12 +90,12 @@ async fn main() -> anyhow::Result<()> {
     
     // Execute command on all servers
     println!("\nExecuting command on all servers...");
-    match commander.execute_on_all("uname -a").await {
+    match commander.execute_on_all(&FleetCommand::new("uname -a")).await {
         Ok(results) => {
-            for (server_id, result) in results {
-                match result {
-                    Ok(output) => println!("  {} Output: {}", server_id, output.trim()),
-                    Err(e) => println!("  {} Error: {}", server_id, e),
+            for result in results {
+                match &result.error {
+                    None => println!("  {} Output: {}", result.server_id, result.stdout.trim()),
+                    Some(e) => println!("  {} Error: {}", result.server_id, e),
                 }
             }
         }